@@ -0,0 +1,713 @@
+//! Translates the MIR of every function that is reachable from `main` into C.
+
+use crate::types::int_name;
+use cell_common::{
+    error::Error,
+    profiler,
+    source::{Encoding, FileId, Locatable, Span},
+};
+use cell_mir::{
+    body::{
+        AggregateKind, BinOp, Body, Operand, Place, PlaceElem, Rvalue, StatementKind,
+        TerminatorKind, UnOp,
+    },
+    db::MirDatabase,
+    layout::{Layout, Target, Variants},
+    mono::{self, Instance},
+    opt::OptLevel,
+    pretty::item_name,
+};
+use cell_syntax::ast::ItemId;
+use cell_typeck::{
+    consteval::ConstValue,
+    lower::AdtDef,
+    prelude::{self, HostFn},
+    ty::{Ty, TyKind},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    sync::Arc,
+};
+
+const RUNTIME: &str = include_str!("runtime.h");
+
+/// Translates the `main` function of the root module, and every
+/// function that is called by it, into a single C source file,
+/// using the MIR that is optimized at `level`.
+pub fn generate(db: &dyn MirDatabase, level: OptLevel) -> Result<String, Locatable<Error>> {
+    let _profiler = profiler::trace("CodegenC", "Generate");
+
+    let main = mono::find_main(db)?;
+    let instances = mono::collect(db, &[Instance::new(main, vec![])], level)?;
+
+    let mut cx = Codegen {
+        db,
+        names: HashSet::new(),
+        types: HashMap::new(),
+        eq_fns: HashMap::new(),
+        print_fns: HashMap::new(),
+        instances: HashMap::new(),
+        statics: HashMap::new(),
+        typedefs: String::new(),
+        structs: String::new(),
+        globals: String::new(),
+        helpers: String::new(),
+        prototypes: String::new(),
+        functions: String::new(),
+        error: None,
+    };
+    for instance in &instances {
+        let symbol = instance.symbol(db);
+        cx.names.insert(symbol.clone());
+        cx.instances.insert(instance.clone(), symbol);
+    }
+    for instance in &instances {
+        let body = db.optimized_mir(instance.def, level).unwrap();
+        let name = cx.instance(instance);
+        FnCodegen::new(&mut cx, &body, &instance.args).generate(&name);
+    }
+    let entry = cx.instance(&instances[0]);
+    if let Some(err) = cx.error {
+        return Err(err);
+    }
+
+    let mut out = String::new();
+    out.push_str("/* Generated by the cell C backend. */\n\n");
+    out.push_str(RUNTIME);
+    for section in &[
+        &cx.typedefs,
+        &cx.structs,
+        &cx.globals,
+        &cx.helpers,
+        &cx.prototypes,
+        &cx.functions,
+    ] {
+        if !section.is_empty() {
+            out.push('\n');
+            out.push_str(section);
+        }
+    }
+    write!(
+        out,
+        "\nint main(void) {{\n    {}();\n    return 0;\n}}\n",
+        entry
+    )
+    .unwrap();
+    Ok(out)
+}
+
+/// Returns a C string literal.
+///
+/// Every byte that isn't printable ASCII is written as an octal escape,
+/// and `?` is escaped to avoid trigraphs.
+pub fn c_string(val: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in val.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Returns a C literal of an integer, that is stored as its `bits`.
+fn int_literal(bits: u128, width: u16, signed: bool) -> String {
+    if width == 128 {
+        let (high, low) = ((bits >> 64) as u64, bits as u64);
+        return format!(
+            "((cell_{})(((cell_u128)UINT64_C({}) << 64) | UINT64_C({})))",
+            int_name(width, signed),
+            high,
+            low
+        );
+    }
+
+    let shift = 128 - width as u32;
+    if signed {
+        let val = ((bits << shift) as i128) >> shift;
+        if val == -(1 << (width - 1)) {
+            format!("INT{}_MIN", width)
+        } else {
+            format!("((int{}_t){})", width, val)
+        }
+    } else {
+        format!("((uint{}_t){}u)", width, (bits << shift) >> shift)
+    }
+}
+
+fn float_literal(val: f64, width: u16) -> String {
+    let val = if val.is_nan() {
+        "NAN".to_string()
+    } else if val.is_infinite() {
+        format!("({}INFINITY)", if val < 0.0 { "-" } else { "" })
+    } else {
+        format!("{:?}", val)
+    };
+    if width == 32 {
+        format!("((float){})", val)
+    } else {
+        val
+    }
+}
+
+pub struct Codegen<'db> {
+    pub db: &'db dyn MirDatabase,
+    /// Every name that is defined at the top level of the output.
+    names: HashSet<String>,
+    pub types: HashMap<Ty, String>,
+    pub eq_fns: HashMap<Ty, String>,
+    pub print_fns: HashMap<Ty, String>,
+    /// The mangled name of every function instance.
+    instances: HashMap<Instance, String>,
+    statics: HashMap<ItemId, String>,
+    // The sections of the output, in the order they are written.
+    pub typedefs: String,
+    pub structs: String,
+    globals: String,
+    pub helpers: String,
+    prototypes: String,
+    functions: String,
+    /// The first error that happened while generating the code.
+    error: Option<Locatable<Error>>,
+}
+
+impl Codegen<'_> {
+    /// Returns the layout of a type on the machine the C compiler targets,
+    /// which is assumed to be the host.
+    pub fn layout(&self, ty: Ty) -> Arc<Layout> {
+        self.db.layout_of(ty, Target::HOST)
+    }
+
+    /// Returns an unused identifier, that starts with the prefix
+    /// and is derived from the name.
+    pub fn global_name(&mut self, prefix: &str, name: &str) -> String {
+        let mut ident = format!("{}_", prefix);
+        for c in name.chars() {
+            if c.is_ascii_alphanumeric() {
+                ident.push(c);
+            } else if !ident.ends_with('_') {
+                ident.push('_');
+            }
+        }
+        let ident = ident.trim_end_matches('_').to_string();
+
+        let mut unique = ident.clone();
+        let mut idx = 1;
+        while !self.names.insert(unique.clone()) {
+            unique = format!("{}_{}", ident, idx);
+            idx += 1;
+        }
+        unique
+    }
+
+    /// Returns the name of the function of an instance.
+    fn instance(&self, instance: &Instance) -> String {
+        self.instances[instance].clone()
+    }
+
+    /// Returns the name of the global variable that stores the `static`.
+    fn static_name(&mut self, item: ItemId) -> String {
+        if let Some(name) = self.statics.get(&item) {
+            return name.clone();
+        }
+
+        let db = self.db;
+        let name = self.global_name("static", &item_name(db, item));
+        self.statics.insert(item, name.clone());
+
+        let ty = db.global_ty(item);
+        let c_ty = self.c_type(ty);
+        let init = match db.const_value(item) {
+            Ok(val) => self.initializer(&val, ty),
+            Err(err) => {
+                self.error.get_or_insert(err);
+                "{0}".to_string()
+            }
+        };
+        writeln!(self.globals, "static {} {} = {};", c_ty, name, init).unwrap();
+        name
+    }
+
+    /// Returns a C expression of a constant value.
+    fn constant(&mut self, val: &ConstValue, ty: Ty) -> String {
+        match val {
+            ConstValue::Tuple(_) | ConstValue::NamedTuple(_) => {
+                format!("({}){}", self.c_type(ty), self.initializer(val, ty))
+            }
+            _ => self.initializer(val, ty),
+        }
+    }
+
+    /// Returns the initializer of a constant value, which can also be used for globals.
+    fn initializer(&mut self, val: &ConstValue, ty: Ty) -> String {
+        let db = self.db;
+        let vals = match val {
+            ConstValue::Int(int) => {
+                return int_literal(int.as_u128(), int.width(), int.is_signed())
+            }
+            ConstValue::Float { val, width } => return float_literal(val.into_inner(), *width),
+            ConstValue::Bool(val) => return val.to_string(),
+            ConstValue::Char(val) => return format!("((cell_char){:#x})", *val as u32),
+            ConstValue::String(val) => return c_string(val),
+            ConstValue::Unit => return "((cell_unit)0)".to_string(),
+            ConstValue::Tuple(vals) => {
+                let tys = match ty.kind(db) {
+                    TyKind::Tuple(tys) => tys,
+                    _ => panic!("tuple constant with a type that is not a tuple"),
+                };
+                vals.iter()
+                    .zip(tys)
+                    .map(|(val, ty)| self.initializer(val, ty))
+                    .collect::<Vec<_>>()
+            }
+            ConstValue::NamedTuple(vals) => {
+                let fields = match ty.kind(db) {
+                    TyKind::NamedTuple(fields) => {
+                        fields.into_iter().map(|(name, _)| name).collect()
+                    }
+                    TyKind::Adt(item, _) => match &*db.adt_def(item) {
+                        AdtDef::Struct { fields } => fields.iter().map(|(name, _)| *name).collect(),
+                        AdtDef::Union { .. } => vec![],
+                    },
+                    _ => vec![],
+                };
+                let names = self.field_names(ty);
+                let mut inits = vec![];
+                for (field, val) in vals {
+                    if let Some(idx) = fields.iter().position(|name| name == field) {
+                        let field_ty = cell_mir::body::field_ty(db, ty, idx as u32);
+                        let init = self.initializer(val, field_ty);
+                        inits.push(format!(".{} = {}", names[idx], init));
+                    }
+                }
+                inits
+            }
+        };
+        if vals.is_empty() {
+            "{0}".to_string()
+        } else {
+            format!("{{{}}}", vals.join(", "))
+        }
+    }
+}
+
+struct FnCodegen<'a, 'db> {
+    cx: &'a mut Codegen<'db>,
+    body: &'a Body,
+    /// The generic arguments of this instance.
+    args: &'a [Ty],
+    /// The type of every local, with the generic arguments substituted.
+    tys: Vec<Ty>,
+    /// The file the function is defined in.
+    file: FileId,
+    /// The code of the block that is currently generated.
+    out: String,
+    /// The blocks that are the target of a `goto`.
+    labels: HashSet<u32>,
+}
+
+impl<'a, 'db> FnCodegen<'a, 'db> {
+    fn new(cx: &'a mut Codegen<'db>, body: &'a Body, args: &'a [Ty]) -> Self {
+        let db = cx.db;
+        let tys = body
+            .locals
+            .iter()
+            .map(|local| local.ty.subst(db, args))
+            .collect();
+        Self {
+            cx,
+            body,
+            args,
+            tys,
+            file: db.lookup_intern_item(body.item).file,
+            out: String::new(),
+            labels: HashSet::new(),
+        }
+    }
+
+    fn generate(mut self, name: &str) {
+        let args = self.body.args().collect::<Vec<_>>();
+        let mut params = vec![];
+        for arg in &args {
+            let ty = self.cx.c_type(self.tys[arg.index()]);
+            params.push(format!("{} _{}", ty, arg.0));
+        }
+        if params.is_empty() {
+            params.push("void".to_string());
+        }
+        let ret = self.cx.c_type(self.tys[0]);
+        let signature = format!("static {} {}({})", ret, name, params.join(", "));
+        writeln!(self.cx.prototypes, "{};", signature).unwrap();
+
+        let mut out = format!("{} {{\n", signature);
+        for idx in 0..self.tys.len() {
+            if idx == 0 || idx > args.len() {
+                let ty = self.cx.c_type(self.tys[idx]);
+                if ty == "cell_unit" {
+                    writeln!(out, "    {} _{} = 0;", ty, idx).unwrap();
+                } else {
+                    writeln!(out, "    {} _{};", ty, idx).unwrap();
+                }
+            }
+        }
+
+        let mut blocks = vec![];
+        for (block, data) in self.body.basic_blocks() {
+            for statement in &data.statements {
+                match &statement.kind {
+                    StatementKind::Assign(place, rvalue) => {
+                        let (place, _) = self.place(place);
+                        let rvalue = self.rvalue(rvalue, statement.span);
+                        writeln!(self.out, "    {} = {};", place, rvalue).unwrap();
+                    }
+                    StatementKind::Phi(..) => unreachable!("phi nodes are removed before codegen"),
+                    StatementKind::Nop => {}
+                }
+            }
+
+            let terminator = data.terminator();
+            self.terminator(&terminator.kind, terminator.span, block.0 + 1);
+            blocks.push(std::mem::take(&mut self.out));
+        }
+
+        // only blocks that are jumped to need a label
+        for (idx, block) in blocks.iter().enumerate() {
+            if self.labels.contains(&(idx as u32)) {
+                writeln!(out, "bb{}:", idx).unwrap();
+            }
+            out.push_str(block);
+        }
+        out.push_str("}\n\n");
+        self.cx.functions.push_str(&out);
+    }
+
+    fn subst(&self, ty: Ty) -> Ty {
+        ty.subst(self.cx.db, self.args)
+    }
+
+    fn operand_ty(&self, op: &Operand) -> Ty {
+        self.subst(op.ty(self.cx.db, self.body))
+    }
+
+    /// Returns a string literal with the location of the span,
+    /// which is used to report errors at runtime.
+    fn location(&self, span: Span) -> String {
+        let db = self.cx.db;
+        let pos = db.line_col(self.file, span.start(), Encoding::Char);
+        c_string(&format!(
+            "{}:{}:{}",
+            db.name(self.file),
+            pos.line + 1,
+            pos.col + 1
+        ))
+    }
+
+    /// Returns the C expression of the place, and its type.
+    fn place(&mut self, place: &Place) -> (String, Ty) {
+        let db = self.cx.db;
+        let mut expr = format!("_{}", place.local.0);
+        let mut ty = self.tys[place.local.index()];
+        for elem in &place.projection {
+            match *elem {
+                PlaceElem::Deref => {
+                    expr = format!("(*{})", expr);
+                    ty = pointee(db, ty);
+                }
+                PlaceElem::Field(idx) => {
+                    let field = &self.cx.field_names(ty)[idx as usize];
+                    expr = format!("{}.{}", expr, field);
+                    ty = cell_mir::body::field_ty(db, ty, idx);
+                }
+                PlaceElem::Downcast(idx) => {
+                    let variant = &self.cx.variant_names(ty)[idx as usize];
+                    expr = format!("{}.data.{}", expr, variant);
+                    ty = cell_mir::body::variant_ty(db, ty, idx);
+                }
+                PlaceElem::Index(local) => {
+                    expr = format!("{}[_{}]", expr, local.0);
+                    ty = pointee(db, ty);
+                }
+            }
+        }
+        (expr, ty)
+    }
+
+    fn operand(&mut self, op: &Operand) -> String {
+        match op {
+            Operand::Copy(place) => self.place(place).0,
+            Operand::Constant(constant) => {
+                let ty = self.subst(constant.ty);
+                self.cx.constant(&constant.val, ty)
+            }
+        }
+    }
+
+    fn rvalue(&mut self, rvalue: &Rvalue, span: Span) -> String {
+        let db = self.cx.db;
+        match rvalue {
+            Rvalue::Use(op) => self.operand(op),
+            Rvalue::BinaryOp(op, left, right) => {
+                let ty = self.operand_ty(left);
+                let amount_ty = self.operand_ty(right);
+                let left = self.operand(left);
+                let right = self.operand(right);
+                self.binary(*op, ty, amount_ty, &left, &right, span)
+            }
+            Rvalue::UnaryOp(op, val) => {
+                let ty = self.operand_ty(val);
+                let val = self.operand(val);
+                match (op, ty.kind(db)) {
+                    (UnOp::Not, TyKind::Bool) => format!("(!{})", val),
+                    (UnOp::Not, _) => format!("(({})~{})", self.cx.c_type(ty), val),
+                    (UnOp::Neg, TyKind::Int { width, signed }) => format!(
+                        "cell_neg_{}({}, {})",
+                        int_name(width, signed),
+                        val,
+                        self.location(span)
+                    ),
+                    (UnOp::Neg, _) => format!("(-{})", val),
+                }
+            }
+            Rvalue::AddressOf(place) => format!("&{}", self.place(place).0),
+            Rvalue::AddressOfStatic(item) => format!("&{}", self.cx.static_name(*item)),
+            Rvalue::Discriminant(place) => {
+                let (val, ty) = self.place(place);
+                self.cx.discriminant(&val, ty)
+            }
+            Rvalue::Aggregate(kind, ops) => {
+                let ty = self.subst(rvalue.ty(db, self.body));
+                let c_ty = self.cx.c_type(ty);
+                let ops = ops.iter().map(|op| self.operand(op)).collect::<Vec<_>>();
+                match kind {
+                    AggregateKind::Variant(_, idx, _) => {
+                        let variant = &self.cx.variant_names(ty)[*idx as usize];
+                        let niche = matches!(self.cx.layout(ty).variants, Variants::Niche { .. });
+                        match ops.first() {
+                            // the niche variant is stored as a null pointer
+                            _ if niche && ops.is_empty() => format!("({}){{0}}", c_ty),
+                            Some(data) if niche => {
+                                format!("({}){{.data.{} = {}}}", c_ty, variant, data)
+                            }
+                            Some(data) => format!(
+                                "({}){{.tag = {}, .data.{} = {}}}",
+                                c_ty, idx, variant, data
+                            ),
+                            None => format!("({}){{.tag = {}}}", c_ty, idx),
+                        }
+                    }
+                    _ if ops.is_empty() => format!("({}){{0}}", c_ty),
+                    _ => format!("({}){{{}}}", c_ty, ops.join(", ")),
+                }
+            }
+        }
+    }
+
+    /// Returns the C expression of a binary operator.
+    ///
+    /// The right operand of shifts and pointer arithmetic can be of any
+    /// integer type, which is passed as `right_ty`.
+    fn binary(
+        &mut self,
+        op: BinOp,
+        ty: Ty,
+        right_ty: Ty,
+        left: &str,
+        right: &str,
+        span: Span,
+    ) -> String {
+        let db = self.cx.db;
+        match op {
+            BinOp::Eq => return self.cx.equal(left, right, ty),
+            BinOp::Ne => return format!("(!{})", self.cx.equal(left, right, ty)),
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                return format!("({} {} {})", left, op.symbol(), right)
+            }
+            _ => {}
+        }
+
+        match ty.kind(db) {
+            TyKind::Int { width, signed } => {
+                let name = int_name(width, signed);
+                let loc = self.location(span);
+                match op {
+                    BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor => format!(
+                        "(({})({} {} {}))",
+                        self.cx.c_type(ty),
+                        left,
+                        op.symbol(),
+                        right
+                    ),
+                    BinOp::Shl | BinOp::Shr => {
+                        let amount = match right_ty.kind(db) {
+                            TyKind::Int { width, signed } => int_name(width, signed),
+                            _ => panic!("shift amount is not an integer"),
+                        };
+                        format!(
+                            "cell_{}_{}({}, cell_shamt_{}({}, {}, \"{}\", {}))",
+                            if op == BinOp::Shl { "shl" } else { "shr" },
+                            name,
+                            left,
+                            amount,
+                            right,
+                            width,
+                            name,
+                            loc
+                        )
+                    }
+                    _ => {
+                        let func = match op {
+                            BinOp::Add => "add",
+                            BinOp::Sub => "sub",
+                            BinOp::Mul => "mul",
+                            BinOp::Div => "div",
+                            BinOp::Rem => "rem",
+                            _ => "pow",
+                        };
+                        format!("cell_{}_{}({}, {}, {})", func, name, left, right, loc)
+                    }
+                }
+            }
+            TyKind::Float { width } => {
+                let suffix = if width == 32 { "f" } else { "" };
+                match op {
+                    BinOp::Rem => format!("fmod{}({}, {})", suffix, left, right),
+                    BinOp::Pow => format!("pow{}({}, {})", suffix, left, right),
+                    _ => format!("({} {} {})", left, op.symbol(), right),
+                }
+            }
+            // pointer arithmetic, and bit operators on `bool`s
+            _ => format!("({} {} {})", left, op.symbol(), right),
+        }
+    }
+
+    fn terminator(&mut self, kind: &TerminatorKind, span: Span, next: u32) {
+        let db = self.cx.db;
+        match kind {
+            TerminatorKind::Goto { target } => self.jump(target.0, next),
+            TerminatorKind::SwitchInt {
+                discr,
+                targets,
+                otherwise,
+            } => {
+                let ty = self.operand_ty(discr);
+                let discr = self.operand(discr);
+                match (targets.as_slice(), ty.kind(db)) {
+                    ([(0, target)], TyKind::Bool) => {
+                        let otherwise = self.goto(otherwise.0);
+                        writeln!(self.out, "    if ({}) {}", discr, otherwise).unwrap();
+                        self.jump(target.0, next);
+                    }
+                    (targets, kind) => {
+                        writeln!(self.out, "    switch ({}) {{", discr).unwrap();
+                        for (val, target) in targets {
+                            let val = match kind {
+                                TyKind::Int { width, signed } => int_literal(*val, width, signed),
+                                _ => val.to_string(),
+                            };
+                            let target = self.goto(target.0);
+                            writeln!(self.out, "    case {}: {}", val, target).unwrap();
+                        }
+                        let otherwise = self.goto(otherwise.0);
+                        writeln!(self.out, "    default: {}\n    }}", otherwise).unwrap();
+                    }
+                }
+            }
+            TerminatorKind::Return => self.out.push_str("    return _0;\n"),
+            TerminatorKind::Call {
+                func,
+                generic_args,
+                args,
+                destination,
+                target,
+            } => {
+                let generic_args = generic_args
+                    .iter()
+                    .map(|&ty| self.subst(ty))
+                    .collect::<Vec<_>>();
+                self.call(*func, generic_args, args, destination, span);
+                self.jump(target.0, next);
+            }
+            TerminatorKind::Unreachable => {
+                let loc = self.location(span);
+                writeln!(
+                    self.out,
+                    "    cell_trap({}, \"entered unreachable code\");",
+                    loc
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    /// Returns a `goto` statement, and remembers that the target needs a label.
+    fn goto(&mut self, target: u32) -> String {
+        self.labels.insert(target);
+        format!("goto bb{};", target)
+    }
+
+    /// Jumps to the target, unless it's the next block.
+    fn jump(&mut self, target: u32, next: u32) {
+        if target != next {
+            let goto = self.goto(target);
+            writeln!(self.out, "    {}", goto).unwrap();
+        }
+    }
+
+    fn call(
+        &mut self,
+        func: ItemId,
+        generic_args: Vec<Ty>,
+        args: &[Operand],
+        dest: &Place,
+        span: Span,
+    ) {
+        let db = self.cx.db;
+        match prelude::host_fn(db, func) {
+            Some(HostFn::Println) => {
+                // the first argument is the pointer to `io`
+                let ty = self.operand_ty(&args[1]);
+                let val = self.operand(&args[1]);
+                let print = self.cx.print(&val, ty, false);
+                writeln!(self.out, "    {}\n    putchar('\\n');", print).unwrap();
+                return;
+            }
+            Some(HostFn::Alloc) => {
+                let count = self.operand(&args[0]);
+                let elem = self.cx.c_type(generic_args[0]);
+                let (dest, _) = self.place(dest);
+                writeln!(
+                    self.out,
+                    "    {} = cell_alloc((uint64_t){}, sizeof({}), {});",
+                    dest,
+                    count,
+                    elem,
+                    self.location(span)
+                )
+                .unwrap();
+                return;
+            }
+            None => {}
+        }
+
+        let func = self
+            .cx
+            .instance(&Instance::resolve(db, func, &generic_args));
+        let args = args.iter().map(|arg| self.operand(arg)).collect::<Vec<_>>();
+        let (dest, _) = self.place(dest);
+        writeln!(self.out, "    {} = {}({});", dest, func, args.join(", ")).unwrap();
+    }
+}
+
+fn pointee(db: &dyn MirDatabase, ty: Ty) -> Ty {
+    match ty.kind(db) {
+        TyKind::Pointer(inner) => inner,
+        _ => panic!("dereferenced a type that is not a pointer"),
+    }
+}
@@ -0,0 +1,64 @@
+//! A backend that translates the MIR into portable C99.
+//!
+//! Integers map onto the fixed width types of `stdint.h`, tuples, named
+//! tuples and `type`s become structs, and unions become tagged unions,
+//! which store the index of their variant next to a C `union` of the data.
+//! The size of the tag, and unions that use a niche, follow the layouts of
+//! [`cell_mir::layout`]. `@repr(packed)` types use the `packed` attribute
+//! of GCC and Clang, which is the only thing that isn't C99.
+//! Every generic function is generated once for every combination of
+//! generic arguments it is called with, which are collected by
+//! [`cell_mir::mono`] and named by their mangled symbol.
+//!
+//! Arithmetic is checked at runtime by the helpers inside `runtime.h`,
+//! which stop the program with the same errors as the interpreter.
+//!
+//! [`cell_mir::layout`]: ../cell_mir/layout/index.html
+//! [`cell_mir::mono`]: ../cell_mir/mono/index.html
+
+mod codegen;
+mod types;
+
+pub use codegen::generate;
+
+use std::{
+    env,
+    ffi::OsString,
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Compiles the generated C source into an executable at `output`,
+/// using the system C compiler.
+///
+/// The compiler is taken from the `CC` environment variable,
+/// and defaults to `cc`.
+pub fn build(source: &str, output: &Path) -> io::Result<()> {
+    let cc = env::var_os("CC").unwrap_or_else(|| OsString::from("cc"));
+    let mut child = Command::new(&cc)
+        .args(["-std=c99", "-O2", "-o"])
+        .arg(output)
+        // read the source from stdin
+        .args(["-x", "c", "-", "-lm"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin of the C compiler is piped")
+        .write_all(source.as_bytes())?;
+
+    let result = child.wait_with_output()?;
+    if result.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "`{}` failed with {}:\n{}",
+            cc.to_string_lossy(),
+            result.status,
+            String::from_utf8_lossy(&result.stderr)
+        )))
+    }
+}
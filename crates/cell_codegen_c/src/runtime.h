@@ -0,0 +1,398 @@
+/* The runtime support code that is included into every generated program. */
+
+#include <inttypes.h>
+#include <math.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef uint8_t cell_unit;
+typedef uint32_t cell_char;
+typedef const char *cell_string;
+
+#ifdef __SIZEOF_INT128__
+typedef __int128 cell_i128;
+typedef unsigned __int128 cell_u128;
+#define CELL_U128_MAX (~(cell_u128)0)
+#define CELL_I128_MAX ((cell_i128)(CELL_U128_MAX >> 1))
+#define CELL_I128_MIN (-CELL_I128_MAX - 1)
+#endif
+
+static void cell_trap(const char *loc, const char *fmt, ...) {
+    va_list args;
+    fflush(stdout);
+    fputs("error: runtime: ", stderr);
+    va_start(args, fmt);
+    vfprintf(stderr, fmt, args);
+    va_end(args);
+    fprintf(stderr, "\n  --> %s\n", loc);
+    exit(101);
+}
+
+static inline void cell_overflow(const char *ty, const char *loc) {
+    cell_trap(loc, "this arithmetic operation will overflow `%s`", ty);
+}
+
+static inline void *cell_alloc(uint64_t count, size_t size, const char *loc) {
+    void *ptr;
+    if (size == 0) {
+        size = 1;
+    }
+    if (count > SIZE_MAX / size) {
+        cell_trap(loc, "out of memory");
+    }
+    ptr = calloc(count == 0 ? 1 : (size_t)count, size);
+    if (ptr == NULL) {
+        cell_trap(loc, "out of memory");
+    }
+    return ptr;
+}
+
+/* Printing values the same way as the interpreter does. */
+
+static inline void cell_print_utf8(cell_char val) {
+    if (val < 0x80) {
+        putchar((int)val);
+    } else if (val < 0x800) {
+        putchar((int)(0xC0 | (val >> 6)));
+        putchar((int)(0x80 | (val & 0x3F)));
+    } else if (val < 0x10000) {
+        putchar((int)(0xE0 | (val >> 12)));
+        putchar((int)(0x80 | ((val >> 6) & 0x3F)));
+        putchar((int)(0x80 | (val & 0x3F)));
+    } else {
+        putchar((int)(0xF0 | (val >> 18)));
+        putchar((int)(0x80 | ((val >> 12) & 0x3F)));
+        putchar((int)(0x80 | ((val >> 6) & 0x3F)));
+        putchar((int)(0x80 | (val & 0x3F)));
+    }
+}
+
+static inline void cell_print_escaped(cell_char val, cell_char quote) {
+    switch (val) {
+    case '\t': fputs("\\t", stdout); return;
+    case '\n': fputs("\\n", stdout); return;
+    case '\r': fputs("\\r", stdout); return;
+    case '\\': fputs("\\\\", stdout); return;
+    case '\0': fputs("\\0", stdout); return;
+    default: break;
+    }
+    if (val == quote) {
+        putchar('\\');
+        putchar((int)quote);
+    } else if (val < 0x20 || val == 0x7F) {
+        printf("\\u{%" PRIx32 "}", val);
+    } else {
+        cell_print_utf8(val);
+    }
+}
+
+static inline void cell_print_char(cell_char val, bool nested) {
+    if (!nested) {
+        cell_print_utf8(val);
+        return;
+    }
+    putchar('\'');
+    cell_print_escaped(val, '\'');
+    putchar('\'');
+}
+
+/* Decodes the next character of an UTF-8 string. */
+static inline cell_char cell_next_utf8(const unsigned char **str) {
+    const unsigned char *s = *str;
+    cell_char val;
+    if (s[0] < 0x80) {
+        val = s[0];
+        *str += 1;
+    } else if (s[0] < 0xE0) {
+        val = ((cell_char)(s[0] & 0x1F) << 6) | (s[1] & 0x3F);
+        *str += 2;
+    } else if (s[0] < 0xF0) {
+        val = ((cell_char)(s[0] & 0x0F) << 12) | ((cell_char)(s[1] & 0x3F) << 6) | (s[2] & 0x3F);
+        *str += 3;
+    } else {
+        val = ((cell_char)(s[0] & 0x07) << 18) | ((cell_char)(s[1] & 0x3F) << 12) |
+              ((cell_char)(s[2] & 0x3F) << 6) | (s[3] & 0x3F);
+        *str += 4;
+    }
+    return val;
+}
+
+static inline void cell_print_string(cell_string val, bool nested) {
+    const unsigned char *s = (const unsigned char *)val;
+    if (!nested) {
+        fputs(val, stdout);
+        return;
+    }
+    putchar('"');
+    while (*s != '\0') {
+        cell_print_escaped(cell_next_utf8(&s), '"');
+    }
+    putchar('"');
+}
+
+static inline void cell_print_bool(bool val) {
+    fputs(val ? "true" : "false", stdout);
+}
+
+static inline void cell_print_unit(cell_unit val) {
+    (void)val;
+    fputs("()", stdout);
+}
+
+static inline void cell_print_pointer(const void *val) {
+    printf("<pointer %p>", val);
+}
+
+/* Prints the shortest representation that reads back as the same value,
+ * without using an exponent. */
+static inline void cell_print_float(double val) {
+    char buf[32], digits[32];
+    int precision, exp, len = 0, point, i;
+    char *c;
+
+    if (isnan(val)) {
+        fputs("NaN", stdout);
+        return;
+    }
+    if (signbit(val)) {
+        putchar('-');
+        val = -val;
+    }
+    if (isinf(val)) {
+        fputs("inf", stdout);
+        return;
+    }
+
+    for (precision = 0; precision < 17; precision++) {
+        snprintf(buf, sizeof(buf), "%.*e", precision, val);
+        if (strtod(buf, NULL) == val) {
+            break;
+        }
+    }
+    for (c = buf; *c != 'e'; c++) {
+        if (*c != '.') {
+            digits[len++] = *c;
+        }
+    }
+    exp = atoi(c + 1);
+    while (len > 1 && digits[len - 1] == '0') {
+        len--;
+    }
+
+    point = exp + 1;
+    if (point <= 0) {
+        fputs("0.", stdout);
+        for (i = 0; i < -point; i++) {
+            putchar('0');
+        }
+        fwrite(digits, 1, (size_t)len, stdout);
+    } else if (point >= len) {
+        fwrite(digits, 1, (size_t)len, stdout);
+        for (i = len; i < point; i++) {
+            putchar('0');
+        }
+    } else {
+        fwrite(digits, 1, (size_t)point, stdout);
+        putchar('.');
+        fwrite(digits + point, 1, (size_t)(len - point), stdout);
+    }
+}
+
+/* Checked integer arithmetic, which stops the program on overflow. */
+
+#define CELL_FMT(N, T, C, FMT)                                                                     \
+    static inline char *cell_fmt_##N(char *buf, T val) {                                           \
+        sprintf(buf, "%" FMT, (C)val);                                                             \
+        return buf;                                                                                \
+    }
+
+#define CELL_SIGNED(N, T, MIN, MAX)                                                                \
+    static inline T cell_add_##N(T a, T b, const char *loc) {                                      \
+        if ((b > 0 && a > MAX - b) || (b < 0 && a < MIN - b)) {                                    \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return (T)(a + b);                                                                         \
+    }                                                                                              \
+    static inline T cell_sub_##N(T a, T b, const char *loc) {                                      \
+        if ((b < 0 && a > MAX + b) || (b > 0 && a < MIN + b)) {                                    \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return (T)(a - b);                                                                         \
+    }                                                                                              \
+    static inline T cell_mul_##N(T a, T b, const char *loc) {                                      \
+        bool overflow;                                                                             \
+        if (a > 0) {                                                                               \
+            overflow = b > 0 ? a > MAX / b : b < MIN / a;                                          \
+        } else {                                                                                   \
+            overflow = b > 0 ? a < MIN / b : a != 0 && b < MAX / a;                                \
+        }                                                                                          \
+        if (overflow) {                                                                            \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return (T)(a * b);                                                                         \
+    }                                                                                              \
+    static inline T cell_div_##N(T a, T b, const char *loc) {                                      \
+        if (b == 0) {                                                                              \
+            cell_trap(loc, "attempt to divide by zero");                                           \
+        }                                                                                          \
+        if (a == MIN && b == -1) {                                                                 \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return (T)(a / b);                                                                         \
+    }                                                                                              \
+    static inline T cell_rem_##N(T a, T b, const char *loc) {                                      \
+        if (b == 0) {                                                                              \
+            cell_trap(loc, "attempt to calculate the remainder with a divisor of zero");           \
+        }                                                                                          \
+        /* `MIN % -1` is zero, but undefined in C */                                               \
+        if (b == -1) {                                                                             \
+            return 0;                                                                              \
+        }                                                                                          \
+        return (T)(a % b);                                                                         \
+    }                                                                                              \
+    static inline T cell_neg_##N(T a, const char *loc) {                                           \
+        if (a == MIN) {                                                                            \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return (T)-a;                                                                              \
+    }
+
+#define CELL_UNSIGNED(N, T, MAX)                                                                   \
+    static inline T cell_add_##N(T a, T b, const char *loc) {                                      \
+        if (a > MAX - b) {                                                                         \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return (T)(a + b);                                                                         \
+    }                                                                                              \
+    static inline T cell_sub_##N(T a, T b, const char *loc) {                                      \
+        if (a < b) {                                                                               \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return (T)(a - b);                                                                         \
+    }                                                                                              \
+    static inline T cell_mul_##N(T a, T b, const char *loc) {                                      \
+        if (b != 0 && a > MAX / b) {                                                               \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return (T)(a * b);                                                                         \
+    }                                                                                              \
+    static inline T cell_div_##N(T a, T b, const char *loc) {                                      \
+        if (b == 0) {                                                                              \
+            cell_trap(loc, "attempt to divide by zero");                                           \
+        }                                                                                          \
+        return (T)(a / b);                                                                         \
+    }                                                                                              \
+    static inline T cell_rem_##N(T a, T b, const char *loc) {                                      \
+        if (b == 0) {                                                                              \
+            cell_trap(loc, "attempt to calculate the remainder with a divisor of zero");           \
+        }                                                                                          \
+        return (T)(a % b);                                                                         \
+    }                                                                                              \
+    static inline T cell_neg_##N(T a, const char *loc) {                                           \
+        if (a != 0) {                                                                              \
+            cell_overflow(#N, loc);                                                                \
+        }                                                                                          \
+        return 0;                                                                                  \
+    }
+
+/* `W` is an unsigned type that is at least as wide as `T`.
+ * `amount != 0 && !(amount > 0)` checks for negative values
+ * without warnings for unsigned types. */
+#define CELL_INT(N, T, W)                                                                          \
+    static inline T cell_pow_##N(T base, T exp, const char *loc) {                                 \
+        T acc = 1;                                                                                 \
+        if (exp != 0 && !(exp > 0)) {                                                              \
+            cell_trap(loc, "attempt to raise an integer to a negative power");                     \
+        }                                                                                          \
+        if (exp == 0) {                                                                            \
+            return 1;                                                                              \
+        }                                                                                          \
+        while (exp > 1) {                                                                          \
+            if (exp & 1) {                                                                         \
+                acc = cell_mul_##N(acc, base, loc);                                                \
+            }                                                                                      \
+            exp /= 2;                                                                              \
+            base = cell_mul_##N(base, base, loc);                                                  \
+        }                                                                                          \
+        return cell_mul_##N(acc, base, loc);                                                       \
+    }                                                                                              \
+    static inline unsigned cell_shamt_##N(T amount, unsigned bits, const char *ty,                 \
+                                          const char *loc) {                                       \
+        char buf[48];                                                                              \
+        if ((amount != 0 && !(amount > 0)) || (W)amount >= bits) {                                 \
+            cell_trap(loc, "attempt to shift `%s` by `%s`, which is too large", ty,                \
+                      cell_fmt_##N(buf, amount));                                                  \
+        }                                                                                          \
+        return (unsigned)amount;                                                                   \
+    }                                                                                              \
+    static inline T cell_shl_##N(T a, unsigned amount) {                                           \
+        return (T)((W)a << amount);                                                                \
+    }                                                                                              \
+    static inline T cell_shr_##N(T a, unsigned amount) {                                           \
+        return (T)(a >> amount);                                                                   \
+    }                                                                                              \
+    static inline void cell_print_##N(T val) {                                                     \
+        char buf[48];                                                                              \
+        fputs(cell_fmt_##N(buf, val), stdout);                                                     \
+    }
+
+CELL_FMT(i8, int8_t, int64_t, PRId64)
+CELL_FMT(i16, int16_t, int64_t, PRId64)
+CELL_FMT(i32, int32_t, int64_t, PRId64)
+CELL_FMT(i64, int64_t, int64_t, PRId64)
+CELL_FMT(u8, uint8_t, uint64_t, PRIu64)
+CELL_FMT(u16, uint16_t, uint64_t, PRIu64)
+CELL_FMT(u32, uint32_t, uint64_t, PRIu64)
+CELL_FMT(u64, uint64_t, uint64_t, PRIu64)
+
+CELL_SIGNED(i8, int8_t, INT8_MIN, INT8_MAX)
+CELL_SIGNED(i16, int16_t, INT16_MIN, INT16_MAX)
+CELL_SIGNED(i32, int32_t, INT32_MIN, INT32_MAX)
+CELL_SIGNED(i64, int64_t, INT64_MIN, INT64_MAX)
+CELL_UNSIGNED(u8, uint8_t, UINT8_MAX)
+CELL_UNSIGNED(u16, uint16_t, UINT16_MAX)
+CELL_UNSIGNED(u32, uint32_t, UINT32_MAX)
+CELL_UNSIGNED(u64, uint64_t, UINT64_MAX)
+
+CELL_INT(i8, int8_t, uint64_t)
+CELL_INT(i16, int16_t, uint64_t)
+CELL_INT(i32, int32_t, uint64_t)
+CELL_INT(i64, int64_t, uint64_t)
+CELL_INT(u8, uint8_t, uint64_t)
+CELL_INT(u16, uint16_t, uint64_t)
+CELL_INT(u32, uint32_t, uint64_t)
+CELL_INT(u64, uint64_t, uint64_t)
+
+#ifdef __SIZEOF_INT128__
+static inline char *cell_fmt_u128(char *buf, cell_u128 val) {
+    char digits[48];
+    int len = 0, i;
+    do {
+        digits[len++] = (char)('0' + (int)(val % 10));
+        val /= 10;
+    } while (val != 0);
+    for (i = 0; i < len; i++) {
+        buf[i] = digits[len - 1 - i];
+    }
+    buf[len] = '\0';
+    return buf;
+}
+
+static inline char *cell_fmt_i128(char *buf, cell_i128 val) {
+    if (val < 0) {
+        buf[0] = '-';
+        cell_fmt_u128(buf + 1, (cell_u128)0 - (cell_u128)val);
+        return buf;
+    }
+    return cell_fmt_u128(buf, (cell_u128)val);
+}
+
+CELL_SIGNED(i128, cell_i128, CELL_I128_MIN, CELL_I128_MAX)
+CELL_UNSIGNED(u128, cell_u128, CELL_U128_MAX)
+CELL_INT(i128, cell_i128, cell_u128)
+CELL_INT(u128, cell_u128, cell_u128)
+#endif
@@ -0,0 +1,377 @@
+//! How types are represented in C, and the functions that compare
+//! and print values of aggregate types.
+
+use crate::codegen::{c_string, Codegen};
+use cell_mir::{
+    body::{field_ty, variant_ty},
+    layout::Variants,
+};
+use cell_syntax::ast::{ItemId, ItemKind, Repr};
+use cell_typeck::{
+    lower::AdtDef,
+    ty::{Ty, TyKind},
+};
+use std::fmt::Write;
+
+/// Names that can't be used as fields, because they are keywords
+/// or macros of the included headers.
+const RESERVED: &[&str] = &[
+    "auto", "bool", "break", "case", "char", "const", "continue", "default", "do", "double",
+    "else", "enum", "errno", "extern", "false", "float", "for", "goto", "if", "inline", "int",
+    "long", "register", "restrict", "return", "short", "signed", "sizeof", "static", "stderr",
+    "stdin", "stdout", "struct", "switch", "true", "typedef", "union", "unsigned", "void",
+    "volatile", "while",
+];
+
+/// Returns the name of a field, that can't be confused with a keyword or macro.
+fn member(name: &str) -> String {
+    // object-like macros are upper case, like `NULL` or `INT8_MAX`
+    let macro_like = !name.chars().any(|c| c.is_ascii_lowercase());
+    if macro_like || RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns the name that is used for an integer type in the runtime.
+pub fn int_name(width: u16, signed: bool) -> String {
+    format!("{}{}", if signed { 'i' } else { 'u' }, width)
+}
+
+impl Codegen<'_> {
+    /// Returns the C type of a type, and defines it if necessary.
+    pub fn c_type(&mut self, ty: Ty) -> String {
+        let db = self.db;
+        match ty.kind(db) {
+            TyKind::Int { width: 128, signed } => {
+                format!("cell_{}", int_name(128, signed))
+            }
+            TyKind::Int { width, signed } => {
+                format!("{}int{}_t", if signed { "" } else { "u" }, width)
+            }
+            TyKind::Float { width: 32 } => "float".to_string(),
+            TyKind::Float { .. } => "double".to_string(),
+            TyKind::Bool => "bool".to_string(),
+            TyKind::Char => "cell_char".to_string(),
+            TyKind::String => "cell_string".to_string(),
+            TyKind::Pointer(inner) => format!("{}*", self.c_type(inner)),
+            TyKind::Tuple(_) | TyKind::NamedTuple(_) | TyKind::Adt(..) => self.aggregate(ty),
+            _ => "cell_unit".to_string(),
+        }
+    }
+
+    /// Returns the name of the struct of a tuple, named tuple or `type`,
+    /// and defines it if necessary.
+    ///
+    /// Structs are defined after every type they contain by value,
+    /// and are declared before they are defined, so pointers to them
+    /// can be used by the types they contain.
+    fn aggregate(&mut self, ty: Ty) -> String {
+        if let Some(name) = self.types.get(&ty) {
+            return name.clone();
+        }
+
+        let db = self.db;
+        let name = match ty.kind(db) {
+            TyKind::Adt(item, args) => {
+                let mut name = self.adt_name(item);
+                for arg in args {
+                    name.push('_');
+                    name.push_str(&arg.display(db));
+                }
+                self.global_name("ty", &name)
+            }
+            TyKind::Tuple(_) => self.global_name("ty", "tuple"),
+            _ => self.global_name("ty", "named_tuple"),
+        };
+        self.types.insert(ty, name.clone());
+        writeln!(self.typedefs, "typedef struct {0} {0};", name).unwrap();
+
+        let packed = match ty.kind(db) {
+            TyKind::Adt(item, _) => match db.lookup_intern_item(item).kind {
+                ItemKind::TypeDef(def) => def.repr == Repr::Packed,
+                ItemKind::Union(union) => union.repr == Repr::Packed,
+                _ => false,
+            },
+            _ => false,
+        };
+        let attrs = if packed {
+            "__attribute__((packed)) "
+        } else {
+            ""
+        };
+
+        let mut members = String::new();
+        match ty.kind(db) {
+            TyKind::Adt(item, _) if matches!(&*db.adt_def(item), AdtDef::Union { .. }) => {
+                // unions that use a niche are only the data of their variants
+                if let Variants::Tagged { tag_size, .. } = self.layout(ty).variants {
+                    writeln!(members, "    uint{}_t tag;", tag_size * 8).unwrap();
+                }
+                let variants = self.variant_names(ty);
+                if !variants.is_empty() {
+                    writeln!(members, "    union {}{{", attrs).unwrap();
+                    for (idx, variant) in variants.iter().enumerate() {
+                        let data = self.c_type(variant_ty(db, ty, idx as u32));
+                        writeln!(members, "        {} {};", data, variant).unwrap();
+                    }
+                    members.push_str("    } data;\n");
+                }
+            }
+            _ => {
+                let fields = self.field_names(ty);
+                for (idx, field) in fields.iter().enumerate() {
+                    let field_ty = self.c_type(field_ty(db, ty, idx as u32));
+                    writeln!(members, "    {} {};", field_ty, field).unwrap();
+                }
+                // C doesn't allow empty structs
+                if fields.is_empty() {
+                    members.push_str("    cell_unit unused;\n");
+                }
+            }
+        }
+        writeln!(
+            self.structs,
+            "struct {}{} {{\n{}}};\n",
+            attrs, name, members
+        )
+        .unwrap();
+        name
+    }
+
+    /// Returns the C expression of the index of the variant of a union.
+    pub fn discriminant(&mut self, val: &str, ty: Ty) -> String {
+        let db = self.db;
+        match self.layout(ty).variants.clone() {
+            Variants::Niche {
+                dataful,
+                niche_variant,
+                niche,
+            } => {
+                let mut niche_val =
+                    format!("{}.data.{}", val, self.variant_names(ty)[dataful as usize]);
+                let mut inner = variant_ty(db, ty, dataful);
+                for idx in niche.path {
+                    niche_val.push('.');
+                    niche_val.push_str(&self.field_names(inner)[idx as usize]);
+                    inner = field_ty(db, inner, idx);
+                }
+                format!("({} != NULL ? {} : {})", niche_val, dataful, niche_variant)
+            }
+            _ => format!("{}.tag", val),
+        }
+    }
+
+    fn adt_name(&self, item: ItemId) -> String {
+        match self.db.lookup_intern_item(item).kind.name() {
+            Some(name) => self.db.rodeo().resolve(&name.id).to_string(),
+            None => "unknown".to_string(),
+        }
+    }
+
+    /// Returns the C names of the fields of a tuple, named tuple or `type`.
+    pub fn field_names(&self, ty: Ty) -> Vec<String> {
+        let db = self.db;
+        let name = |name| member(db.rodeo().resolve(&name));
+        match ty.kind(db) {
+            TyKind::Tuple(tys) => (0..tys.len()).map(|idx| format!("_{}", idx)).collect(),
+            TyKind::NamedTuple(fields) => {
+                fields.into_iter().map(|(field, _)| name(field)).collect()
+            }
+            TyKind::Adt(item, _) => match &*db.adt_def(item) {
+                AdtDef::Struct { fields } => fields.iter().map(|(field, _)| name(*field)).collect(),
+                AdtDef::Union { .. } => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Returns the C names of the variants of a union.
+    pub fn variant_names(&self, ty: Ty) -> Vec<String> {
+        let db = self.db;
+        match ty.kind(db) {
+            TyKind::Adt(item, _) => match &*db.adt_def(item) {
+                AdtDef::Union { variants } => variants
+                    .iter()
+                    .map(|(variant, _)| member(db.rodeo().resolve(variant)))
+                    .collect(),
+                AdtDef::Struct { .. } => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Returns a C expression, that is `true` if both values are equal.
+    pub fn equal(&mut self, left: &str, right: &str, ty: Ty) -> String {
+        match ty.kind(self.db) {
+            TyKind::String => format!("(strcmp({}, {}) == 0)", left, right),
+            TyKind::Tuple(_) | TyKind::NamedTuple(_) | TyKind::Adt(..) => {
+                format!("{}({}, {})", self.eq_fn(ty), left, right)
+            }
+            _ => format!("({} == {})", left, right),
+        }
+    }
+
+    /// Returns the function that compares values of an aggregate type.
+    fn eq_fn(&mut self, ty: Ty) -> String {
+        if let Some(name) = self.eq_fns.get(&ty) {
+            return name.clone();
+        }
+
+        let db = self.db;
+        let c_ty = self.c_type(ty);
+        let name = self.global_name("eq", &c_ty);
+        self.eq_fns.insert(ty, name.clone());
+
+        let mut body = String::new();
+        match ty.kind(db) {
+            TyKind::Adt(item, _) if matches!(&*db.adt_def(item), AdtDef::Union { .. }) => {
+                let (a, b) = (self.discriminant("a", ty), self.discriminant("b", ty));
+                writeln!(
+                    body,
+                    "    if ({} != {}) {{\n        return false;\n    }}",
+                    a, b
+                )
+                .unwrap();
+                writeln!(body, "    switch ({}) {{", a).unwrap();
+                for (idx, variant) in self.variant_names(ty).iter().enumerate() {
+                    // variants without data are handled by the `default` case
+                    let data = variant_ty(db, ty, idx as u32);
+                    if data.kind(db) == TyKind::Unit {
+                        continue;
+                    }
+                    let left = format!("a.data.{}", variant);
+                    let right = format!("b.data.{}", variant);
+                    let equal = self.equal(&left, &right, data);
+                    writeln!(body, "    case {}:\n        return {};", idx, equal).unwrap();
+                }
+                body.push_str("    default:\n        return true;\n    }\n");
+            }
+            _ => {
+                let mut equal = vec![];
+                for (idx, field) in self.field_names(ty).iter().enumerate() {
+                    let field_ty = field_ty(db, ty, idx as u32);
+                    let left = format!("a.{}", field);
+                    let right = format!("b.{}", field);
+                    equal.push(self.equal(&left, &right, field_ty));
+                }
+                if equal.is_empty() {
+                    equal.push("true".to_string());
+                }
+                writeln!(body, "    return {};", equal.join(" && ")).unwrap();
+            }
+        }
+
+        writeln!(
+            self.helpers,
+            "static bool {}({1} a, {1} b) {{\n{2}}}\n",
+            name, c_ty, body
+        )
+        .unwrap();
+        name
+    }
+
+    /// Returns a C statement, that prints the value.
+    ///
+    /// Strings and chars are quoted if they are `nested` inside of another value.
+    pub fn print(&mut self, val: &str, ty: Ty, nested: bool) -> String {
+        match ty.kind(self.db) {
+            TyKind::Int { width, signed } => {
+                format!("cell_print_{}({});", int_name(width, signed), val)
+            }
+            TyKind::Float { .. } => format!("cell_print_float({});", val),
+            TyKind::Bool => format!("cell_print_bool({});", val),
+            TyKind::Char => format!("cell_print_char({}, {});", val, nested),
+            TyKind::String => format!("cell_print_string({}, {});", val, nested),
+            TyKind::Pointer(_) => format!("cell_print_pointer({});", val),
+            TyKind::Tuple(_) | TyKind::NamedTuple(_) | TyKind::Adt(..) => {
+                format!("{}({});", self.print_fn(ty), val)
+            }
+            _ => format!("cell_print_unit({});", val),
+        }
+    }
+
+    /// Returns the function that prints values of an aggregate type.
+    fn print_fn(&mut self, ty: Ty) -> String {
+        if let Some(name) = self.print_fns.get(&ty) {
+            return name.clone();
+        }
+
+        let db = self.db;
+        let c_ty = self.c_type(ty);
+        let name = self.global_name("print", &c_ty);
+        self.print_fns.insert(ty, name.clone());
+
+        let puts = |text: &str| format!("    fputs({}, stdout);\n", c_string(text));
+        let mut body = String::new();
+        match ty.kind(db) {
+            TyKind::Adt(item, _) if matches!(&*db.adt_def(item), AdtDef::Union { .. }) => {
+                let variants = match &*db.adt_def(item) {
+                    AdtDef::Union { variants } => variants.clone(),
+                    AdtDef::Struct { .. } => unreachable!(),
+                };
+                let union = self.adt_name(item);
+                let tag = self.discriminant("val", ty);
+                writeln!(body, "    switch ({}) {{", tag).unwrap();
+                for (idx, field) in self.variant_names(ty).iter().enumerate() {
+                    let (variant, data) = variants[idx];
+                    let variant = format!("{}:{}", union, db.rodeo().resolve(&variant));
+                    writeln!(body, "    case {}:\n        {}", idx, puts(&variant).trim()).unwrap();
+                    if data.is_some() {
+                        let data = variant_ty(db, ty, idx as u32);
+                        let print = self.print(&format!("val.data.{}", field), data, true);
+                        writeln!(
+                            body,
+                            "        putchar('(');\n        {}\n        putchar(')');",
+                            print
+                        )
+                        .unwrap();
+                    }
+                    body.push_str("        break;\n");
+                }
+                body.push_str("    }\n");
+            }
+            kind => {
+                // named tuples and `type`s print the names of their fields
+                let (mut text, names) = match kind {
+                    TyKind::NamedTuple(fields) => (
+                        "{".to_string(),
+                        fields.into_iter().map(|(field, _)| Some(field)).collect(),
+                    ),
+                    TyKind::Adt(item, _) => match &*db.adt_def(item) {
+                        AdtDef::Struct { fields } => (
+                            format!("{} {{", self.adt_name(item)),
+                            fields.iter().map(|(field, _)| Some(*field)).collect(),
+                        ),
+                        AdtDef::Union { .. } => unreachable!(),
+                    },
+                    _ => ("{".to_string(), vec![None; self.field_names(ty).len()]),
+                };
+
+                for (idx, field) in self.field_names(ty).iter().enumerate() {
+                    if idx > 0 {
+                        text.push_str(", ");
+                    }
+                    if let Some(name) = names[idx] {
+                        write!(text, "{}: ", db.rodeo().resolve(&name)).unwrap();
+                    }
+                    body.push_str(&puts(&std::mem::take(&mut text)));
+                    let field_ty = field_ty(db, ty, idx as u32);
+                    let print = self.print(&format!("val.{}", field), field_ty, true);
+                    writeln!(body, "    {}", print).unwrap();
+                }
+                text.push('}');
+                body.push_str(&puts(&text));
+            }
+        }
+
+        writeln!(
+            self.helpers,
+            "static void {}({} val) {{\n{}}}\n",
+            name, c_ty, body
+        )
+        .unwrap();
+        name
+    }
+}
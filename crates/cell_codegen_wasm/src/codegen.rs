@@ -0,0 +1,1126 @@
+//! Translates the MIR of every function that is reachable from `main`,
+//! or that is exported, into a WebAssembly module in the text format.
+
+use crate::{function::FnCodegen, runtime};
+use cell_common::{
+    error::{CodegenError, Error, RuntimeError},
+    profiler,
+    source::{FileId, Locatable, Span},
+};
+use cell_mir::{
+    body::field_ty,
+    db::MirDatabase,
+    layout::{align_to, Layout, Target, Variants},
+    mono::{self, Instance},
+    opt::OptLevel,
+};
+use cell_syntax::ast::{ItemId, ItemKind, Visibility};
+use cell_typeck::{
+    consteval::ConstValue,
+    lower::AdtDef,
+    scope,
+    ty::{Ty, TyKind},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    sync::Arc,
+};
+
+/// The address of the first byte of data.
+///
+/// The bytes before it are never used, so a null pointer is never valid.
+pub const DATA_START: u32 = 16;
+/// The size of the stack, which starts behind the data.
+pub const STACK_SIZE: u32 = 1 << 20;
+const PAGE_SIZE: u32 = 1 << 16;
+
+/// The type of a WebAssembly value.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ValType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F32 => "f32",
+            ValType::F64 => "f64",
+        }
+    }
+}
+
+/// How values of a type are represented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repr {
+    /// Values without any data, like `()`, which are not stored at all.
+    Zst,
+    /// A single WebAssembly value.
+    Scalar(ValType),
+    /// Values that are stored in linear memory, and are passed around
+    /// using their address.
+    Memory,
+}
+
+/// An integer type, whose arithmetic is checked by helper functions.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct IntTy {
+    pub width: u16,
+    pub signed: bool,
+}
+
+impl IntTy {
+    pub fn name(self) -> String {
+        format!("{}{}", if self.signed { 'i' } else { 'u' }, self.width)
+    }
+
+    /// Integers with up to 32 bits are stored as `i32`s, which are sign
+    /// or zero extended depending on the signedness.
+    pub fn val_type(self) -> ValType {
+        if self.width <= 32 {
+            ValType::I32
+        } else {
+            ValType::I64
+        }
+    }
+
+    pub fn min(self) -> i64 {
+        if self.signed {
+            i64::MIN >> (64 - self.width)
+        } else {
+            0
+        }
+    }
+
+    pub fn max(self) -> i64 {
+        if self.signed {
+            i64::MAX >> (64 - self.width)
+        } else {
+            (u64::MAX >> (64 - self.width)) as i64
+        }
+    }
+}
+
+/// Returns a string in the text format, that contains the bytes.
+pub fn wat_string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{:02x}", byte).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Translates the `main` function of the root module, every public
+/// function that isn't generic, and every function that is called
+/// by them, into a WebAssembly module in the text format, using the MIR
+/// that is optimized at `level`.
+pub fn generate(db: &dyn MirDatabase, level: OptLevel) -> Result<String, Locatable<Error>> {
+    let _profiler = profiler::trace("CodegenWasm", "Generate");
+
+    let tree = db.module_tree();
+    let root = tree.root();
+    let main = mono::find_main(db).ok();
+
+    let mut cx = Codegen::new(db);
+    let mut exports = vec![];
+    if let Some(main) = main {
+        exports.push(("main".to_string(), main));
+    }
+    let prelude = db.source_root().prelude();
+    for file in tree.files().filter(|&file| Some(file) != prelude) {
+        for &item in &db.file_scope(file).items {
+            let data = db.lookup_intern_item(item);
+            let is_free_fn = match &data.kind {
+                ItemKind::Def(def) => def.generics.is_empty(),
+                _ => false,
+            };
+            if data.vis == Visibility::Public
+                && is_free_fn
+                && scope::parent_item(db, item).is_none()
+                && Some(item) != main
+            {
+                let name = data.kind.name().unwrap().id;
+                let mut path = mono::module_path(db, file);
+                path.push(db.rodeo().resolve(&name).to_string());
+                exports.push((path.join("."), item));
+            }
+        }
+    }
+    if exports.is_empty() {
+        return Err(Locatable::new(
+            RuntimeError::NoMain.into(),
+            root,
+            Span::default(),
+        ));
+    }
+
+    let roots = exports
+        .iter()
+        .map(|&(_, item)| Instance::new(item, vec![]))
+        .collect::<Vec<_>>();
+    let instances = mono::collect(db, &roots, level)?;
+    for instance in &instances {
+        let name = format!("${}", instance.symbol(db));
+        cx.names.insert(name.clone());
+        cx.instances.insert(instance.clone(), name);
+    }
+    for (name, root) in exports.into_iter().zip(&roots) {
+        let func = cx.instance(root);
+        cx.exports.push((name.0, func));
+    }
+    for instance in &instances {
+        let body = db.optimized_mir(instance.def, level).unwrap();
+        let name = cx.instance(instance);
+        let display = instance.display(db);
+        let function = FnCodegen::new(&mut cx, &body, &instance.args).generate(&name, &display);
+        cx.functions.push_str(&function);
+    }
+    runtime::generate(&mut cx);
+    if let Some(err) = cx.error {
+        return Err(err);
+    }
+
+    Ok(cx.finish())
+}
+
+pub struct Codegen<'db> {
+    pub db: &'db dyn MirDatabase,
+    /// Every name that is defined at the top level of the module.
+    names: HashSet<String>,
+    /// The mangled name of every function instance.
+    instances: HashMap<Instance, String>,
+    /// The initial content of the memory, starting at [`DATA_START`].
+    ///
+    /// [`DATA_START`]: ./constant.DATA_START.html
+    data: Vec<u8>,
+    /// The address of every string, which stores its length
+    /// as an `u32`, followed by its bytes.
+    strings: HashMap<String, u32>,
+    statics: HashMap<ItemId, u32>,
+    eq_fns: HashMap<Ty, String>,
+    print_fns: HashMap<Ty, String>,
+    /// The helper functions for integer arithmetic that were already generated.
+    int_fns: HashSet<String>,
+    pub helpers: String,
+    functions: String,
+    /// The exported name and the function of every export.
+    exports: Vec<(String, String)>,
+    /// The first error that happened while generating the code.
+    error: Option<Locatable<Error>>,
+}
+
+impl<'db> Codegen<'db> {
+    fn new(db: &'db dyn MirDatabase) -> Self {
+        Self {
+            db,
+            names: HashSet::new(),
+            instances: HashMap::new(),
+            data: vec![],
+            strings: HashMap::new(),
+            statics: HashMap::new(),
+            eq_fns: HashMap::new(),
+            print_fns: HashMap::new(),
+            int_fns: HashSet::new(),
+            helpers: String::new(),
+            functions: String::new(),
+            exports: vec![],
+            error: None,
+        }
+    }
+
+    /// Puts the whole module together.
+    fn finish(self) -> String {
+        let stack_base = align_to(DATA_START + self.data.len() as u32, 16);
+        let stack_top = stack_base + STACK_SIZE;
+        let pages = stack_top.div_ceil(PAGE_SIZE);
+
+        let mut out = String::from("(module\n");
+        out.push_str(runtime::IMPORTS);
+        writeln!(out, "  (memory (export \"memory\") {})", pages).unwrap();
+        writeln!(out, "  (global $sp (mut i32) (i32.const {}))", stack_top).unwrap();
+        writeln!(out, "  (global $stack_base i32 (i32.const {}))", stack_base).unwrap();
+        writeln!(out, "  (global $heap (mut i32) (i32.const {}))", stack_top).unwrap();
+        if !self.data.is_empty() {
+            writeln!(
+                out,
+                "  (data (i32.const {}) {})",
+                DATA_START,
+                wat_string(&self.data)
+            )
+            .unwrap();
+        }
+        out.push('\n');
+        out.push_str(&self.helpers);
+        out.push_str(&self.functions);
+        for (name, func) in &self.exports {
+            writeln!(
+                out,
+                "  (export {} (func {}))",
+                wat_string(name.as_bytes()),
+                func
+            )
+            .unwrap();
+        }
+        out.push_str(")\n");
+        out
+    }
+
+    /// Records an error, if there wasn't one before.
+    pub fn error(&mut self, err: Locatable<Error>) {
+        self.error.get_or_insert(err);
+    }
+
+    /// Reports types that can't be used by this backend,
+    /// and returns `false` if the type is one of them.
+    pub fn check_ty(&mut self, ty: Ty, file: FileId, span: Span) -> bool {
+        if contains_int128(self.db, ty, &mut HashSet::new()) {
+            let err = CodegenError::Unsupported {
+                what: "128-bit integers".to_string(),
+                backend: "WebAssembly".to_string(),
+            };
+            self.error(Locatable::new(err.into(), file, span));
+            return false;
+        }
+        true
+    }
+
+    /// Returns an unused identifier, that starts with the prefix
+    /// and is derived from the name.
+    pub fn global_name(&mut self, prefix: &str, name: &str) -> String {
+        let mut ident = format!("${}_", prefix);
+        for c in name.chars() {
+            if c.is_ascii_alphanumeric() {
+                ident.push(c);
+            } else if !ident.ends_with('_') {
+                ident.push('_');
+            }
+        }
+        let ident = ident.trim_end_matches('_').to_string();
+
+        let mut unique = ident.clone();
+        let mut idx = 1;
+        while !self.names.insert(unique.clone()) {
+            unique = format!("{}_{}", ident, idx);
+            idx += 1;
+        }
+        unique
+    }
+
+    /// Returns the name of the function of an instance.
+    pub fn instance(&self, instance: &Instance) -> String {
+        self.instances[instance].clone()
+    }
+
+    /// Returns the layout of a type in linear memory.
+    pub fn layout(&self, ty: Ty) -> Arc<Layout> {
+        self.db.layout_of(ty, Target::WASM32)
+    }
+
+    /// Returns the instructions that turn the address of a
+    /// union into the index of its variant.
+    pub fn discriminant(&self, layout: &Layout, offset: u32) -> Vec<String> {
+        match &layout.variants {
+            Variants::Tagged { tag_size, .. } => {
+                let load = match tag_size {
+                    1 => "i32.load8_u",
+                    2 => "i32.load16_u",
+                    _ => "i32.load",
+                };
+                vec![format!("{} offset={}", load, offset)]
+            }
+            Variants::Niche {
+                dataful,
+                niche_variant,
+                niche,
+            } => vec![
+                format!("i32.load offset={}", offset + niche.offset),
+                "if (result i32)".to_string(),
+                format!("  i32.const {}", dataful),
+                "else".to_string(),
+                format!("  i32.const {}", niche_variant),
+                "end".to_string(),
+            ],
+            Variants::Single => panic!("read the discriminant of a type that isn't a union"),
+        }
+    }
+
+    pub fn repr(&mut self, ty: Ty) -> Repr {
+        match ty.kind(self.db) {
+            TyKind::Int { width, signed } => Repr::Scalar(IntTy { width, signed }.val_type()),
+            TyKind::Float { width: 32 } => Repr::Scalar(ValType::F32),
+            TyKind::Float { .. } => Repr::Scalar(ValType::F64),
+            TyKind::Bool | TyKind::Char | TyKind::String | TyKind::Pointer(_) => {
+                Repr::Scalar(ValType::I32)
+            }
+            _ if self.layout(ty).size == 0 => Repr::Zst,
+            _ => Repr::Memory,
+        }
+    }
+
+    /// Returns the instruction that loads a scalar of the type.
+    pub fn load(&self, ty: Ty) -> &'static str {
+        match ty.kind(self.db) {
+            TyKind::Int { width: 8, signed } => {
+                if signed {
+                    "i32.load8_s"
+                } else {
+                    "i32.load8_u"
+                }
+            }
+            TyKind::Int { width: 16, signed } => {
+                if signed {
+                    "i32.load16_s"
+                } else {
+                    "i32.load16_u"
+                }
+            }
+            TyKind::Int { width: 64, .. } => "i64.load",
+            TyKind::Float { width: 32 } => "f32.load",
+            TyKind::Float { .. } => "f64.load",
+            TyKind::Bool => "i32.load8_u",
+            _ => "i32.load",
+        }
+    }
+
+    /// Returns the instruction that stores a scalar of the type.
+    pub fn store(&self, ty: Ty) -> &'static str {
+        match ty.kind(self.db) {
+            TyKind::Int { width: 8, .. } | TyKind::Bool => "i32.store8",
+            TyKind::Int { width: 16, .. } => "i32.store16",
+            TyKind::Int { width: 64, .. } => "i64.store",
+            TyKind::Float { width: 32 } => "f32.store",
+            TyKind::Float { .. } => "f64.store",
+            _ => "i32.store",
+        }
+    }
+
+    /// Appends bytes to the data, and returns their address.
+    fn push_data(&mut self, bytes: &[u8], align: u32) -> u32 {
+        let len = align_to(self.data.len() as u32, align);
+        self.data.resize(len as usize, 0);
+        let addr = DATA_START + len;
+        self.data.extend_from_slice(bytes);
+        addr
+    }
+
+    /// Returns the address of a string.
+    pub fn string(&mut self, val: &str) -> u32 {
+        if let Some(&addr) = self.strings.get(val) {
+            return addr;
+        }
+        let mut bytes = (val.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(val.as_bytes());
+        let addr = self.push_data(&bytes, 4);
+        self.strings.insert(val.to_string(), addr);
+        addr
+    }
+
+    /// Returns the address of a `static`.
+    pub fn static_addr(&mut self, item: ItemId) -> u32 {
+        if let Some(&addr) = self.statics.get(&item) {
+            return addr;
+        }
+
+        let db = self.db;
+        let data = db.lookup_intern_item(item);
+        let ty = db.global_ty(item);
+        self.check_ty(ty, data.file, data.span);
+        let addr = self.constant_data(ty, |cx, addr| match db.const_value(item) {
+            Ok(val) => cx.write_const(addr, &val, ty),
+            Err(err) => cx.error(err),
+        });
+        self.statics.insert(item, addr);
+        addr
+    }
+
+    /// Reserves memory for a value of the type, which is initialized using `init`.
+    pub fn constant_data(&mut self, ty: Ty, init: impl FnOnce(&mut Self, u32)) -> u32 {
+        let layout = self.layout(ty);
+        let addr = self.push_data(&vec![0; layout.size as usize], layout.align);
+        init(self, addr);
+        addr
+    }
+
+    /// Writes a constant value into the data at the address.
+    pub fn write_const(&mut self, addr: u32, val: &ConstValue, ty: Ty) {
+        let db = self.db;
+        let bytes = match val {
+            ConstValue::Int(int) => {
+                let bytes = int.as_u128().to_le_bytes();
+                bytes[..int.width() as usize / 8].to_vec()
+            }
+            ConstValue::Float { val, width: 32 } => {
+                (val.into_inner() as f32).to_le_bytes().to_vec()
+            }
+            ConstValue::Float { val, .. } => val.into_inner().to_le_bytes().to_vec(),
+            ConstValue::Bool(val) => vec![*val as u8],
+            ConstValue::Char(val) => (*val as u32).to_le_bytes().to_vec(),
+            ConstValue::String(val) => self.string(val).to_le_bytes().to_vec(),
+            ConstValue::Unit => vec![],
+            ConstValue::Tuple(vals) => {
+                let layout = self.layout(ty);
+                for (idx, val) in vals.iter().enumerate() {
+                    let field_ty = field_ty(db, ty, idx as u32);
+                    self.write_const(addr + layout.fields[idx], val, field_ty);
+                }
+                return;
+            }
+            ConstValue::NamedTuple(vals) => {
+                let fields = match ty.kind(db) {
+                    TyKind::NamedTuple(fields) => {
+                        fields.into_iter().map(|(name, _)| name).collect()
+                    }
+                    TyKind::Adt(item, _) => match &*db.adt_def(item) {
+                        AdtDef::Struct { fields } => fields.iter().map(|(name, _)| *name).collect(),
+                        AdtDef::Union { .. } => vec![],
+                    },
+                    _ => vec![],
+                };
+                let layout = self.layout(ty);
+                for (field, val) in vals {
+                    if let Some(idx) = fields.iter().position(|name| name == field) {
+                        let field_ty = field_ty(db, ty, idx as u32);
+                        self.write_const(addr + layout.fields[idx], val, field_ty);
+                    }
+                }
+                return;
+            }
+        };
+        let start = (addr - DATA_START) as usize;
+        self.data[start..start + bytes.len()].copy_from_slice(&bytes);
+    }
+
+    /// Adds a function to the helpers, unless a function with the name already exists.
+    ///
+    /// `define` is only called if the function is new.
+    fn helper(&mut self, name: String, define: impl FnOnce(&mut Self) -> String) -> String {
+        if self.int_fns.insert(name.clone()) {
+            let func = define(self);
+            self.helpers.push_str(&func);
+        }
+        name
+    }
+
+    /// Returns the helper that applies the checked operator to two integers.
+    ///
+    /// The helpers take both operands and the address of the location, that
+    /// is reported on errors. Shifts take the amount as an `i64`.
+    pub fn int_fn(&mut self, op: &'static str, int: IntTy) -> String {
+        let name = format!("${}_{}", op, int.name());
+        self.helper(name.clone(), |cx| {
+            let vt = int.val_type().name();
+            let overflow = cx.trap(
+                &format!("this arithmetic operation will overflow `{}`", int.name()),
+                "local.get $loc",
+                "i64.const 0",
+            );
+            let mut locals = vec![];
+            let mut code = vec![];
+            let params = match op {
+                "neg" => format!("(param $a {}) (param $loc i32)", vt),
+                "shl" | "shr" => format!("(param $a {}) (param $b i64) (param $loc i32)", vt),
+                _ => format!("(param $a {0}) (param $b {0}) (param $loc i32)", vt),
+            };
+            let if_trap = |code: &mut Vec<String>, trap: &[String]| {
+                code.push("if".to_string());
+                code.extend(trap.iter().map(|instr| format!("  {}", instr)));
+                code.push("end".to_string());
+            };
+
+            match op {
+                "add" | "sub" | "mul" if int.width <= 32 => {
+                    // compute the exact result as an `i64`
+                    let extend = if int.signed {
+                        "i64.extend_i32_s"
+                    } else {
+                        "i64.extend_i32_u"
+                    };
+                    locals.push("(local $r i64)");
+                    code.extend(vec![
+                        "local.get $a".to_string(),
+                        extend.to_string(),
+                        "local.get $b".to_string(),
+                        extend.to_string(),
+                        format!("i64.{}", op),
+                        "local.tee $r".to_string(),
+                    ]);
+                    if int.signed {
+                        code.extend(vec![
+                            format!("i64.const {}", int.min()),
+                            "i64.lt_s".to_string(),
+                            "local.get $r".to_string(),
+                            format!("i64.const {}", int.max()),
+                            "i64.gt_s".to_string(),
+                            "i32.or".to_string(),
+                        ]);
+                    } else {
+                        code.extend(vec![
+                            format!("i64.const {}", int.max()),
+                            "i64.gt_u".to_string(),
+                        ]);
+                    }
+                    if_trap(&mut code, &overflow);
+                    code.extend(vec!["local.get $r".to_string(), "i32.wrap_i64".to_string()]);
+                }
+                "add" | "sub" => {
+                    locals.push("(local $r i64)");
+                    code.extend(vec![
+                        "local.get $a".to_string(),
+                        "local.get $b".to_string(),
+                        format!("i64.{}", op),
+                        "local.set $r".to_string(),
+                    ]);
+                    let check: &[&str] = match (op, int.signed) {
+                        // the sign of the result differs from the signs of both operands
+                        ("add", true) => &[
+                            "local.get $a",
+                            "local.get $r",
+                            "i64.xor",
+                            "local.get $b",
+                            "local.get $r",
+                            "i64.xor",
+                            "i64.and",
+                            "i64.const 0",
+                            "i64.lt_s",
+                        ],
+                        ("sub", true) => &[
+                            "local.get $a",
+                            "local.get $b",
+                            "i64.xor",
+                            "local.get $a",
+                            "local.get $r",
+                            "i64.xor",
+                            "i64.and",
+                            "i64.const 0",
+                            "i64.lt_s",
+                        ],
+                        ("add", false) => &["local.get $r", "local.get $a", "i64.lt_u"],
+                        _ => &["local.get $a", "local.get $b", "i64.lt_u"],
+                    };
+                    code.extend(check.iter().map(|instr| instr.to_string()));
+                    if_trap(&mut code, &overflow);
+                    code.push("local.get $r".to_string());
+                }
+                "mul" => {
+                    // the multiplication overflowed if dividing the result
+                    // by one operand doesn't return the other one
+                    locals.push("(local $r i64)");
+                    let div = if int.signed { "i64.div_s" } else { "i64.div_u" };
+                    code.extend(vec![
+                        "local.get $a".to_string(),
+                        "local.get $b".to_string(),
+                        "i64.mul".to_string(),
+                        "local.set $r".to_string(),
+                        "local.get $a".to_string(),
+                        "i64.eqz".to_string(),
+                        "if (result i32)".to_string(),
+                        "  i32.const 0".to_string(),
+                        "else".to_string(),
+                    ]);
+                    if int.signed {
+                        // `-1 * MIN` overflows, but `MIN / -1` would trap
+                        code.extend(vec![
+                            "  local.get $a".to_string(),
+                            "  i64.const -1".to_string(),
+                            "  i64.eq".to_string(),
+                            "  local.get $b".to_string(),
+                            format!("  i64.const {}", int.min()),
+                            "  i64.eq".to_string(),
+                            "  i32.and".to_string(),
+                            "  if (result i32)".to_string(),
+                            "    i32.const 1".to_string(),
+                            "  else".to_string(),
+                        ]);
+                    }
+                    code.extend(vec![
+                        "  local.get $r".to_string(),
+                        "  local.get $a".to_string(),
+                        format!("  {}", div),
+                        "  local.get $b".to_string(),
+                        "  i64.ne".to_string(),
+                    ]);
+                    if int.signed {
+                        code.push("  end".to_string());
+                    }
+                    code.push("end".to_string());
+                    if_trap(&mut code, &overflow);
+                    code.push("local.get $r".to_string());
+                }
+                "div" | "rem" => {
+                    let message = if op == "div" {
+                        "attempt to divide by zero"
+                    } else {
+                        "attempt to calculate the remainder with a divisor of zero"
+                    };
+                    let zero = cx.trap(message, "local.get $loc", "i64.const 0");
+                    code.extend(vec!["local.get $b".to_string(), format!("{}.eqz", vt)]);
+                    if_trap(&mut code, &zero);
+                    // `MIN % -1` is zero, which is also what `rem_s` returns
+                    if int.signed && op == "div" {
+                        code.extend(vec![
+                            "local.get $a".to_string(),
+                            format!("{}.const {}", vt, int.min()),
+                            format!("{}.eq", vt),
+                            "local.get $b".to_string(),
+                            format!("{}.const -1", vt),
+                            format!("{}.eq", vt),
+                            "i32.and".to_string(),
+                        ]);
+                        if_trap(&mut code, &overflow);
+                    }
+                    code.extend(vec![
+                        "local.get $a".to_string(),
+                        "local.get $b".to_string(),
+                        format!("{}.{}_{}", vt, op, if int.signed { 's' } else { 'u' }),
+                    ]);
+                }
+                "pow" => {
+                    let mul = cx.int_fn("mul", int);
+                    let negative = cx.trap(
+                        "attempt to raise an integer to a negative power",
+                        "local.get $loc",
+                        "i64.const 0",
+                    );
+                    locals.push(if int.width <= 32 {
+                        "(local $acc i32)"
+                    } else {
+                        "(local $acc i64)"
+                    });
+                    if int.signed {
+                        code.extend(vec![
+                            "local.get $b".to_string(),
+                            format!("{}.const 0", vt),
+                            format!("{}.lt_s", vt),
+                        ]);
+                        if_trap(&mut code, &negative);
+                    }
+                    let call = |a: &str, b: &str| {
+                        vec![
+                            format!("local.get {}", a),
+                            format!("local.get {}", b),
+                            "local.get $loc".to_string(),
+                            format!("call {}", mul),
+                        ]
+                    };
+                    code.extend(vec![
+                        "local.get $b".to_string(),
+                        format!("{}.eqz", vt),
+                        "if".to_string(),
+                        format!("  {}.const 1", vt),
+                        "  return".to_string(),
+                        "end".to_string(),
+                        format!("{}.const 1", vt),
+                        "local.set $acc".to_string(),
+                        "block $done".to_string(),
+                        "  loop $next".to_string(),
+                        "    local.get $b".to_string(),
+                        format!("    {}.const 1", vt),
+                        format!("    {}.le_u", vt),
+                        "    br_if $done".to_string(),
+                        "    local.get $b".to_string(),
+                        format!("    {}.const 1", vt),
+                        format!("    {}.and", vt),
+                        format!("    {}.eqz", vt),
+                        "    i32.eqz".to_string(),
+                        "    if".to_string(),
+                    ]);
+                    code.extend(
+                        call("$acc", "$a")
+                            .into_iter()
+                            .map(|i| format!("      {}", i)),
+                    );
+                    code.push("      local.set $acc".to_string());
+                    code.push("    end".to_string());
+                    code.extend(vec![
+                        "    local.get $b".to_string(),
+                        format!("    {}.const 1", vt),
+                        format!("    {}.shr_u", vt),
+                        "    local.set $b".to_string(),
+                    ]);
+                    code.extend(call("$a", "$a").into_iter().map(|i| format!("    {}", i)));
+                    code.extend(vec![
+                        "    local.set $a".to_string(),
+                        "    br $next".to_string(),
+                        "  end".to_string(),
+                        "end".to_string(),
+                    ]);
+                    code.extend(call("$acc", "$a"));
+                }
+                "neg" => {
+                    if int.signed {
+                        code.extend(vec![
+                            "local.get $a".to_string(),
+                            format!("{}.const {}", vt, int.min()),
+                            format!("{}.eq", vt),
+                        ]);
+                    } else {
+                        code.extend(vec![
+                            "local.get $a".to_string(),
+                            format!("{}.eqz", vt),
+                            "i32.eqz".to_string(),
+                        ]);
+                    }
+                    if_trap(&mut code, &overflow);
+                    code.extend(vec![
+                        format!("{}.const 0", vt),
+                        "local.get $a".to_string(),
+                        format!("{}.sub", vt),
+                    ]);
+                }
+                _ => {
+                    let too_large = cx.trap(
+                        &format!(
+                            "attempt to shift `{}` by `{{}}`, which is too large",
+                            int.name()
+                        ),
+                        "local.get $loc",
+                        "local.get $b",
+                    );
+                    code.extend(vec![
+                        "local.get $b".to_string(),
+                        "i64.const 0".to_string(),
+                        "i64.lt_s".to_string(),
+                        "local.get $b".to_string(),
+                        format!("i64.const {}", int.width),
+                        "i64.ge_s".to_string(),
+                        "i32.or".to_string(),
+                    ]);
+                    if_trap(&mut code, &too_large);
+                    code.push("local.get $a".to_string());
+                    code.push("local.get $b".to_string());
+                    if int.width <= 32 {
+                        code.push("i32.wrap_i64".to_string());
+                    }
+                    let shift = match (op, int.signed) {
+                        ("shl", _) => "shl",
+                        (_, true) => "shr_s",
+                        _ => "shr_u",
+                    };
+                    code.push(format!("{}.{}", vt, shift));
+                    if op == "shl" {
+                        code.extend(cx.normalize(int));
+                    }
+                }
+            }
+
+            let mut func = format!("  (func {} {} (result {})\n", name, params, vt);
+            for local in locals {
+                writeln!(func, "    {}", local).unwrap();
+            }
+            for instr in code {
+                writeln!(func, "    {}", instr).unwrap();
+            }
+            func.push_str("  )\n\n");
+            func
+        })
+    }
+
+    /// Returns the instructions that truncate the result of an operation
+    /// on an integer with less than 32 bits to its width.
+    pub fn normalize(&self, int: IntTy) -> Vec<String> {
+        match int.width {
+            8 | 16 if int.signed => vec![
+                format!("i32.const {}", 32 - int.width),
+                "i32.shl".to_string(),
+                format!("i32.const {}", 32 - int.width),
+                "i32.shr_s".to_string(),
+            ],
+            8 | 16 => vec![format!("i32.const {}", int.max()), "i32.and".to_string()],
+            _ => vec![],
+        }
+    }
+
+    /// Returns the instructions that compare two values of the type,
+    /// which are on the stack, and push `1` if they are equal.
+    pub fn equal(&mut self, ty: Ty) -> Vec<String> {
+        match self.repr(ty) {
+            Repr::Zst => vec!["i32.const 1".to_string()],
+            Repr::Scalar(_) if ty.kind(self.db) == TyKind::String => {
+                vec!["call $cell_string_eq".to_string()]
+            }
+            Repr::Scalar(vt) => vec![format!("{}.eq", vt.name())],
+            Repr::Memory => vec![format!("call {}", self.eq_fn(ty))],
+        }
+    }
+
+    /// Returns the function that compares two values of a type that
+    /// is stored in memory, using their addresses.
+    fn eq_fn(&mut self, ty: Ty) -> String {
+        if let Some(name) = self.eq_fns.get(&ty) {
+            return name.clone();
+        }
+
+        let db = self.db;
+        let name = self.global_name("eq", &ty.display(db));
+        self.eq_fns.insert(ty, name.clone());
+
+        let layout = self.layout(ty);
+        let mut code = vec![];
+        match ty.kind(db) {
+            TyKind::Adt(item, _) if matches!(&*db.adt_def(item), AdtDef::Union { .. }) => {
+                let variants = match &*db.adt_def(item) {
+                    AdtDef::Union { variants } => variants.len(),
+                    AdtDef::Struct { .. } => unreachable!(),
+                };
+                code.push("local.get $a".to_string());
+                code.extend(self.discriminant(&layout, 0));
+                code.push("local.get $b".to_string());
+                code.extend(self.discriminant(&layout, 0));
+                code.extend(vec![
+                    "i32.ne".to_string(),
+                    "if".to_string(),
+                    "  i32.const 0".to_string(),
+                    "  return".to_string(),
+                    "end".to_string(),
+                ]);
+                for idx in 0..variants {
+                    let data = cell_mir::body::variant_ty(db, ty, idx as u32);
+                    if self.repr(data) == Repr::Zst {
+                        continue;
+                    }
+                    code.push("local.get $a".to_string());
+                    code.extend(self.discriminant(&layout, 0));
+                    code.extend(vec![
+                        format!("i32.const {}", idx),
+                        "i32.eq".to_string(),
+                        "if".to_string(),
+                    ]);
+                    let compare = self.compare_fields(data, layout.data_offset());
+                    code.extend(compare.into_iter().map(|instr| format!("  {}", instr)));
+                    code.extend(vec!["  return".to_string(), "end".to_string()]);
+                }
+                code.push("i32.const 1".to_string());
+            }
+            _ => {
+                code.push("i32.const 1".to_string());
+                for (idx, &offset) in layout.fields.iter().enumerate() {
+                    let field = field_ty(db, ty, idx as u32);
+                    code.extend(self.compare_fields(field, offset));
+                    code.push("i32.and".to_string());
+                }
+            }
+        }
+
+        let mut func = format!(
+            "  (func {} (param $a i32) (param $b i32) (result i32)\n",
+            name
+        );
+        for instr in code {
+            writeln!(func, "    {}", instr).unwrap();
+        }
+        func.push_str("  )\n\n");
+        self.helpers.push_str(&func);
+        name
+    }
+
+    /// Returns the instructions that compare the values of the type at
+    /// `offset` behind the addresses `$a` and `$b`.
+    fn compare_fields(&mut self, ty: Ty, offset: u32) -> Vec<String> {
+        let mut code = vec![];
+        for base in &["$a", "$b"] {
+            code.push(format!("local.get {}", base));
+            code.extend(self.field_value(ty, offset));
+        }
+        code.extend(self.equal(ty));
+        code
+    }
+
+    /// Returns the instructions that turn the address on the stack into the
+    /// value of the type at `offset` behind it, which is an address itself
+    /// if the value is stored in memory.
+    fn field_value(&mut self, ty: Ty, offset: u32) -> Vec<String> {
+        match self.repr(ty) {
+            Repr::Zst => vec!["drop".to_string()],
+            Repr::Scalar(_) => vec![format!("{} offset={}", self.load(ty), offset)],
+            Repr::Memory if offset == 0 => vec![],
+            Repr::Memory => vec![format!("i32.const {}", offset), "i32.add".to_string()],
+        }
+    }
+
+    /// Returns the instructions that print the value of the type, which is on the stack.
+    ///
+    /// Strings and chars are quoted if they are `nested` inside of another value.
+    pub fn print(&mut self, ty: Ty, nested: bool) -> Vec<String> {
+        let db = self.db;
+        let call = |func: &str| format!("call {}", func);
+        match ty.kind(db) {
+            TyKind::Int { width, signed } => {
+                let mut code = vec![];
+                if width <= 32 {
+                    code.push(
+                        if signed {
+                            "i64.extend_i32_s"
+                        } else {
+                            "i64.extend_i32_u"
+                        }
+                        .to_string(),
+                    );
+                }
+                code.push(format!("i32.const {}", signed as u8));
+                code.push(call("$cell_print_int"));
+                code
+            }
+            TyKind::Float { width } => {
+                let mut code = vec![];
+                if width == 32 {
+                    code.push("f64.promote_f32".to_string());
+                }
+                code.push(call("$cell_print_float"));
+                code
+            }
+            TyKind::Bool => vec![call("$cell_print_bool")],
+            TyKind::Char => vec![
+                format!("i32.const {}", nested as u8),
+                call("$cell_print_char"),
+            ],
+            TyKind::String => vec![
+                format!("i32.const {}", nested as u8),
+                call("$cell_print_string"),
+            ],
+            TyKind::Pointer(_) => vec![call("$cell_print_pointer")],
+            TyKind::Unit => self.print_text("()"),
+            _ => match self.repr(ty) {
+                Repr::Memory => vec![call(&self.print_fn(ty))],
+                // zero-sized values have no address, but their fields aren't read anyway
+                _ => vec!["i32.const 0".to_string(), call(&self.print_fn(ty))],
+            },
+        }
+    }
+
+    /// Returns the instructions that print a constant text.
+    pub fn print_text(&mut self, text: &str) -> Vec<String> {
+        let addr = self.string(text);
+        vec![
+            format!("i32.const {}", addr),
+            "i32.const 0".to_string(),
+            "call $cell_print_string".to_string(),
+        ]
+    }
+
+    /// Returns the function that prints a value of a type that is stored
+    /// in memory, using its address.
+    fn print_fn(&mut self, ty: Ty) -> String {
+        if let Some(name) = self.print_fns.get(&ty) {
+            return name.clone();
+        }
+
+        let db = self.db;
+        let name = self.global_name("print", &ty.display(db));
+        self.print_fns.insert(ty, name.clone());
+
+        let layout = self.layout(ty);
+        let resolve = |name| db.rodeo().resolve(&name).to_string();
+        let mut code = vec![];
+        match ty.kind(db) {
+            TyKind::Adt(item, _) if matches!(&*db.adt_def(item), AdtDef::Union { .. }) => {
+                let variants = match &*db.adt_def(item) {
+                    AdtDef::Union { variants } => variants.clone(),
+                    AdtDef::Struct { .. } => unreachable!(),
+                };
+                let union = resolve(db.lookup_intern_item(item).kind.name().unwrap().id);
+                for (idx, (variant, data)) in variants.into_iter().enumerate() {
+                    code.push("local.get $val".to_string());
+                    code.extend(self.discriminant(&layout, 0));
+                    code.extend(vec![
+                        format!("i32.const {}", idx),
+                        "i32.eq".to_string(),
+                        "if".to_string(),
+                    ]);
+                    let mut body = self.print_text(&format!("{}:{}", union, resolve(variant)));
+                    if data.is_some() {
+                        let data = cell_mir::body::variant_ty(db, ty, idx as u32);
+                        body.extend(self.print_text("("));
+                        body.push("local.get $val".to_string());
+                        body.extend(self.field_value(data, layout.data_offset()));
+                        body.extend(self.print(data, true));
+                        body.extend(self.print_text(")"));
+                    }
+                    code.extend(body.into_iter().map(|instr| format!("  {}", instr)));
+                    code.push("end".to_string());
+                }
+            }
+            kind => {
+                // named tuples and `type`s print the names of their fields
+                let (mut text, names) = match kind {
+                    TyKind::NamedTuple(fields) => (
+                        "{".to_string(),
+                        fields.into_iter().map(|(field, _)| Some(field)).collect(),
+                    ),
+                    TyKind::Adt(item, _) => match &*db.adt_def(item) {
+                        AdtDef::Struct { fields } => {
+                            let name = db.lookup_intern_item(item).kind.name().unwrap().id;
+                            (
+                                format!("{} {{", resolve(name)),
+                                fields.iter().map(|(field, _)| Some(*field)).collect(),
+                            )
+                        }
+                        AdtDef::Union { .. } => unreachable!(),
+                    },
+                    _ => ("{".to_string(), vec![None; layout.fields.len()]),
+                };
+
+                for (idx, name) in names.into_iter().enumerate() {
+                    if idx > 0 {
+                        text.push_str(", ");
+                    }
+                    if let Some(name) = name {
+                        write!(text, "{}: ", resolve(name)).unwrap();
+                    }
+                    code.extend(self.print_text(&std::mem::take(&mut text)));
+                    let field = field_ty(db, ty, idx as u32);
+                    code.push("local.get $val".to_string());
+                    code.extend(self.field_value(field, layout.fields[idx]));
+                    code.extend(self.print(field, true));
+                }
+                text.push('}');
+                code.extend(self.print_text(&text));
+            }
+        }
+
+        let mut func = format!("  (func {} (param $val i32)\n", name);
+        for instr in code {
+            writeln!(func, "    {}", instr).unwrap();
+        }
+        func.push_str("  )\n\n");
+        self.helpers.push_str(&func);
+        name
+    }
+}
+
+/// Checks if the type contains a 128-bit integer.
+fn contains_int128(db: &dyn MirDatabase, ty: Ty, visited: &mut HashSet<Ty>) -> bool {
+    if !visited.insert(ty) {
+        return false;
+    }
+    match ty.kind(db) {
+        TyKind::Int { width, .. } => width == 128,
+        TyKind::Pointer(inner) => contains_int128(db, inner, visited),
+        TyKind::Tuple(tys) => tys.into_iter().any(|ty| contains_int128(db, ty, visited)),
+        TyKind::NamedTuple(fields) => fields
+            .into_iter()
+            .any(|(_, ty)| contains_int128(db, ty, visited)),
+        TyKind::Adt(item, args) => match &*db.adt_def(item) {
+            AdtDef::Struct { fields } => fields
+                .iter()
+                .any(|(_, ty)| contains_int128(db, ty.subst(db, &args), visited)),
+            AdtDef::Union { variants } => variants
+                .iter()
+                .filter_map(|(_, data)| *data)
+                .any(|ty| contains_int128(db, ty.subst(db, &args), visited)),
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,1030 @@
+//! Translates the body of a single function.
+//!
+//! Locals that hold a single scalar are WebAssembly locals, every other
+//! local lives in the stack frame of the function, which is allocated
+//! below `$sp`. Aggregates are passed by their address, and are copied
+//! into the frame of the callee. Aggregates are returned by writing them
+//! to the address that is passed as the hidden `$ret` parameter.
+//!
+//! The basic blocks are nested `block`s inside of a `loop`. Jumps to a later
+//! block branch out of the blocks in front of it, and jumps back store the
+//! target in `$bb` and branch to the `br_table` at the start of the loop.
+
+use crate::codegen::{Codegen, IntTy, Repr, ValType};
+use cell_common::source::{Encoding, FileId, Span};
+use cell_mir::{
+    body::{
+        field_ty, variant_ty, AggregateKind, BasicBlockData, BinOp, Body, Constant, Local, Operand,
+        Place, PlaceElem, Rvalue, StatementKind, TerminatorKind, UnOp,
+    },
+    layout::{align_to, Variants},
+    mono::Instance,
+};
+use cell_syntax::ast::ItemId;
+use cell_typeck::{
+    consteval::ConstValue,
+    prelude::{self, HostFn},
+    ty::{Ty, TyKind},
+};
+use std::{collections::HashSet, fmt::Write};
+
+/// Where the value of a local is stored.
+#[derive(Debug, Clone, Copy)]
+enum Storage {
+    /// Locals without any data.
+    None,
+    /// A WebAssembly local, which is named after the local.
+    Local(ValType),
+    /// A slot of the stack frame, at the offset from `$fp`.
+    Frame(u32),
+}
+
+/// Where a place is, after its address was computed.
+#[derive(Debug, Clone, Copy)]
+enum Loc {
+    Local(Local),
+    /// The address is on the stack, and the place is at the offset behind it.
+    Memory(u32),
+}
+
+pub struct FnCodegen<'a, 'db> {
+    cx: &'a mut Codegen<'db>,
+    body: &'a Body,
+    /// The generic arguments of this instance.
+    args: &'a [Ty],
+    /// The type of every local, with the generic arguments substituted.
+    tys: Vec<Ty>,
+    storage: Vec<Storage>,
+    frame_size: u32,
+    /// The offset of the memory, that is used to build aggregates.
+    temp: u32,
+    /// The file the function is defined in.
+    file: FileId,
+    code: Vec<String>,
+    indent: usize,
+    /// The type of every scratch local, and if it is used by the current statement.
+    scratch: Vec<(ValType, bool)>,
+    /// The basic block that is currently generated.
+    current: u32,
+    /// If the blocks are nested inside the dispatch loop.
+    dispatch: bool,
+}
+
+impl<'a, 'db> FnCodegen<'a, 'db> {
+    pub fn new(cx: &'a mut Codegen<'db>, body: &'a Body, args: &'a [Ty]) -> Self {
+        let db = cx.db;
+        let tys = body
+            .locals
+            .iter()
+            .map(|local| local.ty.subst(db, args))
+            .collect();
+        Self {
+            cx,
+            body,
+            args,
+            tys,
+            storage: vec![],
+            frame_size: 0,
+            temp: 0,
+            file: db.lookup_intern_item(body.item).file,
+            code: vec![],
+            indent: 0,
+            scratch: vec![],
+            current: 0,
+            dispatch: false,
+        }
+    }
+
+    /// Returns the function in the text format.
+    pub fn generate(mut self, name: &str, display: &str) -> String {
+        for (idx, local) in self.body.locals.iter().enumerate() {
+            if !self.cx.check_ty(self.tys[idx], self.file, local.span) {
+                return String::new();
+            }
+        }
+        // optimizations may replace a local with a constant of its type
+        for (_, block) in self.body.basic_blocks() {
+            for (span, constant) in constants(block) {
+                let ty = constant.ty.subst(self.cx.db, self.args);
+                if !self.cx.check_ty(ty, self.file, span) {
+                    return String::new();
+                }
+            }
+        }
+        self.allocate_frame();
+
+        let ret = self.repr(0);
+        let mut signature = format!("  (func {}", name);
+        if ret == Repr::Memory {
+            signature.push_str(" (param $ret i32)");
+        }
+        for arg in self.body.args() {
+            let idx = arg.index();
+            match (self.repr(idx), self.storage[idx]) {
+                (Repr::Zst, _) => {}
+                (Repr::Scalar(vt), Storage::Local(_)) => {
+                    write!(signature, " (param $l{} {})", idx, vt.name()).unwrap()
+                }
+                (Repr::Scalar(vt), _) => {
+                    write!(signature, " (param $p{} {})", idx, vt.name()).unwrap()
+                }
+                (Repr::Memory, _) => write!(signature, " (param $p{} i32)", idx).unwrap(),
+            }
+        }
+        if let Repr::Scalar(vt) = ret {
+            write!(signature, " (result {})", vt.name()).unwrap();
+        }
+
+        self.prologue(display);
+        let blocks = self.body.basic_blocks().count();
+        self.dispatch = blocks > 1
+            || !self.body.blocks[0]
+                .terminator()
+                .kind
+                .successors()
+                .is_empty();
+        if self.dispatch {
+            self.emit("loop $dispatch");
+            for idx in (0..blocks).rev() {
+                self.emit(format!("block $bb{}", idx));
+            }
+            self.emit("local.get $bb");
+            let labels = (0..blocks)
+                .map(|idx| format!("$bb{}", idx))
+                .collect::<Vec<_>>();
+            self.emit(format!("br_table {}", labels.join(" ")));
+        }
+        for (block, data) in self.body.basic_blocks() {
+            self.current = block.0;
+            if self.dispatch {
+                self.emit("end");
+            }
+            for statement in &data.statements {
+                self.release_scratch();
+                match &statement.kind {
+                    StatementKind::Assign(place, rvalue) => {
+                        self.assign(place, rvalue, statement.span)
+                    }
+                    StatementKind::Phi(..) => unreachable!("phi nodes are removed before codegen"),
+                    StatementKind::Nop => {}
+                }
+            }
+            self.release_scratch();
+            let terminator = data.terminator();
+            self.terminator(&terminator.kind, terminator.span);
+        }
+        if self.dispatch {
+            self.emit("end");
+            self.emit("unreachable");
+        }
+
+        let mut out = signature;
+        out.push('\n');
+        out.push_str("    (local $fp i32)\n");
+        if self.dispatch {
+            out.push_str("    (local $bb i32)\n");
+        }
+        for idx in self.body.arg_count + 1..self.tys.len() {
+            if let Storage::Local(vt) = self.storage[idx] {
+                writeln!(out, "    (local $l{} {})", idx, vt.name()).unwrap();
+            }
+        }
+        if let Storage::Local(vt) = self.storage[0] {
+            writeln!(out, "    (local $l0 {})", vt.name()).unwrap();
+        }
+        for (idx, (vt, _)) in self.scratch.iter().enumerate() {
+            writeln!(out, "    (local $s{} {})", idx, vt.name()).unwrap();
+        }
+        for instr in &self.code {
+            writeln!(out, "    {}", instr).unwrap();
+        }
+        out.push_str("  )\n\n");
+        out
+    }
+
+    /// Decides where every local is stored, and computes the size of the frame.
+    fn allocate_frame(&mut self) {
+        let db = self.cx.db;
+
+        // the address of scalars can only be taken if they are in memory
+        let mut addressed = HashSet::new();
+        let mut temp_size = 0;
+        for (_, data) in self.body.basic_blocks() {
+            for statement in &data.statements {
+                match &statement.kind {
+                    StatementKind::Assign(_, Rvalue::AddressOf(place))
+                        if !place
+                            .projection
+                            .iter()
+                            .any(|elem| matches!(elem, PlaceElem::Deref | PlaceElem::Index(_))) =>
+                    {
+                        addressed.insert(place.local);
+                    }
+                    StatementKind::Assign(_, rvalue @ Rvalue::Aggregate(..)) => {
+                        let ty = self.subst(rvalue.ty(db, self.body));
+                        temp_size = temp_size.max(self.cx.layout(ty).size);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut size = 0;
+        for idx in 0..self.tys.len() {
+            let storage = match self.repr(idx) {
+                Repr::Zst => Storage::None,
+                Repr::Scalar(vt) if !addressed.contains(&Local(idx as u32)) => Storage::Local(vt),
+                _ => {
+                    let layout = self.cx.layout(self.tys[idx]);
+                    size = align_to(size, layout.align);
+                    let offset = size;
+                    size += layout.size;
+                    Storage::Frame(offset)
+                }
+            };
+            self.storage.push(storage);
+        }
+        self.temp = align_to(size, 8);
+        // every frame takes some space, so infinite recursion is always detected
+        self.frame_size = align_to(self.temp + temp_size, 16).max(16);
+    }
+
+    /// Allocates the frame, and copies the arguments that are stored in memory into it.
+    fn prologue(&mut self, display: &str) {
+        let item = self.cx.db.lookup_intern_item(self.body.item);
+        let loc = self.location(item.span);
+        let trap = self.cx.trap(
+            &format!("stack overflow while calling `{}`", display),
+            &loc,
+            "i64.const 0",
+        );
+        self.emit("global.get $sp");
+        self.emit(format!("i32.const {}", self.frame_size));
+        self.emit("i32.sub");
+        self.emit("local.tee $fp");
+        self.emit("global.get $stack_base");
+        self.emit("i32.lt_s");
+        self.emit("if");
+        self.indent += 1;
+        for instr in trap {
+            self.emit(instr);
+        }
+        self.emit("unreachable");
+        self.indent -= 1;
+        self.emit("end");
+        self.emit("local.get $fp");
+        self.emit("global.set $sp");
+
+        for arg in self.body.args() {
+            let idx = arg.index();
+            let offset = match self.storage[idx] {
+                Storage::Frame(offset) => offset,
+                _ => continue,
+            };
+            let ty = self.tys[idx];
+            self.emit("local.get $fp");
+            match self.repr(idx) {
+                Repr::Memory => {
+                    let size = self.cx.layout(ty).size;
+                    self.emit(format!("i32.const {}", offset));
+                    self.emit("i32.add");
+                    self.emit(format!("local.get $p{}", idx));
+                    self.emit(format!("i32.const {}", size));
+                    self.emit("memory.copy");
+                }
+                _ => {
+                    self.emit(format!("local.get $p{}", idx));
+                    let store = self.cx.store(ty);
+                    self.emit(format!("{} offset={}", store, offset));
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self, instr: impl Into<String>) {
+        let instr = instr.into();
+        self.code
+            .push(format!("{}{}", "  ".repeat(self.indent), instr));
+    }
+
+    fn emit_all(&mut self, code: Vec<String>) {
+        for instr in code {
+            self.emit(instr);
+        }
+    }
+
+    fn subst(&self, ty: Ty) -> Ty {
+        ty.subst(self.cx.db, self.args)
+    }
+
+    fn operand_ty(&self, op: &Operand) -> Ty {
+        self.subst(op.ty(self.cx.db, self.body))
+    }
+
+    fn place_ty(&self, place: &Place) -> Ty {
+        self.subst(place.ty(self.cx.db, self.body))
+    }
+
+    fn repr(&mut self, local: usize) -> Repr {
+        self.cx.repr(self.tys[local])
+    }
+
+    /// Returns an unused scratch local of the type, which stays in use
+    /// until the current statement is finished.
+    fn scratch(&mut self, vt: ValType) -> String {
+        let idx = match self
+            .scratch
+            .iter()
+            .position(|&(ty, used)| ty == vt && !used)
+        {
+            Some(idx) => idx,
+            None => {
+                self.scratch.push((vt, false));
+                self.scratch.len() - 1
+            }
+        };
+        self.scratch[idx].1 = true;
+        format!("$s{}", idx)
+    }
+
+    fn release_scratch(&mut self) {
+        for (_, used) in &mut self.scratch {
+            *used = false;
+        }
+    }
+
+    /// Returns the instruction that pushes the address of a string with the
+    /// location of the span, which is used to report errors at runtime.
+    fn location(&mut self, span: Span) -> String {
+        let db = self.cx.db;
+        let pos = db.line_col(self.file, span.start(), Encoding::Char);
+        let loc = format!("{}:{}:{}", db.name(self.file), pos.line + 1, pos.col + 1);
+        format!("i32.const {}", self.cx.string(&loc))
+    }
+
+    /// Computes the address of the place, if it's stored in memory.
+    fn place(&mut self, place: &Place) -> Loc {
+        let db = self.cx.db;
+        let mut ty = self.tys[place.local.index()];
+        let mut loc = match self.storage[place.local.index()] {
+            Storage::Frame(offset) => {
+                self.emit("local.get $fp");
+                Loc::Memory(offset)
+            }
+            _ => Loc::Local(place.local),
+        };
+        for elem in &place.projection {
+            match *elem {
+                PlaceElem::Deref | PlaceElem::Index(_) => {
+                    self.load(loc, ty);
+                    ty = pointee(db, ty);
+                    if let PlaceElem::Index(idx) = *elem {
+                        self.local_value(idx);
+                        if self.cx.repr(self.tys[idx.index()]) == Repr::Scalar(ValType::I64) {
+                            self.emit("i32.wrap_i64");
+                        }
+                        let size = self.cx.layout(ty).size;
+                        self.emit(format!("i32.const {}", size));
+                        self.emit("i32.mul");
+                        self.emit("i32.add");
+                    }
+                    loc = Loc::Memory(0);
+                }
+                PlaceElem::Field(idx) | PlaceElem::Downcast(idx) => {
+                    let layout = self.cx.layout(ty);
+                    let offset = match elem {
+                        PlaceElem::Field(_) => layout.fields[idx as usize],
+                        _ => layout.data_offset(),
+                    };
+                    loc = match loc {
+                        Loc::Memory(base) => Loc::Memory(base + offset),
+                        Loc::Local(_) => panic!("projected a local that isn't stored in memory"),
+                    };
+                    ty = match elem {
+                        PlaceElem::Field(_) => field_ty(db, ty, idx),
+                        _ => variant_ty(db, ty, idx),
+                    };
+                }
+            }
+        }
+        loc
+    }
+
+    /// Pushes the address of a place that is stored in memory,
+    /// and returns the offset that has to be added to it.
+    fn address(&mut self, place: &Place) -> u32 {
+        match self.place(place) {
+            Loc::Memory(offset) => offset,
+            Loc::Local(_) => panic!("took the address of a local that isn't stored in memory"),
+        }
+    }
+
+    /// Pushes the full address of a place that is stored in memory.
+    fn address_of(&mut self, place: &Place) {
+        let offset = self.address(place);
+        if offset > 0 {
+            self.emit(format!("i32.const {}", offset));
+            self.emit("i32.add");
+        }
+    }
+
+    /// Pushes the scalar of the type that is stored at the location.
+    fn load(&mut self, loc: Loc, ty: Ty) {
+        match loc {
+            Loc::Local(local) => self.emit(format!("local.get $l{}", local.0)),
+            Loc::Memory(offset) => {
+                let load = self.cx.load(ty);
+                self.emit(format!("{} offset={}", load, offset));
+            }
+        }
+    }
+
+    /// Pushes the value of a local that stores a scalar.
+    fn local_value(&mut self, local: Local) {
+        let ty = self.tys[local.index()];
+        match self.storage[local.index()] {
+            Storage::None => {}
+            Storage::Local(_) => self.emit(format!("local.get $l{}", local.0)),
+            Storage::Frame(offset) => {
+                self.emit("local.get $fp");
+                self.load(Loc::Memory(offset), ty);
+            }
+        }
+    }
+
+    /// Stores the scalar that is pushed by `value` into the place.
+    fn write_scalar(&mut self, place: &Place, ty: Ty, value: impl FnOnce(&mut Self)) {
+        if let (true, Storage::Local(_)) = (
+            place.projection.is_empty(),
+            self.storage[place.local.index()],
+        ) {
+            value(self);
+            self.emit(format!("local.set $l{}", place.local.0));
+            return;
+        }
+
+        let offset = self.address(place);
+        value(self);
+        let store = self.cx.store(ty);
+        self.emit(format!("{} offset={}", store, offset));
+    }
+
+    /// Pushes the value of an operand, or its address if it's stored in memory.
+    fn operand(&mut self, op: &Operand) {
+        let ty = self.operand_ty(op);
+        match op {
+            Operand::Copy(place) => match self.cx.repr(ty) {
+                Repr::Zst => {}
+                Repr::Scalar(_) => {
+                    let loc = self.place(place);
+                    self.load(loc, ty);
+                }
+                Repr::Memory => self.address_of(place),
+            },
+            Operand::Constant(constant) => self.constant(&constant.val, ty),
+        }
+    }
+
+    fn constant(&mut self, val: &ConstValue, ty: Ty) {
+        let vt = match self.cx.repr(ty) {
+            Repr::Zst => return,
+            Repr::Scalar(vt) => vt.name(),
+            Repr::Memory => {
+                let addr = self
+                    .cx
+                    .constant_data(ty, |cx, addr| cx.write_const(addr, val, ty));
+                self.emit(format!("i32.const {}", addr));
+                return;
+            }
+        };
+        let val = match val {
+            ConstValue::Int(int) if int.is_signed() => int.as_i128().to_string(),
+            ConstValue::Int(int) => int.as_u128().to_string(),
+            ConstValue::Float { val, width } => float_literal(val.into_inner(), *width),
+            ConstValue::Bool(val) => (*val as u8).to_string(),
+            ConstValue::Char(val) => (*val as u32).to_string(),
+            ConstValue::String(val) => self.cx.string(val).to_string(),
+            _ => panic!("aggregate constant with a scalar type"),
+        };
+        self.emit(format!("{}.const {}", vt, val));
+    }
+
+    fn assign(&mut self, place: &Place, rvalue: &Rvalue, span: Span) {
+        let ty = self.place_ty(place);
+        match self.cx.repr(ty) {
+            Repr::Zst => {}
+            Repr::Scalar(_) => self.write_scalar(place, ty, |this| this.rvalue(rvalue, span)),
+            Repr::Memory => {
+                let dest = self.scratch(ValType::I32);
+                self.address_of(place);
+                self.emit(format!("local.set {}", dest));
+                match rvalue {
+                    Rvalue::Aggregate(kind, ops) => self.aggregate(&dest, ty, kind, ops),
+                    _ => {
+                        let size = self.cx.layout(ty).size;
+                        self.emit(format!("local.get {}", dest));
+                        self.rvalue(rvalue, span);
+                        self.emit(format!("i32.const {}", size));
+                        self.emit("memory.copy");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes an aggregate to the address inside `dest`.
+    fn aggregate(&mut self, dest: &str, ty: Ty, kind: &AggregateKind, ops: &[Operand]) {
+        let db = self.cx.db;
+        let layout = self.cx.layout(ty);
+
+        // operands that are read from memory could overlap with the destination,
+        // so the aggregate is built in the frame, and copied afterwards
+        let reads_memory = ops.iter().any(|op| match op {
+            Operand::Copy(place) => {
+                !place.projection.is_empty()
+                    || matches!(self.storage[place.local.index()], Storage::Frame(_))
+            }
+            Operand::Constant(_) => false,
+        });
+        let target = if reads_memory {
+            let target = self.scratch(ValType::I32);
+            self.emit("local.get $fp");
+            self.emit(format!("i32.const {}", self.temp));
+            self.emit("i32.add");
+            self.emit(format!("local.set {}", target));
+            target
+        } else {
+            dest.to_string()
+        };
+
+        match kind {
+            AggregateKind::Variant(_, idx, _) => {
+                match &layout.variants {
+                    Variants::Tagged { tag_size, .. } => {
+                        let store = match tag_size {
+                            1 => "i32.store8",
+                            2 => "i32.store16",
+                            _ => "i32.store",
+                        };
+                        self.emit(format!("local.get {}", target));
+                        self.emit(format!("i32.const {}", idx));
+                        self.emit(store);
+                    }
+                    // the other variant has data, which includes the niche
+                    Variants::Niche {
+                        niche_variant,
+                        niche,
+                        ..
+                    } if niche_variant == idx => {
+                        self.emit(format!("local.get {}", target));
+                        self.emit("i32.const 0");
+                        self.emit(format!("i32.store offset={}", niche.offset));
+                    }
+                    _ => {}
+                }
+                if let Some(data) = ops.first() {
+                    let data_ty = variant_ty(db, ty, *idx);
+                    self.store_operand(&target, layout.data_offset(), data, data_ty);
+                }
+            }
+            _ => {
+                for (idx, op) in ops.iter().enumerate() {
+                    let field = field_ty(db, ty, idx as u32);
+                    self.store_operand(&target, layout.fields[idx], op, field);
+                }
+            }
+        }
+
+        if target != dest {
+            self.emit(format!("local.get {}", dest));
+            self.emit(format!("local.get {}", target));
+            self.emit(format!("i32.const {}", layout.size));
+            self.emit("memory.copy");
+        }
+    }
+
+    /// Writes the operand to the address inside `base`, at the offset.
+    fn store_operand(&mut self, base: &str, offset: u32, op: &Operand, ty: Ty) {
+        match self.cx.repr(ty) {
+            Repr::Zst => {}
+            Repr::Scalar(_) => {
+                self.emit(format!("local.get {}", base));
+                self.operand(op);
+                let store = self.cx.store(ty);
+                self.emit(format!("{} offset={}", store, offset));
+            }
+            Repr::Memory => {
+                let size = self.cx.layout(ty).size;
+                self.emit(format!("local.get {}", base));
+                self.emit(format!("i32.const {}", offset));
+                self.emit("i32.add");
+                self.operand(op);
+                self.emit(format!("i32.const {}", size));
+                self.emit("memory.copy");
+            }
+        }
+    }
+
+    /// Pushes the value of an rvalue, or its address if it's stored in memory.
+    fn rvalue(&mut self, rvalue: &Rvalue, span: Span) {
+        let db = self.cx.db;
+        match rvalue {
+            Rvalue::Use(op) => self.operand(op),
+            Rvalue::BinaryOp(op, left, right) => self.binary(*op, left, right, span),
+            Rvalue::UnaryOp(op, val) => {
+                let ty = self.operand_ty(val);
+                self.operand(val);
+                match (op, ty.kind(db)) {
+                    (UnOp::Not, TyKind::Int { width, signed }) => {
+                        let int = IntTy { width, signed };
+                        let vt = int.val_type().name();
+                        self.emit(format!("{}.const -1", vt));
+                        self.emit(format!("{}.xor", vt));
+                        if !signed {
+                            let normalize = self.cx.normalize(int);
+                            self.emit_all(normalize);
+                        }
+                    }
+                    (UnOp::Not, _) => self.emit("i32.eqz"),
+                    (UnOp::Neg, TyKind::Int { width, signed }) => {
+                        let loc = self.location(span);
+                        self.emit(loc);
+                        let func = self.cx.int_fn("neg", IntTy { width, signed });
+                        self.emit(format!("call {}", func));
+                    }
+                    (UnOp::Neg, _) => {
+                        let vt = self.val_type(ty);
+                        self.emit(format!("{}.neg", vt.name()));
+                    }
+                }
+            }
+            Rvalue::AddressOf(place) => self.address_of(place),
+            Rvalue::AddressOfStatic(item) => {
+                let addr = self.cx.static_addr(*item);
+                self.emit(format!("i32.const {}", addr));
+            }
+            Rvalue::Discriminant(place) => {
+                let offset = self.address(place);
+                let layout = self.cx.layout(self.place_ty(place));
+                for instr in self.cx.discriminant(&layout, offset) {
+                    self.emit(instr);
+                }
+            }
+            Rvalue::Aggregate(..) => panic!("aggregate with a type that isn't stored in memory"),
+        }
+    }
+
+    fn val_type(&mut self, ty: Ty) -> ValType {
+        match self.cx.repr(ty) {
+            Repr::Scalar(vt) => vt,
+            _ => ValType::I32,
+        }
+    }
+
+    /// Converts the integer of the type on the stack into an `i64`.
+    fn extend(&mut self, ty: Ty) {
+        if let TyKind::Int { width, signed } = ty.kind(self.cx.db) {
+            if width <= 32 {
+                self.emit(if signed {
+                    "i64.extend_i32_s"
+                } else {
+                    "i64.extend_i32_u"
+                });
+            }
+        }
+    }
+
+    /// Pushes the result of a binary operator.
+    ///
+    /// The right operand of shifts and pointer arithmetic can be of any integer type.
+    fn binary(&mut self, op: BinOp, left: &Operand, right: &Operand, span: Span) {
+        let db = self.cx.db;
+        let ty = self.operand_ty(left);
+        let right_ty = self.operand_ty(right);
+        let vt = self.val_type(ty).name();
+        let kind = ty.kind(db);
+
+        self.operand(left);
+        match (op, kind.clone()) {
+            (BinOp::Rem, TyKind::Float { width }) | (BinOp::Pow, TyKind::Float { width }) => {
+                // both are computed by the host using `f64`s
+                if width == 32 {
+                    self.emit("f64.promote_f32");
+                }
+                self.operand(right);
+                if width == 32 {
+                    self.emit("f64.promote_f32");
+                }
+                let func = if op == BinOp::Rem { "fmod" } else { "pow" };
+                self.emit(format!("call $cell_{}", func));
+                if width == 32 {
+                    self.emit("f32.demote_f64");
+                }
+                return;
+            }
+            (BinOp::Shl, _) | (BinOp::Shr, _) => {
+                self.operand(right);
+                self.extend(right_ty);
+            }
+            (_, TyKind::Pointer(inner)) => {
+                self.operand(right);
+                if self.val_type(right_ty) == ValType::I64 {
+                    self.emit("i32.wrap_i64");
+                }
+                let size = self.cx.layout(inner).size;
+                self.emit(format!("i32.const {}", size));
+                self.emit("i32.mul");
+            }
+            _ => self.operand(right),
+        }
+
+        match op {
+            BinOp::Eq | BinOp::Ne => {
+                let equal = self.cx.equal(ty);
+                self.emit_all(equal);
+                if op == BinOp::Ne {
+                    self.emit("i32.eqz");
+                }
+                return;
+            }
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                let cmp = match op {
+                    BinOp::Lt => "lt",
+                    BinOp::Le => "le",
+                    BinOp::Gt => "gt",
+                    _ => "ge",
+                };
+                let suffix = match kind {
+                    TyKind::Float { .. } => "",
+                    TyKind::Int { signed: true, .. } => "_s",
+                    _ => "_u",
+                };
+                self.emit(format!("{}.{}{}", vt, cmp, suffix));
+                return;
+            }
+            _ => {}
+        }
+
+        match kind {
+            TyKind::Int { width, signed } => {
+                let func = match op {
+                    BinOp::BitAnd => return self.emit(format!("{}.and", vt)),
+                    BinOp::BitOr => return self.emit(format!("{}.or", vt)),
+                    BinOp::BitXor => return self.emit(format!("{}.xor", vt)),
+                    BinOp::Add => "add",
+                    BinOp::Sub => "sub",
+                    BinOp::Mul => "mul",
+                    BinOp::Div => "div",
+                    BinOp::Rem => "rem",
+                    BinOp::Shl => "shl",
+                    BinOp::Shr => "shr",
+                    _ => "pow",
+                };
+                let loc = self.location(span);
+                self.emit(loc);
+                let func = self.cx.int_fn(func, IntTy { width, signed });
+                self.emit(format!("call {}", func));
+            }
+            TyKind::Float { .. } => {
+                let instr = match op {
+                    BinOp::Add => "add",
+                    BinOp::Sub => "sub",
+                    BinOp::Mul => "mul",
+                    _ => "div",
+                };
+                self.emit(format!("{}.{}", vt, instr));
+            }
+            TyKind::Pointer(_) => {
+                self.emit(if op == BinOp::Sub {
+                    "i32.sub"
+                } else {
+                    "i32.add"
+                });
+            }
+            // bit operators on `bool`s
+            _ => {
+                let instr = match op {
+                    BinOp::BitAnd => "and",
+                    BinOp::BitOr => "or",
+                    _ => "xor",
+                };
+                self.emit(format!("i32.{}", instr));
+            }
+        }
+    }
+
+    fn terminator(&mut self, kind: &TerminatorKind, span: Span) {
+        let db = self.cx.db;
+        match kind {
+            TerminatorKind::Goto { target } => self.jump(target.0),
+            TerminatorKind::SwitchInt {
+                discr,
+                targets,
+                otherwise,
+            } => {
+                let ty = self.operand_ty(discr);
+                let vt = self.val_type(ty);
+                let discr_local = self.scratch(vt);
+                self.operand(discr);
+                self.emit(format!("local.set {}", discr_local));
+                for &(val, target) in targets {
+                    let val = match ty.kind(db) {
+                        TyKind::Int {
+                            width,
+                            signed: true,
+                        } => {
+                            let shift = 128 - width as u32;
+                            (((val << shift) as i128) >> shift).to_string()
+                        }
+                        _ => val.to_string(),
+                    };
+                    self.emit(format!("local.get {}", discr_local));
+                    self.emit(format!("{}.const {}", vt.name(), val));
+                    self.emit(format!("{}.eq", vt.name()));
+                    self.emit("if");
+                    self.indent += 1;
+                    self.branch(target.0);
+                    self.indent -= 1;
+                    self.emit("end");
+                }
+                self.jump(otherwise.0);
+            }
+            TerminatorKind::Return => self.ret(),
+            TerminatorKind::Call {
+                func,
+                generic_args,
+                args,
+                destination,
+                target,
+            } => {
+                let generic_args = generic_args
+                    .iter()
+                    .map(|&ty| self.subst(ty))
+                    .collect::<Vec<_>>();
+                self.call(*func, generic_args, args, destination, span);
+                self.jump(target.0);
+            }
+            TerminatorKind::Unreachable => {
+                let loc = self.location(span);
+                let trap = self
+                    .cx
+                    .trap("entered unreachable code", &loc, "i64.const 0");
+                self.emit_all(trap);
+                self.emit("unreachable");
+            }
+        }
+    }
+
+    /// Jumps to the target, unless it's the next block.
+    fn jump(&mut self, target: u32) {
+        if target != self.current + 1 {
+            self.branch(target);
+        }
+    }
+
+    /// Jumps to the start of the target.
+    fn branch(&mut self, target: u32) {
+        if target > self.current {
+            // the end of the block is the start of the target
+            self.emit(format!("br $bb{}", target));
+        } else {
+            self.emit(format!("i32.const {}", target));
+            self.emit("local.set $bb");
+            self.emit("br $dispatch");
+        }
+    }
+
+    /// Frees the frame, and returns the value of `_0`.
+    fn ret(&mut self) {
+        let ret = self.repr(0);
+        if ret == Repr::Memory {
+            let size = self.cx.layout(self.tys[0]).size;
+            self.emit("local.get $ret");
+            self.address_of(&Place {
+                local: Local(0),
+                projection: vec![],
+            });
+            self.emit(format!("i32.const {}", size));
+            self.emit("memory.copy");
+        }
+        self.emit("local.get $fp");
+        self.emit(format!("i32.const {}", self.frame_size));
+        self.emit("i32.add");
+        self.emit("global.set $sp");
+        if let Repr::Scalar(_) = ret {
+            self.local_value(Local(0));
+        }
+        self.emit("return");
+    }
+
+    fn call(
+        &mut self,
+        func: ItemId,
+        generic_args: Vec<Ty>,
+        args: &[Operand],
+        dest: &Place,
+        span: Span,
+    ) {
+        let db = self.cx.db;
+        match prelude::host_fn(db, func) {
+            Some(HostFn::Println) => {
+                // the first argument is the pointer to `io`
+                let ty = self.operand_ty(&args[1]);
+                self.operand(&args[1]);
+                let print = self.cx.print(ty, false);
+                self.emit_all(print);
+                let newline = self.cx.print_text("\n");
+                self.emit_all(newline);
+                return;
+            }
+            Some(HostFn::Alloc) => {
+                let dest_ty = self.place_ty(dest);
+                let count_ty = self.operand_ty(&args[0]);
+                let size = self.cx.layout(generic_args[0]).size;
+                let loc = self.location(span);
+                self.write_scalar(dest, dest_ty, |this| {
+                    this.operand(&args[0]);
+                    this.extend(count_ty);
+                    this.emit(format!("i32.const {}", size));
+                    this.emit(loc);
+                    this.emit("call $cell_alloc");
+                });
+                return;
+            }
+            None => {}
+        }
+
+        let func = self
+            .cx
+            .instance(&Instance::resolve(db, func, &generic_args));
+        let ty = self.place_ty(dest);
+        let call = |this: &mut Self| {
+            for arg in args {
+                this.operand(arg);
+            }
+            this.emit(format!("call {}", func));
+        };
+        match self.cx.repr(ty) {
+            Repr::Zst => call(self),
+            Repr::Scalar(_) => self.write_scalar(dest, ty, call),
+            Repr::Memory => {
+                self.address_of(dest);
+                call(self);
+            }
+        }
+    }
+}
+
+/// Returns every constant that is used inside of the block, together
+/// with the span of its statement or terminator.
+fn constants(block: &BasicBlockData) -> Vec<(Span, &Constant)> {
+    let mut ops = vec![];
+    for statement in block.statements.iter() {
+        let span = statement.span;
+        match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Use(op))
+            | StatementKind::Assign(_, Rvalue::UnaryOp(_, op)) => ops.push((span, op)),
+            StatementKind::Assign(_, Rvalue::BinaryOp(_, left, right)) => {
+                ops.extend([(span, left), (span, right)])
+            }
+            StatementKind::Assign(_, Rvalue::Aggregate(_, args)) => {
+                ops.extend(args.iter().map(|op| (span, op)))
+            }
+            StatementKind::Assign(..) | StatementKind::Phi(..) | StatementKind::Nop => {}
+        }
+    }
+
+    let terminator = block.terminator();
+    match &terminator.kind {
+        TerminatorKind::SwitchInt { discr, .. } => ops.push((terminator.span, discr)),
+        TerminatorKind::Call { args, .. } => {
+            ops.extend(args.iter().map(|op| (terminator.span, op)))
+        }
+        _ => {}
+    }
+
+    ops.into_iter()
+        .filter_map(|(span, op)| match op {
+            Operand::Constant(constant) => Some((span, constant)),
+            Operand::Copy(_) => None,
+        })
+        .collect()
+}
+
+fn pointee(db: &dyn cell_mir::db::MirDatabase, ty: Ty) -> Ty {
+    match ty.kind(db) {
+        TyKind::Pointer(inner) => inner,
+        _ => panic!("dereferenced a type that is not a pointer"),
+    }
+}
+
+/// Returns a float in the text format.
+fn float_literal(val: f64, width: u16) -> String {
+    if val.is_nan() {
+        "nan".to_string()
+    } else if width == 32 {
+        format!("{:?}", val as f32)
+    } else {
+        format!("{:?}", val)
+    }
+}
@@ -0,0 +1,108 @@
+// Runs a module that was generated by the cell WebAssembly backend.
+//
+// usage: node host.js <module.wasm> [export]
+//
+// Calls `main`, or the given export, and exits with 101 if the
+// program stops with a runtime error.
+
+"use strict";
+
+const fs = require("fs");
+
+const [path, entry = "main"] = process.argv.slice(2);
+const decoder = new TextDecoder();
+let memory;
+
+class Trap extends Error {}
+
+function write(text) {
+    fs.writeSync(1, text);
+}
+
+function string(ptr) {
+    const len = new DataView(memory.buffer).getUint32(ptr, true);
+    return decoder.decode(new Uint8Array(memory.buffer, ptr + 4, len));
+}
+
+// Escapes a string or char the same way as Rust's `Debug` implementation.
+function escape(text, quote) {
+    let out = "";
+    for (const c of text) {
+        const code = c.codePointAt(0);
+        if (c === "\t") out += "\\t";
+        else if (c === "\n") out += "\\n";
+        else if (c === "\r") out += "\\r";
+        else if (c === "\\") out += "\\\\";
+        else if (code === 0) out += "\\0";
+        else if (c === quote) out += "\\" + quote;
+        else if (code < 0x20 || code === 0x7f) out += "\\u{" + code.toString(16) + "}";
+        else out += c;
+    }
+    return quote + out + quote;
+}
+
+// Prints the shortest representation that reads back as the
+// same value, without using an exponent.
+function float(val) {
+    if (Number.isNaN(val)) return "NaN";
+    if (!Number.isFinite(val)) return val < 0 ? "-inf" : "inf";
+    if (Object.is(val, -0)) return "-0";
+    if (Number.isInteger(val)) return BigInt(val).toString();
+
+    const text = String(val);
+    const exp = text.indexOf("e");
+    if (exp < 0) return text;
+
+    // only small numbers are printed with an exponent
+    const sign = text.startsWith("-") ? "-" : "";
+    const digits = text.slice(sign.length, exp).replace(".", "");
+    const zeros = -Number(text.slice(exp + 1)) - 1;
+    return sign + "0." + "0".repeat(zeros) + digits;
+}
+
+const imports = {
+    cell: {
+        print_int(val, signed) {
+            write((signed ? val : BigInt.asUintN(64, val)).toString());
+        },
+        print_float(val) {
+            write(float(val));
+        },
+        print_char(val, quoted) {
+            const c = String.fromCodePoint(val);
+            write(quoted ? escape(c, "'") : c);
+        },
+        print_string(ptr, quoted) {
+            const text = string(ptr);
+            write(quoted ? escape(text, '"') : text);
+        },
+        trap(message, loc, arg) {
+            const text = string(message).replace("{}", arg.toString());
+            throw new Trap(`${text}\n  --> ${string(loc)}`);
+        },
+        fmod: (a, b) => a % b,
+        pow: Math.pow,
+    },
+};
+
+const wasm = new WebAssembly.Module(fs.readFileSync(path));
+const instance = new WebAssembly.Instance(wasm, imports);
+memory = instance.exports.memory;
+
+if (typeof instance.exports[entry] !== "function") {
+    process.stderr.write(`error: the module doesn't export \`${entry}\`\n`);
+    process.exit(1);
+}
+
+try {
+    instance.exports[entry]();
+} catch (err) {
+    if (err instanceof Trap) {
+        process.stderr.write(`error: runtime: ${err.message}\n`);
+    } else if (err instanceof RangeError) {
+        process.stderr.write(`error: runtime: stack overflow\n`);
+    } else {
+        throw err;
+    }
+    process.exit(101);
+}
@@ -0,0 +1,46 @@
+//! A backend that translates the MIR into a WebAssembly module.
+//!
+//! Pointers are addresses into the linear memory, which contains the
+//! data of strings and `static`s, followed by the stack and the heap.
+//! Values are laid out as described by [`cell_mir::layout`] for
+//! the `wasm32` target. `main` and every public, non-generic function
+//! are exported, and functions inside of modules are exported using their
+//! path, like `math.add`.
+//!
+//! Scalars are passed as WebAssembly values, where integers with up to
+//! 32 bits are `i32`s. Aggregates are passed using their address, and
+//! functions that return an aggregate take the address the result is
+//! written to as their first parameter.
+//!
+//! Printing and reporting runtime errors is done by the host, which has to
+//! provide the functions inside the `cell` import module:
+//!
+//! - `print_int(val: i64, signed: i32)`
+//! - `print_float(val: f64)`
+//! - `print_char(val: i32, quoted: i32)`
+//! - `print_string(ptr: i32, quoted: i32)`, where `ptr` points to the
+//!   length of the string as an `u32`, followed by its UTF-8 bytes
+//! - `trap(message: i32, location: i32, arg: i64)`, which must not return,
+//!   and replaces `{}` inside the message with `arg`
+//! - `fmod(a: f64, b: f64) -> f64` and `pow(a: f64, b: f64) -> f64`
+//!
+//! `host.js` implements them to run a module with Node.js.
+//!
+//! 128-bit integers are not supported.
+//!
+//! [`cell_mir::layout`]: ../cell_mir/layout/index.html
+
+mod codegen;
+mod function;
+mod runtime;
+
+pub use codegen::generate;
+
+/// The source of a Node.js script, that runs the module at the path
+/// of its first argument.
+pub const NODE_HOST: &str = include_str!("host.js");
+
+/// Translates the text format into the binary format.
+pub fn assemble(wat: &str) -> Result<Vec<u8>, wat::Error> {
+    wat::parse_str(wat)
+}
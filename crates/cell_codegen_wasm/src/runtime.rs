@@ -0,0 +1,185 @@
+//! The functions that are imported from the host, and the runtime
+//! support functions that are included into every module.
+
+use crate::codegen::Codegen;
+
+/// The functions that the host has to provide inside the `cell` module.
+pub const IMPORTS: &str = r#"  (import "cell" "print_int" (func $cell_print_int (param i64 i32)))
+  (import "cell" "print_float" (func $cell_print_float (param f64)))
+  (import "cell" "print_char" (func $cell_print_char (param i32 i32)))
+  (import "cell" "print_string" (func $cell_print_string (param i32 i32)))
+  (import "cell" "trap" (func $cell_trap (param i32 i32 i64)))
+  (import "cell" "fmod" (func $cell_fmod (param f64 f64) (result f64)))
+  (import "cell" "pow" (func $cell_pow (param f64 f64) (result f64)))
+"#;
+
+const STRING_EQ: &str = r#"  (func $cell_string_eq (param $a i32) (param $b i32) (result i32)
+    (local $len i32)
+    local.get $a
+    local.get $b
+    i32.eq
+    if
+      i32.const 1
+      return
+    end
+    local.get $a
+    i32.load
+    local.tee $len
+    local.get $b
+    i32.load
+    i32.ne
+    if
+      i32.const 0
+      return
+    end
+    block $done
+      loop $next
+        local.get $len
+        i32.eqz
+        br_if $done
+        local.get $len
+        i32.const 1
+        i32.sub
+        local.set $len
+        local.get $a
+        local.get $len
+        i32.add
+        i32.load8_u offset=4
+        local.get $b
+        local.get $len
+        i32.add
+        i32.load8_u offset=4
+        i32.ne
+        if
+          i32.const 0
+          return
+        end
+        br $next
+      end
+    end
+    i32.const 1
+  )
+
+"#;
+
+impl Codegen<'_> {
+    /// Returns the instructions that trap with the message, at the location
+    /// that is pushed by `loc`.
+    ///
+    /// The message can contain `{}`, which is replaced by the `i64`
+    /// that is pushed by `arg`.
+    pub fn trap(&mut self, message: &str, loc: &str, arg: &str) -> Vec<String> {
+        let message = self.string(message);
+        vec![
+            format!("i32.const {}", message),
+            loc.to_string(),
+            arg.to_string(),
+            "call $cell_trap".to_string(),
+        ]
+    }
+}
+
+/// Adds the runtime support functions to the helpers.
+pub fn generate(cx: &mut Codegen<'_>) {
+    let (true_, false_) = (cx.string("true"), cx.string("false"));
+    cx.helpers.push_str(&format!(
+        r#"  (func $cell_print_bool (param $val i32)
+    local.get $val
+    if (result i32)
+      i32.const {}
+    else
+      i32.const {}
+    end
+    i32.const 0
+    call $cell_print_string
+  )
+
+"#,
+        true_, false_
+    ));
+
+    let (open, close) = (cx.string("<pointer "), cx.string(">"));
+    cx.helpers.push_str(&format!(
+        r#"  (func $cell_print_pointer (param $val i32)
+    i32.const {}
+    i32.const 0
+    call $cell_print_string
+    local.get $val
+    i64.extend_i32_u
+    i32.const 0
+    call $cell_print_int
+    i32.const {}
+    i32.const 0
+    call $cell_print_string
+  )
+
+"#,
+        open, close
+    ));
+
+    cx.helpers.push_str(STRING_EQ);
+
+    // memory is never freed, and new memory is always zeroed
+    let oom = cx.trap("out of memory", "local.get $loc", "i64.const 0");
+    let oom = oom.join("\n      ");
+    cx.helpers.push_str(&format!(
+        r#"  (func $cell_alloc (param $count i64) (param $size i32) (param $loc i32) (result i32)
+    (local $ptr i32)
+    (local $end i64)
+    local.get $count
+    i64.const 4294967296
+    i64.ge_u
+    if
+      {0}
+      unreachable
+    end
+    global.get $heap
+    i32.const 7
+    i32.add
+    i32.const -8
+    i32.and
+    local.tee $ptr
+    i64.extend_i32_u
+    local.get $count
+    local.get $size
+    i64.extend_i32_u
+    i64.mul
+    i64.const 1
+    i64.add
+    i64.add
+    local.tee $end
+    memory.size
+    i64.extend_i32_u
+    i64.const 65536
+    i64.mul
+    i64.gt_u
+    if
+      local.get $end
+      memory.size
+      i64.extend_i32_u
+      i64.const 65536
+      i64.mul
+      i64.sub
+      i64.const 65535
+      i64.add
+      i64.const 65536
+      i64.div_u
+      i32.wrap_i64
+      memory.grow
+      i32.const -1
+      i32.eq
+      if
+        {0}
+        unreachable
+      end
+    end
+    local.get $end
+    i32.wrap_i64
+    global.set $heap
+    local.get $ptr
+  )
+
+"#,
+        oom
+    ));
+}
@@ -2,7 +2,7 @@
 
 use crate::{
     source::{FileId, Locatable, Span},
-    Diagnostic,
+    Diagnostic, Label,
 };
 use derive_more::{Display, Error, From};
 
@@ -10,16 +10,41 @@ pub type ParseResult<T> = std::result::Result<T, Locatable<SyntaxError>>;
 
 /// The central error enum which can be **any** error generated
 /// by the compiler.
-#[derive(Debug, Clone, From, Display, Error)]
+#[derive(Debug, Clone, From, Display, Error, PartialEq, Eq, Hash)]
 pub enum Error {
     #[display(fmt = "syntax: {}", _0)]
     Syntax(SyntaxError),
+    #[display(fmt = "type: {}", _0)]
+    Type(TypeError),
+    #[display(fmt = "const: {}", _0)]
+    Const(ConstError),
+    #[display(fmt = "runtime: {}", _0)]
+    Runtime(RuntimeError),
+    #[display(fmt = "codegen: {}", _0)]
+    Codegen(CodegenError),
+}
+
+impl Error {
+    /// Returns the stable code of this error, which is listed in `Errors.md`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Syntax(err) => err.code(),
+            Error::Type(err) => err.code(),
+            Error::Const(err) => err.code(),
+            Error::Runtime(err) => err.code(),
+            Error::Codegen(err) => err.code(),
+        }
+    }
 }
 
 impl IntoDiagnostic for Error {
     fn into_diagnostic(self, span: Span, file: FileId) -> Diagnostic {
         match self {
             Error::Syntax(err) => err.into_diagnostic(span, file),
+            Error::Type(err) => err.into_diagnostic(span, file),
+            Error::Const(err) => err.into_diagnostic(span, file),
+            Error::Runtime(err) => err.into_diagnostic(span, file),
+            Error::Codegen(err) => err.into_diagnostic(span, file),
         }
     }
 }
@@ -36,18 +61,403 @@ pub trait IntoDiagnostic {
 }
 
 /// Any error that can happen during parsing.
-#[derive(Debug, Clone, Display, Error)]
-pub enum SyntaxError {}
+#[derive(Debug, Clone, Display, Error, PartialEq, Eq, Hash)]
+pub enum SyntaxError {
+    #[display(fmt = "expected {}, found {}", expected, found)]
+    UnexpectedToken { expected: String, found: String },
+    #[display(fmt = "expected {}, found end of file", expected)]
+    UnexpectedEof { expected: String },
+    #[display(fmt = "invalid token")]
+    InvalidToken,
+    #[display(fmt = "invalid integer literal")]
+    InvalidInt,
+    #[display(fmt = "integer literal is too large")]
+    IntTooLarge,
+    #[display(fmt = "invalid float literal")]
+    InvalidFloat,
+    #[display(fmt = "invalid character literal")]
+    InvalidChar,
+    #[display(fmt = "unknown escape sequence")]
+    InvalidEscape,
+    #[display(fmt = "unknown attribute, expected `repr`")]
+    UnknownAttribute,
+    #[display(fmt = "unknown representation `{}`, expected `C` or `packed`", name)]
+    UnknownRepr { name: String },
+    #[display(fmt = "`@repr` can only be applied to `type`s and unions")]
+    MisplacedRepr,
+    #[display(fmt = "an item can only have one `@repr`")]
+    DuplicateRepr,
+}
+
+impl SyntaxError {
+    /// Returns the stable code of this error, which is listed in `Errors.md`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SyntaxError::UnexpectedToken { .. } => "E0001",
+            SyntaxError::UnexpectedEof { .. } => "E0002",
+            SyntaxError::InvalidToken => "E0003",
+            SyntaxError::InvalidInt => "E0004",
+            SyntaxError::IntTooLarge => "E0005",
+            SyntaxError::InvalidFloat => "E0006",
+            SyntaxError::InvalidChar => "E0007",
+            SyntaxError::InvalidEscape => "E0008",
+            SyntaxError::UnknownAttribute => "E0009",
+            SyntaxError::UnknownRepr { .. } => "E0010",
+            SyntaxError::MisplacedRepr => "E0011",
+            SyntaxError::DuplicateRepr => "E0012",
+        }
+    }
+}
 
 impl IntoDiagnostic for SyntaxError {
     fn into_diagnostic(self, span: Span, file: FileId) -> Diagnostic {
-        todo!()
+        Diagnostic::error()
+            .with_code(self.code())
+            .with_message(self.to_string())
+            .with_labels(vec![Label::primary(file, span)])
+    }
+}
+
+/// Any error that can be found while resolving names and
+/// checking types.
+#[derive(Debug, Clone, Display, Error, PartialEq, Eq, Hash)]
+pub enum TypeError {
+    #[display(fmt = "cannot find value `{}` in this scope", name)]
+    UnresolvedName { name: String },
+    #[display(fmt = "cannot find type `{}` in this scope", name)]
+    UnresolvedType { name: String },
+    #[display(fmt = "cannot find trait `{}` in this scope", name)]
+    UnresolvedTrait { name: String },
+    #[display(fmt = "unresolved import `{}`", path)]
+    UnresolvedImport { path: String },
+    #[display(fmt = "file not found for module `{}`, expected `{}`", name, path)]
+    ModuleNotFound { name: String, path: String },
+    #[display(fmt = "{} `{}` is private", kind, name)]
+    PrivateItem { kind: String, name: String },
+    #[display(fmt = "field `{}` of type `{}` is private", field, ty)]
+    PrivateField { ty: String, field: String },
+    #[display(fmt = "variant `{}` of union `{}` is private", variant, union)]
+    PrivateVariant { union: String, variant: String },
+    #[display(fmt = "expected type, found `{}`", name)]
+    ExpectedType { name: String },
+    #[display(fmt = "type alias `{}` refers to itself", name)]
+    CyclicAlias { name: String },
+    #[display(fmt = "the name `{}` is defined multiple times", name)]
+    DuplicateDefinition {
+        name: String,
+        previous_file: FileId,
+        previous: Span,
+    },
+    #[display(fmt = "mismatched types: expected `{}`, found `{}`", expected, found)]
+    Mismatch { expected: String, found: String },
+    #[display(fmt = "expected {} arguments, found {}", expected, found)]
+    WrongArgCount { expected: usize, found: usize },
+    #[display(fmt = "expected {} generic arguments, found {}", expected, found)]
+    WrongGenericCount { expected: usize, found: usize },
+    #[display(fmt = "`{}` is not a function", name)]
+    NotCallable { name: String },
+    #[display(fmt = "`{}` can not be used as a value", name)]
+    NotAValue { name: String },
+    #[display(fmt = "no field `{}` on type `{}`", field, ty)]
+    NoField { ty: String, field: String },
+    #[display(fmt = "missing field `{}` in initializer of `{}`", field, ty)]
+    MissingField { ty: String, field: String },
+    #[display(fmt = "no method named `{}` found for type `{}`", method, ty)]
+    NoMethod { ty: String, method: String },
+    #[display(
+        fmt = "multiple applicable methods named `{}` found for type `{}`",
+        method,
+        ty
+    )]
+    AmbiguousMethod { ty: String, method: String },
+    #[display(fmt = "cannot apply `{}` to type `{}`", op, ty)]
+    InvalidOperand { op: String, ty: String },
+    #[display(fmt = "invalid left-hand side of assignment")]
+    NotAPlace,
+    #[display(fmt = "`this` can only be used inside of methods")]
+    ThisOutsideMethod,
+    #[display(fmt = "`{}` can only be used inside of a loop", keyword)]
+    OutsideOfLoop { keyword: String },
+    #[display(fmt = "function `{}` may not return a value of type `{}`", name, ty)]
+    MissingReturn { name: String, ty: String },
+    #[display(fmt = "type annotations needed")]
+    CannotInfer,
+    #[display(fmt = "not all trait methods implemented, missing: `{}`", method)]
+    MissingTraitMethod { trait_: String, method: String },
+    #[display(fmt = "method `{}` is not a member of trait `{}`", method, trait_)]
+    NotATraitMember { trait_: String, method: String },
+    #[display(
+        fmt = "method `{}` has an incompatible signature for trait `{}`",
+        method,
+        trait_
+    )]
+    TraitSignatureMismatch { trait_: String, method: String },
+    #[display(
+        fmt = "conflicting implementations of trait `{}` for type `{}`",
+        trait_,
+        ty
+    )]
+    OverlappingImpls {
+        trait_: String,
+        ty: String,
+        previous_file: FileId,
+        previous: Span,
+    },
+    #[display(fmt = "duplicate definitions of method `{}` for type `{}`", method, ty)]
+    DuplicateMethod {
+        ty: String,
+        method: String,
+        previous_file: FileId,
+        previous: Span,
+    },
+    #[display(fmt = "the trait `{}` is not implemented for `{}`", trait_, ty)]
+    TraitNotImplemented { trait_: String, ty: String },
+    #[display(fmt = "non-exhaustive patterns: {} not covered", patterns)]
+    NonExhaustive { patterns: String },
+    #[display(
+        fmt = "range patterns can only be used for integers and chars, found `{}`",
+        ty
+    )]
+    InvalidRangePattern { ty: String },
+    #[display(fmt = "lower bound of range pattern is greater than the upper bound")]
+    EmptyRangePattern,
+    #[display(fmt = "recursive type `{}` has infinite size", name)]
+    InfiniteSize { name: String },
+}
+
+impl TypeError {
+    /// Returns the stable code of this error, which is listed in `Errors.md`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypeError::UnresolvedName { .. } => "E0100",
+            TypeError::UnresolvedType { .. } => "E0101",
+            TypeError::UnresolvedTrait { .. } => "E0102",
+            TypeError::UnresolvedImport { .. } => "E0103",
+            TypeError::ModuleNotFound { .. } => "E0104",
+            TypeError::PrivateItem { .. } => "E0105",
+            TypeError::PrivateField { .. } => "E0106",
+            TypeError::PrivateVariant { .. } => "E0107",
+            TypeError::ExpectedType { .. } => "E0108",
+            TypeError::CyclicAlias { .. } => "E0109",
+            TypeError::DuplicateDefinition { .. } => "E0110",
+            TypeError::Mismatch { .. } => "E0111",
+            TypeError::WrongArgCount { .. } => "E0112",
+            TypeError::WrongGenericCount { .. } => "E0113",
+            TypeError::NotCallable { .. } => "E0114",
+            TypeError::NotAValue { .. } => "E0115",
+            TypeError::NoField { .. } => "E0116",
+            TypeError::MissingField { .. } => "E0117",
+            TypeError::NoMethod { .. } => "E0118",
+            TypeError::AmbiguousMethod { .. } => "E0119",
+            TypeError::InvalidOperand { .. } => "E0120",
+            TypeError::NotAPlace => "E0121",
+            TypeError::ThisOutsideMethod => "E0122",
+            TypeError::OutsideOfLoop { .. } => "E0123",
+            TypeError::MissingReturn { .. } => "E0124",
+            TypeError::CannotInfer => "E0125",
+            TypeError::MissingTraitMethod { .. } => "E0126",
+            TypeError::NotATraitMember { .. } => "E0127",
+            TypeError::TraitSignatureMismatch { .. } => "E0128",
+            TypeError::OverlappingImpls { .. } => "E0129",
+            TypeError::DuplicateMethod { .. } => "E0130",
+            TypeError::TraitNotImplemented { .. } => "E0131",
+            TypeError::NonExhaustive { .. } => "E0132",
+            TypeError::InvalidRangePattern { .. } => "E0133",
+            TypeError::EmptyRangePattern => "E0134",
+            TypeError::InfiniteSize { .. } => "E0135",
+        }
+    }
+}
+
+impl IntoDiagnostic for TypeError {
+    fn into_diagnostic(self, span: Span, file: FileId) -> Diagnostic {
+        let mut labels = vec![Label::primary(file, span)];
+        match &self {
+            TypeError::DuplicateDefinition {
+                previous_file,
+                previous,
+                ..
+            }
+            | TypeError::OverlappingImpls {
+                previous_file,
+                previous,
+                ..
+            }
+            | TypeError::DuplicateMethod {
+                previous_file,
+                previous,
+                ..
+            } => {
+                labels.push(
+                    Label::secondary(*previous_file, *previous)
+                        .with_message("previous definition here"),
+                );
+            }
+            _ => {}
+        }
+
+        Diagnostic::error()
+            .with_code(self.code())
+            .with_message(self.to_string())
+            .with_labels(labels)
+    }
+}
+
+/// Any error that can happen while evaluating an expression at compile time.
+#[derive(Debug, Clone, Display, Error, PartialEq, Eq, Hash)]
+pub enum ConstError {
+    #[display(fmt = "this arithmetic operation will overflow `{}`", ty)]
+    Overflow { ty: String },
+    #[display(fmt = "literal out of range for `{}`", ty)]
+    LiteralOutOfRange { ty: String },
+    #[display(fmt = "attempt to divide by zero")]
+    DivisionByZero,
+    #[display(fmt = "attempt to calculate the remainder with a divisor of zero")]
+    RemainderByZero,
+    #[display(fmt = "attempt to shift `{}` by `{}`, which is too large", ty, amount)]
+    ShiftTooLarge { ty: String, amount: String },
+    #[display(fmt = "attempt to raise an integer to a negative power")]
+    NegativeExponent,
+    #[display(fmt = "expression can not be evaluated at compile time")]
+    NotConst,
+    #[display(fmt = "cycle detected when evaluating `{}`", name)]
+    Cycle { name: String },
+}
+
+impl ConstError {
+    /// Returns the stable code of this error, which is listed in `Errors.md`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConstError::Overflow { .. } => "E0200",
+            ConstError::LiteralOutOfRange { .. } => "E0201",
+            ConstError::DivisionByZero => "E0202",
+            ConstError::RemainderByZero => "E0203",
+            ConstError::ShiftTooLarge { .. } => "E0204",
+            ConstError::NegativeExponent => "E0205",
+            ConstError::NotConst => "E0206",
+            ConstError::Cycle { .. } => "E0207",
+        }
+    }
+}
+
+impl IntoDiagnostic for ConstError {
+    fn into_diagnostic(self, span: Span, file: FileId) -> Diagnostic {
+        Diagnostic::error()
+            .with_code(self.code())
+            .with_message(self.to_string())
+            .with_labels(vec![Label::primary(file, span)])
+    }
+}
+
+/// Any error that can happen while running a program.
+#[derive(Debug, Clone, Display, Error, PartialEq, Eq, Hash)]
+pub enum RuntimeError {
+    /// An arithmetic operation that overflowed, divided by zero
+    /// or shifted by too many bits.
+    #[display(fmt = "{}", _0)]
+    Arithmetic(ConstError),
+    #[display(fmt = "dereferenced a dangling or out of bounds pointer")]
+    InvalidPointer,
+    #[display(fmt = "stack overflow while calling `{}`", name)]
+    StackOverflow { name: String },
+    #[display(fmt = "entered unreachable code")]
+    Unreachable,
+    #[display(fmt = "out of memory")]
+    OutOfMemory,
+    #[display(fmt = "no `main` function found")]
+    NoMain,
+    #[display(fmt = "failed to write output: {}", message)]
+    Output { message: String },
+}
+
+impl RuntimeError {
+    /// Returns the stable code of this error, which is listed in `Errors.md`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::Arithmetic(_) => "E0300",
+            RuntimeError::InvalidPointer => "E0301",
+            RuntimeError::StackOverflow { .. } => "E0302",
+            RuntimeError::Unreachable => "E0303",
+            RuntimeError::OutOfMemory => "E0304",
+            RuntimeError::NoMain => "E0305",
+            RuntimeError::Output { .. } => "E0306",
+        }
+    }
+}
+
+impl IntoDiagnostic for RuntimeError {
+    fn into_diagnostic(self, span: Span, file: FileId) -> Diagnostic {
+        Diagnostic::error()
+            .with_code(self.code())
+            .with_message(self.to_string())
+            .with_labels(vec![Label::primary(file, span)])
+    }
+}
+
+/// Any error that prevents a backend from generating code.
+#[derive(Debug, Clone, Display, Error, PartialEq, Eq, Hash)]
+pub enum CodegenError {
+    #[display(fmt = "{} are not supported by the {} backend", what, backend)]
+    Unsupported { what: String, backend: String },
+    #[display(fmt = "reached the recursion limit while instantiating `{}`", func)]
+    RecursionLimit { func: String },
+}
+
+impl CodegenError {
+    /// Returns the stable code of this error, which is listed in `Errors.md`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CodegenError::Unsupported { .. } => "E0400",
+            CodegenError::RecursionLimit { .. } => "E0401",
+        }
+    }
+}
+
+impl IntoDiagnostic for CodegenError {
+    fn into_diagnostic(self, span: Span, file: FileId) -> Diagnostic {
+        let notes = match &self {
+            CodegenError::RecursionLimit { func } => vec![format!(
+                "`{}` is called with larger generic arguments every time",
+                func
+            )],
+            CodegenError::Unsupported { .. } => vec![],
+        };
+        Diagnostic::error()
+            .with_code(self.code())
+            .with_message(self.to_string())
+            .with_labels(vec![Label::primary(file, span)])
+            .with_notes(notes)
+    }
+}
+
+/// Anything that is suspicious, but doesn't prevent compilation.
+#[derive(Debug, Clone, Display, PartialEq, Eq, Hash)]
+pub enum Warning {
+    #[display(fmt = "unreachable pattern")]
+    UnreachablePattern,
+}
+
+impl Warning {
+    /// Returns the stable code of this warning, which is listed in `Errors.md`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Warning::UnreachablePattern => "W0001",
+        }
+    }
+}
+
+impl IntoDiagnostic for Warning {
+    fn into_diagnostic(self, span: Span, file: FileId) -> Diagnostic {
+        Diagnostic::warning()
+            .with_code(self.code())
+            .with_message(self.to_string())
+            .with_labels(vec![Label::primary(file, span)])
     }
 }
 
-impl<T: IntoDiagnostic> Into<Diagnostic> for Locatable<T> {
-    fn into(self) -> Diagnostic {
-        let (data, file, span) = self.destruct();
+impl<T: IntoDiagnostic> From<Locatable<T>> for Diagnostic {
+    fn from(loc: Locatable<T>) -> Diagnostic {
+        let (data, file, span) = loc.destruct();
         data.into_diagnostic(span, file)
     }
 }
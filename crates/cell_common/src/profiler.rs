@@ -1,6 +1,7 @@
 //! The Profiler used by the compiler.
 
 #[cfg(feature = "profiler")]
+#[allow(clippy::module_inception)]
 mod profiler;
 
 #[cfg(feature = "profiler")]
@@ -1,26 +1,44 @@
 //! Abstractions for source code files.
 
 mod file;
+mod line_col;
 mod span;
 
-pub use file::{File, FileCache, FileId};
+pub use file::{FileCache, FileId};
+pub use line_col::{Encoding, LineCol, LineTable};
 pub use span::{Index, Locatable, Span, Spanned};
 
 use smol_str::SmolStr;
-use std::{ops::Range, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 /// A database that is meant to handle every source related stuff,
 /// like interning files.
+///
+/// A file is identified by its path, which is interned once and keeps
+/// the same [`FileId`] for the whole session. The text of a file is an
+/// input, so it can be changed using `set_file_text`, and only the
+/// queries that depend on the file are recomputed.
+///
+/// [`FileId`]: ./struct.FileId.html
 #[salsa::query_group(SourceDatabaseStorage)]
 pub trait SourceDatabase: salsa::Database {
-    /// Interns the given [`File`] and returns a [`FileId`]
-    /// which can later be used to lookup a file using `lookup_intern_file`.
+    /// Interns the path of a file and returns a [`FileId`]
+    /// which can later be used to lookup the path using `lookup_intern_path`.
     #[salsa::interned]
-    fn intern_file(&self, file: File) -> FileId;
+    fn intern_path(&self, path: SmolStr) -> FileId;
 
-    /// Looks up the given `FileId` and then returns a reference to the source of
-    /// the File.
-    fn source(&self, file: FileId) -> Arc<String>;
+    /// The source code of the file.
+    #[salsa::input]
+    fn file_text(&self, file: FileId) -> Arc<String>;
+
+    /// The files that make up the program that is compiled.
+    #[salsa::input]
+    fn source_root(&self) -> Arc<SourceRoot>;
 
     /// Looks up the given `FileId` and then returns a reference to the name of
     /// the File.
@@ -41,14 +59,114 @@ pub trait SourceDatabase: salsa::Database {
     /// Returns the start index of the line in the file.
     #[salsa::invoke(file::line_start)]
     fn line_start(&self, file: FileId, line_index: usize) -> Option<usize>;
-}
 
-fn source(db: &dyn SourceDatabase, file: FileId) -> Arc<String> {
-    let file = db.lookup_intern_file(file);
-    file.source()
+    /// Returns the precomputed lines of the file, which are used to
+    /// convert between byte indices and line/column positions.
+    #[salsa::invoke(line_col::line_table)]
+    fn line_table(&self, file: FileId) -> Arc<LineTable>;
+
+    /// Returns the line and column of the byte index in the given file.
+    #[salsa::transparent]
+    #[salsa::invoke(line_col::line_col)]
+    fn line_col(&self, file: FileId, index: Index, encoding: Encoding) -> LineCol;
+
+    /// Returns the byte index of the line and column in the given file,
+    /// or `None` if the line doesn't exist.
+    #[salsa::transparent]
+    #[salsa::invoke(line_col::line_col_index)]
+    fn line_col_index(&self, file: FileId, pos: LineCol, encoding: Encoding) -> Option<Index>;
 }
 
 fn name(db: &dyn SourceDatabase, file: FileId) -> Arc<SmolStr> {
-    let file = db.lookup_intern_file(file);
-    file.name()
+    Arc::new(db.lookup_intern_path(file))
+}
+
+/// Returns the file at `path` after setting its text,
+/// which replaces the previous text if the file already exists.
+pub fn set_file(
+    db: &mut dyn SourceDatabase,
+    path: impl Into<SmolStr>,
+    text: impl Into<String>,
+) -> FileId {
+    let file = db.intern_path(path.into());
+    db.set_file_text(file, Arc::new(text.into()));
+    file
+}
+
+/// Every file that belongs to a program, keyed by their path.
+///
+/// The root file is the root module of the program. Every other module
+/// is looked up using the path of the module that imports it.
+/// The items of the prelude, if there is one, are available in every module.
+///
+/// Outer files enclose the root module, like the earlier inputs of the REPL
+/// enclose the current one. Their items are available in the root module,
+/// and items of later outer files shadow the ones of earlier outer files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceRoot {
+    root: FileId,
+    prelude: Option<FileId>,
+    outer: Vec<FileId>,
+    files: BTreeMap<PathBuf, FileId>,
+}
+
+impl SourceRoot {
+    /// Creates a new `SourceRoot` where the root module is the
+    /// file `root` at `path`.
+    pub fn new(path: impl Into<PathBuf>, root: FileId) -> Self {
+        let mut files = BTreeMap::new();
+        files.insert(path.into(), root);
+        Self {
+            root,
+            prelude: None,
+            outer: vec![],
+            files,
+        }
+    }
+
+    /// Adds the prelude at the given path.
+    pub fn set_prelude(&mut self, path: impl Into<PathBuf>, file: FileId) {
+        self.insert(path, file);
+        self.prelude = Some(file);
+    }
+
+    /// Adds an outer file at the given path, which encloses every outer file
+    /// that was added before it.
+    pub fn push_outer(&mut self, path: impl Into<PathBuf>, file: FileId) {
+        self.insert(path, file);
+        self.outer.push(file);
+    }
+
+    /// Adds a file at the given path, replacing the previous file at this path.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, file: FileId) {
+        self.files.insert(path.into(), file);
+    }
+
+    /// Returns the file of the root module.
+    pub fn root(&self) -> FileId {
+        self.root
+    }
+
+    /// Returns the file of the prelude.
+    pub fn prelude(&self) -> Option<FileId> {
+        self.prelude
+    }
+
+    /// Returns every outer file, from the outermost to the innermost one.
+    pub fn outer(&self) -> &[FileId] {
+        &self.outer
+    }
+
+    /// Returns the file at the given path.
+    pub fn file(&self, path: &Path) -> Option<FileId> {
+        self.files.get(path).copied()
+    }
+
+    /// Returns the path of the given file.
+    pub fn path(&self, file: FileId) -> Option<&Path> {
+        self.files
+            .iter()
+            .find(|(_, &id)| id == file)
+            .map(|(path, _)| path.as_path())
+    }
 }
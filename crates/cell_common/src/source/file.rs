@@ -7,43 +7,12 @@ use smol_str::SmolStr;
 use std::{cmp::Ordering, ops::Range, string::String, sync::Arc};
 
 intern_id_struct! {
-    /// An id used to intern a [`File`].
-    ///
-    /// [`File`]: ./struct.File.html
+    /// An id used to intern the path of a file.
     pub struct FileId;
 }
 
-/// A file that can be interned by a [`SourceDatabase`].
-///
-/// A file is composed of a name and a source string.
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
-pub struct File {
-    name: Arc<SmolStr>,
-    source: Arc<String>,
-}
-
-impl File {
-    /// Creates a new `File`.
-    pub fn new(name: impl Into<SmolStr>, source: impl Into<String>) -> Self {
-        Self {
-            name: Arc::new(name.into()),
-            source: Arc::new(source.into()),
-        }
-    }
-
-    /// Returns a reference to the `name` of this file.
-    pub fn name(&self) -> Arc<SmolStr> {
-        Arc::clone(&self.name)
-    }
-
-    /// Returns a reference to the `source` of this file.
-    pub fn source(&self) -> Arc<String> {
-        Arc::clone(&self.source)
-    }
-}
-
 pub fn line_starts(db: &dyn SourceDatabase, file: FileId) -> Arc<Vec<usize>> {
-    let starts = codespan_reporting::files::line_starts(&db.source(file)).collect();
+    let starts = codespan_reporting::files::line_starts(&db.file_text(file)).collect();
     Arc::new(starts)
 }
 
@@ -51,7 +20,7 @@ pub fn line_start(db: &dyn SourceDatabase, file: FileId, line_index: usize) -> O
     let len = db.line_starts(file).len();
     match line_index.cmp(&len) {
         Ordering::Less => db.line_starts(file).get(line_index).copied(),
-        Ordering::Equal => Some(db.source(file).len()),
+        Ordering::Equal => Some(db.file_text(file).len()),
         Ordering::Greater => None,
     }
 }
@@ -112,7 +81,7 @@ impl<'db> Files<'db> for FileCache<'db> {
     }
 
     fn source(&'db self, id: Self::FileId) -> Option<Self::Source> {
-        let source = self.db.source(id);
+        let source = self.db.file_text(id);
         Some(StringRef { arc: source })
     }
 
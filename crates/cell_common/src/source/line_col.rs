@@ -0,0 +1,310 @@
+//! Conversions between byte indices and line/column positions.
+
+use super::{FileId, Index, SourceDatabase, Span};
+use std::{collections::HashMap, sync::Arc};
+
+/// The unit in which the column of a [`LineCol`] is counted.
+///
+/// [`LineCol`]: ./struct.LineCol.html
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Encoding {
+    /// The column counts UTF-8 bytes.
+    Utf8,
+    /// The column counts UTF-16 code units, which is used by the language server protocol.
+    Utf16,
+    /// The column counts unicode scalar values.
+    Char,
+}
+
+impl Encoding {
+    fn len(self, c: char) -> usize {
+        match self {
+            Encoding::Utf8 => c.len_utf8(),
+            Encoding::Utf16 => c.len_utf16(),
+            Encoding::Char => 1,
+        }
+    }
+}
+
+/// A zero based line and column in a file.
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: u32,
+    pub col: u32,
+}
+
+impl LineCol {
+    /// Creates a new `LineCol`.
+    pub fn new(line: u32, col: u32) -> Self {
+        Self { line, col }
+    }
+}
+
+/// A char that takes more than one byte, relative to the start of its line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WideChar {
+    start: usize,
+    c: char,
+}
+
+impl WideChar {
+    fn end(self) -> usize {
+        self.start + self.c.len_utf8()
+    }
+}
+
+/// The line starts of a file, together with every char that is not ASCII.
+///
+/// Lines that only contain ASCII have the same column in every [`Encoding`],
+/// so converting a position in these lines doesn't have to look at the text.
+///
+/// [`Encoding`]: ./enum.Encoding.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineTable {
+    /// The start of every line.
+    starts: Vec<usize>,
+    /// The end of every line, excluding the line terminator.
+    ends: Vec<usize>,
+    /// The wide chars of every line that is not ASCII only.
+    wide: HashMap<u32, Vec<WideChar>>,
+}
+
+impl LineTable {
+    /// Precomputes the lines of the text.
+    pub fn new(text: &str) -> Self {
+        let mut table = Self {
+            starts: vec![0],
+            ends: vec![],
+            wide: HashMap::new(),
+        };
+
+        let mut line = 0;
+        let mut start = 0;
+        for (idx, c) in text.char_indices() {
+            if c == '\n' {
+                let end = if text[..idx].ends_with('\r') {
+                    idx - 1
+                } else {
+                    idx
+                };
+                table.ends.push(end);
+                table.starts.push(idx + 1);
+                line += 1;
+                start = idx + 1;
+            } else if !c.is_ascii() {
+                table.wide.entry(line).or_default().push(WideChar {
+                    start: idx - start,
+                    c,
+                });
+            }
+        }
+        table.ends.push(text.len());
+        table
+    }
+
+    /// Returns the number of lines.
+    pub fn lines(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Returns the position of the byte index.
+    ///
+    /// Indices after the end of the text are moved to the end of the text,
+    /// and indices inside a char are counted as the start of the char.
+    pub fn line_col(&self, index: Index, encoding: Encoding) -> LineCol {
+        let index = usize::from(index).min(*self.ends.last().unwrap());
+        let line = self.starts.partition_point(|&start| start <= index) - 1;
+        let bytes = index - self.starts[line];
+        let mut col = bytes;
+
+        let wide = self.wide.get(&(line as u32)).map_or(&[][..], Vec::as_slice);
+        for c in wide.iter().take_while(|c| c.start < bytes) {
+            if bytes < c.end() {
+                col -= bytes - c.start;
+                break;
+            }
+            col -= c.c.len_utf8() - encoding.len(c.c);
+        }
+        LineCol::new(line as u32, col as u32)
+    }
+
+    /// Returns the byte index of the position, or `None` if the line doesn't exist.
+    ///
+    /// Columns after the end of the line are moved to the end of the line,
+    /// and columns inside a char are moved to the start of the char.
+    pub fn index(&self, pos: LineCol, encoding: Encoding) -> Option<Index> {
+        let line = pos.line as usize;
+        let (start, end) = (*self.starts.get(line)?, self.ends[line]);
+        let mut col = pos.col as usize;
+
+        if let Some(wide) = self.wide.get(&pos.line) {
+            for c in wide {
+                if col <= c.start {
+                    break;
+                }
+                // `col` is in bytes up to `c.start`, but counts in
+                // the encoding after it
+                let units = col - c.start;
+                col = if units < encoding.len(c.c) {
+                    c.start
+                } else {
+                    c.end() + units - encoding.len(c.c)
+                };
+            }
+        }
+        Some(Index::from((start + col).min(end) as u32))
+    }
+
+    /// Returns the positions of the start and end of the span.
+    pub fn span_to_line_cols(&self, span: Span, encoding: Encoding) -> (LineCol, LineCol) {
+        (
+            self.line_col(span.start(), encoding),
+            self.line_col(span.end(), encoding),
+        )
+    }
+
+    /// Returns the span between the two positions.
+    pub fn line_cols_to_span(
+        &self,
+        start: LineCol,
+        end: LineCol,
+        encoding: Encoding,
+    ) -> Option<Span> {
+        let start = self.index(start, encoding)?;
+        let end = self.index(end, encoding)?;
+        Some(Span::new(start, end.max(start)))
+    }
+}
+
+pub fn line_table(db: &dyn SourceDatabase, file: FileId) -> Arc<LineTable> {
+    Arc::new(LineTable::new(&db.file_text(file)))
+}
+
+pub fn line_col(
+    db: &dyn SourceDatabase,
+    file: FileId,
+    index: Index,
+    encoding: Encoding,
+) -> LineCol {
+    db.line_table(file).line_col(index, encoding)
+}
+
+pub fn line_col_index(
+    db: &dyn SourceDatabase,
+    file: FileId,
+    pos: LineCol,
+    encoding: Encoding,
+) -> Option<Index> {
+    db.line_table(file).index(pos, encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODINGS: [Encoding; 3] = [Encoding::Utf8, Encoding::Utf16, Encoding::Char];
+
+    fn line_col(table: &LineTable, index: u32, encoding: Encoding) -> (u32, u32) {
+        let pos = table.line_col(Index::from(index), encoding);
+        (pos.line, pos.col)
+    }
+
+    fn index(table: &LineTable, line: u32, col: u32, encoding: Encoding) -> Option<u32> {
+        table
+            .index(LineCol::new(line, col), encoding)
+            .map(u32::from)
+    }
+
+    #[test]
+    fn ascii() {
+        let table = LineTable::new("abc\n\nde");
+        assert_eq!(table.lines(), 3);
+        for encoding in ENCODINGS {
+            assert_eq!(line_col(&table, 0, encoding), (0, 0));
+            assert_eq!(line_col(&table, 3, encoding), (0, 3));
+            assert_eq!(line_col(&table, 4, encoding), (1, 0));
+            assert_eq!(line_col(&table, 6, encoding), (2, 1));
+            assert_eq!(index(&table, 0, 2, encoding), Some(2));
+            assert_eq!(index(&table, 1, 0, encoding), Some(4));
+            assert_eq!(index(&table, 2, 2, encoding), Some(7));
+        }
+    }
+
+    #[test]
+    fn multibyte() {
+        // `é` is 2 bytes and 1 UTF-16 unit, `𝄞` is 4 bytes and 2 UTF-16 units
+        let table = LineTable::new("aé𝄞b\nxé");
+        let cases = [
+            // index, UTF-8, UTF-16, Char
+            (0, 0, 0, 0),
+            (1, 1, 1, 1),
+            (3, 3, 2, 2),
+            (7, 7, 4, 3),
+            (8, 8, 5, 4),
+        ];
+        for &(idx, utf8, utf16, chars) in &cases {
+            assert_eq!(line_col(&table, idx, Encoding::Utf8), (0, utf8));
+            assert_eq!(line_col(&table, idx, Encoding::Utf16), (0, utf16));
+            assert_eq!(line_col(&table, idx, Encoding::Char), (0, chars));
+            assert_eq!(index(&table, 0, utf8, Encoding::Utf8), Some(idx));
+            assert_eq!(index(&table, 0, utf16, Encoding::Utf16), Some(idx));
+            assert_eq!(index(&table, 0, chars, Encoding::Char), Some(idx));
+        }
+
+        // the columns of wide chars are relative to the start of their line
+        assert_eq!(line_col(&table, 10, Encoding::Utf8), (1, 1));
+        assert_eq!(line_col(&table, 12, Encoding::Utf8), (1, 3));
+        assert_eq!(line_col(&table, 12, Encoding::Utf16), (1, 2));
+        assert_eq!(line_col(&table, 12, Encoding::Char), (1, 2));
+        assert_eq!(index(&table, 1, 2, Encoding::Utf16), Some(12));
+    }
+
+    #[test]
+    fn inside_char() {
+        let table = LineTable::new("aé𝄞b");
+        // byte 2 is inside `é`, bytes 4 to 6 are inside `𝄞`
+        assert_eq!(line_col(&table, 2, Encoding::Utf8), (0, 1));
+        assert_eq!(line_col(&table, 2, Encoding::Utf16), (0, 1));
+        assert_eq!(line_col(&table, 2, Encoding::Char), (0, 1));
+        for idx in 4..7 {
+            assert_eq!(line_col(&table, idx, Encoding::Utf8), (0, 3));
+            assert_eq!(line_col(&table, idx, Encoding::Utf16), (0, 2));
+            assert_eq!(line_col(&table, idx, Encoding::Char), (0, 2));
+        }
+
+        // columns between the bytes or the UTF-16 units of a char
+        assert_eq!(index(&table, 0, 2, Encoding::Utf8), Some(1));
+        assert_eq!(index(&table, 0, 5, Encoding::Utf8), Some(3));
+        assert_eq!(index(&table, 0, 3, Encoding::Utf16), Some(3));
+    }
+
+    #[test]
+    fn past_end() {
+        let table = LineTable::new("aé\nb");
+        for encoding in ENCODINGS {
+            assert_eq!(index(&table, 0, 10, encoding), Some(3));
+            assert_eq!(index(&table, 1, 10, encoding), Some(5));
+            assert_eq!(index(&table, 2, 0, encoding), None);
+            assert_eq!(line_col(&table, 100, encoding), (1, 1));
+        }
+        assert_eq!(line_col(&table, 3, Encoding::Utf8), (0, 3));
+        assert_eq!(line_col(&table, 3, Encoding::Utf16), (0, 2));
+    }
+
+    #[test]
+    fn crlf() {
+        let table = LineTable::new("aé\r\nb\r\n");
+        assert_eq!(table.lines(), 3);
+        for encoding in ENCODINGS {
+            assert_eq!(line_col(&table, 5, encoding), (1, 0));
+            assert_eq!(line_col(&table, 6, encoding), (1, 1));
+            assert_eq!(line_col(&table, 8, encoding), (2, 0));
+            assert_eq!(index(&table, 1, 0, encoding), Some(5));
+            assert_eq!(index(&table, 2, 0, encoding), Some(8));
+        }
+        // the line ends in front of the `\r`
+        assert_eq!(index(&table, 0, 10, Encoding::Utf8), Some(3));
+        assert_eq!(index(&table, 1, 10, Encoding::Char), Some(6));
+        assert_eq!(line_col(&table, 3, Encoding::Char), (0, 2));
+    }
+}
@@ -1,10 +1,7 @@
 //! Types for indexing a range in a source string.
 
 use crate::source::FileId;
-use std::{
-    fmt,
-    ops::{Deref, Range},
-};
+use std::{fmt, ops::Range};
 use text_size::TextRange;
 
 /// A index to a single byte in a string.
@@ -36,7 +33,7 @@ impl Span {
     ///
     /// [`Index`]: ./type.Index.html
     pub fn end(self) -> Index {
-        self.range.start()
+        self.range.end()
     }
 
     /// Returns the length of self
@@ -44,6 +41,18 @@ impl Span {
         self.range.len()
     }
 
+    /// Checks if this span is empty.
+    pub fn is_empty(self) -> bool {
+        self.range.is_empty()
+    }
+
+    /// Returns the smallest span that covers both `self` and `other`.
+    pub fn merge(self, other: Span) -> Span {
+        Span {
+            range: self.range.cover(other.range),
+        }
+    }
+
     /// Check if this span contains the given index.
     pub fn contains(self, index: Index) -> bool {
         self.range.contains(index)
@@ -72,10 +81,10 @@ where
     }
 }
 
-impl Into<Span> for Range<usize> {
-    fn into(self) -> Span {
-        let start = self.start as u32;
-        let end = self.end as u32;
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Span {
+        let start = range.start as u32;
+        let end = range.end as u32;
         Span::new(start, end)
     }
 }
@@ -20,7 +20,7 @@ impl From<Spur> for StringId {
 ///
 /// The `StringInterner` uses an `Arc<ThreadedRodeo>` and thus
 /// can be cloned to create more references.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct StringInterner {
     rodeo: Arc<ThreadedRodeo>,
 }
@@ -28,9 +28,7 @@ pub struct StringInterner {
 impl StringInterner {
     /// Creates a new string interner.
     pub fn new() -> Self {
-        Self {
-            rodeo: Arc::new(ThreadedRodeo::new()),
-        }
+        Self::default()
     }
 
     /// Gets the key for the given val or interns it.
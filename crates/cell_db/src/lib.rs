@@ -0,0 +1,196 @@
+//! The database that combines every query group of the compiler.
+//!
+//! Every tool that works on cell programs, like the compiler driver,
+//! uses a [`RootDatabase`] instead of defining its own database.
+//!
+//! [`RootDatabase`]: ./struct.RootDatabase.html
+
+use cell_common::{
+    source::{self, FileId, SourceDatabase, SourceDatabaseStorage, SourceRoot},
+    strings::StringInterner,
+};
+use cell_mir::db::MirDatabaseStorage;
+use cell_parser::db::{ParseDatabase, ParseDatabaseStorage};
+use cell_syntax::{
+    ast::{Expr, ExprId, Item, ItemId, Pattern, PatternId, Stmt, StmtId, Type, TypeId},
+    db::AstDatabase,
+};
+use cell_typeck::{
+    db::{TypeckDatabase, TypeckDatabaseStorage},
+    module, prelude,
+};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The database that contains every query of the compiler.
+///
+/// A new database already has an empty string interner as its `rodeo`,
+/// but no source root.
+#[salsa::database(
+    SourceDatabaseStorage,
+    ParseDatabaseStorage,
+    TypeckDatabaseStorage,
+    MirDatabaseStorage
+)]
+pub struct RootDatabase {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for RootDatabase {}
+
+impl AstDatabase for RootDatabase {
+    fn lookup_item(&self, id: ItemId) -> Item {
+        self.lookup_intern_item(id)
+    }
+
+    fn lookup_stmt(&self, id: StmtId) -> Stmt {
+        self.lookup_intern_stmt(id)
+    }
+
+    fn lookup_expr(&self, id: ExprId) -> Expr {
+        self.lookup_intern_expr(id)
+    }
+
+    fn lookup_type(&self, id: TypeId) -> Type {
+        self.lookup_intern_type(id)
+    }
+
+    fn lookup_pattern(&self, id: PatternId) -> Pattern {
+        self.lookup_intern_pattern(id)
+    }
+
+    fn insert_item(&self, item: Item) -> ItemId {
+        self.intern_item(item)
+    }
+
+    fn insert_stmt(&self, stmt: Stmt) -> StmtId {
+        self.intern_stmt(stmt)
+    }
+
+    fn insert_expr(&self, expr: Expr) -> ExprId {
+        self.intern_expr(expr)
+    }
+
+    fn insert_type(&self, ty: Type) -> TypeId {
+        self.intern_type(ty)
+    }
+
+    fn insert_pattern(&self, pattern: Pattern) -> PatternId {
+        self.intern_pattern(pattern)
+    }
+}
+
+impl salsa::ParallelDatabase for RootDatabase {
+    fn snapshot(&self) -> salsa::Snapshot<Self> {
+        salsa::Snapshot::new(RootDatabase {
+            storage: self.storage.snapshot(),
+        })
+    }
+}
+
+impl Default for RootDatabase {
+    fn default() -> Self {
+        let mut db = Self {
+            storage: salsa::Storage::default(),
+        };
+        db.set_rodeo(StringInterner::new());
+        db
+    }
+}
+
+impl RootDatabase {
+    /// Creates a database without any files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the file at `path` into the database.
+    ///
+    /// If the file was already loaded, its text is replaced.
+    pub fn load_file(&mut self, path: &Path) -> io::Result<FileId> {
+        let source = fs::read_to_string(path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("failed to read `{}`: {}", path.display(), err),
+            )
+        })?;
+        Ok(source::set_file(self, path.display().to_string(), source))
+    }
+
+    /// Makes the file at `path` the root module of the program, and reads
+    /// every module that is imported by it, directly or indirectly.
+    ///
+    /// Imports of modules that don't exist are ignored here,
+    /// they are reported when the program is checked.
+    pub fn load_program(&mut self, path: &Path) -> io::Result<FileId> {
+        self.load_program_with(path, &HashMap::new())
+    }
+
+    /// Like `load_program`, but the files in `overlay` are not read from
+    /// disk and their text in the overlay is used instead.
+    ///
+    /// This is used by editors, where files can be changed without being saved.
+    pub fn load_program_with(
+        &mut self,
+        path: &Path,
+        overlay: &HashMap<PathBuf, String>,
+    ) -> io::Result<FileId> {
+        let file = self.load_overlay_file(path, overlay)?;
+        let mut root = SourceRoot::new(path, file);
+        prelude::load(self, &mut root);
+        self.set_source_root(Arc::new(root));
+
+        // every new module can import more modules,
+        // so the module tree is rebuilt until no new file is found
+        loop {
+            let tree = self.module_tree();
+            let mut root = (*self.source_root()).clone();
+            let mut changed = false;
+
+            for module in tree.files() {
+                let path = match root.path(module) {
+                    Some(path) => path.to_path_buf(),
+                    None => continue,
+                };
+                for name in self.module_imports(module).iter() {
+                    let name = self.rodeo().resolve(name).to_string();
+                    let candidates = module::module_candidates(&path, module == tree.root(), &name);
+                    if candidates.iter().any(|path| root.file(path).is_some()) {
+                        continue;
+                    }
+                    let exists = |path: &&PathBuf| overlay.contains_key(*path) || path.is_file();
+                    if let Some(path) = candidates.iter().find(exists) {
+                        root.insert(path, self.load_overlay_file(path, overlay)?);
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+            self.set_source_root(Arc::new(root));
+        }
+        Ok(file)
+    }
+
+    /// Loads the file from the overlay, or from disk if it's not in the overlay.
+    fn load_overlay_file(
+        &mut self,
+        path: &Path,
+        overlay: &HashMap<PathBuf, String>,
+    ) -> io::Result<FileId> {
+        match overlay.get(path) {
+            Some(text) => Ok(source::set_file(
+                self,
+                path.display().to_string(),
+                text.clone(),
+            )),
+            None => self.load_file(path),
+        }
+    }
+}
@@ -0,0 +1,915 @@
+use crate::{
+    memory::Memory,
+    value::{Pointer, Value},
+};
+use cell_common::{
+    error::{Error, RuntimeError},
+    profiler,
+    source::{FileId, Locatable, Span},
+    strings::StringId,
+};
+use cell_syntax::ast::{
+    Assign, BinOp, Binary, Block, CmpOp, ExprId, ExprKind, If, ItemId, ItemKind, Literal, Match,
+    MethodCall, PatternId, PatternKind, StmtId, StmtKind, UnOp, Unary, While,
+};
+use cell_typeck::{
+    consteval::{self, ConstValue},
+    db::TypeckDatabase,
+    infer::{Callee, LocalId, Res, TypeckResults},
+    lower::AdtDef,
+    prelude::{self, HostFn},
+    scope, traits,
+    ty::{Ty, TyKind},
+};
+use std::{
+    cell::Cell, collections::HashMap, convert::TryFrom, io::Write, panic, sync::Arc, thread,
+};
+
+/// The maximum number of nested calls, before the program is stopped with
+/// a stack overflow. This is about as deep as the VM can recurse.
+const MAX_DEPTH: usize = 1 << 17;
+
+/// The size of the stack of the thread that [`with_stack`] starts.
+///
+/// Every call is evaluated recursively, so a debug build may run out of
+/// stack before `MAX_DEPTH` is reached, and the program is stopped when
+/// less than `STACK_RESERVE` bytes of it are left.
+const STACK_SIZE: usize = 1 << 30;
+const STACK_RESERVE: usize = 16 << 20;
+
+/// The maximum number of values a single call to `alloc` can allocate.
+const MAX_ALLOC: usize = 1 << 24;
+
+thread_local! {
+    /// The address of the start of the stack, if this thread was started by [`with_stack`].
+    static STACK_START: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Why the evaluation of an expression stopped early.
+enum Control {
+    Return(Value),
+    Break,
+    Continue,
+    Error(Locatable<Error>),
+}
+
+type EvalResult<T> = Result<T, Control>;
+
+/// A function that is currently executed.
+struct Frame {
+    file: FileId,
+    results: Arc<TypeckResults>,
+    /// The arguments for the generic parameters of the function.
+    generic_args: Vec<Ty>,
+    /// The allocation of every local, indexed by `LocalId`.
+    locals: Vec<Pointer>,
+    /// Allocations for values that had to be stored in memory to
+    /// take a pointer to them, e.g. the receiver of `make().method()`.
+    temps: Vec<Pointer>,
+}
+
+/// The state of an interpreter that doesn't borrow the database, which is
+/// the memory and the value of every `const` and `static` that was used.
+///
+/// The REPL keeps it between inputs, while the database is changed.
+#[derive(Default)]
+pub struct State {
+    memory: Memory,
+    consts: HashMap<ItemId, Value>,
+    statics: HashMap<ItemId, Pointer>,
+}
+
+/// Runs `f` on a thread with a stack that is large enough for deep recursion.
+///
+/// An interpreter on any other thread only counts its calls, so it may run out
+/// of stack before it reaches `MAX_DEPTH`. The interpreter borrows the
+/// database, so `f` usually creates it from a snapshot of the database.
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        let handle = thread::Builder::new()
+            .name("interpreter".to_string())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let start = 0u8;
+                STACK_START.with(|stack| stack.set(Some(&start as *const u8 as usize)));
+                f()
+            })
+            .expect("failed to start the interpreter thread");
+        handle
+            .join()
+            .unwrap_or_else(|err| panic::resume_unwind(err))
+    })
+}
+
+/// Checks if less than `STACK_RESERVE` bytes of the stack of [`with_stack`] are left.
+fn stack_exhausted() -> bool {
+    let here = 0u8;
+    let here = &here as *const u8 as usize;
+    STACK_START.with(|start| match start.get() {
+        Some(start) => start.abs_diff(here) > STACK_SIZE - STACK_RESERVE,
+        None => false,
+    })
+}
+
+/// Evaluates type checked functions directly.
+pub struct Interpreter<'db> {
+    db: &'db dyn TypeckDatabase,
+    memory: Memory,
+    frames: Vec<Frame>,
+    consts: HashMap<ItemId, Value>,
+    statics: HashMap<ItemId, Pointer>,
+    /// Where `io.println` writes to.
+    out: Box<dyn Write + 'db>,
+}
+
+impl<'db> Interpreter<'db> {
+    /// Creates an interpreter that prints to stdout.
+    pub fn new(db: &'db dyn TypeckDatabase) -> Self {
+        Self::with_output(db, std::io::stdout())
+    }
+
+    /// Creates an interpreter that prints to the given writer.
+    pub fn with_output(db: &'db dyn TypeckDatabase, out: impl Write + 'db) -> Self {
+        Self::with_state(db, State::default(), out)
+    }
+
+    /// Creates an interpreter that continues with the state of an earlier one.
+    pub fn with_state(db: &'db dyn TypeckDatabase, state: State, out: impl Write + 'db) -> Self {
+        Self {
+            db,
+            memory: state.memory,
+            frames: vec![],
+            consts: state.consts,
+            statics: state.statics,
+            out: Box::new(out),
+        }
+    }
+
+    /// Returns the state of this interpreter, so it can be continued later.
+    pub fn into_state(self) -> State {
+        State {
+            memory: self.memory,
+            consts: self.consts,
+            statics: self.statics,
+        }
+    }
+
+    /// Calls the `main` function of the root module.
+    pub fn run_main(&mut self) -> Result<Value, Locatable<Error>> {
+        let db = self.db;
+        let root = db.module_tree().root();
+        let name = db.rodeo().get_or_intern("main");
+        let main = scope::resolve_value(db, root, name)
+            .filter(|&item| matches!(db.lookup_intern_item(item).kind, ItemKind::Def(_)));
+
+        match main {
+            Some(main) => self.call(main, &[], vec![]),
+            None => Err(Locatable::new(
+                RuntimeError::NoMain.into(),
+                root,
+                Span::default(),
+            )),
+        }
+    }
+
+    /// Calls a function with the given generic and value arguments.
+    ///
+    /// For methods, the first argument is `this`.
+    pub fn call(
+        &mut self,
+        def: ItemId,
+        generic_args: &[Ty],
+        args: Vec<Value>,
+    ) -> Result<Value, Locatable<Error>> {
+        let _profiler = profiler::trace("Interp", "Call");
+
+        let item = self.db.lookup_intern_item(def);
+        let span = item.kind.name().map_or(item.span, |name| name.span);
+        self.invoke(def, generic_args.to_vec(), args, (item.file, span))
+    }
+
+    /// Evaluates the initializer of a `const` or `static`.
+    pub fn eval_global(&mut self, item: ItemId) -> Result<Value, Locatable<Error>> {
+        let data = self.db.lookup_intern_item(item);
+        let global = match data.kind {
+            ItemKind::Const(global) | ItemKind::Static(global) => global,
+            _ => panic!("`eval_global` called on an item that is not a global"),
+        };
+
+        let results = self.db.typeck(item);
+        if let Some(err) = results.errors.first() {
+            return Err(err.clone());
+        }
+
+        self.frames.push(Frame {
+            file: data.file,
+            results,
+            generic_args: vec![],
+            locals: vec![],
+            temps: vec![],
+        });
+        let result = self.eval_expr(global.val);
+        self.pop_frame();
+
+        match result {
+            Ok(val) | Err(Control::Return(val)) => Ok(val),
+            Err(Control::Error(err)) => Err(err),
+            Err(Control::Break) | Err(Control::Continue) => {
+                unreachable!("`break` outside of a loop")
+            }
+        }
+    }
+
+    fn invoke(
+        &mut self,
+        def: ItemId,
+        generic_args: Vec<Ty>,
+        args: Vec<Value>,
+        (file, span): (FileId, Span),
+    ) -> Result<Value, Locatable<Error>> {
+        let db = self.db;
+        if let Some(host) = prelude::host_fn(db, def) {
+            return self.host(host, args, file, span);
+        }
+
+        let (def, generic_args) = traits::resolve_instance(db, def, &generic_args);
+        let item = db.lookup_intern_item(def);
+        let body = match &item.kind {
+            ItemKind::Def(def) => def.body.clone(),
+            _ => None,
+        };
+        let body = match body {
+            Some(body) => body,
+            None => return Err(Locatable::new(RuntimeError::Unreachable.into(), file, span)),
+        };
+
+        if self.frames.len() >= MAX_DEPTH || stack_exhausted() {
+            let name = item
+                .kind
+                .name()
+                .map_or_else(String::new, |name| db.rodeo().resolve(&name.id).to_string());
+            return Err(Locatable::new(
+                RuntimeError::StackOverflow { name }.into(),
+                file,
+                span,
+            ));
+        }
+
+        self.push_frame(def, item.file, generic_args, args)?;
+        let result = self.eval_block(&body).map(|()| Value::unit());
+        self.pop_frame();
+        finish(result)
+    }
+
+    /// Evaluates the body of a function without parameters, where the locals
+    /// are typed by `results` and the first locals are already stored in `env`.
+    /// Every other local is allocated, like in a call.
+    ///
+    /// Returns the value of the last statement, instead of the value the
+    /// function returns, together with the pointer of every local. The locals
+    /// stay allocated, so they can be the `env` of another function.
+    /// This is how the REPL evaluates an input.
+    pub fn eval_body(
+        &mut self,
+        def: ItemId,
+        results: Arc<TypeckResults>,
+        env: Vec<Pointer>,
+    ) -> Result<(Value, Vec<Pointer>), Locatable<Error>> {
+        let item = self.db.lookup_intern_item(def);
+        let body = match item.kind {
+            ItemKind::Def(def) => def.body,
+            _ => None,
+        };
+        let body = body.expect("`eval_body` called on an item without a body");
+        if let Some(err) = results.errors.first() {
+            return Err(err.clone());
+        }
+
+        let outer = env.len();
+        let mut locals = env;
+        while locals.len() < results.locals.len() {
+            locals.push(self.memory.alloc(Value::unit()));
+        }
+        self.frames.push(Frame {
+            file: item.file,
+            results,
+            generic_args: vec![],
+            locals,
+            temps: vec![],
+        });
+
+        let mut result = Ok(Value::unit());
+        for &stmt in &body.stmts {
+            result = self.eval_stmt(stmt);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        let frame = self.frames.pop().expect("no frame to pop");
+        for ptr in &frame.temps {
+            self.memory.free(ptr);
+        }
+        match finish(result) {
+            Ok(val) => Ok((val, frame.locals)),
+            Err(err) => {
+                for ptr in &frame.locals[outer..] {
+                    self.memory.free(ptr);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Type checks the function and pushes a frame for it,
+    /// where the parameters are initialized with `args`.
+    fn push_frame(
+        &mut self,
+        def: ItemId,
+        file: FileId,
+        generic_args: Vec<Ty>,
+        args: Vec<Value>,
+    ) -> Result<(), Locatable<Error>> {
+        let results = self.db.typeck(def);
+        if let Some(err) = results.errors.first() {
+            return Err(err.clone());
+        }
+
+        let locals = results
+            .locals
+            .iter()
+            .map(|_| self.memory.alloc(Value::unit()))
+            .collect::<Vec<_>>();
+        // `this` is passed as the first argument
+        let params = results
+            .this
+            .into_iter()
+            .chain(results.params.iter().copied());
+        for (local, arg) in params.zip(args) {
+            self.memory.write(&locals[local.0 as usize], arg);
+        }
+
+        self.frames.push(Frame {
+            file,
+            results,
+            generic_args,
+            locals,
+            temps: vec![],
+        });
+        Ok(())
+    }
+
+    fn pop_frame(&mut self) {
+        let frame = self.frames.pop().expect("no frame to pop");
+        for ptr in frame.locals.iter().chain(&frame.temps) {
+            self.memory.free(ptr);
+        }
+    }
+
+    fn host(
+        &mut self,
+        host: HostFn,
+        args: Vec<Value>,
+        file: FileId,
+        span: Span,
+    ) -> Result<Value, Locatable<Error>> {
+        match host {
+            HostFn::Println => {
+                // the first argument is the pointer to `io`
+                let text = args
+                    .get(1)
+                    .map_or_else(String::new, |val| val.display(self.db));
+                writeln!(self.out, "{}", text).map_err(|err| {
+                    let err = RuntimeError::Output {
+                        message: err.to_string(),
+                    };
+                    Locatable::new(err.into(), file, span)
+                })?;
+                Ok(Value::unit())
+            }
+            HostFn::Alloc => {
+                let count = match args.first() {
+                    Some(Value::Scalar(ConstValue::Int(count))) => {
+                        usize::try_from(count.as_u128()).unwrap_or(usize::MAX)
+                    }
+                    _ => 0,
+                };
+                if count > MAX_ALLOC {
+                    return Err(Locatable::new(RuntimeError::OutOfMemory.into(), file, span));
+                }
+                // the values are uninitialized, so any value works
+                let ptr = self.memory.alloc_many(vec![Value::unit(); count]);
+                Ok(Value::Pointer(ptr))
+            }
+        }
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("no active frame")
+    }
+
+    fn results(&self) -> &TypeckResults {
+        &self.frame().results
+    }
+
+    fn error(&self, err: RuntimeError, span: Span) -> Control {
+        Control::Error(Locatable::new(err.into(), self.frame().file, span))
+    }
+
+    fn local(&self, local: LocalId) -> Pointer {
+        self.frame().locals[local.0 as usize].clone()
+    }
+
+    fn read(&self, ptr: &Pointer, span: Span) -> EvalResult<Value> {
+        match self.memory.read(ptr) {
+            Some(val) => Ok(val.clone()),
+            None => Err(self.error(RuntimeError::InvalidPointer, span)),
+        }
+    }
+
+    fn write(&mut self, ptr: &Pointer, val: Value, span: Span) -> EvalResult<()> {
+        match self.memory.write(ptr, val) {
+            Some(()) => Ok(()),
+            None => Err(self.error(RuntimeError::InvalidPointer, span)),
+        }
+    }
+
+    fn eval_block(&mut self, block: &Block) -> EvalResult<()> {
+        for &stmt in &block.stmts {
+            self.eval_stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluates a statement and returns the value of it,
+    /// which is unit for a `var`.
+    fn eval_stmt(&mut self, stmt: StmtId) -> EvalResult<Value> {
+        match self.db.lookup_intern_stmt(stmt).kind {
+            StmtKind::Var(var) => {
+                let val = self.eval_expr(var.val)?;
+                let local = self.local(self.results().vars[&stmt]);
+                self.write(&local, val, var.name.span)?;
+                Ok(Value::unit())
+            }
+            StmtKind::Expr(expr) => self.eval_expr(expr),
+        }
+    }
+
+    fn eval_expr(&mut self, id: ExprId) -> EvalResult<Value> {
+        let expr = self.db.lookup_intern_expr(id);
+        let span = expr.span;
+        match expr.kind {
+            ExprKind::Literal(lit) => self.literal(&lit, false, self.results().type_of(id), span),
+            ExprKind::Binary(binary) => self.binary(&binary, span),
+            ExprKind::Comparison(cmp) => {
+                let left = self.eval_expr(cmp.left)?;
+                let right = self.eval_expr(cmp.right)?;
+                let result = self.compare(*cmp.op.data(), &left, &right, span)?;
+                Ok(Value::Scalar(ConstValue::Bool(result)))
+            }
+            ExprKind::Unary(unary) => self.unary(&unary, span),
+            ExprKind::Tuple(tuple) => {
+                let vals = tuple
+                    .values
+                    .into_iter()
+                    .map(|val| self.eval_expr(val))
+                    .collect::<EvalResult<_>>()?;
+                Ok(Value::Tuple(vals))
+            }
+            ExprKind::NamedTuple(tuple) => {
+                let mut vals = tuple
+                    .values
+                    .into_iter()
+                    .map(|(name, val)| Ok((name.id, self.eval_expr(val)?)))
+                    .collect::<EvalResult<Vec<_>>>()?;
+
+                // fields are stored in the order of the type, not the order
+                // they are written in
+                let order = match self.results().type_of(id).kind(self.db) {
+                    TyKind::NamedTuple(fields) => fields.iter().map(|(name, _)| *name).collect(),
+                    TyKind::Adt(item, _) => match &*self.db.adt_def(item) {
+                        AdtDef::Struct { fields } => {
+                            let order = fields.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                            let fields = sort_fields(&mut vals, &order);
+                            return Ok(Value::Struct(item, fields));
+                        }
+                        AdtDef::Union { .. } => vec![],
+                    },
+                    _ => vec![],
+                };
+                let fields = sort_fields(&mut vals, &order);
+                Ok(Value::NamedTuple(order.into_iter().zip(fields).collect()))
+            }
+            ExprKind::If(if_) => {
+                self.if_(&if_)?;
+                Ok(Value::unit())
+            }
+            ExprKind::Match(match_) => self.match_(&match_, span),
+            ExprKind::While(while_) => {
+                self.while_(&while_)?;
+                Ok(Value::unit())
+            }
+            ExprKind::Block(block) => {
+                self.eval_block(&block)?;
+                Ok(Value::unit())
+            }
+            ExprKind::Path(_) => match self.results().resolutions[&id] {
+                Res::Local(local) => self.read(&self.local(local), span),
+                Res::Variant(union, variant) => Ok(Value::Variant(union, variant, None)),
+                Res::Def(item) => match self.db.lookup_intern_item(item).kind {
+                    ItemKind::Static(_) => {
+                        let ptr = self.static_ptr(item)?;
+                        self.read(&ptr, span)
+                    }
+                    _ => self.const_(item),
+                },
+            },
+            ExprKind::Call(call) => {
+                let args = call
+                    .args
+                    .iter()
+                    .map(|&arg| self.eval_expr(arg))
+                    .collect::<EvalResult<Vec<_>>>()?;
+                let callee = self.results().callees[&id].clone();
+                self.callee(callee, args, span)
+            }
+            ExprKind::MethodCall(call) => self.method_call(id, &call, span),
+            ExprKind::Field(field) => {
+                let mut val = self.eval_expr(field.val)?;
+                while let Value::Pointer(ptr) = val {
+                    val = self.read(&ptr, span)?;
+                }
+                let idx = self.results().fields[&id];
+                match val.field(idx) {
+                    Some(field) => Ok(field.clone()),
+                    None => Err(self.error(RuntimeError::InvalidPointer, span)),
+                }
+            }
+            ExprKind::Assign(assign) => {
+                self.assign(&assign, span)?;
+                Ok(Value::unit())
+            }
+            ExprKind::Return(val) => {
+                let val = match val {
+                    Some(val) => self.eval_expr(val)?,
+                    None => Value::unit(),
+                };
+                Err(Control::Return(val))
+            }
+            ExprKind::Break => Err(Control::Break),
+            ExprKind::Continue => Err(Control::Continue),
+            ExprKind::This => {
+                let this = self.results().this.expect("`this` outside of a method");
+                self.read(&self.local(this), span)
+            }
+        }
+    }
+
+    /// Returns a pointer to the value of a place expression.
+    ///
+    /// Any other expression is evaluated and stored in a temporary.
+    fn place(&mut self, id: ExprId) -> EvalResult<Pointer> {
+        let expr = self.db.lookup_intern_expr(id);
+        match expr.kind {
+            ExprKind::Path(_) => match self.results().resolutions[&id] {
+                Res::Local(local) => return Ok(self.local(local)),
+                Res::Def(item) => {
+                    if let ItemKind::Static(_) = self.db.lookup_intern_item(item).kind {
+                        return self.static_ptr(item);
+                    }
+                }
+                Res::Variant(..) => {}
+            },
+            ExprKind::This => {
+                let this = self.results().this.expect("`this` outside of a method");
+                return Ok(self.local(this));
+            }
+            ExprKind::Unary(Unary { op, val }) if *op.data() == UnOp::Deref => {
+                return match self.eval_expr(val)? {
+                    Value::Pointer(ptr) => Ok(ptr),
+                    _ => unreachable!("dereferenced a value that is not a pointer"),
+                };
+            }
+            ExprKind::Field(field) => {
+                let mut ptr = self.place(field.val)?;
+                let mut ty = self.results().type_of(field.val);
+                while let TyKind::Pointer(inner) = ty.kind(self.db) {
+                    ptr = match self.read(&ptr, expr.span)? {
+                        Value::Pointer(ptr) => ptr,
+                        _ => unreachable!("dereferenced a value that is not a pointer"),
+                    };
+                    ty = inner;
+                }
+                return Ok(ptr.field(self.results().fields[&id]));
+            }
+            _ => {}
+        }
+
+        let val = self.eval_expr(id)?;
+        let ptr = self.memory.alloc(val);
+        self.frames
+            .last_mut()
+            .expect("no active frame")
+            .temps
+            .push(ptr.clone());
+        Ok(ptr)
+    }
+
+    fn literal(&self, lit: &Literal, negate: bool, ty: Ty, span: Span) -> EvalResult<Value> {
+        consteval::eval_literal(lit, negate, &ty.kind(self.db))
+            .map(Value::Scalar)
+            .map_err(|err| self.error(RuntimeError::Arithmetic(err), span))
+    }
+
+    fn binary(&mut self, binary: &Binary, span: Span) -> EvalResult<Value> {
+        let op = *binary.op.data();
+        let left = self.eval_expr(binary.left)?;
+        match (op, &left) {
+            (BinOp::LogicalAnd, Value::Scalar(ConstValue::Bool(false)))
+            | (BinOp::LogicalOr, Value::Scalar(ConstValue::Bool(true))) => return Ok(left),
+            _ => {}
+        }
+        let right = self.eval_expr(binary.right)?;
+        self.binary_op(op, left, right, span)
+    }
+
+    fn binary_op(&self, op: BinOp, left: Value, right: Value, span: Span) -> EvalResult<Value> {
+        match (left, right) {
+            (Value::Pointer(ptr), Value::Scalar(ConstValue::Int(count))) => {
+                let count = if count.is_signed() {
+                    count.as_i128()
+                } else {
+                    count.as_u128() as i128
+                };
+                match op {
+                    BinOp::Add => Ok(Value::Pointer(ptr.offset(count))),
+                    BinOp::Sub => Ok(Value::Pointer(ptr.offset(-count))),
+                    _ => unreachable!("invalid pointer arithmetic"),
+                }
+            }
+            (Value::Scalar(left), Value::Scalar(right)) => consteval::binary_op(op, left, right)
+                .map(Value::Scalar)
+                .map_err(|err| self.error(RuntimeError::Arithmetic(err), span)),
+            _ => unreachable!("invalid operands for `{}`", op),
+        }
+    }
+
+    fn compare(&self, op: CmpOp, left: &Value, right: &Value, span: Span) -> EvalResult<bool> {
+        match (left, right) {
+            (Value::Scalar(left), Value::Scalar(right)) => consteval::compare(op, left, right)
+                .map_err(|err| self.error(RuntimeError::Arithmetic(err), span)),
+            _ => match op {
+                CmpOp::Equal => Ok(left == right),
+                CmpOp::NotEqual => Ok(left != right),
+                _ => unreachable!("invalid operands for `{}`", op),
+            },
+        }
+    }
+
+    fn unary(&mut self, unary: &Unary, span: Span) -> EvalResult<Value> {
+        let op = *unary.op.data();
+        match op {
+            UnOp::Ref => Ok(Value::Pointer(self.place(unary.val)?)),
+            UnOp::Deref => match self.eval_expr(unary.val)? {
+                Value::Pointer(ptr) => self.read(&ptr, span),
+                _ => unreachable!("dereferenced a value that is not a pointer"),
+            },
+            UnOp::Not | UnOp::Plus | UnOp::Minus => {
+                // negative literals are evaluated directly, so the minimum
+                // value of a signed integer doesn't overflow
+                if op == UnOp::Minus {
+                    if let ExprKind::Literal(lit) = self.db.lookup_intern_expr(unary.val).kind {
+                        let ty = self.results().type_of(unary.val);
+                        return self.literal(&lit, true, ty, span);
+                    }
+                }
+                match self.eval_expr(unary.val)? {
+                    Value::Scalar(val) => consteval::unary_op(op, val)
+                        .map(Value::Scalar)
+                        .map_err(|err| self.error(RuntimeError::Arithmetic(err), span)),
+                    _ => unreachable!("invalid operand for a unary operator"),
+                }
+            }
+        }
+    }
+
+    fn eval_bool(&mut self, id: ExprId) -> EvalResult<bool> {
+        match self.eval_expr(id)? {
+            Value::Scalar(ConstValue::Bool(val)) => Ok(val),
+            _ => unreachable!("condition is not a `bool`"),
+        }
+    }
+
+    fn if_(&mut self, if_: &If) -> EvalResult<()> {
+        if self.eval_bool(if_.cond)? {
+            return self.eval_block(&if_.then);
+        }
+        for arm in &if_.arms {
+            if self.eval_bool(arm.cond)? {
+                return self.eval_block(&arm.action);
+            }
+        }
+        match &if_.else_ {
+            Some(block) => self.eval_block(block),
+            None => Ok(()),
+        }
+    }
+
+    fn while_(&mut self, while_: &While) -> EvalResult<()> {
+        loop {
+            if let Some(cond) = while_.cond {
+                if !self.eval_bool(cond)? {
+                    return Ok(());
+                }
+            }
+            match self.eval_block(&while_.block) {
+                Ok(()) | Err(Control::Continue) => {}
+                Err(Control::Break) => return Ok(()),
+                Err(control) => return Err(control),
+            }
+        }
+    }
+
+    fn match_(&mut self, match_: &Match, span: Span) -> EvalResult<Value> {
+        let val = self.eval_expr(match_.val)?;
+        for arm in &match_.arms {
+            if self.pattern(arm.pattern, &val)? {
+                return self.eval_expr(arm.body);
+            }
+        }
+        Err(self.error(RuntimeError::Unreachable, span))
+    }
+
+    /// Tests if the value matches the pattern, and binds the
+    /// value to every binding inside the pattern.
+    fn pattern(&mut self, id: PatternId, val: &Value) -> EvalResult<bool> {
+        let db = self.db;
+        let pattern = db.lookup_intern_pattern(id);
+        let span = pattern.span;
+        match pattern.kind {
+            PatternKind::Wildcard => Ok(true),
+            PatternKind::Binding(_) => {
+                let local = self.local(self.results().bindings[&id]);
+                self.write(&local, val.clone(), span)?;
+                Ok(true)
+            }
+            PatternKind::Literal(lit) => {
+                let ty = self.results().pattern_types[&id];
+                let lit = self.literal(&lit, false, ty, span)?;
+                self.compare(CmpOp::Equal, val, &lit, span)
+            }
+            PatternKind::Range { start, end } => {
+                let ty = self.results().pattern_types[&id];
+                let start = self.literal(&start, false, ty, span)?;
+                let end = self.literal(&end, false, ty, span)?;
+                Ok(self.compare(CmpOp::GreaterEqual, val, &start, span)?
+                    && self.compare(CmpOp::LessEqual, val, &end, span)?)
+            }
+            PatternKind::Tuple(patterns) => {
+                for (idx, pattern) in patterns.into_iter().enumerate() {
+                    let field = val
+                        .field(idx as u32)
+                        .expect("tuple pattern has too many fields");
+                    if !self.pattern(pattern, field)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            PatternKind::NamedTuple(fields) | PatternKind::Struct { fields, .. } => {
+                for (name, pattern) in fields {
+                    let field = match val {
+                        Value::NamedTuple(vals) => vals
+                            .iter()
+                            .find(|(field, _)| *field == name.id)
+                            .map(|(_, val)| val),
+                        Value::Struct(item, vals) => db
+                            .adt_def(*item)
+                            .field(name.id)
+                            .and_then(|(idx, _)| vals.get(idx)),
+                        _ => None,
+                    };
+                    let field = field.expect("pattern contains an unknown field");
+                    if !self.pattern(pattern, field)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            PatternKind::Variant { data, .. } => {
+                let (_, idx) = self.results().pattern_variants[&id];
+                match val {
+                    Value::Variant(_, variant, val) if *variant == idx => match (data, val) {
+                        (Some(pattern), Some(val)) => self.pattern(pattern, val),
+                        (Some(pattern), None) => self.pattern(pattern, &Value::unit()),
+                        (None, _) => Ok(true),
+                    },
+                    _ => Ok(false),
+                }
+            }
+        }
+    }
+
+    fn method_call(&mut self, id: ExprId, call: &MethodCall, span: Span) -> EvalResult<Value> {
+        let callee = self.results().callees[&id].clone();
+        let mut args = vec![];
+        if let Callee::Fn { .. } = callee {
+            let receiver = self.results().receivers[&id];
+            let mut ptr = self.place(call.receiver)?;
+            for _ in 0..receiver.derefs {
+                ptr = match self.read(&ptr, span)? {
+                    Value::Pointer(ptr) => ptr,
+                    _ => unreachable!("dereferenced a value that is not a pointer"),
+                };
+            }
+            args.push(if receiver.autoref {
+                Value::Pointer(ptr)
+            } else {
+                self.read(&ptr, span)?
+            });
+        }
+
+        for &arg in &call.args {
+            args.push(self.eval_expr(arg)?);
+        }
+        self.callee(callee, args, span)
+    }
+
+    fn callee(&mut self, callee: Callee, args: Vec<Value>, span: Span) -> EvalResult<Value> {
+        match callee {
+            Callee::Fn { def, args: tys } => {
+                let frame = self.frame();
+                let tys = tys
+                    .into_iter()
+                    .map(|ty| ty.subst(self.db, &frame.generic_args))
+                    .collect();
+                let file = frame.file;
+                self.invoke(def, tys, args, (file, span))
+                    .map_err(Control::Error)
+            }
+            Callee::Variant { union, variant, .. } => {
+                let data = args.into_iter().next().map(Box::new);
+                Ok(Value::Variant(union, variant, data))
+            }
+        }
+    }
+
+    fn assign(&mut self, assign: &Assign, span: Span) -> EvalResult<()> {
+        let ptr = self.place(assign.target)?;
+        let val = match &assign.op {
+            Some(op) => {
+                let current = self.read(&ptr, span)?;
+                let val = self.eval_expr(assign.val)?;
+                self.binary_op(*op.data(), current, val, span)?
+            }
+            None => self.eval_expr(assign.val)?,
+        };
+        self.write(&ptr, val, span)
+    }
+
+    fn const_(&mut self, item: ItemId) -> EvalResult<Value> {
+        if let Some(val) = self.consts.get(&item) {
+            return Ok(val.clone());
+        }
+        let val = self.eval_global(item).map_err(Control::Error)?;
+        self.consts.insert(item, val.clone());
+        Ok(val)
+    }
+
+    /// Returns the allocation of a `static`, which is
+    /// initialized the first time it's used.
+    fn static_ptr(&mut self, item: ItemId) -> EvalResult<Pointer> {
+        if let Some(ptr) = self.statics.get(&item) {
+            return Ok(ptr.clone());
+        }
+        let val = self.eval_global(item).map_err(Control::Error)?;
+        let ptr = self.memory.alloc(val);
+        self.statics.insert(item, ptr.clone());
+        Ok(ptr)
+    }
+}
+
+/// Turns the result of evaluating a function body into the value it returns.
+fn finish(result: EvalResult<Value>) -> Result<Value, Locatable<Error>> {
+    match result {
+        Ok(val) | Err(Control::Return(val)) => Ok(val),
+        Err(Control::Error(err)) => Err(err),
+        Err(Control::Break) | Err(Control::Continue) => {
+            unreachable!("`break` outside of a loop")
+        }
+    }
+}
+
+/// Sorts the values of a named tuple expression by the given field order.
+fn sort_fields(vals: &mut Vec<(StringId, Value)>, order: &[StringId]) -> Vec<Value> {
+    order
+        .iter()
+        .map(|name| {
+            let idx = vals
+                .iter()
+                .position(|(field, _)| field == name)
+                .expect("named tuple is missing a field");
+            vals.swap_remove(idx).1
+        })
+        .collect()
+}
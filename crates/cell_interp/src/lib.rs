@@ -0,0 +1,12 @@
+//! A tree-walking interpreter, that evaluates type checked functions
+//! directly, without lowering them first.
+//!
+//! Integers have the exact width and signedness of their type, and every
+//! operation that overflows, divides by zero or shifts by too many bits
+//! stops the program with an error.
+
+mod interp;
+mod memory;
+pub mod value;
+
+pub use interp::{with_stack, Interpreter, State};
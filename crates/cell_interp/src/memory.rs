@@ -0,0 +1,87 @@
+//! The memory that stores every local and `static` of a running program.
+
+use crate::value::{Pointer, Value};
+use std::convert::TryFrom;
+
+/// A single allocation, that stores one or more values.
+struct Allocation {
+    /// Increased every time the slot of this allocation is reused,
+    /// to detect pointers to freed allocations.
+    generation: u32,
+    values: Option<Vec<Value>>,
+}
+
+#[derive(Default)]
+pub(crate) struct Memory {
+    allocs: Vec<Allocation>,
+    /// Slots of allocations that were freed.
+    free: Vec<u32>,
+}
+
+impl Memory {
+    /// Allocates a new value and returns a pointer to it.
+    pub fn alloc(&mut self, val: Value) -> Pointer {
+        self.alloc_many(vec![val])
+    }
+
+    /// Allocates multiple values next to each other and
+    /// returns a pointer to the first one.
+    pub fn alloc_many(&mut self, vals: Vec<Value>) -> Pointer {
+        let alloc = match self.free.pop() {
+            Some(alloc) => {
+                let slot = &mut self.allocs[alloc as usize];
+                slot.generation += 1;
+                slot.values = Some(vals);
+                alloc
+            }
+            None => {
+                self.allocs.push(Allocation {
+                    generation: 0,
+                    values: Some(vals),
+                });
+                self.allocs.len() as u32 - 1
+            }
+        };
+        Pointer::new(alloc, self.allocs[alloc as usize].generation)
+    }
+
+    /// Frees the allocation the pointer points to.
+    pub fn free(&mut self, ptr: &Pointer) {
+        let slot = &mut self.allocs[ptr.alloc as usize];
+        if slot.generation == ptr.generation && slot.values.take().is_some() {
+            self.free.push(ptr.alloc);
+        }
+    }
+
+    /// Returns the value the pointer points to, or `None` if the
+    /// pointer is dangling or out of bounds.
+    pub fn read(&self, ptr: &Pointer) -> Option<&Value> {
+        let slot = self.allocs.get(ptr.alloc as usize)?;
+        if slot.generation != ptr.generation {
+            return None;
+        }
+        let offset = usize::try_from(ptr.offset).ok()?;
+        let mut val = slot.values.as_ref()?.get(offset)?;
+        for &idx in &ptr.path {
+            val = val.field(idx)?;
+        }
+        Some(val)
+    }
+
+    /// Overwrites the value the pointer points to.
+    ///
+    /// Returns `None` if the pointer is dangling or out of bounds.
+    pub fn write(&mut self, ptr: &Pointer, new: Value) -> Option<()> {
+        let slot = self.allocs.get_mut(ptr.alloc as usize)?;
+        if slot.generation != ptr.generation {
+            return None;
+        }
+        let offset = usize::try_from(ptr.offset).ok()?;
+        let mut val = slot.values.as_mut()?.get_mut(offset)?;
+        for &idx in &ptr.path {
+            val = val.field_mut(idx)?;
+        }
+        *val = new;
+        Some(())
+    }
+}
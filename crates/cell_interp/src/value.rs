@@ -0,0 +1,191 @@
+//! The values that are produced while interpreting a program.
+
+use cell_common::strings::StringId;
+use cell_syntax::ast::ItemId;
+use cell_typeck::{consteval::ConstValue, db::TypeckDatabase, lower::AdtDef};
+use std::convert::TryFrom;
+
+/// A value that is stored inside a local, a `static` or that
+/// is the result of evaluating an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// An integer, float, `bool`, `char`, string or `()`.
+    Scalar(ConstValue),
+    Tuple(Vec<Value>),
+    NamedTuple(Vec<(StringId, Value)>),
+    /// A value of a `type`, with the value of every field in the
+    /// order they are declared in.
+    Struct(ItemId, Vec<Value>),
+    /// A variant of a union, together with the index of the variant
+    /// and its data.
+    Variant(ItemId, u32, Option<Box<Value>>),
+    Pointer(Pointer),
+}
+
+impl Value {
+    /// Returns the value of `()`.
+    pub fn unit() -> Self {
+        Value::Scalar(ConstValue::Unit)
+    }
+
+    /// Converts a value that was evaluated at compile time.
+    pub fn from_const(val: ConstValue) -> Self {
+        match val {
+            ConstValue::Tuple(vals) => {
+                Value::Tuple(vals.into_iter().map(Value::from_const).collect())
+            }
+            ConstValue::NamedTuple(fields) => Value::NamedTuple(
+                fields
+                    .into_iter()
+                    .map(|(name, val)| (name, Value::from_const(val)))
+                    .collect(),
+            ),
+            val => Value::Scalar(val),
+        }
+    }
+
+    /// Returns the field at the given index of a tuple, named tuple or struct.
+    pub fn field(&self, idx: u32) -> Option<&Value> {
+        match self {
+            Value::Tuple(vals) | Value::Struct(_, vals) => vals.get(idx as usize),
+            Value::NamedTuple(fields) => fields.get(idx as usize).map(|(_, val)| val),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the field at the given index.
+    pub fn field_mut(&mut self, idx: u32) -> Option<&mut Value> {
+        match self {
+            Value::Tuple(vals) | Value::Struct(_, vals) => vals.get_mut(idx as usize),
+            Value::NamedTuple(fields) => fields.get_mut(idx as usize).map(|(_, val)| val),
+            _ => None,
+        }
+    }
+
+    /// Returns the human readable representation that is used by `io.println`.
+    ///
+    /// Strings and chars are only quoted if they are part of another value.
+    pub fn display(&self, db: &dyn TypeckDatabase) -> String {
+        match self {
+            Value::Scalar(ConstValue::String(val)) => val.clone(),
+            Value::Scalar(ConstValue::Char(val)) => val.to_string(),
+            val => val.display_nested(db),
+        }
+    }
+
+    fn display_nested(&self, db: &dyn TypeckDatabase) -> String {
+        let fields = |fields: &mut dyn Iterator<Item = (StringId, &Value)>| {
+            fields
+                .map(|(name, val)| {
+                    format!("{}: {}", db.rodeo().resolve(&name), val.display_nested(db))
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            Value::Scalar(val) => match val {
+                ConstValue::Int(int) => int.to_string(),
+                ConstValue::Float { val, .. } => val.to_string(),
+                ConstValue::Bool(val) => val.to_string(),
+                ConstValue::Char(val) => format!("{:?}", val),
+                ConstValue::String(val) => format!("{:?}", val),
+                ConstValue::Unit => "()".to_string(),
+                ConstValue::Tuple(_) | ConstValue::NamedTuple(_) => {
+                    Value::from_const(val.clone()).display_nested(db)
+                }
+            },
+            Value::Tuple(vals) => {
+                let vals = vals
+                    .iter()
+                    .map(|val| val.display_nested(db))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", vals.join(", "))
+            }
+            Value::NamedTuple(vals) => format!(
+                "{{{}}}",
+                fields(&mut vals.iter().map(|(name, val)| (*name, val)))
+            ),
+            Value::Struct(item, vals) => {
+                let names = match &*db.adt_def(*item) {
+                    AdtDef::Struct { fields } => {
+                        fields.iter().map(|(name, _)| *name).collect::<Vec<_>>()
+                    }
+                    AdtDef::Union { .. } => vec![],
+                };
+                format!(
+                    "{} {{{}}}",
+                    item_name(db, *item),
+                    fields(&mut names.into_iter().zip(vals))
+                )
+            }
+            Value::Variant(item, idx, data) => {
+                let variant = match &*db.adt_def(*item) {
+                    AdtDef::Union { variants } => {
+                        db.rodeo().resolve(&variants[*idx as usize].0).to_string()
+                    }
+                    AdtDef::Struct { .. } => idx.to_string(),
+                };
+                let name = format!("{}:{}", item_name(db, *item), variant);
+                match data {
+                    Some(data) => format!("{}({})", name, data.display_nested(db)),
+                    None => name,
+                }
+            }
+            Value::Pointer(ptr) => format!("<pointer {}+{}>", ptr.alloc, ptr.offset),
+        }
+    }
+}
+
+/// A pointer into the memory of the interpreter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    pub(crate) alloc: u32,
+    /// The generation of the allocation at the time the pointer
+    /// was created, used to detect dangling pointers.
+    pub(crate) generation: u32,
+    /// The index of the value inside the allocation.
+    pub(crate) offset: i64,
+    /// The field indices that lead from the value at `offset`
+    /// to the value this pointer points to.
+    pub(crate) path: Vec<u32>,
+}
+
+impl Pointer {
+    pub(crate) fn new(alloc: u32, generation: u32) -> Self {
+        Self {
+            alloc,
+            generation,
+            offset: 0,
+            path: vec![],
+        }
+    }
+
+    /// Returns a pointer to a field of the value this pointer points to.
+    pub(crate) fn field(&self, idx: u32) -> Self {
+        let mut ptr = self.clone();
+        ptr.path.push(idx);
+        ptr
+    }
+
+    /// Moves the pointer by `count` values.
+    ///
+    /// Pointers to fields can't be moved, so the result of moving
+    /// them will always be out of bounds.
+    pub(crate) fn offset(&self, count: i128) -> Self {
+        let mut ptr = self.clone();
+        if !ptr.path.is_empty() && count != 0 {
+            ptr.offset = -1;
+        } else {
+            ptr.offset = i64::try_from(ptr.offset as i128 + count).unwrap_or(-1);
+        }
+        ptr
+    }
+}
+
+fn item_name(db: &dyn TypeckDatabase, item: ItemId) -> String {
+    match db.lookup_intern_item(item).kind.name() {
+        Some(name) => db.rodeo().resolve(&name.id).to_string(),
+        None => "{unknown}".to_string(),
+    }
+}
@@ -0,0 +1,1058 @@
+//! Finds the symbols inside a program, and everything that refers to them.
+//!
+//! Every feature of the language server is built on top of the
+//! occurrences of an item, which are all names inside the item together
+//! with the symbol they refer to. They are computed from the results of
+//! name resolution and type checking, so they are only as good as the
+//! parser's error recovery allows.
+
+use cell_common::source::{FileId, Span, Spanned};
+use cell_syntax::ast::{
+    Block, ExprId, ExprKind, FieldName, Generic, Identifier, ItemId, ItemKind, Path, PatternId,
+    PatternKind, StmtId, StmtKind, Type, TypeId,
+};
+use cell_typeck::{
+    db::TypeckDatabase,
+    infer::{Callee, LocalId, Res, TypeckResults},
+    lower::AdtDef,
+    privacy,
+    scope::{self, Binding, Namespace},
+    traits,
+    ty::{Ty, TyKind},
+    unify::InferTable,
+};
+use std::sync::Arc;
+
+/// Every keyword that is offered as a completion.
+const KEYWORDS: &[&str] = &[
+    "var", "if", "else", "match", "while", "return", "break", "continue", "this", "true", "false",
+    "def", "type", "alias", "union", "trait", "impl", "const", "static", "import", "use", "pub",
+];
+
+/// Something that can be referred to by a name.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Symbol {
+    Item(ItemId),
+    /// A local variable of the function or global.
+    Local(ItemId, LocalId),
+    /// A field of a `type`, together with the index of the field.
+    Field(ItemId, u32),
+    /// A variant of a union, together with the index of the variant.
+    Variant(ItemId, u32),
+    /// The module that is declared by an `import`.
+    Module(FileId),
+}
+
+/// A single suggestion for the name at the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+}
+
+/// What a completion refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Symbol(Symbol),
+    /// A field of a named tuple, which has no definition.
+    TupleField,
+    Keyword,
+}
+
+/// Checks if the offset is inside the span, or right at its end.
+fn touches(span: Span, offset: usize) -> bool {
+    usize::from(span.start()) <= offset && offset <= usize::from(span.end())
+}
+
+/// Checks if the item has expressions that are type checked.
+fn has_body(db: &dyn TypeckDatabase, item: ItemId) -> bool {
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Def(def) => def.body.is_some(),
+        ItemKind::Const(_) | ItemKind::Static(_) => true,
+        _ => false,
+    }
+}
+
+/// Returns every item, including methods, whose span touches the offset.
+fn items_at(db: &dyn TypeckDatabase, file: FileId, offset: usize) -> Vec<ItemId> {
+    db.file_scope(file)
+        .items
+        .iter()
+        .copied()
+        .filter(|&item| touches(db.lookup_intern_item(item).span, offset))
+        .collect()
+}
+
+/// Returns the innermost item with a body that touches the offset.
+fn body_at(db: &dyn TypeckDatabase, file: FileId, offset: usize) -> Option<ItemId> {
+    items_at(db, file, offset)
+        .into_iter()
+        .filter(|&item| has_body(db, item))
+        .min_by_key(|&item| db.lookup_intern_item(item).span.len())
+}
+
+/// Returns the symbol whose name is at the offset, together with the span of the name.
+pub fn symbol_at(db: &dyn TypeckDatabase, file: FileId, offset: usize) -> Option<Spanned<Symbol>> {
+    items_at(db, file, offset)
+        .into_iter()
+        .flat_map(|item| Collector::collect(db, item).names)
+        .filter(|name| touches(name.span(), offset))
+        .min_by_key(|name| name.span().len())
+}
+
+/// Returns the file and span of the name that defines the symbol.
+pub fn definition(db: &dyn TypeckDatabase, symbol: Symbol) -> Option<(FileId, Span)> {
+    match symbol {
+        Symbol::Item(item) => {
+            let data = db.lookup_intern_item(item);
+            let span = data.kind.name().map_or(data.span, |name| name.span);
+            Some((data.file, span))
+        }
+        Symbol::Local(item, local) => {
+            let file = db.lookup_intern_item(item).file;
+            Some((file, db.typeck(item).local(local).span))
+        }
+        Symbol::Field(item, idx) => {
+            let data = db.lookup_intern_item(item);
+            match data.kind {
+                ItemKind::TypeDef(def) => {
+                    Some((data.file, def.fields.get(idx as usize)?.name.span))
+                }
+                _ => None,
+            }
+        }
+        Symbol::Variant(item, idx) => {
+            let data = db.lookup_intern_item(item);
+            match data.kind {
+                ItemKind::Union(union) => {
+                    Some((data.file, union.variants.get(idx as usize)?.name.span))
+                }
+                _ => None,
+            }
+        }
+        Symbol::Module(file) => Some((file, Span::default())),
+    }
+}
+
+/// Returns every name that refers to the symbol, including its definition.
+pub fn references(db: &dyn TypeckDatabase, symbol: Symbol) -> Vec<(FileId, Span)> {
+    let items = match symbol {
+        // locals can only be used inside the item they are defined in
+        Symbol::Local(item, _) => vec![item],
+        _ => db
+            .module_tree()
+            .files()
+            .flat_map(|file| db.file_scope(file).items.clone())
+            .collect(),
+    };
+
+    let mut references = vec![];
+    for item in items {
+        let file = db.lookup_intern_item(item).file;
+        for name in Collector::collect(db, item).names {
+            if *name.data() == symbol && !references.contains(&(file, name.span())) {
+                references.push((file, name.span()));
+            }
+        }
+    }
+    references
+}
+
+/// Returns the name of the symbol, or `None` for modules.
+pub fn name(db: &dyn TypeckDatabase, symbol: Symbol) -> Option<String> {
+    let id = match symbol {
+        Symbol::Item(item) => db.lookup_intern_item(item).kind.name()?.id,
+        Symbol::Local(item, local) => db.typeck(item).local(local).name,
+        Symbol::Field(item, idx) => match &*db.adt_def(item) {
+            AdtDef::Struct { fields } => fields.get(idx as usize)?.0,
+            AdtDef::Union { .. } => return None,
+        },
+        Symbol::Variant(item, idx) => match &*db.adt_def(item) {
+            AdtDef::Union { variants } => variants.get(idx as usize)?.0,
+            AdtDef::Struct { .. } => return None,
+        },
+        Symbol::Module(_) => return None,
+    };
+    Some(db.rodeo().resolve(&id).to_string())
+}
+
+/// Returns a short description of the symbol, which looks like its declaration.
+pub fn describe(db: &dyn TypeckDatabase, symbol: Symbol) -> String {
+    let rodeo = db.rodeo();
+    let resolve = |id| rodeo.resolve(&id).to_string();
+    match symbol {
+        Symbol::Item(item) => describe_item(db, item),
+        Symbol::Local(item, local) => {
+            let local = db.typeck(item).local(local).clone();
+            format!("{}: {}", resolve(local.name), local.ty.display(db))
+        }
+        Symbol::Field(item, idx) => match &*db.adt_def(item) {
+            AdtDef::Struct { fields } => {
+                let (name, ty) = fields[idx as usize];
+                format!("{}: {}", resolve(name), ty.display(db))
+            }
+            AdtDef::Union { .. } => String::new(),
+        },
+        Symbol::Variant(item, idx) => match &*db.adt_def(item) {
+            AdtDef::Union { variants } => {
+                let union = describe_name(db, item);
+                let (name, data) = variants[idx as usize];
+                match data {
+                    Some(data) => format!("{}:{} = {}", union, resolve(name), data.display(db)),
+                    None => format!("{}:{}", union, resolve(name)),
+                }
+            }
+            AdtDef::Struct { .. } => String::new(),
+        },
+        Symbol::Module(file) => format!("module {}", db.name(file)),
+    }
+}
+
+fn describe_name(db: &dyn TypeckDatabase, item: ItemId) -> String {
+    match db.lookup_intern_item(item).kind.name() {
+        Some(name) => db.rodeo().resolve(&name.id).to_string(),
+        None => String::new(),
+    }
+}
+
+fn describe_generics(db: &dyn TypeckDatabase, generics: &[Generic]) -> String {
+    if generics.is_empty() {
+        return String::new();
+    }
+    let rodeo = db.rodeo();
+    let generics = generics
+        .iter()
+        .map(|generic| {
+            let bounds = generic
+                .bounds
+                .iter()
+                .map(|bound| rodeo.resolve(&bound.id))
+                .collect::<Vec<_>>();
+            if bounds.is_empty() {
+                rodeo.resolve(&generic.name.id).to_string()
+            } else {
+                format!(
+                    "{}: {}",
+                    rodeo.resolve(&generic.name.id),
+                    bounds.join(" + ")
+                )
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", generics.join(", "))
+}
+
+fn describe_item(db: &dyn TypeckDatabase, item: ItemId) -> String {
+    let name = describe_name(db, item);
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Def(def) => {
+            let sig = db.fn_sig(item);
+            let args = def
+                .args
+                .iter()
+                .zip(sig.params.iter())
+                .map(|((name, _), ty)| {
+                    format!("{}: {}", db.rodeo().resolve(&name.id), ty.display(db))
+                })
+                .collect::<Vec<_>>();
+            let mut desc = format!(
+                "def {}{}({})",
+                name,
+                describe_generics(db, &def.generics),
+                args.join(", ")
+            );
+            if sig.ret.kind(db) != TyKind::Unit {
+                desc.push_str(&format!(": {}", sig.ret.display(db)));
+            }
+            desc
+        }
+        ItemKind::TypeDef(def) => format!("type {}{}", name, describe_generics(db, &def.generics)),
+        ItemKind::Union(union) => {
+            format!("union {}{}", name, describe_generics(db, &union.generics))
+        }
+        ItemKind::Alias(alias) => {
+            format!(
+                "type alias {}{}",
+                name,
+                describe_generics(db, &alias.generics)
+            )
+        }
+        ItemKind::Trait(_) => format!("trait {}", name),
+        ItemKind::Impl(imp) => {
+            let ty = db.impl_self_ty(item).display(db);
+            match imp.trait_ {
+                Some(trait_) => format!("impl {}: {}", ty, db.rodeo().resolve(&trait_.id)),
+                None => format!("impl {}", ty),
+            }
+        }
+        ItemKind::Const(_) => format!("const {}: {}", name, db.global_ty(item).display(db)),
+        ItemKind::Static(_) => format!("static {}: {}", name, db.global_ty(item).display(db)),
+        ItemKind::Import(_) => format!("import {}", name),
+        ItemKind::Use(_) => format!("use {}", name),
+    }
+}
+
+/// Checks if `name` can be used as the name of something.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_ident = chars.next().is_some_and(char::is_alphabetic);
+    starts_ident && chars.all(|c| c.is_alphanumeric() || c == '_') && !KEYWORDS.contains(&name)
+}
+
+/// Returns a description of the symbol or expression at the offset,
+/// together with its span.
+///
+/// Names are described by their declaration, and every other
+/// expression by its type.
+pub fn hover(db: &dyn TypeckDatabase, file: FileId, offset: usize) -> Option<Spanned<String>> {
+    if let Some(symbol) = symbol_at(db, file, offset) {
+        let (symbol, span) = symbol.destruct();
+        return Some(Spanned::new(describe(db, symbol), span));
+    }
+
+    let item = body_at(db, file, offset)?;
+    let results = db.typeck(item);
+    let (span, expr) = Collector::collect(db, item)
+        .exprs
+        .into_iter()
+        .filter(|(span, _)| touches(*span, offset))
+        .min_by_key(|(span, _)| span.len())?;
+    let ty = results.expr_types.get(&expr)?;
+    Some(Spanned::new(ty.display(db), span))
+}
+
+/// Returns every name that can be written at the offset.
+pub fn completions(db: &dyn TypeckDatabase, file: FileId, offset: usize) -> Vec<Completion> {
+    let text = db.file_text(file);
+    let offset = offset.min(text.len());
+    // the part of the name that is already typed
+    let start = ident_start(&text, offset);
+
+    match text[..start].chars().next_back() {
+        Some('.') => member_completions(db, file, start - 1),
+        Some(':') => path_completions(db, file, start - 1),
+        _ => scope_completions(db, file, offset),
+    }
+}
+
+/// Returns the start of the identifier that ends at `offset`.
+fn ident_start(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map_or(offset, |(idx, _)| idx)
+}
+
+fn symbol_completion(db: &dyn TypeckDatabase, label: String, symbol: Symbol) -> Completion {
+    Completion {
+        label,
+        kind: CompletionKind::Symbol(symbol),
+        detail: Some(describe(db, symbol)),
+    }
+}
+
+fn item_completion(db: &dyn TypeckDatabase, item: ItemId) -> Completion {
+    symbol_completion(db, describe_name(db, item), Symbol::Item(item))
+}
+
+/// Completes the locals that are visible at the offset, every item
+/// in scope and keywords.
+fn scope_completions(db: &dyn TypeckDatabase, file: FileId, offset: usize) -> Vec<Completion> {
+    let rodeo = db.rodeo();
+    let mut completions: Vec<Completion> = vec![];
+    let mut add = |completion: Completion| {
+        // later locals shadow earlier ones
+        completions.retain(|other| other.label != completion.label);
+        completions.push(completion);
+    };
+
+    if let Some(item) = body_at(db, file, offset) {
+        let results = db.typeck(item);
+        for local in visible_locals(db, item, &results, offset) {
+            let name = rodeo.resolve(&results.local(local).name).to_string();
+            add(symbol_completion(db, name, Symbol::Local(item, local)));
+        }
+    }
+
+    let scope = db.file_scope(file);
+    for &item in scope.values.values().chain(scope.types.values()) {
+        add(item_completion(db, item));
+    }
+    for (&name, &use_) in scope.uses.iter() {
+        let binding = [Namespace::Values, Namespace::Types, Namespace::Modules]
+            .iter()
+            .find_map(|&ns| scope::resolve_use(db, use_, ns));
+        let symbol = match binding.map(|binding| binding.res) {
+            Some(Binding::Item(item)) => Symbol::Item(item),
+            Some(Binding::Module(module)) => Symbol::Module(module),
+            None => continue,
+        };
+        add(symbol_completion(
+            db,
+            rodeo.resolve(&name).to_string(),
+            symbol,
+        ));
+    }
+    for &name in scope.imports.keys() {
+        if let Some(module) = db.module_tree().child(file, name) {
+            add(symbol_completion(
+                db,
+                rodeo.resolve(&name).to_string(),
+                Symbol::Module(module),
+            ));
+        }
+    }
+
+    if let Some(prelude) = db
+        .source_root()
+        .prelude()
+        .filter(|&prelude| prelude != file)
+    {
+        let scope = db.file_scope(prelude);
+        for &item in scope.values.values().chain(scope.types.values()) {
+            if privacy::is_item_accessible(db, file, item) {
+                add(item_completion(db, item));
+            }
+        }
+    }
+
+    for keyword in KEYWORDS {
+        add(Completion {
+            label: keyword.to_string(),
+            kind: CompletionKind::Keyword,
+            detail: None,
+        });
+    }
+    completions
+}
+
+/// Completes the fields and methods of the value before the `.` at `dot`.
+fn member_completions(db: &dyn TypeckDatabase, file: FileId, dot: usize) -> Vec<Completion> {
+    let item = match body_at(db, file, dot) {
+        Some(item) => item,
+        None => return vec![],
+    };
+    let results = db.typeck(item);
+    let mut ty = match receiver_ty(db, file, item, &results, dot) {
+        Some(ty) => ty,
+        None => return vec![],
+    };
+    while let TyKind::Pointer(inner) = ty.kind(db) {
+        ty = inner;
+    }
+
+    let mut completions = vec![];
+    match ty.kind(db) {
+        TyKind::Adt(adt, _) => {
+            if let AdtDef::Struct { fields } = &*db.adt_def(adt) {
+                for (idx, (name, _)) in fields.iter().enumerate() {
+                    let name = db.rodeo().resolve(name).to_string();
+                    completions.push(symbol_completion(db, name, Symbol::Field(adt, idx as u32)));
+                }
+            }
+        }
+        TyKind::NamedTuple(fields) => {
+            for (name, ty) in fields {
+                completions.push(Completion {
+                    label: db.rodeo().resolve(&name).to_string(),
+                    kind: CompletionKind::TupleField,
+                    detail: Some(ty.display(db)),
+                });
+            }
+        }
+        TyKind::Param(idx, _) => {
+            let generics = db.generics_of(item);
+            for &bound in &generics.params[idx as usize].bounds {
+                for method in methods_of(db, bound) {
+                    completions.push(item_completion(db, method));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    for &imp in db.crate_impls().iter() {
+        let mut table = InferTable::new(db);
+        if traits::match_impl(&mut table, db, imp, ty).is_none() {
+            continue;
+        }
+        let mut methods = methods_of(db, imp);
+        // default methods of the trait, that are not overridden by the impl
+        if let Some(trait_) = db.impl_trait(imp) {
+            for method in methods_of(db, trait_) {
+                let name = traits::def(db, method).name.id;
+                if traits::impl_method(db, imp, name) == Some(method) {
+                    methods.push(method);
+                }
+            }
+        }
+        for method in methods {
+            if privacy::is_item_accessible(db, file, method) {
+                completions.push(item_completion(db, method));
+            }
+        }
+    }
+    completions
+}
+
+/// Returns the methods of an `impl` or `trait`.
+fn methods_of(db: &dyn TypeckDatabase, item: ItemId) -> Vec<ItemId> {
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Impl(imp) => imp.methods,
+        ItemKind::Trait(trait_) => trait_.methods,
+        _ => vec![],
+    }
+}
+
+/// Returns the type of the expression that ends at the `.` at `dot`.
+///
+/// While the member is typed, the expression is usually not parsed,
+/// so the name before the `.` is looked up as a local instead.
+fn receiver_ty(
+    db: &dyn TypeckDatabase,
+    file: FileId,
+    item: ItemId,
+    results: &TypeckResults,
+    dot: usize,
+) -> Option<Ty> {
+    let parsed = Collector::collect(db, item)
+        .exprs
+        .into_iter()
+        .filter(|(span, _)| usize::from(span.end()) == dot)
+        .max_by_key(|(span, _)| span.len())
+        .and_then(|(_, expr)| results.expr_types.get(&expr).copied());
+    if parsed.is_some() {
+        return parsed;
+    }
+
+    let text = db.file_text(file);
+    let name = &text[ident_start(&text, dot)..dot];
+    if name == "this" {
+        return results.this.map(|this| results.local(this).ty);
+    }
+    visible_locals(db, item, results, dot)
+        .into_iter()
+        .rev()
+        .map(|local| results.local(local))
+        .find(|local| db.rodeo().resolve(&local.name) == name)
+        .map(|local| local.ty)
+}
+
+/// Completes the variants of a union or the items of a module,
+/// whose name is before the `:` at `colon`.
+fn path_completions(db: &dyn TypeckDatabase, file: FileId, colon: usize) -> Vec<Completion> {
+    let text = db.file_text(file);
+    let mut segments = vec![];
+    let mut end = colon;
+    loop {
+        let start = ident_start(&text, end);
+        if start == end {
+            break;
+        }
+        segments.push(Identifier {
+            span: Span::from(start..end),
+            id: db.rodeo().get_or_intern(&text[start..end]),
+        });
+        match start.checked_sub(1) {
+            Some(colon) if text[colon..].starts_with(':') => end = colon,
+            _ => break,
+        }
+    }
+    segments.reverse();
+    if segments.is_empty() {
+        return vec![];
+    }
+
+    let union = scope::resolve_type_path(db, file, &segments).map(|resolved| resolved.res);
+    if let Some(union) = union {
+        if let ItemKind::Union(def) = db.lookup_intern_item(union).kind {
+            return def
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(idx, variant)| {
+                    let name = db.rodeo().resolve(&variant.name.id).to_string();
+                    symbol_completion(db, name, Symbol::Variant(union, idx as u32))
+                })
+                .collect();
+        }
+    }
+
+    match scope::resolve_module_path(db, file, &segments) {
+        Some(module) => {
+            let scope = db.file_scope(module);
+            scope
+                .values
+                .values()
+                .chain(scope.types.values())
+                .copied()
+                .filter(|&item| privacy::is_item_accessible(db, file, item))
+                .map(|item| item_completion(db, item))
+                .collect()
+        }
+        None => vec![],
+    }
+}
+
+/// Returns every local of the item that is in scope at the offset,
+/// in the order they are defined.
+fn visible_locals(
+    db: &dyn TypeckDatabase,
+    item: ItemId,
+    results: &TypeckResults,
+    offset: usize,
+) -> Vec<LocalId> {
+    let mut scope = LocalScope {
+        db,
+        results,
+        offset,
+        locals: results.this.into_iter().collect(),
+    };
+    scope.locals.extend(results.params.iter().copied());
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Def(def) => {
+            if let Some(body) = &def.body {
+                scope.block(body);
+            }
+        }
+        ItemKind::Const(global) | ItemKind::Static(global) => scope.expr(global.val),
+        _ => {}
+    }
+    scope.locals
+}
+
+/// Collects the locals that are visible at an offset.
+struct LocalScope<'a> {
+    db: &'a dyn TypeckDatabase,
+    results: &'a TypeckResults,
+    offset: usize,
+    locals: Vec<LocalId>,
+}
+
+impl LocalScope<'_> {
+    fn block(&mut self, block: &Block) {
+        if !touches(block.span, self.offset) {
+            return;
+        }
+        for &id in &block.stmts {
+            let stmt = self.db.lookup_intern_stmt(id);
+            if usize::from(stmt.span.end()) <= self.offset {
+                if let Some(&local) = self.results.vars.get(&id) {
+                    self.locals.push(local);
+                }
+            } else if touches(stmt.span, self.offset) {
+                match stmt.kind {
+                    StmtKind::Var(var) => self.expr(var.val),
+                    StmtKind::Expr(expr) => self.expr(expr),
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, id: ExprId) {
+        let expr = self.db.lookup_intern_expr(id);
+        if !touches(expr.span, self.offset) {
+            return;
+        }
+        match expr.kind {
+            ExprKind::Block(block) => self.block(&block),
+            ExprKind::If(if_) => {
+                self.expr(if_.cond);
+                self.block(&if_.then);
+                for arm in &if_.arms {
+                    self.expr(arm.cond);
+                    self.block(&arm.action);
+                }
+                if let Some(else_) = &if_.else_ {
+                    self.block(else_);
+                }
+            }
+            ExprKind::While(while_) => {
+                if let Some(cond) = while_.cond {
+                    self.expr(cond);
+                }
+                self.block(&while_.block);
+            }
+            ExprKind::Match(match_) => {
+                self.expr(match_.val);
+                for arm in &match_.arms {
+                    if touches(self.db.lookup_intern_expr(arm.body).span, self.offset) {
+                        self.bindings(arm.pattern);
+                        self.expr(arm.body);
+                    }
+                }
+            }
+            kind => {
+                for child in child_exprs(&kind) {
+                    self.expr(child);
+                }
+            }
+        }
+    }
+
+    /// Adds every local that is bound by the pattern.
+    fn bindings(&mut self, id: PatternId) {
+        if let Some(&local) = self.results.bindings.get(&id) {
+            self.locals.push(local);
+        }
+        match self.db.lookup_intern_pattern(id).kind {
+            PatternKind::Tuple(patterns) => patterns
+                .into_iter()
+                .for_each(|pattern| self.bindings(pattern)),
+            PatternKind::NamedTuple(fields) | PatternKind::Struct { fields, .. } => fields
+                .into_iter()
+                .for_each(|(_, pattern)| self.bindings(pattern)),
+            PatternKind::Variant {
+                data: Some(data), ..
+            } => self.bindings(data),
+            _ => {}
+        }
+    }
+}
+
+/// Returns the direct subexpressions of an expression that doesn't contain a block.
+fn child_exprs(kind: &ExprKind) -> Vec<ExprId> {
+    match kind {
+        ExprKind::Binary(binary) => vec![binary.left, binary.right],
+        ExprKind::Comparison(cmp) => vec![cmp.left, cmp.right],
+        ExprKind::Unary(unary) => vec![unary.val],
+        ExprKind::Tuple(tuple) => tuple.values.clone(),
+        ExprKind::NamedTuple(tuple) => tuple.values.iter().map(|(_, val)| *val).collect(),
+        ExprKind::Call(call) => std::iter::once(call.callee)
+            .chain(call.args.iter().copied())
+            .collect(),
+        ExprKind::MethodCall(call) => std::iter::once(call.receiver)
+            .chain(call.args.iter().copied())
+            .collect(),
+        ExprKind::Field(field) => vec![field.val],
+        ExprKind::Assign(assign) => vec![assign.target, assign.val],
+        ExprKind::Return(val) => val.iter().copied().collect(),
+        _ => vec![],
+    }
+}
+
+/// Collects every name inside an item together with the symbol it refers
+/// to, and the span of every expression.
+struct Collector<'db> {
+    db: &'db dyn TypeckDatabase,
+    item: ItemId,
+    file: FileId,
+    results: Arc<TypeckResults>,
+    names: Vec<Spanned<Symbol>>,
+    exprs: Vec<(Span, ExprId)>,
+}
+
+impl<'db> Collector<'db> {
+    fn collect(db: &'db dyn TypeckDatabase, item: ItemId) -> Self {
+        let data = db.lookup_intern_item(item);
+        let results = match data.kind {
+            ItemKind::Def(_) | ItemKind::Const(_) | ItemKind::Static(_) => db.typeck(item),
+            _ => Arc::default(),
+        };
+        let mut collector = Self {
+            db,
+            item,
+            file: data.file,
+            results,
+            names: vec![],
+            exprs: vec![],
+        };
+        collector.item(data.kind);
+        collector
+    }
+
+    fn push(&mut self, symbol: Symbol, span: Span) {
+        self.names.push(Spanned::new(symbol, span));
+    }
+
+    fn item(&mut self, kind: ItemKind) {
+        let item = Symbol::Item(self.item);
+        match kind {
+            ItemKind::TypeDef(def) => {
+                self.push(item, def.name.span);
+                self.generics(&def.generics);
+                for (idx, field) in def.fields.iter().enumerate() {
+                    self.push(Symbol::Field(self.item, idx as u32), field.name.span);
+                    self.ty(field.ty);
+                }
+            }
+            ItemKind::Def(def) => {
+                self.push(item, def.name.span);
+                self.generics(&def.generics);
+                for (idx, (name, ty)) in def.args.iter().enumerate() {
+                    if let Some(&local) = self.results.params.get(idx) {
+                        self.push(Symbol::Local(self.item, local), name.span);
+                    }
+                    self.ty(*ty);
+                }
+                self.ty(def.return_ty);
+                if let Some(body) = &def.body {
+                    self.block(body);
+                }
+            }
+            ItemKind::Alias(alias) => {
+                self.push(item, alias.name.span);
+                self.generics(&alias.generics);
+                self.ty(alias.ty);
+            }
+            ItemKind::Union(union) => {
+                self.push(item, union.name.span);
+                self.generics(&union.generics);
+                for (idx, variant) in union.variants.iter().enumerate() {
+                    self.push(Symbol::Variant(self.item, idx as u32), variant.name.span);
+                    if let Some(data) = variant.data {
+                        self.ty(data);
+                    }
+                }
+            }
+            ItemKind::Trait(trait_) => self.push(item, trait_.name.span),
+            ItemKind::Impl(imp) => {
+                self.generics(&imp.generics);
+                self.ty(imp.ty);
+                if let Some(name) = &imp.trait_ {
+                    self.trait_(name);
+                }
+            }
+            ItemKind::Const(global) | ItemKind::Static(global) => {
+                self.push(item, global.name.span);
+                self.ty(global.ty);
+                self.expr(global.val);
+            }
+            ItemKind::Import(import) => {
+                if let Some(module) = self.db.module_tree().child(self.file, import.name.id) {
+                    self.push(Symbol::Module(module), import.name.span);
+                }
+            }
+            ItemKind::Use(use_) => {
+                let name = match use_.path.segments.last() {
+                    Some(name) => name.span,
+                    None => return,
+                };
+                let binding = [Namespace::Values, Namespace::Types, Namespace::Modules]
+                    .iter()
+                    .find_map(|&ns| scope::resolve_use(self.db, self.item, ns));
+                match binding.map(|binding| binding.res) {
+                    Some(Binding::Item(target)) => self.push(Symbol::Item(target), name),
+                    Some(Binding::Module(module)) => self.push(Symbol::Module(module), name),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    fn generics(&mut self, generics: &[Generic]) {
+        for bound in generics.iter().flat_map(|generic| generic.bounds.iter()) {
+            self.trait_(bound);
+        }
+    }
+
+    fn trait_(&mut self, name: &Identifier) {
+        if let Some(trait_) = scope::resolve_trait(self.db, self.file, name.id) {
+            self.push(Symbol::Item(trait_), name.span);
+        }
+    }
+
+    fn ty(&mut self, ty: TypeId) {
+        let ty = self.db.lookup_intern_type(ty);
+        self.type_(&ty);
+    }
+
+    fn type_(&mut self, ty: &Type) {
+        match ty {
+            Type::Pointer(inner) => self.ty(*inner),
+            Type::Tuple(tys) => tys.iter().for_each(|&ty| self.ty(ty)),
+            Type::NamedTuple(fields) => fields.iter().for_each(|(_, ty)| self.ty(*ty)),
+            Type::Named { name, generics } => {
+                // generic parameters shadow every item
+                let is_param = self.db.generics_of(self.item).find(name.id).is_some();
+                if !is_param {
+                    if let Some(item) = scope::resolve_type(self.db, self.file, name.id) {
+                        self.push(Symbol::Item(item), name.span);
+                    }
+                }
+                generics.iter().for_each(|&ty| self.ty(ty));
+            }
+            _ => {}
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for &stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, id: StmtId) {
+        match self.db.lookup_intern_stmt(id).kind {
+            StmtKind::Var(var) => {
+                if let Some(&local) = self.results.vars.get(&id) {
+                    self.push(Symbol::Local(self.item, local), var.name.span);
+                }
+                if let Some(ty) = &var.ty {
+                    self.type_(ty);
+                }
+                self.expr(var.val);
+            }
+            StmtKind::Expr(expr) => self.expr(expr),
+        }
+    }
+
+    fn expr(&mut self, id: ExprId) {
+        let expr = self.db.lookup_intern_expr(id);
+        self.exprs.push((expr.span, id));
+        match expr.kind {
+            ExprKind::Block(block) => self.block(&block),
+            ExprKind::If(if_) => {
+                self.expr(if_.cond);
+                self.block(&if_.then);
+                for arm in &if_.arms {
+                    self.expr(arm.cond);
+                    self.block(&arm.action);
+                }
+                if let Some(else_) = &if_.else_ {
+                    self.block(else_);
+                }
+            }
+            ExprKind::While(while_) => {
+                if let Some(cond) = while_.cond {
+                    self.expr(cond);
+                }
+                self.block(&while_.block);
+            }
+            ExprKind::Match(match_) => {
+                self.expr(match_.val);
+                for arm in &match_.arms {
+                    self.pattern(arm.pattern);
+                    self.expr(arm.body);
+                }
+            }
+            ExprKind::Path(path) => match self.results.resolutions.get(&id).copied() {
+                Some(Res::Local(local)) => {
+                    let name = path.segments.last().unwrap().span;
+                    self.push(Symbol::Local(self.item, local), name);
+                }
+                Some(Res::Def(item)) => {
+                    let name = path.segments.last().unwrap().span;
+                    self.push(Symbol::Item(item), name);
+                }
+                Some(Res::Variant(union, idx)) => self.variant_path(&path, union, idx),
+                None => {}
+            },
+            ExprKind::MethodCall(call) => {
+                match self.results.callees.get(&id).cloned() {
+                    Some(Callee::Fn { def, .. }) => self.push(Symbol::Item(def), call.name.span),
+                    Some(Callee::Variant { union, variant, .. }) => {
+                        // the receiver of `Union:Variant.new(data)` is not a value,
+                        // so it's not resolved like other paths
+                        let receiver = self.db.lookup_intern_expr(call.receiver);
+                        match receiver.kind {
+                            ExprKind::Path(path)
+                                if !self.results.resolutions.contains_key(&call.receiver) =>
+                            {
+                                self.variant_path(&path, union, variant)
+                            }
+                            _ => {}
+                        }
+                    }
+                    None => {}
+                }
+                self.expr(call.receiver);
+                call.args.iter().for_each(|&arg| self.expr(arg));
+            }
+            ExprKind::Field(field) => {
+                self.expr(field.val);
+                if let FieldName::Named(name) = &field.field {
+                    let adt = self.adt_of(field.val);
+                    let idx = self.results.fields.get(&id).copied();
+                    if let (Some(adt), Some(idx)) = (adt, idx) {
+                        self.push(Symbol::Field(adt, idx), name.span);
+                    }
+                }
+            }
+            ExprKind::This => {
+                if let Some(this) = self.results.this {
+                    self.push(Symbol::Local(self.item, this), expr.span);
+                }
+            }
+            kind => {
+                for child in child_exprs(&kind) {
+                    self.expr(child);
+                }
+            }
+        }
+    }
+
+    /// Adds the variant at the end of the path, and the union before it.
+    fn variant_path(&mut self, path: &Path, union: ItemId, idx: u32) {
+        let mut segments = path.segments.iter().rev();
+        if let Some(variant) = segments.next() {
+            self.push(Symbol::Variant(union, idx), variant.span);
+        }
+        if let Some(name) = segments.next() {
+            self.push(Symbol::Item(union), name.span);
+        }
+    }
+
+    /// Returns the `type` or `union` of the expression, after dereferencing it.
+    fn adt_of(&self, expr: ExprId) -> Option<ItemId> {
+        let mut ty = *self.results.expr_types.get(&expr)?;
+        while let TyKind::Pointer(inner) = ty.kind(self.db) {
+            ty = inner;
+        }
+        match ty.kind(self.db) {
+            TyKind::Adt(item, _) => Some(item),
+            _ => None,
+        }
+    }
+
+    fn pattern(&mut self, id: PatternId) {
+        let pattern = self.db.lookup_intern_pattern(id);
+        match pattern.kind {
+            PatternKind::Binding(name) => {
+                if let Some(&local) = self.results.bindings.get(&id) {
+                    self.push(Symbol::Local(self.item, local), name.span);
+                }
+            }
+            PatternKind::Tuple(patterns) => patterns
+                .into_iter()
+                .for_each(|pattern| self.pattern(pattern)),
+            PatternKind::NamedTuple(fields) => fields
+                .into_iter()
+                .for_each(|(_, pattern)| self.pattern(pattern)),
+            PatternKind::Struct { name, fields } => {
+                let adt = match self
+                    .results
+                    .pattern_types
+                    .get(&id)
+                    .map(|ty| ty.kind(self.db))
+                {
+                    Some(TyKind::Adt(adt, _)) => Some(adt),
+                    _ => None,
+                };
+                if let Some(adt) = adt {
+                    self.push(Symbol::Item(adt), name.span);
+                }
+                for (field, pattern) in fields {
+                    let idx =
+                        adt.and_then(|adt| Some((adt, self.db.adt_def(adt).field(field.id)?.0)));
+                    if let Some((adt, idx)) = idx {
+                        self.push(Symbol::Field(adt, idx as u32), field.span);
+                    }
+                    self.pattern(pattern);
+                }
+            }
+            PatternKind::Variant { path, data } => {
+                if let Some(&(union, idx)) = self.results.pattern_variants.get(&id) {
+                    self.variant_path(&path, union, idx);
+                }
+                if let Some(data) = data {
+                    self.pattern(data);
+                }
+            }
+            PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Conversions between the types of the compiler and the
+//! types of the language server protocol.
+
+use cell_common::{
+    source::{Encoding, FileId, Index, LineCol, SourceDatabase, Span},
+    Diagnostic,
+};
+use codespan_reporting::diagnostic::{LabelStyle, Severity};
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Position, Range,
+    Url,
+};
+use std::path::PathBuf;
+
+/// Returns the path of a `file://` URI.
+pub fn path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path().ok()
+}
+
+/// Returns the URI of the file, if it's a file on disk.
+pub fn url(db: &dyn SourceDatabase, file: FileId) -> Option<Url> {
+    Url::from_file_path(db.name(file).as_str()).ok()
+}
+
+/// Converts a byte offset into a position, whose column counts UTF-16 code units.
+pub fn position(db: &dyn SourceDatabase, file: FileId, offset: usize) -> Position {
+    let pos = db.line_col(file, Index::from(offset as u32), Encoding::Utf16);
+    Position::new(pos.line, pos.col)
+}
+
+/// Converts a position into a byte offset.
+///
+/// Positions after the end of a line are moved to the end of the line.
+pub fn offset(db: &dyn SourceDatabase, file: FileId, position: Position) -> usize {
+    let pos = LineCol::new(position.line, position.character);
+    db.line_col_index(file, pos, Encoding::Utf16)
+        .map_or_else(|| db.file_text(file).len(), usize::from)
+}
+
+pub fn range(db: &dyn SourceDatabase, file: FileId, span: Span) -> Range {
+    Range::new(
+        position(db, file, span.start().into()),
+        position(db, file, span.end().into()),
+    )
+}
+
+pub fn location(db: &dyn SourceDatabase, file: FileId, span: Span) -> Option<Location> {
+    Some(Location::new(url(db, file)?, range(db, file, span)))
+}
+
+/// Converts a diagnostic that is reported in `file`.
+///
+/// The primary label becomes the range of the diagnostic, every other label
+/// is related information and the notes are appended to the message.
+pub fn diagnostic(
+    db: &dyn SourceDatabase,
+    file: FileId,
+    diagnostic: &Diagnostic,
+) -> lsp_types::Diagnostic {
+    let primary = diagnostic
+        .labels
+        .iter()
+        .find(|label| label.style == LabelStyle::Primary && label.file_id == file);
+    let range = primary.map_or_else(Range::default, |label| {
+        range(db, file, Span::from(label.range.clone()))
+    });
+
+    let severity = match diagnostic.severity {
+        Severity::Bug | Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Note => DiagnosticSeverity::INFORMATION,
+        Severity::Help => DiagnosticSeverity::HINT,
+    };
+
+    let mut message = diagnostic.message.clone();
+    if let Some(label) = primary.filter(|label| !label.message.is_empty()) {
+        message.push_str(&format!("\n{}", label.message));
+    }
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\n{}", note));
+    }
+
+    let related = diagnostic
+        .labels
+        .iter()
+        .filter(|label| label.style == LabelStyle::Secondary)
+        .filter_map(|label| {
+            Some(DiagnosticRelatedInformation {
+                location: location(db, label.file_id, Span::from(label.range.clone()))?,
+                message: label.message.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    lsp_types::Diagnostic {
+        range,
+        severity: Some(severity),
+        code: diagnostic.code.clone().map(NumberOrString::String),
+        source: Some("cell".to_string()),
+        message,
+        related_information: Some(related).filter(|related| !related.is_empty()),
+        ..lsp_types::Diagnostic::default()
+    }
+}
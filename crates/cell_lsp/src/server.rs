@@ -0,0 +1,581 @@
+//! The state of the language server, and the handlers for every
+//! request and notification.
+
+use crate::{
+    analysis::{self, CompletionKind, Symbol},
+    convert,
+};
+use cell_common::{
+    source::{self, FileId, SourceDatabase, Span},
+    Diagnostic,
+};
+use cell_db::RootDatabase;
+use cell_parser::db::ParseDatabase;
+use cell_syntax::ast::{ItemId, ItemKind};
+use cell_typeck::{db::TypeckDatabase, scope};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response, ResponseError};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, PrepareRenameRequest,
+        References, Rename, Request as _,
+    },
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, Location, MarkupContent, MarkupKind,
+    PrepareRenameResponse, PublishDiagnosticsParams, ReferenceParams, RenameParams, SymbolKind,
+    TextDocumentPositionParams, TextEdit, Url, WorkspaceEdit,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+/// The name of the root module that is loaded when the server starts.
+const MAIN: &str = "main.cell";
+
+pub struct Server {
+    connection: Connection,
+    db: RootDatabase,
+    /// The path of the root module, once a program is loaded.
+    root: Option<PathBuf>,
+    /// The text of every document that is open in the editor,
+    /// which is used instead of the text on disk.
+    open: HashMap<PathBuf, String>,
+    /// Every document that has diagnostics.
+    published: HashSet<Url>,
+}
+
+impl Server {
+    /// Creates a server, which loads the `main.cell` inside the
+    /// workspace as the root module, if there is one.
+    pub fn new(connection: Connection, workspace: Option<PathBuf>) -> Self {
+        let mut server = Self {
+            connection,
+            db: RootDatabase::new(),
+            root: None,
+            open: HashMap::new(),
+            published: HashSet::new(),
+        };
+        let main = workspace.map(|workspace| workspace.join(MAIN));
+        if let Some(main) = main.filter(|path| path.is_file()) {
+            server.load(main);
+        }
+        server
+    }
+
+    /// Handles messages until the client shuts down the server.
+    pub fn run(&mut self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        self.publish_diagnostics()?;
+
+        let receiver = self.connection.receiver.clone();
+        for message in &receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.request(request);
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => {
+                    self.notification(notification);
+                    self.publish_diagnostics()?;
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the program whose root module is at `root`.
+    fn load(&mut self, root: PathBuf) {
+        match self.db.load_program_with(&root, &self.open) {
+            Ok(_) => self.root = Some(root),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    /// Reads the program again, starting at the root module.
+    fn reload(&mut self) {
+        if let Some(root) = self.root.clone() {
+            self.load(root);
+        }
+    }
+
+    /// Returns the file at the URI, if it's part of the program.
+    fn file(&self, uri: &Url) -> Option<FileId> {
+        self.root.as_ref()?;
+        let path = convert::path(uri)?;
+        self.db.source_root().file(&path)
+    }
+
+    fn notification(&mut self, notification: Notification) {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = match parse_params(notification.params) {
+                    Ok(params) => params,
+                    Err(_) => return,
+                };
+                let path = match convert::path(&params.text_document.uri) {
+                    Some(path) => path,
+                    None => return,
+                };
+                self.open.insert(path.clone(), params.text_document.text);
+
+                // a file that is not part of the program becomes the root module
+                match self.file(&params.text_document.uri) {
+                    Some(_) => self.reload(),
+                    None => self.load(path),
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = match parse_params(notification.params) {
+                    Ok(params) => params,
+                    Err(_) => return,
+                };
+                let path = match convert::path(&params.text_document.uri) {
+                    Some(path) => path,
+                    None => return,
+                };
+                // every change contains the whole document
+                let text = match params.content_changes.into_iter().last() {
+                    Some(change) => change.text,
+                    None => return,
+                };
+                self.open.insert(path.clone(), text.clone());
+                self.change(&path, text);
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = match parse_params(notification.params) {
+                    Ok(params) => params,
+                    Err(_) => return,
+                };
+                if let Some(path) = convert::path(&params.text_document.uri) {
+                    self.open.remove(&path);
+                    self.reload();
+                }
+            }
+            // new modules could have been saved to disk
+            DidSaveTextDocument::METHOD => self.reload(),
+            _ => {}
+        }
+    }
+
+    /// Updates the text of a file.
+    ///
+    /// The program is only loaded again if the file imports other modules
+    /// than before, so only the queries that depend on the file are recomputed.
+    fn change(&mut self, path: &Path, text: String) {
+        let file = match self
+            .root
+            .as_ref()
+            .and_then(|_| self.db.source_root().file(path))
+        {
+            Some(file) => file,
+            None => return,
+        };
+        let imports = self.db.module_imports(file);
+        source::set_file(&mut self.db, path.display().to_string(), text);
+        if self.db.module_imports(file) != imports {
+            self.reload();
+        }
+    }
+
+    /// Publishes the diagnostics of every module, and removes the
+    /// diagnostics of files that are no longer part of the program.
+    fn publish_diagnostics(&mut self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let mut published = HashSet::new();
+        if self.root.is_some() {
+            let db = &self.db;
+            let prelude = db.source_root().prelude();
+            for file in db
+                .module_tree()
+                .files()
+                .filter(|&file| Some(file) != prelude)
+            {
+                let uri = match convert::url(db, file) {
+                    Some(uri) => uri,
+                    None => continue,
+                };
+                let diagnostics = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let errors = db.check_file(file);
+                    let warnings = db.file_warnings(file);
+                    let errors = errors.iter().cloned().map(Diagnostic::from);
+                    let warnings = warnings.iter().cloned().map(Diagnostic::from);
+                    errors
+                        .chain(warnings)
+                        .map(|diagnostic| convert::diagnostic(db, file, &diagnostic))
+                        .collect::<Vec<_>>()
+                }))
+                .unwrap_or_default();
+                self.send_diagnostics(uri.clone(), diagnostics)?;
+                published.insert(uri);
+            }
+        }
+
+        for uri in self.published.difference(&published) {
+            self.send_diagnostics(uri.clone(), vec![])?;
+        }
+        self.published = published;
+        Ok(())
+    }
+
+    fn send_diagnostics(
+        &self,
+        uri: Url,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+    ) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+
+    /// Answers a request.
+    ///
+    /// A request that makes the compiler panic is answered with an
+    /// error, so a single bug doesn't take down the whole server.
+    fn request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.dispatch(request)));
+        let result = result.unwrap_or_else(|_| {
+            Err(ResponseError {
+                code: ErrorCode::InternalError as i32,
+                message: "the request failed because of a bug in the compiler".to_string(),
+                data: None,
+            })
+        });
+        match result {
+            Ok(result) => Response {
+                id,
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => Response {
+                id,
+                result: None,
+                error: Some(error),
+            },
+        }
+    }
+
+    fn dispatch(&self, request: Request) -> Result<Value, ResponseError> {
+        match request.method.as_str() {
+            HoverRequest::METHOD => self.handle::<HoverRequest>(request, Self::hover),
+            GotoDefinition::METHOD => self.handle::<GotoDefinition>(request, Self::definition),
+            References::METHOD => self.handle::<References>(request, Self::references),
+            DocumentSymbolRequest::METHOD => {
+                self.handle::<DocumentSymbolRequest>(request, Self::document_symbols)
+            }
+            Completion::METHOD => self.handle::<Completion>(request, Self::completion),
+            PrepareRenameRequest::METHOD => {
+                self.handle::<PrepareRenameRequest>(request, Self::prepare_rename)
+            }
+            Rename::METHOD => self.handle::<Rename>(request, Self::rename),
+            method => Err(ResponseError {
+                code: ErrorCode::MethodNotFound as i32,
+                message: format!("unknown request `{}`", method),
+                data: None,
+            }),
+        }
+    }
+
+    fn handle<R: lsp_types::request::Request>(
+        &self,
+        request: Request,
+        handler: fn(&Self, R::Params) -> R::Result,
+    ) -> Result<Value, ResponseError> {
+        let params = parse_params(request.params)?;
+        let result = handler(self, params);
+        Ok(serde_json::to_value(result).expect("failed to serialize a response"))
+    }
+
+    /// Returns the file and byte offset of a position.
+    fn position(&self, params: &TextDocumentPositionParams) -> Option<(FileId, usize)> {
+        let file = self.file(&params.text_document.uri)?;
+        Some((file, convert::offset(&self.db, file, params.position)))
+    }
+
+    fn symbol_at(&self, params: &TextDocumentPositionParams) -> Option<Symbol> {
+        let (file, offset) = self.position(params)?;
+        let symbol = analysis::symbol_at(&self.db, file, offset)?;
+        Some(*symbol.data())
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let (file, offset) = self.position(&params.text_document_position_params)?;
+        let (text, span) = analysis::hover(&self.db, file, offset)?.destruct();
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```cell\n{}\n```", text),
+            }),
+            range: Some(convert::range(&self.db, file, span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let symbol = self.symbol_at(&params.text_document_position_params)?;
+        let (file, span) = analysis::definition(&self.db, symbol)?;
+        let location = convert::location(&self.db, file, span)?;
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let symbol = self.symbol_at(&params.text_document_position)?;
+        let definition = analysis::definition(&self.db, symbol);
+        let references = analysis::references(&self.db, symbol)
+            .into_iter()
+            .filter(|&reference| {
+                params.context.include_declaration || Some(reference) != definition
+            })
+            .filter_map(|(file, span)| convert::location(&self.db, file, span))
+            .collect();
+        Some(references)
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let file = self.file(&params.text_document.uri)?;
+        let symbols = self
+            .db
+            .parse(file)
+            .items
+            .iter()
+            .filter_map(|&item| self.item_symbol(file, item))
+            .collect();
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+
+    fn item_symbol(&self, file: FileId, item: ItemId) -> Option<DocumentSymbol> {
+        let db = &self.db;
+        let data = db.lookup_intern_item(item);
+        let name_of = |id| db.rodeo().resolve(&id).to_string();
+        let range = convert::range(db, file, data.span);
+        let symbol = |name: String, kind, selection, children| {
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name,
+                detail: None,
+                kind,
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range: convert::range(db, file, selection),
+                children,
+            }
+        };
+        let methods = |methods: &[ItemId]| {
+            let methods = methods
+                .iter()
+                .filter_map(|&method| self.item_symbol(file, method))
+                .collect();
+            Some(methods)
+        };
+
+        let mut symbol = match &data.kind {
+            ItemKind::Def(def) => {
+                let kind = match scope::parent_item(db, item) {
+                    Some(_) => SymbolKind::METHOD,
+                    None => SymbolKind::FUNCTION,
+                };
+                symbol(name_of(def.name.id), kind, def.name.span, None)
+            }
+            ItemKind::TypeDef(def) => {
+                let fields = def
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let span = convert::range(db, file, field.name.span);
+                        #[allow(deprecated)]
+                        DocumentSymbol {
+                            name: name_of(field.name.id),
+                            detail: None,
+                            kind: SymbolKind::FIELD,
+                            tags: None,
+                            deprecated: None,
+                            range: span,
+                            selection_range: span,
+                            children: None,
+                        }
+                    })
+                    .collect();
+                symbol(
+                    name_of(def.name.id),
+                    SymbolKind::STRUCT,
+                    def.name.span,
+                    Some(fields),
+                )
+            }
+            ItemKind::Union(union) => {
+                let variants = union
+                    .variants
+                    .iter()
+                    .map(|variant| {
+                        let span = convert::range(db, file, variant.name.span);
+                        #[allow(deprecated)]
+                        DocumentSymbol {
+                            name: name_of(variant.name.id),
+                            detail: None,
+                            kind: SymbolKind::ENUM_MEMBER,
+                            tags: None,
+                            deprecated: None,
+                            range: span,
+                            selection_range: span,
+                            children: None,
+                        }
+                    })
+                    .collect();
+                symbol(
+                    name_of(union.name.id),
+                    SymbolKind::ENUM,
+                    union.name.span,
+                    Some(variants),
+                )
+            }
+            ItemKind::Alias(alias) => symbol(
+                name_of(alias.name.id),
+                SymbolKind::TYPE_PARAMETER,
+                alias.name.span,
+                None,
+            ),
+            ItemKind::Trait(trait_) => symbol(
+                name_of(trait_.name.id),
+                SymbolKind::INTERFACE,
+                trait_.name.span,
+                methods(&trait_.methods),
+            ),
+            ItemKind::Impl(imp) => symbol(
+                analysis::describe(db, Symbol::Item(item)),
+                SymbolKind::OBJECT,
+                data.span,
+                methods(&imp.methods),
+            ),
+            ItemKind::Const(global) => symbol(
+                name_of(global.name.id),
+                SymbolKind::CONSTANT,
+                global.name.span,
+                None,
+            ),
+            ItemKind::Static(global) => symbol(
+                name_of(global.name.id),
+                SymbolKind::VARIABLE,
+                global.name.span,
+                None,
+            ),
+            ItemKind::Import(import) => symbol(
+                name_of(import.name.id),
+                SymbolKind::MODULE,
+                import.name.span,
+                None,
+            ),
+            ItemKind::Use(_) => return None,
+        };
+        if !matches!(data.kind, ItemKind::Impl(_)) {
+            symbol.detail = Some(analysis::describe(db, Symbol::Item(item)));
+        }
+        Some(symbol)
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let (file, offset) = self.position(&params.text_document_position)?;
+        let items = analysis::completions(&self.db, file, offset)
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.label,
+                kind: Some(self.completion_kind(completion.kind)),
+                detail: completion.detail,
+                ..CompletionItem::default()
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn completion_kind(&self, kind: CompletionKind) -> CompletionItemKind {
+        let symbol = match kind {
+            CompletionKind::Symbol(symbol) => symbol,
+            CompletionKind::TupleField => return CompletionItemKind::FIELD,
+            CompletionKind::Keyword => return CompletionItemKind::KEYWORD,
+        };
+        let item = match symbol {
+            Symbol::Item(item) => item,
+            Symbol::Local(..) => return CompletionItemKind::VARIABLE,
+            Symbol::Field(..) => return CompletionItemKind::FIELD,
+            Symbol::Variant(..) => return CompletionItemKind::ENUM_MEMBER,
+            Symbol::Module(_) => return CompletionItemKind::MODULE,
+        };
+        match self.db.lookup_intern_item(item).kind {
+            ItemKind::Def(_) if scope::parent_item(&self.db, item).is_some() => {
+                CompletionItemKind::METHOD
+            }
+            ItemKind::Def(_) => CompletionItemKind::FUNCTION,
+            ItemKind::TypeDef(_) => CompletionItemKind::STRUCT,
+            ItemKind::Union(_) => CompletionItemKind::ENUM,
+            ItemKind::Alias(_) => CompletionItemKind::TYPE_PARAMETER,
+            ItemKind::Trait(_) => CompletionItemKind::INTERFACE,
+            ItemKind::Const(_) => CompletionItemKind::CONSTANT,
+            ItemKind::Static(_) => CompletionItemKind::VARIABLE,
+            ItemKind::Impl(_) | ItemKind::Import(_) | ItemKind::Use(_) => {
+                CompletionItemKind::MODULE
+            }
+        }
+    }
+
+    /// Returns the symbol at the position and the span of its name,
+    /// if it can be renamed.
+    ///
+    /// Modules, `this` and everything that is defined in
+    /// the prelude can't be renamed.
+    fn renameable(&self, params: &TextDocumentPositionParams) -> Option<(Symbol, FileId, Span)> {
+        let (file, offset) = self.position(params)?;
+        let (symbol, span) = analysis::symbol_at(&self.db, file, offset)?.destruct();
+        let name = analysis::name(&self.db, symbol)?;
+        let (definition, _) = analysis::definition(&self.db, symbol)?;
+        let text = self.db.file_text(file);
+        let written = &text[std::ops::Range::<usize>::from(span)];
+        if written != name || Some(definition) == self.db.source_root().prelude() {
+            return None;
+        }
+        Some((symbol, file, span))
+    }
+
+    fn prepare_rename(&self, params: TextDocumentPositionParams) -> Option<PrepareRenameResponse> {
+        let (_, file, span) = self.renameable(&params)?;
+        let range = convert::range(&self.db, file, span);
+        Some(PrepareRenameResponse::Range(range))
+    }
+
+    fn rename(&self, params: RenameParams) -> Option<WorkspaceEdit> {
+        let (symbol, ..) = self.renameable(&params.text_document_position)?;
+        if !analysis::is_valid_name(&params.new_name) {
+            return None;
+        }
+
+        let mut changes = HashMap::<Url, Vec<TextEdit>>::new();
+        for (file, span) in analysis::references(&self.db, symbol) {
+            let uri = convert::url(&self.db, file)?;
+            let range = convert::range(&self.db, file, span);
+            changes
+                .entry(uri)
+                .or_default()
+                .push(TextEdit::new(range, params.new_name.clone()));
+        }
+        Some(WorkspaceEdit::new(changes))
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, ResponseError> {
+    serde_json::from_value(params).map_err(|err| ResponseError {
+        code: ErrorCode::InvalidParams as i32,
+        message: err.to_string(),
+        data: None,
+    })
+}
@@ -0,0 +1,469 @@
+//! The data structures that make up the MIR of a function.
+
+use cell_common::{source::Span, strings::StringId};
+use cell_syntax::ast::ItemId;
+use cell_typeck::{
+    consteval::ConstValue,
+    db::TypeckDatabase,
+    lower::AdtDef,
+    ty::{Ty, TyKind},
+};
+
+/// An index into the locals of a [`Body`].
+///
+/// [`Body`]: ./struct.Body.html
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Local(pub u32);
+
+/// The local that stores the return value of a function.
+pub const RETURN_PLACE: Local = Local(0);
+
+impl Local {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// An index into the basic blocks of a [`Body`].
+///
+/// [`Body`]: ./struct.Body.html
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BasicBlock(pub u32);
+
+/// The block where the execution of every function starts.
+pub const START_BLOCK: BasicBlock = BasicBlock(0);
+
+impl BasicBlock {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A position inside of a body.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub block: BasicBlock,
+    /// The index of the statement inside the block. The terminator
+    /// is at the index after the last statement.
+    pub statement: usize,
+}
+
+/// The MIR of a single function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Body {
+    /// The function this body belongs to.
+    pub item: ItemId,
+    /// Every local of the function.
+    ///
+    /// The first local is the [`RETURN_PLACE`], followed by one local
+    /// for every argument, starting with `this` for methods.
+    /// Every other local is a variable or a temporary.
+    ///
+    /// [`RETURN_PLACE`]: ./constant.RETURN_PLACE.html
+    pub locals: Vec<LocalDecl>,
+    /// The number of arguments, including `this`.
+    pub arg_count: usize,
+    pub blocks: Vec<BasicBlockData>,
+}
+
+impl Body {
+    /// Returns the declaration of the local.
+    pub fn local(&self, local: Local) -> &LocalDecl {
+        &self.locals[local.index()]
+    }
+
+    /// Returns the data of the basic block.
+    pub fn block(&self, block: BasicBlock) -> &BasicBlockData {
+        &self.blocks[block.index()]
+    }
+
+    /// Returns the locals of every argument.
+    pub fn args(&self) -> impl Iterator<Item = Local> {
+        (1..=self.arg_count as u32).map(Local)
+    }
+
+    /// Returns every basic block together with its index.
+    pub fn basic_blocks(&self) -> impl Iterator<Item = (BasicBlock, &BasicBlockData)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(idx, data)| (BasicBlock(idx as u32), data))
+    }
+
+    /// Returns the type of the return value.
+    pub fn return_ty(&self) -> Ty {
+        self.local(RETURN_PLACE).ty
+    }
+
+    /// Returns the blocks that can jump to every block.
+    ///
+    /// Every predecessor is only listed once, even if it has multiple
+    /// edges to the same block.
+    pub fn predecessors(&self) -> Vec<Vec<BasicBlock>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for (block, data) in self.basic_blocks() {
+            for target in data.terminator().kind.successors() {
+                let preds: &mut Vec<_> = &mut predecessors[target.index()];
+                if !preds.contains(&block) {
+                    preds.push(block);
+                }
+            }
+        }
+        predecessors
+    }
+}
+
+/// The declaration of a local.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalDecl {
+    pub ty: Ty,
+    /// The name of the variable, or `None` for temporaries.
+    pub name: Option<StringId>,
+    pub span: Span,
+}
+
+/// A sequence of statements that is always executed from the start
+/// to the end, followed by a terminator that transfers the control
+/// to other blocks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BasicBlockData {
+    pub statements: Vec<Statement>,
+    /// The terminator of this block.
+    ///
+    /// This is only `None` while the block is being built.
+    pub terminator: Option<Terminator>,
+}
+
+impl BasicBlockData {
+    /// Returns the terminator of this block.
+    pub fn terminator(&self) -> &Terminator {
+        self.terminator
+            .as_ref()
+            .expect("every basic block has a terminator")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub span: Span,
+    pub kind: StatementKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatementKind {
+    /// Evaluates the rvalue and writes it into the place.
+    Assign(Place, Rvalue),
+    /// Writes the operand of the block that jumped to this block into
+    /// the local.
+    ///
+    /// Phi nodes only exist while the body is in SSA form, and are
+    /// always at the start of a block, with one operand for every
+    /// predecessor.
+    Phi(Local, Vec<(BasicBlock, Operand)>),
+    /// A statement that does nothing.
+    Nop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terminator {
+    pub span: Span,
+    pub kind: TerminatorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminatorKind {
+    Goto {
+        target: BasicBlock,
+    },
+    /// Jumps to the block of the first value that is equal to `discr`,
+    /// or to `otherwise` if no value matches.
+    ///
+    /// The values are the bits of an integer, `bool` or `char`.
+    SwitchInt {
+        discr: Operand,
+        targets: Vec<(u128, BasicBlock)>,
+        otherwise: BasicBlock,
+    },
+    /// Returns the value inside the [`RETURN_PLACE`] to the caller.
+    ///
+    /// [`RETURN_PLACE`]: ./constant.RETURN_PLACE.html
+    Return,
+    /// Calls the function `func` with the generic arguments `generic_args`,
+    /// writes the result into `destination` and continues at `target`.
+    ///
+    /// Calls to trait methods point to the method inside the trait.
+    Call {
+        func: ItemId,
+        generic_args: Vec<Ty>,
+        args: Vec<Operand>,
+        destination: Place,
+        target: BasicBlock,
+    },
+    /// Marks a location that can never be reached, like the end of a
+    /// `match` without a matching arm.
+    Unreachable,
+}
+
+impl TerminatorKind {
+    /// Returns every block that can be executed after this terminator.
+    pub fn successors(&self) -> Vec<BasicBlock> {
+        match self {
+            TerminatorKind::Goto { target } | TerminatorKind::Call { target, .. } => vec![*target],
+            TerminatorKind::SwitchInt {
+                targets, otherwise, ..
+            } => targets
+                .iter()
+                .map(|(_, target)| *target)
+                .chain(std::iter::once(*otherwise))
+                .collect(),
+            TerminatorKind::Return | TerminatorKind::Unreachable => vec![],
+        }
+    }
+}
+
+/// A location in memory, like a local or a field behind a pointer.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Place {
+    pub local: Local,
+    pub projection: Vec<PlaceElem>,
+}
+
+/// A single projection of a place.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum PlaceElem {
+    /// Dereferences a pointer.
+    Deref,
+    /// The field at the index of a tuple, named tuple or `type`.
+    Field(u32),
+    /// The element at the offset, that is stored in the local, behind a
+    /// pointer. `p[i]` is the same place as `*(p + i)`.
+    Index(Local),
+    /// The data of the variant at the index of a union. This is only
+    /// valid if the union currently holds that variant.
+    Downcast(u32),
+}
+
+impl From<Local> for Place {
+    fn from(local: Local) -> Self {
+        Self {
+            local,
+            projection: vec![],
+        }
+    }
+}
+
+impl Place {
+    /// Returns this place with another projection appended.
+    pub fn project(&self, elem: PlaceElem) -> Place {
+        let mut place = self.clone();
+        place.projection.push(elem);
+        place
+    }
+
+    /// Returns the type of the value at this place.
+    pub fn ty(&self, db: &dyn TypeckDatabase, body: &Body) -> Ty {
+        self.projection
+            .iter()
+            .fold(body.local(self.local).ty, |ty, elem| match elem {
+                PlaceElem::Deref | PlaceElem::Index(_) => match ty.kind(db) {
+                    TyKind::Pointer(inner) => inner,
+                    _ => panic!("dereferenced a place that is not a pointer"),
+                },
+                PlaceElem::Field(idx) => field_ty(db, ty, *idx),
+                PlaceElem::Downcast(idx) => variant_ty(db, ty, *idx),
+            })
+    }
+}
+
+/// Returns the type of the field at the index of a tuple, named tuple or `type`.
+pub fn field_ty(db: &dyn TypeckDatabase, ty: Ty, idx: u32) -> Ty {
+    let idx = idx as usize;
+    match ty.kind(db) {
+        TyKind::Tuple(tys) => tys[idx],
+        TyKind::NamedTuple(fields) => fields[idx].1,
+        TyKind::Adt(item, args) => match &*db.adt_def(item) {
+            AdtDef::Struct { fields } => fields[idx].1.subst(db, &args),
+            AdtDef::Union { .. } => panic!("unions have no fields"),
+        },
+        _ => panic!("accessed a field of a type without fields"),
+    }
+}
+
+/// Returns the type of the data of the variant at the index of a union.
+///
+/// Variants without data have the type `()`.
+pub fn variant_ty(db: &dyn TypeckDatabase, ty: Ty, idx: u32) -> Ty {
+    let data = match ty.kind(db) {
+        TyKind::Adt(item, args) => match &*db.adt_def(item) {
+            AdtDef::Union { variants } => variants[idx as usize].1.map(|ty| ty.subst(db, &args)),
+            AdtDef::Struct { .. } => panic!("structs have no variants"),
+        },
+        _ => panic!("downcasted a type that is not a union"),
+    };
+    data.unwrap_or_else(|| TyKind::Unit.intern(db))
+}
+
+/// A value that is used by an rvalue or terminator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    /// Reads the value at the place.
+    Copy(Place),
+    Constant(Constant),
+}
+
+impl Operand {
+    pub fn ty(&self, db: &dyn TypeckDatabase, body: &Body) -> Ty {
+        match self {
+            Operand::Copy(place) => place.ty(db, body),
+            Operand::Constant(constant) => constant.ty,
+        }
+    }
+}
+
+/// A constant scalar value.
+///
+/// Constant tuples and named tuples are built using aggregates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constant {
+    pub ty: Ty,
+    pub val: ConstValue,
+}
+
+/// The right hand side of an assignment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rvalue {
+    Use(Operand),
+    BinaryOp(BinOp, Operand, Operand),
+    UnaryOp(UnOp, Operand),
+    /// Creates a pointer to the place.
+    AddressOf(Place),
+    /// Creates a pointer to a `static`.
+    AddressOfStatic(ItemId),
+    /// Reads the index of the variant that is stored inside a union.
+    ///
+    /// The discriminant is always a `u32`.
+    Discriminant(Place),
+    /// Creates a tuple, named tuple, `type` or union variant.
+    Aggregate(AggregateKind, Vec<Operand>),
+}
+
+impl Rvalue {
+    pub fn ty(&self, db: &dyn TypeckDatabase, body: &Body) -> Ty {
+        match self {
+            Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => op.ty(db, body),
+            Rvalue::BinaryOp(op, left, _) => {
+                if op.is_comparison() {
+                    TyKind::Bool.intern(db)
+                } else {
+                    left.ty(db, body)
+                }
+            }
+            Rvalue::AddressOf(place) => TyKind::Pointer(place.ty(db, body)).intern(db),
+            Rvalue::AddressOfStatic(item) => TyKind::Pointer(db.global_ty(*item)).intern(db),
+            Rvalue::Discriminant(_) => discriminant_ty(db),
+            Rvalue::Aggregate(kind, ops) => {
+                let tys = ops.iter().map(|op| op.ty(db, body));
+                let kind = match kind {
+                    AggregateKind::Tuple => TyKind::Tuple(tys.collect()),
+                    AggregateKind::NamedTuple(names) => {
+                        TyKind::NamedTuple(names.iter().copied().zip(tys).collect())
+                    }
+                    AggregateKind::Adt(item, args) | AggregateKind::Variant(item, _, args) => {
+                        TyKind::Adt(*item, args.clone())
+                    }
+                };
+                kind.intern(db)
+            }
+        }
+    }
+}
+
+/// Returns the type of a discriminant.
+pub fn discriminant_ty(db: &dyn TypeckDatabase) -> Ty {
+    TyKind::Int {
+        width: 32,
+        signed: false,
+    }
+    .intern(db)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AggregateKind {
+    Tuple,
+    /// A named tuple with the names of every field.
+    NamedTuple(Vec<StringId>),
+    /// A `type` with its generic arguments. The operands are
+    /// in the order of the fields of the definition.
+    Adt(ItemId, Vec<Ty>),
+    /// The variant at the index of a union, with the generic arguments
+    /// of the union. The only operand is the data of the variant, if any.
+    Variant(ItemId, u32, Vec<Ty>),
+}
+
+/// A binary operation.
+///
+/// Integer arithmetic traps on overflow, division by zero and
+/// shifts by more bits than the integer has.
+/// If the left operand is a pointer, `Add` and `Sub` move the pointer
+/// by the number of elements in the right operand.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinOp {
+    /// Checks if this operator compares its operands and produces a `bool`.
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge
+        )
+    }
+
+    /// Returns the symbol of the operator.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Pow => "**",
+            BinOp::BitAnd => "&",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "^",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum UnOp {
+    /// Logical negation for `bool`s and bitwise negation for integers.
+    Not,
+    /// Arithmetic negation.
+    Neg,
+}
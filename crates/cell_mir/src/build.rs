@@ -0,0 +1,860 @@
+//! Lowering of type checked function bodies into MIR.
+//!
+//! Every expression is lowered into the basic block that is currently
+//! being built. Expressions that produce a value either write it into
+//! a destination place, or are turned into an operand, which may require
+//! a temporary. Control flow like `if`, `while`, `match` and the logical
+//! operators is lowered into `SwitchInt` terminators.
+
+use crate::{
+    body::{
+        discriminant_ty, AggregateKind, BasicBlock, BasicBlockData, BinOp, Body, Constant, Local,
+        LocalDecl, Operand, Place, PlaceElem, Rvalue, Statement, StatementKind, Terminator,
+        TerminatorKind, UnOp, RETURN_PLACE,
+    },
+    db::MirDatabase,
+};
+use cell_common::{profiler, source::Span, strings::StringId};
+use cell_syntax::ast::{
+    self, Assign, Binary, Block, Expr, ExprId, ExprKind, If, ItemId, ItemKind, Literal, Match,
+    MethodCall, NamedTuple, PatternId, PatternKind, StmtKind, While,
+};
+use cell_typeck::{
+    consteval::{self, ConstValue},
+    infer::{Callee, LocalId, Res, TypeckResults},
+    lower::AdtDef,
+    ty::{Ty, TyKind},
+};
+use std::sync::Arc;
+
+pub(crate) fn mir_body(db: &dyn MirDatabase, item: ItemId) -> Option<Arc<Body>> {
+    let _profiler = profiler::trace("Mir", "Build");
+
+    let def = match db.lookup_intern_item(item).kind {
+        ItemKind::Def(def) => def,
+        _ => return None,
+    };
+    let block = def.body.as_ref()?;
+    let results = db.typeck(item);
+    if !results.errors.is_empty() || !db.check_item(item).is_empty() {
+        return None;
+    }
+
+    let ret = LocalDecl {
+        ty: db.fn_sig(item).ret,
+        name: None,
+        span: def.name.span,
+    };
+    let locals = std::iter::once(ret)
+        .chain(results.locals.iter().map(|local| LocalDecl {
+            ty: local.ty,
+            name: Some(local.name),
+            span: local.span,
+        }))
+        .collect();
+
+    let mut builder = Builder {
+        db,
+        results: &results,
+        locals,
+        blocks: vec![BasicBlockData::default()],
+        current: BasicBlock(0),
+        loops: vec![],
+        failed: false,
+    };
+    builder.block(block);
+    builder.finish(item, block.span)
+}
+
+/// The targets of `break` and `continue` inside a loop.
+struct LoopScope {
+    continue_: BasicBlock,
+    break_: BasicBlock,
+}
+
+struct Builder<'a> {
+    db: &'a dyn MirDatabase,
+    results: &'a TypeckResults,
+    locals: Vec<LocalDecl>,
+    blocks: Vec<BasicBlockData>,
+    /// The block new statements are added to.
+    current: BasicBlock,
+    loops: Vec<LoopScope>,
+    /// Set if a constant that is used by the function couldn't be evaluated.
+    failed: bool,
+}
+
+impl Builder<'_> {
+    fn finish(mut self, item: ItemId, span: Span) -> Option<Arc<Body>> {
+        if self.failed {
+            return None;
+        }
+
+        let ret = self.locals[RETURN_PLACE.index()].ty;
+        if self.kind(ret) == TyKind::Unit {
+            let unit = self.unit();
+            self.assign(span, RETURN_PLACE.into(), Rvalue::Use(unit));
+        }
+        self.terminate(span, TerminatorKind::Return);
+        debug_assert!(self.blocks.iter().all(|block| block.terminator.is_some()));
+
+        let arg_count = self.results.params.len() + self.results.this.is_some() as usize;
+        Some(Arc::new(Body {
+            item,
+            locals: self.locals,
+            arg_count,
+            blocks: self.blocks,
+        }))
+    }
+
+    fn ty(&self, kind: TyKind) -> Ty {
+        self.db.intern_ty(kind)
+    }
+
+    fn kind(&self, ty: Ty) -> TyKind {
+        self.db.lookup_intern_ty(ty)
+    }
+
+    fn type_of(&self, expr: ExprId) -> Ty {
+        self.results.type_of(expr)
+    }
+
+    /// Returns the MIR local of a local of the type checker.
+    fn local(&self, id: LocalId) -> Local {
+        Local(id.0 + 1)
+    }
+
+    fn temp(&mut self, ty: Ty, span: Span) -> Place {
+        let local = Local(self.locals.len() as u32);
+        self.locals.push(LocalDecl {
+            ty,
+            name: None,
+            span,
+        });
+        local.into()
+    }
+
+    fn new_block(&mut self) -> BasicBlock {
+        self.blocks.push(BasicBlockData::default());
+        BasicBlock(self.blocks.len() as u32 - 1)
+    }
+
+    fn assign(&mut self, span: Span, place: Place, rvalue: Rvalue) {
+        let statement = Statement {
+            span,
+            kind: StatementKind::Assign(place, rvalue),
+        };
+        self.blocks[self.current.index()].statements.push(statement);
+    }
+
+    fn terminate(&mut self, span: Span, kind: TerminatorKind) {
+        let block = &mut self.blocks[self.current.index()];
+        debug_assert!(block.terminator.is_none());
+        block.terminator = Some(Terminator { span, kind });
+    }
+
+    fn goto(&mut self, span: Span, target: BasicBlock) {
+        self.terminate(span, TerminatorKind::Goto { target });
+    }
+
+    /// Continues in a new block, after the current block was terminated
+    /// by an expression that never finishes, like `return`.
+    ///
+    /// The new block is unreachable.
+    fn dead_block(&mut self) {
+        self.current = self.new_block();
+    }
+
+    /// Jumps to `fail` if `cond` is `false` and continues in a new block otherwise.
+    fn branch_unless(&mut self, span: Span, cond: Operand, fail: BasicBlock) {
+        let next = self.new_block();
+        self.terminate(
+            span,
+            TerminatorKind::SwitchInt {
+                discr: cond,
+                targets: vec![(0, fail)],
+                otherwise: next,
+            },
+        );
+        self.current = next;
+    }
+
+    fn constant(&self, ty: Ty, val: ConstValue) -> Operand {
+        Operand::Constant(Constant { ty, val })
+    }
+
+    fn unit(&self) -> Operand {
+        self.constant(self.ty(TyKind::Unit), ConstValue::Unit)
+    }
+
+    fn block(&mut self, block: &Block) {
+        for &id in &block.stmts {
+            match self.db.lookup_intern_stmt(id).kind {
+                StmtKind::Var(var) => {
+                    let local = self.local(self.results.vars[&id]);
+                    self.expr_into(local.into(), var.val);
+                }
+                StmtKind::Expr(expr) => self.effect(expr),
+            }
+        }
+    }
+
+    /// Lowers an expression whose value is not used.
+    fn effect(&mut self, id: ExprId) {
+        let expr = self.db.lookup_intern_expr(id);
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::If(if_) => self.if_(span, if_),
+            ExprKind::While(while_) => self.while_(span, while_),
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Assign(assign) => self.assign_expr(assign),
+            ExprKind::Return(val) => {
+                match val {
+                    Some(val) => self.expr_into(RETURN_PLACE.into(), *val),
+                    None => {
+                        let unit = self.unit();
+                        self.assign(span, RETURN_PLACE.into(), Rvalue::Use(unit));
+                    }
+                }
+                self.terminate(span, TerminatorKind::Return);
+                self.dead_block();
+            }
+            ExprKind::Break | ExprKind::Continue => {
+                let scope = self.loops.last().expect("`break` outside of a loop");
+                let target = match expr.kind {
+                    ExprKind::Break => scope.break_,
+                    _ => scope.continue_,
+                };
+                self.goto(span, target);
+                self.dead_block();
+            }
+            _ => {
+                let temp = self.temp(self.type_of(id), span);
+                self.expr_into(temp, id);
+            }
+        }
+    }
+
+    /// Lowers an expression and writes its value into `dest`.
+    fn expr_into(&mut self, dest: Place, id: ExprId) {
+        let expr = self.db.lookup_intern_expr(id);
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Binary(bin)
+                if matches!(
+                    bin.op.data(),
+                    ast::BinOp::LogicalAnd | ast::BinOp::LogicalOr
+                ) =>
+            {
+                self.logical(dest, span, bin)
+            }
+            ExprKind::If(_) | ExprKind::While(_) | ExprKind::Block(_) | ExprKind::Assign(_) => {
+                self.effect(id);
+                let unit = self.unit();
+                self.assign(span, dest, Rvalue::Use(unit));
+            }
+            ExprKind::Return(_) | ExprKind::Break | ExprKind::Continue => self.effect(id),
+            ExprKind::Match(match_) => self.match_(dest, span, match_),
+            ExprKind::Call(call) => self.call(dest, id, span, None, &call.args),
+            ExprKind::MethodCall(call) => self.method_call(dest, id, span, call),
+            _ => {
+                let rvalue = self.rvalue(id, &expr);
+                self.assign(span, dest, rvalue);
+            }
+        }
+    }
+
+    /// Lowers an expression that produces a value without any control flow.
+    fn rvalue(&mut self, id: ExprId, expr: &Expr) -> Rvalue {
+        match &expr.kind {
+            ExprKind::Literal(lit) => Rvalue::Use(self.literal(id, lit, false)),
+            ExprKind::Binary(bin) => {
+                let left = self.operand(bin.left);
+                let right = self.operand(bin.right);
+                Rvalue::BinaryOp(bin_op(*bin.op.data()), left, right)
+            }
+            ExprKind::Comparison(cmp) => {
+                let left = self.operand(cmp.left);
+                let right = self.operand(cmp.right);
+                Rvalue::BinaryOp(cmp_op(*cmp.op.data()), left, right)
+            }
+            ExprKind::Unary(unary) => match unary.op.data() {
+                ast::UnOp::Ref => Rvalue::AddressOf(self.place(unary.val)),
+                ast::UnOp::Deref => Rvalue::Use(Operand::Copy(self.place(id))),
+                ast::UnOp::Not => Rvalue::UnaryOp(UnOp::Not, self.operand(unary.val)),
+                ast::UnOp::Plus => Rvalue::Use(self.operand(unary.val)),
+                ast::UnOp::Minus => match self.db.lookup_intern_expr(unary.val).kind {
+                    ExprKind::Literal(lit) => Rvalue::Use(self.literal(id, &lit, true)),
+                    _ => Rvalue::UnaryOp(UnOp::Neg, self.operand(unary.val)),
+                },
+            },
+            ExprKind::Tuple(tuple) => {
+                let ops = tuple.values.iter().map(|&val| self.operand(val)).collect();
+                Rvalue::Aggregate(AggregateKind::Tuple, ops)
+            }
+            ExprKind::NamedTuple(tuple) => self.named_tuple(id, tuple),
+            ExprKind::Path(_) => self.path(id, expr.span),
+            ExprKind::Field(_) | ExprKind::This => Rvalue::Use(Operand::Copy(self.place(id))),
+            _ => unreachable!("expression with control flow lowered as rvalue"),
+        }
+    }
+
+    /// Lowers an expression into an operand, which may require a temporary.
+    fn operand(&mut self, id: ExprId) -> Operand {
+        let expr = self.db.lookup_intern_expr(id);
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Literal(lit) => return self.literal(id, lit, false),
+            ExprKind::Unary(unary) if *unary.op.data() == ast::UnOp::Minus => {
+                if let ExprKind::Literal(lit) = self.db.lookup_intern_expr(unary.val).kind {
+                    return self.literal(id, &lit, true);
+                }
+                let val = self.operand(unary.val);
+                let temp = self.temp(self.type_of(id), span);
+                self.assign(span, temp.clone(), Rvalue::UnaryOp(UnOp::Neg, val));
+                return Operand::Copy(temp);
+            }
+            ExprKind::Path(_) => match self.results.resolutions.get(&id) {
+                Some(Res::Local(local)) => return Operand::Copy(self.local(*local).into()),
+                Some(Res::Def(item)) => {
+                    if let ItemKind::Const(_) = self.db.lookup_intern_item(*item).kind {
+                        return self.const_item(*item, span);
+                    }
+                }
+                _ => {}
+            },
+            ExprKind::This => return Operand::Copy(self.this()),
+            _ => {}
+        }
+
+        let temp = self.temp(self.type_of(id), span);
+        self.expr_into(temp.clone(), id);
+        Operand::Copy(temp)
+    }
+
+    /// Lowers an expression into a place.
+    ///
+    /// Expressions that don't refer to a memory location are
+    /// stored inside a temporary.
+    fn place(&mut self, id: ExprId) -> Place {
+        let expr = self.db.lookup_intern_expr(id);
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Path(_) => match self.results.resolutions.get(&id) {
+                Some(Res::Local(local)) => return self.local(*local).into(),
+                Some(Res::Def(item)) => {
+                    if let ItemKind::Static(_) = self.db.lookup_intern_item(*item).kind {
+                        let ty = self.ty(TyKind::Pointer(self.db.global_ty(*item)));
+                        let ptr = self.temp(ty, span);
+                        self.assign(span, ptr.clone(), Rvalue::AddressOfStatic(*item));
+                        return ptr.project(PlaceElem::Deref);
+                    }
+                }
+                _ => {}
+            },
+            ExprKind::This => return self.this(),
+            ExprKind::Unary(unary) if *unary.op.data() == ast::UnOp::Deref => {
+                // `*(ptr + idx)` is lowered into an index projection
+                let val = self.db.lookup_intern_expr(unary.val);
+                if let ExprKind::Binary(bin) = val.kind {
+                    let is_ptr = matches!(self.kind(self.type_of(bin.left)), TyKind::Pointer(_));
+                    if *bin.op.data() == ast::BinOp::Add && is_ptr {
+                        let base = self.place(bin.left);
+                        let idx = self.operand(bin.right);
+                        let idx = self.operand_local(idx, span);
+                        return base.project(PlaceElem::Index(idx));
+                    }
+                }
+                return self.place(unary.val).project(PlaceElem::Deref);
+            }
+            ExprKind::Field(field) => {
+                let mut place = self.place(field.val);
+                let mut ty = self.type_of(field.val);
+                while let TyKind::Pointer(inner) = self.kind(ty) {
+                    place = place.project(PlaceElem::Deref);
+                    ty = inner;
+                }
+                return place.project(PlaceElem::Field(self.results.fields[&id]));
+            }
+            _ => {}
+        }
+
+        let temp = self.temp(self.type_of(id), span);
+        self.expr_into(temp.clone(), id);
+        temp
+    }
+
+    /// Returns a local that holds the value of the operand.
+    fn operand_local(&mut self, op: Operand, span: Span) -> Local {
+        match op {
+            Operand::Copy(place) if place.projection.is_empty() => place.local,
+            op => {
+                let ty = match &op {
+                    Operand::Copy(_) => unreachable!(),
+                    Operand::Constant(constant) => constant.ty,
+                };
+                let temp = self.temp(ty, span);
+                self.assign(span, temp.clone(), Rvalue::Use(op));
+                temp.local
+            }
+        }
+    }
+
+    fn this(&self) -> Place {
+        let this = self.results.this.expect("`this` outside of a method");
+        self.local(this).into()
+    }
+
+    /// Evaluates the literal of the expression `id`, which may be negated.
+    fn literal(&mut self, id: ExprId, lit: &Literal, negate: bool) -> Operand {
+        let ty = self.type_of(id);
+        let val = self.literal_value(ty, lit, negate);
+        self.constant(ty, val)
+    }
+
+    fn literal_value(&mut self, ty: Ty, lit: &Literal, negate: bool) -> ConstValue {
+        consteval::eval_literal(lit, negate, &self.kind(ty)).unwrap_or_else(|_| {
+            self.failed = true;
+            ConstValue::Unit
+        })
+    }
+
+    /// Returns the value of a `const` item.
+    fn const_item(&mut self, item: ItemId, span: Span) -> Operand {
+        match self.db.const_value(item) {
+            Ok(val) => self.const_value(val, self.db.global_ty(item), span),
+            Err(_) => {
+                self.failed = true;
+                self.unit()
+            }
+        }
+    }
+
+    /// Turns a constant into an operand, building tuples and
+    /// named tuples out of their fields.
+    fn const_value(&mut self, val: ConstValue, ty: Ty, span: Span) -> Operand {
+        let (kind, vals) = match (val, self.kind(ty)) {
+            (ConstValue::Tuple(vals), TyKind::Tuple(tys)) => {
+                (AggregateKind::Tuple, vals.into_iter().zip(tys).collect())
+            }
+            (ConstValue::NamedTuple(vals), TyKind::NamedTuple(fields)) => {
+                let names = fields.iter().map(|(name, _)| *name).collect();
+                (AggregateKind::NamedTuple(names), by_name(vals, fields))
+            }
+            (ConstValue::NamedTuple(vals), TyKind::Adt(item, args)) => {
+                let fields = match &*self.db.adt_def(item) {
+                    AdtDef::Struct { fields } => fields
+                        .iter()
+                        .map(|(name, ty)| (*name, ty.subst(self.db, &args)))
+                        .collect(),
+                    AdtDef::Union { .. } => unreachable!("union constructed from a named tuple"),
+                };
+                (AggregateKind::Adt(item, args), by_name(vals, fields))
+            }
+            (val, _) => return self.constant(ty, val),
+        };
+
+        let ops = vals
+            .into_iter()
+            .map(|(val, ty)| self.const_value(val, ty, span))
+            .collect();
+        let temp = self.temp(ty, span);
+        self.assign(span, temp.clone(), Rvalue::Aggregate(kind, ops));
+        Operand::Copy(temp)
+    }
+
+    fn path(&mut self, id: ExprId, span: Span) -> Rvalue {
+        match self.results.resolutions[&id] {
+            Res::Local(local) => Rvalue::Use(Operand::Copy(self.local(local).into())),
+            Res::Variant(union, idx) => {
+                let args = match self.kind(self.type_of(id)) {
+                    TyKind::Adt(_, args) => args,
+                    _ => vec![],
+                };
+                Rvalue::Aggregate(AggregateKind::Variant(union, idx, args), vec![])
+            }
+            Res::Def(item) => match self.db.lookup_intern_item(item).kind {
+                ItemKind::Const(_) => Rvalue::Use(self.const_item(item, span)),
+                ItemKind::Static(_) => Rvalue::Use(Operand::Copy(self.place(id))),
+                _ => unreachable!("path to an item that is not a value"),
+            },
+        }
+    }
+
+    /// Lowers a named tuple, or the construction of a `type`.
+    ///
+    /// The values are evaluated in the order they are written,
+    /// but stored in the order of the fields of the type.
+    fn named_tuple(&mut self, id: ExprId, tuple: &NamedTuple) -> Rvalue {
+        let values = tuple
+            .values
+            .iter()
+            .map(|(name, val)| (name.id, self.operand(*val)))
+            .collect::<Vec<_>>();
+
+        let (kind, names) = match self.kind(self.type_of(id)) {
+            TyKind::NamedTuple(fields) => {
+                let names = fields.into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+                (AggregateKind::NamedTuple(names.clone()), names)
+            }
+            TyKind::Adt(item, args) => {
+                let names = match &*self.db.adt_def(item) {
+                    AdtDef::Struct { fields } => fields.iter().map(|(name, _)| *name).collect(),
+                    AdtDef::Union { .. } => unreachable!("union constructed from a named tuple"),
+                };
+                (AggregateKind::Adt(item, args), names)
+            }
+            _ => unreachable!("named tuple with a type that has no fields"),
+        };
+
+        let ops = names
+            .iter()
+            .map(|name| {
+                let (_, op) = values
+                    .iter()
+                    .find(|(value, _)| value == name)
+                    .expect("every field is initialized");
+                op.clone()
+            })
+            .collect();
+        Rvalue::Aggregate(kind, ops)
+    }
+
+    /// Lowers `&&` and `||`, which only evaluate the right operand if necessary.
+    fn logical(&mut self, dest: Place, span: Span, bin: &Binary) {
+        let left = self.operand(bin.left);
+        let right = self.new_block();
+        let short = self.new_block();
+        let join = self.new_block();
+
+        let is_or = *bin.op.data() == ast::BinOp::LogicalOr;
+        let (targets, otherwise) = if is_or {
+            (vec![(0, right)], short)
+        } else {
+            (vec![(0, short)], right)
+        };
+        self.terminate(
+            span,
+            TerminatorKind::SwitchInt {
+                discr: left,
+                targets,
+                otherwise,
+            },
+        );
+
+        self.current = short;
+        let val = self.constant(self.ty(TyKind::Bool), ConstValue::Bool(is_or));
+        self.assign(span, dest.clone(), Rvalue::Use(val));
+        self.goto(span, join);
+
+        self.current = right;
+        self.expr_into(dest, bin.right);
+        self.goto(span, join);
+        self.current = join;
+    }
+
+    fn if_(&mut self, span: Span, if_: &If) {
+        let join = self.new_block();
+        let arms = std::iter::once((if_.cond, &if_.then))
+            .chain(if_.arms.iter().map(|arm| (arm.cond, &arm.action)));
+        for (cond, block) in arms {
+            let cond = self.operand(cond);
+            let then = self.new_block();
+            let next = self.new_block();
+            self.terminate(
+                span,
+                TerminatorKind::SwitchInt {
+                    discr: cond,
+                    targets: vec![(0, next)],
+                    otherwise: then,
+                },
+            );
+
+            self.current = then;
+            self.block(block);
+            self.goto(span, join);
+            self.current = next;
+        }
+
+        if let Some(else_) = &if_.else_ {
+            self.block(else_);
+        }
+        self.goto(span, join);
+        self.current = join;
+    }
+
+    fn while_(&mut self, span: Span, while_: &While) {
+        let header = self.new_block();
+        let exit = self.new_block();
+        self.goto(span, header);
+        self.current = header;
+
+        if let Some(cond) = while_.cond {
+            let cond = self.operand(cond);
+            self.branch_unless(span, cond, exit);
+        }
+
+        self.loops.push(LoopScope {
+            continue_: header,
+            break_: exit,
+        });
+        self.block(&while_.block);
+        self.loops.pop();
+
+        self.goto(span, header);
+        self.current = exit;
+    }
+
+    fn assign_expr(&mut self, assign: &Assign) {
+        let span = self.db.lookup_intern_expr(assign.target).span;
+        let target = self.place(assign.target);
+        match &assign.op {
+            Some(op) => {
+                let val = self.operand(assign.val);
+                let rvalue =
+                    Rvalue::BinaryOp(bin_op(*op.data()), Operand::Copy(target.clone()), val);
+                self.assign(span, target, rvalue);
+            }
+            None => self.expr_into(target, assign.val),
+        }
+    }
+
+    /// Lowers a `match` by testing the pattern of every arm one after another.
+    fn match_(&mut self, dest: Place, span: Span, match_: &Match) {
+        let scrutinee = self.place(match_.val);
+        let join = self.new_block();
+        for arm in &match_.arms {
+            let fail = self.new_block();
+            self.pattern(&scrutinee, arm.pattern, fail);
+            self.expr_into(dest.clone(), arm.body);
+            self.goto(span, join);
+            self.current = fail;
+        }
+
+        // matches are exhaustive, so the last arm can never fail
+        self.terminate(span, TerminatorKind::Unreachable);
+        self.current = join;
+    }
+
+    /// Tests if the value at `place` matches the pattern, jumps to `fail` if it
+    /// doesn't, and binds every variable of the pattern otherwise.
+    fn pattern(&mut self, place: &Place, id: PatternId, fail: BasicBlock) {
+        let pattern = self.db.lookup_intern_pattern(id);
+        let span = pattern.span;
+        let ty = self.results.pattern_types[&id];
+
+        match pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Binding(_) => {
+                let local = self.local(self.results.bindings[&id]);
+                self.assign(
+                    span,
+                    local.into(),
+                    Rvalue::Use(Operand::Copy(place.clone())),
+                );
+            }
+            PatternKind::Literal(lit) => {
+                let val = self.literal_value(ty, &lit, false);
+                let bits = match &val {
+                    ConstValue::Int(int) => Some(int.as_u128()),
+                    ConstValue::Bool(val) => Some(*val as u128),
+                    ConstValue::Char(val) => Some(*val as u128),
+                    ConstValue::Unit => return,
+                    _ => None,
+                };
+
+                match bits {
+                    Some(bits) => {
+                        let next = self.new_block();
+                        self.terminate(
+                            span,
+                            TerminatorKind::SwitchInt {
+                                discr: Operand::Copy(place.clone()),
+                                targets: vec![(bits, next)],
+                                otherwise: fail,
+                            },
+                        );
+                        self.current = next;
+                    }
+                    None => {
+                        let val = self.constant(ty, val);
+                        let rvalue = Rvalue::BinaryOp(BinOp::Eq, Operand::Copy(place.clone()), val);
+                        self.test(span, rvalue, fail);
+                    }
+                }
+            }
+            PatternKind::Range { start, end } => {
+                for (lit, op) in [(start, BinOp::Ge), (end, BinOp::Le)] {
+                    let val = self.literal_value(ty, &lit, false);
+                    let val = self.constant(ty, val);
+                    let rvalue = Rvalue::BinaryOp(op, Operand::Copy(place.clone()), val);
+                    self.test(span, rvalue, fail);
+                }
+            }
+            PatternKind::Tuple(pats) => {
+                for (idx, pat) in pats.into_iter().enumerate() {
+                    self.pattern(&place.project(PlaceElem::Field(idx as u32)), pat, fail);
+                }
+            }
+            PatternKind::NamedTuple(fields) | PatternKind::Struct { fields, .. } => {
+                for (name, pat) in fields {
+                    let idx = self.field_index(ty, name.id);
+                    self.pattern(&place.project(PlaceElem::Field(idx)), pat, fail);
+                }
+            }
+            PatternKind::Variant { data, .. } => {
+                let (_, idx) = self.results.pattern_variants[&id];
+                let discr = self.temp(discriminant_ty(self.db), span);
+                self.assign(span, discr.clone(), Rvalue::Discriminant(place.clone()));
+
+                let next = self.new_block();
+                self.terminate(
+                    span,
+                    TerminatorKind::SwitchInt {
+                        discr: Operand::Copy(discr),
+                        targets: vec![(idx as u128, next)],
+                        otherwise: fail,
+                    },
+                );
+                self.current = next;
+
+                if let Some(data) = data {
+                    self.pattern(&place.project(PlaceElem::Downcast(idx)), data, fail);
+                }
+            }
+        }
+    }
+
+    /// Evaluates a comparison and jumps to `fail` if it's `false`.
+    fn test(&mut self, span: Span, rvalue: Rvalue, fail: BasicBlock) {
+        let cond = self.temp(self.ty(TyKind::Bool), span);
+        self.assign(span, cond.clone(), rvalue);
+        self.branch_unless(span, Operand::Copy(cond), fail);
+    }
+
+    /// Returns the index of the field `name` of a named tuple or `type`.
+    fn field_index(&self, ty: Ty, name: StringId) -> u32 {
+        let idx = match self.kind(ty) {
+            TyKind::NamedTuple(fields) => fields.iter().position(|(field, _)| *field == name),
+            TyKind::Adt(item, _) => self.db.adt_def(item).field(name).map(|(idx, _)| idx),
+            _ => None,
+        };
+        idx.expect("fields of patterns are checked during type checking") as u32
+    }
+
+    /// Lowers a call, or the construction of a variant, with an optional
+    /// receiver that is passed as the first argument.
+    fn call(
+        &mut self,
+        dest: Place,
+        id: ExprId,
+        span: Span,
+        receiver: Option<Operand>,
+        args: &[ExprId],
+    ) {
+        let callee = self.results.callees[&id].clone();
+        let ops = receiver
+            .into_iter()
+            .chain(args.iter().map(|&arg| self.operand(arg)))
+            .collect::<Vec<_>>();
+
+        match callee {
+            Callee::Fn { def, args } => {
+                let target = self.new_block();
+                self.terminate(
+                    span,
+                    TerminatorKind::Call {
+                        func: def,
+                        generic_args: args,
+                        args: ops,
+                        destination: dest,
+                        target,
+                    },
+                );
+                self.current = target;
+            }
+            Callee::Variant {
+                union,
+                variant,
+                args,
+            } => {
+                let kind = AggregateKind::Variant(union, variant, args);
+                self.assign(span, dest, Rvalue::Aggregate(kind, ops));
+            }
+        }
+    }
+
+    fn method_call(&mut self, dest: Place, id: ExprId, span: Span, call: &MethodCall) {
+        let receiver = match self.results.receivers.get(&id) {
+            Some(adjust) => {
+                let mut place = self.place(call.receiver);
+                let mut ty = self.type_of(call.receiver);
+                for _ in 0..adjust.derefs {
+                    place = place.project(PlaceElem::Deref);
+                    ty = match self.kind(ty) {
+                        TyKind::Pointer(inner) => inner,
+                        _ => unreachable!("dereferenced a receiver that is not a pointer"),
+                    };
+                }
+
+                if adjust.autoref {
+                    let ptr = self.temp(self.ty(TyKind::Pointer(ty)), span);
+                    self.assign(span, ptr.clone(), Rvalue::AddressOf(place));
+                    Some(Operand::Copy(ptr))
+                } else {
+                    Some(Operand::Copy(place))
+                }
+            }
+            // the construction of a variant
+            None => None,
+        };
+        self.call(dest, id, span, receiver, &call.args);
+    }
+}
+
+/// Orders the values of a named tuple by the given fields.
+fn by_name(
+    vals: Vec<(StringId, ConstValue)>,
+    fields: Vec<(StringId, Ty)>,
+) -> Vec<(ConstValue, Ty)> {
+    fields
+        .into_iter()
+        .map(|(name, ty)| {
+            let (_, val) = vals
+                .iter()
+                .find(|(field, _)| *field == name)
+                .expect("every field is initialized");
+            (val.clone(), ty)
+        })
+        .collect()
+}
+
+fn bin_op(op: ast::BinOp) -> BinOp {
+    match op {
+        ast::BinOp::BitiwseAnd => BinOp::BitAnd,
+        ast::BinOp::BitwiseOr => BinOp::BitOr,
+        ast::BinOp::BitwiseXor => BinOp::BitXor,
+        ast::BinOp::LeftShift => BinOp::Shl,
+        ast::BinOp::RightShift => BinOp::Shr,
+        ast::BinOp::Add => BinOp::Add,
+        ast::BinOp::Sub => BinOp::Sub,
+        ast::BinOp::Mul => BinOp::Mul,
+        ast::BinOp::Div => BinOp::Div,
+        ast::BinOp::Mod => BinOp::Rem,
+        ast::BinOp::Pow => BinOp::Pow,
+        ast::BinOp::LogicalAnd | ast::BinOp::LogicalOr => {
+            unreachable!("logical operators are lowered into branches")
+        }
+    }
+}
+
+fn cmp_op(op: ast::CmpOp) -> BinOp {
+    match op {
+        ast::CmpOp::Equal => BinOp::Eq,
+        ast::CmpOp::NotEqual => BinOp::Ne,
+        ast::CmpOp::Less => BinOp::Lt,
+        ast::CmpOp::LessEqual => BinOp::Le,
+        ast::CmpOp::Greater => BinOp::Gt,
+        ast::CmpOp::GreaterEqual => BinOp::Ge,
+    }
+}
@@ -0,0 +1,33 @@
+//! The salsa database for building MIR.
+
+use crate::{
+    body::Body,
+    build,
+    layout::{self, Layout, Target},
+    opt::{self, OptLevel},
+};
+use cell_syntax::ast::ItemId;
+use cell_typeck::{db::TypeckDatabase, ty::Ty};
+use std::sync::Arc;
+
+#[salsa::query_group(MirDatabaseStorage)]
+pub trait MirDatabase: TypeckDatabase {
+    /// Builds the MIR of a function.
+    ///
+    /// Returns `None` if the item is not a function with a body,
+    /// or if the function contains errors.
+    #[salsa::invoke(build::mir_body)]
+    fn mir_body(&self, item: ItemId) -> Option<Arc<Body>>;
+
+    /// Returns the MIR of a function after running every optimization
+    /// that is enabled at the level.
+    #[salsa::invoke(opt::optimized_mir)]
+    fn optimized_mir(&self, item: ItemId, level: OptLevel) -> Option<Arc<Body>>;
+
+    /// Computes the size, alignment and field offsets of a type.
+    ///
+    /// The type must not contain generic parameters.
+    #[salsa::invoke(layout::layout_of)]
+    #[salsa::cycle(layout::layout_cycle)]
+    fn layout_of(&self, ty: Ty, target: Target) -> Arc<Layout>;
+}
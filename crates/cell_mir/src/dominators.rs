@@ -0,0 +1,149 @@
+//! Computes which blocks of a body dominate each other.
+//!
+//! A block dominates another block if every path from the start block to
+//! the other block goes through it. The dominators are computed using
+//! the algorithm from "A Simple, Fast Dominance Algorithm" by Cooper,
+//! Harvey and Kennedy.
+
+use crate::body::{BasicBlock, Body, START_BLOCK};
+
+/// The dominator tree of a body.
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    /// The immediate dominator of every block, which is the block
+    /// itself for the start block and `None` for unreachable blocks.
+    idoms: Vec<Option<BasicBlock>>,
+    /// The position of every reachable block in reverse postorder.
+    order: Vec<usize>,
+}
+
+impl Dominators {
+    pub fn new(body: &Body) -> Self {
+        let rpo = reverse_postorder(body);
+        let mut order = vec![usize::MAX; body.blocks.len()];
+        for (idx, block) in rpo.iter().enumerate() {
+            order[block.index()] = idx;
+        }
+
+        let predecessors = body.predecessors();
+        let mut idoms = vec![None; body.blocks.len()];
+        idoms[START_BLOCK.index()] = Some(START_BLOCK);
+
+        let intersect = |idoms: &[Option<BasicBlock>], mut a: BasicBlock, mut b: BasicBlock| {
+            while a != b {
+                while order[a.index()] > order[b.index()] {
+                    a = idoms[a.index()].unwrap();
+                }
+                while order[b.index()] > order[a.index()] {
+                    b = idoms[b.index()].unwrap();
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in rpo.iter().skip(1) {
+                let mut preds = predecessors[block.index()]
+                    .iter()
+                    .copied()
+                    .filter(|pred| idoms[pred.index()].is_some());
+                let first = preds.next().unwrap();
+                let idom = preds.fold(first, |idom, pred| intersect(&idoms, pred, idom));
+                if idoms[block.index()] != Some(idom) {
+                    idoms[block.index()] = Some(idom);
+                    changed = true;
+                }
+            }
+        }
+
+        Self { idoms, order }
+    }
+
+    /// Checks if the block can be reached from the start block.
+    pub fn is_reachable(&self, block: BasicBlock) -> bool {
+        self.idoms[block.index()].is_some()
+    }
+
+    /// Returns the closest block that dominates the block, other than itself.
+    ///
+    /// Returns `None` for the start block and for unreachable blocks.
+    pub fn immediate_dominator(&self, block: BasicBlock) -> Option<BasicBlock> {
+        self.idoms[block.index()].filter(|&idom| idom != block)
+    }
+
+    /// Checks if `a` dominates `b`. Every block dominates itself.
+    pub fn dominates(&self, a: BasicBlock, mut b: BasicBlock) -> bool {
+        if !self.is_reachable(a) || !self.is_reachable(b) {
+            return false;
+        }
+        while self.order[b.index()] > self.order[a.index()] {
+            b = self.idoms[b.index()].unwrap();
+        }
+        a == b
+    }
+
+    /// Returns the blocks that are immediately dominated by every block.
+    pub fn children(&self) -> Vec<Vec<BasicBlock>> {
+        let mut children = vec![vec![]; self.idoms.len()];
+        for idx in 0..self.idoms.len() {
+            let block = BasicBlock(idx as u32);
+            if let Some(idom) = self.immediate_dominator(block) {
+                children[idom.index()].push(block);
+            }
+        }
+        children
+    }
+
+    /// Returns the dominance frontier of every block, which are the blocks
+    /// where the dominance of the block ends.
+    pub fn frontiers(&self, body: &Body) -> Vec<Vec<BasicBlock>> {
+        let mut frontiers = vec![vec![]; body.blocks.len()];
+        for (block, preds) in body.predecessors().into_iter().enumerate() {
+            let block = BasicBlock(block as u32);
+            let idom = match self.idoms[block.index()] {
+                Some(idom) if preds.len() >= 2 => idom,
+                _ => continue,
+            };
+
+            for pred in preds.into_iter().filter(|&pred| self.is_reachable(pred)) {
+                let mut runner = pred;
+                while runner != idom {
+                    let frontier: &mut Vec<_> = &mut frontiers[runner.index()];
+                    if !frontier.contains(&block) {
+                        frontier.push(block);
+                    }
+                    runner = self.idoms[runner.index()].unwrap();
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+/// Returns every reachable block in reverse postorder, which means that
+/// every block comes before its successors, ignoring back edges.
+pub fn reverse_postorder(body: &Body) -> Vec<BasicBlock> {
+    let mut visited = vec![false; body.blocks.len()];
+    let mut postorder = vec![];
+    let mut stack = vec![(START_BLOCK, 0)];
+    visited[START_BLOCK.index()] = true;
+
+    while let Some((block, idx)) = stack.pop() {
+        let successors = body.block(block).terminator().kind.successors();
+        match successors.get(idx) {
+            Some(&next) => {
+                stack.push((block, idx + 1));
+                if !visited[next.index()] {
+                    visited[next.index()] = true;
+                    stack.push((next, 0));
+                }
+            }
+            None => postorder.push(block),
+        }
+    }
+
+    postorder.reverse();
+    postorder
+}
@@ -0,0 +1,467 @@
+//! The memory layout of types.
+//!
+//! Fields are always stored in declaration order, each aligned to its
+//! natural alignment like in C. How a `type` or union is laid out
+//! further depends on its representation:
+//!
+//! - `@repr(C)` types match the equivalent C struct. Unions are a
+//!   `u32` tag followed by a C union of the data of every variant.
+//! - `@repr(packed)` types are laid out like `@repr(C)` types, but
+//!   without any padding and with an alignment of 1.
+//! - every other union uses the smallest tag that fits all variants. If
+//!   a union has one variant without data and one whose data contains
+//!   a pointer or string, like `Option[*T]`, the variant without data
+//!   is stored as a null pointer instead, and the union has no tag.
+//!
+//! Types that are zero sized in cell, like `()`, are zero sized here too.
+
+use crate::{
+    body::{field_ty, variant_ty},
+    db::MirDatabase,
+    mono::{self, Instance},
+    opt::OptLevel,
+};
+use cell_common::{
+    error::Error,
+    source::{FileId, Locatable},
+};
+use cell_syntax::ast::{ItemKind, Repr};
+use cell_typeck::{
+    lower::AdtDef,
+    ty::{Ty, TyKind},
+};
+use std::{collections::HashSet, fmt::Write, sync::Arc};
+
+/// The machine a layout is computed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Target {
+    /// The size of a pointer in bytes.
+    pub pointer_size: u32,
+    /// The greatest alignment of any scalar. Larger scalars,
+    /// like `i128`, are aligned to this.
+    pub max_align: u32,
+}
+
+impl Target {
+    /// The machine the compiler is running on.
+    pub const HOST: Target = Target {
+        pointer_size: std::mem::size_of::<usize>() as u32,
+        max_align: 16,
+    };
+
+    /// The `wasm32` WebAssembly target.
+    pub const WASM32: Target = Target {
+        pointer_size: 4,
+        max_align: 8,
+    };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u32,
+    pub align: u32,
+    /// The offset of every field of a tuple, named tuple or `type`.
+    pub fields: Vec<u32>,
+    pub variants: Variants,
+    /// A pointer inside this type, that is never null.
+    pub niche: Option<Niche>,
+}
+
+/// How the variant of a union is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Variants {
+    /// The type is not a union.
+    Single,
+    /// The index of the variant is stored at offset 0, followed by
+    /// the data of the variant at `data_offset`.
+    Tagged { tag_size: u32, data_offset: u32 },
+    /// The union has no tag, and its data is stored at offset 0. The
+    /// `niche_variant` is stored by setting the `niche` of the data
+    /// of the `dataful` variant to null.
+    Niche {
+        dataful: u32,
+        niche_variant: u32,
+        niche: Niche,
+    },
+}
+
+/// The location of a pointer or string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Niche {
+    pub offset: u32,
+    pub size: u32,
+    /// The indices of the fields that lead to the pointer.
+    pub path: Vec<u32>,
+}
+
+impl Layout {
+    fn scalar(size: u32, target: Target) -> Self {
+        Self {
+            size,
+            align: size.clamp(1, target.max_align),
+            fields: vec![],
+            variants: Variants::Single,
+            niche: None,
+        }
+    }
+
+    fn pointer(target: Target) -> Self {
+        Self {
+            niche: Some(Niche {
+                offset: 0,
+                size: target.pointer_size,
+                path: vec![],
+            }),
+            ..Self::scalar(target.pointer_size, target)
+        }
+    }
+
+    /// Returns the offset of the data of every variant of a union.
+    pub fn data_offset(&self) -> u32 {
+        match self.variants {
+            Variants::Tagged { data_offset, .. } => data_offset,
+            Variants::Single | Variants::Niche { .. } => 0,
+        }
+    }
+}
+
+/// Rounds `offset` up to the next multiple of `align`.
+pub fn align_to(offset: u32, align: u32) -> u32 {
+    offset.div_ceil(align) * align
+}
+
+pub(crate) fn layout_of(db: &dyn MirDatabase, ty: Ty, target: Target) -> Arc<Layout> {
+    let fields = |count: usize, repr: Repr| {
+        let tys = (0..count).map(|idx| field_ty(db, ty, idx as u32));
+        struct_layout(db, tys, repr, target)
+    };
+
+    let layout = match ty.kind(db) {
+        TyKind::Int { width, .. } => Layout::scalar(width as u32 / 8, target),
+        TyKind::Float { width } => Layout::scalar(width as u32 / 8, target),
+        TyKind::Bool => Layout::scalar(1, target),
+        TyKind::Char => Layout::scalar(4, target),
+        TyKind::Pointer(_) | TyKind::String => Layout::pointer(target),
+        TyKind::Tuple(tys) => fields(tys.len(), Repr::Default),
+        TyKind::NamedTuple(tys) => fields(tys.len(), Repr::Default),
+        TyKind::Adt(item, _) => {
+            let repr = match db.lookup_intern_item(item).kind {
+                ItemKind::TypeDef(def) => def.repr,
+                ItemKind::Union(union) => union.repr,
+                _ => Repr::Default,
+            };
+            match &*db.adt_def(item) {
+                AdtDef::Struct { fields: tys } => fields(tys.len(), repr),
+                AdtDef::Union { variants } => {
+                    let data = (0..variants.len())
+                        .map(|idx| db.layout_of(variant_ty(db, ty, idx as u32), target))
+                        .collect::<Vec<_>>();
+                    union_layout(&data, repr)
+                }
+            }
+        }
+        _ => Layout::scalar(0, target),
+    };
+    Arc::new(layout)
+}
+
+/// Recovers from types that contain themselves, which are
+/// already reported by the type checker.
+pub(crate) fn layout_cycle(
+    _db: &dyn MirDatabase,
+    _cycle: &[String],
+    _ty: &Ty,
+    target: &Target,
+) -> Arc<Layout> {
+    Arc::new(Layout::scalar(0, *target))
+}
+
+fn struct_layout(
+    db: &dyn MirDatabase,
+    tys: impl Iterator<Item = Ty>,
+    repr: Repr,
+    target: Target,
+) -> Layout {
+    let mut fields = vec![];
+    let mut niche = None;
+    let mut size = 0;
+    let mut align = 1;
+    for (idx, ty) in tys.enumerate() {
+        let field = db.layout_of(ty, target);
+        if repr != Repr::Packed {
+            size = align_to(size, field.align);
+            align = align.max(field.align);
+        }
+        if let (None, Some(inner)) = (&niche, &field.niche) {
+            let mut path = vec![idx as u32];
+            path.extend(&inner.path);
+            niche = Some(Niche {
+                offset: size + inner.offset,
+                size: inner.size,
+                path,
+            });
+        }
+        fields.push(size);
+        size += field.size;
+    }
+
+    Layout {
+        size: align_to(size, align),
+        align,
+        fields,
+        variants: Variants::Single,
+        niche,
+    }
+}
+
+fn union_layout(data: &[Arc<Layout>], repr: Repr) -> Layout {
+    let size = data.iter().map(|data| data.size).max().unwrap_or(0);
+    let data_align = data.iter().map(|data| data.align).fold(1, u32::max);
+
+    if let (Repr::Default, [a, b]) = (repr, data) {
+        let niche = match (&a.niche, &b.niche) {
+            (None, Some(niche)) if a.size == 0 => Some((1, 0, niche)),
+            (Some(niche), None) if b.size == 0 => Some((0, 1, niche)),
+            _ => None,
+        };
+        if let Some((dataful, niche_variant, niche)) = niche {
+            return Layout {
+                size,
+                align: data_align,
+                fields: vec![],
+                variants: Variants::Niche {
+                    dataful,
+                    niche_variant,
+                    niche: niche.clone(),
+                },
+                niche: None,
+            };
+        }
+    }
+
+    let tag_size = match repr {
+        Repr::Default if data.len() <= 1 << 8 => 1,
+        Repr::Default if data.len() <= 1 << 16 => 2,
+        _ => 4,
+    };
+    let (align, data_offset) = match repr {
+        Repr::Packed => (1, tag_size),
+        Repr::Default | Repr::C => {
+            let align = data_align.max(tag_size);
+            (align, align_to(tag_size, data_align))
+        }
+    };
+
+    Layout {
+        size: align_to(data_offset + size, align),
+        align,
+        fields: vec![],
+        variants: Variants::Tagged {
+            tag_size,
+            data_offset,
+        },
+        niche: None,
+    }
+}
+
+/// Returns the layout of every `type` and union inside the file
+/// that has no generic parameters. The instances of generic ones
+/// are dumped by [`dump_instances`].
+///
+/// ```text
+/// type Point: size 16, align 8
+///     0..4    x: i32
+///     4..8    (padding)
+///     8..16   y: *i32
+/// ```
+pub fn dump_file(db: &dyn MirDatabase, file: FileId, target: Target) -> String {
+    let mut out = String::new();
+    for &item in db.parse(file).items.iter() {
+        match db.lookup_intern_item(item).kind {
+            ItemKind::TypeDef(def) if def.generics.is_empty() => {}
+            ItemKind::Union(union) if union.generics.is_empty() => {}
+            _ => continue,
+        }
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&dump(db, TyKind::Adt(item, vec![]).intern(db), target));
+    }
+    out
+}
+
+/// Returns the layout of every instance of a generic `type` or union, like
+/// `Opt[*i32]`, that is used by the functions reachable from `main`.
+///
+/// The functions are collected from the MIR that is optimized at `level`.
+pub fn dump_instances(
+    db: &dyn MirDatabase,
+    level: OptLevel,
+    target: Target,
+) -> Result<String, Locatable<Error>> {
+    let main = Instance::new(mono::find_main(db)?, vec![]);
+    let mut visited = HashSet::new();
+    let mut adts = vec![];
+    for instance in mono::collect(db, &[main], level)? {
+        let body = db
+            .optimized_mir(instance.def, level)
+            .ok_or_else(|| mono::body_error(db, instance.def))?;
+        for local in &body.locals {
+            let ty = local.ty.subst(db, &instance.args);
+            generic_adts(db, ty, &mut visited, &mut adts);
+        }
+    }
+
+    let layouts = adts
+        .into_iter()
+        .map(|ty| dump(db, ty, target))
+        .collect::<Vec<_>>();
+    Ok(layouts.join("\n"))
+}
+
+/// Adds every instance of a generic `type` or union that is part of `ty`, or
+/// that a pointer inside of it points to, to `adts`, unless it was visited.
+fn generic_adts(db: &dyn MirDatabase, ty: Ty, visited: &mut HashSet<Ty>, adts: &mut Vec<Ty>) {
+    if !visited.insert(ty) {
+        return;
+    }
+    match ty.kind(db) {
+        TyKind::Pointer(inner) => generic_adts(db, inner, visited, adts),
+        TyKind::Tuple(tys) => {
+            for ty in tys {
+                generic_adts(db, ty, visited, adts);
+            }
+        }
+        TyKind::NamedTuple(fields) => {
+            for (_, ty) in fields {
+                generic_adts(db, ty, visited, adts);
+            }
+        }
+        TyKind::Adt(item, args) => {
+            if !args.is_empty() {
+                adts.push(ty);
+            }
+            let tys: Vec<Ty> = match &*db.adt_def(item) {
+                AdtDef::Struct { fields } => fields.iter().map(|(_, ty)| *ty).collect(),
+                AdtDef::Union { variants } => variants.iter().filter_map(|(_, ty)| *ty).collect(),
+            };
+            for ty in tys {
+                generic_adts(db, ty.subst(db, &args), visited, adts);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns a human readable description of the layout of a type.
+pub fn dump(db: &dyn MirDatabase, ty: Ty, target: Target) -> String {
+    let layout = db.layout_of(ty, target);
+    let name = match ty.kind(db) {
+        TyKind::Adt(item, _) => match db.lookup_intern_item(item).kind {
+            ItemKind::TypeDef(_) => format!("type {}", ty.display(db)),
+            _ => format!("union {}", ty.display(db)),
+        },
+        _ => ty.display(db),
+    };
+    let mut out = format!("{}: size {}, align {}", name, layout.size, layout.align);
+    if let Some(niche) = &layout.niche {
+        let _ = write!(out, ", niche at {}", niche.offset);
+    }
+    out.push('\n');
+
+    let mut end = 0;
+    match layout.variants {
+        Variants::Single => {
+            let names = field_names(db, ty);
+            for (idx, &offset) in layout.fields.iter().enumerate() {
+                let field = field_ty(db, ty, idx as u32);
+                let size = db.layout_of(field, target).size;
+                let text = format!("{}: {}", names[idx], field.display(db));
+                dump_entry(&mut out, &mut end, offset, size, &text);
+            }
+        }
+        Variants::Tagged { tag_size, .. } => {
+            dump_entry(
+                &mut out,
+                &mut end,
+                0,
+                tag_size,
+                &format!("tag: u{}", tag_size * 8),
+            );
+            let offset = layout.data_offset();
+            if offset > end {
+                dump_entry(&mut out, &mut end, tag_size, offset - tag_size, "(padding)");
+            }
+            // every variant starts at the same offset
+            for (idx, name) in field_names(db, ty).iter().enumerate() {
+                let data = variant_ty(db, ty, idx as u32);
+                let mut variant_end = offset;
+                let size = db.layout_of(data, target).size;
+                let text = format!("{} = {}", name, data.display(db));
+                dump_entry(&mut out, &mut variant_end, offset, size, &text);
+                end = end.max(variant_end);
+            }
+        }
+        Variants::Niche {
+            dataful,
+            niche_variant,
+            ref niche,
+        } => {
+            let names = field_names(db, ty);
+            let data = variant_ty(db, ty, dataful);
+            let size = db.layout_of(data, target).size;
+            let text = format!("{} = {}", names[dataful as usize], data.display(db));
+            dump_entry(&mut out, &mut end, 0, size, &text);
+            let _ = writeln!(
+                out,
+                "    {} is stored as null at offset {}",
+                names[niche_variant as usize], niche.offset
+            );
+        }
+    }
+    if layout.size > end {
+        let _ = writeln!(
+            out,
+            "    {:<8}(padding)",
+            format!("{}..{}", end, layout.size)
+        );
+    }
+    out
+}
+
+fn dump_entry(out: &mut String, end: &mut u32, offset: u32, size: u32, text: &str) {
+    if offset > *end {
+        let _ = writeln!(out, "    {:<8}(padding)", format!("{}..{}", end, offset));
+    }
+    let _ = writeln!(
+        out,
+        "    {:<8}{}",
+        format!("{}..{}", offset, offset + size),
+        text
+    );
+    *end = offset + size;
+}
+
+/// Returns the names of the fields or variants of a type.
+fn field_names(db: &dyn MirDatabase, ty: Ty) -> Vec<String> {
+    let rodeo = db.rodeo();
+    match ty.kind(db) {
+        TyKind::Tuple(tys) => (0..tys.len()).map(|idx| idx.to_string()).collect(),
+        TyKind::NamedTuple(fields) => fields
+            .iter()
+            .map(|(name, _)| rodeo.resolve(name).to_string())
+            .collect(),
+        TyKind::Adt(item, _) => match &*db.adt_def(item) {
+            AdtDef::Struct { fields } => fields
+                .iter()
+                .map(|(name, _)| rodeo.resolve(name).to_string())
+                .collect(),
+            AdtDef::Union { variants } => variants
+                .iter()
+                .map(|(name, _)| rodeo.resolve(name).to_string())
+                .collect(),
+        },
+        _ => vec![],
+    }
+}
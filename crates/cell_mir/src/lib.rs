@@ -0,0 +1,14 @@
+//! The mid-level intermediate representation (MIR) of cell programs.
+//!
+//! The MIR of a function is a control-flow graph of basic blocks. It's
+//! built from a type checked function body and used by every backend.
+
+pub mod body;
+mod build;
+pub mod db;
+pub mod dominators;
+pub mod layout;
+pub mod mono;
+pub mod opt;
+pub mod pretty;
+pub mod verify;
@@ -0,0 +1,312 @@
+//! Collects every function instance that is reachable from a set of
+//! roots, like `main`, and gives each of them a unique symbol name.
+//!
+//! A generic function is instantiated once for every combination of
+//! generic arguments it's called with, and calls to trait methods are
+//! resolved to the method of the matching impl.
+//!
+//! Symbol names are mangled like this:
+//!
+//! ```text
+//! symbol    = "_C" path [ "I" { type } "E" ]
+//! path      = { ident } [ "X" type [ "F" { ident } ] "E" ] ident
+//! ident     = <length in bytes> <name>
+//! type      = "i" <width> "_" | "u" <width> "_" | "f" <width> "_"
+//!           | "b" | "c" | "s" | "v" | "z"
+//!           | "P" type
+//!           | "T" { type } "E"
+//!           | "N" { ident type } "E"
+//!           | "A" { ident } [ "I" { type } "E" ] "E"
+//!           | "p" <index> "_"
+//!           | "x"
+//! ```
+//!
+//! where the path starts with the modules of the item. Methods of an
+//! `impl` have an `X` segment with the type and trait of the impl, and
+//! methods of a trait have the name of the trait as a segment.
+//! `p` is the generic parameter with the given index, which appears in
+//! the type of a generic impl, and `x` is a type that failed to check or
+//! was never inferred, which can't appear in a program without errors.
+//! For example, `Vec.push[i32]` inside of the module `vec`
+//! becomes `_C3vecXA3vec3VecIp0_EEE4pushIi32_E`.
+
+use crate::{body::TerminatorKind, db::MirDatabase, opt::OptLevel, pretty::item_name};
+use cell_common::{
+    error::{CodegenError, Error, RuntimeError},
+    profiler,
+    source::{FileId, Locatable, Span},
+};
+use cell_syntax::ast::{ItemId, ItemKind};
+use cell_typeck::{
+    prelude, scope, traits,
+    ty::{Ty, TyKind},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+/// How many instances of the same function may call each other
+/// before the instantiation is considered infinitely recursive.
+const RECURSION_LIMIT: usize = 128;
+
+/// A function together with the generic arguments it's called with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Instance {
+    pub def: ItemId,
+    pub args: Vec<Ty>,
+}
+
+impl Instance {
+    pub fn new(def: ItemId, args: Vec<Ty>) -> Self {
+        Self { def, args }
+    }
+
+    /// Returns the instance that is executed by calling `def`, which is the
+    /// method of the matching impl if `def` is a method of a trait.
+    pub fn resolve(db: &dyn MirDatabase, def: ItemId, args: &[Ty]) -> Self {
+        let (def, args) = traits::resolve_instance(db, def, args);
+        Self { def, args }
+    }
+
+    /// Returns the name of the function, followed by its generic arguments,
+    /// like `Vec.push[i32]`.
+    pub fn display(&self, db: &dyn MirDatabase) -> String {
+        let name = item_name(db, self.def);
+        if self.args.is_empty() {
+            return name;
+        }
+        let args = self
+            .args
+            .iter()
+            .map(|ty| ty.display(db))
+            .collect::<Vec<_>>();
+        format!("{}[{}]", name, args.join(", "))
+    }
+
+    /// Returns the mangled symbol name of this instance, which only
+    /// contains ASCII letters, digits and underscores if every
+    /// name inside of it does.
+    pub fn symbol(&self, db: &dyn MirDatabase) -> String {
+        let mut out = String::from("_C");
+        mangle_path(db, self.def, &mut out);
+        mangle_args(db, &self.args, &mut out);
+        out
+    }
+}
+
+/// Returns the `main` function of the root module.
+pub fn find_main(db: &dyn MirDatabase) -> Result<ItemId, Locatable<Error>> {
+    let root = db.module_tree().root();
+    let name = db.rodeo().get_or_intern("main");
+    scope::resolve_value(db, root, name)
+        .filter(|&item| matches!(db.lookup_intern_item(item).kind, ItemKind::Def(_)))
+        .ok_or_else(|| Locatable::new(RuntimeError::NoMain.into(), root, Span::default()))
+}
+
+/// Returns the error that prevented the MIR of the function from being built.
+pub fn body_error(db: &dyn MirDatabase, def: ItemId) -> Locatable<Error> {
+    let results = db.typeck(def);
+    let checked = db.check_item(def);
+    results
+        .errors
+        .iter()
+        .chain(checked.iter())
+        .next()
+        .cloned()
+        .unwrap_or_else(|| {
+            let item = db.lookup_intern_item(def);
+            Locatable::new(RuntimeError::Unreachable.into(), item.file, item.span)
+        })
+}
+
+/// Returns the names of the modules from the root module down to the file,
+/// without the root module itself.
+pub fn module_path(db: &dyn MirDatabase, mut file: FileId) -> Vec<String> {
+    let tree = db.module_tree();
+    let mut path = vec![];
+    while let Some(parent) = tree.module(file).and_then(|module| module.parent) {
+        let module = tree.module(parent).unwrap();
+        if let Some((name, _)) = module.children.iter().find(|(_, &child)| child == file) {
+            path.push(db.rodeo().resolve(name).to_string());
+        }
+        file = parent;
+    }
+    path.reverse();
+    path
+}
+
+/// Collects the roots, and every instance that is called by them,
+/// in the order they are first reached.
+///
+/// The calls are taken from the MIR that is optimized at `level`, so
+/// functions that are inlined everywhere are not collected. Calls to
+/// functions that are implemented by the host, like `Io.println`,
+/// are not collected either.
+pub fn collect(
+    db: &dyn MirDatabase,
+    roots: &[Instance],
+    level: OptLevel,
+) -> Result<Vec<Instance>, Locatable<Error>> {
+    let _profiler = profiler::trace("Mir", "Collect");
+
+    let mut collector = Collector {
+        db,
+        level,
+        visited: HashSet::new(),
+        instances: vec![],
+        depths: HashMap::new(),
+    };
+    for root in roots {
+        collector.visit(root.clone(), None)?;
+    }
+    Ok(collector.instances)
+}
+
+struct Collector<'db> {
+    db: &'db dyn MirDatabase,
+    level: OptLevel,
+    visited: HashSet<Instance>,
+    instances: Vec<Instance>,
+    /// How many instances of every function are currently being visited.
+    depths: HashMap<ItemId, usize>,
+}
+
+impl Collector<'_> {
+    /// Visits the instance, which is called at `call`, and everything it calls.
+    fn visit(
+        &mut self,
+        instance: Instance,
+        call: Option<(FileId, Span)>,
+    ) -> Result<(), Locatable<Error>> {
+        let db = self.db;
+        if !self.visited.insert(instance.clone()) {
+            return Ok(());
+        }
+        self.instances.push(instance.clone());
+
+        let depth = self.depths.entry(instance.def).or_default();
+        *depth += 1;
+        if *depth > RECURSION_LIMIT {
+            let err = CodegenError::RecursionLimit {
+                func: item_name(db, instance.def),
+            };
+            let (file, span) = call.unwrap_or_else(|| {
+                let item = db.lookup_intern_item(instance.def);
+                (item.file, item.span)
+            });
+            return Err(Locatable::new(err.into(), file, span));
+        }
+
+        let body = db
+            .optimized_mir(instance.def, self.level)
+            .ok_or_else(|| body_error(db, instance.def))?;
+        let file = db.lookup_intern_item(instance.def).file;
+        for (_, block) in body.basic_blocks() {
+            let terminator = block.terminator();
+            if let TerminatorKind::Call {
+                func, generic_args, ..
+            } = &terminator.kind
+            {
+                if prelude::host_fn(db, *func).is_some() {
+                    continue;
+                }
+                let generic_args = generic_args
+                    .iter()
+                    .map(|ty| ty.subst(db, &instance.args))
+                    .collect::<Vec<_>>();
+                let callee = Instance::resolve(db, *func, &generic_args);
+                self.visit(callee, Some((file, terminator.span)))?;
+            }
+        }
+
+        *self.depths.get_mut(&instance.def).unwrap() -= 1;
+        Ok(())
+    }
+}
+
+fn mangle_ident(name: &str, out: &mut String) {
+    write!(out, "{}{}", name.len(), name).unwrap();
+}
+
+/// Mangles the modules, parent and name of an item.
+fn mangle_path(db: &dyn MirDatabase, item: ItemId, out: &mut String) {
+    let data = db.lookup_intern_item(item);
+    for module in module_path(db, data.file) {
+        mangle_ident(&module, out);
+    }
+
+    if let Some(parent) = scope::parent_item(db, item) {
+        match db.lookup_intern_item(parent).kind {
+            ItemKind::Impl(_) => {
+                out.push('X');
+                mangle_ty(db, db.impl_self_ty(parent), out);
+                if let Some(trait_) = db.impl_trait(parent) {
+                    out.push('F');
+                    mangle_path(db, trait_, out);
+                }
+                out.push('E');
+            }
+            kind => mangle_ident(&name_of(db, kind), out),
+        }
+    }
+    mangle_ident(&name_of(db, data.kind), out);
+}
+
+fn name_of(db: &dyn MirDatabase, kind: ItemKind) -> String {
+    match kind.name() {
+        Some(name) => db.rodeo().resolve(&name.id).to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+fn mangle_args(db: &dyn MirDatabase, args: &[Ty], out: &mut String) {
+    if !args.is_empty() {
+        out.push('I');
+        for &ty in args {
+            mangle_ty(db, ty, out);
+        }
+        out.push('E');
+    }
+}
+
+fn mangle_ty(db: &dyn MirDatabase, ty: Ty, out: &mut String) {
+    match ty.kind(db) {
+        TyKind::Int { width, signed } => {
+            write!(out, "{}{}_", if signed { 'i' } else { 'u' }, width).unwrap()
+        }
+        TyKind::Float { width } => write!(out, "f{}_", width).unwrap(),
+        TyKind::Bool => out.push('b'),
+        TyKind::Char => out.push('c'),
+        TyKind::String => out.push('s'),
+        TyKind::Unit => out.push('v'),
+        TyKind::Never => out.push('z'),
+        TyKind::Pointer(inner) => {
+            out.push('P');
+            mangle_ty(db, inner, out);
+        }
+        TyKind::Tuple(tys) => {
+            out.push('T');
+            for ty in tys {
+                mangle_ty(db, ty, out);
+            }
+            out.push('E');
+        }
+        TyKind::NamedTuple(fields) => {
+            out.push('N');
+            for (name, ty) in fields {
+                mangle_ident(db.rodeo().resolve(&name), out);
+                mangle_ty(db, ty, out);
+            }
+            out.push('E');
+        }
+        TyKind::Adt(item, args) => {
+            out.push('A');
+            mangle_path(db, item, out);
+            mangle_args(db, &args, out);
+            out.push('E');
+        }
+        TyKind::Param(idx, _) => write!(out, "p{}_", idx).unwrap(),
+        TyKind::Infer(_) | TyKind::Error => out.push('x'),
+    }
+}
@@ -0,0 +1,175 @@
+//! Optimizations that transform the MIR of a function into equivalent,
+//! but faster MIR.
+//!
+//! Every optimization is a [`Pass`], and the passes are run in the order
+//! of the pipeline. A pass only runs if the [`OptLevel`] is at least the
+//! level of the pass, so `-O0` runs no pass at all and `-O1` only runs
+//! the cheap ones.
+//!
+//! Some passes work on the SSA form of the body, so they run between
+//! `IntoSsa` and `FromSsa`. The backends never see phi nodes.
+//!
+//! [`Pass`]: ./trait.Pass.html
+//! [`OptLevel`]: ./enum.OptLevel.html
+
+mod const_prop;
+mod copy_prop;
+mod dce;
+mod inline;
+mod simplify_cfg;
+mod ssa;
+pub(crate) mod visit;
+
+use crate::{body::Body, db::MirDatabase, pretty, verify};
+use cell_common::profiler;
+use cell_syntax::ast::ItemId;
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Whether every body is verified after it's built and after every pass.
+///
+/// This is a global flag, like the profiler, because it never changes
+/// the result of a query. It only turns bugs into panics.
+static VERIFY: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables verifying the MIR after every optimization pass.
+///
+/// This is meant for debugging the compiler, because a body that fails
+/// to verify makes the compiler panic.
+pub fn set_verify(enabled: bool) {
+    VERIFY.store(enabled, Ordering::Relaxed);
+}
+
+/// Panics if verifying is enabled and the body isn't well formed.
+fn check(db: &dyn MirDatabase, body: &Body, after: &str) {
+    if !VERIFY.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Err(errors) = verify::verify(db, body) {
+        panic!(
+            "invalid MIR for `{}` after {}:\n{}\n\n{}",
+            pretty::item_name(db, body.item),
+            after,
+            errors.join("\n"),
+            pretty::pretty_body(db, body)
+        );
+    }
+}
+
+/// How much the MIR is optimized.
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// No optimizations.
+    #[default]
+    O0,
+    /// Optimizations that make the code faster without making it larger.
+    O1,
+    /// Every optimization, including inlining.
+    O2,
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            OptLevel::O0 => 0,
+            OptLevel::O1 => 1,
+            OptLevel::O2 => 2,
+        };
+        write!(f, "{}", level)
+    }
+}
+
+/// The error that is returned if an [`OptLevel`] can't be parsed.
+///
+/// [`OptLevel`]: ./enum.OptLevel.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptLevelError(String);
+
+impl fmt::Display for ParseOptLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid optimization level `{}`, expected `0`, `1` or `2`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseOptLevelError {}
+
+impl FromStr for OptLevel {
+    type Err = ParseOptLevelError;
+
+    /// Parses the number after `-O`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            _ => Err(ParseOptLevelError(s.to_string())),
+        }
+    }
+}
+
+/// A single optimization.
+pub trait Pass {
+    /// The name of the pass, which is used for profiling.
+    fn name(&self) -> &'static str;
+
+    /// The lowest level at which this pass is run.
+    fn level(&self) -> OptLevel;
+
+    /// Transforms the body.
+    fn run(&self, db: &dyn MirDatabase, body: &mut Body);
+}
+
+/// Every pass, in the order they are run.
+///
+/// The CFG is simplified multiple times, because the other passes
+/// often leave behind blocks that can be merged or removed.
+/// Copy propagation runs again after leaving SSA form, to remove
+/// most of the copies that replaced the phi nodes.
+const PIPELINE: &[&dyn Pass] = &[
+    &inline::Inline,
+    &simplify_cfg::SimplifyCfg,
+    &ssa::IntoSsa,
+    &const_prop::ConstProp,
+    &copy_prop::CopyProp,
+    &ssa::FromSsa,
+    &copy_prop::CopyProp,
+    &simplify_cfg::SimplifyCfg,
+    &dce::DeadCodeElimination,
+    &simplify_cfg::SimplifyCfg,
+];
+
+/// Runs every pass of the pipeline, that is enabled at the level, on the body.
+pub fn optimize(db: &dyn MirDatabase, body: &mut Body, level: OptLevel) {
+    for pass in PIPELINE.iter().filter(|pass| pass.level() <= level) {
+        let _profiler = profiler::trace("Mir", pass.name());
+        pass.run(db, body);
+        check(db, body, pass.name());
+    }
+}
+
+pub(crate) fn optimized_mir(
+    db: &dyn MirDatabase,
+    item: ItemId,
+    level: OptLevel,
+) -> Option<Arc<Body>> {
+    let body = db.mir_body(item)?;
+    check(db, &body, "building");
+    if level == OptLevel::O0 {
+        return Some(body);
+    }
+
+    let _profiler = profiler::trace("Mir", "Optimize");
+    let mut body = (*body).clone();
+    optimize(db, &mut body, level);
+    Some(Arc::new(body))
+}
@@ -0,0 +1,175 @@
+//! Replaces locals that always hold the same constant with that constant,
+//! and evaluates operations on constants at compile time.
+//!
+//! Operations that would trap at runtime, like an overflowing addition,
+//! are kept, so the program still traps when it reaches them.
+
+use super::{
+    visit::{local_uses, MutVisitor},
+    OptLevel, Pass,
+};
+use crate::{
+    body::{BinOp, Body, Constant, Local, Operand, Rvalue, StatementKind, UnOp, RETURN_PLACE},
+    db::MirDatabase,
+};
+use cell_syntax::ast::{self, CmpOp};
+use cell_typeck::{
+    consteval::{self, ConstValue},
+    ty::TyKind,
+};
+use std::collections::HashMap;
+
+pub struct ConstProp;
+
+impl Pass for ConstProp {
+    fn name(&self) -> &'static str {
+        "ConstProp"
+    }
+
+    fn level(&self) -> OptLevel {
+        OptLevel::O1
+    }
+
+    fn run(&self, db: &dyn MirDatabase, body: &mut Body) {
+        let uses = local_uses(body);
+
+        // a local can only be replaced if the constant is the only value
+        // it ever holds, and nobody can change it through a pointer
+        let candidates = body
+            .locals
+            .iter()
+            .enumerate()
+            .map(|(idx, _)| {
+                let uses = &uses[idx];
+                idx != RETURN_PLACE.index()
+                    && idx > body.arg_count
+                    && uses.writes == 1
+                    && !uses.address_taken
+            })
+            .collect::<Vec<_>>();
+
+        let mut propagator = Propagator {
+            constants: HashMap::new(),
+        };
+        loop {
+            propagator.visit_body(body);
+
+            let mut changed = false;
+            for block in body.blocks.iter_mut() {
+                for statement in block.statements.iter_mut() {
+                    let (place, rvalue) = match &mut statement.kind {
+                        StatementKind::Assign(place, rvalue) => (place, rvalue),
+                        StatementKind::Phi(..) | StatementKind::Nop => continue,
+                    };
+
+                    if let Some(val) = fold(db, rvalue) {
+                        *rvalue = Rvalue::Use(Operand::Constant(val));
+                    }
+                    if let Rvalue::Use(Operand::Constant(val)) = rvalue {
+                        if place.projection.is_empty()
+                            && candidates[place.local.index()]
+                            && propagator
+                                .constants
+                                .insert(place.local, val.clone())
+                                .is_none()
+                        {
+                            changed = true;
+                        }
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+/// Replaces reads of locals, that are known to be constants, with the constant.
+struct Propagator {
+    constants: HashMap<Local, Constant>,
+}
+
+impl MutVisitor for Propagator {
+    fn visit_operand(&mut self, op: &mut Operand) {
+        if let Operand::Copy(place) = op {
+            if place.projection.is_empty() {
+                if let Some(constant) = self.constants.get(&place.local) {
+                    *op = Operand::Constant(constant.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Evaluates the rvalue if every operand of it is a constant,
+/// and the evaluation doesn't fail.
+fn fold(db: &dyn MirDatabase, rvalue: &Rvalue) -> Option<Constant> {
+    match rvalue {
+        Rvalue::UnaryOp(op, Operand::Constant(val)) => {
+            let op = match op {
+                UnOp::Not => ast::UnOp::Not,
+                UnOp::Neg => ast::UnOp::Minus,
+            };
+            let result = consteval::unary_op(op, val.val.clone()).ok()?;
+            Some(Constant {
+                ty: val.ty,
+                val: result,
+            })
+        }
+        Rvalue::BinaryOp(op, Operand::Constant(left), Operand::Constant(right)) => {
+            if let Some(op) = cmp_op(*op) {
+                let result = consteval::compare(op, &left.val, &right.val).ok()?;
+                return Some(Constant {
+                    ty: TyKind::Bool.intern(db),
+                    val: ConstValue::Bool(result),
+                });
+            }
+
+            // only the basic float operations are exactly rounded, the others
+            // are computed by the host at runtime and may round differently
+            if let (BinOp::Rem | BinOp::Pow, ConstValue::Float { .. }) = (op, &left.val) {
+                return None;
+            }
+            let result = consteval::binary_op(bin_op(*op), left.val.clone(), right.val.clone());
+            Some(Constant {
+                ty: left.ty,
+                val: result.ok()?,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn cmp_op(op: BinOp) -> Option<CmpOp> {
+    let op = match op {
+        BinOp::Eq => CmpOp::Equal,
+        BinOp::Ne => CmpOp::NotEqual,
+        BinOp::Lt => CmpOp::Less,
+        BinOp::Le => CmpOp::LessEqual,
+        BinOp::Gt => CmpOp::Greater,
+        BinOp::Ge => CmpOp::GreaterEqual,
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn bin_op(op: BinOp) -> ast::BinOp {
+    match op {
+        BinOp::Add => ast::BinOp::Add,
+        BinOp::Sub => ast::BinOp::Sub,
+        BinOp::Mul => ast::BinOp::Mul,
+        BinOp::Div => ast::BinOp::Div,
+        BinOp::Rem => ast::BinOp::Mod,
+        BinOp::Pow => ast::BinOp::Pow,
+        BinOp::BitAnd => ast::BinOp::BitiwseAnd,
+        BinOp::BitOr => ast::BinOp::BitwiseOr,
+        BinOp::BitXor => ast::BinOp::BitwiseXor,
+        BinOp::Shl => ast::BinOp::LeftShift,
+        BinOp::Shr => ast::BinOp::RightShift,
+        BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+            unreachable!("comparisons are folded using `compare`")
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Removes copies between locals, like `_2 = _1`, by using the source
+//! of the copy everywhere instead of the destination.
+//!
+//! This is only done if both locals are assigned once and can't be
+//! changed through a pointer, so they always hold the same value.
+
+use super::{
+    visit::{local_uses, MutVisitor},
+    OptLevel, Pass,
+};
+use crate::{
+    body::{Body, Local, Operand, Rvalue, StatementKind, RETURN_PLACE},
+    db::MirDatabase,
+};
+
+pub struct CopyProp;
+
+impl Pass for CopyProp {
+    fn name(&self) -> &'static str {
+        "CopyProp"
+    }
+
+    fn level(&self) -> OptLevel {
+        OptLevel::O2
+    }
+
+    fn run(&self, _db: &dyn MirDatabase, body: &mut Body) {
+        let uses = local_uses(body);
+        let single = |local: Local| {
+            let uses = &uses[local.index()];
+            local != RETURN_PLACE && uses.writes == 1 && !uses.address_taken
+        };
+
+        // maps every local to the local it's a copy of
+        let mut copies = (0..body.locals.len() as u32).map(Local).collect::<Vec<_>>();
+        let locals = &body.locals;
+        for block in body.blocks.iter_mut() {
+            for statement in block.statements.iter_mut() {
+                let (dest, src) = match &statement.kind {
+                    StatementKind::Assign(dest, Rvalue::Use(Operand::Copy(src)))
+                        if dest.projection.is_empty() && src.projection.is_empty() =>
+                    {
+                        (dest.local, src.local)
+                    }
+                    _ => continue,
+                };
+                let same_ty = locals[dest.index()].ty == locals[src.index()].ty;
+                if dest.index() > body.arg_count && same_ty && single(dest) && single(src) {
+                    copies[dest.index()] = src;
+                    statement.kind = StatementKind::Nop;
+                }
+            }
+        }
+
+        // resolve chains of copies, like `_2 = _1; _3 = _2`,
+        // stopping at cycles, which can only appear in dead code
+        for idx in 0..copies.len() {
+            let mut local = copies[idx];
+            let mut steps = 0;
+            while copies[local.index()] != local && steps < copies.len() {
+                local = copies[local.index()];
+                steps += 1;
+            }
+            copies[idx] = local;
+        }
+
+        Replacer { copies }.visit_body(body);
+    }
+}
+
+struct Replacer {
+    copies: Vec<Local>,
+}
+
+impl MutVisitor for Replacer {
+    fn visit_local(&mut self, local: &mut Local) {
+        *local = self.copies[local.index()];
+    }
+}
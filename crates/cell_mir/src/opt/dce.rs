@@ -0,0 +1,139 @@
+//! Removes assignments to locals that are never read, and every local
+//! that isn't used anymore.
+//!
+//! Assignments whose rvalue may trap, like integer arithmetic or reading
+//! through a pointer, are kept, so the program still traps when it
+//! reaches them.
+
+use super::{
+    visit::{local_uses, MutVisitor},
+    OptLevel, Pass,
+};
+use crate::{
+    body::{
+        BinOp, Body, Local, Operand, Place, PlaceElem, Rvalue, StatementKind, UnOp, RETURN_PLACE,
+    },
+    db::MirDatabase,
+};
+use cell_typeck::ty::TyKind;
+
+pub struct DeadCodeElimination;
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "DeadCodeElimination"
+    }
+
+    fn level(&self) -> OptLevel {
+        OptLevel::O1
+    }
+
+    fn run(&self, db: &dyn MirDatabase, body: &mut Body) {
+        // removing an assignment may make the locals it reads unused,
+        // so this is repeated until nothing changes
+        loop {
+            let uses = local_uses(body);
+            let mut changed = false;
+            for idx in 0..body.blocks.len() {
+                for stmt in 0..body.blocks[idx].statements.len() {
+                    let (local, pure) = match &body.blocks[idx].statements[stmt].kind {
+                        StatementKind::Assign(place, rvalue) => (
+                            place.local,
+                            !is_indirect(place) && !may_trap(db, body, rvalue),
+                        ),
+                        StatementKind::Phi(local, _) => (*local, true),
+                        StatementKind::Nop => continue,
+                    };
+                    let uses = &uses[local.index()];
+                    let dead =
+                        local != RETURN_PLACE && uses.reads == 0 && !uses.address_taken && pure;
+                    if dead {
+                        body.blocks[idx].statements[stmt].kind = StatementKind::Nop;
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        for block in body.blocks.iter_mut() {
+            block
+                .statements
+                .retain(|statement| statement.kind != StatementKind::Nop);
+        }
+        remove_unused_locals(body);
+    }
+}
+
+/// Checks if the place is behind a pointer.
+fn is_indirect(place: &Place) -> bool {
+    place
+        .projection
+        .iter()
+        .any(|elem| matches!(elem, PlaceElem::Deref | PlaceElem::Index(_)))
+}
+
+/// Checks if evaluating the rvalue may trap.
+fn may_trap(db: &dyn MirDatabase, body: &Body, rvalue: &Rvalue) -> bool {
+    let operand = |op: &Operand| matches!(op, Operand::Copy(place) if is_indirect(place));
+    let is_int = |op: &Operand| !matches!(op.ty(db, body).kind(db), TyKind::Float { .. });
+
+    match rvalue {
+        Rvalue::Use(op) => operand(op),
+        Rvalue::UnaryOp(op, val) => operand(val) || (*op == UnOp::Neg && is_int(val)),
+        Rvalue::BinaryOp(op, left, right) => {
+            let arithmetic = matches!(
+                op,
+                BinOp::Add
+                    | BinOp::Sub
+                    | BinOp::Mul
+                    | BinOp::Div
+                    | BinOp::Rem
+                    | BinOp::Pow
+                    | BinOp::Shl
+                    | BinOp::Shr
+            );
+            operand(left) || operand(right) || (arithmetic && is_int(left))
+        }
+        Rvalue::AddressOf(_) | Rvalue::AddressOfStatic(_) => false,
+        Rvalue::Discriminant(place) => is_indirect(place),
+        Rvalue::Aggregate(_, ops) => ops.iter().any(operand),
+    }
+}
+
+/// Removes every local that isn't used, except for the return place
+/// and the arguments.
+fn remove_unused_locals(body: &mut Body) {
+    let uses = local_uses(body);
+    let mut map = vec![None; body.locals.len()];
+    let mut next = 0;
+    for (idx, uses) in uses.iter().enumerate() {
+        if idx <= body.arg_count || !uses.is_unused() {
+            map[idx] = Some(Local(next));
+            next += 1;
+        }
+    }
+    if next as usize == body.locals.len() {
+        return;
+    }
+
+    let mut idx = 0;
+    body.locals.retain(|_| {
+        idx += 1;
+        map[idx - 1].is_some()
+    });
+    Renumber { map }.visit_body(body);
+}
+
+struct Renumber {
+    map: Vec<Option<Local>>,
+}
+
+impl MutVisitor for Renumber {
+    fn visit_local(&mut self, local: &mut Local) {
+        *local = self.map[local.index()].expect("used locals are never removed");
+    }
+}
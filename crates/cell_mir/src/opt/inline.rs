@@ -0,0 +1,201 @@
+//! Replaces calls to small functions with the body of the function.
+//!
+//! Only functions that don't call other functions are inlined, so inlining
+//! never has to stop at recursive calls and the caller only grows by the
+//! size of its callees.
+
+use super::{
+    visit::{successors_mut, MutVisitor},
+    OptLevel, Pass,
+};
+use crate::{
+    body::{
+        BasicBlock, BasicBlockData, Body, Local, LocalDecl, Operand, Place, Rvalue, Statement,
+        StatementKind, Terminator, TerminatorKind,
+    },
+    db::MirDatabase,
+    mono::Instance,
+};
+use cell_common::source::Span;
+use cell_syntax::ast::{ItemId, ItemKind};
+use cell_typeck::{prelude, scope, ty::Ty};
+use std::sync::Arc;
+
+/// The maximum number of statements of a function that is inlined.
+const MAX_STATEMENTS: usize = 16;
+
+pub struct Inline;
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "Inline"
+    }
+
+    fn level(&self) -> OptLevel {
+        OptLevel::O2
+    }
+
+    fn run(&self, db: &dyn MirDatabase, body: &mut Body) {
+        // the blocks of inlined functions are appended to the body,
+        // but they never contain calls
+        let mut idx = 0;
+        while idx < body.blocks.len() {
+            let terminator = body.blocks[idx].terminator().clone();
+            if let TerminatorKind::Call {
+                func,
+                generic_args,
+                args,
+                destination,
+                target,
+            } = terminator.kind
+            {
+                if let Some((callee, callee_args)) = inlinable(db, body.item, func, &generic_args) {
+                    let call = Call {
+                        block: BasicBlock(idx as u32),
+                        span: terminator.span,
+                        args,
+                        destination,
+                        target,
+                    };
+                    inline(db, body, call, &callee, &callee_args);
+                }
+            }
+            idx += 1;
+        }
+    }
+}
+
+/// Returns the body and generic arguments of the function that is called,
+/// if it can be inlined.
+fn inlinable(
+    db: &dyn MirDatabase,
+    caller: ItemId,
+    func: ItemId,
+    generic_args: &[Ty],
+) -> Option<(Arc<Body>, Vec<Ty>)> {
+    if prelude::host_fn(db, func).is_some() {
+        return None;
+    }
+
+    // calls to trait methods can only be inlined if the impl is known
+    let instance = Instance::resolve(db, func, generic_args);
+    let in_trait = scope::parent_item(db, instance.def)
+        .is_some_and(|parent| matches!(db.lookup_intern_item(parent).kind, ItemKind::Trait(_)));
+    if instance.def == caller || in_trait {
+        return None;
+    }
+
+    let callee = db.mir_body(instance.def)?;
+    let calls = callee
+        .basic_blocks()
+        .any(|(_, block)| matches!(block.terminator().kind, TerminatorKind::Call { .. }));
+    let size = callee
+        .blocks
+        .iter()
+        .map(|block| block.statements.len())
+        .sum::<usize>();
+    if calls || size > MAX_STATEMENTS {
+        return None;
+    }
+    Some((callee, instance.args))
+}
+
+struct Call {
+    block: BasicBlock,
+    span: Span,
+    args: Vec<Operand>,
+    destination: Place,
+    target: BasicBlock,
+}
+
+/// Replaces the call with the body of the callee.
+///
+/// The arguments are written into the locals of the callee, and every
+/// `Return` of the callee jumps to a new block that writes the return value
+/// into the destination of the call.
+fn inline(db: &dyn MirDatabase, body: &mut Body, call: Call, callee: &Body, args: &[Ty]) {
+    let local_offset = body.locals.len() as u32;
+    let block_offset = body.blocks.len() as u32;
+    let ret = BasicBlock(block_offset + callee.blocks.len() as u32);
+
+    // spans of a callee from another file would point to the wrong code,
+    // so they're replaced with the span of the call
+    let same_file =
+        db.lookup_intern_item(body.item).file == db.lookup_intern_item(callee.item).file;
+    let span = |span: Span| if same_file { span } else { call.span };
+
+    body.locals
+        .extend(callee.locals.iter().map(|decl| LocalDecl {
+            ty: decl.ty.subst(db, args),
+            name: decl.name,
+            span: span(decl.span),
+        }));
+
+    let mut integrator = Integrator {
+        db,
+        args,
+        local_offset,
+    };
+    for block in callee.blocks.iter() {
+        let mut block = block.clone();
+        integrator.visit_block(&mut block);
+        for statement in block.statements.iter_mut() {
+            statement.span = span(statement.span);
+        }
+
+        let terminator = block.terminator.as_mut().unwrap();
+        terminator.span = span(terminator.span);
+        match &mut terminator.kind {
+            TerminatorKind::Return => terminator.kind = TerminatorKind::Goto { target: ret },
+            kind => successors_mut(kind, |target| target.0 += block_offset),
+        }
+        body.blocks.push(block);
+    }
+
+    let result = Rvalue::Use(Operand::Copy(Local(local_offset).into()));
+    body.blocks.push(BasicBlockData {
+        statements: vec![Statement {
+            span: call.span,
+            kind: StatementKind::Assign(call.destination, result),
+        }],
+        terminator: Some(Terminator {
+            span: call.span,
+            kind: TerminatorKind::Goto {
+                target: call.target,
+            },
+        }),
+    });
+
+    let block = &mut body.blocks[call.block.index()];
+    for (idx, arg) in call.args.into_iter().enumerate() {
+        let local = Local(local_offset + 1 + idx as u32);
+        block.statements.push(Statement {
+            span: call.span,
+            kind: StatementKind::Assign(local.into(), Rvalue::Use(arg)),
+        });
+    }
+    block.terminator = Some(Terminator {
+        span: call.span,
+        kind: TerminatorKind::Goto {
+            target: BasicBlock(block_offset),
+        },
+    });
+}
+
+/// Moves the locals of the callee behind the locals of the caller,
+/// and replaces the generic parameters of the callee with the arguments.
+struct Integrator<'a> {
+    db: &'a dyn MirDatabase,
+    args: &'a [Ty],
+    local_offset: u32,
+}
+
+impl MutVisitor for Integrator<'_> {
+    fn visit_local(&mut self, local: &mut Local) {
+        local.0 += self.local_offset;
+    }
+
+    fn visit_ty(&mut self, ty: &mut Ty) {
+        *ty = ty.subst(self.db, self.args);
+    }
+}
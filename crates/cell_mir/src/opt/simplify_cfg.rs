@@ -0,0 +1,184 @@
+//! Simplifies the control-flow graph by removing blocks and edges that are
+//! not needed.
+//!
+//! `SwitchInt`s on constants become `Goto`s, jumps to empty blocks go
+//! directly to the end of the chain, a block that is only reached by a
+//! `Goto` is merged into its predecessor, and unreachable blocks are removed.
+
+use super::{visit::successors_mut, OptLevel, Pass};
+use crate::{
+    body::{BasicBlock, Body, Operand, StatementKind, Terminator, TerminatorKind, START_BLOCK},
+    db::MirDatabase,
+};
+use cell_typeck::consteval::ConstValue;
+use std::mem;
+
+pub struct SimplifyCfg;
+
+impl Pass for SimplifyCfg {
+    fn name(&self) -> &'static str {
+        "SimplifyCfg"
+    }
+
+    fn level(&self) -> OptLevel {
+        OptLevel::O1
+    }
+
+    fn run(&self, _db: &dyn MirDatabase, body: &mut Body) {
+        loop {
+            remove_dead_blocks(body);
+            let folded = fold_switches(body);
+            let threaded = thread_gotos(body);
+            let merged = merge_blocks(body);
+            if !folded && !threaded && !merged {
+                break;
+            }
+        }
+    }
+}
+
+/// Returns the bits that a `SwitchInt` compares against the value.
+fn switch_bits(val: &ConstValue) -> Option<u128> {
+    match val {
+        ConstValue::Int(int) => Some(int.as_u128()),
+        ConstValue::Bool(val) => Some(*val as u128),
+        ConstValue::Char(val) => Some(*val as u128),
+        _ => None,
+    }
+}
+
+/// Replaces `SwitchInt`s that always jump to the same block with `Goto`s.
+fn fold_switches(body: &mut Body) -> bool {
+    let mut changed = false;
+    for block in body.blocks.iter_mut() {
+        let terminator = block.terminator.as_mut().unwrap();
+        let target = match &terminator.kind {
+            TerminatorKind::SwitchInt {
+                discr: Operand::Constant(constant),
+                targets,
+                otherwise,
+            } => match switch_bits(&constant.val) {
+                Some(bits) => targets
+                    .iter()
+                    .find(|(val, _)| *val == bits)
+                    .map_or(*otherwise, |(_, target)| *target),
+                None => continue,
+            },
+            TerminatorKind::SwitchInt {
+                targets, otherwise, ..
+            } if targets.iter().all(|(_, target)| target == otherwise) => *otherwise,
+            _ => continue,
+        };
+        terminator.kind = TerminatorKind::Goto { target };
+        changed = true;
+    }
+    changed
+}
+
+/// Makes every edge that leads into a chain of empty blocks, that end with
+/// a `Goto`, point to the end of the chain.
+fn thread_gotos(body: &mut Body) -> bool {
+    let forward = |start: BasicBlock| {
+        let mut visited = vec![false; body.blocks.len()];
+        let mut block = start;
+        loop {
+            visited[block.index()] = true;
+            let data = body.block(block);
+            let empty = data
+                .statements
+                .iter()
+                .all(|statement| statement.kind == StatementKind::Nop);
+            match data.terminator().kind {
+                TerminatorKind::Goto { target } if empty && !visited[target.index()] => {
+                    block = target
+                }
+                _ => return block,
+            }
+        }
+    };
+    let targets = body
+        .basic_blocks()
+        .map(|(block, _)| forward(block))
+        .collect::<Vec<_>>();
+
+    let mut changed = false;
+    for block in body.blocks.iter_mut() {
+        let terminator = block.terminator.as_mut().unwrap();
+        successors_mut(&mut terminator.kind, |target| {
+            let forwarded = targets[target.index()];
+            if *target != forwarded {
+                *target = forwarded;
+                changed = true;
+            }
+        });
+    }
+    changed
+}
+
+/// Merges every block that is only reached by a `Goto` into its predecessor.
+fn merge_blocks(body: &mut Body) -> bool {
+    let mut predecessors = vec![0usize; body.blocks.len()];
+    for (_, block) in body.basic_blocks() {
+        for target in block.terminator().kind.successors() {
+            predecessors[target.index()] += 1;
+        }
+    }
+
+    let mut changed = false;
+    for idx in 0..body.blocks.len() {
+        while let TerminatorKind::Goto { target } = body.blocks[idx].terminator().kind {
+            if target.index() == idx || target == START_BLOCK || predecessors[target.index()] != 1 {
+                break;
+            }
+
+            // the merged block is unreachable now, and removed later
+            let unreachable = Terminator {
+                span: body.blocks[target.index()].terminator().span,
+                kind: TerminatorKind::Unreachable,
+            };
+            let merged = mem::take(&mut body.blocks[target.index()]);
+            body.blocks[target.index()].terminator = Some(unreachable);
+
+            let block = &mut body.blocks[idx];
+            block.statements.extend(merged.statements);
+            block.terminator = merged.terminator;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Removes every block that can't be reached from the start block.
+fn remove_dead_blocks(body: &mut Body) {
+    let mut reachable = vec![false; body.blocks.len()];
+    let mut stack = vec![START_BLOCK];
+    while let Some(block) = stack.pop() {
+        if !mem::replace(&mut reachable[block.index()], true) {
+            stack.extend(body.block(block).terminator().kind.successors());
+        }
+    }
+    if reachable.iter().all(|&reachable| reachable) {
+        return;
+    }
+
+    let mut map = vec![None; body.blocks.len()];
+    let mut next = 0;
+    for (idx, &reachable) in reachable.iter().enumerate() {
+        if reachable {
+            map[idx] = Some(BasicBlock(next));
+            next += 1;
+        }
+    }
+
+    let mut idx = 0;
+    body.blocks.retain(|_| {
+        idx += 1;
+        reachable[idx - 1]
+    });
+    for block in body.blocks.iter_mut() {
+        let terminator = block.terminator.as_mut().unwrap();
+        successors_mut(&mut terminator.kind, |target| {
+            *target = map[target.index()].unwrap();
+        });
+    }
+}
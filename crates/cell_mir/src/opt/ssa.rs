@@ -0,0 +1,392 @@
+//! Converts the locals of a body into SSA form, and back out of it.
+//!
+//! In SSA form every local is only assigned once, which makes it a lot
+//! easier for other passes to reason about the value of a local.
+//! If a local is assigned in multiple blocks, a phi node is inserted where
+//! the assignments meet, which selects the value depending on the block
+//! that was executed before.
+//!
+//! Only locals that are always assigned as a whole, and never have their
+//! address taken, are converted. The phi nodes are placed at the iterated
+//! dominance frontiers of the assignments, but only where the local is live.
+
+use super::{
+    visit::{for_each_place, local_uses, successors_mut, MutVisitor, PlaceContext},
+    OptLevel, Pass,
+};
+use crate::{
+    body::{
+        BasicBlock, BasicBlockData, Body, Local, Operand, Place, PlaceElem, Rvalue, Statement,
+        StatementKind, Terminator, TerminatorKind, RETURN_PLACE, START_BLOCK,
+    },
+    db::MirDatabase,
+    dominators::Dominators,
+};
+use cell_common::source::Span;
+
+pub struct IntoSsa;
+
+impl Pass for IntoSsa {
+    fn name(&self) -> &'static str {
+        "IntoSsa"
+    }
+
+    fn level(&self) -> OptLevel {
+        OptLevel::O1
+    }
+
+    fn run(&self, _db: &dyn MirDatabase, body: &mut Body) {
+        // the start block can't have phi nodes,
+        // because the arguments are assigned before it
+        if !body.predecessors()[START_BLOCK.index()].is_empty() {
+            return;
+        }
+
+        let promotable = promotable_locals(body);
+        if !promotable.iter().any(|&promotable| promotable) {
+            return;
+        }
+
+        let dominators = Dominators::new(body);
+        let phis = place_phis(body, &dominators, &promotable);
+        insert_phis(body, &phis);
+
+        let mut renamer = Renamer {
+            stacks: promotable
+                .iter()
+                .enumerate()
+                .map(|(idx, &promotable)| match promotable {
+                    true => vec![Local(idx as u32)],
+                    false => vec![],
+                })
+                .collect(),
+            phis,
+            children: dominators.children(),
+        };
+        renamer.rename(body, START_BLOCK);
+    }
+}
+
+/// Returns whether every local can be converted into SSA form.
+fn promotable_locals(body: &Body) -> Vec<bool> {
+    let mut promotable = local_uses(body)
+        .iter()
+        .enumerate()
+        .map(|(idx, uses)| idx != RETURN_PLACE.index() && !uses.address_taken)
+        .collect::<Vec<_>>();
+
+    for_each_place(body, |place, ctx, _| {
+        if ctx == PlaceContext::Write && !place.projection.is_empty() {
+            promotable[place.local.index()] = false;
+        }
+    });
+    promotable
+}
+
+/// Returns the locals that every block reads before writing them,
+/// and the locals that every block writes.
+fn reads_and_writes(body: &Body, promotable: &[bool]) -> (Vec<Vec<bool>>, Vec<Vec<bool>>) {
+    let mut reads = vec![vec![false; body.locals.len()]; body.blocks.len()];
+    let mut writes = vec![vec![false; body.locals.len()]; body.blocks.len()];
+    for_each_place(body, |place, ctx, location| {
+        let block = location.block.index();
+        let mut read = |local: Local| {
+            if promotable[local.index()] && !writes[block][local.index()] {
+                reads[block][local.index()] = true;
+            }
+        };
+
+        for elem in place.projection.iter() {
+            if let PlaceElem::Index(local) = elem {
+                read(*local);
+            }
+        }
+        if ctx == PlaceContext::Write && place.projection.is_empty() {
+            writes[block][place.local.index()] = true;
+        } else {
+            read(place.local);
+        }
+    });
+    (reads, writes)
+}
+
+/// Returns the locals that need a phi node at the start of every block.
+fn place_phis(body: &Body, dominators: &Dominators, promotable: &[bool]) -> Vec<Vec<Local>> {
+    let (reads, writes) = reads_and_writes(body, promotable);
+
+    // a local is live at the start of a block if the block reads it before
+    // writing it, or if it's live in a successor and the block doesn't write it
+    let mut live = reads;
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (block, data) in body.basic_blocks() {
+            for succ in data.terminator().kind.successors() {
+                for idx in 0..body.locals.len() {
+                    let live_in = live[succ.index()][idx] && !writes[block.index()][idx];
+                    if live_in && !live[block.index()][idx] {
+                        live[block.index()][idx] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    let frontiers = dominators.frontiers(body);
+    let mut phis = vec![vec![]; body.blocks.len()];
+    for idx in (0..body.locals.len()).filter(|&idx| promotable[idx]) {
+        let mut has_phi = vec![false; body.blocks.len()];
+        let mut worklist = (0..body.blocks.len())
+            .filter(|&block| writes[block][idx])
+            .collect::<Vec<_>>();
+
+        while let Some(block) = worklist.pop() {
+            for frontier in frontiers[block].iter().map(|block| block.index()) {
+                if has_phi[frontier] || !live[frontier][idx] {
+                    continue;
+                }
+                has_phi[frontier] = true;
+                phis[frontier].push(Local(idx as u32));
+                // the phi node is a new assignment of the local
+                if !writes[frontier][idx] {
+                    worklist.push(frontier);
+                }
+            }
+        }
+    }
+    phis
+}
+
+/// Inserts the phi nodes at the start of the blocks.
+///
+/// Every operand is the original local, which is replaced while renaming.
+/// Only operands of predecessors that can't be reached keep it.
+fn insert_phis(body: &mut Body, phis: &[Vec<Local>]) {
+    let predecessors = body.predecessors();
+    for (idx, locals) in phis.iter().enumerate() {
+        let nodes = locals
+            .iter()
+            .map(|&local| {
+                let args = predecessors[idx]
+                    .iter()
+                    .map(|&pred| (pred, Operand::Copy(local.into())))
+                    .collect();
+                Statement {
+                    span: body.locals[local.index()].span,
+                    kind: StatementKind::Phi(local, args),
+                }
+            })
+            .collect::<Vec<_>>();
+        body.blocks[idx].statements.splice(0..0, nodes);
+    }
+}
+
+/// Gives every assignment of a promotable local a new local,
+/// by walking the dominator tree.
+struct Renamer {
+    /// The current local of every original local, which is empty
+    /// if the local isn't promotable.
+    stacks: Vec<Vec<Local>>,
+    /// The original locals of the phi nodes of every block.
+    phis: Vec<Vec<Local>>,
+    /// The children of every block in the dominator tree.
+    children: Vec<Vec<BasicBlock>>,
+}
+
+impl Renamer {
+    fn rename(&mut self, body: &mut Body, block: BasicBlock) {
+        let mut defined = vec![];
+
+        let mut data = std::mem::take(&mut body.blocks[block.index()]);
+        for statement in data.statements.iter_mut() {
+            match &mut statement.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    self.visit_rvalue(rvalue);
+                    self.define(body, place, &mut defined);
+                }
+                StatementKind::Phi(local, _) => {
+                    // the operands are renamed by the predecessors
+                    let mut place = Place::from(*local);
+                    self.define(body, &mut place, &mut defined);
+                    *local = place.local;
+                }
+                StatementKind::Nop => {}
+            }
+        }
+
+        let terminator = data.terminator.as_mut().unwrap();
+        match &mut terminator.kind {
+            TerminatorKind::SwitchInt { discr, .. } => self.visit_operand(discr),
+            TerminatorKind::Call {
+                args, destination, ..
+            } => {
+                for arg in args {
+                    self.visit_operand(arg);
+                }
+                self.define(body, destination, &mut defined);
+            }
+            TerminatorKind::Goto { .. } | TerminatorKind::Return | TerminatorKind::Unreachable => {}
+        }
+        let successors = data.terminator().kind.successors();
+        body.blocks[block.index()] = data;
+
+        for succ in successors {
+            let phis = &self.phis[succ.index()];
+            for (statement, original) in body.blocks[succ.index()].statements.iter_mut().zip(phis) {
+                if let StatementKind::Phi(_, args) = &mut statement.kind {
+                    let current = *self.stacks[original.index()].last().unwrap();
+                    for (_, op) in args.iter_mut().filter(|(pred, _)| *pred == block) {
+                        *op = Operand::Copy(current.into());
+                    }
+                }
+            }
+        }
+
+        for child in self.children[block.index()].clone() {
+            self.rename(body, child);
+        }
+
+        for local in defined {
+            self.stacks[local.index()].pop();
+        }
+    }
+
+    /// Renames the written place, and gives it a new local
+    /// if it's a promotable local.
+    fn define(&mut self, body: &mut Body, place: &mut Place, defined: &mut Vec<Local>) {
+        let original = place.local;
+        if !place.projection.is_empty() || self.stacks[original.index()].is_empty() {
+            self.visit_place(place, PlaceContext::Write);
+            return;
+        }
+
+        let local = Local(body.locals.len() as u32);
+        body.locals.push(body.locals[original.index()].clone());
+        self.stacks[original.index()].push(local);
+        defined.push(original);
+        place.local = local;
+    }
+}
+
+impl MutVisitor for Renamer {
+    fn visit_local(&mut self, local: &mut Local) {
+        if let Some(current) = self.stacks[local.index()].last() {
+            *local = *current;
+        }
+    }
+}
+
+pub struct FromSsa;
+
+impl Pass for FromSsa {
+    fn name(&self) -> &'static str {
+        "FromSsa"
+    }
+
+    fn level(&self) -> OptLevel {
+        OptLevel::O1
+    }
+
+    /// Replaces every phi node with copies at the end of the predecessors.
+    ///
+    /// If a predecessor can jump to multiple blocks, the copies are put
+    /// into a new block on the edge, so they only happen if the edge is taken.
+    fn run(&self, _db: &dyn MirDatabase, body: &mut Body) {
+        let predecessors = body.predecessors();
+        // the blocks that are added for edges never contain phi nodes
+        for (idx, preds) in predecessors.iter().enumerate() {
+            let block = BasicBlock(idx as u32);
+            let phis = body.blocks[idx]
+                .statements
+                .iter()
+                .filter_map(|statement| match &statement.kind {
+                    StatementKind::Phi(local, args) => Some((statement.span, *local, args.clone())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if phis.is_empty() {
+                continue;
+            }
+            body.blocks[idx]
+                .statements
+                .retain(|statement| !matches!(statement.kind, StatementKind::Phi(..)));
+
+            for &pred in preds {
+                let copies = phis
+                    .iter()
+                    .filter_map(|(span, local, args)| {
+                        let (_, op) = args.iter().find(|(other, _)| *other == pred)?;
+                        match op {
+                            Operand::Copy(place) if *place == Place::from(*local) => None,
+                            op => Some((*span, *local, op.clone())),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let copies = parallel_copies(body, copies);
+                if copies.is_empty() {
+                    continue;
+                }
+
+                let data = &mut body.blocks[pred.index()];
+                if let TerminatorKind::Goto { .. } = data.terminator().kind {
+                    data.statements.extend(copies);
+                    continue;
+                }
+
+                let span = data.terminator().span;
+                let edge = BasicBlock(body.blocks.len() as u32);
+                let terminator = body.blocks[pred.index()].terminator.as_mut().unwrap();
+                successors_mut(&mut terminator.kind, |target| {
+                    if *target == block {
+                        *target = edge;
+                    }
+                });
+                body.blocks.push(BasicBlockData {
+                    statements: copies,
+                    terminator: Some(Terminator {
+                        span,
+                        kind: TerminatorKind::Goto { target: block },
+                    }),
+                });
+            }
+        }
+    }
+}
+
+/// Turns copies, that all happen at the same time, into a sequence of copies.
+///
+/// A phi node can read the local of another phi node in the same block,
+/// so these operands are copied into temporaries before any of the
+/// phi nodes is assigned.
+fn parallel_copies(body: &mut Body, copies: Vec<(Span, Local, Operand)>) -> Vec<Statement> {
+    let assign = |span, local: Local, op| Statement {
+        span,
+        kind: StatementKind::Assign(local.into(), Rvalue::Use(op)),
+    };
+    let reads_dest = |op: &Operand| match op {
+        Operand::Copy(place) => copies.iter().any(|(_, dest, _)| {
+            place.local == *dest || place.projection.contains(&PlaceElem::Index(*dest))
+        }),
+        Operand::Constant(_) => false,
+    };
+    let needs_temp = copies
+        .iter()
+        .map(|(_, _, op)| copies.len() > 1 && reads_dest(op))
+        .collect::<Vec<_>>();
+
+    let mut statements = vec![];
+    let mut assigns = vec![];
+    for ((span, local, op), needs_temp) in copies.into_iter().zip(needs_temp) {
+        if needs_temp {
+            let temp = Local(body.locals.len() as u32);
+            body.locals.push(body.locals[local.index()].clone());
+            statements.push(assign(span, temp, op));
+            assigns.push(assign(span, local, Operand::Copy(temp.into())));
+        } else {
+            assigns.push(assign(span, local, op));
+        }
+    }
+    statements.extend(assigns);
+    statements
+}
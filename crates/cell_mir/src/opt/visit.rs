@@ -0,0 +1,235 @@
+//! Helpers for walking over every place, local and type of a body.
+
+use crate::body::{
+    AggregateKind, BasicBlock, BasicBlockData, Body, Local, Location, Operand, Place, PlaceElem,
+    Rvalue, StatementKind, TerminatorKind,
+};
+use cell_typeck::ty::Ty;
+
+/// How a place is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceContext {
+    Read,
+    Write,
+    AddressOf,
+}
+
+/// Modifies the operands, places and locals of a body in place.
+///
+/// The default methods walk into everything below them,
+/// so an implementation only overrides what it's interested in.
+pub trait MutVisitor {
+    fn visit_body(&mut self, body: &mut Body) {
+        for block in body.blocks.iter_mut() {
+            self.visit_block(block);
+        }
+    }
+
+    fn visit_block(&mut self, block: &mut BasicBlockData) {
+        for statement in block.statements.iter_mut() {
+            match &mut statement.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    self.visit_rvalue(rvalue);
+                    self.visit_place(place, PlaceContext::Write);
+                }
+                StatementKind::Phi(local, args) => {
+                    for (_, op) in args {
+                        self.visit_operand(op);
+                    }
+                    self.visit_local(local);
+                }
+                StatementKind::Nop => {}
+            }
+        }
+        if let Some(terminator) = &mut block.terminator {
+            self.visit_terminator(&mut terminator.kind);
+        }
+    }
+
+    fn visit_terminator(&mut self, kind: &mut TerminatorKind) {
+        match kind {
+            TerminatorKind::SwitchInt { discr, .. } => self.visit_operand(discr),
+            TerminatorKind::Call {
+                generic_args,
+                args,
+                destination,
+                ..
+            } => {
+                for ty in generic_args {
+                    self.visit_ty(ty);
+                }
+                for arg in args {
+                    self.visit_operand(arg);
+                }
+                self.visit_place(destination, PlaceContext::Write);
+            }
+            TerminatorKind::Goto { .. } | TerminatorKind::Return | TerminatorKind::Unreachable => {}
+        }
+    }
+
+    fn visit_rvalue(&mut self, rvalue: &mut Rvalue) {
+        match rvalue {
+            Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => self.visit_operand(op),
+            Rvalue::BinaryOp(_, left, right) => {
+                self.visit_operand(left);
+                self.visit_operand(right);
+            }
+            Rvalue::AddressOf(place) => self.visit_place(place, PlaceContext::AddressOf),
+            Rvalue::Discriminant(place) => self.visit_place(place, PlaceContext::Read),
+            Rvalue::Aggregate(kind, ops) => {
+                if let AggregateKind::Adt(_, args) | AggregateKind::Variant(_, _, args) = kind {
+                    for ty in args {
+                        self.visit_ty(ty);
+                    }
+                }
+                for op in ops {
+                    self.visit_operand(op);
+                }
+            }
+            Rvalue::AddressOfStatic(_) => {}
+        }
+    }
+
+    fn visit_operand(&mut self, op: &mut Operand) {
+        match op {
+            Operand::Copy(place) => self.visit_place(place, PlaceContext::Read),
+            Operand::Constant(constant) => self.visit_ty(&mut constant.ty),
+        }
+    }
+
+    fn visit_place(&mut self, place: &mut Place, _ctx: PlaceContext) {
+        self.visit_local(&mut place.local);
+        for elem in place.projection.iter_mut() {
+            if let PlaceElem::Index(local) = elem {
+                self.visit_local(local);
+            }
+        }
+    }
+
+    fn visit_local(&mut self, _local: &mut Local) {}
+
+    fn visit_ty(&mut self, _ty: &mut Ty) {}
+}
+
+/// How often a local is used.
+#[derive(Debug, Clone, Default)]
+pub struct LocalUse {
+    pub reads: usize,
+    /// How often the local, or a part of it, is written to.
+    /// Writes through a pointer that is stored inside of the local
+    /// are reads of the local.
+    pub writes: usize,
+    /// Whether a pointer to the local, or a part of it, is created.
+    pub address_taken: bool,
+}
+
+impl LocalUse {
+    pub fn is_unused(&self) -> bool {
+        self.reads == 0 && self.writes == 0 && !self.address_taken
+    }
+}
+
+/// Counts the uses of every local.
+///
+/// Arguments count as written once, by the caller.
+pub fn local_uses(body: &Body) -> Vec<LocalUse> {
+    let mut uses = vec![LocalUse::default(); body.locals.len()];
+    for arg in body.args() {
+        uses[arg.index()].writes += 1;
+    }
+
+    for_each_place(body, |place, ctx, _| {
+        let indirect = place
+            .projection
+            .iter()
+            .any(|elem| matches!(elem, PlaceElem::Deref | PlaceElem::Index(_)));
+        let local = &mut uses[place.local.index()];
+        match ctx {
+            _ if indirect => local.reads += 1,
+            PlaceContext::Read => local.reads += 1,
+            PlaceContext::Write => local.writes += 1,
+            PlaceContext::AddressOf => local.address_taken = true,
+        }
+
+        for elem in place.projection.iter() {
+            if let PlaceElem::Index(local) = elem {
+                uses[local.index()].reads += 1;
+            }
+        }
+    });
+    uses
+}
+
+/// Calls `f` with every place of the body, how it's used and where.
+///
+/// The operands of a phi node are used at the location of the phi node.
+pub fn for_each_place(body: &Body, mut f: impl FnMut(&Place, PlaceContext, Location)) {
+    let operand = |op: &Operand, f: &mut dyn FnMut(&Place, PlaceContext)| {
+        if let Operand::Copy(place) = op {
+            f(place, PlaceContext::Read);
+        }
+    };
+
+    for (block, data) in body.basic_blocks() {
+        for (idx, statement) in data.statements.iter().enumerate() {
+            let location = Location {
+                block,
+                statement: idx,
+            };
+            let mut f = |place: &Place, ctx| f(place, ctx, location);
+            let (place, rvalue) = match &statement.kind {
+                StatementKind::Assign(place, rvalue) => (place, rvalue),
+                StatementKind::Phi(local, args) => {
+                    args.iter().for_each(|(_, op)| operand(op, &mut f));
+                    f(&Place::from(*local), PlaceContext::Write);
+                    continue;
+                }
+                StatementKind::Nop => continue,
+            };
+            match rvalue {
+                Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => operand(op, &mut f),
+                Rvalue::BinaryOp(_, left, right) => {
+                    operand(left, &mut f);
+                    operand(right, &mut f);
+                }
+                Rvalue::AddressOf(place) => f(place, PlaceContext::AddressOf),
+                Rvalue::Discriminant(place) => f(place, PlaceContext::Read),
+                Rvalue::Aggregate(_, ops) => ops.iter().for_each(|op| operand(op, &mut f)),
+                Rvalue::AddressOfStatic(_) => {}
+            }
+            f(place, PlaceContext::Write);
+        }
+
+        let location = Location {
+            block,
+            statement: data.statements.len(),
+        };
+        let mut f = |place: &Place, ctx| f(place, ctx, location);
+        match &data.terminator().kind {
+            TerminatorKind::SwitchInt { discr, .. } => operand(discr, &mut f),
+            TerminatorKind::Call {
+                args, destination, ..
+            } => {
+                args.iter().for_each(|arg| operand(arg, &mut f));
+                f(destination, PlaceContext::Write);
+            }
+            TerminatorKind::Goto { .. } | TerminatorKind::Return | TerminatorKind::Unreachable => {}
+        }
+    }
+}
+
+/// Calls `f` with every block that is the target of an edge in the CFG.
+pub fn successors_mut(kind: &mut TerminatorKind, mut f: impl FnMut(&mut BasicBlock)) {
+    match kind {
+        TerminatorKind::Goto { target } | TerminatorKind::Call { target, .. } => f(target),
+        TerminatorKind::SwitchInt {
+            targets, otherwise, ..
+        } => {
+            for (_, target) in targets.iter_mut() {
+                f(target);
+            }
+            f(otherwise);
+        }
+        TerminatorKind::Return | TerminatorKind::Unreachable => {}
+    }
+}
@@ -0,0 +1,335 @@
+//! A human readable representation of MIR, that is used for debugging.
+//!
+//! ```text
+//! def fib(_1: i32): i32 {
+//!     debug n => _1;
+//!     let _2: bool;
+//!
+//!     bb0: {
+//!         _2 = _1 == const 0_i32;
+//!         switchInt(_2) -> [0: bb2, otherwise: bb1];
+//!     }
+//!     ...
+//! }
+//! ```
+
+use crate::{
+    body::{
+        field_ty, variant_ty, AggregateKind, Body, Constant, Operand, Place, PlaceElem, Rvalue,
+        StatementKind, TerminatorKind, UnOp,
+    },
+    db::MirDatabase,
+    opt::OptLevel,
+};
+use cell_common::{source::FileId, strings::StringId};
+use cell_syntax::ast::{ItemId, ItemKind};
+use cell_typeck::{
+    consteval::ConstValue,
+    lower::AdtDef,
+    scope,
+    ty::{Ty, TyKind},
+};
+use std::fmt::Write;
+
+/// Returns the MIR of every function and method inside the file,
+/// after it was optimized at `level`.
+///
+/// Functions that contain errors are skipped.
+pub fn pretty_file(db: &dyn MirDatabase, file: FileId, level: OptLevel) -> String {
+    let mut out = String::new();
+    for &item in db.parse(file).items.iter() {
+        let items = match db.lookup_intern_item(item).kind {
+            ItemKind::Def(_) => vec![item],
+            ItemKind::Impl(imp) => imp.methods,
+            ItemKind::Trait(trait_) => trait_.methods,
+            _ => continue,
+        };
+
+        for item in items {
+            if let Some(body) = db.optimized_mir(item, level) {
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+                out.push_str(&pretty_body(db, &body));
+            }
+        }
+    }
+    out
+}
+
+/// Returns the MIR of a single function.
+pub fn pretty_body(db: &dyn MirDatabase, body: &Body) -> String {
+    Printer { db, body }.body()
+}
+
+/// Returns the name of a function, prefixed by the type
+/// or trait it's defined in.
+pub fn item_name(db: &dyn MirDatabase, item: ItemId) -> String {
+    let name = match db.lookup_intern_item(item).kind.name() {
+        Some(name) => db.rodeo().resolve(&name.id).to_string(),
+        None => "{unknown}".to_string(),
+    };
+
+    match scope::parent_item(db, item) {
+        Some(parent) => match db.lookup_intern_item(parent).kind {
+            ItemKind::Impl(_) => format!("{}.{}", db.impl_self_ty(parent).display(db), name),
+            _ => format!("{}.{}", item_name(db, parent), name),
+        },
+        None => name,
+    }
+}
+
+struct Printer<'a> {
+    db: &'a dyn MirDatabase,
+    body: &'a Body,
+}
+
+impl Printer<'_> {
+    fn body(&self) -> String {
+        let db = self.db;
+        let body = self.body;
+        let mut out = String::new();
+
+        // the generics of the parent are already part of the name
+        let generics = db.generics_of(body.item);
+        let own = &generics.params[generics.parent_count..];
+        let generics = if own.is_empty() {
+            String::new()
+        } else {
+            let names = own
+                .iter()
+                .map(|param| db.rodeo().resolve(&param.name).to_string())
+                .collect::<Vec<_>>();
+            format!("[{}]", names.join(", "))
+        };
+        let args = body
+            .args()
+            .map(|arg| format!("_{}: {}", arg.0, body.local(arg).ty.display(db)))
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "def {}{}({}): {} {{",
+            item_name(db, body.item),
+            generics,
+            args.join(", "),
+            body.return_ty().display(db)
+        )
+        .unwrap();
+
+        for (idx, local) in body.locals.iter().enumerate() {
+            if let Some(name) = local.name {
+                writeln!(out, "    debug {} => _{};", db.rodeo().resolve(&name), idx).unwrap();
+            }
+        }
+        for (idx, local) in body.locals.iter().enumerate().skip(body.arg_count + 1) {
+            writeln!(out, "    let _{}: {};", idx, local.ty.display(db)).unwrap();
+        }
+
+        for (block, data) in body.basic_blocks() {
+            writeln!(out, "\n    bb{}: {{", block.0).unwrap();
+            for statement in &data.statements {
+                match &statement.kind {
+                    StatementKind::Assign(place, rvalue) => writeln!(
+                        out,
+                        "        {} = {};",
+                        self.place(place),
+                        self.rvalue(rvalue)
+                    )
+                    .unwrap(),
+                    StatementKind::Phi(local, args) => {
+                        let args = args
+                            .iter()
+                            .map(|(block, op)| format!("bb{}: {}", block.0, self.operand(op)))
+                            .collect::<Vec<_>>();
+                        writeln!(out, "        _{} = phi [{}];", local.0, args.join(", ")).unwrap()
+                    }
+                    StatementKind::Nop => writeln!(out, "        nop;").unwrap(),
+                }
+            }
+            if let Some(terminator) = &data.terminator {
+                writeln!(out, "        {};", self.terminator(&terminator.kind)).unwrap();
+            }
+            writeln!(out, "    }}").unwrap();
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    fn terminator(&self, kind: &TerminatorKind) -> String {
+        match kind {
+            TerminatorKind::Goto { target } => format!("goto -> bb{}", target.0),
+            TerminatorKind::SwitchInt {
+                discr,
+                targets,
+                otherwise,
+            } => {
+                let targets = targets
+                    .iter()
+                    .map(|(val, target)| format!("{}: bb{}", val, target.0))
+                    .collect::<Vec<_>>();
+                format!(
+                    "switchInt({}) -> [{}, otherwise: bb{}]",
+                    self.operand(discr),
+                    targets.join(", "),
+                    otherwise.0
+                )
+            }
+            TerminatorKind::Return => "return".to_string(),
+            TerminatorKind::Call {
+                func,
+                generic_args,
+                args,
+                destination,
+                target,
+            } => format!(
+                "{} = {}{}({}) -> bb{}",
+                self.place(destination),
+                item_name(self.db, *func),
+                self.generic_args(generic_args),
+                self.operands(args),
+                target.0
+            ),
+            TerminatorKind::Unreachable => "unreachable".to_string(),
+        }
+    }
+
+    fn place(&self, place: &Place) -> String {
+        let db = self.db;
+        let mut out = format!("_{}", place.local.0);
+        let mut ty = self.body.local(place.local).ty;
+        for elem in &place.projection {
+            ty = match elem {
+                PlaceElem::Deref => {
+                    out = format!("(*{})", out);
+                    pointee(db, ty)
+                }
+                PlaceElem::Field(idx) => {
+                    out = format!("{}.{}", out, idx);
+                    field_ty(db, ty, *idx)
+                }
+                PlaceElem::Index(idx) => {
+                    out = format!("{}[_{}]", out, idx.0);
+                    pointee(db, ty)
+                }
+                PlaceElem::Downcast(idx) => {
+                    out = format!("({} as {})", out, variant_name(db, ty, *idx));
+                    variant_ty(db, ty, *idx)
+                }
+            };
+        }
+        out
+    }
+
+    fn operand(&self, op: &Operand) -> String {
+        match op {
+            Operand::Copy(place) => self.place(place),
+            Operand::Constant(Constant { ty, val }) => {
+                format!("const {}", const_value(val, *ty, self.db))
+            }
+        }
+    }
+
+    fn operands(&self, ops: &[Operand]) -> String {
+        ops.iter()
+            .map(|op| self.operand(op))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn generic_args(&self, args: &[Ty]) -> String {
+        if args.is_empty() {
+            return String::new();
+        }
+        let args = args
+            .iter()
+            .map(|ty| ty.display(self.db))
+            .collect::<Vec<_>>();
+        format!("[{}]", args.join(", "))
+    }
+
+    fn rvalue(&self, rvalue: &Rvalue) -> String {
+        let db = self.db;
+        match rvalue {
+            Rvalue::Use(op) => self.operand(op),
+            Rvalue::BinaryOp(op, left, right) => format!(
+                "{} {} {}",
+                self.operand(left),
+                op.symbol(),
+                self.operand(right)
+            ),
+            Rvalue::UnaryOp(UnOp::Not, op) => format!("!{}", self.operand(op)),
+            Rvalue::UnaryOp(UnOp::Neg, op) => format!("-{}", self.operand(op)),
+            Rvalue::AddressOf(place) => format!("&{}", self.place(place)),
+            Rvalue::AddressOfStatic(item) => format!("&static {}", item_name(db, *item)),
+            Rvalue::Discriminant(place) => format!("discriminant({})", self.place(place)),
+            Rvalue::Aggregate(kind, ops) => match kind {
+                AggregateKind::Tuple => format!("{{{}}}", self.operands(ops)),
+                AggregateKind::NamedTuple(names) => {
+                    format!("{{{}}}", self.fields(names.iter().copied(), ops))
+                }
+                AggregateKind::Adt(item, args) => {
+                    let ty = TyKind::Adt(*item, args.clone()).intern(db);
+                    let names = match &*db.adt_def(*item) {
+                        AdtDef::Struct { fields } => {
+                            fields.iter().map(|(name, _)| *name).collect::<Vec<_>>()
+                        }
+                        AdtDef::Union { .. } => vec![],
+                    };
+                    format!(
+                        "{} {{{}}}",
+                        ty.display(db),
+                        self.fields(names.into_iter(), ops)
+                    )
+                }
+                AggregateKind::Variant(item, idx, args) => {
+                    let ty = TyKind::Adt(*item, args.clone()).intern(db);
+                    let name = format!("{}:{}", ty.display(db), variant_name(db, ty, *idx));
+                    if ops.is_empty() {
+                        name
+                    } else {
+                        format!("{}({})", name, self.operands(ops))
+                    }
+                }
+            },
+        }
+    }
+
+    fn fields(&self, names: impl Iterator<Item = StringId>, ops: &[Operand]) -> String {
+        names
+            .zip(ops)
+            .map(|(name, op)| format!("{}: {}", self.db.rodeo().resolve(&name), self.operand(op)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn pointee(db: &dyn MirDatabase, ty: Ty) -> Ty {
+    match ty.kind(db) {
+        TyKind::Pointer(inner) => inner,
+        _ => ty,
+    }
+}
+
+fn variant_name(db: &dyn MirDatabase, ty: Ty, idx: u32) -> String {
+    match ty.kind(db) {
+        TyKind::Adt(item, _) => match &*db.adt_def(item) {
+            AdtDef::Union { variants } => db.rodeo().resolve(&variants[idx as usize].0).to_string(),
+            AdtDef::Struct { .. } => idx.to_string(),
+        },
+        _ => idx.to_string(),
+    }
+}
+
+/// Returns a constant value with the type as a suffix for numbers.
+fn const_value(val: &ConstValue, ty: Ty, db: &dyn MirDatabase) -> String {
+    match val {
+        ConstValue::Int(int) => format!("{}_{}", int, ty.display(db)),
+        ConstValue::Float { val, .. } => format!("{:?}_{}", val.into_inner(), ty.display(db)),
+        ConstValue::Bool(val) => val.to_string(),
+        ConstValue::Char(val) => format!("{:?}", val),
+        ConstValue::String(val) => format!("{:?}", val),
+        ConstValue::Unit => "()".to_string(),
+        ConstValue::Tuple(_) | ConstValue::NamedTuple(_) => format!("{:?}", val),
+    }
+}
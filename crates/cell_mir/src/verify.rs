@@ -0,0 +1,625 @@
+//! Checks that a body is well formed.
+//!
+//! The verifier catches bugs in the MIR builder and the optimizations,
+//! so the errors are meant for compiler developers and not for users.
+//! It checks that
+//!
+//! - every block has a terminator that jumps to blocks that exist,
+//! - phi nodes are at the start of a block, with one operand for every
+//!   predecessor,
+//! - the operands of every rvalue and terminator have the right types, and
+//! - every local that is assigned once is assigned before it's used,
+//!   which means that the assignment dominates every use.
+
+use crate::{
+    body::{
+        discriminant_ty, field_ty, variant_ty, AggregateKind, BinOp, Body, Location, Operand,
+        Place, PlaceElem, Rvalue, StatementKind, TerminatorKind, UnOp, RETURN_PLACE, START_BLOCK,
+    },
+    db::MirDatabase,
+    dominators::Dominators,
+    opt::visit::{for_each_place, local_uses, PlaceContext},
+};
+use cell_typeck::{
+    consteval::ConstValue,
+    lower::AdtDef,
+    ty::{Ty, TyKind},
+};
+
+/// Checks the body and returns every problem that was found.
+pub fn verify(db: &dyn MirDatabase, body: &Body) -> Result<(), Vec<String>> {
+    let mut verifier = Verifier {
+        db,
+        body,
+        errors: vec![],
+    };
+    verifier.verify();
+    if verifier.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(verifier.errors)
+    }
+}
+
+struct Verifier<'a> {
+    db: &'a dyn MirDatabase,
+    body: &'a Body,
+    errors: Vec<String>,
+}
+
+impl Verifier<'_> {
+    fn error(&mut self, location: Location, msg: String) {
+        self.errors.push(format!(
+            "bb{}[{}]: {}",
+            location.block.0, location.statement, msg
+        ));
+    }
+
+    fn verify(&mut self) {
+        // the later checks walk the CFG and the locals,
+        // so they can only be done if both are valid
+        self.check_structure();
+        if !self.errors.is_empty() {
+            return;
+        }
+
+        self.check_phis();
+        for (block, data) in self.body.basic_blocks() {
+            for (idx, statement) in data.statements.iter().enumerate() {
+                let location = Location {
+                    block,
+                    statement: idx,
+                };
+                self.check_statement(location, &statement.kind);
+            }
+            let location = Location {
+                block,
+                statement: data.statements.len(),
+            };
+            self.check_terminator(location, &data.terminator().kind);
+        }
+        self.check_dominance();
+    }
+
+    /// Checks that every block has a terminator and every block and local
+    /// that is referenced exists.
+    fn check_structure(&mut self) {
+        let body = self.body;
+        if body.blocks.is_empty() {
+            self.errors.push("the body has no blocks".to_string());
+            return;
+        }
+        if body.arg_count >= body.locals.len() {
+            self.errors
+                .push("the body has less locals than arguments".to_string());
+            return;
+        }
+
+        for (block, data) in body.basic_blocks() {
+            let location = Location {
+                block,
+                statement: data.statements.len(),
+            };
+            let terminator = match &data.terminator {
+                Some(terminator) => terminator,
+                None => {
+                    self.error(location, "block without a terminator".to_string());
+                    continue;
+                }
+            };
+            for target in terminator.kind.successors() {
+                if target.index() >= body.blocks.len() {
+                    self.error(
+                        location,
+                        format!("jump to bb{}, which doesn't exist", target.0),
+                    );
+                }
+            }
+
+            for (idx, statement) in data.statements.iter().enumerate() {
+                if let StatementKind::Phi(_, args) = &statement.kind {
+                    for (pred, _) in args {
+                        if pred.index() >= body.blocks.len() {
+                            let location = Location {
+                                block,
+                                statement: idx,
+                            };
+                            let msg = format!("phi operand for bb{}, which doesn't exist", pred.0);
+                            self.error(location, msg);
+                        }
+                    }
+                }
+            }
+        }
+        if !self.errors.is_empty() {
+            return;
+        }
+
+        let mut missing = vec![];
+        for_each_place(body, |place, _, location| {
+            let locals =
+                std::iter::once(place.local).chain(place.projection.iter().filter_map(|elem| {
+                    match elem {
+                        PlaceElem::Index(local) => Some(*local),
+                        _ => None,
+                    }
+                }));
+            for local in locals {
+                if local.index() >= body.locals.len() {
+                    missing.push((location, local));
+                }
+            }
+        });
+        for (location, local) in missing {
+            self.error(
+                location,
+                format!("use of `_{}`, which doesn't exist", local.0),
+            );
+        }
+    }
+
+    /// Checks that phi nodes are only at the start of blocks and have
+    /// an operand of the right type for every predecessor.
+    fn check_phis(&mut self) {
+        let predecessors = self.body.predecessors();
+        for (block, data) in self.body.basic_blocks() {
+            let mut seen_other = false;
+            for (idx, statement) in data.statements.iter().enumerate() {
+                let location = Location {
+                    block,
+                    statement: idx,
+                };
+                let (local, args) = match &statement.kind {
+                    StatementKind::Phi(local, args) => (*local, args),
+                    _ => {
+                        seen_other = true;
+                        continue;
+                    }
+                };
+
+                if seen_other {
+                    self.error(location, "phi node after other statements".to_string());
+                }
+                if block == START_BLOCK {
+                    self.error(location, "phi node in the start block".to_string());
+                }
+
+                let preds = &predecessors[block.index()];
+                for (idx, (pred, _)) in args.iter().enumerate() {
+                    if !preds.contains(pred) {
+                        let msg =
+                            format!("phi operand for bb{}, which isn't a predecessor", pred.0);
+                        self.error(location, msg);
+                    }
+                    if args[..idx].iter().any(|(other, _)| other == pred) {
+                        self.error(location, format!("multiple phi operands for bb{}", pred.0));
+                    }
+                }
+                for pred in preds {
+                    if !args.iter().any(|(other, _)| other == pred) {
+                        self.error(location, format!("missing phi operand for bb{}", pred.0));
+                    }
+                }
+
+                let ty = self.body.local(local).ty;
+                for (_, op) in args {
+                    if let Some(op_ty) = self.operand_ty(location, op) {
+                        self.expect_same(location, ty, op_ty);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_statement(&mut self, location: Location, kind: &StatementKind) {
+        if let StatementKind::Assign(place, rvalue) = kind {
+            let place_ty = self.place_ty(location, place);
+            let rvalue_ty = self.rvalue_ty(location, rvalue);
+            if let (Some(place_ty), Some(rvalue_ty)) = (place_ty, rvalue_ty) {
+                self.expect_same(location, place_ty, rvalue_ty);
+            }
+        }
+    }
+
+    fn check_terminator(&mut self, location: Location, kind: &TerminatorKind) {
+        let db = self.db;
+        match kind {
+            TerminatorKind::SwitchInt { discr, .. } => {
+                if let Some(ty) = self.operand_ty(location, discr) {
+                    if !matches!(
+                        ty.kind(db),
+                        TyKind::Int { .. } | TyKind::Bool | TyKind::Char
+                    ) {
+                        let msg = format!("switch on a value of type `{}`", ty.display(db));
+                        self.error(location, msg);
+                    }
+                }
+            }
+            TerminatorKind::Call {
+                func,
+                generic_args,
+                args,
+                destination,
+                ..
+            } => {
+                let sig = db.fn_sig(*func).subst(db, generic_args);
+                let params = sig.this.iter().chain(sig.params.iter()).collect::<Vec<_>>();
+                if params.len() != args.len() {
+                    let msg = format!(
+                        "call with {} arguments to a function with {} parameters",
+                        args.len(),
+                        params.len()
+                    );
+                    self.error(location, msg);
+                }
+                for (&param, arg) in params.into_iter().zip(args) {
+                    if let Some(ty) = self.operand_ty(location, arg) {
+                        self.expect_same(location, param, ty);
+                    }
+                }
+                if let Some(ty) = self.place_ty(location, destination) {
+                    self.expect_same(location, ty, sig.ret);
+                }
+            }
+            TerminatorKind::Goto { .. } | TerminatorKind::Return | TerminatorKind::Unreachable => {}
+        }
+    }
+
+    /// Checks that every local, that is assigned exactly once, is assigned
+    /// at a location that dominates every use of it.
+    fn check_dominance(&mut self) {
+        let body = self.body;
+        let dominators = Dominators::new(body);
+        let uses = local_uses(body);
+        let single = |idx: usize| {
+            let uses = &uses[idx];
+            idx != RETURN_PLACE.index() && uses.writes == 1 && !uses.address_taken
+        };
+
+        // arguments are assigned by the caller, before the start block
+        let mut defs = vec![None; body.locals.len()];
+        for_each_place(body, |place, ctx, location| {
+            let direct = !place
+                .projection
+                .iter()
+                .any(|elem| matches!(elem, PlaceElem::Deref | PlaceElem::Index(_)));
+            if ctx == PlaceContext::Write && direct && place.local.index() > body.arg_count {
+                defs[place.local.index()] = Some(location);
+            }
+        });
+
+        let dominates = |def: Location, location: Location| {
+            if def.block == location.block {
+                def.statement < location.statement
+            } else {
+                dominators.dominates(def.block, location.block)
+            }
+        };
+
+        let mut errors = vec![];
+        let mut check_use = |place: &Place, location: Location| {
+            let locals =
+                std::iter::once(place.local).chain(place.projection.iter().filter_map(|elem| {
+                    match elem {
+                        PlaceElem::Index(local) => Some(*local),
+                        _ => None,
+                    }
+                }));
+            for local in locals {
+                if let (true, Some(def)) = (single(local.index()), defs[local.index()]) {
+                    if !dominates(def, location) {
+                        errors.push((location, local, def));
+                    }
+                }
+            }
+        };
+
+        for_each_place(body, |place, ctx, location| {
+            let block = body.block(location.block);
+            let is_phi = matches!(
+                block.statements.get(location.statement).map(|s| &s.kind),
+                Some(StatementKind::Phi(..))
+            );
+            if !dominators.is_reachable(location.block) || is_phi {
+                return;
+            }
+            if ctx == PlaceContext::Write {
+                // a write through a pointer reads the pointer
+                let indirect = place
+                    .projection
+                    .iter()
+                    .any(|elem| matches!(elem, PlaceElem::Deref | PlaceElem::Index(_)));
+                if !indirect {
+                    return;
+                }
+            }
+            check_use(place, location);
+        });
+
+        // the operands of a phi node are used at the end of the predecessor
+        for (block, data) in body.basic_blocks() {
+            if !dominators.is_reachable(block) {
+                continue;
+            }
+            for statement in data.statements.iter() {
+                if let StatementKind::Phi(_, args) = &statement.kind {
+                    for (pred, op) in args {
+                        if let Operand::Copy(place) = op {
+                            let end = Location {
+                                block: *pred,
+                                statement: body.block(*pred).statements.len() + 1,
+                            };
+                            if dominators.is_reachable(*pred) {
+                                check_use(place, end);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (location, local, def) in errors {
+            let msg = format!(
+                "`_{}` is used before it's assigned in bb{}[{}]",
+                local.0, def.block.0, def.statement
+            );
+            self.error(location, msg);
+        }
+    }
+
+    fn expect_same(&mut self, location: Location, expected: Ty, found: Ty) {
+        let db = self.db;
+        if !expected.same_as(db, found) {
+            let msg = format!(
+                "expected a value of type `{}`, found `{}`",
+                expected.display(db),
+                found.display(db)
+            );
+            self.error(location, msg);
+        }
+    }
+
+    /// Returns the type of the place, or `None` if a projection is invalid.
+    fn place_ty(&mut self, location: Location, place: &Place) -> Option<Ty> {
+        let db = self.db;
+        let mut ty = self.body.local(place.local).ty;
+        for elem in place.projection.iter() {
+            let next = match (elem, ty.kind(db)) {
+                (PlaceElem::Deref, TyKind::Pointer(inner)) => Some(inner),
+                (PlaceElem::Index(local), TyKind::Pointer(inner)) => {
+                    let index = self.body.local(*local).ty;
+                    if !matches!(index.kind(db), TyKind::Int { .. }) {
+                        let msg = format!("index of type `{}`", index.display(db));
+                        self.error(location, msg);
+                    }
+                    Some(inner)
+                }
+                (PlaceElem::Field(idx), TyKind::Tuple(tys)) if (*idx as usize) < tys.len() => {
+                    Some(field_ty(db, ty, *idx))
+                }
+                (PlaceElem::Field(idx), TyKind::NamedTuple(fields))
+                    if (*idx as usize) < fields.len() =>
+                {
+                    Some(field_ty(db, ty, *idx))
+                }
+                (PlaceElem::Field(idx), TyKind::Adt(item, _)) => match &*db.adt_def(item) {
+                    AdtDef::Struct { fields } if (*idx as usize) < fields.len() => {
+                        Some(field_ty(db, ty, *idx))
+                    }
+                    _ => None,
+                },
+                (PlaceElem::Downcast(idx), TyKind::Adt(item, _)) => match &*db.adt_def(item) {
+                    AdtDef::Union { variants } if (*idx as usize) < variants.len() => {
+                        Some(variant_ty(db, ty, *idx))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            ty = match next {
+                Some(ty) => ty,
+                None => {
+                    let msg = format!("invalid projection {:?} of `{}`", elem, ty.display(db));
+                    self.error(location, msg);
+                    return None;
+                }
+            };
+        }
+        Some(ty)
+    }
+
+    fn operand_ty(&mut self, location: Location, op: &Operand) -> Option<Ty> {
+        let db = self.db;
+        let constant = match op {
+            Operand::Copy(place) => return self.place_ty(location, place),
+            Operand::Constant(constant) => constant,
+        };
+
+        let matches = match (&constant.val, constant.ty.kind(db)) {
+            (ConstValue::Int(int), TyKind::Int { width, signed }) => {
+                int.width() == width && int.is_signed() == signed
+            }
+            (ConstValue::Float { width, .. }, TyKind::Float { width: ty_width }) => {
+                *width == ty_width
+            }
+            (ConstValue::Bool(_), TyKind::Bool)
+            | (ConstValue::Char(_), TyKind::Char)
+            | (ConstValue::String(_), TyKind::String)
+            | (ConstValue::Unit, TyKind::Unit) => true,
+            _ => false,
+        };
+        if !matches {
+            let msg = format!(
+                "constant {:?} of type `{}`",
+                constant.val,
+                constant.ty.display(db)
+            );
+            self.error(location, msg);
+        }
+        Some(constant.ty)
+    }
+
+    /// Returns the type of the rvalue, or `None` if its operands are invalid.
+    fn rvalue_ty(&mut self, location: Location, rvalue: &Rvalue) -> Option<Ty> {
+        let db = self.db;
+        let ty = match rvalue {
+            Rvalue::Use(op) => self.operand_ty(location, op)?,
+            Rvalue::BinaryOp(op, left, right) => {
+                let left = self.operand_ty(location, left)?;
+                let right = self.operand_ty(location, right)?;
+                return self.binary_op_ty(location, *op, left, right);
+            }
+            Rvalue::UnaryOp(op, val) => {
+                let ty = self.operand_ty(location, val)?;
+                let valid = matches!(
+                    (op, ty.kind(db)),
+                    (UnOp::Neg, TyKind::Int { .. })
+                        | (UnOp::Neg, TyKind::Float { .. })
+                        | (UnOp::Not, TyKind::Int { .. })
+                        | (UnOp::Not, TyKind::Bool)
+                );
+                if !valid {
+                    let msg = format!("`{:?}` applied to `{}`", op, ty.display(db));
+                    self.error(location, msg);
+                    return None;
+                }
+                ty
+            }
+            Rvalue::AddressOf(place) => TyKind::Pointer(self.place_ty(location, place)?).intern(db),
+            Rvalue::AddressOfStatic(item) => TyKind::Pointer(db.global_ty(*item)).intern(db),
+            Rvalue::Discriminant(place) => {
+                let ty = self.place_ty(location, place)?;
+                let is_union = match ty.kind(db) {
+                    TyKind::Adt(item, _) => matches!(&*db.adt_def(item), AdtDef::Union { .. }),
+                    _ => false,
+                };
+                if !is_union {
+                    let msg = format!("discriminant of `{}`, which isn't a union", ty.display(db));
+                    self.error(location, msg);
+                    return None;
+                }
+                discriminant_ty(db)
+            }
+            Rvalue::Aggregate(kind, ops) => {
+                let tys = ops
+                    .iter()
+                    .map(|op| self.operand_ty(location, op))
+                    .collect::<Option<Vec<_>>>()?;
+                return self.aggregate_ty(location, kind, tys);
+            }
+        };
+        Some(ty)
+    }
+
+    fn binary_op_ty(&mut self, location: Location, op: BinOp, left: Ty, right: Ty) -> Option<Ty> {
+        let db = self.db;
+        let is_int = |ty: Ty| matches!(ty.kind(db), TyKind::Int { .. });
+        let valid = match (op, left.kind(db)) {
+            _ if op.is_comparison() => left.same_as(db, right),
+            (BinOp::Add, TyKind::Pointer(_)) | (BinOp::Sub, TyKind::Pointer(_)) => is_int(right),
+            (BinOp::Shl, TyKind::Int { .. }) | (BinOp::Shr, TyKind::Int { .. }) => is_int(right),
+            (BinOp::BitAnd, TyKind::Bool)
+            | (BinOp::BitOr, TyKind::Bool)
+            | (BinOp::BitXor, TyKind::Bool) => left.same_as(db, right),
+            (BinOp::Shl, _) | (BinOp::Shr, _) => false,
+            (_, TyKind::Int { .. }) => left.same_as(db, right),
+            (BinOp::BitAnd, _) | (BinOp::BitOr, _) | (BinOp::BitXor, _) => false,
+            (_, TyKind::Float { .. }) => left.same_as(db, right),
+            _ => false,
+        };
+        if !valid {
+            let msg = format!(
+                "`{}` applied to `{}` and `{}`",
+                op.symbol(),
+                left.display(db),
+                right.display(db)
+            );
+            self.error(location, msg);
+            return None;
+        }
+
+        if op.is_comparison() {
+            Some(TyKind::Bool.intern(db))
+        } else {
+            Some(left)
+        }
+    }
+
+    fn aggregate_ty(
+        &mut self,
+        location: Location,
+        kind: &AggregateKind,
+        tys: Vec<Ty>,
+    ) -> Option<Ty> {
+        let db = self.db;
+        let (expected, ty) = match kind {
+            AggregateKind::Tuple => return Some(TyKind::Tuple(tys).intern(db)),
+            AggregateKind::NamedTuple(names) => {
+                if names.len() != tys.len() {
+                    let msg = format!(
+                        "named tuple with {} names and {} fields",
+                        names.len(),
+                        tys.len()
+                    );
+                    self.error(location, msg);
+                    return None;
+                }
+                let fields = names.iter().copied().zip(tys).collect();
+                return Some(TyKind::NamedTuple(fields).intern(db));
+            }
+            AggregateKind::Adt(item, args) => {
+                let ty = TyKind::Adt(*item, args.clone()).intern(db);
+                let expected = match &*db.adt_def(*item) {
+                    AdtDef::Struct { fields } => (0..fields.len() as u32)
+                        .map(|idx| field_ty(db, ty, idx))
+                        .collect(),
+                    AdtDef::Union { .. } => {
+                        self.error(
+                            location,
+                            format!("`{}` built like a struct", ty.display(db)),
+                        );
+                        return None;
+                    }
+                };
+                (expected, ty)
+            }
+            AggregateKind::Variant(item, idx, args) => {
+                let ty = TyKind::Adt(*item, args.clone()).intern(db);
+                let expected = match &*db.adt_def(*item) {
+                    AdtDef::Union { variants } if (*idx as usize) < variants.len() => {
+                        match variants[*idx as usize].1 {
+                            Some(_) => vec![variant_ty(db, ty, *idx)],
+                            None => vec![],
+                        }
+                    }
+                    _ => {
+                        let msg = format!(
+                            "variant {} of `{}`, which doesn't exist",
+                            idx,
+                            ty.display(db)
+                        );
+                        self.error(location, msg);
+                        return None;
+                    }
+                };
+                (expected, ty)
+            }
+        };
+
+        if expected.len() != tys.len() {
+            let msg = format!(
+                "`{}` built with {} operands instead of {}",
+                ty.display(db),
+                tys.len(),
+                expected.len()
+            );
+            self.error(location, msg);
+            return None;
+        }
+        for (expected, found) in expected.into_iter().zip(tys) {
+            self.expect_same(location, expected, found);
+        }
+        Some(ty)
+    }
+}
@@ -1,14 +1,25 @@
 //! The salsa database for the whole frontend.
 
-use crate::token::{self, Token};
+use crate::{
+    parse::{self, ParsedFile},
+    token::{self, Token},
+};
 use cell_common::{
     source::{FileId, SourceDatabase},
     strings::StringInterner,
 };
-use cell_syntax::ast::{Expr, ExprId, Item, ItemId, Stmt, StmtId, Type, TypeId};
+use cell_syntax::{
+    ast::{Expr, ExprId, Item, ItemId, Pattern, PatternId, Stmt, StmtId, Type, TypeId},
+    db::AstDatabase,
+};
+use std::sync::Arc;
 
+/// The queries of the parser.
+///
+/// A database has to implement `AstDatabase` by forwarding
+/// to the interning queries of this group.
 #[salsa::query_group(ParseDatabaseStorage)]
-pub trait ParseDatabase: SourceDatabase {
+pub trait ParseDatabase: SourceDatabase + AstDatabase {
     #[salsa::input]
     fn rodeo(&self) -> StringInterner;
 
@@ -24,12 +35,19 @@ pub trait ParseDatabase: SourceDatabase {
     #[salsa::interned]
     fn intern_type(&self, ty: Type) -> TypeId;
 
+    #[salsa::interned]
+    fn intern_pattern(&self, pattern: Pattern) -> PatternId;
+
     /// Takes the source of the file and turns it into a sequence
     /// of tokens.
     fn lex(&self, file: FileId) -> Vec<Token>;
+
+    /// Parses the tokens of the file into a list of items.
+    #[salsa::invoke(parse::parse)]
+    fn parse(&self, file: FileId) -> Arc<ParsedFile>;
 }
 
 fn lex(db: &dyn ParseDatabase, file: FileId) -> Vec<Token> {
-    let source = &*db.source(file);
+    let source = &*db.file_text(file);
     token::lex(source)
 }
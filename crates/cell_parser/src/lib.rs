@@ -1,3 +1,4 @@
 pub mod db;
 pub mod parse;
-mod token;
+pub mod pretty;
+pub mod token;
@@ -3,12 +3,321 @@
 //! The parser is implemented using pratt parsing for
 //! expressions and recursive decent for the rest.
 
-use crate::token::Token;
-use cell_common::{error::ErrorHandler, source::FileId};
-use std::{iter::Peekable, vec::IntoIter};
+mod expr;
+mod item;
+mod pattern;
+mod ty;
 
-pub struct Parser {
-    tokens: Peekable<IntoIter<Token>>,
+use crate::{
+    db::ParseDatabase,
+    token::{Kind, Token},
+};
+use cell_common::{
+    error::{Error, ErrorHandler, ParseResult, SyntaxError},
+    profiler,
+    source::{FileId, Locatable, Span},
+};
+use cell_syntax::ast::{Generic, Identifier, ItemId, StmtId};
+use std::{fmt::Display, sync::Arc};
+
+/// The result of parsing a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedFile {
+    /// Every top level item inside the file.
+    pub items: Vec<ItemId>,
+    /// All errors that occurred while parsing the file.
+    pub errors: Vec<Locatable<Error>>,
+}
+
+/// A single input of the REPL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Items(Vec<ItemId>),
+    Stmts(Vec<StmtId>),
+}
+
+/// The result of parsing an input of the REPL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedInput {
+    pub input: Input,
+    /// All errors that occurred while parsing the input.
+    pub errors: Vec<Locatable<Error>>,
+}
+
+pub(crate) fn parse(db: &dyn ParseDatabase, file: FileId) -> Arc<ParsedFile> {
+    let parser = Parser::new(db, file);
+    Arc::new(parser.parse_file())
+}
+
+pub struct Parser<'db> {
+    db: &'db dyn ParseDatabase,
+    tokens: Vec<Token>,
+    pos: usize,
     errors: ErrorHandler,
     file: FileId,
+    source: Arc<String>,
+    /// The span of the last token that was consumed.
+    prev_span: Span,
+}
+
+impl<'db> Parser<'db> {
+    /// Creates a new parser that will parse the tokens of the given file.
+    ///
+    /// Comments are removed from the token stream and every invalid
+    /// token is reported as an error.
+    pub fn new(db: &'db dyn ParseDatabase, file: FileId) -> Self {
+        let mut errors = ErrorHandler::default();
+        let tokens = db
+            .lex(file)
+            .into_iter()
+            .filter(|token| match token.kind {
+                Kind::Comment => false,
+                Kind::Error => {
+                    errors.error(Locatable::new(
+                        Error::from(SyntaxError::InvalidToken),
+                        file,
+                        token.span,
+                    ));
+                    false
+                }
+                _ => true,
+            })
+            .collect();
+
+        Self {
+            db,
+            tokens,
+            pos: 0,
+            errors,
+            file,
+            source: db.file_text(file),
+            prev_span: Span::default(),
+        }
+    }
+
+    /// Parses every item in the file until the end of the file is reached.
+    ///
+    /// If an item fails to parse, the error is recorded and the
+    /// parser skips to the beginning of the next item.
+    pub fn parse_file(mut self) -> ParsedFile {
+        let _profiler = profiler::trace("Parse", "Parse");
+
+        let mut items = vec![];
+        while self.peek().is_some() {
+            let start = self.pos;
+            match self.item() {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    self.errors.error(err.map(Error::from));
+                    if self.pos == start {
+                        self.next();
+                    }
+                    self.recover_item();
+                }
+            }
+        }
+
+        ParsedFile {
+            items,
+            errors: self.errors.take_errors(),
+        }
+    }
+
+    /// Parses an input of the REPL, which is either a list of items,
+    /// if it starts with an item, or a list of statements.
+    ///
+    /// The `;` after the last statement is optional.
+    pub fn parse_input(mut self) -> ParsedInput {
+        if self.peek_kind().is_some_and(Kind::starts_item) {
+            let file = self.parse_file();
+            return ParsedInput {
+                input: Input::Items(file.items),
+                errors: file.errors,
+            };
+        }
+
+        let mut stmts = vec![];
+        while self.peek().is_some() {
+            let pos = self.pos;
+            match self.input_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(err) => {
+                    self.errors.error(err.map(Error::from));
+                    if self.pos == pos {
+                        self.next();
+                    }
+                    self.recover_stmt();
+                }
+            }
+        }
+
+        ParsedInput {
+            input: Input::Stmts(stmts),
+            errors: self.errors.take_errors(),
+        }
+    }
+
+    /// Skips tokens until a token is found that starts a new item.
+    fn recover_item(&mut self) {
+        while let Some(kind) = self.peek_kind() {
+            if kind.starts_item() {
+                break;
+            }
+            self.next();
+        }
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn peek_kind(&self) -> Option<Kind> {
+        self.peek().map(|t| t.kind)
+    }
+
+    /// Looks at the token `n` tokens after the current one.
+    fn peek_nth_kind(&self, n: usize) -> Option<Kind> {
+        self.tokens.get(self.pos + n).map(|t| t.kind)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek()?;
+        self.pos += 1;
+        self.prev_span = token.span;
+        Some(token)
+    }
+
+    fn at(&self, kind: Kind) -> bool {
+        self.peek_kind() == Some(kind)
+    }
+
+    /// Consumes the next token if it is of the given kind.
+    fn eat(&mut self, kind: Kind) -> Option<Token> {
+        if self.at(kind) {
+            self.next()
+        } else {
+            None
+        }
+    }
+
+    /// Consumes the next token and returns an error if it's not
+    /// of the given kind.
+    fn expect(&mut self, kind: Kind) -> ParseResult<Token> {
+        match self.eat(kind) {
+            Some(token) => Ok(token),
+            None => Err(self.unexpected(format!("`{}`", kind))),
+        }
+    }
+
+    /// Creates an error at the current token, saying that
+    /// `expected` was expected.
+    fn unexpected(&self, expected: impl Display) -> Locatable<SyntaxError> {
+        let expected = expected.to_string();
+        match self.peek() {
+            Some(token) => Locatable::new(
+                SyntaxError::UnexpectedToken {
+                    expected,
+                    found: format!("`{}`", self.text(token.span)),
+                },
+                self.file,
+                token.span,
+            ),
+            None => {
+                let end = Span::new(self.prev_span.end(), self.prev_span.end());
+                Locatable::new(SyntaxError::UnexpectedEof { expected }, self.file, end)
+            }
+        }
+    }
+
+    fn error(&self, err: SyntaxError, span: Span) -> Locatable<SyntaxError> {
+        Locatable::new(err, self.file, span)
+    }
+
+    /// Returns the source text for the given span.
+    fn text(&self, span: Span) -> &str {
+        &self.source[std::ops::Range::<usize>::from(span)]
+    }
+
+    /// Returns the span that starts at `start` and ends at the
+    /// last consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        start.merge(self.prev_span)
+    }
+
+    /// Returns the span of the next token, or an empty span
+    /// at the end of the file.
+    fn current_span(&self) -> Span {
+        match self.peek() {
+            Some(token) => token.span,
+            None => Span::new(self.prev_span.end(), self.prev_span.end()),
+        }
+    }
+
+    fn ident(&mut self) -> ParseResult<Identifier> {
+        let token = self.expect(Kind::Identifier)?;
+        let id = self.db.rodeo().get_or_intern(self.text(token.span));
+        Ok(Identifier {
+            span: token.span,
+            id,
+        })
+    }
+
+    /// Parses a list of `item`s that are separated by `,` and surrounded
+    /// by `open` and `close`. A trailing comma is allowed.
+    fn delimited<T>(
+        &mut self,
+        open: Kind,
+        close: Kind,
+        mut item: impl FnMut(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<Vec<T>> {
+        self.expect(open)?;
+        let mut items = vec![];
+        while !self.at(close) {
+            items.push(item(self)?);
+            if self.eat(Kind::Comma).is_none() {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(items)
+    }
+
+    /// Parses an optional list of generics, like `[A: Trait, B]`.
+    fn generics(&mut self) -> ParseResult<Vec<Generic>> {
+        if !self.at(Kind::LeftBracket) {
+            return Ok(vec![]);
+        }
+
+        self.delimited(Kind::LeftBracket, Kind::RightBracket, |this| {
+            let name = this.ident()?;
+            let mut bounds = vec![];
+            if this.eat(Kind::Colon).is_some() {
+                bounds.push(this.ident()?);
+                while this.eat(Kind::Plus).is_some() {
+                    bounds.push(this.ident()?);
+                }
+            }
+            Ok(Generic { name, bounds })
+        })
+    }
+}
+
+impl Kind {
+    /// Checks if this token kind can start a new item.
+    fn starts_item(self) -> bool {
+        matches!(
+            self,
+            Kind::Def
+                | Kind::Type
+                | Kind::Union
+                | Kind::Trait
+                | Kind::Impl
+                | Kind::Const
+                | Kind::Static
+                | Kind::Import
+                | Kind::Use
+                | Kind::Pub
+                | Kind::At
+        )
+    }
 }
@@ -0,0 +1,583 @@
+//! Pratt parsing of expressions and parsing of statements.
+
+use super::Parser;
+use crate::token::Kind;
+use cell_common::{
+    error::{ParseResult, SyntaxError},
+    source::{Span, Spanned},
+};
+use cell_syntax::ast::{
+    Assign, BinOp, Binary, Call, CmpOp, Comparison, Expr, ExprId, ExprKind, Field, FieldName,
+    Float, If, IfArm, Int, Literal, Match, MatchArm, MethodCall, NamedTuple, Path, Stmt, StmtId,
+    StmtKind, Tuple, UnOp, Unary, Var, While,
+};
+use ordered_float::NotNan;
+
+/// The precedence of every infix operator.
+///
+/// The higher the value, the stronger the operator binds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Logical = 1,
+    Equality,
+    Comparison,
+    Bitwise,
+    Shifting,
+    Addition,
+    Multiplication,
+    Exponents,
+}
+
+/// An infix operator that was found in the token stream.
+#[derive(Clone, Copy)]
+enum Infix {
+    Bin(BinOp),
+    Cmp(CmpOp),
+}
+
+impl Kind {
+    fn infix(self) -> Option<(Infix, Precedence)> {
+        use Infix::{Bin, Cmp};
+        use Precedence::*;
+
+        let op = match self {
+            Kind::Ampersand2 => (Bin(BinOp::LogicalAnd), Logical),
+            Kind::Pipe2 => (Bin(BinOp::LogicalOr), Logical),
+            Kind::EqualEqual => (Cmp(CmpOp::Equal), Equality),
+            Kind::NotEqual => (Cmp(CmpOp::NotEqual), Equality),
+            Kind::LessThan => (Cmp(CmpOp::Less), Comparison),
+            Kind::LessThanEqual => (Cmp(CmpOp::LessEqual), Comparison),
+            Kind::GreaterThan => (Cmp(CmpOp::Greater), Comparison),
+            Kind::GreaterThanEqual => (Cmp(CmpOp::GreaterEqual), Comparison),
+            Kind::Ampersand => (Bin(BinOp::BitiwseAnd), Bitwise),
+            Kind::Pipe => (Bin(BinOp::BitwiseOr), Bitwise),
+            Kind::Caret => (Bin(BinOp::BitwiseXor), Bitwise),
+            Kind::LeftShift => (Bin(BinOp::LeftShift), Shifting),
+            Kind::RightShift => (Bin(BinOp::RightShift), Shifting),
+            Kind::Plus => (Bin(BinOp::Add), Addition),
+            Kind::Minus => (Bin(BinOp::Sub), Addition),
+            Kind::Star => (Bin(BinOp::Mul), Multiplication),
+            Kind::Slash => (Bin(BinOp::Div), Multiplication),
+            Kind::Percent => (Bin(BinOp::Mod), Multiplication),
+            Kind::StarStar => (Bin(BinOp::Pow), Exponents),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn assign_op(self) -> Option<Option<BinOp>> {
+        let op = match self {
+            Kind::Equal => None,
+            Kind::AmpersandEqual => Some(BinOp::BitiwseAnd),
+            Kind::PipeEqual => Some(BinOp::BitwiseOr),
+            Kind::CaretEqual => Some(BinOp::BitwiseXor),
+            Kind::LeftShiftEqual => Some(BinOp::LeftShift),
+            Kind::RightShiftEqual => Some(BinOp::RightShift),
+            Kind::PlusEqual => Some(BinOp::Add),
+            Kind::MinusEqual => Some(BinOp::Sub),
+            Kind::StarEqual => Some(BinOp::Mul),
+            Kind::SlashEqual => Some(BinOp::Div),
+            Kind::PercentEqual => Some(BinOp::Mod),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn prefix(self) -> Option<UnOp> {
+        let op = match self {
+            Kind::Ampersand => UnOp::Ref,
+            Kind::Star => UnOp::Deref,
+            Kind::Bang => UnOp::Not,
+            Kind::Plus => UnOp::Plus,
+            Kind::Minus => UnOp::Minus,
+            _ => return None,
+        };
+        Some(op)
+    }
+}
+
+impl Parser<'_> {
+    /// Parses a single statement and interns it.
+    pub(super) fn stmt(&mut self) -> ParseResult<StmtId> {
+        self.stmt_with_end(false)
+    }
+
+    /// Parses a statement of a REPL input, where the `;` of the
+    /// last statement can be omitted.
+    pub(super) fn input_stmt(&mut self) -> ParseResult<StmtId> {
+        self.stmt_with_end(true)
+    }
+
+    fn stmt_with_end(&mut self, eof_ends_stmt: bool) -> ParseResult<StmtId> {
+        let start = self.current_span();
+        let kind = if self.eat(Kind::Var).is_some() {
+            let name = self.ident()?;
+            let ty = if self.eat(Kind::Colon).is_some() {
+                let ty = self.ty()?;
+                Some(self.db.lookup_intern_type(ty))
+            } else {
+                None
+            };
+            self.expect(Kind::Equal)?;
+            let val = self.expr()?;
+            if !eof_ends_stmt || self.peek().is_some() {
+                self.stmt_end();
+            }
+            StmtKind::Var(Var { name, ty, val })
+        } else {
+            let expr = self.expr()?;
+            let block_like = matches!(
+                self.db.lookup_intern_expr(expr).kind,
+                ExprKind::If(_) | ExprKind::Match(_) | ExprKind::While(_) | ExprKind::Block(_)
+            );
+            let optional = block_like || (eof_ends_stmt && self.peek().is_none());
+            if !optional || self.at(Kind::Semicolon) {
+                self.stmt_end();
+            }
+            StmtKind::Expr(expr)
+        };
+
+        Ok(self.db.intern_stmt(Stmt {
+            span: self.span_from(start),
+            kind,
+        }))
+    }
+
+    /// Consumes the `;` at the end of a statement.
+    ///
+    /// A missing `;` is reported, but the statement is kept, because
+    /// it's most likely just not typed yet.
+    fn stmt_end(&mut self) {
+        if self.eat(Kind::Semicolon).is_none() {
+            let err = self.unexpected("`;`");
+            self.errors.error(err.map(Into::into));
+        }
+    }
+
+    /// Parses a single expression, including assignments.
+    pub(super) fn expr(&mut self) -> ParseResult<ExprId> {
+        let start = self.current_span();
+        let target = self.binary(Precedence::Logical)?;
+
+        let op = match self.peek_kind().and_then(Kind::assign_op) {
+            Some(op) => op,
+            None => return Ok(target),
+        };
+        let op_span = self.next().unwrap().span;
+        // assignments are right associative
+        let val = self.expr()?;
+
+        Ok(self.intern(
+            start,
+            ExprKind::Assign(Assign {
+                target,
+                op: op.map(|op| Spanned::new(op, op_span)),
+                val,
+            }),
+        ))
+    }
+
+    /// Parses all infix operators that have a precedence of at least `min`.
+    fn binary(&mut self, min: Precedence) -> ParseResult<ExprId> {
+        let start = self.current_span();
+        let mut left = self.unary()?;
+
+        loop {
+            let (op, prec) = match self.peek_kind().and_then(Kind::infix) {
+                Some((op, prec)) if prec >= min => (op, prec),
+                _ => break,
+            };
+            let op_span = self.next().unwrap().span;
+
+            // `**` is the only right associative operator
+            let right = if prec == Precedence::Exponents {
+                self.binary(prec)?
+            } else {
+                match next_precedence(prec) {
+                    Some(next) => self.binary(next)?,
+                    None => self.unary()?,
+                }
+            };
+
+            let kind = match op {
+                Infix::Bin(op) => ExprKind::Binary(Binary {
+                    left,
+                    op: Spanned::new(op, op_span),
+                    right,
+                }),
+                Infix::Cmp(op) => ExprKind::Comparison(Comparison {
+                    left,
+                    op: Spanned::new(op, op_span),
+                    right,
+                }),
+            };
+            left = self.intern(start, kind);
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> ParseResult<ExprId> {
+        let start = self.current_span();
+        match self.peek_kind().and_then(Kind::prefix) {
+            Some(op) => {
+                let op_span = self.next().unwrap().span;
+                let val = self.unary()?;
+                Ok(self.intern(
+                    start,
+                    ExprKind::Unary(Unary {
+                        op: Spanned::new(op, op_span),
+                        val,
+                    }),
+                ))
+            }
+            None => self.postfix(),
+        }
+    }
+
+    /// Parses calls, method calls and field accesses.
+    fn postfix(&mut self) -> ParseResult<ExprId> {
+        let start = self.current_span();
+        let mut expr = self.primary()?;
+
+        loop {
+            match self.peek_kind() {
+                Some(Kind::LeftParen) => {
+                    let args = self.delimited(Kind::LeftParen, Kind::RightParen, Self::expr)?;
+                    expr = self.intern(start, ExprKind::Call(Call { callee: expr, args }));
+                }
+                Some(Kind::Dot) => {
+                    self.next();
+                    match self.peek_kind() {
+                        Some(Kind::Integer) => {
+                            let token = self.next().unwrap();
+                            let field = self.field_index(token.span)?;
+                            expr = self.field(start, expr, field);
+                        }
+                        // `tuple.0.1` is lexed as `tuple`, `.`, `0.1`
+                        Some(Kind::Float) => {
+                            let token = self.next().unwrap();
+                            let text = self.text(token.span);
+                            let dot = match text.find('.') {
+                                Some(dot) => dot,
+                                None => return Err(self.unexpected("field")),
+                            };
+                            let dot = token.span.start() + text_size(dot);
+                            let first = Span::new(token.span.start(), dot);
+                            let second = Span::new(dot + text_size(1), token.span.end());
+
+                            let field = self.field_index(first)?;
+                            expr = self.field(start, expr, field);
+                            let field = self.field_index(second)?;
+                            expr = self.field(start, expr, field);
+                        }
+                        _ => {
+                            let name = self.ident()?;
+                            if self.at(Kind::LeftParen) {
+                                let args =
+                                    self.delimited(Kind::LeftParen, Kind::RightParen, Self::expr)?;
+                                let call = MethodCall {
+                                    receiver: expr,
+                                    name,
+                                    args,
+                                };
+                                expr = self.intern(start, ExprKind::MethodCall(call));
+                            } else {
+                                expr = self.field(start, expr, FieldName::Named(name));
+                            }
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn field_index(&self, span: Span) -> ParseResult<FieldName> {
+        match self.text(span).parse::<u32>() {
+            Ok(idx) => Ok(FieldName::Index(Spanned::new(idx, span))),
+            Err(_) => Err(self.error(SyntaxError::InvalidInt, span)),
+        }
+    }
+
+    fn field(&mut self, start: Span, val: ExprId, field: FieldName) -> ExprId {
+        self.intern(start, ExprKind::Field(Field { val, field }))
+    }
+
+    fn primary(&mut self) -> ParseResult<ExprId> {
+        let start = self.current_span();
+        let kind = match self.peek_kind() {
+            Some(Kind::Integer) | Some(Kind::Float) | Some(Kind::Bool) | Some(Kind::Char)
+            | Some(Kind::String) => ExprKind::Literal(self.literal()?),
+            Some(Kind::LeftParen) => {
+                self.next();
+                if self.eat(Kind::RightParen).is_some() {
+                    ExprKind::Literal(Literal::Unit)
+                } else {
+                    let expr = self.expr()?;
+                    self.expect(Kind::RightParen)?;
+                    return Ok(expr);
+                }
+            }
+            Some(Kind::LeftCurly) => self.tuple()?,
+            Some(Kind::Identifier) => ExprKind::Path(self.path()?),
+            Some(Kind::This) => {
+                self.next();
+                ExprKind::This
+            }
+            Some(Kind::If) => ExprKind::If(self.if_()?),
+            Some(Kind::Match) => ExprKind::Match(self.match_()?),
+            Some(Kind::While) => {
+                self.next();
+                let cond = if self.at(Kind::LeftCurly) {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                let block = self.block()?;
+                ExprKind::While(While { cond, block })
+            }
+            Some(Kind::Return) => {
+                self.next();
+                let val = match self.peek_kind() {
+                    None | Some(Kind::Semicolon) | Some(Kind::RightCurly) | Some(Kind::Comma) => {
+                        None
+                    }
+                    _ => Some(self.expr()?),
+                };
+                ExprKind::Return(val)
+            }
+            Some(Kind::Break) => {
+                self.next();
+                ExprKind::Break
+            }
+            Some(Kind::Continue) => {
+                self.next();
+                ExprKind::Continue
+            }
+            _ => return Err(self.unexpected("expression")),
+        };
+
+        Ok(self.intern(start, kind))
+    }
+
+    /// Parses a path like `foo` or `Complex:First`.
+    pub(super) fn path(&mut self) -> ParseResult<Path> {
+        let mut segments = vec![self.ident()?];
+        while self.at(Kind::Colon) && self.peek_nth_kind(1) == Some(Kind::Identifier) {
+            self.next();
+            segments.push(self.ident()?);
+        }
+        Ok(Path { segments })
+    }
+
+    /// Parses a named tuple (`{a: 1, b: 2}`) or an unnamed tuple (`{1, 2}`).
+    fn tuple(&mut self) -> ParseResult<ExprKind> {
+        let named = self.peek_nth_kind(1) == Some(Kind::Identifier)
+            && self.peek_nth_kind(2) == Some(Kind::Colon);
+
+        if named {
+            let values = self.delimited(Kind::LeftCurly, Kind::RightCurly, |this| {
+                let name = this.ident()?;
+                this.expect(Kind::Colon)?;
+                Ok((name, this.expr()?))
+            })?;
+            Ok(ExprKind::NamedTuple(NamedTuple { values }))
+        } else {
+            let values = self.delimited(Kind::LeftCurly, Kind::RightCurly, Self::expr)?;
+            Ok(ExprKind::Tuple(Tuple { values }))
+        }
+    }
+
+    fn if_(&mut self) -> ParseResult<If> {
+        self.expect(Kind::If)?;
+        let cond = self.expr()?;
+        let then = self.block()?;
+
+        let mut arms = vec![];
+        let mut else_ = None;
+        while self.eat(Kind::Else).is_some() {
+            if self.eat(Kind::If).is_some() {
+                let cond = self.expr()?;
+                let action = self.block()?;
+                arms.push(IfArm { cond, action });
+            } else {
+                else_ = Some(self.block()?);
+                break;
+            }
+        }
+
+        Ok(If {
+            cond,
+            then,
+            arms,
+            else_,
+        })
+    }
+
+    fn match_(&mut self) -> ParseResult<Match> {
+        self.expect(Kind::Match)?;
+        let val = self.expr()?;
+        self.expect(Kind::LeftCurly)?;
+
+        let mut arms = vec![];
+        while !self.at(Kind::RightCurly) {
+            let pattern = self.pattern()?;
+            self.expect(Kind::ThinArrow)?;
+
+            let body = if self.at(Kind::LeftCurly) {
+                let start = self.current_span();
+                let block = self.block()?;
+                self.intern(start, ExprKind::Block(block))
+            } else {
+                self.expr()?
+            };
+            let is_block = matches!(self.db.lookup_intern_expr(body).kind, ExprKind::Block(_));
+            arms.push(MatchArm { pattern, body });
+
+            if self.eat(Kind::Comma).is_none() && !is_block {
+                break;
+            }
+        }
+        self.expect(Kind::RightCurly)?;
+
+        Ok(Match { val, arms })
+    }
+
+    pub(super) fn literal(&mut self) -> ParseResult<Literal> {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return Err(self.unexpected("literal")),
+        };
+        let text = self.text(token.span);
+
+        let lit = match token.kind {
+            Kind::Integer => {
+                let val = parse_int(text).map_err(|err| self.error(err, token.span))?;
+                Literal::Int(Int { signed: false, val })
+            }
+            Kind::Float => {
+                let val = text
+                    .replace('_', "")
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|val| NotNan::new(val).ok())
+                    .ok_or_else(|| self.error(SyntaxError::InvalidFloat, token.span))?;
+                Literal::Float(Float { val })
+            }
+            Kind::Bool => Literal::Bool(text == "true"),
+            Kind::Char => {
+                let val = unescape(&text[1..text.len() - 1])
+                    .map_err(|err| self.error(err, token.span))?;
+                let mut chars = val.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Literal::Char(c),
+                    _ => return Err(self.error(SyntaxError::InvalidChar, token.span)),
+                }
+            }
+            Kind::String => {
+                let val = unescape(&text[1..text.len() - 1])
+                    .map_err(|err| self.error(err, token.span))?;
+                Literal::String(val)
+            }
+            _ => return Err(self.unexpected("literal")),
+        };
+
+        self.next();
+        Ok(lit)
+    }
+
+    /// Interns an expression that started at `start` and ends
+    /// at the last consumed token.
+    fn intern(&mut self, start: Span, kind: ExprKind) -> ExprId {
+        let span = self.span_from(start);
+        self.db.intern_expr(Expr { span, kind })
+    }
+}
+
+/// Returns the precedence that is one level higher than `prec`.
+fn next_precedence(prec: Precedence) -> Option<Precedence> {
+    use Precedence::*;
+
+    let next = match prec {
+        Logical => Equality,
+        Equality => Comparison,
+        Comparison => Bitwise,
+        Bitwise => Shifting,
+        Shifting => Addition,
+        Addition => Multiplication,
+        Multiplication => Exponents,
+        Exponents => return None,
+    };
+    Some(next)
+}
+
+fn text_size(size: usize) -> cell_common::source::Index {
+    cell_common::source::Index::from(size as u32)
+}
+
+/// Parses an integer literal, which can be a decimal, hex or binary number.
+fn parse_int(text: &str) -> Result<u128, SyntaxError> {
+    let text = text.replace('_', "");
+    let (digits, radix) = if let Some(hex) = text.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(bin) = text.strip_prefix("0b") {
+        (bin, 2)
+    } else {
+        (text.as_str(), 10)
+    };
+
+    u128::from_str_radix(digits, radix).map_err(|err| {
+        use std::num::IntErrorKind;
+        match err.kind() {
+            IntErrorKind::PosOverflow => SyntaxError::IntTooLarge,
+            _ => SyntaxError::InvalidInt,
+        }
+    })
+}
+
+/// Resolves all escape sequences inside a string or char literal.
+fn unescape(text: &str) -> Result<String, SyntaxError> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next().ok_or(SyntaxError::InvalidEscape)? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '\'' => '\'',
+            '"' => '"',
+            'x' => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+                u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|byte| byte.is_ascii())
+                    .ok_or(SyntaxError::InvalidEscape)? as char
+            }
+            // `\u{e9}`, with one to six hex digits of a unicode scalar value
+            'u' => {
+                if chars.next() != Some('{') {
+                    return Err(SyntaxError::InvalidEscape);
+                }
+                let hex = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                Some(hex.as_str())
+                    .filter(|hex| (1..=6).contains(&hex.len()))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(std::char::from_u32)
+                    .ok_or(SyntaxError::InvalidEscape)?
+            }
+            _ => return Err(SyntaxError::InvalidEscape),
+        };
+        out.push(escaped);
+    }
+    Ok(out)
+}
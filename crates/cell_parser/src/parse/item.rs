@@ -0,0 +1,312 @@
+//! Recursive decent parsing of items.
+
+use super::Parser;
+use crate::token::Kind;
+use cell_common::error::{ParseResult, SyntaxError};
+use cell_syntax::ast::{
+    Alias, Block, Def, FieldDef, Global, Impl, Import, Item, ItemId, ItemKind, Repr, Trait, Type,
+    TypeDef, Union, Use, Variant, Visibility,
+};
+
+impl Parser<'_> {
+    /// Parses a single item and interns it.
+    pub(super) fn item(&mut self) -> ParseResult<ItemId> {
+        let start = self.current_span();
+        let repr = self.repr()?;
+        let vis = self.visibility();
+        let kind = match self.peek_kind() {
+            Some(Kind::Type) if self.peek_nth_kind(1) != Some(Kind::Alias) => {
+                ItemKind::TypeDef(self.type_def(repr)?)
+            }
+            Some(Kind::Union) => ItemKind::Union(self.union(repr)?),
+            _ if repr.is_some() => {
+                let span = self.span_from(start);
+                return Err(self.error(SyntaxError::MisplacedRepr, span));
+            }
+            Some(Kind::Def) => ItemKind::Def(self.def(false)?),
+            Some(Kind::Type) => ItemKind::Alias(self.alias()?),
+            Some(Kind::Trait) => ItemKind::Trait(self.trait_()?),
+            Some(Kind::Impl) => ItemKind::Impl(self.impl_()?),
+            Some(Kind::Const) => ItemKind::Const(self.global(Kind::Const)?),
+            Some(Kind::Static) => ItemKind::Static(self.global(Kind::Static)?),
+            Some(Kind::Import) => ItemKind::Import(self.import()?),
+            Some(Kind::Use) => ItemKind::Use(self.use_()?),
+            _ => return Err(self.unexpected("item")),
+        };
+
+        Ok(self.db.intern_item(Item {
+            span: self.span_from(start),
+            file: self.file,
+            vis,
+            kind,
+        }))
+    }
+
+    /// Parses the attributes in front of an item, of which
+    /// only a single `@repr(...)` is allowed.
+    fn repr(&mut self) -> ParseResult<Option<Repr>> {
+        let mut repr = None;
+        while self.peek_kind() == Some(Kind::At) {
+            let start = self.current_span();
+            let attr = self.attribute()?;
+            if repr.is_some() {
+                let span = self.span_from(start);
+                return Err(self.error(SyntaxError::DuplicateRepr, span));
+            }
+            repr = Some(attr);
+        }
+        Ok(repr)
+    }
+
+    /// Parses a single `@repr(...)` attribute.
+    fn attribute(&mut self) -> ParseResult<Repr> {
+        self.expect(Kind::At)?;
+        let attr = self.ident()?;
+        if self.db.rodeo().resolve(&attr.id) != "repr" {
+            return Err(self.error(SyntaxError::UnknownAttribute, attr.span));
+        }
+        self.expect(Kind::LeftParen)?;
+        let name = self.ident()?;
+        let repr = match self.db.rodeo().resolve(&name.id) {
+            "C" => Repr::C,
+            "packed" => Repr::Packed,
+            other => {
+                let err = SyntaxError::UnknownRepr {
+                    name: other.to_string(),
+                };
+                return Err(self.error(err, name.span));
+            }
+        };
+        self.expect(Kind::RightParen)?;
+        Ok(repr)
+    }
+
+    /// Parses an optional `pub` keyword.
+    fn visibility(&mut self) -> Visibility {
+        match self.eat(Kind::Pub) {
+            Some(_) => Visibility::Public,
+            None => Visibility::Private,
+        }
+    }
+
+    /// Parses a function definition.
+    ///
+    /// If `allow_decl` is `true`, the body can be replaced by a `;`,
+    /// which is used for methods inside a trait.
+    fn def(&mut self, allow_decl: bool) -> ParseResult<Def> {
+        self.expect(Kind::Def)?;
+        let name = self.ident()?;
+        let generics = self.generics()?;
+        let args = self.delimited(Kind::LeftParen, Kind::RightParen, |this| {
+            let name = this.ident()?;
+            this.expect(Kind::Colon)?;
+            Ok((name, this.ty()?))
+        })?;
+
+        let return_ty = if self.eat(Kind::Colon).is_some() {
+            self.ty()?
+        } else {
+            self.db.intern_type(Type::Unit)
+        };
+
+        let body = if allow_decl && self.eat(Kind::Semicolon).is_some() {
+            None
+        } else {
+            Some(self.block()?)
+        };
+
+        Ok(Def {
+            name,
+            generics,
+            args,
+            return_ty,
+            body,
+        })
+    }
+
+    /// Parses a `def` and interns it as a separate item.
+    fn method(&mut self, allow_decl: bool) -> ParseResult<ItemId> {
+        let start = self.current_span();
+        let vis = self.visibility();
+        let def = self.def(allow_decl)?;
+        Ok(self.db.intern_item(Item {
+            span: self.span_from(start),
+            file: self.file,
+            vis,
+            kind: ItemKind::Def(def),
+        }))
+    }
+
+    /// Parses a list of methods surrounded by curly braces.
+    fn methods(&mut self, allow_decl: bool) -> ParseResult<Vec<ItemId>> {
+        self.expect(Kind::LeftCurly)?;
+        let mut methods = vec![];
+        while !self.at(Kind::RightCurly) && self.peek().is_some() {
+            methods.push(self.method(allow_decl)?);
+        }
+        self.expect(Kind::RightCurly)?;
+        Ok(methods)
+    }
+
+    fn alias(&mut self) -> ParseResult<Alias> {
+        self.expect(Kind::Type)?;
+        self.expect(Kind::Alias)?;
+        let name = self.ident()?;
+        let generics = self.generics()?;
+        self.expect(Kind::Equal)?;
+        let ty = self.ty()?;
+        self.expect(Kind::Semicolon)?;
+        Ok(Alias { name, generics, ty })
+    }
+
+    fn import(&mut self) -> ParseResult<Import> {
+        self.expect(Kind::Import)?;
+        let name = self.ident()?;
+        self.expect(Kind::Semicolon)?;
+        Ok(Import { name })
+    }
+
+    fn use_(&mut self) -> ParseResult<Use> {
+        self.expect(Kind::Use)?;
+        let path = self.path()?;
+        self.expect(Kind::Semicolon)?;
+        Ok(Use { path })
+    }
+
+    /// Parses a `const` or `static` item, depending on `keyword`.
+    fn global(&mut self, keyword: Kind) -> ParseResult<Global> {
+        self.expect(keyword)?;
+        let name = self.ident()?;
+        self.expect(Kind::Colon)?;
+        let ty = self.ty()?;
+        self.expect(Kind::Equal)?;
+        let val = self.expr()?;
+        self.expect(Kind::Semicolon)?;
+        Ok(Global { name, ty, val })
+    }
+
+    fn type_def(&mut self, repr: Option<Repr>) -> ParseResult<TypeDef> {
+        self.expect(Kind::Type)?;
+        let name = self.ident()?;
+        let generics = self.generics()?;
+        let fields = self.delimited(Kind::LeftCurly, Kind::RightCurly, |this| {
+            let vis = this.visibility();
+            let name = this.ident()?;
+            this.expect(Kind::Colon)?;
+            let ty = this.ty()?;
+            Ok(FieldDef { vis, name, ty })
+        })?;
+        Ok(TypeDef {
+            name,
+            generics,
+            fields,
+            repr: repr.unwrap_or(Repr::Default),
+        })
+    }
+
+    fn union(&mut self, repr: Option<Repr>) -> ParseResult<Union> {
+        self.expect(Kind::Union)?;
+        let name = self.ident()?;
+        let generics = self.generics()?;
+        let variants = self.delimited(Kind::LeftCurly, Kind::RightCurly, |this| {
+            let vis = this.visibility();
+            let name = this.ident()?;
+            let data = if this.eat(Kind::Equal).is_some() {
+                Some(this.ty()?)
+            } else {
+                None
+            };
+            Ok(Variant { vis, name, data })
+        })?;
+        Ok(Union {
+            name,
+            generics,
+            variants,
+            repr: repr.unwrap_or(Repr::Default),
+        })
+    }
+
+    fn trait_(&mut self) -> ParseResult<Trait> {
+        self.expect(Kind::Trait)?;
+        let name = self.ident()?;
+        self.eat(Kind::Equal);
+        let methods = self.methods(true)?;
+        Ok(Trait { name, methods })
+    }
+
+    fn impl_(&mut self) -> ParseResult<Impl> {
+        self.expect(Kind::Impl)?;
+        let generics = self.generics()?;
+        let ty = self.ty()?;
+        let trait_ = if self.eat(Kind::Colon).is_some() {
+            Some(self.ident()?)
+        } else {
+            None
+        };
+        let methods = self.methods(false)?;
+        Ok(Impl {
+            generics,
+            ty,
+            trait_,
+            methods,
+        })
+    }
+
+    /// Parses a block of statements surrounded by curly braces.
+    ///
+    /// A statement that fails to parse is reported, and parsing continues
+    /// after the next `;`, so one typo doesn't hide the rest of the block.
+    pub(super) fn block(&mut self) -> ParseResult<Block> {
+        let start = self.expect(Kind::LeftCurly)?.span;
+        let mut stmts = vec![];
+        while !self.at(Kind::RightCurly) {
+            // a block that is not closed yet ends before the next item,
+            // so the item is not swallowed by the block
+            if self.peek_kind().is_none_or(Kind::starts_item) {
+                let err = self.unexpected("`}`");
+                self.errors.error(err.map(Into::into));
+                return Ok(Block {
+                    span: self.span_from(start),
+                    stmts,
+                });
+            }
+
+            let pos = self.pos;
+            match self.stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(err) => {
+                    self.errors.error(err.map(Into::into));
+                    if self.pos == pos {
+                        self.next();
+                    }
+                    self.recover_stmt();
+                }
+            }
+        }
+        self.expect(Kind::RightCurly)?;
+
+        Ok(Block {
+            span: self.span_from(start),
+            stmts,
+        })
+    }
+
+    /// Skips tokens until the end of the current statement.
+    pub(super) fn recover_stmt(&mut self) {
+        let mut depth = 0usize;
+        while let Some(kind) = self.peek_kind() {
+            match kind {
+                Kind::Semicolon if depth == 0 => {
+                    self.next();
+                    break;
+                }
+                Kind::RightCurly if depth == 0 => break,
+                Kind::LeftCurly => depth += 1,
+                Kind::RightCurly => depth -= 1,
+                kind if depth == 0 && kind.starts_item() => break,
+                _ => {}
+            }
+            self.next();
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Parsing of the patterns inside `match` arms.
+
+use super::Parser;
+use crate::token::Kind;
+use cell_common::error::{ParseResult, SyntaxError};
+use cell_syntax::ast::{Float, Identifier, Int, Literal, Pattern, PatternId, PatternKind};
+
+impl Parser<'_> {
+    /// Parses a pattern and interns it.
+    pub(super) fn pattern(&mut self) -> ParseResult<PatternId> {
+        let start = self.current_span();
+        let kind = match self.peek_kind() {
+            Some(Kind::Else) => {
+                self.next();
+                PatternKind::Wildcard
+            }
+            Some(Kind::Identifier) => match self.peek_nth_kind(1) {
+                Some(Kind::Colon) => {
+                    let path = self.path()?;
+                    let data = match self.eat(Kind::Equal) {
+                        Some(_) => Some(self.pattern()?),
+                        None => None,
+                    };
+                    PatternKind::Variant { path, data }
+                }
+                Some(Kind::LeftCurly) => {
+                    let name = self.ident()?;
+                    let fields = self.field_patterns()?;
+                    PatternKind::Struct { name, fields }
+                }
+                _ => PatternKind::Binding(self.ident()?),
+            },
+            Some(Kind::LeftCurly) => {
+                let named = self.peek_nth_kind(1) == Some(Kind::Identifier)
+                    && self.peek_nth_kind(2) == Some(Kind::Colon);
+
+                if named {
+                    PatternKind::NamedTuple(self.field_patterns()?)
+                } else {
+                    let pats = self.delimited(Kind::LeftCurly, Kind::RightCurly, Self::pattern)?;
+                    PatternKind::Tuple(pats)
+                }
+            }
+            _ => {
+                let start = self.signed_literal()?;
+                if self.eat(Kind::DotDot).is_some() {
+                    let end = self.signed_literal()?;
+                    PatternKind::Range { start, end }
+                } else {
+                    PatternKind::Literal(start)
+                }
+            }
+        };
+
+        let span = self.span_from(start);
+        Ok(self.db.intern_pattern(Pattern { span, kind }))
+    }
+
+    /// Parses the fields of a struct or named tuple pattern, like `{a, b: pattern}`.
+    fn field_patterns(&mut self) -> ParseResult<Vec<(Identifier, PatternId)>> {
+        self.delimited(Kind::LeftCurly, Kind::RightCurly, |this| {
+            let name = this.ident()?;
+            let pattern = if this.eat(Kind::Colon).is_some() {
+                this.pattern()?
+            } else {
+                this.db.intern_pattern(Pattern {
+                    span: name.span,
+                    kind: PatternKind::Binding(name.clone()),
+                })
+            };
+            Ok((name, pattern))
+        })
+    }
+
+    /// Parses a literal that may be prefixed by a `-`.
+    fn signed_literal(&mut self) -> ParseResult<Literal> {
+        let minus = match self.eat(Kind::Minus) {
+            Some(minus) => minus.span,
+            None => return self.literal(),
+        };
+
+        match self.literal()? {
+            Literal::Int(Int { val, .. }) => Ok(Literal::Int(Int { signed: true, val })),
+            Literal::Float(Float { val }) => Ok(Literal::Float(Float { val: -val })),
+            _ => Err(self.error(
+                SyntaxError::UnexpectedToken {
+                    expected: "number".to_string(),
+                    found: "`-`".to_string(),
+                },
+                minus,
+            )),
+        }
+    }
+}
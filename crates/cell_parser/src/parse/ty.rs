@@ -0,0 +1,85 @@
+//! Parsing of types.
+
+use super::Parser;
+use crate::token::Kind;
+use cell_common::error::ParseResult;
+use cell_syntax::ast::{Type, TypeId};
+
+impl Parser<'_> {
+    /// Parses a type and interns it.
+    pub(super) fn ty(&mut self) -> ParseResult<TypeId> {
+        let ty = match self.peek_kind() {
+            Some(Kind::Star) => {
+                self.next();
+                Type::Pointer(self.ty()?)
+            }
+            Some(Kind::LeftParen) => {
+                self.next();
+                self.expect(Kind::RightParen)?;
+                Type::Unit
+            }
+            Some(Kind::LeftCurly) if self.peek_nth_kind(2) == Some(Kind::Colon) => {
+                let fields = self.delimited(Kind::LeftCurly, Kind::RightCurly, |this| {
+                    let name = this.ident()?;
+                    this.expect(Kind::Colon)?;
+                    Ok((name, this.ty()?))
+                })?;
+                Type::NamedTuple(fields)
+            }
+            Some(Kind::LeftCurly) => {
+                let types = self.delimited(Kind::LeftCurly, Kind::RightCurly, Self::ty)?;
+                Type::Tuple(types)
+            }
+            Some(Kind::Identifier) => {
+                let name = self.ident()?;
+                let text = self.text(name.span);
+                match primitive(text) {
+                    Some(ty) => ty,
+                    None => {
+                        let generics = if self.at(Kind::LeftBracket) {
+                            self.delimited(Kind::LeftBracket, Kind::RightBracket, Self::ty)?
+                        } else {
+                            vec![]
+                        };
+                        Type::Named { name, generics }
+                    }
+                }
+            }
+            _ => return Err(self.unexpected("type")),
+        };
+
+        Ok(self.db.intern_type(ty))
+    }
+}
+
+/// Returns the builtin type that has the given name.
+fn primitive(name: &str) -> Option<Type> {
+    let ty = match name {
+        "bool" => Type::Bool,
+        "char" => Type::Char,
+        "String" => Type::String,
+        "f32" => Type::Float { width: 32 },
+        "f64" => Type::Float { width: 64 },
+        "usz" => Type::Int {
+            width: 64,
+            signed: false,
+        },
+        "isz" => Type::Int {
+            width: 64,
+            signed: true,
+        },
+        _ => {
+            let signed = match name.chars().next()? {
+                'i' => true,
+                'u' => false,
+                _ => return None,
+            };
+            let width = name[1..].parse::<u16>().ok()?;
+            if !matches!(width, 8 | 16 | 32 | 64 | 128) {
+                return None;
+            }
+            Type::Int { width, signed }
+        }
+    };
+    Some(ty)
+}
@@ -0,0 +1,706 @@
+//! Printing of the syntax tree back into source code.
+//!
+//! The printed code parses into the same syntax tree, except for spans.
+//! Parentheses are only added where the precedence of an operator requires them.
+//!
+//! ```text
+//! let expr = db.lookup_intern_expr(id);
+//! println!("{}", expr.display(db));
+//! ```
+
+use crate::db::ParseDatabase;
+use cell_syntax::ast::{
+    BinOp, Block, CmpOp, Expr, ExprId, ExprKind, FieldName, Generic, Identifier, Item, ItemId,
+    ItemKind, Literal, Pattern, PatternId, PatternKind, Repr, Stmt, StmtId, StmtKind, Type, TypeId,
+    Visibility,
+};
+use std::fmt::{self, Write};
+
+/// A node of the syntax tree that can be printed as source code.
+pub trait Print {
+    fn print(&self, p: &mut Printer<'_>);
+
+    /// Returns a value that displays this node as source code.
+    fn display<'a>(&'a self, db: &'a dyn ParseDatabase) -> Display<'a, Self> {
+        Display { db, node: self }
+    }
+}
+
+/// Displays a node of the syntax tree as source code.
+pub struct Display<'a, T: ?Sized> {
+    db: &'a dyn ParseDatabase,
+    node: &'a T,
+}
+
+impl<T: Print + ?Sized> fmt::Display for Display<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut p = Printer {
+            db: self.db,
+            out: String::new(),
+            indent: 0,
+        };
+        self.node.print(&mut p);
+        f.write_str(&p.out)
+    }
+}
+
+impl Print for Item {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.item(self)
+    }
+}
+
+impl Print for ItemId {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.item(&p.db.lookup_intern_item(*self))
+    }
+}
+
+impl Print for Stmt {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.stmt(self)
+    }
+}
+
+impl Print for StmtId {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.stmt(&p.db.lookup_intern_stmt(*self))
+    }
+}
+
+impl Print for Expr {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.expr_kind(&self.kind)
+    }
+}
+
+impl Print for ExprId {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.expr(*self, Prec::Assign)
+    }
+}
+
+impl Print for Type {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.ty(self)
+    }
+}
+
+impl Print for TypeId {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.ty(&p.db.lookup_intern_type(*self))
+    }
+}
+
+impl Print for Pattern {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.pattern(self)
+    }
+}
+
+impl Print for PatternId {
+    fn print(&self, p: &mut Printer<'_>) {
+        p.pattern(&p.db.lookup_intern_pattern(*self))
+    }
+}
+
+/// The precedence of an expression, from the loosest to the tightest binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    /// Assignments and `return` with a value, which take everything to their right.
+    Assign,
+    Logical,
+    Equality,
+    Comparison,
+    Bitwise,
+    Shifting,
+    Addition,
+    Multiplication,
+    Exponents,
+    Unary,
+    /// Calls, field accesses and every expression that is never split by an operator.
+    Postfix,
+}
+
+impl Prec {
+    fn of(kind: &ExprKind) -> Prec {
+        match kind {
+            ExprKind::Assign(_) | ExprKind::Return(Some(_)) => Prec::Assign,
+            ExprKind::Binary(binary) => Prec::binary(binary.op.data()),
+            ExprKind::Comparison(cmp) => match cmp.op.data() {
+                CmpOp::Equal | CmpOp::NotEqual => Prec::Equality,
+                _ => Prec::Comparison,
+            },
+            ExprKind::Unary(_) => Prec::Unary,
+            _ => Prec::Postfix,
+        }
+    }
+
+    fn binary(op: &BinOp) -> Prec {
+        match op {
+            BinOp::LogicalAnd | BinOp::LogicalOr => Prec::Logical,
+            BinOp::BitiwseAnd | BinOp::BitwiseOr | BinOp::BitwiseXor => Prec::Bitwise,
+            BinOp::LeftShift | BinOp::RightShift => Prec::Shifting,
+            BinOp::Add | BinOp::Sub => Prec::Addition,
+            BinOp::Mul | BinOp::Div | BinOp::Mod => Prec::Multiplication,
+            BinOp::Pow => Prec::Exponents,
+        }
+    }
+
+    /// Returns the precedence that is one level higher.
+    fn next(self) -> Prec {
+        match self {
+            Prec::Assign => Prec::Logical,
+            Prec::Logical => Prec::Equality,
+            Prec::Equality => Prec::Comparison,
+            Prec::Comparison => Prec::Bitwise,
+            Prec::Bitwise => Prec::Shifting,
+            Prec::Shifting => Prec::Addition,
+            Prec::Addition => Prec::Multiplication,
+            Prec::Multiplication => Prec::Exponents,
+            Prec::Exponents => Prec::Unary,
+            Prec::Unary | Prec::Postfix => Prec::Postfix,
+        }
+    }
+}
+
+/// Prints the syntax tree into a string.
+pub struct Printer<'db> {
+    db: &'db dyn ParseDatabase,
+    out: String,
+    indent: usize,
+}
+
+impl Printer<'_> {
+    fn name(&mut self, name: &Identifier) {
+        self.out.push_str(self.db.rodeo().resolve(&name.id));
+    }
+
+    /// Starts a new line at the current indentation.
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    /// Prints the elements separated by `, `.
+    fn list<T>(&mut self, elems: &[T], mut f: impl FnMut(&mut Self, &T)) {
+        for (idx, elem) in elems.iter().enumerate() {
+            if idx > 0 {
+                self.out.push_str(", ");
+            }
+            f(self, elem);
+        }
+    }
+
+    /// Prints every element on its own line between curly braces.
+    fn lines<T>(&mut self, elems: &[T], mut f: impl FnMut(&mut Self, &T)) {
+        self.out.push('{');
+        if elems.is_empty() {
+            self.out.push('}');
+            return;
+        }
+
+        self.indent += 1;
+        for elem in elems {
+            self.newline();
+            f(self, elem);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    fn item(&mut self, item: &Item) {
+        let repr = match &item.kind {
+            ItemKind::TypeDef(def) => def.repr,
+            ItemKind::Union(union) => union.repr,
+            _ => Repr::Default,
+        };
+        match repr {
+            Repr::Default => {}
+            Repr::C => {
+                self.out.push_str("@repr(C)");
+                self.newline();
+            }
+            Repr::Packed => {
+                self.out.push_str("@repr(packed)");
+                self.newline();
+            }
+        }
+        self.visibility(item.vis);
+
+        match &item.kind {
+            ItemKind::TypeDef(def) => {
+                self.out.push_str("type ");
+                self.name(&def.name);
+                self.generics(&def.generics);
+                self.out.push(' ');
+                self.lines(&def.fields, |this, field| {
+                    this.visibility(field.vis);
+                    this.name(&field.name);
+                    this.out.push_str(": ");
+                    this.type_id(field.ty);
+                    this.out.push(',');
+                });
+            }
+            ItemKind::Def(def) => {
+                self.out.push_str("def ");
+                self.name(&def.name);
+                self.generics(&def.generics);
+                self.out.push('(');
+                self.list(&def.args, |this, (name, ty)| {
+                    this.name(name);
+                    this.out.push_str(": ");
+                    this.type_id(*ty);
+                });
+                self.out.push(')');
+
+                let return_ty = self.db.lookup_intern_type(def.return_ty);
+                if return_ty != Type::Unit {
+                    self.out.push_str(": ");
+                    self.ty(&return_ty);
+                }
+                match &def.body {
+                    Some(body) => {
+                        self.out.push(' ');
+                        self.block(body);
+                    }
+                    None => self.out.push(';'),
+                }
+            }
+            ItemKind::Alias(alias) => {
+                self.out.push_str("type alias ");
+                self.name(&alias.name);
+                self.generics(&alias.generics);
+                self.out.push_str(" = ");
+                self.type_id(alias.ty);
+                self.out.push(';');
+            }
+            ItemKind::Union(union) => {
+                self.out.push_str("union ");
+                self.name(&union.name);
+                self.generics(&union.generics);
+                self.out.push(' ');
+                self.lines(&union.variants, |this, variant| {
+                    this.visibility(variant.vis);
+                    this.name(&variant.name);
+                    if let Some(ty) = variant.data {
+                        this.out.push_str(" = ");
+                        this.type_id(ty);
+                    }
+                    this.out.push(',');
+                });
+            }
+            ItemKind::Trait(trait_) => {
+                self.out.push_str("trait ");
+                self.name(&trait_.name);
+                self.out.push_str(" = ");
+                self.methods(&trait_.methods);
+            }
+            ItemKind::Impl(impl_) => {
+                self.out.push_str("impl");
+                self.generics(&impl_.generics);
+                self.out.push(' ');
+                self.type_id(impl_.ty);
+                if let Some(trait_) = &impl_.trait_ {
+                    self.out.push_str(": ");
+                    self.name(trait_);
+                }
+                self.out.push(' ');
+                self.methods(&impl_.methods);
+            }
+            ItemKind::Const(global) | ItemKind::Static(global) => {
+                let keyword = match item.kind {
+                    ItemKind::Const(_) => "const ",
+                    _ => "static ",
+                };
+                self.out.push_str(keyword);
+                self.name(&global.name);
+                self.out.push_str(": ");
+                self.type_id(global.ty);
+                self.out.push_str(" = ");
+                self.expr(global.val, Prec::Assign);
+                self.out.push(';');
+            }
+            ItemKind::Import(import) => {
+                self.out.push_str("import ");
+                self.name(&import.name);
+                self.out.push(';');
+            }
+            ItemKind::Use(use_) => {
+                self.out.push_str("use ");
+                self.path(&use_.path.segments);
+                self.out.push(';');
+            }
+        }
+    }
+
+    fn visibility(&mut self, vis: Visibility) {
+        if vis.is_public() {
+            self.out.push_str("pub ");
+        }
+    }
+
+    fn generics(&mut self, generics: &[Generic]) {
+        if generics.is_empty() {
+            return;
+        }
+
+        self.out.push('[');
+        self.list(generics, |this, generic| {
+            this.name(&generic.name);
+            for (idx, bound) in generic.bounds.iter().enumerate() {
+                this.out.push_str(if idx == 0 { ": " } else { " + " });
+                this.name(bound);
+            }
+        });
+        self.out.push(']');
+    }
+
+    fn methods(&mut self, methods: &[ItemId]) {
+        self.lines(methods, |this, &method| {
+            let method = this.db.lookup_intern_item(method);
+            this.item(&method);
+        });
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.lines(&block.stmts, |this, &stmt| {
+            let stmt = this.db.lookup_intern_stmt(stmt);
+            this.stmt(&stmt);
+        });
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Var(var) => {
+                self.out.push_str("var ");
+                self.name(&var.name);
+                if let Some(ty) = &var.ty {
+                    self.out.push_str(": ");
+                    self.ty(ty);
+                }
+                self.out.push_str(" = ");
+                self.expr(var.val, Prec::Assign);
+                self.out.push(';');
+            }
+            StmtKind::Expr(expr) => {
+                let kind = self.db.lookup_intern_expr(*expr).kind;
+                self.expr_kind(&kind);
+                let block_like = matches!(
+                    kind,
+                    ExprKind::If(_) | ExprKind::Match(_) | ExprKind::While(_) | ExprKind::Block(_)
+                );
+                if !block_like {
+                    self.out.push(';');
+                }
+            }
+        }
+    }
+
+    /// Prints the expression, surrounded by parentheses if its
+    /// precedence is lower than `min`.
+    fn expr(&mut self, id: ExprId, min: Prec) {
+        let kind = self.db.lookup_intern_expr(id).kind;
+        if Prec::of(&kind) < min {
+            self.out.push('(');
+            self.expr_kind(&kind);
+            self.out.push(')');
+        } else {
+            self.expr_kind(&kind);
+        }
+    }
+
+    fn expr_kind(&mut self, kind: &ExprKind) {
+        match kind {
+            ExprKind::Literal(lit) => self.literal(lit),
+            ExprKind::Binary(binary) => {
+                let prec = Prec::binary(binary.op.data());
+                // `**` is the only right associative operator
+                let (left, right) = if prec == Prec::Exponents {
+                    (prec.next(), prec)
+                } else {
+                    (prec, prec.next())
+                };
+                self.expr(binary.left, left);
+                write!(self.out, " {} ", binary.op.data()).unwrap();
+                self.expr(binary.right, right);
+            }
+            ExprKind::Comparison(cmp) => {
+                let prec = Prec::of(kind);
+                self.expr(cmp.left, prec);
+                write!(self.out, " {} ", cmp.op.data()).unwrap();
+                self.expr(cmp.right, prec.next());
+            }
+            ExprKind::Unary(unary) => {
+                write!(self.out, "{}", unary.op.data()).unwrap();
+                // `& &a` would be lexed as `&&` without the space
+                let val = self.db.lookup_intern_expr(unary.val).kind;
+                if matches!(val, ExprKind::Unary(_)) {
+                    self.out.push(' ');
+                }
+                self.expr(unary.val, Prec::Unary);
+            }
+            ExprKind::Tuple(tuple) => {
+                self.out.push('{');
+                self.list(&tuple.values, |this, &val| this.expr(val, Prec::Assign));
+                self.out.push('}');
+            }
+            ExprKind::NamedTuple(tuple) => {
+                self.out.push('{');
+                self.list(&tuple.values, |this, (name, val)| {
+                    this.name(name);
+                    this.out.push_str(": ");
+                    this.expr(*val, Prec::Assign);
+                });
+                self.out.push('}');
+            }
+            ExprKind::If(if_) => {
+                self.out.push_str("if ");
+                self.expr(if_.cond, Prec::Assign);
+                self.out.push(' ');
+                self.block(&if_.then);
+                for arm in &if_.arms {
+                    self.out.push_str(" else if ");
+                    self.expr(arm.cond, Prec::Assign);
+                    self.out.push(' ');
+                    self.block(&arm.action);
+                }
+                if let Some(else_) = &if_.else_ {
+                    self.out.push_str(" else ");
+                    self.block(else_);
+                }
+            }
+            ExprKind::Match(match_) => {
+                self.out.push_str("match ");
+                self.expr(match_.val, Prec::Assign);
+                self.out.push(' ');
+                self.lines(&match_.arms, |this, arm| {
+                    this.pattern(&this.db.lookup_intern_pattern(arm.pattern));
+                    this.out.push_str(" -> ");
+                    this.expr(arm.body, Prec::Assign);
+                    this.out.push(',');
+                });
+            }
+            ExprKind::While(while_) => {
+                self.out.push_str("while ");
+                if let Some(cond) = while_.cond {
+                    // a condition that starts with `{` would be parsed as the body
+                    let kind = self.db.lookup_intern_expr(cond).kind;
+                    if matches!(kind, ExprKind::Tuple(_) | ExprKind::NamedTuple(_)) {
+                        self.out.push('(');
+                        self.expr_kind(&kind);
+                        self.out.push(')');
+                    } else {
+                        self.expr(cond, Prec::Assign);
+                    }
+                    self.out.push(' ');
+                }
+                self.block(&while_.block);
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Path(path) => self.path(&path.segments),
+            ExprKind::Call(call) => {
+                self.expr(call.callee, Prec::Postfix);
+                self.args(&call.args);
+            }
+            ExprKind::MethodCall(call) => {
+                self.expr(call.receiver, Prec::Postfix);
+                self.out.push('.');
+                self.name(&call.name);
+                self.args(&call.args);
+            }
+            ExprKind::Field(field) => {
+                self.expr(field.val, Prec::Postfix);
+                self.out.push('.');
+                match &field.field {
+                    FieldName::Named(name) => self.name(name),
+                    FieldName::Index(idx) => write!(self.out, "{}", idx.data()).unwrap(),
+                }
+            }
+            ExprKind::Assign(assign) => {
+                self.expr(assign.target, Prec::Logical);
+                match &assign.op {
+                    Some(op) => write!(self.out, " {}= ", op.data()).unwrap(),
+                    None => self.out.push_str(" = "),
+                }
+                self.expr(assign.val, Prec::Assign);
+            }
+            ExprKind::Return(val) => {
+                self.out.push_str("return");
+                if let Some(val) = val {
+                    self.out.push(' ');
+                    self.expr(*val, Prec::Assign);
+                }
+            }
+            ExprKind::Break => self.out.push_str("break"),
+            ExprKind::Continue => self.out.push_str("continue"),
+            ExprKind::This => self.out.push_str("this"),
+        }
+    }
+
+    fn args(&mut self, args: &[ExprId]) {
+        self.out.push('(');
+        self.list(args, |this, &arg| this.expr(arg, Prec::Assign));
+        self.out.push(')');
+    }
+
+    fn path(&mut self, segments: &[Identifier]) {
+        for (idx, segment) in segments.iter().enumerate() {
+            if idx > 0 {
+                self.out.push(':');
+            }
+            self.name(segment);
+        }
+    }
+
+    fn literal(&mut self, lit: &Literal) {
+        match lit {
+            Literal::Int(int) => {
+                if int.signed {
+                    self.out.push('-');
+                }
+                write!(self.out, "{}", int.val).unwrap();
+            }
+            // `Debug` always prints a `.` or an exponent, so the literal stays a float
+            Literal::Float(float) => write!(self.out, "{:?}", float.val.into_inner()).unwrap(),
+            Literal::Bool(val) => write!(self.out, "{}", val).unwrap(),
+            Literal::String(val) => {
+                self.out.push('"');
+                for c in val.chars() {
+                    self.escaped(c, '"');
+                }
+                self.out.push('"');
+            }
+            Literal::Char(c) => {
+                self.out.push('\'');
+                self.escaped(*c, '\'');
+                self.out.push('\'');
+            }
+            Literal::Unit => self.out.push_str("()"),
+        }
+    }
+
+    /// Prints a char of a string or char literal, which is delimited by `quote`.
+    fn escaped(&mut self, c: char, quote: char) {
+        match c {
+            '\n' => self.out.push_str("\\n"),
+            '\r' => self.out.push_str("\\r"),
+            '\t' => self.out.push_str("\\t"),
+            '\0' => self.out.push_str("\\0"),
+            '\\' => self.out.push_str("\\\\"),
+            '"' if quote == '"' => self.out.push_str("\\\""),
+            '\'' if quote == '\'' => self.out.push_str("\\'"),
+            c if c.is_ascii_control() => write!(self.out, "\\x{:02x}", c as u8).unwrap(),
+            c if c.is_control() => write!(self.out, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => self.out.push(c),
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Wildcard => self.out.push_str("else"),
+            PatternKind::Binding(name) => self.name(name),
+            PatternKind::Literal(lit) => self.literal(lit),
+            PatternKind::Range { start, end } => {
+                self.literal(start);
+                self.out.push_str("..");
+                self.literal(end);
+            }
+            PatternKind::Tuple(patterns) => {
+                self.out.push('{');
+                self.list(patterns, |this, &pattern| this.pattern_id(pattern));
+                self.out.push('}');
+            }
+            PatternKind::NamedTuple(fields) => {
+                self.out.push('{');
+                self.field_patterns(fields);
+                self.out.push('}');
+            }
+            PatternKind::Struct { name, fields } => {
+                self.name(name);
+                if fields.is_empty() {
+                    self.out.push_str(" {}");
+                } else {
+                    self.out.push_str(" { ");
+                    self.field_patterns(fields);
+                    self.out.push_str(" }");
+                }
+            }
+            PatternKind::Variant { path, data } => {
+                self.path(&path.segments);
+                if let Some(data) = data {
+                    self.out.push_str(" = ");
+                    self.pattern_id(*data);
+                }
+            }
+        }
+    }
+
+    fn pattern_id(&mut self, id: PatternId) {
+        let pattern = self.db.lookup_intern_pattern(id);
+        self.pattern(&pattern);
+    }
+
+    fn field_patterns(&mut self, fields: &[(Identifier, PatternId)]) {
+        self.list(fields, |this, (name, pattern)| {
+            this.name(name);
+            // `{a}` is short for `{a: a}`
+            let pattern = this.db.lookup_intern_pattern(*pattern);
+            if !matches!(&pattern.kind, PatternKind::Binding(binding) if binding.id == name.id) {
+                this.out.push_str(": ");
+                this.pattern(&pattern);
+            }
+        });
+    }
+
+    fn type_id(&mut self, id: TypeId) {
+        let ty = self.db.lookup_intern_type(id);
+        self.ty(&ty);
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Int { width, signed } => {
+                write!(self.out, "{}{}", if *signed { 'i' } else { 'u' }, width).unwrap()
+            }
+            Type::Float { width } => write!(self.out, "f{}", width).unwrap(),
+            Type::Bool => self.out.push_str("bool"),
+            Type::String => self.out.push_str("String"),
+            Type::Unit => self.out.push_str("()"),
+            Type::Char => self.out.push_str("char"),
+            Type::Pointer(inner) => {
+                self.out.push('*');
+                // `**T` would be lexed as the `**` operator
+                if matches!(self.db.lookup_intern_type(*inner), Type::Pointer(_)) {
+                    self.out.push(' ');
+                }
+                self.type_id(*inner);
+            }
+            Type::Tuple(types) => {
+                self.out.push('{');
+                self.list(types, |this, &ty| this.type_id(ty));
+                self.out.push('}');
+            }
+            Type::NamedTuple(fields) => {
+                self.out.push('{');
+                self.list(fields, |this, (name, ty)| {
+                    this.name(name);
+                    this.out.push_str(": ");
+                    this.type_id(*ty);
+                });
+                self.out.push('}');
+            }
+            Type::Named { name, generics } => {
+                self.name(name);
+                if !generics.is_empty() {
+                    self.out.push('[');
+                    self.list(generics, |this, &ty| this.type_id(ty));
+                    self.out.push(']');
+                }
+            }
+        }
+    }
+}
@@ -17,9 +17,9 @@ pub enum Kind {
     #[token("true")]
     #[token("false")]
     Bool,
-    #[regex(r"'[^']'")]
+    #[regex(r"'([^'\\\n]|[\u{80}-\u{10FFFF}]|\\[^\n][^'\\\n]*)'")]
     Char,
-    #[regex(r#"(\\.|[^\\"])*"#)]
+    #[regex(r#""(\\.|[^\\"])*""#)]
     String,
 
     #[token("var")]
@@ -42,6 +42,26 @@ pub enum Kind {
     Impl,
     #[token("alias")]
     Alias,
+    #[token("trait")]
+    Trait,
+    #[token("return")]
+    Return,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
+    #[token("this")]
+    This,
+    #[token("const")]
+    Const,
+    #[token("static")]
+    Static,
+    #[token("import")]
+    Import,
+    #[token("use")]
+    Use,
+    #[token("pub")]
+    Pub,
 
     #[token("&&")]
     Ampersand2,
@@ -87,6 +107,8 @@ pub enum Kind {
     GreaterThanEqual,
     #[token(".")]
     Dot,
+    #[token("..")]
+    DotDot,
     #[token(",")]
     Comma,
     #[token(":")]
@@ -107,11 +129,34 @@ pub enum Kind {
     RightBracket,
     #[token("->")]
     ThinArrow,
+    #[token("@")]
+    At,
+
+    #[token("&=")]
+    AmpersandEqual,
+    #[token("|=")]
+    PipeEqual,
+    #[token("^=")]
+    CaretEqual,
+    #[token("<<=")]
+    LeftShiftEqual,
+    #[token(">>=")]
+    RightShiftEqual,
+    #[token("+=")]
+    PlusEqual,
+    #[token("-=")]
+    MinusEqual,
+    #[token("*=")]
+    StarEqual,
+    #[token("/=")]
+    SlashEqual,
+    #[token("%=")]
+    PercentEqual,
 
     #[regex(r"//[^\n]*")]
     Comment,
     #[error]
-    #[regex(r"[ \t\n\f]+", logos::skip)]
+    #[regex(r"[ \t\r\n\f]+", logos::skip)]
     Error,
 }
 
@@ -134,6 +179,16 @@ impl fmt::Display for Kind {
             Kind::Union => "union",
             Kind::Impl => "impl",
             Kind::Alias => "alias",
+            Kind::Trait => "trait",
+            Kind::Return => "return",
+            Kind::Break => "break",
+            Kind::Continue => "continue",
+            Kind::This => "this",
+            Kind::Const => "const",
+            Kind::Static => "static",
+            Kind::Import => "import",
+            Kind::Use => "use",
+            Kind::Pub => "pub",
             Kind::Ampersand2 => "&&",
             Kind::Ampersand => "&",
             Kind::Pipe2 => "||",
@@ -156,6 +211,7 @@ impl fmt::Display for Kind {
             Kind::GreaterThan => ">",
             Kind::GreaterThanEqual => ">=",
             Kind::Dot => ".",
+            Kind::DotDot => "..",
             Kind::Comma => ",",
             Kind::Colon => ":",
             Kind::Semicolon => ";",
@@ -166,6 +222,17 @@ impl fmt::Display for Kind {
             Kind::LeftBracket => "[",
             Kind::RightBracket => "]",
             Kind::ThinArrow => "->",
+            Kind::At => "@",
+            Kind::AmpersandEqual => "&=",
+            Kind::PipeEqual => "|=",
+            Kind::CaretEqual => "^=",
+            Kind::LeftShiftEqual => "<<=",
+            Kind::RightShiftEqual => ">>=",
+            Kind::PlusEqual => "+=",
+            Kind::MinusEqual => "-=",
+            Kind::StarEqual => "*=",
+            Kind::SlashEqual => "/=",
+            Kind::PercentEqual => "%=",
             Kind::Comment => "comment",
             Kind::Error => "error",
         };
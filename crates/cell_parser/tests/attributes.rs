@@ -0,0 +1,56 @@
+//! Checks the errors of `@repr` attributes.
+
+use cell_common::{source, Diagnostic};
+use cell_db::RootDatabase;
+use cell_parser::db::ParseDatabase;
+
+/// Parses `text` and returns the code of every error,
+/// together with the source code it points to.
+fn errors(text: &str) -> Vec<(String, String)> {
+    let mut db = RootDatabase::new();
+    let file = source::set_file(&mut db, "attributes.cell", text);
+    db.parse(file)
+        .errors
+        .iter()
+        .cloned()
+        .map(|err| {
+            let diagnostic = Diagnostic::from(err);
+            let range = diagnostic.labels[0].range.clone();
+            (diagnostic.code.unwrap_or_default(), text[range].to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn single_repr() {
+    assert_eq!(errors("@repr(C) type A { a: i32 }"), []);
+    assert_eq!(errors("@repr(packed)\npub union B { X }"), []);
+}
+
+#[test]
+fn duplicate_repr() {
+    assert_eq!(
+        errors("@repr(C) @repr(packed) type A { a: i32 }"),
+        [("E0012".to_string(), "@repr(packed)".to_string())]
+    );
+    assert_eq!(
+        errors("@repr(C)\n@repr(C)\nunion B { X }"),
+        [("E0012".to_string(), "@repr(C)".to_string())]
+    );
+}
+
+#[test]
+fn invalid_repr() {
+    assert_eq!(
+        errors("@repr(C) def main() {}"),
+        [("E0011".to_string(), "@repr(C)".to_string())]
+    );
+    assert_eq!(
+        errors("@repr(Rust) type A {}"),
+        [("E0010".to_string(), "Rust".to_string())]
+    );
+    assert_eq!(
+        errors("@inline type A {}"),
+        [("E0009".to_string(), "inline".to_string())]
+    );
+}
@@ -0,0 +1,269 @@
+//! Checks that printing a syntax tree and parsing the result again gives
+//! the same syntax tree, except for spans.
+
+use cell_common::source::{self, FileId, Span, Spanned};
+use cell_db::RootDatabase;
+use cell_parser::{db::ParseDatabase, pretty::Print};
+use cell_syntax::{
+    ast::{Block, Expr, ExprKind, FieldName, Identifier, Item, ItemId, Pattern, Stmt},
+    db::AstDatabase,
+    fold::{self, Fold},
+};
+
+/// Replaces every span with an empty one and the file of every item
+/// with `file`, so two trees are equal if they only differ in their spans.
+struct EraseSpans {
+    file: FileId,
+}
+
+fn erase<T: Copy>(spanned: Spanned<T>) -> Spanned<T> {
+    Spanned::new(*spanned.data(), Span::default())
+}
+
+impl Fold for EraseSpans {
+    fn fold_item(&mut self, db: &dyn AstDatabase, item: Item) -> Item {
+        let item = fold::walk_item(self, db, item);
+        Item {
+            span: Span::default(),
+            file: self.file,
+            ..item
+        }
+    }
+
+    fn fold_block(&mut self, db: &dyn AstDatabase, block: Block) -> Block {
+        let block = fold::walk_block(self, db, block);
+        Block {
+            span: Span::default(),
+            ..block
+        }
+    }
+
+    fn fold_stmt(&mut self, db: &dyn AstDatabase, stmt: Stmt) -> Stmt {
+        let stmt = fold::walk_stmt(self, db, stmt);
+        Stmt {
+            span: Span::default(),
+            ..stmt
+        }
+    }
+
+    fn fold_expr(&mut self, db: &dyn AstDatabase, expr: Expr) -> Expr {
+        let kind = match fold::walk_expr(self, db, expr).kind {
+            ExprKind::Binary(mut binary) => {
+                binary.op = erase(binary.op);
+                ExprKind::Binary(binary)
+            }
+            ExprKind::Comparison(mut cmp) => {
+                cmp.op = erase(cmp.op);
+                ExprKind::Comparison(cmp)
+            }
+            ExprKind::Unary(mut unary) => {
+                unary.op = erase(unary.op);
+                ExprKind::Unary(unary)
+            }
+            ExprKind::Assign(mut assign) => {
+                assign.op = assign.op.map(erase);
+                ExprKind::Assign(assign)
+            }
+            ExprKind::Field(mut field) => {
+                if let FieldName::Index(idx) = field.field {
+                    field.field = FieldName::Index(erase(idx));
+                }
+                ExprKind::Field(field)
+            }
+            kind => kind,
+        };
+        Expr {
+            span: Span::default(),
+            kind,
+        }
+    }
+
+    fn fold_pattern(&mut self, db: &dyn AstDatabase, pattern: Pattern) -> Pattern {
+        let pattern = fold::walk_pattern(self, db, pattern);
+        Pattern {
+            span: Span::default(),
+            ..pattern
+        }
+    }
+
+    fn fold_ident(&mut self, _db: &dyn AstDatabase, ident: Identifier) -> Identifier {
+        Identifier {
+            span: Span::default(),
+            ..ident
+        }
+    }
+}
+
+/// Parses `text` as the file `name` and panics if it has errors.
+fn parse(db: &mut RootDatabase, name: &str, text: &str) -> (FileId, Vec<ItemId>) {
+    let file = source::set_file(db, name, text);
+    let parsed = db.parse(file);
+    assert!(
+        parsed.errors.is_empty(),
+        "`{}` has errors: {:?}\n{}",
+        name,
+        parsed.errors,
+        text
+    );
+    (file, parsed.items.clone())
+}
+
+fn print(db: &RootDatabase, items: &[ItemId]) -> String {
+    items
+        .iter()
+        .map(|item| format!("{}\n", item.display(db)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn erase_spans(db: &RootDatabase, file: FileId, items: &[ItemId]) -> Vec<ItemId> {
+    let mut fold = EraseSpans { file };
+    items
+        .iter()
+        .map(|&item| fold.fold_item_id(db, item))
+        .collect()
+}
+
+/// Checks that `text` parses into the same items after it was printed,
+/// and that printing is stable, and returns the printed code.
+fn round_trip(text: &str) -> String {
+    let mut db = RootDatabase::new();
+    let (file, items) = parse(&mut db, "original.cell", text);
+    let printed = print(&db, &items);
+    let (_, reparsed) = parse(&mut db, "printed.cell", &printed);
+
+    assert_eq!(
+        erase_spans(&db, file, &items),
+        erase_spans(&db, file, &reparsed),
+        "the printed code parses differently:\n{}",
+        printed
+    );
+    assert_eq!(printed, print(&db, &reparsed));
+    printed
+}
+
+/// Returns the code blocks of a markdown file that contain cell code,
+/// which are the ones without a language or marked as `python`.
+fn code_blocks(markdown: &str) -> Vec<String> {
+    let mut blocks = vec![];
+    // the current block and whether it contains cell code
+    let mut block: Option<(String, bool)> = None;
+    for line in markdown.lines() {
+        match (line.strip_prefix("```"), &mut block) {
+            (Some(_), Some((code, is_cell))) => {
+                if *is_cell {
+                    blocks.push(std::mem::take(code));
+                }
+                block = None;
+            }
+            (Some(lang), None) => {
+                block = Some((String::new(), lang.is_empty() || lang == "python"));
+            }
+            (None, Some((code, _))) => {
+                code.push_str(line);
+                code.push('\n');
+            }
+            (None, None) => {}
+        }
+    }
+    blocks
+}
+
+fn has_errors(text: &str) -> bool {
+    let mut db = RootDatabase::new();
+    let file = source::set_file(&mut db, "block.cell", text);
+    !db.parse(file).errors.is_empty()
+}
+
+/// Checks the code blocks of the documentation. Blocks that leave out code
+/// with `...` are skipped, and so is the example of `if match`, which can't
+/// be parsed yet.
+///
+/// Blocks that end with statements are checked with the statements as the body
+/// of a function, and a block that lists expressions, like `true, false`,
+/// is checked as tuples.
+fn round_trip_docs(markdown: &str) {
+    let blocks = code_blocks(markdown);
+    assert!(!blocks.is_empty());
+    let blocks = blocks
+        .iter()
+        .filter(|block| !block.contains("...") && !block.contains("if match"));
+    for block in blocks {
+        let lines = block.lines().collect::<Vec<_>>();
+        // the items are the first `n` lines and the statements are the rest
+        let split = (0..=lines.len()).rev().map(|n| {
+            let stmts = lines[n..]
+                .iter()
+                .map(|line| format!("    {}\n", line))
+                .collect::<String>();
+            format!("{}\ndef main() {{\n{}}}\n", lines[..n].join("\n"), stmts)
+        });
+        let tuples = lines
+            .iter()
+            .map(|line| format!("    {{{}}};\n", line))
+            .collect::<String>();
+        let tuples = format!("def main() {{\n{}}}\n", tuples);
+
+        let text = std::iter::once(block.clone())
+            .chain(split)
+            .chain(std::iter::once(tuples))
+            .find(|text| !has_errors(text))
+            .unwrap_or_else(|| panic!("failed to parse the code block:\n{}", block));
+        round_trip(&text);
+    }
+}
+
+#[test]
+fn lang_md() {
+    round_trip_docs(include_str!("../../../Lang.md"));
+}
+
+#[test]
+fn readme() {
+    round_trip_docs(include_str!("../../../README.md"));
+}
+
+#[test]
+fn precedence() {
+    let cases = [
+        ("(a << 1) + 2", "(a << 1) + 2"),
+        ("a << 1 + 2", "a << 1 + 2"),
+        ("(a + b) * c", "(a + b) * c"),
+        ("a - (b - c)", "a - (b - c)"),
+        ("(a - b) - c", "a - b - c"),
+        ("(a ** b) ** c", "(a ** b) ** c"),
+        ("a ** (b ** c)", "a ** b ** c"),
+        ("-(1)", "-1"),
+        ("-(-1)", "- -1"),
+        ("(-1).x", "(-1).x"),
+        ("-(a.x)", "-a.x"),
+        ("(*p).x", "(*p).x"),
+        ("*(p + 1)", "*(p + 1)"),
+        ("!(a && b)", "!(a && b)"),
+        ("(a == b) == c", "a == b == c"),
+        ("a == (b == c)", "a == (b == c)"),
+        ("(a < b) == (c < d)", "a < b == c < d"),
+        ("(a & b) == c", "a & b == c"),
+        ("(a = b) + 1", "(a = b) + 1"),
+        ("a = (b = c)", "a = b = c"),
+        ("(f)(1)", "f(1)"),
+        ("(a + b).c()", "(a + b).c()"),
+    ];
+    for (expr, expected) in cases.iter() {
+        let printed = round_trip(&format!("def main() {{\n    {};\n}}\n", expr));
+        let printed = printed.lines().nth(1).unwrap().trim().trim_end_matches(';');
+        assert_eq!(printed, *expected, "`{}` is printed wrong", expr);
+    }
+}
+
+#[test]
+fn literals() {
+    round_trip(
+        r#"def main() {
+    io.println("tab\t, quote \", newline\n, unicode é 日");
+    io.println({'\'', '\\', '\0', 'é', '\u{1F600}', '\x41'});
+    io.println({0x1F, 0b101, 1_000, 1.5e3, 0.25, true, ()});
+}
+"#,
+    );
+}
@@ -2,10 +2,11 @@
 
 use cell_common::{
     intern_id_struct,
-    source::{Span, Spanned},
+    source::{FileId, Span, Spanned},
     strings::StringId,
 };
 use ordered_float::NotNan;
+use std::fmt;
 
 intern_id_struct! {
     /// An identifier to intern an `Item`.
@@ -16,19 +17,36 @@ intern_id_struct! {
     pub struct ExprId;
     /// An identifier to intern a `Type`.
     pub struct TypeId;
+    /// An identifier to intern a `Pattern`.
+    pub struct PatternId;
 }
 
 /// Any type that the user can choose as a function
 /// argument ot type field.
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Type {
-    Int { width: u16, signed: bool },
-    Float { width: u16 },
+    Int {
+        width: u16,
+        signed: bool,
+    },
+    Float {
+        width: u16,
+    },
     Bool,
     String,
     Unit,
     Char,
     Pointer(TypeId),
+    /// A tuple type like `{i32, String}`.
+    Tuple(Vec<TypeId>),
+    /// A named tuple type like `{a: i32, b: String}`.
+    NamedTuple(Vec<(Identifier, TypeId)>),
+    /// A reference to a user defined type, a generic or a type alias,
+    /// with optional generic arguments. E.g. `Array[T]`.
+    Named {
+        name: Identifier,
+        generics: Vec<TypeId>,
+    },
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -43,31 +61,176 @@ pub struct Block {
     pub stmts: Vec<StmtId>,
 }
 
+/// A generic parameter like `T` or `A: SomeTrait`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Generic {
+    pub name: Identifier,
+    pub bounds: Vec<Identifier>,
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct Item {
     pub span: Span,
+    /// The file this item was defined in.
+    pub file: FileId,
+    pub vis: Visibility,
     pub kind: ItemKind,
 }
 
+/// Whether something can be accessed from other modules.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Visibility {
+    /// Marked with `pub`.
+    Public,
+    Private,
+}
+
+impl Visibility {
+    pub fn is_public(self) -> bool {
+        self == Visibility::Public
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum ItemKind {
     TypeDef(TypeDef),
     Def(Def),
+    Alias(Alias),
+    Union(Union),
+    Trait(Trait),
+    Impl(Impl),
+    Const(Global),
+    Static(Global),
+    Import(Import),
+    Use(Use),
+}
+
+impl ItemKind {
+    /// Returns the name of this item, if it has one.
+    pub fn name(&self) -> Option<&Identifier> {
+        match self {
+            ItemKind::TypeDef(def) => Some(&def.name),
+            ItemKind::Def(def) => Some(&def.name),
+            ItemKind::Alias(alias) => Some(&alias.name),
+            ItemKind::Union(union) => Some(&union.name),
+            ItemKind::Trait(trait_) => Some(&trait_.name),
+            ItemKind::Const(global) | ItemKind::Static(global) => Some(&global.name),
+            ItemKind::Import(import) => Some(&import.name),
+            ItemKind::Use(use_) => use_.path.segments.last(),
+            ItemKind::Impl(_) => None,
+        }
+    }
+}
+
+/// How the fields of a `type` or the variants of a union are laid out
+/// in memory, which is chosen using `@repr(...)` in front of the item.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Repr {
+    /// The layout is chosen by the compiler, which is free to use
+    /// a smaller tag or a niche for unions.
+    Default,
+    /// `@repr(C)`, which lays out the type like the equivalent C struct.
+    /// Unions are a struct of an `u32` tag and a C union of the data.
+    C,
+    /// `@repr(packed)`, which is like `C`, but without any padding.
+    Packed,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct TypeDef {
-    // TODO: Generics
     pub name: Identifier,
-    pub fields: Vec<(Identifier, TypeId)>,
+    pub generics: Vec<Generic>,
+    pub fields: Vec<FieldDef>,
+    pub repr: Repr,
+}
+
+/// A single field of a `type`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct FieldDef {
+    pub vis: Visibility,
+    pub name: Identifier,
+    pub ty: TypeId,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct Def {
     pub name: Identifier,
+    pub generics: Vec<Generic>,
     pub args: Vec<(Identifier, TypeId)>,
     pub return_ty: TypeId,
-    pub stmts: Vec<StmtId>,
+    /// The body of this function.
+    ///
+    /// This is only `None` for methods that are declared
+    /// inside a trait without a default implementation.
+    pub body: Option<Block>,
+}
+
+/// `type alias Name = Type;`
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Alias {
+    pub name: Identifier,
+    pub generics: Vec<Generic>,
+    pub ty: TypeId,
+}
+
+/// `const NAME: Type = value;` or `static NAME: Type = value;`
+///
+/// The value of a `const` is inlined everywhere it's used, while a `static`
+/// lives at a fixed location in memory.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Global {
+    pub name: Identifier,
+    pub ty: TypeId,
+    pub val: ExprId,
+}
+
+/// `import name;`, which declares a submodule that is defined in
+/// the file `name.cell` or `name/mod.cell`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Import {
+    pub name: Identifier,
+}
+
+/// `use module:name;`, which brings an item or module of another
+/// module into scope under its last segment.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Use {
+    pub path: Path,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Union {
+    pub name: Identifier,
+    pub generics: Vec<Generic>,
+    pub variants: Vec<Variant>,
+    pub repr: Repr,
+}
+
+/// A single variant of a union, with an optional type
+/// that represents the data inside the variant.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Variant {
+    pub vis: Visibility,
+    pub name: Identifier,
+    pub data: Option<TypeId>,
+}
+
+/// `trait Name = { def method(); }`
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Trait {
+    pub name: Identifier,
+    /// Every method of this trait. Each item is a `Def`.
+    pub methods: Vec<ItemId>,
+}
+
+/// `impl[T] Type { ... }` or `impl[T] Type: Trait { ... }`
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Impl {
+    pub generics: Vec<Generic>,
+    pub ty: TypeId,
+    pub trait_: Option<Identifier>,
+    /// Every method inside this impl block. Each item is a `Def`.
+    pub methods: Vec<ItemId>,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -107,6 +270,59 @@ pub enum ExprKind {
     If(If),
     Match(Match),
     While(While),
+    Block(Block),
+    /// A path to a value, e.g. `foo` or `Complex:First`.
+    Path(Path),
+    Call(Call),
+    MethodCall(MethodCall),
+    Field(Field),
+    Assign(Assign),
+    Return(Option<ExprId>),
+    Break,
+    Continue,
+    This,
+}
+
+/// A path with one or more segments, separated by `:`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Path {
+    pub segments: Vec<Identifier>,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Call {
+    pub callee: ExprId,
+    pub args: Vec<ExprId>,
+}
+
+/// `receiver.name(args)`
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct MethodCall {
+    pub receiver: ExprId,
+    pub name: Identifier,
+    pub args: Vec<ExprId>,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Field {
+    pub val: ExprId,
+    pub field: FieldName,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum FieldName {
+    /// `tuple.name`
+    Named(Identifier),
+    /// `tuple.0`
+    Index(Spanned<u32>),
+}
+
+/// `target = val` or, if `op` is set, `target op= val`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Assign {
+    pub target: ExprId,
+    pub op: Option<Spanned<BinOp>>,
+    pub val: ExprId,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -123,15 +339,45 @@ pub struct Match {
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct MatchArm {
-    pub pattern: MatchPattern,
-    pub body: Block,
+    pub pattern: PatternId,
+    pub body: ExprId,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub enum MatchPattern {
-    // TODO: Some real patterns here
-    Literal(Literal),
+pub struct Pattern {
+    pub span: Span,
+    pub kind: PatternKind,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum PatternKind {
+    /// `else`, which matches everything.
     Wildcard,
+    /// Matches everything and binds the value to the name.
+    Binding(Identifier),
+    Literal(Literal),
+    /// `start..end`, where both ends are inclusive.
+    Range {
+        start: Literal,
+        end: Literal,
+    },
+    /// `{a, b}`
+    Tuple(Vec<PatternId>),
+    /// `{a: pattern, b}`
+    ///
+    /// A field without a pattern binds the field to a variable
+    /// with the same name.
+    NamedTuple(Vec<(Identifier, PatternId)>),
+    /// `Name { a, b: pattern }`
+    Struct {
+        name: Identifier,
+        fields: Vec<(Identifier, PatternId)>,
+    },
+    /// `Union:Variant` or `Union:Variant = pattern`
+    Variant {
+        path: Path,
+        data: Option<PatternId>,
+    },
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -139,7 +385,7 @@ pub struct If {
     pub cond: ExprId,
     pub then: Block,
     pub arms: Vec<IfArm>,
-    pub else_: Block,
+    pub else_: Option<Block>,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -178,7 +424,7 @@ pub struct Unary {
     pub val: ExprId,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum BinOp {
     LogicalAnd,
     LogicalOr,
@@ -195,7 +441,7 @@ pub enum BinOp {
     Pow,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum CmpOp {
     Equal,
     NotEqual,
@@ -205,7 +451,7 @@ pub enum CmpOp {
     GreaterEqual,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum UnOp {
     Ref,
     Deref,
@@ -214,6 +460,54 @@ pub enum UnOp {
     Minus,
 }
 
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinOp::LogicalAnd => "&&",
+            BinOp::LogicalOr => "||",
+            BinOp::BitiwseAnd => "&",
+            BinOp::BitwiseOr => "|",
+            BinOp::BitwiseXor => "^",
+            BinOp::LeftShift => "<<",
+            BinOp::RightShift => ">>",
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Pow => "**",
+        };
+        f.write_str(op)
+    }
+}
+
+impl fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            CmpOp::Equal => "==",
+            CmpOp::NotEqual => "!=",
+            CmpOp::Less => "<",
+            CmpOp::LessEqual => "<=",
+            CmpOp::Greater => ">",
+            CmpOp::GreaterEqual => ">=",
+        };
+        f.write_str(op)
+    }
+}
+
+impl fmt::Display for UnOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            UnOp::Ref => "&",
+            UnOp::Deref => "*",
+            UnOp::Not => "!",
+            UnOp::Plus => "+",
+            UnOp::Minus => "-",
+        };
+        f.write_str(op)
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Literal {
     Int(Int),
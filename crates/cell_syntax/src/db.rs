@@ -0,0 +1,30 @@
+//! Access to the interned syntax tree.
+
+use crate::ast::{Expr, ExprId, Item, ItemId, Pattern, PatternId, Stmt, StmtId, Type, TypeId};
+
+/// A database that interns the nodes of the syntax tree.
+///
+/// The interner itself lives in the `ParseDatabase` of `cell_parser`,
+/// which has this trait as a supertrait, so every parse database
+/// can be used as an `&dyn AstDatabase`.
+pub trait AstDatabase {
+    fn lookup_item(&self, id: ItemId) -> Item;
+
+    fn lookup_stmt(&self, id: StmtId) -> Stmt;
+
+    fn lookup_expr(&self, id: ExprId) -> Expr;
+
+    fn lookup_type(&self, id: TypeId) -> Type;
+
+    fn lookup_pattern(&self, id: PatternId) -> Pattern;
+
+    fn insert_item(&self, item: Item) -> ItemId;
+
+    fn insert_stmt(&self, stmt: Stmt) -> StmtId;
+
+    fn insert_expr(&self, expr: Expr) -> ExprId;
+
+    fn insert_type(&self, ty: Type) -> TypeId;
+
+    fn insert_pattern(&self, pattern: Pattern) -> PatternId;
+}
@@ -0,0 +1,416 @@
+//! Rewriting of the syntax tree.
+//!
+//! A [`Fold`] takes every node by value and returns the node that replaces it.
+//! By default, every method rebuilds its node from the folded children, and
+//! the `*_id` methods intern the result again, which returns the old id if
+//! nothing changed.
+//!
+//! ```text
+//! /// Replaces every `-x` with `0 - x`.
+//! struct Negation;
+//!
+//! impl Fold for Negation {
+//!     fn fold_expr(&mut self, db: &dyn AstDatabase, expr: Expr) -> Expr {
+//!         let expr = walk_expr(self, db, expr);
+//!         match expr.kind {
+//!             ExprKind::Unary(unary) if *unary.op.data() == UnOp::Minus => ...,
+//!             kind => Expr { kind, ..expr },
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [`Fold`]: ./trait.Fold.html
+
+use crate::{
+    ast::{
+        Block, Expr, ExprId, ExprKind, FieldDef, FieldName, Generic, Global, Identifier, IfArm,
+        Item, ItemId, ItemKind, Literal, MatchArm, Pattern, PatternId, PatternKind, Stmt, StmtId,
+        StmtKind, Type, TypeId, Variant,
+    },
+    db::AstDatabase,
+};
+
+/// Rebuilds the syntax tree, resolving and interning every id through the database.
+pub trait Fold {
+    fn fold_item_id(&mut self, db: &dyn AstDatabase, id: ItemId) -> ItemId {
+        let item = self.fold_item(db, db.lookup_item(id));
+        db.insert_item(item)
+    }
+
+    fn fold_item(&mut self, db: &dyn AstDatabase, item: Item) -> Item {
+        walk_item(self, db, item)
+    }
+
+    fn fold_generic(&mut self, db: &dyn AstDatabase, generic: Generic) -> Generic {
+        walk_generic(self, db, generic)
+    }
+
+    fn fold_block(&mut self, db: &dyn AstDatabase, block: Block) -> Block {
+        walk_block(self, db, block)
+    }
+
+    fn fold_stmt_id(&mut self, db: &dyn AstDatabase, id: StmtId) -> StmtId {
+        let stmt = self.fold_stmt(db, db.lookup_stmt(id));
+        db.insert_stmt(stmt)
+    }
+
+    fn fold_stmt(&mut self, db: &dyn AstDatabase, stmt: Stmt) -> Stmt {
+        walk_stmt(self, db, stmt)
+    }
+
+    fn fold_expr_id(&mut self, db: &dyn AstDatabase, id: ExprId) -> ExprId {
+        let expr = self.fold_expr(db, db.lookup_expr(id));
+        db.insert_expr(expr)
+    }
+
+    fn fold_expr(&mut self, db: &dyn AstDatabase, expr: Expr) -> Expr {
+        walk_expr(self, db, expr)
+    }
+
+    fn fold_match_arm(&mut self, db: &dyn AstDatabase, arm: MatchArm) -> MatchArm {
+        walk_match_arm(self, db, arm)
+    }
+
+    fn fold_type_id(&mut self, db: &dyn AstDatabase, id: TypeId) -> TypeId {
+        let ty = self.fold_type(db, db.lookup_type(id));
+        db.insert_type(ty)
+    }
+
+    fn fold_type(&mut self, db: &dyn AstDatabase, ty: Type) -> Type {
+        walk_type(self, db, ty)
+    }
+
+    fn fold_pattern_id(&mut self, db: &dyn AstDatabase, id: PatternId) -> PatternId {
+        let pattern = self.fold_pattern(db, db.lookup_pattern(id));
+        db.insert_pattern(pattern)
+    }
+
+    fn fold_pattern(&mut self, db: &dyn AstDatabase, pattern: Pattern) -> Pattern {
+        walk_pattern(self, db, pattern)
+    }
+
+    fn fold_ident(&mut self, _db: &dyn AstDatabase, ident: Identifier) -> Identifier {
+        ident
+    }
+
+    fn fold_literal(&mut self, _db: &dyn AstDatabase, lit: Literal) -> Literal {
+        lit
+    }
+}
+
+fn fold_generics<F: Fold + ?Sized>(
+    f: &mut F,
+    db: &dyn AstDatabase,
+    generics: Vec<Generic>,
+) -> Vec<Generic> {
+    generics
+        .into_iter()
+        .map(|g| f.fold_generic(db, g))
+        .collect()
+}
+
+fn fold_idents<F: Fold + ?Sized>(
+    f: &mut F,
+    db: &dyn AstDatabase,
+    idents: Vec<Identifier>,
+) -> Vec<Identifier> {
+    idents.into_iter().map(|i| f.fold_ident(db, i)).collect()
+}
+
+fn fold_expr_ids<F: Fold + ?Sized>(
+    f: &mut F,
+    db: &dyn AstDatabase,
+    ids: Vec<ExprId>,
+) -> Vec<ExprId> {
+    ids.into_iter().map(|e| f.fold_expr_id(db, e)).collect()
+}
+
+fn fold_fields<F: Fold + ?Sized, T>(
+    f: &mut F,
+    db: &dyn AstDatabase,
+    fields: Vec<(Identifier, T)>,
+    mut fold: impl FnMut(&mut F, T) -> T,
+) -> Vec<(Identifier, T)> {
+    fields
+        .into_iter()
+        .map(|(name, val)| (f.fold_ident(db, name), fold(f, val)))
+        .collect()
+}
+
+pub fn walk_item<F: Fold + ?Sized>(f: &mut F, db: &dyn AstDatabase, mut item: Item) -> Item {
+    item.kind = match item.kind {
+        ItemKind::TypeDef(mut def) => {
+            def.name = f.fold_ident(db, def.name);
+            def.generics = fold_generics(f, db, def.generics);
+            def.fields = def
+                .fields
+                .into_iter()
+                .map(|field| FieldDef {
+                    vis: field.vis,
+                    name: f.fold_ident(db, field.name),
+                    ty: f.fold_type_id(db, field.ty),
+                })
+                .collect();
+            ItemKind::TypeDef(def)
+        }
+        ItemKind::Def(mut def) => {
+            def.name = f.fold_ident(db, def.name);
+            def.generics = fold_generics(f, db, def.generics);
+            def.args = fold_fields(f, db, def.args, |f, ty| f.fold_type_id(db, ty));
+            def.return_ty = f.fold_type_id(db, def.return_ty);
+            def.body = def.body.map(|body| f.fold_block(db, body));
+            ItemKind::Def(def)
+        }
+        ItemKind::Alias(mut alias) => {
+            alias.name = f.fold_ident(db, alias.name);
+            alias.generics = fold_generics(f, db, alias.generics);
+            alias.ty = f.fold_type_id(db, alias.ty);
+            ItemKind::Alias(alias)
+        }
+        ItemKind::Union(mut union) => {
+            union.name = f.fold_ident(db, union.name);
+            union.generics = fold_generics(f, db, union.generics);
+            union.variants = union
+                .variants
+                .into_iter()
+                .map(|variant| Variant {
+                    vis: variant.vis,
+                    name: f.fold_ident(db, variant.name),
+                    data: variant.data.map(|ty| f.fold_type_id(db, ty)),
+                })
+                .collect();
+            ItemKind::Union(union)
+        }
+        ItemKind::Trait(mut trait_) => {
+            trait_.name = f.fold_ident(db, trait_.name);
+            trait_.methods = trait_
+                .methods
+                .into_iter()
+                .map(|m| f.fold_item_id(db, m))
+                .collect();
+            ItemKind::Trait(trait_)
+        }
+        ItemKind::Impl(mut impl_) => {
+            impl_.generics = fold_generics(f, db, impl_.generics);
+            impl_.ty = f.fold_type_id(db, impl_.ty);
+            impl_.trait_ = impl_.trait_.map(|trait_| f.fold_ident(db, trait_));
+            impl_.methods = impl_
+                .methods
+                .into_iter()
+                .map(|m| f.fold_item_id(db, m))
+                .collect();
+            ItemKind::Impl(impl_)
+        }
+        ItemKind::Const(global) => ItemKind::Const(fold_global(f, db, global)),
+        ItemKind::Static(global) => ItemKind::Static(fold_global(f, db, global)),
+        ItemKind::Import(mut import) => {
+            import.name = f.fold_ident(db, import.name);
+            ItemKind::Import(import)
+        }
+        ItemKind::Use(mut use_) => {
+            use_.path.segments = fold_idents(f, db, use_.path.segments);
+            ItemKind::Use(use_)
+        }
+    };
+    item
+}
+
+fn fold_global<F: Fold + ?Sized>(f: &mut F, db: &dyn AstDatabase, global: Global) -> Global {
+    Global {
+        name: f.fold_ident(db, global.name),
+        ty: f.fold_type_id(db, global.ty),
+        val: f.fold_expr_id(db, global.val),
+    }
+}
+
+pub fn walk_generic<F: Fold + ?Sized>(
+    f: &mut F,
+    db: &dyn AstDatabase,
+    generic: Generic,
+) -> Generic {
+    Generic {
+        name: f.fold_ident(db, generic.name),
+        bounds: fold_idents(f, db, generic.bounds),
+    }
+}
+
+pub fn walk_block<F: Fold + ?Sized>(f: &mut F, db: &dyn AstDatabase, block: Block) -> Block {
+    Block {
+        span: block.span,
+        stmts: block
+            .stmts
+            .into_iter()
+            .map(|s| f.fold_stmt_id(db, s))
+            .collect(),
+    }
+}
+
+pub fn walk_stmt<F: Fold + ?Sized>(f: &mut F, db: &dyn AstDatabase, mut stmt: Stmt) -> Stmt {
+    stmt.kind = match stmt.kind {
+        StmtKind::Var(mut var) => {
+            var.name = f.fold_ident(db, var.name);
+            var.ty = var.ty.map(|ty| f.fold_type(db, ty));
+            var.val = f.fold_expr_id(db, var.val);
+            StmtKind::Var(var)
+        }
+        StmtKind::Expr(expr) => StmtKind::Expr(f.fold_expr_id(db, expr)),
+    };
+    stmt
+}
+
+pub fn walk_expr<F: Fold + ?Sized>(f: &mut F, db: &dyn AstDatabase, mut expr: Expr) -> Expr {
+    expr.kind = match expr.kind {
+        ExprKind::Literal(lit) => ExprKind::Literal(f.fold_literal(db, lit)),
+        ExprKind::Binary(mut binary) => {
+            binary.left = f.fold_expr_id(db, binary.left);
+            binary.right = f.fold_expr_id(db, binary.right);
+            ExprKind::Binary(binary)
+        }
+        ExprKind::Comparison(mut cmp) => {
+            cmp.left = f.fold_expr_id(db, cmp.left);
+            cmp.right = f.fold_expr_id(db, cmp.right);
+            ExprKind::Comparison(cmp)
+        }
+        ExprKind::Unary(mut unary) => {
+            unary.val = f.fold_expr_id(db, unary.val);
+            ExprKind::Unary(unary)
+        }
+        ExprKind::Tuple(mut tuple) => {
+            tuple.values = fold_expr_ids(f, db, tuple.values);
+            ExprKind::Tuple(tuple)
+        }
+        ExprKind::NamedTuple(mut tuple) => {
+            tuple.values = fold_fields(f, db, tuple.values, |f, e| f.fold_expr_id(db, e));
+            ExprKind::NamedTuple(tuple)
+        }
+        ExprKind::If(mut if_) => {
+            if_.cond = f.fold_expr_id(db, if_.cond);
+            if_.then = f.fold_block(db, if_.then);
+            if_.arms = if_
+                .arms
+                .into_iter()
+                .map(|arm| IfArm {
+                    cond: f.fold_expr_id(db, arm.cond),
+                    action: f.fold_block(db, arm.action),
+                })
+                .collect();
+            if_.else_ = if_.else_.map(|else_| f.fold_block(db, else_));
+            ExprKind::If(if_)
+        }
+        ExprKind::Match(mut match_) => {
+            match_.val = f.fold_expr_id(db, match_.val);
+            match_.arms = match_
+                .arms
+                .into_iter()
+                .map(|arm| f.fold_match_arm(db, arm))
+                .collect();
+            ExprKind::Match(match_)
+        }
+        ExprKind::While(mut while_) => {
+            while_.cond = while_.cond.map(|cond| f.fold_expr_id(db, cond));
+            while_.block = f.fold_block(db, while_.block);
+            ExprKind::While(while_)
+        }
+        ExprKind::Block(block) => ExprKind::Block(f.fold_block(db, block)),
+        ExprKind::Path(mut path) => {
+            path.segments = fold_idents(f, db, path.segments);
+            ExprKind::Path(path)
+        }
+        ExprKind::Call(mut call) => {
+            call.callee = f.fold_expr_id(db, call.callee);
+            call.args = fold_expr_ids(f, db, call.args);
+            ExprKind::Call(call)
+        }
+        ExprKind::MethodCall(mut call) => {
+            call.receiver = f.fold_expr_id(db, call.receiver);
+            call.name = f.fold_ident(db, call.name);
+            call.args = fold_expr_ids(f, db, call.args);
+            ExprKind::MethodCall(call)
+        }
+        ExprKind::Field(mut field) => {
+            field.val = f.fold_expr_id(db, field.val);
+            field.field = match field.field {
+                FieldName::Named(name) => FieldName::Named(f.fold_ident(db, name)),
+                index => index,
+            };
+            ExprKind::Field(field)
+        }
+        ExprKind::Assign(mut assign) => {
+            assign.target = f.fold_expr_id(db, assign.target);
+            assign.val = f.fold_expr_id(db, assign.val);
+            ExprKind::Assign(assign)
+        }
+        ExprKind::Return(val) => ExprKind::Return(val.map(|val| f.fold_expr_id(db, val))),
+        kind @ ExprKind::Break | kind @ ExprKind::Continue | kind @ ExprKind::This => kind,
+    };
+    expr
+}
+
+pub fn walk_match_arm<F: Fold + ?Sized>(
+    f: &mut F,
+    db: &dyn AstDatabase,
+    arm: MatchArm,
+) -> MatchArm {
+    MatchArm {
+        pattern: f.fold_pattern_id(db, arm.pattern),
+        body: f.fold_expr_id(db, arm.body),
+    }
+}
+
+pub fn walk_type<F: Fold + ?Sized>(f: &mut F, db: &dyn AstDatabase, ty: Type) -> Type {
+    match ty {
+        Type::Pointer(inner) => Type::Pointer(f.fold_type_id(db, inner)),
+        Type::Tuple(types) => {
+            Type::Tuple(types.into_iter().map(|t| f.fold_type_id(db, t)).collect())
+        }
+        Type::NamedTuple(fields) => {
+            Type::NamedTuple(fold_fields(f, db, fields, |f, t| f.fold_type_id(db, t)))
+        }
+        Type::Named { name, generics } => Type::Named {
+            name: f.fold_ident(db, name),
+            generics: generics
+                .into_iter()
+                .map(|t| f.fold_type_id(db, t))
+                .collect(),
+        },
+        ty => ty,
+    }
+}
+
+pub fn walk_pattern<F: Fold + ?Sized>(
+    f: &mut F,
+    db: &dyn AstDatabase,
+    mut pattern: Pattern,
+) -> Pattern {
+    pattern.kind = match pattern.kind {
+        PatternKind::Wildcard => PatternKind::Wildcard,
+        PatternKind::Binding(name) => PatternKind::Binding(f.fold_ident(db, name)),
+        PatternKind::Literal(lit) => PatternKind::Literal(f.fold_literal(db, lit)),
+        PatternKind::Range { start, end } => PatternKind::Range {
+            start: f.fold_literal(db, start),
+            end: f.fold_literal(db, end),
+        },
+        PatternKind::Tuple(patterns) => PatternKind::Tuple(
+            patterns
+                .into_iter()
+                .map(|p| f.fold_pattern_id(db, p))
+                .collect(),
+        ),
+        PatternKind::NamedTuple(fields) => {
+            PatternKind::NamedTuple(fold_fields(f, db, fields, |f, p| f.fold_pattern_id(db, p)))
+        }
+        PatternKind::Struct { name, fields } => PatternKind::Struct {
+            name: f.fold_ident(db, name),
+            fields: fold_fields(f, db, fields, |f, p| f.fold_pattern_id(db, p)),
+        },
+        PatternKind::Variant { mut path, data } => {
+            path.segments = fold_idents(f, db, path.segments);
+            PatternKind::Variant {
+                path,
+                data: data.map(|p| f.fold_pattern_id(db, p)),
+            }
+        }
+    };
+    pattern
+}
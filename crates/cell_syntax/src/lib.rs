@@ -1,3 +1,6 @@
 //! All syntax related stuff.
 
 pub mod ast;
+pub mod db;
+pub mod fold;
+pub mod visit;
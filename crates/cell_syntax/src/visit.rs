@@ -0,0 +1,298 @@
+//! Traversal of the syntax tree by reference.
+//!
+//! Every method of [`Visitor`] walks into all children of its node by default,
+//! so an implementation only has to override the nodes it's interested in.
+//! An overriding method can call the `walk_*` function of the same node
+//! to continue the traversal into the children.
+//!
+//! ```text
+//! struct Calls(usize);
+//!
+//! impl Visitor for Calls {
+//!     fn visit_expr(&mut self, db: &dyn AstDatabase, expr: &Expr) {
+//!         if let ExprKind::Call(_) = expr.kind {
+//!             self.0 += 1;
+//!         }
+//!         walk_expr(self, db, expr);
+//!     }
+//! }
+//! ```
+//!
+//! [`Visitor`]: ./trait.Visitor.html
+
+use crate::{
+    ast::{
+        Block, Expr, ExprId, ExprKind, FieldName, Generic, Identifier, Item, ItemId, ItemKind,
+        Literal, MatchArm, Pattern, PatternId, PatternKind, Stmt, StmtId, StmtKind, Type, TypeId,
+    },
+    db::AstDatabase,
+};
+
+/// Walks the syntax tree, resolving every id through the database.
+pub trait Visitor {
+    fn visit_item_id(&mut self, db: &dyn AstDatabase, id: ItemId) {
+        self.visit_item(db, &db.lookup_item(id));
+    }
+
+    fn visit_item(&mut self, db: &dyn AstDatabase, item: &Item) {
+        walk_item(self, db, item);
+    }
+
+    fn visit_generic(&mut self, db: &dyn AstDatabase, generic: &Generic) {
+        walk_generic(self, db, generic);
+    }
+
+    fn visit_block(&mut self, db: &dyn AstDatabase, block: &Block) {
+        walk_block(self, db, block);
+    }
+
+    fn visit_stmt_id(&mut self, db: &dyn AstDatabase, id: StmtId) {
+        self.visit_stmt(db, &db.lookup_stmt(id));
+    }
+
+    fn visit_stmt(&mut self, db: &dyn AstDatabase, stmt: &Stmt) {
+        walk_stmt(self, db, stmt);
+    }
+
+    fn visit_expr_id(&mut self, db: &dyn AstDatabase, id: ExprId) {
+        self.visit_expr(db, &db.lookup_expr(id));
+    }
+
+    fn visit_expr(&mut self, db: &dyn AstDatabase, expr: &Expr) {
+        walk_expr(self, db, expr);
+    }
+
+    fn visit_match_arm(&mut self, db: &dyn AstDatabase, arm: &MatchArm) {
+        walk_match_arm(self, db, arm);
+    }
+
+    fn visit_type_id(&mut self, db: &dyn AstDatabase, id: TypeId) {
+        self.visit_type(db, &db.lookup_type(id));
+    }
+
+    fn visit_type(&mut self, db: &dyn AstDatabase, ty: &Type) {
+        walk_type(self, db, ty);
+    }
+
+    fn visit_pattern_id(&mut self, db: &dyn AstDatabase, id: PatternId) {
+        self.visit_pattern(db, &db.lookup_pattern(id));
+    }
+
+    fn visit_pattern(&mut self, db: &dyn AstDatabase, pattern: &Pattern) {
+        walk_pattern(self, db, pattern);
+    }
+
+    fn visit_ident(&mut self, _db: &dyn AstDatabase, _ident: &Identifier) {}
+
+    fn visit_literal(&mut self, _db: &dyn AstDatabase, _lit: &Literal) {}
+}
+
+pub fn walk_item<V: Visitor + ?Sized>(v: &mut V, db: &dyn AstDatabase, item: &Item) {
+    match &item.kind {
+        ItemKind::TypeDef(def) => {
+            v.visit_ident(db, &def.name);
+            def.generics.iter().for_each(|g| v.visit_generic(db, g));
+            for field in &def.fields {
+                v.visit_ident(db, &field.name);
+                v.visit_type_id(db, field.ty);
+            }
+        }
+        ItemKind::Def(def) => {
+            v.visit_ident(db, &def.name);
+            def.generics.iter().for_each(|g| v.visit_generic(db, g));
+            for (name, ty) in &def.args {
+                v.visit_ident(db, name);
+                v.visit_type_id(db, *ty);
+            }
+            v.visit_type_id(db, def.return_ty);
+            if let Some(body) = &def.body {
+                v.visit_block(db, body);
+            }
+        }
+        ItemKind::Alias(alias) => {
+            v.visit_ident(db, &alias.name);
+            alias.generics.iter().for_each(|g| v.visit_generic(db, g));
+            v.visit_type_id(db, alias.ty);
+        }
+        ItemKind::Union(union) => {
+            v.visit_ident(db, &union.name);
+            union.generics.iter().for_each(|g| v.visit_generic(db, g));
+            for variant in &union.variants {
+                v.visit_ident(db, &variant.name);
+                if let Some(ty) = variant.data {
+                    v.visit_type_id(db, ty);
+                }
+            }
+        }
+        ItemKind::Trait(trait_) => {
+            v.visit_ident(db, &trait_.name);
+            trait_.methods.iter().for_each(|&m| v.visit_item_id(db, m));
+        }
+        ItemKind::Impl(impl_) => {
+            impl_.generics.iter().for_each(|g| v.visit_generic(db, g));
+            v.visit_type_id(db, impl_.ty);
+            if let Some(trait_) = &impl_.trait_ {
+                v.visit_ident(db, trait_);
+            }
+            impl_.methods.iter().for_each(|&m| v.visit_item_id(db, m));
+        }
+        ItemKind::Const(global) | ItemKind::Static(global) => {
+            v.visit_ident(db, &global.name);
+            v.visit_type_id(db, global.ty);
+            v.visit_expr_id(db, global.val);
+        }
+        ItemKind::Import(import) => v.visit_ident(db, &import.name),
+        ItemKind::Use(use_) => use_.path.segments.iter().for_each(|s| v.visit_ident(db, s)),
+    }
+}
+
+pub fn walk_generic<V: Visitor + ?Sized>(v: &mut V, db: &dyn AstDatabase, generic: &Generic) {
+    v.visit_ident(db, &generic.name);
+    generic.bounds.iter().for_each(|b| v.visit_ident(db, b));
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(v: &mut V, db: &dyn AstDatabase, block: &Block) {
+    block.stmts.iter().for_each(|&s| v.visit_stmt_id(db, s));
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(v: &mut V, db: &dyn AstDatabase, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Var(var) => {
+            v.visit_ident(db, &var.name);
+            if let Some(ty) = &var.ty {
+                v.visit_type(db, ty);
+            }
+            v.visit_expr_id(db, var.val);
+        }
+        StmtKind::Expr(expr) => v.visit_expr_id(db, *expr),
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(v: &mut V, db: &dyn AstDatabase, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Literal(lit) => v.visit_literal(db, lit),
+        ExprKind::Binary(binary) => {
+            v.visit_expr_id(db, binary.left);
+            v.visit_expr_id(db, binary.right);
+        }
+        ExprKind::Comparison(cmp) => {
+            v.visit_expr_id(db, cmp.left);
+            v.visit_expr_id(db, cmp.right);
+        }
+        ExprKind::Unary(unary) => v.visit_expr_id(db, unary.val),
+        ExprKind::Tuple(tuple) => tuple.values.iter().for_each(|&e| v.visit_expr_id(db, e)),
+        ExprKind::NamedTuple(tuple) => {
+            for (name, val) in &tuple.values {
+                v.visit_ident(db, name);
+                v.visit_expr_id(db, *val);
+            }
+        }
+        ExprKind::If(if_) => {
+            v.visit_expr_id(db, if_.cond);
+            v.visit_block(db, &if_.then);
+            for arm in &if_.arms {
+                v.visit_expr_id(db, arm.cond);
+                v.visit_block(db, &arm.action);
+            }
+            if let Some(else_) = &if_.else_ {
+                v.visit_block(db, else_);
+            }
+        }
+        ExprKind::Match(match_) => {
+            v.visit_expr_id(db, match_.val);
+            match_
+                .arms
+                .iter()
+                .for_each(|arm| v.visit_match_arm(db, arm));
+        }
+        ExprKind::While(while_) => {
+            if let Some(cond) = while_.cond {
+                v.visit_expr_id(db, cond);
+            }
+            v.visit_block(db, &while_.block);
+        }
+        ExprKind::Block(block) => v.visit_block(db, block),
+        ExprKind::Path(path) => path.segments.iter().for_each(|s| v.visit_ident(db, s)),
+        ExprKind::Call(call) => {
+            v.visit_expr_id(db, call.callee);
+            call.args.iter().for_each(|&e| v.visit_expr_id(db, e));
+        }
+        ExprKind::MethodCall(call) => {
+            v.visit_expr_id(db, call.receiver);
+            v.visit_ident(db, &call.name);
+            call.args.iter().for_each(|&e| v.visit_expr_id(db, e));
+        }
+        ExprKind::Field(field) => {
+            v.visit_expr_id(db, field.val);
+            if let FieldName::Named(name) = &field.field {
+                v.visit_ident(db, name);
+            }
+        }
+        ExprKind::Assign(assign) => {
+            v.visit_expr_id(db, assign.target);
+            v.visit_expr_id(db, assign.val);
+        }
+        ExprKind::Return(val) => {
+            if let Some(val) = val {
+                v.visit_expr_id(db, *val);
+            }
+        }
+        ExprKind::Break | ExprKind::Continue | ExprKind::This => {}
+    }
+}
+
+pub fn walk_match_arm<V: Visitor + ?Sized>(v: &mut V, db: &dyn AstDatabase, arm: &MatchArm) {
+    v.visit_pattern_id(db, arm.pattern);
+    v.visit_expr_id(db, arm.body);
+}
+
+pub fn walk_type<V: Visitor + ?Sized>(v: &mut V, db: &dyn AstDatabase, ty: &Type) {
+    match ty {
+        Type::Int { .. } | Type::Float { .. } => {}
+        Type::Bool | Type::String | Type::Unit | Type::Char => {}
+        Type::Pointer(inner) => v.visit_type_id(db, *inner),
+        Type::Tuple(types) => types.iter().for_each(|&t| v.visit_type_id(db, t)),
+        Type::NamedTuple(fields) => {
+            for (name, ty) in fields {
+                v.visit_ident(db, name);
+                v.visit_type_id(db, *ty);
+            }
+        }
+        Type::Named { name, generics } => {
+            v.visit_ident(db, name);
+            generics.iter().for_each(|&t| v.visit_type_id(db, t));
+        }
+    }
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(v: &mut V, db: &dyn AstDatabase, pattern: &Pattern) {
+    match &pattern.kind {
+        PatternKind::Wildcard => {}
+        PatternKind::Binding(name) => v.visit_ident(db, name),
+        PatternKind::Literal(lit) => v.visit_literal(db, lit),
+        PatternKind::Range { start, end } => {
+            v.visit_literal(db, start);
+            v.visit_literal(db, end);
+        }
+        PatternKind::Tuple(patterns) => patterns.iter().for_each(|&p| v.visit_pattern_id(db, p)),
+        PatternKind::NamedTuple(fields) => {
+            for (name, pattern) in fields {
+                v.visit_ident(db, name);
+                v.visit_pattern_id(db, *pattern);
+            }
+        }
+        PatternKind::Struct { name, fields } => {
+            v.visit_ident(db, name);
+            for (name, pattern) in fields {
+                v.visit_ident(db, name);
+                v.visit_pattern_id(db, *pattern);
+            }
+        }
+        PatternKind::Variant { path, data } => {
+            path.segments.iter().for_each(|s| v.visit_ident(db, s));
+            if let Some(data) = data {
+                v.visit_pattern_id(db, *data);
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Collects every error of a file.
+
+use crate::{
+    db::TypeckDatabase,
+    lower::{AdtDef, TyLowerer},
+    module, privacy,
+    scope::{self, Namespace},
+    ty::{Ty, TyKind},
+};
+use cell_common::{
+    error::{Error, TypeError, Warning},
+    source::{FileId, Locatable},
+};
+use cell_syntax::ast::{Generic, Identifier, ItemId, ItemKind};
+use std::{collections::HashSet, sync::Arc};
+
+pub(crate) fn check_item(db: &dyn TypeckDatabase, id: ItemId) -> Arc<Vec<Locatable<Error>>> {
+    let item = db.lookup_intern_item(id);
+    let mut lowerer = TyLowerer::for_item(db, id);
+    let mut errors = vec![];
+
+    let mut check_generics = |generics: &[Generic]| {
+        let bounds = generics.iter().flat_map(|generic| &generic.bounds);
+        for bound in bounds {
+            if scope::resolve_trait(db, item.file, bound.id).is_none() {
+                let err = TypeError::UnresolvedTrait {
+                    name: db.rodeo().resolve(&bound.id).to_string(),
+                };
+                errors.push(Locatable::new(err.into(), item.file, bound.span));
+            }
+        }
+    };
+
+    match &item.kind {
+        ItemKind::TypeDef(def) => {
+            check_generics(&def.generics);
+            for field in &def.fields {
+                lowerer.lower(field.ty);
+            }
+            check_infinite_size(db, id, &def.name, &mut errors);
+        }
+        ItemKind::Def(def) => {
+            check_generics(&def.generics);
+            for (_, ty) in &def.args {
+                lowerer.lower(*ty);
+            }
+            lowerer.lower(def.return_ty);
+        }
+        ItemKind::Alias(alias) => {
+            check_generics(&alias.generics);
+            lowerer.lower(alias.ty);
+        }
+        ItemKind::Union(union) => {
+            check_generics(&union.generics);
+            for ty in union.variants.iter().filter_map(|variant| variant.data) {
+                lowerer.lower(ty);
+            }
+            check_infinite_size(db, id, &union.name, &mut errors);
+        }
+        ItemKind::Impl(imp) => {
+            check_generics(&imp.generics);
+            lowerer.lower(imp.ty);
+        }
+        ItemKind::Const(global) | ItemKind::Static(global) => {
+            lowerer.lower(global.ty);
+        }
+        ItemKind::Use(use_) => {
+            let resolved = [Namespace::Types, Namespace::Values, Namespace::Modules]
+                .iter()
+                .filter_map(|&ns| scope::resolve_use(db, id, ns))
+                .collect::<Vec<_>>();
+            let segments = &use_.path.segments;
+            let span = segments[0].span.merge(segments[segments.len() - 1].span);
+
+            if resolved.is_empty() {
+                let path = segments
+                    .iter()
+                    .map(|segment| db.rodeo().resolve(&segment.id).to_string())
+                    .collect::<Vec<_>>()
+                    .join(":");
+                errors.push(Locatable::new(
+                    TypeError::UnresolvedImport { path }.into(),
+                    item.file,
+                    span,
+                ));
+            } else if resolved.iter().all(|res| res.private.is_some()) {
+                let private = resolved[0].private.unwrap();
+                errors.push(privacy::private_item_error(db, item.file, span, private));
+            }
+        }
+        ItemKind::Trait(_) | ItemKind::Import(_) => {}
+    }
+
+    errors.extend(lowerer.finish());
+    Arc::new(errors)
+}
+
+pub(crate) fn check_file(db: &dyn TypeckDatabase, file: FileId) -> Arc<Vec<Locatable<Error>>> {
+    let mut errors = db.parse(file).errors.clone();
+
+    let scope = db.file_scope(file);
+    errors.extend(scope.errors.iter().cloned());
+    errors.extend(module::check_imports(db, file));
+    for &item in &scope.items {
+        errors.extend(db.check_item(item).iter().cloned());
+
+        if has_body(db, item) {
+            errors.extend(db.typeck(item).errors.iter().cloned());
+        }
+        if let ItemKind::Const(_) | ItemKind::Static(_) = db.lookup_intern_item(item).kind {
+            if let Err(err) = db.const_value(item) {
+                errors.push(err);
+            }
+        }
+    }
+    errors.extend(db.check_impls(file).iter().cloned());
+
+    // errors inside globals are also reported by every use of them
+    errors.retain(|err| err.file() == file);
+    errors.sort_by_key(|err| (err.span().start(), err.span().end()));
+    errors.dedup();
+    Arc::new(errors)
+}
+
+pub(crate) fn file_warnings(db: &dyn TypeckDatabase, file: FileId) -> Arc<Vec<Locatable<Warning>>> {
+    let mut warnings = vec![];
+    for &item in &db.file_scope(file).items {
+        if has_body(db, item) {
+            warnings.extend(db.typeck(item).warnings.iter().cloned());
+        }
+    }
+
+    warnings.sort_by_key(|warn| (warn.span().start(), warn.span().end()));
+    Arc::new(warnings)
+}
+
+/// Reports an error if a `type` or union contains itself
+/// without a pointer in between.
+fn check_infinite_size(
+    db: &dyn TypeckDatabase,
+    item: ItemId,
+    name: &Identifier,
+    errors: &mut Vec<Locatable<Error>>,
+) {
+    let mut visited = HashSet::new();
+    let recursive = adt_field_tys(db, item)
+        .into_iter()
+        .any(|ty| contains_adt(db, ty, item, &mut visited));
+    if recursive {
+        let err = TypeError::InfiniteSize {
+            name: db.rodeo().resolve(&name.id).to_string(),
+        };
+        errors.push(Locatable::new(
+            err.into(),
+            db.lookup_intern_item(item).file,
+            name.span,
+        ));
+    }
+}
+
+/// Checks if `ty` stores a value of the `adt` type inline.
+fn contains_adt(db: &dyn TypeckDatabase, ty: Ty, adt: ItemId, visited: &mut HashSet<Ty>) -> bool {
+    // types like `type A[T] { a: A[{T}] }` never repeat, so give up
+    // after a while. `A` itself reports the error in that case.
+    const MAX_VISITED: usize = 1024;
+    if visited.len() > MAX_VISITED || !visited.insert(ty) {
+        return false;
+    }
+
+    match ty.kind(db) {
+        TyKind::Tuple(tys) => tys.into_iter().any(|ty| contains_adt(db, ty, adt, visited)),
+        TyKind::NamedTuple(fields) => fields
+            .into_iter()
+            .any(|(_, ty)| contains_adt(db, ty, adt, visited)),
+        TyKind::Adt(item, _) if item == adt => true,
+        TyKind::Adt(item, args) => adt_field_tys(db, item)
+            .into_iter()
+            .any(|ty| contains_adt(db, ty.subst(db, &args), adt, visited)),
+        _ => false,
+    }
+}
+
+fn adt_field_tys(db: &dyn TypeckDatabase, item: ItemId) -> Vec<Ty> {
+    match &*db.adt_def(item) {
+        AdtDef::Struct { fields } => fields.iter().map(|(_, ty)| *ty).collect(),
+        AdtDef::Union { variants } => variants.iter().filter_map(|(_, ty)| *ty).collect(),
+    }
+}
+
+/// Checks if the item contains expressions that can be type checked.
+fn has_body(db: &dyn TypeckDatabase, item: ItemId) -> bool {
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Def(def) => def.body.is_some(),
+        ItemKind::Const(_) | ItemKind::Static(_) => true,
+        _ => false,
+    }
+}
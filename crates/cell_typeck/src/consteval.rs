@@ -0,0 +1,555 @@
+//! Evaluation of expressions at compile time.
+//!
+//! Integers are evaluated with the exact width and signedness of their
+//! type, so every operation that would overflow, divide by zero or shift
+//! by too many bits at runtime is reported as an error instead.
+
+use crate::{
+    db::TypeckDatabase,
+    infer::{Res, TypeckResults},
+    ty::TyKind,
+};
+use cell_common::{
+    error::{ConstError, Error},
+    source::{FileId, Locatable, Span},
+    strings::StringId,
+};
+use cell_syntax::ast::{BinOp, CmpOp, ExprId, ExprKind, ItemId, ItemKind, Literal, UnOp};
+use ordered_float::OrderedFloat;
+use std::{cmp::Ordering, collections::HashSet, convert::TryFrom, fmt};
+
+/// An integer of a specific width and signedness.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct ConstInt {
+    /// The two's complement representation, truncated to `width` bits.
+    bits: u128,
+    width: u16,
+    signed: bool,
+}
+
+impl ConstInt {
+    /// Creates an integer from an unsigned value, if the value fits into the type.
+    pub fn from_u128(val: u128, width: u16, signed: bool) -> Option<Self> {
+        let max = if signed {
+            (i128::MAX >> (128 - width as u32)) as u128
+        } else {
+            mask(width)
+        };
+        if val <= max {
+            Some(Self {
+                bits: val,
+                width,
+                signed,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Creates an integer from a signed value, if the value fits into the type.
+    pub fn from_i128(val: i128, width: u16, signed: bool) -> Option<Self> {
+        if !signed {
+            return if val < 0 {
+                None
+            } else {
+                Self::from_u128(val as u128, width, signed)
+            };
+        }
+
+        let shift = 128 - width as u32;
+        if val < i128::MIN >> shift || val > i128::MAX >> shift {
+            return None;
+        }
+        Some(Self {
+            bits: val as u128 & mask(width),
+            width,
+            signed,
+        })
+    }
+
+    /// Returns the value of a signed integer.
+    pub fn as_i128(self) -> i128 {
+        let shift = 128 - self.width as u32;
+        ((self.bits << shift) as i128) >> shift
+    }
+
+    /// Returns the value of an unsigned integer.
+    pub fn as_u128(self) -> u128 {
+        self.bits
+    }
+
+    pub fn width(self) -> u16 {
+        self.width
+    }
+
+    pub fn is_signed(self) -> bool {
+        self.signed
+    }
+
+    fn is_negative(self) -> bool {
+        self.signed && self.as_i128() < 0
+    }
+
+    fn with_bits(self, bits: u128) -> Self {
+        Self {
+            bits: bits & mask(self.width),
+            ..self
+        }
+    }
+
+    fn type_name(self) -> String {
+        format!("{}{}", if self.signed { 'i' } else { 'u' }, self.width)
+    }
+
+    fn overflow(self) -> ConstError {
+        ConstError::Overflow {
+            ty: self.type_name(),
+        }
+    }
+
+    fn cmp(self, other: Self) -> Ordering {
+        if self.signed {
+            self.as_i128().cmp(&other.as_i128())
+        } else {
+            self.bits.cmp(&other.bits)
+        }
+    }
+}
+
+impl fmt::Display for ConstInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.signed {
+            write!(f, "{}", self.as_i128())
+        } else {
+            write!(f, "{}", self.bits)
+        }
+    }
+}
+
+/// The value of an expression that was evaluated at compile time.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum ConstValue {
+    Int(ConstInt),
+    Float { val: OrderedFloat<f64>, width: u16 },
+    Bool(bool),
+    Char(char),
+    String(String),
+    Unit,
+    Tuple(Vec<ConstValue>),
+    NamedTuple(Vec<(StringId, ConstValue)>),
+}
+
+/// Evaluates the expression, which is part of the body that `results` belong to.
+pub fn eval(
+    db: &dyn TypeckDatabase,
+    file: FileId,
+    results: &TypeckResults,
+    expr: ExprId,
+) -> Result<ConstValue, Locatable<Error>> {
+    let eval = ConstEval {
+        db,
+        results,
+        globals: true,
+    };
+    eval.eval(expr)
+        .map_err(|(err, span)| Locatable::new(err.into(), file, span))
+}
+
+pub(crate) fn const_value(
+    db: &dyn TypeckDatabase,
+    item: ItemId,
+) -> Result<ConstValue, Locatable<Error>> {
+    let global = match db.lookup_intern_item(item).kind {
+        ItemKind::Const(global) | ItemKind::Static(global) => global,
+        _ => panic!("`const_value` called on an item that is not a `const` or `static`"),
+    };
+
+    let results = db.typeck(item);
+    if let Some(err) = results.errors.first() {
+        return Err(err.clone());
+    }
+    eval(db, db.lookup_intern_item(item).file, &results, global.val)
+}
+
+pub(crate) fn const_value_cycle(
+    db: &dyn TypeckDatabase,
+    _cycle: &[String],
+    item: &ItemId,
+) -> Result<ConstValue, Locatable<Error>> {
+    let item = db.lookup_intern_item(*item);
+    let name = item.kind.name().expect("globals always have a name");
+    let err = ConstError::Cycle {
+        name: db.rodeo().resolve(&name.id).to_string(),
+    };
+    Err(Locatable::new(err.into(), item.file, name.span))
+}
+
+/// Evaluates every constant operation inside a function body and
+/// reports the ones that would fail at runtime.
+pub(crate) fn check_body(db: &dyn TypeckDatabase, file: FileId, results: &mut TypeckResults) {
+    let eval = ConstEval {
+        db,
+        results: &*results,
+        globals: false,
+    };
+
+    let mut exprs = results
+        .expr_types
+        .keys()
+        .map(|&expr| (expr, db.lookup_intern_expr(expr)))
+        .collect::<Vec<_>>();
+    exprs.sort_by_key(|(_, expr)| (expr.span.start(), expr.span.end()));
+
+    // negative literals are checked together with their `-`
+    let negated = exprs
+        .iter()
+        .filter_map(|(_, expr)| match &expr.kind {
+            ExprKind::Unary(unary) if *unary.op.data() == UnOp::Minus => Some(unary.val),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    let mut errors = vec![];
+    for (id, expr) in exprs {
+        let candidate = match expr.kind {
+            ExprKind::Literal(Literal::Int(_)) => !negated.contains(&id),
+            ExprKind::Binary(_) | ExprKind::Comparison(_) | ExprKind::Unary(_) => true,
+            _ => false,
+        };
+        if !candidate {
+            continue;
+        }
+
+        match eval.eval(id) {
+            Err((ConstError::NotConst, _)) | Ok(_) => {}
+            Err((err, span)) => {
+                let err = Locatable::new(err.into(), file, span);
+                if !errors.contains(&err) {
+                    errors.push(err);
+                }
+            }
+        }
+    }
+    results.errors.extend(errors);
+}
+
+type EvalResult<T> = Result<T, (ConstError, Span)>;
+
+struct ConstEval<'a> {
+    db: &'a dyn TypeckDatabase,
+    results: &'a TypeckResults,
+    /// Whether paths to other constants are evaluated. This must be disabled
+    /// while type checking, because `const_value` itself depends on `typeck`.
+    globals: bool,
+}
+
+impl ConstEval<'_> {
+    fn eval(&self, id: ExprId) -> EvalResult<ConstValue> {
+        let expr = self.db.lookup_intern_expr(id);
+        let span = expr.span;
+
+        match expr.kind {
+            ExprKind::Literal(lit) => self.literal(id, &lit, false, span),
+            ExprKind::Unary(unary) => {
+                let op = *unary.op.data();
+                if op == UnOp::Minus {
+                    if let ExprKind::Literal(lit) = self.db.lookup_intern_expr(unary.val).kind {
+                        return self.literal(unary.val, &lit, true, span);
+                    }
+                }
+
+                let val = self.eval(unary.val)?;
+                unary_op(op, val).map_err(|err| (err, span))
+            }
+            ExprKind::Binary(bin) => {
+                let op = *bin.op.data();
+                let left = self.eval(bin.left)?;
+                match (op, &left) {
+                    (BinOp::LogicalAnd, ConstValue::Bool(false)) => return Ok(left),
+                    (BinOp::LogicalOr, ConstValue::Bool(true)) => return Ok(left),
+                    _ => {}
+                }
+
+                let right = self.eval(bin.right)?;
+                binary_op(op, left, right).map_err(|err| (err, span))
+            }
+            ExprKind::Comparison(cmp) => {
+                let left = self.eval(cmp.left)?;
+                let right = self.eval(cmp.right)?;
+                compare(*cmp.op.data(), &left, &right)
+                    .map(ConstValue::Bool)
+                    .map_err(|err| (err, span))
+            }
+            ExprKind::Tuple(tuple) => tuple
+                .values
+                .into_iter()
+                .map(|val| self.eval(val))
+                .collect::<EvalResult<_>>()
+                .map(ConstValue::Tuple),
+            ExprKind::NamedTuple(tuple) => tuple
+                .values
+                .into_iter()
+                .map(|(name, val)| Ok((name.id, self.eval(val)?)))
+                .collect::<EvalResult<_>>()
+                .map(ConstValue::NamedTuple),
+            ExprKind::Path(_) => match self.results.resolutions.get(&id) {
+                Some(Res::Def(item)) if self.globals => {
+                    match self.db.lookup_intern_item(*item).kind {
+                        ItemKind::Const(_) => {
+                            self.db.const_value(*item).map_err(|err| match err.data() {
+                                Error::Const(const_err) => (const_err.clone(), err.span()),
+                                _ => (ConstError::NotConst, span),
+                            })
+                        }
+                        _ => Err((ConstError::NotConst, span)),
+                    }
+                }
+                _ => Err((ConstError::NotConst, span)),
+            },
+            _ => Err((ConstError::NotConst, span)),
+        }
+    }
+
+    /// Evaluates a literal, which may be prefixed by a `-`.
+    fn literal(
+        &self,
+        id: ExprId,
+        lit: &Literal,
+        negate: bool,
+        span: Span,
+    ) -> EvalResult<ConstValue> {
+        let kind = match self.results.expr_types.get(&id) {
+            Some(ty) => ty.kind(self.db),
+            None => return Err((ConstError::NotConst, span)),
+        };
+        eval_literal(lit, negate, &kind).map_err(|err| (err, span))
+    }
+}
+
+/// Evaluates a literal of the given type, which is negated if `negate` is `true`.
+///
+/// Negative integer literals inside patterns are represented by a
+/// `Literal::Int` where `signed` is `true`, which is handled like `negate`.
+pub fn eval_literal(lit: &Literal, negate: bool, ty: &TyKind) -> Result<ConstValue, ConstError> {
+    let val = match (lit, ty) {
+        (Literal::Int(int), TyKind::Int { width, signed }) => {
+            let (width, signed) = (*width, *signed);
+            let val = if negate != int.signed {
+                if int.val > i128::MIN.unsigned_abs() {
+                    None
+                } else {
+                    ConstInt::from_i128((int.val as i128).wrapping_neg(), width, signed)
+                }
+            } else {
+                ConstInt::from_u128(int.val, width, signed)
+            };
+
+            let ty = format!("{}{}", if signed { 'i' } else { 'u' }, width);
+            let val = val.ok_or(ConstError::LiteralOutOfRange { ty })?;
+            return Ok(ConstValue::Int(val));
+        }
+        (Literal::Float(float), TyKind::Float { width }) => ConstValue::Float {
+            val: OrderedFloat(round(float.val.into_inner(), *width)),
+            width: *width,
+        },
+        (Literal::Bool(val), _) => ConstValue::Bool(*val),
+        (Literal::Char(val), _) => ConstValue::Char(*val),
+        (Literal::String(val), _) => ConstValue::String(val.clone()),
+        (Literal::Unit, _) => ConstValue::Unit,
+        _ => return Err(ConstError::NotConst),
+    };
+
+    if negate {
+        unary_op(UnOp::Minus, val)
+    } else {
+        Ok(val)
+    }
+}
+
+/// Returns a mask for the lowest `width` bits.
+fn mask(width: u16) -> u128 {
+    u128::MAX >> (128 - width as u32)
+}
+
+/// Rounds a float to the precision of a float type with the given width.
+fn round(val: f64, width: u16) -> f64 {
+    if width == 32 {
+        val as f32 as f64
+    } else {
+        val
+    }
+}
+
+/// Applies a unary operator to a value.
+pub fn unary_op(op: UnOp, val: ConstValue) -> Result<ConstValue, ConstError> {
+    let val = match (op, val) {
+        (UnOp::Plus, val) => val,
+        (UnOp::Minus, ConstValue::Int(int)) => {
+            let neg = if int.signed {
+                int.as_i128().checked_neg()
+            } else if int.bits == 0 {
+                Some(0)
+            } else {
+                None
+            };
+            let neg = neg.and_then(|neg| ConstInt::from_i128(neg, int.width, int.signed));
+            ConstValue::Int(neg.ok_or_else(|| int.overflow())?)
+        }
+        (UnOp::Minus, ConstValue::Float { val, width }) => ConstValue::Float { val: -val, width },
+        (UnOp::Not, ConstValue::Int(int)) => ConstValue::Int(int.with_bits(!int.bits)),
+        (UnOp::Not, ConstValue::Bool(val)) => ConstValue::Bool(!val),
+        _ => return Err(ConstError::NotConst),
+    };
+    Ok(val)
+}
+
+/// Applies a binary operator to two values of the same type.
+pub fn binary_op(op: BinOp, left: ConstValue, right: ConstValue) -> Result<ConstValue, ConstError> {
+    let val = match (left, right) {
+        (ConstValue::Int(a), ConstValue::Int(b)) => ConstValue::Int(int_op(op, a, b)?),
+        (ConstValue::Float { val: a, width }, ConstValue::Float { val: b, .. }) => {
+            let (a, b) = (a.into_inner(), b.into_inner());
+            let val = match op {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul => a * b,
+                BinOp::Div => a / b,
+                BinOp::Mod => a % b,
+                BinOp::Pow => a.powf(b),
+                _ => return Err(ConstError::NotConst),
+            };
+            ConstValue::Float {
+                val: OrderedFloat(round(val, width)),
+                width,
+            }
+        }
+        (ConstValue::Bool(a), ConstValue::Bool(b)) => ConstValue::Bool(match op {
+            BinOp::LogicalAnd | BinOp::BitiwseAnd => a & b,
+            BinOp::LogicalOr | BinOp::BitwiseOr => a | b,
+            BinOp::BitwiseXor => a ^ b,
+            _ => return Err(ConstError::NotConst),
+        }),
+        _ => return Err(ConstError::NotConst),
+    };
+    Ok(val)
+}
+
+fn int_op(op: BinOp, a: ConstInt, b: ConstInt) -> Result<ConstInt, ConstError> {
+    match op {
+        BinOp::BitiwseAnd => return Ok(a.with_bits(a.bits & b.bits)),
+        BinOp::BitwiseOr => return Ok(a.with_bits(a.bits | b.bits)),
+        BinOp::BitwiseXor => return Ok(a.with_bits(a.bits ^ b.bits)),
+        BinOp::LeftShift | BinOp::RightShift => return shift(op, a, b),
+        BinOp::Div | BinOp::Mod if b.bits == 0 => {
+            return Err(match op {
+                BinOp::Div => ConstError::DivisionByZero,
+                _ => ConstError::RemainderByZero,
+            })
+        }
+        BinOp::Pow if b.is_negative() => return Err(ConstError::NegativeExponent),
+        _ => {}
+    }
+
+    let result = if a.signed {
+        let (x, y) = (a.as_i128(), b.as_i128());
+        let val = match op {
+            BinOp::Add => x.checked_add(y),
+            BinOp::Sub => x.checked_sub(y),
+            BinOp::Mul => x.checked_mul(y),
+            BinOp::Div => x.checked_div(y),
+            BinOp::Mod => x.checked_rem(y),
+            BinOp::Pow => pow_i128(x, b),
+            _ => return Err(ConstError::NotConst),
+        };
+        val.and_then(|val| ConstInt::from_i128(val, a.width, a.signed))
+    } else {
+        let (x, y) = (a.bits, b.bits);
+        let val = match op {
+            BinOp::Add => x.checked_add(y),
+            BinOp::Sub => x.checked_sub(y),
+            BinOp::Mul => x.checked_mul(y),
+            BinOp::Div => x.checked_div(y),
+            BinOp::Mod => x.checked_rem(y),
+            BinOp::Pow => pow_u128(x, b),
+            _ => return Err(ConstError::NotConst),
+        };
+        val.and_then(|val| ConstInt::from_u128(val, a.width, a.signed))
+    };
+
+    result.ok_or_else(|| a.overflow())
+}
+
+fn shift(op: BinOp, a: ConstInt, amount: ConstInt) -> Result<ConstInt, ConstError> {
+    if amount.is_negative() || amount.bits >= a.width as u128 {
+        return Err(ConstError::ShiftTooLarge {
+            ty: a.type_name(),
+            amount: amount.to_string(),
+        });
+    }
+
+    let amount = amount.bits as u32;
+    let bits = match op {
+        BinOp::LeftShift => a.bits << amount,
+        _ if a.signed => (a.as_i128() >> amount) as u128,
+        _ => a.bits >> amount,
+    };
+    Ok(a.with_bits(bits))
+}
+
+/// Returns `x ** exp` or `None` if the result doesn't fit into an `u128`.
+fn pow_u128(x: u128, exp: ConstInt) -> Option<u128> {
+    match (x, exp.bits) {
+        (0, 0) | (1, _) => Some(1),
+        (0, _) => Some(0),
+        (_, exp) if exp > u32::MAX as u128 => None,
+        (_, exp) => x.checked_pow(exp as u32),
+    }
+}
+
+/// Returns `x ** exp` or `None` if the result doesn't fit into an `i128`.
+fn pow_i128(x: i128, exp: ConstInt) -> Option<i128> {
+    let exp = exp.bits;
+    match x {
+        -1 if exp & 1 == 0 => Some(1),
+        -1 => Some(-1),
+        _ => {
+            let abs = pow_u128(x.unsigned_abs(), ConstInt::from_u128(exp, 128, false)?)?;
+            if x < 0 && exp & 1 == 1 {
+                0i128.checked_sub_unsigned(abs)
+            } else {
+                i128::try_from(abs).ok()
+            }
+        }
+    }
+}
+
+/// Compares two values using the given operator.
+pub fn compare(op: CmpOp, left: &ConstValue, right: &ConstValue) -> Result<bool, ConstError> {
+    let ordering = match (left, right) {
+        (ConstValue::Int(a), ConstValue::Int(b)) => Some(a.cmp(*b)),
+        (ConstValue::Float { val: a, .. }, ConstValue::Float { val: b, .. }) => {
+            a.into_inner().partial_cmp(&b.into_inner())
+        }
+        (ConstValue::Char(a), ConstValue::Char(b)) => Some(a.cmp(b)),
+        (ConstValue::Bool(a), ConstValue::Bool(b)) => Some(a.cmp(b)),
+        _ => match op {
+            CmpOp::Equal => return Ok(left == right),
+            CmpOp::NotEqual => return Ok(left != right),
+            _ => return Err(ConstError::NotConst),
+        },
+    };
+
+    let result = match ordering {
+        Some(ordering) => match op {
+            CmpOp::Equal => ordering == Ordering::Equal,
+            CmpOp::NotEqual => ordering != Ordering::Equal,
+            CmpOp::Less => ordering == Ordering::Less,
+            CmpOp::LessEqual => ordering != Ordering::Greater,
+            CmpOp::Greater => ordering == Ordering::Greater,
+            CmpOp::GreaterEqual => ordering != Ordering::Less,
+        },
+        // comparisons with NaN
+        None => op == CmpOp::NotEqual,
+    };
+    Ok(result)
+}
@@ -0,0 +1,98 @@
+//! The salsa database for type checking.
+
+use crate::{
+    check,
+    consteval::{self, ConstValue},
+    infer::{self, TypeckResults},
+    lower::{self, AdtDef, FnSig, Generics},
+    module::{self, ModuleTree},
+    scope::{self, FileScope},
+    traits,
+    ty::{Ty, TyKind},
+};
+use cell_common::{
+    error::{Error, Warning},
+    source::{FileId, Locatable},
+    strings::StringId,
+};
+use cell_parser::db::ParseDatabase;
+use cell_syntax::ast::ItemId;
+use std::sync::Arc;
+
+#[salsa::query_group(TypeckDatabaseStorage)]
+pub trait TypeckDatabase: ParseDatabase {
+    #[salsa::interned]
+    fn intern_ty(&self, ty: TyKind) -> Ty;
+
+    /// Returns the names of every module that is imported by the file.
+    #[salsa::invoke(module::module_imports)]
+    fn module_imports(&self, file: FileId) -> Arc<Vec<StringId>>;
+
+    /// Builds the tree of every module that is reachable from the root module.
+    #[salsa::invoke(module::module_tree)]
+    fn module_tree(&self) -> Arc<ModuleTree>;
+
+    /// Collects every item of the file that can be referenced by name.
+    #[salsa::invoke(scope::file_scope)]
+    fn file_scope(&self, file: FileId) -> Arc<FileScope>;
+
+    /// Returns the generic parameters of an item, including the
+    /// ones of the `impl` or `trait` it's defined in.
+    #[salsa::invoke(lower::generics_of)]
+    fn generics_of(&self, item: ItemId) -> Arc<Generics>;
+
+    /// Returns the type that an `impl` is implemented for.
+    #[salsa::invoke(lower::impl_self_ty)]
+    fn impl_self_ty(&self, item: ItemId) -> Ty;
+
+    /// Returns the signature of a function.
+    #[salsa::invoke(lower::fn_sig)]
+    fn fn_sig(&self, item: ItemId) -> Arc<FnSig>;
+
+    /// Returns the declared type of a `const` or `static`.
+    #[salsa::invoke(lower::global_ty)]
+    fn global_ty(&self, item: ItemId) -> Ty;
+
+    /// Evaluates the initializer of a `const` or `static`.
+    #[salsa::invoke(consteval::const_value)]
+    #[salsa::cycle(consteval::const_value_cycle)]
+    fn const_value(&self, item: ItemId) -> Result<ConstValue, Locatable<Error>>;
+
+    /// Returns the fields or variants of a `type` or `union`.
+    #[salsa::invoke(lower::adt_def)]
+    fn adt_def(&self, item: ItemId) -> Arc<AdtDef>;
+
+    /// Returns the trait that is implemented by an `impl`, if any.
+    #[salsa::invoke(traits::impl_trait)]
+    fn impl_trait(&self, item: ItemId) -> Option<ItemId>;
+
+    /// Returns every `impl` inside the file.
+    #[salsa::invoke(traits::impls)]
+    fn impls(&self, file: FileId) -> Arc<Vec<ItemId>>;
+
+    /// Returns every `impl` of every module in the module tree.
+    #[salsa::invoke(traits::crate_impls)]
+    fn crate_impls(&self) -> Arc<Vec<ItemId>>;
+
+    /// Infers the types inside the body of a function or the
+    /// initializer of a `const` or `static`.
+    #[salsa::invoke(infer::typeck)]
+    fn typeck(&self, item: ItemId) -> Arc<TypeckResults>;
+
+    /// Checks the signature of a single item.
+    #[salsa::invoke(check::check_item)]
+    fn check_item(&self, item: ItemId) -> Arc<Vec<Locatable<Error>>>;
+
+    /// Checks that every `impl` of the file is valid and that no impls overlap.
+    #[salsa::invoke(traits::check_impls)]
+    fn check_impls(&self, file: FileId) -> Arc<Vec<Locatable<Error>>>;
+
+    /// Runs every check on the file and returns all errors,
+    /// including syntax errors.
+    #[salsa::invoke(check::check_file)]
+    fn check_file(&self, file: FileId) -> Arc<Vec<Locatable<Error>>>;
+
+    /// Returns every warning of the file.
+    #[salsa::invoke(check::file_warnings)]
+    fn file_warnings(&self, file: FileId) -> Arc<Vec<Locatable<Warning>>>;
+}
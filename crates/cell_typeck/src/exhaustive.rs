@@ -0,0 +1,617 @@
+//! Checks that `match` expressions cover every possible value and
+//! finds arms that can never be reached.
+//!
+//! The implementation is based on the usefulness algorithm from
+//! "Warnings for pattern matching" by Luc Maranget. A pattern is useful
+//! with respect to a list of patterns, if it matches a value that none of
+//! the patterns in the list matches. An arm is unreachable if its pattern
+//! is not useful with respect to the arms above it, and a match is
+//! exhaustive if `else` is not useful with respect to all arms.
+
+use crate::{
+    consteval,
+    db::TypeckDatabase,
+    infer::TypeckResults,
+    lower::AdtDef,
+    ty::{Ty, TyKind},
+};
+use cell_common::{
+    error::{Error, TypeError, Warning},
+    source::{FileId, Locatable, Span},
+    strings::StringId,
+};
+use cell_syntax::ast::{ExprId, ExprKind, Identifier, Int, Literal, PatternId, PatternKind};
+
+/// The maximum number of missing patterns that are listed in an error.
+const MAX_WITNESSES: usize = 3;
+
+/// The sign bit of a `i128`, which is flipped to store signed
+/// integers in a `u128` without changing their order.
+const SIGN_BIT: u128 = 1 << 127;
+
+/// The highest value of a `char`.
+const CHAR_MAX: u128 = 0x10_FFFF;
+
+/// A constructor for values of a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Ctor {
+    /// The only constructor of tuples, named tuples, structs and `()`.
+    Single,
+    Bool(bool),
+    /// An inclusive range of integers or chars.
+    ///
+    /// Signed integers are stored with a flipped sign bit.
+    Range(u128, u128),
+    /// The variant of a union with the given index.
+    Variant(u32),
+    /// A literal of a type that has infinitely many values, like strings.
+    Opaque(Literal),
+    /// Matches every value.
+    Wildcard,
+}
+
+/// A pattern that was split into its constructor and its fields.
+#[derive(Debug, Clone)]
+struct Pat {
+    ctor: Ctor,
+    fields: Vec<Pat>,
+    ty: Ty,
+}
+
+impl Pat {
+    fn wild(ty: Ty) -> Self {
+        Self {
+            ctor: Ctor::Wildcard,
+            fields: vec![],
+            ty,
+        }
+    }
+}
+
+/// Checks the `match` expression for unreachable arms and missing patterns.
+pub(crate) fn check_match(
+    db: &dyn TypeckDatabase,
+    file: FileId,
+    results: &mut TypeckResults,
+    expr: ExprId,
+) {
+    let match_ = match db.lookup_intern_expr(expr).kind {
+        ExprKind::Match(match_) => match_,
+        _ => panic!("`check_match` called on an expression that is not a `match`"),
+    };
+
+    let scrutinee = results.type_of(match_.val);
+    if scrutinee.any(db, &mut |kind| *kind == TyKind::Error) {
+        return;
+    }
+
+    let cx = MatchCx { db, results };
+    let mut errors = vec![];
+    let rows = match_
+        .arms
+        .iter()
+        .map(|arm| cx.lower(arm.pattern, &mut errors))
+        .collect::<Vec<_>>();
+
+    if !errors.is_empty() {
+        let errors = errors
+            .into_iter()
+            .map(|(err, span)| Locatable::new(err, file, span));
+        results.errors.extend(errors);
+        return;
+    }
+
+    let mut warnings = vec![];
+    for (idx, (arm, row)) in match_.arms.iter().zip(&rows).enumerate() {
+        let above = rows[..idx]
+            .iter()
+            .map(|row| vec![row.clone()])
+            .collect::<Vec<_>>();
+        if cx.usefulness(&above, std::slice::from_ref(row)).is_empty() {
+            let span = db.lookup_intern_pattern(arm.pattern).span;
+            warnings.push(Locatable::new(Warning::UnreachablePattern, file, span));
+        }
+    }
+
+    let rows = rows.into_iter().map(|row| vec![row]).collect::<Vec<_>>();
+    let mut witnesses = Vec::<String>::new();
+    for witness in cx.usefulness(&rows, &[Pat::wild(scrutinee)]) {
+        let witness = cx.display(&witness[0]);
+        if !witnesses.contains(&witness) {
+            witnesses.push(witness);
+        }
+    }
+
+    if !witnesses.is_empty() {
+        let err = TypeError::NonExhaustive {
+            patterns: join_witnesses(&witnesses),
+        };
+        let span = db.lookup_intern_expr(match_.val).span;
+        results.errors.push(Locatable::new(err.into(), file, span));
+    }
+    results.warnings.extend(warnings);
+}
+
+/// Formats the missing patterns like "`A`, `B` and `C`".
+fn join_witnesses(witnesses: &[String]) -> String {
+    let quoted = witnesses
+        .iter()
+        .take(MAX_WITNESSES)
+        .map(|witness| format!("`{}`", witness))
+        .collect::<Vec<_>>();
+
+    match (quoted.split_last(), witnesses.len()) {
+        (Some((last, [])), _) => last.clone(),
+        (Some((last, rest)), len) if len <= MAX_WITNESSES => {
+            format!("{} and {}", rest.join(", "), last)
+        }
+        (_, len) => format!("{} and {} more", quoted.join(", "), len - MAX_WITNESSES),
+    }
+}
+
+struct MatchCx<'a> {
+    db: &'a dyn TypeckDatabase,
+    results: &'a TypeckResults,
+}
+
+impl MatchCx<'_> {
+    /// Splits an AST pattern into its constructor and fields.
+    fn lower(&self, id: PatternId, errors: &mut Vec<(Error, Span)>) -> Pat {
+        let pattern = self.db.lookup_intern_pattern(id);
+        let ty = self.results.pattern_types[&id];
+
+        let (ctor, fields) = match pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => (Ctor::Wildcard, vec![]),
+            PatternKind::Literal(lit) => {
+                self.check_literal(ty, &lit, pattern.span, errors);
+                (self.literal_ctor(ty, lit), vec![])
+            }
+            PatternKind::Range { start, end } => {
+                let start_fits = self.check_literal(ty, &start, pattern.span, errors);
+                let end_fits = self.check_literal(ty, &end, pattern.span, errors);
+                let (start, end) = (self.encode(ty, &start), self.encode(ty, &end));
+                if start_fits && end_fits && start > end {
+                    errors.push((TypeError::EmptyRangePattern.into(), pattern.span));
+                }
+                (Ctor::Range(start, end), vec![])
+            }
+            PatternKind::Tuple(pats) => {
+                let fields = pats
+                    .into_iter()
+                    .map(|pat| self.lower(pat, errors))
+                    .collect();
+                (Ctor::Single, fields)
+            }
+            PatternKind::NamedTuple(fields) | PatternKind::Struct { fields, .. } => {
+                let fields = self.lower_fields(ty, &fields, errors);
+                (Ctor::Single, fields)
+            }
+            PatternKind::Variant { data, .. } => {
+                let (_, idx) = self.results.pattern_variants[&id];
+                let ctor = Ctor::Variant(idx);
+                let fields = match (self.field_tys(ty, &ctor).first(), data) {
+                    (Some(_), Some(data)) => vec![self.lower(data, errors)],
+                    (Some(&data_ty), None) => vec![Pat::wild(data_ty)],
+                    (None, _) => vec![],
+                };
+                (ctor, fields)
+            }
+        };
+
+        Pat { ctor, fields, ty }
+    }
+
+    /// Lowers the fields of a struct or named tuple pattern, in the
+    /// order in which they are declared in the type.
+    ///
+    /// Fields that are not mentioned in the pattern match every value.
+    fn lower_fields(
+        &self,
+        ty: Ty,
+        fields: &[(Identifier, PatternId)],
+        errors: &mut Vec<(Error, Span)>,
+    ) -> Vec<Pat> {
+        self.field_names(ty)
+            .into_iter()
+            .zip(self.field_tys(ty, &Ctor::Single))
+            .map(
+                |(name, field_ty)| match fields.iter().find(|(field, _)| field.id == name) {
+                    Some((_, pat)) => self.lower(*pat, errors),
+                    None => Pat::wild(field_ty),
+                },
+            )
+            .collect()
+    }
+
+    /// Reports an integer literal that doesn't fit into the type of
+    /// its pattern, and returns whether it fits.
+    fn check_literal(
+        &self,
+        ty: Ty,
+        lit: &Literal,
+        span: Span,
+        errors: &mut Vec<(Error, Span)>,
+    ) -> bool {
+        let kind = ty.kind(self.db);
+        if !matches!((lit, &kind), (Literal::Int(_), TyKind::Int { .. })) {
+            return true;
+        }
+        match consteval::eval_literal(lit, false, &kind) {
+            Ok(_) => true,
+            Err(err) => {
+                errors.push((err.into(), span));
+                false
+            }
+        }
+    }
+
+    fn literal_ctor(&self, ty: Ty, lit: Literal) -> Ctor {
+        match lit {
+            Literal::Bool(val) => Ctor::Bool(val),
+            Literal::Unit => Ctor::Single,
+            Literal::Int(_) | Literal::Char(_) => {
+                let val = self.encode(ty, &lit);
+                Ctor::Range(val, val)
+            }
+            Literal::Float(_) | Literal::String(_) => Ctor::Opaque(lit),
+        }
+    }
+
+    /// Turns an integer or char literal into a value that can
+    /// be stored inside a `Ctor::Range`.
+    fn encode(&self, ty: Ty, lit: &Literal) -> u128 {
+        match (lit, ty.kind(self.db)) {
+            (Literal::Int(Int { signed, val }), TyKind::Int { signed: true, .. }) => {
+                let val = if *signed {
+                    (*val as i128).wrapping_neg()
+                } else {
+                    *val as i128
+                };
+                val as u128 ^ SIGN_BIT
+            }
+            (Literal::Int(Int { val, .. }), _) => *val,
+            (Literal::Char(c), _) => *c as u128,
+            _ => 0,
+        }
+    }
+
+    /// Returns every constructor of the type, or `None` if the type
+    /// has infinitely many constructors.
+    fn all_ctors(&self, ty: Ty) -> Option<Vec<Ctor>> {
+        let ctors = match ty.kind(self.db) {
+            TyKind::Bool => vec![Ctor::Bool(false), Ctor::Bool(true)],
+            TyKind::Int { .. } | TyKind::Char => {
+                let (min, max) = int_range(&ty.kind(self.db))?;
+                vec![Ctor::Range(min, max)]
+            }
+            TyKind::Unit | TyKind::Tuple(_) | TyKind::NamedTuple(_) => vec![Ctor::Single],
+            TyKind::Adt(item, _) => match &*self.db.adt_def(item) {
+                AdtDef::Struct { .. } => vec![Ctor::Single],
+                AdtDef::Union { variants } => {
+                    (0..variants.len() as u32).map(Ctor::Variant).collect()
+                }
+            },
+            // the type of a match without arms or a `return` in the scrutinee
+            TyKind::Never => vec![],
+            _ => return None,
+        };
+        Some(ctors)
+    }
+
+    /// Returns the types of the fields of the constructor.
+    fn field_tys(&self, ty: Ty, ctor: &Ctor) -> Vec<Ty> {
+        let db = self.db;
+        match (ty.kind(db), ctor) {
+            (TyKind::Tuple(tys), Ctor::Single) => tys,
+            (TyKind::NamedTuple(fields), Ctor::Single) => {
+                fields.into_iter().map(|(_, ty)| ty).collect()
+            }
+            (TyKind::Adt(item, args), _) => match (&*db.adt_def(item), ctor) {
+                (AdtDef::Struct { fields }, Ctor::Single) => {
+                    fields.iter().map(|(_, ty)| ty.subst(db, &args)).collect()
+                }
+                (AdtDef::Union { variants }, Ctor::Variant(idx)) => variants[*idx as usize]
+                    .1
+                    .map(|ty| ty.subst(db, &args))
+                    .into_iter()
+                    .collect(),
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Returns the names of the fields of a struct or named tuple.
+    fn field_names(&self, ty: Ty) -> Vec<StringId> {
+        match ty.kind(self.db) {
+            TyKind::NamedTuple(fields) => fields.into_iter().map(|(name, _)| name).collect(),
+            TyKind::Adt(item, _) => match &*self.db.adt_def(item) {
+                AdtDef::Struct { fields } => fields.iter().map(|(name, _)| *name).collect(),
+                AdtDef::Union { .. } => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Computes the values that are matched by `v`, but not by any row.
+    ///
+    /// Every returned witness contains one pattern per column. If the
+    /// result is empty, `v` is not useful.
+    fn usefulness(&self, rows: &[Vec<Pat>], v: &[Pat]) -> Vec<Vec<Pat>> {
+        let head = match v.first() {
+            Some(head) => head,
+            None if rows.is_empty() => return vec![vec![]],
+            None => return vec![],
+        };
+        let ty = head.ty;
+        let column = rows.iter().map(|row| &row[0].ctor).collect::<Vec<_>>();
+
+        if head.ctor != Ctor::Wildcard {
+            return split(&head.ctor, &column)
+                .into_iter()
+                .flat_map(|ctor| self.useful_specialized(rows, v, ctor))
+                .collect();
+        }
+
+        let all = self.all_ctors(ty);
+        let split = all
+            .iter()
+            .flatten()
+            .flat_map(|ctor| split(ctor, &column))
+            .collect::<Vec<_>>();
+        let missing = split
+            .iter()
+            .filter(|ctor| !column.iter().any(|head| covers(head, ctor)))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if all.is_some() && missing.is_empty() {
+            return split
+                .into_iter()
+                .flat_map(|ctor| self.useful_specialized(rows, v, ctor))
+                .collect();
+        }
+
+        // only the rows that start with a wildcard can match the missing constructors
+        let default = rows
+            .iter()
+            .filter(|row| row[0].ctor == Ctor::Wildcard)
+            .map(|row| row[1..].to_vec())
+            .collect::<Vec<_>>();
+        let witnesses = self.usefulness(&default, &v[1..]);
+        if witnesses.is_empty() {
+            return witnesses;
+        }
+
+        let seen_ctor = column.iter().any(|ctor| **ctor != Ctor::Wildcard);
+        let heads = match all {
+            Some(_) if seen_ctor || !matches!(split.first(), Some(Ctor::Range(..))) => {
+                merge_ranges(missing)
+                    .into_iter()
+                    .map(|ctor| Pat {
+                        fields: self
+                            .field_tys(ty, &ctor)
+                            .into_iter()
+                            .map(Pat::wild)
+                            .collect(),
+                        ctor,
+                        ty,
+                    })
+                    .collect()
+            }
+            _ => vec![Pat::wild(ty)],
+        };
+
+        witnesses
+            .into_iter()
+            .flat_map(|witness| {
+                heads.iter().map(move |head| {
+                    let mut pats = vec![head.clone()];
+                    pats.extend(witness.iter().cloned());
+                    pats
+                })
+            })
+            .collect()
+    }
+
+    /// Computes the usefulness of `v` for values that are created by `ctor`.
+    fn useful_specialized(&self, rows: &[Vec<Pat>], v: &[Pat], ctor: Ctor) -> Vec<Vec<Pat>> {
+        let ty = v[0].ty;
+        let field_tys = self.field_tys(ty, &ctor);
+        let rows = rows
+            .iter()
+            .filter_map(|row| specialize(row, &ctor, &field_tys))
+            .collect::<Vec<_>>();
+        let v = specialize(v, &ctor, &field_tys).expect("`ctor` was created from `v`");
+
+        self.usefulness(&rows, &v)
+            .into_iter()
+            .map(|mut witness| {
+                let rest = witness.split_off(field_tys.len());
+                let mut pats = vec![Pat {
+                    ctor: ctor.clone(),
+                    fields: witness,
+                    ty,
+                }];
+                pats.extend(rest);
+                pats
+            })
+            .collect()
+    }
+
+    /// Formats a pattern the way it would be written in the source.
+    fn display(&self, pat: &Pat) -> String {
+        let db = self.db;
+        let kind = pat.ty.kind(db);
+        let name = |id| db.rodeo().resolve(&id).to_string();
+        let fields = |names: Vec<_>| {
+            names
+                .into_iter()
+                .zip(&pat.fields)
+                .map(|(field, pat)| format!("{}: {}", name(field), self.display(pat)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match &pat.ctor {
+            Ctor::Wildcard => "else".to_string(),
+            Ctor::Bool(val) => val.to_string(),
+            Ctor::Range(start, end) if start == end => decode(&kind, *start),
+            Ctor::Range(start, end) => {
+                format!("{}..{}", decode(&kind, *start), decode(&kind, *end))
+            }
+            Ctor::Opaque(Literal::String(val)) => format!("{:?}", val),
+            Ctor::Opaque(Literal::Float(val)) => val.val.to_string(),
+            Ctor::Opaque(_) => "else".to_string(),
+            Ctor::Variant(idx) => {
+                let union = match kind {
+                    TyKind::Adt(item, _) => item,
+                    _ => unreachable!("variant of a type that is not a union"),
+                };
+                let union_name = db.lookup_intern_item(union).kind.name().unwrap().id;
+                let variant = match &*db.adt_def(union) {
+                    AdtDef::Union { variants } => variants[*idx as usize].0,
+                    AdtDef::Struct { .. } => unreachable!("variant of a struct"),
+                };
+                let path = format!("{}:{}", name(union_name), name(variant));
+                match pat.fields.first() {
+                    Some(data) if data.ctor != Ctor::Wildcard => {
+                        format!("{} = {}", path, self.display(data))
+                    }
+                    _ => path,
+                }
+            }
+            Ctor::Single => match kind {
+                TyKind::Unit => "()".to_string(),
+                TyKind::Tuple(_) => {
+                    let pats = pat
+                        .fields
+                        .iter()
+                        .map(|pat| self.display(pat))
+                        .collect::<Vec<_>>();
+                    format!("{{{}}}", pats.join(", "))
+                }
+                TyKind::NamedTuple(_) => format!("{{{}}}", fields(self.field_names(pat.ty))),
+                TyKind::Adt(item, _) => {
+                    let struct_name = db.lookup_intern_item(item).kind.name().unwrap().id;
+                    format!(
+                        "{} {{{}}}",
+                        name(struct_name),
+                        fields(self.field_names(pat.ty))
+                    )
+                }
+                _ => "else".to_string(),
+            },
+        }
+    }
+}
+
+/// Returns the smallest and largest value of an integer or char type.
+fn int_range(kind: &TyKind) -> Option<(u128, u128)> {
+    match *kind {
+        TyKind::Int {
+            width,
+            signed: false,
+        } => Some((0, u128::MAX >> (128 - width as u32))),
+        TyKind::Int {
+            width,
+            signed: true,
+        } => {
+            let min = i128::MIN >> (128 - width as u32);
+            let max = i128::MAX >> (128 - width as u32);
+            Some((min as u128 ^ SIGN_BIT, max as u128 ^ SIGN_BIT))
+        }
+        TyKind::Char => Some((0, CHAR_MAX)),
+        _ => None,
+    }
+}
+
+/// Turns a value of a `Ctor::Range` back into the source representation.
+fn decode(kind: &TyKind, val: u128) -> String {
+    match kind {
+        TyKind::Int { signed: true, .. } => ((val ^ SIGN_BIT) as i128).to_string(),
+        TyKind::Char => match std::char::from_u32(val as u32) {
+            Some(c) => format!("{:?}", c),
+            None => format!("'\\u{{{:x}}}'", val),
+        },
+        _ => val.to_string(),
+    }
+}
+
+/// Checks if every value of `ctor` is matched by `head`.
+///
+/// Wildcards are ignored, because they don't contribute a constructor.
+fn covers(head: &Ctor, ctor: &Ctor) -> bool {
+    match (head, ctor) {
+        (Ctor::Wildcard, _) => false,
+        (Ctor::Range(a, b), Ctor::Range(c, d)) => a <= c && d <= b,
+        (head, ctor) => head == ctor,
+    }
+}
+
+/// Splits a constructor into smaller ones, so that every part is either
+/// completely covered or not covered at all by each constructor of the column.
+///
+/// Only ranges can be split, every other constructor is returned as is.
+fn split(ctor: &Ctor, column: &[&Ctor]) -> Vec<Ctor> {
+    let (lo, hi) = match *ctor {
+        Ctor::Range(lo, hi) => (lo, hi),
+        _ => return vec![ctor.clone()],
+    };
+
+    let mut points = vec![lo];
+    for head in column {
+        if let Ctor::Range(start, end) = **head {
+            if start > lo && start <= hi {
+                points.push(start);
+            }
+            match end.checked_add(1) {
+                Some(next) if next > lo && next <= hi => points.push(next),
+                _ => {}
+            }
+        }
+    }
+    points.sort_unstable();
+    points.dedup();
+
+    points
+        .iter()
+        .enumerate()
+        .map(|(idx, &start)| {
+            let end = points.get(idx + 1).map_or(hi, |next| next - 1);
+            Ctor::Range(start, end)
+        })
+        .collect()
+}
+
+/// Merges adjacent ranges, to report missing ranges as a whole.
+fn merge_ranges(ctors: Vec<Ctor>) -> Vec<Ctor> {
+    let mut merged: Vec<Ctor> = vec![];
+    for ctor in ctors {
+        match (merged.last_mut(), &ctor) {
+            (Some(Ctor::Range(_, end)), Ctor::Range(start, new_end))
+                if end.checked_add(1) == Some(*start) =>
+            {
+                *end = *new_end;
+            }
+            _ => merged.push(ctor),
+        }
+    }
+    merged
+}
+
+/// Specializes a row for the given constructor.
+///
+/// Returns `None` if the first pattern of the row can't match values
+/// created by `ctor`, otherwise the first pattern is replaced by its fields.
+fn specialize(row: &[Pat], ctor: &Ctor, field_tys: &[Ty]) -> Option<Vec<Pat>> {
+    let head = &row[0];
+    let mut pats = match (&head.ctor, ctor) {
+        (Ctor::Wildcard, _) => field_tys.iter().map(|ty| Pat::wild(*ty)).collect(),
+        (Ctor::Range(..), Ctor::Range(..)) if covers(&head.ctor, ctor) => vec![],
+        (head_ctor, ctor) if head_ctor == ctor => head.fields.clone(),
+        _ => return None,
+    };
+    pats.extend_from_slice(&row[1..]);
+    Some(pats)
+}
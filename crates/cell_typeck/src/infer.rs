@@ -0,0 +1,1373 @@
+//! Type inference and checking of function bodies.
+
+use crate::{
+    consteval,
+    db::TypeckDatabase,
+    exhaustive,
+    lower::{AdtDef, FnSig, Generics, TyLowerer},
+    prelude, privacy, scope, traits,
+    ty::{InferVar, Ty, TyKind},
+    unify::InferTable,
+};
+use cell_common::{
+    error::{Error, TypeError, Warning},
+    profiler,
+    source::{FileId, Locatable, Span},
+    strings::StringId,
+};
+use cell_syntax::ast::{
+    Assign, BinOp, Binary, Block, Call, CmpOp, Comparison, Expr, ExprId, ExprKind, Field,
+    FieldName, Identifier, If, ItemId, ItemKind, Literal, Match, MethodCall, NamedTuple, Path,
+    PatternId, PatternKind, StmtId, StmtKind, Tuple, UnOp, Unary, While,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// An index into the locals of a function.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LocalId(pub u32);
+
+/// A local variable, function parameter or `this`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Local {
+    pub name: StringId,
+    pub ty: Ty,
+    pub span: Span,
+}
+
+/// What a path expression refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Res {
+    Local(LocalId),
+    Def(ItemId),
+    /// A variant of a union, together with the index of the variant.
+    Variant(ItemId, u32),
+}
+
+/// The function that is called by a call or method call expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Callee {
+    /// A function or method together with the arguments for
+    /// every generic parameter of it.
+    ///
+    /// Calls to trait methods point to the method inside the trait,
+    /// use [`resolve_instance`] to get the method that will be executed.
+    ///
+    /// [`resolve_instance`]: ../traits/fn.resolve_instance.html
+    Fn { def: ItemId, args: Vec<Ty> },
+    /// The construction of a union variant using `Union:Variant.new(data)`.
+    Variant {
+        union: ItemId,
+        variant: u32,
+        args: Vec<Ty>,
+    },
+}
+
+/// How the receiver of a method call has to be adjusted
+/// before it's passed as `this`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Receiver {
+    /// How often the receiver is dereferenced.
+    pub derefs: u32,
+    /// If a pointer to the dereferenced receiver is taken.
+    pub autoref: bool,
+}
+
+/// The results of type checking a single function body.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeckResults {
+    pub locals: Vec<Local>,
+    /// The local that stores `this`, if this function is a method.
+    pub this: Option<LocalId>,
+    /// The locals of every parameter.
+    pub params: Vec<LocalId>,
+    pub expr_types: HashMap<ExprId, Ty>,
+    /// The resolution of every path expression.
+    pub resolutions: HashMap<ExprId, Res>,
+    /// The callee of every call and method call.
+    pub callees: HashMap<ExprId, Callee>,
+    /// Adjustments for the receiver of every method call.
+    pub receivers: HashMap<ExprId, Receiver>,
+    /// The index of the field that is accessed by a field expression.
+    pub fields: HashMap<ExprId, u32>,
+    /// The local that is introduced by every `var` statement.
+    pub vars: HashMap<StmtId, LocalId>,
+    /// The type of the values that are matched by every pattern.
+    pub pattern_types: HashMap<PatternId, Ty>,
+    /// The local that is introduced by every binding pattern.
+    pub bindings: HashMap<PatternId, LocalId>,
+    /// The union and variant index of every variant pattern.
+    pub pattern_variants: HashMap<PatternId, (ItemId, u32)>,
+    pub errors: Vec<Locatable<Error>>,
+    pub warnings: Vec<Locatable<Warning>>,
+}
+
+impl TypeckResults {
+    /// Returns the type of the given expression.
+    pub fn type_of(&self, expr: ExprId) -> Ty {
+        self.expr_types[&expr]
+    }
+
+    /// Returns the local with the given id.
+    pub fn local(&self, id: LocalId) -> &Local {
+        &self.locals[id.0 as usize]
+    }
+}
+
+pub(crate) fn typeck(db: &dyn TypeckDatabase, item: ItemId) -> Arc<TypeckResults> {
+    let _profiler = profiler::trace("Typeck", "Typeck");
+    Arc::new(typeck_with_locals(db, item, &[]))
+}
+
+/// Type checks the body of a function, where the given locals are
+/// declared before `this` and the parameters, so they are the first
+/// locals of the results.
+///
+/// This is used by the REPL, where every input is checked as a new function
+/// that can use the variables of the earlier inputs. Unlike the `typeck`
+/// query, the results are not cached.
+pub fn typeck_with_locals(
+    db: &dyn TypeckDatabase,
+    item: ItemId,
+    locals: &[(StringId, Ty)],
+) -> TypeckResults {
+    let kind = db.lookup_intern_item(item).kind;
+    let sig = match &kind {
+        // the initializer of a global is checked like the body of a function
+        // that returns the type of the global
+        ItemKind::Const(_) | ItemKind::Static(_) => Arc::new(FnSig {
+            params: vec![],
+            ret: db.global_ty(item),
+            this: None,
+        }),
+        _ => db.fn_sig(item),
+    };
+    let mut ctx = InferCtxt {
+        db,
+        item,
+        file: db.lookup_intern_item(item).file,
+        generics: db.generics_of(item),
+        sig,
+        table: InferTable::new(db),
+        results: TypeckResults::default(),
+        scopes: vec![vec![]],
+        loops: vec![],
+        obligations: vec![],
+        matches: vec![],
+    };
+
+    match kind {
+        ItemKind::Def(def) => {
+            for &(name, ty) in locals {
+                ctx.declare(name, ty, def.name.span);
+            }
+            if let Some(this) = ctx.sig.this {
+                let name = db.rodeo().get_or_intern("this");
+                let local = ctx.new_local(name, this, def.name.span);
+                ctx.results.this = Some(local);
+            }
+            for ((name, _), ty) in def.args.iter().zip(ctx.sig.params.clone()) {
+                let local = ctx.declare(name.id, ty, name.span);
+                ctx.results.params.push(local);
+            }
+
+            if let Some(body) = &def.body {
+                let ty = ctx.check_block(body);
+                // the functions of the prelude that are implemented by
+                // the host have empty bodies
+                let ret = ctx.kind(ctx.sig.ret);
+                if ctx.kind(ty) != TyKind::Never
+                    && !matches!(ret, TyKind::Unit | TyKind::Error)
+                    && prelude::host_fn(db, item).is_none()
+                {
+                    let err = TypeError::MissingReturn {
+                        name: db.rodeo().resolve(&def.name.id).to_string(),
+                        ty: ctx.display(ctx.sig.ret),
+                    };
+                    ctx.error(err, def.name.span);
+                }
+            }
+        }
+        ItemKind::Const(global) | ItemKind::Static(global) => {
+            let ty = ctx.sig.ret;
+            ctx.infer_expr(global.val, Some(ty));
+        }
+        _ => panic!("`typeck` called on an item without a body"),
+    }
+
+    ctx.finish()
+}
+
+/// Stores the state while checking a function body.
+struct InferCtxt<'db> {
+    db: &'db dyn TypeckDatabase,
+    item: ItemId,
+    file: FileId,
+    generics: Arc<Generics>,
+    sig: Arc<FnSig>,
+    table: InferTable<'db>,
+    results: TypeckResults,
+    /// The locals that are visible, grouped by block.
+    scopes: Vec<Vec<(StringId, LocalId)>>,
+    /// Whether each loop around the current expression contains a `break`
+    /// that leaves it, from the outermost to the innermost one.
+    loops: Vec<bool>,
+    /// Types that have to implement a trait, which is checked
+    /// once every type is inferred.
+    obligations: Vec<(Ty, ItemId, Span)>,
+    /// Every `match` expression whose exhaustiveness is checked
+    /// once every type is inferred.
+    matches: Vec<ExprId>,
+}
+
+impl<'db> InferCtxt<'db> {
+    fn error(&mut self, err: TypeError, span: Span) -> Ty {
+        self.results
+            .errors
+            .push(Locatable::new(err.into(), self.file, span));
+        self.ty(TyKind::Error)
+    }
+
+    fn ty(&self, kind: TyKind) -> Ty {
+        kind.intern(self.db)
+    }
+
+    fn name(&self, id: StringId) -> String {
+        self.db.rodeo().resolve(&id).to_string()
+    }
+
+    fn display(&self, ty: Ty) -> String {
+        self.table.resolve(ty).display(self.db)
+    }
+
+    fn kind(&self, ty: Ty) -> TyKind {
+        self.table.shallow_resolve(ty).kind(self.db)
+    }
+
+    fn new_local(&mut self, name: StringId, ty: Ty, span: Span) -> LocalId {
+        let id = LocalId(self.results.locals.len() as u32);
+        self.results.locals.push(Local { name, ty, span });
+        id
+    }
+
+    /// Creates a new local and makes it visible in the current scope.
+    fn declare(&mut self, name: StringId, ty: Ty, span: Span) -> LocalId {
+        let id = self.new_local(name, ty, span);
+        self.scopes.last_mut().unwrap().push((name, id));
+        id
+    }
+
+    fn lookup_local(&self, name: StringId) -> Option<LocalId> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(local, _)| *local == name)
+            .map(|(_, id)| *id)
+    }
+
+    /// Reports an error if `actual` can not be used where `expected` is required.
+    fn coerce(&mut self, span: Span, actual: Ty, expected: Ty) {
+        if self.kind(actual) == TyKind::Never {
+            return;
+        }
+        if !self.table.unify(actual, expected) {
+            let err = TypeError::Mismatch {
+                expected: self.display(expected),
+                found: self.display(actual),
+            };
+            self.error(err, span);
+        }
+    }
+
+    /// Checks every statement inside the block in a new scope.
+    ///
+    /// Returns `!` if one of the statements never finishes, and `()` otherwise.
+    fn check_block(&mut self, block: &Block) -> Ty {
+        self.scopes.push(vec![]);
+        let mut diverges = false;
+        for &stmt in &block.stmts {
+            let ty = self.check_stmt(stmt);
+            diverges |= self.kind(ty) == TyKind::Never;
+        }
+        self.scopes.pop();
+        self.ty(if diverges {
+            TyKind::Never
+        } else {
+            TyKind::Unit
+        })
+    }
+
+    /// Checks a statement and returns the type of its value.
+    fn check_stmt(&mut self, id: StmtId) -> Ty {
+        let stmt = self.db.lookup_intern_stmt(id);
+        match stmt.kind {
+            StmtKind::Var(var) => {
+                let ty = match &var.ty {
+                    Some(ty) => {
+                        let mut lowerer = TyLowerer::for_item(self.db, self.item);
+                        let ty = lowerer.lower_type(ty);
+                        self.results.errors.extend(lowerer.finish());
+                        self.infer_expr(var.val, Some(ty));
+                        ty
+                    }
+                    None => self.infer_expr(var.val, None),
+                };
+                let local = self.declare(var.name.id, ty, var.name.span);
+                self.results.vars.insert(id, local);
+                let val = self.results.expr_types[&var.val];
+                if self.kind(val) == TyKind::Never {
+                    val
+                } else {
+                    self.ty(TyKind::Unit)
+                }
+            }
+            StmtKind::Expr(expr) => self.infer_expr(expr, None),
+        }
+    }
+
+    /// Infers the type of an expression and checks that it's compatible
+    /// with the expected type, if there is one.
+    fn infer_expr(&mut self, id: ExprId, expected: Option<Ty>) -> Ty {
+        let expr = self.db.lookup_intern_expr(id);
+        let ty = self.infer_expr_inner(id, &expr, expected);
+        self.results.expr_types.insert(id, ty);
+        if let Some(expected) = expected {
+            self.coerce(expr.span, ty, expected);
+        }
+        ty
+    }
+
+    fn infer_expr_inner(&mut self, id: ExprId, expr: &Expr, expected: Option<Ty>) -> Ty {
+        match &expr.kind {
+            ExprKind::Literal(lit) => self.infer_literal(lit),
+            ExprKind::Binary(bin) => self.infer_binary(bin),
+            ExprKind::Comparison(cmp) => self.infer_comparison(cmp),
+            ExprKind::Unary(unary) => self.infer_unary(unary, expected),
+            ExprKind::Tuple(tuple) => self.infer_tuple(tuple, expected),
+            ExprKind::NamedTuple(tuple) => self.infer_named_tuple(expr.span, tuple, expected),
+            ExprKind::If(if_) => self.infer_if(if_),
+            ExprKind::Match(match_) => self.infer_match(id, match_, expected),
+            ExprKind::While(while_) => self.infer_while(while_),
+            ExprKind::Block(block) => self.check_block(block),
+            ExprKind::Path(path) => self.infer_path(id, expr.span, path),
+            ExprKind::Call(call) => self.infer_call(id, expr.span, call),
+            ExprKind::MethodCall(call) => self.infer_method_call(id, expr.span, call),
+            ExprKind::Field(field) => self.infer_field(id, expr.span, field),
+            ExprKind::Assign(assign) => self.infer_assign(expr.span, assign),
+            ExprKind::Return(val) => {
+                let ret = self.sig.ret;
+                match val {
+                    Some(val) => {
+                        self.infer_expr(*val, Some(ret));
+                    }
+                    None => {
+                        let unit = self.ty(TyKind::Unit);
+                        self.coerce(expr.span, unit, ret);
+                    }
+                }
+                self.ty(TyKind::Never)
+            }
+            ExprKind::Break | ExprKind::Continue => {
+                match self.loops.last_mut() {
+                    Some(breaks) => *breaks |= matches!(expr.kind, ExprKind::Break),
+                    None => {
+                        let keyword = match expr.kind {
+                            ExprKind::Break => "break",
+                            _ => "continue",
+                        };
+                        let err = TypeError::OutsideOfLoop {
+                            keyword: keyword.to_string(),
+                        };
+                        self.error(err, expr.span);
+                    }
+                }
+                self.ty(TyKind::Never)
+            }
+            ExprKind::This => match self.results.this {
+                Some(this) => self.results.local(this).ty,
+                None => self.error(TypeError::ThisOutsideMethod, expr.span),
+            },
+        }
+    }
+
+    fn infer_literal(&mut self, lit: &Literal) -> Ty {
+        match lit {
+            Literal::Int(_) => self.table.new_int_var(),
+            Literal::Float(_) => self.table.new_float_var(),
+            Literal::Bool(_) => self.ty(TyKind::Bool),
+            Literal::String(_) => self.ty(TyKind::String),
+            Literal::Char(_) => self.ty(TyKind::Char),
+            Literal::Unit => self.ty(TyKind::Unit),
+        }
+    }
+
+    fn infer_binary(&mut self, bin: &Binary) -> Ty {
+        let op = *bin.op.data();
+        match op {
+            BinOp::LogicalAnd | BinOp::LogicalOr => {
+                let bool_ = self.ty(TyKind::Bool);
+                self.infer_expr(bin.left, Some(bool_));
+                self.infer_expr(bin.right, Some(bool_));
+                bool_
+            }
+            _ => {
+                let left = self.infer_expr(bin.left, None);
+                self.infer_arithmetic(op, bin.op.span(), left, bin.right)
+            }
+        }
+    }
+
+    /// Checks a binary operator that is not a logical operator.
+    ///
+    /// This is also used for compound assignments like `+=`.
+    fn infer_arithmetic(&mut self, op: BinOp, span: Span, left: Ty, right: ExprId) -> Ty {
+        match (op, self.kind(left)) {
+            // pointer arithmetic
+            (BinOp::Add, TyKind::Pointer(_)) | (BinOp::Sub, TyKind::Pointer(_)) => {
+                let int = self.table.new_int_var();
+                self.infer_expr(right, Some(int));
+                left
+            }
+            (BinOp::LeftShift, _) | (BinOp::RightShift, _) => {
+                let int = self.table.new_int_var();
+                self.infer_expr(right, Some(int));
+                self.require(op, span, left, is_int);
+                left
+            }
+            (BinOp::BitiwseAnd, _) | (BinOp::BitwiseOr, _) | (BinOp::BitwiseXor, _) => {
+                self.infer_expr(right, Some(left));
+                self.require(op, span, left, |kind| is_int(kind) || *kind == TyKind::Bool);
+                left
+            }
+            _ => {
+                self.infer_expr(right, Some(left));
+                self.require(op, span, left, is_numeric);
+                left
+            }
+        }
+    }
+
+    /// Reports an error if `check` returns `false` for the type.
+    ///
+    /// Types that are not known yet, or are errors, are always accepted.
+    fn require(
+        &mut self,
+        op: impl std::fmt::Display,
+        span: Span,
+        ty: Ty,
+        check: impl Fn(&TyKind) -> bool,
+    ) {
+        let kind = self.kind(ty);
+        let unknown = matches!(
+            kind,
+            TyKind::Error | TyKind::Never | TyKind::Infer(InferVar::General(_))
+        );
+        if !unknown && !check(&kind) {
+            let err = TypeError::InvalidOperand {
+                op: op.to_string(),
+                ty: self.display(ty),
+            };
+            self.error(err, span);
+        }
+    }
+
+    fn infer_comparison(&mut self, cmp: &Comparison) -> Ty {
+        let left = self.infer_expr(cmp.left, None);
+        self.infer_expr(cmp.right, Some(left));
+
+        let op = *cmp.op.data();
+        if !matches!(op, CmpOp::Equal | CmpOp::NotEqual) {
+            self.require(op, cmp.op.span(), left, |kind| {
+                is_numeric(kind) || *kind == TyKind::Char
+            });
+        }
+        self.ty(TyKind::Bool)
+    }
+
+    fn infer_unary(&mut self, unary: &Unary, expected: Option<Ty>) -> Ty {
+        let op = *unary.op.data();
+        let span = unary.op.span();
+        match op {
+            UnOp::Ref => {
+                let expected = expected.and_then(|ty| match self.kind(ty) {
+                    TyKind::Pointer(inner) => Some(inner),
+                    _ => None,
+                });
+                let inner = self.infer_expr(unary.val, expected);
+                self.ty(TyKind::Pointer(inner))
+            }
+            UnOp::Deref => {
+                let ty = self.infer_expr(unary.val, None);
+                match self.kind(ty) {
+                    TyKind::Pointer(inner) => inner,
+                    TyKind::Error => ty,
+                    TyKind::Infer(InferVar::General(_)) => {
+                        let inner = self.table.new_var();
+                        let ptr = self.ty(TyKind::Pointer(inner));
+                        self.table.unify(ty, ptr);
+                        inner
+                    }
+                    _ => {
+                        let err = TypeError::InvalidOperand {
+                            op: op.to_string(),
+                            ty: self.display(ty),
+                        };
+                        self.error(err, span)
+                    }
+                }
+            }
+            UnOp::Not => {
+                let ty = self.infer_expr(unary.val, expected);
+                self.require(op, span, ty, |kind| is_int(kind) || *kind == TyKind::Bool);
+                ty
+            }
+            UnOp::Plus | UnOp::Minus => {
+                let ty = self.infer_expr(unary.val, expected);
+                self.require(op, span, ty, is_numeric);
+                ty
+            }
+        }
+    }
+
+    fn infer_tuple(&mut self, tuple: &Tuple, expected: Option<Ty>) -> Ty {
+        let expected = match expected.map(|ty| self.kind(ty)) {
+            Some(TyKind::Tuple(tys)) if tys.len() == tuple.values.len() => {
+                tys.into_iter().map(Some).collect()
+            }
+            _ => vec![None; tuple.values.len()],
+        };
+
+        let tys = tuple
+            .values
+            .iter()
+            .zip(expected)
+            .map(|(val, expected)| self.infer_expr(*val, expected))
+            .collect();
+        self.ty(TyKind::Tuple(tys))
+    }
+
+    /// Infers the type of a named tuple.
+    ///
+    /// If the expected type is a `type` definition, the named tuple
+    /// is used to construct it.
+    fn infer_named_tuple(&mut self, span: Span, tuple: &NamedTuple, expected: Option<Ty>) -> Ty {
+        if let Some((expected, TyKind::Adt(item, args))) = expected.map(|ty| (ty, self.kind(ty))) {
+            if let AdtDef::Struct { fields } = &*self.db.adt_def(item) {
+                for (name, val) in &tuple.values {
+                    match fields.iter().position(|(field, _)| *field == name.id) {
+                        Some(idx) => {
+                            self.check_field_access(item, idx, expected, name);
+                            let ty = fields[idx].1.subst(self.db, &args);
+                            self.infer_expr(*val, Some(ty));
+                        }
+                        None => {
+                            self.infer_expr(*val, None);
+                            let err = TypeError::NoField {
+                                ty: self.display(expected),
+                                field: self.name(name.id),
+                            };
+                            self.error(err, name.span);
+                        }
+                    }
+                }
+
+                for (field, _) in fields {
+                    if !tuple.values.iter().any(|(name, _)| name.id == *field) {
+                        let err = TypeError::MissingField {
+                            ty: self.display(expected),
+                            field: self.name(*field),
+                        };
+                        self.error(err, span);
+                    }
+                }
+                return expected;
+            }
+        }
+
+        let expected = match expected.map(|ty| self.kind(ty)) {
+            Some(TyKind::NamedTuple(fields)) => fields,
+            _ => vec![],
+        };
+        let fields = tuple
+            .values
+            .iter()
+            .map(|(name, val)| {
+                let expected = expected
+                    .iter()
+                    .find(|(field, _)| *field == name.id)
+                    .map(|(_, ty)| *ty);
+                (name.id, self.infer_expr(*val, expected))
+            })
+            .collect();
+        self.ty(TyKind::NamedTuple(fields))
+    }
+
+    /// Returns `!` if there is an `else` and every branch never finishes.
+    fn infer_if(&mut self, if_: &If) -> Ty {
+        let bool_ = self.ty(TyKind::Bool);
+        self.infer_expr(if_.cond, Some(bool_));
+        let mut branches = vec![self.check_block(&if_.then)];
+        for arm in &if_.arms {
+            self.infer_expr(arm.cond, Some(bool_));
+            branches.push(self.check_block(&arm.action));
+        }
+        let diverges = match &if_.else_ {
+            Some(else_) => {
+                branches.push(self.check_block(else_));
+                branches.iter().all(|&ty| self.kind(ty) == TyKind::Never)
+            }
+            None => false,
+        };
+        self.ty(if diverges {
+            TyKind::Never
+        } else {
+            TyKind::Unit
+        })
+    }
+
+    fn infer_while(&mut self, while_: &While) -> Ty {
+        if let Some(cond) = while_.cond {
+            let bool_ = self.ty(TyKind::Bool);
+            self.infer_expr(cond, Some(bool_));
+        }
+        self.loops.push(false);
+        self.check_block(&while_.block);
+        let breaks = self.loops.pop().unwrap();
+        // a loop without a condition only finishes by breaking out of it
+        if while_.cond.is_none() && !breaks {
+            self.ty(TyKind::Never)
+        } else {
+            self.ty(TyKind::Unit)
+        }
+    }
+
+    fn infer_match(&mut self, id: ExprId, match_: &Match, expected: Option<Ty>) -> Ty {
+        let scrutinee = self.infer_expr(match_.val, None);
+        let result = expected.unwrap_or_else(|| self.table.new_var());
+
+        let errors = self.results.errors.len();
+        let mut diverges = true;
+        for arm in &match_.arms {
+            self.scopes.push(vec![]);
+            self.check_pattern(arm.pattern, scrutinee);
+            let ty = self.infer_expr(arm.body, Some(result));
+            if self.kind(ty) != TyKind::Never {
+                diverges = false;
+            }
+            self.scopes.pop();
+        }
+
+        // exhaustiveness can only be checked if the patterns are valid
+        if self.results.errors.len() == errors {
+            self.matches.push(id);
+        }
+
+        if match_.arms.is_empty() {
+            self.ty(TyKind::Unit)
+        } else if diverges {
+            self.ty(TyKind::Never)
+        } else {
+            result
+        }
+    }
+
+    /// Checks that the pattern can match values of the expected type
+    /// and declares every binding inside the pattern.
+    fn check_pattern(&mut self, id: PatternId, expected: Ty) {
+        let pattern = self.db.lookup_intern_pattern(id);
+        let span = pattern.span;
+        self.results.pattern_types.insert(id, expected);
+
+        match pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Binding(name) => {
+                let local = self.declare(name.id, expected, name.span);
+                self.results.bindings.insert(id, local);
+            }
+            PatternKind::Literal(lit) => {
+                let ty = self.infer_literal(&lit);
+                self.coerce(span, ty, expected);
+            }
+            PatternKind::Range { start, end } => {
+                for lit in &[start, end] {
+                    let ty = self.infer_literal(lit);
+                    self.coerce(span, ty, expected);
+                }
+                let kind = self.kind(expected);
+                if !is_int(&kind) && !matches!(kind, TyKind::Char | TyKind::Error) {
+                    let err = TypeError::InvalidRangePattern {
+                        ty: self.display(expected),
+                    };
+                    self.error(err, span);
+                }
+            }
+            PatternKind::Tuple(pats) => {
+                let tys = match self.kind(expected) {
+                    TyKind::Tuple(tys) if tys.len() == pats.len() => tys,
+                    _ => {
+                        let tys = self.table.new_vars(pats.len());
+                        let tuple = self.ty(TyKind::Tuple(tys.clone()));
+                        self.coerce(span, tuple, expected);
+                        tys
+                    }
+                };
+                for (pat, ty) in pats.into_iter().zip(tys) {
+                    self.check_pattern(pat, ty);
+                }
+            }
+            PatternKind::NamedTuple(fields) => self.check_field_patterns(span, &fields, expected),
+            PatternKind::Struct { name, fields } => {
+                let db = self.db;
+                let item = scope::resolve_type(db, self.file, name.id).filter(|&item| {
+                    matches!(db.lookup_intern_item(item).kind, ItemKind::TypeDef(_))
+                });
+                let ty = match item {
+                    Some(item) => {
+                        let args = self.table.new_vars(db.generics_of(item).len());
+                        let ty = self.ty(TyKind::Adt(item, args));
+                        self.coerce(span, ty, expected);
+                        ty
+                    }
+                    None => {
+                        let err = TypeError::UnresolvedType {
+                            name: self.name(name.id),
+                        };
+                        self.error(err, name.span)
+                    }
+                };
+                self.check_field_patterns(span, &fields, ty);
+            }
+            PatternKind::Variant { path, data } => {
+                let variant = self.resolve_variant(span, &path.segments);
+                let (union, idx) = match variant {
+                    Some(variant) => variant,
+                    None => {
+                        let name = self.path_string(&path);
+                        let err = self.error(TypeError::UnresolvedName { name }, span);
+                        if let Some(data) = data {
+                            self.check_pattern(data, err);
+                        }
+                        return;
+                    }
+                };
+                self.results.pattern_variants.insert(id, (union, idx));
+
+                let args = self.table.new_vars(self.db.generics_of(union).len());
+                let ty = self.ty(TyKind::Adt(union, args.clone()));
+                self.coerce(span, ty, expected);
+
+                if let Some(data) = data {
+                    let data_ty = match &*self.db.adt_def(union) {
+                        AdtDef::Union { variants } => variants[idx as usize].1,
+                        AdtDef::Struct { .. } => None,
+                    };
+                    let data_ty = match data_ty {
+                        Some(ty) => ty.subst(self.db, &args),
+                        None => self.ty(TyKind::Unit),
+                    };
+                    self.check_pattern(data, data_ty);
+                }
+            }
+        }
+    }
+
+    /// Checks the fields of a struct or named tuple pattern.
+    fn check_field_patterns(
+        &mut self,
+        span: Span,
+        fields: &[(Identifier, PatternId)],
+        expected: Ty,
+    ) {
+        let tys = match self.kind(expected) {
+            TyKind::Adt(item, args) => match &*self.db.adt_def(item) {
+                AdtDef::Struct { fields } => Some(
+                    fields
+                        .iter()
+                        .map(|(name, ty)| (*name, ty.subst(self.db, &args)))
+                        .collect::<Vec<_>>(),
+                ),
+                AdtDef::Union { .. } => None,
+            },
+            TyKind::NamedTuple(fields) => Some(fields),
+            TyKind::Error => {
+                for (_, pat) in fields {
+                    self.check_pattern(*pat, expected);
+                }
+                return;
+            }
+            _ => None,
+        };
+
+        let tys = match tys {
+            Some(tys) => tys,
+            None => {
+                let tys = fields
+                    .iter()
+                    .map(|(name, _)| (name.id, self.table.new_var()))
+                    .collect::<Vec<_>>();
+                let tuple = self.ty(TyKind::NamedTuple(tys.clone()));
+                self.coerce(span, tuple, expected);
+                tys
+            }
+        };
+
+        for (name, pat) in fields {
+            match tys.iter().position(|(field, _)| *field == name.id) {
+                Some(idx) => {
+                    if let TyKind::Adt(item, _) = self.kind(expected) {
+                        self.check_field_access(item, idx, expected, name);
+                    }
+                    self.check_pattern(*pat, tys[idx].1);
+                }
+                None => {
+                    let err = TypeError::NoField {
+                        ty: self.display(expected),
+                        field: self.name(name.id),
+                    };
+                    let ty = self.error(err, name.span);
+                    self.check_pattern(*pat, ty);
+                }
+            }
+        }
+    }
+
+    /// Resolves `Union:Variant` or `module:Union:Variant` to a union
+    /// and the index of the variant.
+    fn resolve_variant(&mut self, span: Span, segments: &[Identifier]) -> Option<(ItemId, u32)> {
+        let db = self.db;
+        let (variant, union) = segments.split_last()?;
+        if union.is_empty() {
+            return None;
+        }
+        let resolved = scope::resolve_type_path(db, self.file, union)?;
+        let item = resolved.res;
+        if !matches!(db.lookup_intern_item(item).kind, ItemKind::Union(_)) {
+            return None;
+        }
+        let (idx, _) = db.adt_def(item).variant(variant.id)?;
+
+        if let Some(private) = resolved.private {
+            self.private_item(span, private);
+        } else if !privacy::is_variant_accessible(db, self.file, item, idx) {
+            let err = TypeError::PrivateVariant {
+                union: self.display(self.ty(TyKind::Adt(item, vec![]))),
+                variant: self.name(variant.id),
+            };
+            self.error(err, variant.span);
+        }
+        Some((item, idx as u32))
+    }
+
+    /// Reports an error if the field at `idx` of the `type` is private.
+    fn check_field_access(&mut self, adt: ItemId, idx: usize, ty: Ty, name: &Identifier) {
+        if !privacy::is_field_accessible(self.db, self.file, adt, idx) {
+            let err = TypeError::PrivateField {
+                ty: self.display(ty),
+                field: self.name(name.id),
+            };
+            self.error(err, name.span);
+        }
+    }
+
+    /// Reports that the item, which is used at `span`, is private.
+    fn private_item(&mut self, span: Span, item: ItemId) {
+        let err = privacy::private_item_error(self.db, self.file, span, item);
+        self.results.errors.push(err);
+    }
+
+    fn path_string(&self, path: &Path) -> String {
+        path.segments
+            .iter()
+            .map(|segment| self.name(segment.id))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// Resolves a path to a local, an item or a union variant.
+    fn resolve_path(&mut self, span: Span, path: &Path) -> Option<Res> {
+        if let [name] = path.segments.as_slice() {
+            if let Some(local) = self.lookup_local(name.id) {
+                return Some(Res::Local(local));
+            }
+        }
+        if let Some(resolved) = scope::resolve_value_path(self.db, self.file, &path.segments) {
+            if let Some(private) = resolved.private {
+                self.private_item(span, private);
+            }
+            return Some(Res::Def(resolved.res));
+        }
+        if let Some((item, idx)) = self.resolve_variant(span, &path.segments) {
+            return Some(Res::Variant(item, idx));
+        }
+
+        let name = self.path_string(path);
+        self.error(TypeError::UnresolvedName { name }, span);
+        None
+    }
+
+    fn path_name(&self, path: &Path) -> String {
+        let last = path.segments.last().expect("paths are never empty");
+        self.name(last.id)
+    }
+
+    fn infer_path(&mut self, id: ExprId, span: Span, path: &Path) -> Ty {
+        let res = match self.resolve_path(span, path) {
+            Some(res) => res,
+            None => return self.ty(TyKind::Error),
+        };
+        self.results.resolutions.insert(id, res);
+
+        match res {
+            Res::Local(local) => self.results.local(local).ty,
+            Res::Variant(union, idx) => {
+                let def = self.db.adt_def(union);
+                let data = match &*def {
+                    AdtDef::Union { variants } => variants[idx as usize].1,
+                    AdtDef::Struct { .. } => None,
+                };
+                if data.is_some() {
+                    let name = self.path_name(path);
+                    return self.error(TypeError::NotAValue { name }, span);
+                }
+
+                let args = self.table.new_vars(self.db.generics_of(union).len());
+                self.ty(TyKind::Adt(union, args))
+            }
+            Res::Def(item) => match self.db.lookup_intern_item(item).kind {
+                ItemKind::Const(_) | ItemKind::Static(_) => self.db.global_ty(item),
+                _ => {
+                    let name = self.path_name(path);
+                    self.error(TypeError::NotAValue { name }, span)
+                }
+            },
+        }
+    }
+
+    /// Checks the arguments of a call against the parameters of the function.
+    fn check_args(&mut self, span: Span, params: &[Ty], args: &[ExprId]) {
+        if params.len() != args.len() {
+            let err = TypeError::WrongArgCount {
+                expected: params.len(),
+                found: args.len(),
+            };
+            self.error(err, span);
+        }
+
+        for (idx, &arg) in args.iter().enumerate() {
+            self.infer_expr(arg, params.get(idx).copied());
+        }
+    }
+
+    /// Registers that every generic argument has to satisfy the bounds
+    /// of its parameter.
+    fn add_obligations(&mut self, item: ItemId, args: &[Ty], span: Span) {
+        let generics = self.db.generics_of(item);
+        for (param, &arg) in generics.params.iter().zip(args) {
+            for &bound in &param.bounds {
+                self.obligations.push((arg, bound, span));
+            }
+        }
+    }
+
+    fn infer_call(&mut self, id: ExprId, span: Span, call: &Call) -> Ty {
+        let callee = self.db.lookup_intern_expr(call.callee);
+        let res = match &callee.kind {
+            ExprKind::Path(path) => self.resolve_path(callee.span, path).map(|res| (res, path)),
+            _ => {
+                let ty = self.infer_expr(call.callee, None);
+                let err = TypeError::NotCallable {
+                    name: self.display(ty),
+                };
+                self.error(err, callee.span);
+                None
+            }
+        };
+
+        let def = match res {
+            Some((Res::Def(def), _)) => Some(def),
+            Some((_, path)) => {
+                let name = self.path_name(path);
+                self.error(TypeError::NotCallable { name }, callee.span);
+                None
+            }
+            None => None,
+        };
+        let def = match def {
+            Some(def) => {
+                self.results.resolutions.insert(call.callee, Res::Def(def));
+                def
+            }
+            None => {
+                for &arg in &call.args {
+                    self.infer_expr(arg, None);
+                }
+                return self.ty(TyKind::Error);
+            }
+        };
+
+        let args = self.table.new_vars(self.db.generics_of(def).len());
+        let sig = self.db.fn_sig(def).subst(self.db, &args);
+        self.check_args(span, &sig.params, &call.args);
+        self.add_obligations(def, &args, span);
+        self.results.callees.insert(id, Callee::Fn { def, args });
+        sig.ret
+    }
+
+    fn infer_method_call(&mut self, id: ExprId, span: Span, call: &MethodCall) -> Ty {
+        if let Some(ty) = self.infer_variant_new(id, span, call) {
+            return ty;
+        }
+
+        let mut ty = self.infer_expr(call.receiver, None);
+        let mut derefs = 0;
+        let found = loop {
+            let kind = self.kind(ty);
+            match kind {
+                TyKind::Error => break None,
+                TyKind::Infer(_) => {
+                    self.error(TypeError::CannotInfer, span);
+                    break None;
+                }
+                _ => {}
+            }
+
+            if let Some(found) = self.lookup_method(ty, call.name.id, call.name.span) {
+                break Some(found);
+            }
+
+            match kind {
+                TyKind::Pointer(inner) => {
+                    ty = inner;
+                    derefs += 1;
+                }
+                _ => {
+                    let err = TypeError::NoMethod {
+                        ty: self.display(ty),
+                        method: self.name(call.name.id),
+                    };
+                    self.error(err, call.name.span);
+                    break None;
+                }
+            }
+        };
+
+        let (def, args) = match found {
+            Some(found) => found,
+            None => {
+                for &arg in &call.args {
+                    self.infer_expr(arg, None);
+                }
+                return self.ty(TyKind::Error);
+            }
+        };
+
+        let sig = self.db.fn_sig(def).subst(self.db, &args);
+        self.check_args(span, &sig.params, &call.args);
+        self.add_obligations(def, &args, span);
+        self.results.callees.insert(id, Callee::Fn { def, args });
+        self.results.receivers.insert(
+            id,
+            Receiver {
+                derefs,
+                autoref: true,
+            },
+        );
+        sig.ret
+    }
+
+    /// Checks for the construction of a union variant using `Union:Variant.new(data)`.
+    fn infer_variant_new(&mut self, id: ExprId, span: Span, call: &MethodCall) -> Option<Ty> {
+        if self.db.rodeo().resolve(&call.name.id) != "new" {
+            return None;
+        }
+        let receiver = self.db.lookup_intern_expr(call.receiver);
+        let path = match &receiver.kind {
+            ExprKind::Path(path) if path.segments.len() >= 2 => path,
+            _ => return None,
+        };
+        let (union, idx) = match self.resolve_path(receiver.span, path) {
+            Some(Res::Variant(union, idx)) => (union, idx),
+            _ => return None,
+        };
+
+        self.results
+            .resolutions
+            .insert(call.receiver, Res::Variant(union, idx));
+        let args = self.table.new_vars(self.db.generics_of(union).len());
+        let data = match &*self.db.adt_def(union) {
+            AdtDef::Union { variants } => variants[idx as usize].1,
+            AdtDef::Struct { .. } => None,
+        };
+        let params = data
+            .map(|ty| vec![ty.subst(self.db, &args)])
+            .unwrap_or_default();
+        self.check_args(span, &params, &call.args);
+
+        let ty = self.ty(TyKind::Adt(union, args.clone()));
+        self.results.expr_types.insert(call.receiver, ty);
+        self.results.callees.insert(
+            id,
+            Callee::Variant {
+                union,
+                variant: idx,
+                args,
+            },
+        );
+        Some(ty)
+    }
+
+    /// Looks up the method called `name` for the type `ty`.
+    ///
+    /// Methods of inherent impls are preferred over methods of trait impls.
+    /// Returns the method together with the generic arguments for it.
+    fn lookup_method(&mut self, ty: Ty, name: StringId, span: Span) -> Option<(ItemId, Vec<Ty>)> {
+        let db = self.db;
+
+        if let TyKind::Param(idx, _) = self.kind(ty) {
+            let bounds = self.generics.params[idx as usize].bounds.clone();
+            for bound in bounds {
+                if let Some(method) = traits::find_method(db, bound, name) {
+                    let own = db.generics_of(method).len() - 1;
+                    let mut args = vec![ty];
+                    args.extend(self.table.new_vars(own));
+                    return Some((method, args));
+                }
+            }
+            return None;
+        }
+
+        let mut inherent = vec![];
+        let mut from_traits = vec![];
+        for &imp in db.crate_impls().iter() {
+            let method = match traits::impl_method(db, imp, name) {
+                Some(method) => method,
+                None => continue,
+            };
+
+            let snapshot = self.table.snapshot();
+            let matches = traits::match_impl(&mut self.table, db, imp, ty).is_some();
+            self.table.rollback(snapshot);
+            if !matches {
+                continue;
+            }
+
+            if db.impl_trait(imp).is_some() {
+                from_traits.push((imp, method));
+            } else {
+                inherent.push((imp, method));
+            }
+        }
+
+        let candidates = if inherent.is_empty() {
+            from_traits
+        } else {
+            inherent
+        };
+        let (imp, method) = match candidates.as_slice() {
+            [] => return None,
+            [candidate] => *candidate,
+            [candidate, ..] => {
+                let err = TypeError::AmbiguousMethod {
+                    ty: self.display(ty),
+                    method: self.name(name),
+                };
+                self.error(err, span);
+                *candidate
+            }
+        };
+
+        if !privacy::is_item_accessible(db, self.file, method) {
+            self.private_item(span, method);
+        }
+
+        let impl_args = traits::match_impl(&mut self.table, db, imp, ty)?;
+        self.add_obligations(imp, &impl_args, span);
+
+        let own = db.generics_of(method).len() - db.generics_of(method).parent_count;
+        let mut args = if scope::parent_item(db, method) == Some(imp) {
+            impl_args
+        } else {
+            // the default method of a trait
+            vec![ty]
+        };
+        args.extend(self.table.new_vars(own));
+        Some((method, args))
+    }
+
+    fn infer_field(&mut self, id: ExprId, span: Span, field: &Field) -> Ty {
+        let mut ty = self.infer_expr(field.val, None);
+        while let TyKind::Pointer(inner) = self.kind(ty) {
+            ty = inner;
+        }
+
+        let found = match (self.kind(ty), &field.field) {
+            (TyKind::Error, _) => return ty,
+            (TyKind::Infer(_), _) => return self.error(TypeError::CannotInfer, span),
+            (TyKind::Adt(item, args), field) => match (&*self.db.adt_def(item), field) {
+                (AdtDef::Struct { fields }, FieldName::Named(name)) => fields
+                    .iter()
+                    .position(|(field, _)| *field == name.id)
+                    .map(|idx| (idx, fields[idx].1.subst(self.db, &args))),
+                (AdtDef::Struct { fields }, FieldName::Index(idx)) => {
+                    let idx = *idx.data() as usize;
+                    fields
+                        .get(idx)
+                        .map(|(_, ty)| (idx, ty.subst(self.db, &args)))
+                }
+                _ => None,
+            },
+            (TyKind::Tuple(tys), FieldName::Index(idx)) => {
+                let idx = *idx.data() as usize;
+                tys.get(idx).map(|ty| (idx, *ty))
+            }
+            (TyKind::NamedTuple(fields), FieldName::Named(name)) => fields
+                .iter()
+                .position(|(field, _)| *field == name.id)
+                .map(|idx| (idx, fields[idx].1)),
+            (TyKind::NamedTuple(fields), FieldName::Index(idx)) => {
+                let idx = *idx.data() as usize;
+                fields.get(idx).map(|(_, ty)| (idx, *ty))
+            }
+            _ => None,
+        };
+
+        match found {
+            Some((idx, found_ty)) => {
+                if let (TyKind::Adt(item, _), FieldName::Named(name)) =
+                    (self.kind(ty), &field.field)
+                {
+                    self.check_field_access(item, idx, ty, name);
+                }
+                self.results.fields.insert(id, idx as u32);
+                found_ty
+            }
+            None => {
+                let field = match &field.field {
+                    FieldName::Named(name) => self.name(name.id),
+                    FieldName::Index(idx) => idx.data().to_string(),
+                };
+                let err = TypeError::NoField {
+                    ty: self.display(ty),
+                    field,
+                };
+                self.error(err, span)
+            }
+        }
+    }
+
+    /// Checks if the expression refers to a memory location,
+    /// that can be assigned to.
+    fn is_place(&self, expr: ExprId) -> bool {
+        match self.db.lookup_intern_expr(expr).kind {
+            ExprKind::Path(_) => match self.results.resolutions.get(&expr) {
+                Some(Res::Local(_)) => true,
+                Some(Res::Def(item)) => {
+                    matches!(self.db.lookup_intern_item(*item).kind, ItemKind::Static(_))
+                }
+                _ => false,
+            },
+            ExprKind::Unary(unary) => *unary.op.data() == UnOp::Deref,
+            ExprKind::Field(field) => {
+                let base = self.results.expr_types.get(&field.val).copied();
+                let through_pointer =
+                    base.is_some_and(|ty| matches!(self.kind(ty), TyKind::Pointer(_)));
+                through_pointer || self.is_place(field.val)
+            }
+            _ => false,
+        }
+    }
+
+    fn infer_assign(&mut self, span: Span, assign: &Assign) -> Ty {
+        let target = self.infer_expr(assign.target, None);
+        if !self.is_place(assign.target) && !target.is_error(self.db) {
+            let target_span = self.db.lookup_intern_expr(assign.target).span;
+            self.error(TypeError::NotAPlace, target_span);
+        }
+
+        match &assign.op {
+            Some(op) => {
+                let ty = self.infer_arithmetic(*op.data(), op.span(), target, assign.val);
+                self.coerce(span, ty, target);
+            }
+            None => {
+                self.infer_expr(assign.val, Some(target));
+            }
+        }
+        self.ty(TyKind::Unit)
+    }
+
+    /// Resolves every type and reports types that couldn't be inferred,
+    /// as well as unsatisfied trait bounds.
+    fn finish(mut self) -> TypeckResults {
+        let db = self.db;
+        let mut unresolved = vec![];
+
+        let mut exprs = std::mem::take(&mut self.results.expr_types)
+            .into_iter()
+            .map(|(expr, ty)| (db.lookup_intern_expr(expr).span, expr, ty))
+            .collect::<Vec<_>>();
+        exprs.sort_by_key(|(span, _, _)| (span.start(), span.end()));
+        for (span, expr, ty) in exprs {
+            let before = unresolved.len();
+            let ty = self.table.fallback(ty, &mut unresolved);
+            self.results.expr_types.insert(expr, ty);
+
+            // only report every variable once
+            let new = unresolved[before..]
+                .iter()
+                .any(|var| !unresolved[..before].contains(var));
+            if new {
+                self.error(TypeError::CannotInfer, span);
+            }
+        }
+
+        for local in 0..self.results.locals.len() {
+            let ty = self.results.locals[local].ty;
+            self.results.locals[local].ty = self.table.fallback(ty, &mut unresolved);
+        }
+        for ty in self.results.pattern_types.values_mut() {
+            *ty = self.table.fallback(*ty, &mut unresolved);
+        }
+        for callee in self.results.callees.values_mut() {
+            let args = match callee {
+                Callee::Fn { args, .. } | Callee::Variant { args, .. } => args,
+            };
+            for arg in args.iter_mut() {
+                *arg = self.table.fallback(*arg, &mut unresolved);
+            }
+        }
+
+        for (ty, trait_, span) in std::mem::take(&mut self.obligations) {
+            let ty = self.table.fallback(ty, &mut unresolved);
+            if !traits::implements(db, &self.generics, ty, trait_) {
+                let trait_ = db.lookup_intern_item(trait_).kind.name().unwrap().id;
+                let err = TypeError::TraitNotImplemented {
+                    trait_: self.name(trait_),
+                    ty: ty.display(db),
+                };
+                self.error(err, span);
+            }
+        }
+
+        for expr in std::mem::take(&mut self.matches) {
+            exhaustive::check_match(db, self.file, &mut self.results, expr);
+        }
+        consteval::check_body(db, self.file, &mut self.results);
+
+        self.results
+    }
+}
+
+fn is_int(kind: &TyKind) -> bool {
+    matches!(kind, TyKind::Int { .. } | TyKind::Infer(InferVar::Int(_)))
+}
+
+fn is_numeric(kind: &TyKind) -> bool {
+    matches!(
+        kind,
+        TyKind::Int { .. }
+            | TyKind::Float { .. }
+            | TyKind::Infer(InferVar::Int(_))
+            | TyKind::Infer(InferVar::Float(_))
+    )
+}
@@ -0,0 +1,15 @@
+//! Name resolution and type checking of cell programs.
+
+pub mod check;
+pub mod consteval;
+pub mod db;
+mod exhaustive;
+pub mod infer;
+pub mod lower;
+pub mod module;
+pub mod prelude;
+pub mod privacy;
+pub mod scope;
+pub mod traits;
+pub mod ty;
+pub mod unify;
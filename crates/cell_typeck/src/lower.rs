@@ -0,0 +1,395 @@
+//! Lowering of [`ast::Type`]s and item signatures into [`Ty`]s.
+//!
+//! [`ast::Type`]: ../../cell_syntax/ast/enum.Type.html
+//! [`Ty`]: ../ty/struct.Ty.html
+
+use crate::{
+    db::TypeckDatabase,
+    scope,
+    ty::{Ty, TyKind},
+};
+use cell_common::{
+    error::{Error, TypeError},
+    source::{FileId, Locatable, Span},
+    strings::StringId,
+};
+use cell_syntax::ast::{Generic, ItemId, ItemKind, Type, TypeId};
+use std::sync::Arc;
+
+/// A single generic parameter of an item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenericParam {
+    pub name: StringId,
+    pub span: Span,
+    /// The traits this parameter is bound by.
+    pub bounds: Vec<ItemId>,
+}
+
+/// All generic parameters that are in scope for an item.
+///
+/// Methods inherit the generics of the `impl` or `trait` they
+/// are defined in. These come first, followed by the generics
+/// of the item itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Generics {
+    pub params: Vec<GenericParam>,
+    /// The number of parameters that are inherited from the parent item.
+    pub parent_count: usize,
+}
+
+impl Generics {
+    /// Returns the index of the parameter with the given name.
+    ///
+    /// Later parameters shadow earlier ones.
+    pub fn find(&self, name: StringId) -> Option<usize> {
+        self.params.iter().rposition(|param| param.name == name)
+    }
+
+    /// Returns the number of generic parameters.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Checks if there are no generic parameters.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// Returns every parameter as a type, which can be used
+    /// as an identity substitution.
+    pub fn identity(&self, db: &dyn TypeckDatabase) -> Vec<Ty> {
+        self.params
+            .iter()
+            .enumerate()
+            .map(|(idx, param)| TyKind::Param(idx as u32, param.name).intern(db))
+            .collect()
+    }
+}
+
+/// The signature of a function or method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnSig {
+    pub params: Vec<Ty>,
+    pub ret: Ty,
+    /// The type of `this`, if this function is a method.
+    pub this: Option<Ty>,
+}
+
+impl FnSig {
+    /// Substitutes every generic parameter inside this signature.
+    pub fn subst(&self, db: &dyn TypeckDatabase, args: &[Ty]) -> FnSig {
+        FnSig {
+            params: self.params.iter().map(|ty| ty.subst(db, args)).collect(),
+            ret: self.ret.subst(db, args),
+            this: self.this.map(|ty| ty.subst(db, args)),
+        }
+    }
+}
+
+/// The lowered definition of a `type` or a `union`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdtDef {
+    Struct {
+        fields: Vec<(StringId, Ty)>,
+    },
+    Union {
+        variants: Vec<(StringId, Option<Ty>)>,
+    },
+}
+
+impl AdtDef {
+    /// Returns the index and type of the field with the given name.
+    pub fn field(&self, name: StringId) -> Option<(usize, Ty)> {
+        match self {
+            AdtDef::Struct { fields } => fields
+                .iter()
+                .position(|(field, _)| *field == name)
+                .map(|idx| (idx, fields[idx].1)),
+            AdtDef::Union { .. } => None,
+        }
+    }
+
+    /// Returns the index and data type of the variant with the given name.
+    pub fn variant(&self, name: StringId) -> Option<(usize, Option<Ty>)> {
+        match self {
+            AdtDef::Union { variants } => variants
+                .iter()
+                .position(|(variant, _)| *variant == name)
+                .map(|idx| (idx, variants[idx].1)),
+            AdtDef::Struct { .. } => None,
+        }
+    }
+}
+
+/// Converts [`ast::Type`]s into [`Ty`]s, in the context of an item.
+///
+/// [`ast::Type`]: ../../cell_syntax/ast/enum.Type.html
+/// [`Ty`]: ../ty/struct.Ty.html
+pub struct TyLowerer<'db> {
+    db: &'db dyn TypeckDatabase,
+    file: FileId,
+    generics: Arc<Generics>,
+    self_ty: Option<Ty>,
+    /// Aliases that are currently being expanded, used to detect cycles.
+    aliases: Vec<ItemId>,
+    errors: Vec<Locatable<Error>>,
+}
+
+impl<'db> TyLowerer<'db> {
+    /// Creates a lowerer that resolves names like they would be
+    /// resolved inside the given item.
+    pub fn for_item(db: &'db dyn TypeckDatabase, item: ItemId) -> Self {
+        let file = db.lookup_intern_item(item).file;
+        Self {
+            db,
+            file,
+            generics: db.generics_of(item),
+            self_ty: self_ty_of(db, item),
+            aliases: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// Returns every error that occurred while lowering.
+    pub fn finish(self) -> Vec<Locatable<Error>> {
+        self.errors
+    }
+
+    /// Lowers an interned type.
+    pub fn lower(&mut self, ty: TypeId) -> Ty {
+        let ty = self.db.lookup_intern_type(ty);
+        self.lower_type(&ty)
+    }
+
+    /// Lowers a type.
+    pub fn lower_type(&mut self, ty: &Type) -> Ty {
+        let kind = match ty {
+            Type::Int { width, signed } => TyKind::Int {
+                width: *width,
+                signed: *signed,
+            },
+            Type::Float { width } => TyKind::Float { width: *width },
+            Type::Bool => TyKind::Bool,
+            Type::String => TyKind::String,
+            Type::Unit => TyKind::Unit,
+            Type::Char => TyKind::Char,
+            Type::Pointer(inner) => TyKind::Pointer(self.lower(*inner)),
+            Type::Tuple(tys) => TyKind::Tuple(tys.iter().map(|ty| self.lower(*ty)).collect()),
+            Type::NamedTuple(fields) => TyKind::NamedTuple(
+                fields
+                    .iter()
+                    .map(|(name, ty)| (name.id, self.lower(*ty)))
+                    .collect(),
+            ),
+            Type::Named { name, generics } => {
+                return self.lower_named(name.id, name.span, generics)
+            }
+        };
+        kind.intern(self.db)
+    }
+
+    fn lower_named(&mut self, name: StringId, span: Span, generics: &[TypeId]) -> Ty {
+        let db = self.db;
+        let args = generics
+            .iter()
+            .map(|ty| self.lower(*ty))
+            .collect::<Vec<_>>();
+
+        if let Some(idx) = self.generics.find(name) {
+            self.check_generic_count(0, args.len(), span);
+            return TyKind::Param(idx as u32, name).intern(db);
+        }
+
+        if db.rodeo().resolve(&name) == "Self" {
+            if let Some(self_ty) = self.self_ty {
+                self.check_generic_count(0, args.len(), span);
+                return self_ty;
+            }
+        }
+
+        let item = match scope::resolve_type(db, self.file, name) {
+            Some(item) => item,
+            None => {
+                let name = db.rodeo().resolve(&name).to_string();
+                return self.error(TypeError::UnresolvedType { name }, span);
+            }
+        };
+
+        match db.lookup_intern_item(item).kind {
+            ItemKind::TypeDef(def) => {
+                self.check_generic_count(def.generics.len(), args.len(), span);
+                TyKind::Adt(item, args).intern(db)
+            }
+            ItemKind::Union(union) => {
+                self.check_generic_count(union.generics.len(), args.len(), span);
+                TyKind::Adt(item, args).intern(db)
+            }
+            ItemKind::Alias(alias) => {
+                if !self.check_generic_count(alias.generics.len(), args.len(), span) {
+                    return TyKind::Error.intern(db);
+                }
+                if self.aliases.contains(&item) {
+                    let name = db.rodeo().resolve(&name).to_string();
+                    return self.error(TypeError::CyclicAlias { name }, span);
+                }
+
+                let mut lowerer = TyLowerer::for_item(db, item);
+                lowerer.aliases = std::mem::take(&mut self.aliases);
+                lowerer.aliases.push(item);
+                let ty = lowerer.lower(alias.ty);
+                let cyclic = lowerer
+                    .errors
+                    .iter()
+                    .any(|err| matches!(err.data(), Error::Type(TypeError::CyclicAlias { .. })));
+                self.aliases = lowerer.aliases;
+                self.aliases.pop();
+
+                if cyclic {
+                    let name = db.rodeo().resolve(&name).to_string();
+                    self.error(TypeError::CyclicAlias { name }, span)
+                } else {
+                    ty.subst(db, &args)
+                }
+            }
+            _ => {
+                let name = db.rodeo().resolve(&name).to_string();
+                self.error(TypeError::ExpectedType { name }, span)
+            }
+        }
+    }
+
+    /// Reports an error if the number of generics doesn't match.
+    fn check_generic_count(&mut self, expected: usize, found: usize, span: Span) -> bool {
+        if expected != found {
+            self.error(TypeError::WrongGenericCount { expected, found }, span);
+            false
+        } else {
+            true
+        }
+    }
+
+    fn error(&mut self, err: TypeError, span: Span) -> Ty {
+        self.errors
+            .push(Locatable::new(err.into(), self.file, span));
+        TyKind::Error.intern(self.db)
+    }
+}
+
+/// Returns the type that `Self` refers to inside the given item.
+fn self_ty_of(db: &dyn TypeckDatabase, item: ItemId) -> Option<Ty> {
+    let owner = scope::parent_item(db, item).unwrap_or(item);
+    match db.lookup_intern_item(owner).kind {
+        // `Self` inside an `impl` header can't refer to the impl itself
+        ItemKind::Impl(_) if owner == item => None,
+        ItemKind::Impl(_) => Some(db.impl_self_ty(owner)),
+        ItemKind::Trait(_) => {
+            let name = db.rodeo().get_or_intern("Self");
+            Some(TyKind::Param(0, name).intern(db))
+        }
+        _ => None,
+    }
+}
+
+fn own_generics(db: &dyn TypeckDatabase, item: ItemId) -> Vec<GenericParam> {
+    let item = db.lookup_intern_item(item);
+    let generics: &[Generic] = match &item.kind {
+        ItemKind::TypeDef(def) => &def.generics,
+        ItemKind::Def(def) => &def.generics,
+        ItemKind::Alias(alias) => &alias.generics,
+        ItemKind::Union(union) => &union.generics,
+        ItemKind::Impl(imp) => &imp.generics,
+        ItemKind::Trait(_)
+        | ItemKind::Const(_)
+        | ItemKind::Static(_)
+        | ItemKind::Import(_)
+        | ItemKind::Use(_) => &[],
+    };
+
+    generics
+        .iter()
+        .map(|generic| GenericParam {
+            name: generic.name.id,
+            span: generic.name.span,
+            bounds: generic
+                .bounds
+                .iter()
+                .filter_map(|bound| scope::resolve_trait(db, item.file, bound.id))
+                .collect(),
+        })
+        .collect()
+}
+
+pub(crate) fn generics_of(db: &dyn TypeckDatabase, item: ItemId) -> Arc<Generics> {
+    let mut params = match scope::parent_item(db, item) {
+        Some(parent) => db.generics_of(parent).params.clone(),
+        None => vec![],
+    };
+
+    // every trait has an implicit `Self` parameter, that is bound by the trait itself
+    if let ItemKind::Trait(trait_) = db.lookup_intern_item(item).kind {
+        params.push(GenericParam {
+            name: db.rodeo().get_or_intern("Self"),
+            span: trait_.name.span,
+            bounds: vec![item],
+        });
+    }
+
+    let parent_count = params.len();
+    params.extend(own_generics(db, item));
+    Arc::new(Generics {
+        params,
+        parent_count,
+    })
+}
+
+pub(crate) fn impl_self_ty(db: &dyn TypeckDatabase, item: ItemId) -> Ty {
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Impl(imp) => TyLowerer::for_item(db, item).lower(imp.ty),
+        _ => TyKind::Error.intern(db),
+    }
+}
+
+pub(crate) fn fn_sig(db: &dyn TypeckDatabase, item: ItemId) -> Arc<FnSig> {
+    let def = match db.lookup_intern_item(item).kind {
+        ItemKind::Def(def) => def,
+        _ => panic!("`fn_sig` called on a non function item"),
+    };
+
+    let mut lowerer = TyLowerer::for_item(db, item);
+    let params = def.args.iter().map(|(_, ty)| lowerer.lower(*ty)).collect();
+    let ret = lowerer.lower(def.return_ty);
+    let this = lowerer.self_ty.map(|ty| TyKind::Pointer(ty).intern(db));
+
+    Arc::new(FnSig { params, ret, this })
+}
+
+pub(crate) fn global_ty(db: &dyn TypeckDatabase, item: ItemId) -> Ty {
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Const(global) | ItemKind::Static(global) => {
+            TyLowerer::for_item(db, item).lower(global.ty)
+        }
+        _ => panic!("`global_ty` called on an item that is not a `const` or `static`"),
+    }
+}
+
+pub(crate) fn adt_def(db: &dyn TypeckDatabase, item: ItemId) -> Arc<AdtDef> {
+    let mut lowerer = TyLowerer::for_item(db, item);
+    let def = match db.lookup_intern_item(item).kind {
+        ItemKind::TypeDef(def) => AdtDef::Struct {
+            fields: def
+                .fields
+                .iter()
+                .map(|field| (field.name.id, lowerer.lower(field.ty)))
+                .collect(),
+        },
+        ItemKind::Union(union) => AdtDef::Union {
+            variants: union
+                .variants
+                .iter()
+                .map(|variant| (variant.name.id, variant.data.map(|ty| lowerer.lower(ty))))
+                .collect(),
+        },
+        _ => panic!("`adt_def` called on an item that is not a type or union"),
+    };
+    Arc::new(def)
+}
@@ -0,0 +1,200 @@
+//! The module tree of a program.
+//!
+//! Every file is a module. The root module is the root file of the
+//! [`SourceRoot`], and every `import name;` declares a submodule that
+//! is defined in `name.cell` or `name/mod.cell`, relative to the
+//! directory of the importing module.
+//!
+//! The outer files of the `SourceRoot` are the ancestors of the root module,
+//! so private items of an outer file can be used inside the root module.
+//!
+//! [`SourceRoot`]: ../../cell_common/source/struct.SourceRoot.html
+
+use crate::db::TypeckDatabase;
+use cell_common::{
+    error::{Error, TypeError},
+    source::{FileId, Locatable},
+    strings::StringId,
+};
+use cell_syntax::ast::ItemKind;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// A single module inside the module tree.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleData {
+    /// The module that imports this module, or the outer file that encloses
+    /// the root module or another outer file.
+    ///
+    /// This is `None` for the outermost module.
+    pub parent: Option<FileId>,
+    /// Every submodule that is declared using `import`.
+    pub children: HashMap<StringId, FileId>,
+}
+
+/// The tree of every module that is reachable from the root module,
+/// together with the outer files and the prelude.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleTree {
+    root: FileId,
+    modules: HashMap<FileId, ModuleData>,
+    /// Every module in the order they were discovered.
+    order: Vec<FileId>,
+}
+
+impl ModuleTree {
+    /// Returns the file of the root module.
+    pub fn root(&self) -> FileId {
+        self.root
+    }
+
+    /// Returns every file that is part of the tree, starting with the root module.
+    pub fn files(&self) -> impl Iterator<Item = FileId> + '_ {
+        self.order.iter().copied()
+    }
+
+    /// Returns the module that is defined in the given file, if the
+    /// file is part of the tree.
+    pub fn module(&self, file: FileId) -> Option<&ModuleData> {
+        self.modules.get(&file)
+    }
+
+    /// Returns the submodule `name` of the module in `file`.
+    pub fn child(&self, file: FileId, name: StringId) -> Option<FileId> {
+        self.module(file)?.children.get(&name).copied()
+    }
+}
+
+pub(crate) fn module_imports(db: &dyn TypeckDatabase, file: FileId) -> Arc<Vec<StringId>> {
+    let imports = db
+        .parse(file)
+        .items
+        .iter()
+        .filter_map(|&item| match db.lookup_intern_item(item).kind {
+            ItemKind::Import(import) => Some(import.name.id),
+            _ => None,
+        })
+        .collect();
+    Arc::new(imports)
+}
+
+pub(crate) fn module_tree(db: &dyn TypeckDatabase) -> Arc<ModuleTree> {
+    let source_root = db.source_root();
+    let root = source_root.root();
+
+    let mut tree = ModuleTree {
+        root,
+        modules: HashMap::new(),
+        order: vec![root],
+    };
+    // every outer file is the parent of the next one,
+    // and the innermost one is the parent of the root module
+    let mut parent = None;
+    for &file in source_root.outer() {
+        if file != root {
+            tree.modules.insert(
+                file,
+                ModuleData {
+                    parent,
+                    children: HashMap::new(),
+                },
+            );
+            tree.order.push(file);
+            parent = Some(file);
+        }
+    }
+    tree.modules.insert(
+        root,
+        ModuleData {
+            parent,
+            children: HashMap::new(),
+        },
+    );
+
+    let mut idx = 0;
+    while let Some(&file) = tree.order.get(idx) {
+        idx += 1;
+
+        let path = match source_root.path(file) {
+            Some(path) => path,
+            None => continue,
+        };
+        for &name in db.module_imports(file).iter() {
+            let candidates = module_candidates(path, file == root, db.rodeo().resolve(&name));
+            let child = match candidates.iter().find_map(|path| source_root.file(path)) {
+                Some(child) if !tree.modules.contains_key(&child) => child,
+                _ => continue,
+            };
+
+            tree.modules.insert(
+                child,
+                ModuleData {
+                    parent: Some(file),
+                    children: HashMap::new(),
+                },
+            );
+            tree.modules
+                .get_mut(&file)
+                .unwrap()
+                .children
+                .insert(name, child);
+            tree.order.push(child);
+        }
+    }
+
+    // the prelude is a module on its own, that is not imported by any module
+    if let Some(prelude) = source_root.prelude() {
+        if let Entry::Vacant(entry) = tree.modules.entry(prelude) {
+            entry.insert(ModuleData::default());
+            tree.order.push(prelude);
+        }
+    }
+
+    Arc::new(tree)
+}
+
+/// Returns the paths where the submodule `name`, of the module at `path`,
+/// can be defined.
+pub fn module_candidates(path: &Path, is_root: bool, name: &str) -> [PathBuf; 2] {
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let dir = if is_root || path.file_stem().is_some_and(|stem| stem == "mod") {
+        parent.to_path_buf()
+    } else {
+        parent.join(path.file_stem().unwrap_or_default())
+    };
+
+    [
+        dir.join(format!("{}.cell", name)),
+        dir.join(name).join("mod.cell"),
+    ]
+}
+
+/// Reports every `import` of the file that doesn't refer to an existing file.
+pub(crate) fn check_imports(db: &dyn TypeckDatabase, file: FileId) -> Vec<Locatable<Error>> {
+    let tree = db.module_tree();
+    let source_root = db.source_root();
+    let path = match (tree.module(file), source_root.path(file)) {
+        (Some(_), Some(path)) => path,
+        _ => return vec![],
+    };
+
+    db.parse(file)
+        .items
+        .iter()
+        .filter_map(|&item| match db.lookup_intern_item(item).kind {
+            ItemKind::Import(import) if tree.child(file, import.name.id).is_none() => {
+                let name = db.rodeo().resolve(&import.name.id).to_string();
+                let [expected, _] = module_candidates(path, file == tree.root(), &name);
+                let err = TypeError::ModuleNotFound {
+                    name,
+                    path: expected.display().to_string(),
+                };
+                Some(Locatable::new(err.into(), file, import.name.span))
+            }
+            _ => None,
+        })
+        .collect()
+}
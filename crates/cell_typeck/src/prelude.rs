@@ -0,0 +1,50 @@
+//! The prelude, which contains items that are available in every
+//! module and functions that are implemented by the host.
+
+use crate::db::TypeckDatabase;
+use cell_common::source::{SourceDatabase, SourceRoot};
+use cell_syntax::ast::ItemId;
+use salsa::Durability;
+use std::sync::Arc;
+
+/// The source code of the prelude.
+pub const SOURCE: &str = include_str!("prelude.cell");
+
+/// The path of the prelude inside the [`SourceRoot`].
+///
+/// [`SourceRoot`]: ../../cell_common/source/struct.SourceRoot.html
+pub const PATH: &str = "<prelude>";
+
+/// Adds the prelude to the source root.
+///
+/// The prelude never changes, so queries that only depend on it
+/// don't have to be validated again if another file is edited.
+pub fn load(db: &mut dyn SourceDatabase, root: &mut SourceRoot) {
+    let file = db.intern_path(PATH.into());
+    db.set_file_text_with_durability(file, Arc::new(SOURCE.to_string()), Durability::HIGH);
+    root.set_prelude(PATH, file);
+}
+
+/// A function of the prelude that is implemented by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostFn {
+    /// `Io.println`, which prints a value followed by a newline.
+    Println,
+    /// `alloc`, which allocates memory on the heap.
+    Alloc,
+}
+
+/// Returns the host function that `item` refers to, if any.
+pub fn host_fn(db: &dyn TypeckDatabase, item: ItemId) -> Option<HostFn> {
+    let data = db.lookup_intern_item(item);
+    if Some(data.file) != db.source_root().prelude() {
+        return None;
+    }
+
+    let name = data.kind.name()?;
+    match db.rodeo().resolve(&name.id) {
+        "println" => Some(HostFn::Println),
+        "alloc" => Some(HostFn::Alloc),
+        _ => None,
+    }
+}
@@ -0,0 +1,111 @@
+//! Visibility of items, fields, variants and methods.
+//!
+//! Everything is private by default, and can be made public using `pub`.
+//! A private item can only be used inside the module it's defined in,
+//! and inside every submodule of that module.
+
+use crate::{db::TypeckDatabase, scope};
+use cell_common::{
+    error::{Error, TypeError},
+    source::{FileId, Locatable, Span},
+};
+use cell_syntax::ast::{ItemId, ItemKind, Visibility};
+
+/// Checks if something with the visibility `vis`, that is defined in
+/// `module`, can be accessed from the module `from`.
+pub fn is_accessible(
+    db: &dyn TypeckDatabase,
+    from: FileId,
+    module: FileId,
+    vis: Visibility,
+) -> bool {
+    vis.is_public() || is_within(db, from, module)
+}
+
+/// Checks if `file` is `module` or one of its submodules.
+fn is_within(db: &dyn TypeckDatabase, file: FileId, module: FileId) -> bool {
+    let tree = db.module_tree();
+    let mut current = Some(file);
+    while let Some(file) = current {
+        if file == module {
+            return true;
+        }
+        current = tree.module(file).and_then(|data| data.parent);
+    }
+    false
+}
+
+/// Checks if the item can be accessed from the module `from`.
+///
+/// Methods of traits and trait impls are as visible as the trait itself,
+/// which is checked where the trait is brought into scope.
+pub fn is_item_accessible(db: &dyn TypeckDatabase, from: FileId, item: ItemId) -> bool {
+    if let Some(parent) = scope::parent_item(db, item) {
+        match db.lookup_intern_item(parent).kind {
+            ItemKind::Trait(_) => return true,
+            ItemKind::Impl(imp) if imp.trait_.is_some() => return true,
+            _ => {}
+        }
+    }
+
+    let item = db.lookup_intern_item(item);
+    is_accessible(db, from, item.file, item.vis)
+}
+
+/// Checks if the field at `idx` of the `type` can be accessed from the module `from`.
+pub fn is_field_accessible(db: &dyn TypeckDatabase, from: FileId, adt: ItemId, idx: usize) -> bool {
+    let item = db.lookup_intern_item(adt);
+    match &item.kind {
+        ItemKind::TypeDef(def) => is_accessible(db, from, item.file, def.fields[idx].vis),
+        _ => true,
+    }
+}
+
+/// Checks if the variant at `idx` of the `union` can be accessed from the module `from`.
+pub fn is_variant_accessible(
+    db: &dyn TypeckDatabase,
+    from: FileId,
+    union: ItemId,
+    idx: usize,
+) -> bool {
+    let item = db.lookup_intern_item(union);
+    match &item.kind {
+        ItemKind::Union(union) => is_accessible(db, from, item.file, union.variants[idx].vis),
+        _ => true,
+    }
+}
+
+/// Creates the error for using the private `item` at `span`.
+pub(crate) fn private_item_error(
+    db: &dyn TypeckDatabase,
+    file: FileId,
+    span: Span,
+    item: ItemId,
+) -> Locatable<Error> {
+    let kind = db.lookup_intern_item(item).kind;
+    let name = kind
+        .name()
+        .map_or_else(String::new, |name| db.rodeo().resolve(&name.id).to_string());
+    let err = TypeError::PrivateItem {
+        kind: describe(db, item, &kind).to_string(),
+        name,
+    };
+    Locatable::new(err.into(), file, span)
+}
+
+/// Returns a short description of the kind of the item.
+fn describe(db: &dyn TypeckDatabase, item: ItemId, kind: &ItemKind) -> &'static str {
+    match kind {
+        ItemKind::Def(_) if scope::parent_item(db, item).is_some() => "method",
+        ItemKind::Def(_) => "function",
+        ItemKind::TypeDef(_) => "type",
+        ItemKind::Alias(_) => "type alias",
+        ItemKind::Union(_) => "union",
+        ItemKind::Trait(_) => "trait",
+        ItemKind::Impl(_) => "impl",
+        ItemKind::Const(_) => "constant",
+        ItemKind::Static(_) => "static",
+        ItemKind::Import(_) => "module",
+        ItemKind::Use(_) => "import",
+    }
+}
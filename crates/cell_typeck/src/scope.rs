@@ -0,0 +1,375 @@
+//! Name resolution of items.
+
+use crate::{db::TypeckDatabase, privacy};
+use cell_common::{
+    error::{Error, TypeError},
+    source::{FileId, Locatable},
+    strings::StringId,
+};
+use cell_syntax::ast::{Identifier, ItemId, ItemKind};
+use std::{collections::HashMap, sync::Arc};
+
+/// Every item that can be referenced by name inside a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileScope {
+    /// Types, unions, type aliases and traits.
+    pub types: HashMap<StringId, ItemId>,
+    /// Functions, constants and statics.
+    pub values: HashMap<StringId, ItemId>,
+    /// Every `import`, keyed by the name of the module.
+    pub imports: HashMap<StringId, ItemId>,
+    /// Every `use`, keyed by the name it brings into scope.
+    pub uses: HashMap<StringId, ItemId>,
+    /// Maps every method to the `impl` or `trait` it is defined in.
+    pub parents: HashMap<ItemId, ItemId>,
+    /// Every item, including methods, in the order they appear in the file.
+    pub items: Vec<ItemId>,
+    /// Errors for items that are defined multiple times.
+    pub errors: Vec<Locatable<Error>>,
+}
+
+pub(crate) fn file_scope(db: &dyn TypeckDatabase, file: FileId) -> Arc<FileScope> {
+    let mut scope = FileScope::default();
+
+    for &id in &db.parse(file).items {
+        let item = db.lookup_intern_item(id);
+        scope.items.push(id);
+
+        let name = match &item.kind {
+            ItemKind::Impl(imp) => {
+                for &method in &imp.methods {
+                    scope.parents.insert(method, id);
+                    scope.items.push(method);
+                }
+                continue;
+            }
+            ItemKind::Trait(trait_) => {
+                for &method in &trait_.methods {
+                    scope.parents.insert(method, id);
+                    scope.items.push(method);
+                }
+                &trait_.name
+            }
+            kind => kind.name().expect("every item except impls has a name"),
+        };
+
+        let ns = match item.kind {
+            ItemKind::Def(_) | ItemKind::Const(_) | ItemKind::Static(_) => &mut scope.values,
+            ItemKind::Import(_) => &mut scope.imports,
+            ItemKind::Use(_) => &mut scope.uses,
+            _ => &mut scope.types,
+        };
+        if let Some(first) = ns.insert(name.id, id) {
+            // the first definition wins
+            ns.insert(name.id, first);
+            let previous = db.lookup_intern_item(first).kind.name().unwrap().span;
+            scope.errors.push(Locatable::new(
+                TypeError::DuplicateDefinition {
+                    name: db.rodeo().resolve(&name.id).to_string(),
+                    previous_file: file,
+                    previous,
+                }
+                .into(),
+                file,
+                name.span,
+            ));
+        }
+    }
+
+    Arc::new(scope)
+}
+
+/// Returns the `impl` or `trait` item that the given method is defined in.
+pub fn parent_item(db: &dyn TypeckDatabase, item: ItemId) -> Option<ItemId> {
+    let file = db.lookup_intern_item(item).file;
+    db.file_scope(file).parents.get(&item).copied()
+}
+
+/// The different kinds of names that can be looked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    Types,
+    Values,
+    Modules,
+}
+
+/// Something that a name can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Item(ItemId),
+    Module(FileId),
+}
+
+/// The result of resolving a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolved<T> {
+    pub res: T,
+    /// The first item on the path that can't be accessed from
+    /// where the path is used.
+    pub private: Option<ItemId>,
+}
+
+/// Looks up `name` in the namespace `ns` of `module`, where the lookup
+/// happens inside the module `from`.
+///
+/// Items defined in `module` take precedence over the ones brought into
+/// scope by a `use`.
+pub fn resolve_in(
+    db: &dyn TypeckDatabase,
+    from: FileId,
+    module: FileId,
+    ns: Namespace,
+    name: StringId,
+) -> Option<Binding> {
+    Resolver::new(db).lookup(from, module, ns, name)
+}
+
+/// Resolves a path of module names, starting at `file`.
+///
+/// If the first segment is not a module in scope of `file`, it's
+/// looked up in the root module instead.
+pub fn resolve_module_path(
+    db: &dyn TypeckDatabase,
+    file: FileId,
+    segments: &[Identifier],
+) -> Option<FileId> {
+    Resolver::new(db).module_path(file, segments)
+}
+
+/// Resolves the path of a `use` item in the given namespace.
+pub fn resolve_use(
+    db: &dyn TypeckDatabase,
+    item: ItemId,
+    ns: Namespace,
+) -> Option<Resolved<Binding>> {
+    let mut resolver = Resolver::new(db);
+    let res = resolver.use_path(item, ns)?;
+    Some(Resolved {
+        res,
+        private: resolver.private,
+    })
+}
+
+/// Looks up the item with the given name in the type namespace
+/// of the given file.
+pub fn resolve_type(db: &dyn TypeckDatabase, file: FileId, name: StringId) -> Option<ItemId> {
+    item(resolve_in(db, file, file, Namespace::Types, name))
+}
+
+/// Looks up the item with the given name in the value namespace
+/// of the given file.
+pub fn resolve_value(db: &dyn TypeckDatabase, file: FileId, name: StringId) -> Option<ItemId> {
+    item(resolve_in(db, file, file, Namespace::Values, name))
+}
+
+/// Resolves a path like `module:Type` in the type namespace, as seen from `file`.
+pub fn resolve_type_path(
+    db: &dyn TypeckDatabase,
+    file: FileId,
+    segments: &[Identifier],
+) -> Option<Resolved<ItemId>> {
+    resolve_path(db, file, Namespace::Types, segments)
+}
+
+/// Resolves a path like `module:function` in the value namespace, as seen from `file`.
+pub fn resolve_value_path(
+    db: &dyn TypeckDatabase,
+    file: FileId,
+    segments: &[Identifier],
+) -> Option<Resolved<ItemId>> {
+    resolve_path(db, file, Namespace::Values, segments)
+}
+
+fn resolve_path(
+    db: &dyn TypeckDatabase,
+    file: FileId,
+    ns: Namespace,
+    segments: &[Identifier],
+) -> Option<Resolved<ItemId>> {
+    let (last, modules) = segments.split_last()?;
+    let mut resolver = Resolver::new(db);
+    let module = resolver.module_path(file, modules)?;
+    let res = item(resolver.lookup(file, module, ns, last.id))?;
+    Some(Resolved {
+        res,
+        private: resolver.private,
+    })
+}
+
+fn item(binding: Option<Binding>) -> Option<ItemId> {
+    match binding? {
+        Binding::Item(item) => Some(item),
+        Binding::Module(_) => None,
+    }
+}
+
+/// Looks up the trait with the given name in the given file.
+pub fn resolve_trait(db: &dyn TypeckDatabase, file: FileId, name: StringId) -> Option<ItemId> {
+    let item = resolve_type(db, file, name)?;
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Trait(_) => Some(item),
+        _ => None,
+    }
+}
+
+/// Follows `use` items across modules, while making sure that
+/// cyclic `use`s don't cause infinite recursion.
+struct Resolver<'db> {
+    db: &'db dyn TypeckDatabase,
+    /// Every `use` that is currently being resolved.
+    stack: Vec<ItemId>,
+    /// The first item that was found, but is not accessible.
+    private: Option<ItemId>,
+}
+
+impl<'db> Resolver<'db> {
+    fn new(db: &'db dyn TypeckDatabase) -> Self {
+        Self {
+            db,
+            stack: vec![],
+            private: None,
+        }
+    }
+
+    fn lookup(
+        &mut self,
+        from: FileId,
+        module: FileId,
+        ns: Namespace,
+        name: StringId,
+    ) -> Option<Binding> {
+        let scope = self.db.file_scope(module);
+        let own = match ns {
+            Namespace::Types => scope
+                .types
+                .get(&name)
+                .map(|&item| (item, Binding::Item(item))),
+            Namespace::Values => scope
+                .values
+                .get(&name)
+                .map(|&item| (item, Binding::Item(item))),
+            Namespace::Modules => {
+                let child = self.db.module_tree().child(module, name);
+                child.and_then(|child| Some((*scope.imports.get(&name)?, Binding::Module(child))))
+            }
+        };
+        if let Some((item, binding)) = own {
+            self.check_access(from, module, item);
+            return Some(binding);
+        }
+
+        let use_ = match scope.uses.get(&name) {
+            Some(&use_) => use_,
+            None => return self.prelude(from, module, ns, name),
+        };
+
+        // a `use` that can't be accessed is reported where it's defined,
+        // and not by every path that goes through it
+        let private = self.private.take();
+        let binding = self.use_path(use_, ns);
+        self.private = private;
+        self.check_access(from, module, use_);
+        binding
+    }
+
+    /// Looks up a name that is not defined inside the module in the outer
+    /// files, if the module is the root module, and then in the prelude.
+    ///
+    /// Only names that are not prefixed by a module path are looked up.
+    fn prelude(
+        &mut self,
+        from: FileId,
+        module: FileId,
+        ns: Namespace,
+        name: StringId,
+    ) -> Option<Binding> {
+        if from != module {
+            return None;
+        }
+
+        let source_root = self.db.source_root();
+        if module == source_root.root() {
+            for &outer in source_root.outer().iter().rev() {
+                let scope = self.db.file_scope(outer);
+                let item = match ns {
+                    Namespace::Types => scope.types.get(&name),
+                    Namespace::Values => scope.values.get(&name),
+                    Namespace::Modules => None,
+                };
+                if let Some(&item) = item {
+                    self.check_access(from, outer, item);
+                    return Some(Binding::Item(item));
+                }
+            }
+        }
+
+        let prelude = source_root.prelude()?;
+        if module == prelude {
+            return None;
+        }
+
+        let scope = self.db.file_scope(prelude);
+        let item = match ns {
+            Namespace::Types => *scope.types.get(&name)?,
+            Namespace::Values => *scope.values.get(&name)?,
+            Namespace::Modules => return None,
+        };
+        self.check_access(from, prelude, item);
+        Some(Binding::Item(item))
+    }
+
+    fn check_access(&mut self, from: FileId, module: FileId, item: ItemId) {
+        let vis = self.db.lookup_intern_item(item).vis;
+        if self.private.is_none() && !privacy::is_accessible(self.db, from, module, vis) {
+            self.private = Some(item);
+        }
+    }
+
+    fn use_path(&mut self, use_: ItemId, ns: Namespace) -> Option<Binding> {
+        if self.stack.contains(&use_) {
+            return None;
+        }
+
+        let item = self.db.lookup_intern_item(use_);
+        let file = item.file;
+        let path = match item.kind {
+            ItemKind::Use(use_) => use_.path,
+            _ => panic!("item is not a `use`"),
+        };
+        let (last, modules) = path.segments.split_last()?;
+
+        self.stack.push(use_);
+        let module = if modules.is_empty() {
+            Some(self.db.module_tree().root())
+        } else {
+            self.module_path(file, modules)
+        };
+        let binding = module.and_then(|module| self.lookup(file, module, ns, last.id));
+        self.stack.pop();
+        binding
+    }
+
+    fn module_path(&mut self, file: FileId, segments: &[Identifier]) -> Option<FileId> {
+        let (first, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return Some(file),
+        };
+
+        let root = self.db.module_tree().root();
+        let mut module = match self.lookup(file, file, Namespace::Modules, first.id) {
+            Some(Binding::Module(module)) => module,
+            _ => match self.lookup(file, root, Namespace::Modules, first.id)? {
+                Binding::Module(module) => module,
+                Binding::Item(_) => return None,
+            },
+        };
+
+        for segment in rest {
+            module = match self.lookup(file, module, Namespace::Modules, segment.id)? {
+                Binding::Module(module) => module,
+                Binding::Item(_) => return None,
+            };
+        }
+        Some(module)
+    }
+}
@@ -0,0 +1,426 @@
+//! Traits, impls and everything around them.
+//!
+//! This includes checking that impls implement every method of their
+//! trait, detecting overlapping impls, looking up methods and resolving
+//! calls to trait methods to the method of a concrete impl.
+
+use crate::{
+    db::TypeckDatabase,
+    lower::Generics,
+    scope,
+    ty::{Ty, TyKind},
+    unify::InferTable,
+};
+use cell_common::{
+    error::{Error, TypeError},
+    source::{FileId, Locatable, Span},
+    strings::StringId,
+};
+use cell_syntax::ast::{Def, ItemId, ItemKind};
+use std::{collections::HashMap, sync::Arc};
+
+/// The maximum depth when checking if the bounds of an impl are satisfied.
+const MAX_IMPL_DEPTH: usize = 16;
+
+pub(crate) fn impls(db: &dyn TypeckDatabase, file: FileId) -> Arc<Vec<ItemId>> {
+    let impls = db
+        .file_scope(file)
+        .items
+        .iter()
+        .copied()
+        .filter(|&item| matches!(db.lookup_intern_item(item).kind, ItemKind::Impl(_)))
+        .collect();
+    Arc::new(impls)
+}
+
+pub(crate) fn crate_impls(db: &dyn TypeckDatabase) -> Arc<Vec<ItemId>> {
+    let impls = db
+        .module_tree()
+        .files()
+        .flat_map(|file| db.impls(file).iter().copied().collect::<Vec<_>>())
+        .collect();
+    Arc::new(impls)
+}
+
+pub(crate) fn impl_trait(db: &dyn TypeckDatabase, item: ItemId) -> Option<ItemId> {
+    let item = db.lookup_intern_item(item);
+    match item.kind {
+        ItemKind::Impl(imp) => scope::resolve_trait(db, item.file, imp.trait_?.id),
+        _ => None,
+    }
+}
+
+/// Returns the function definition of the given item.
+pub fn def(db: &dyn TypeckDatabase, item: ItemId) -> Def {
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Def(def) => def,
+        _ => panic!("item is not a function"),
+    }
+}
+
+/// Returns all methods of an `impl` or `trait`.
+fn methods_of(db: &dyn TypeckDatabase, item: ItemId) -> Vec<ItemId> {
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Impl(imp) => imp.methods,
+        ItemKind::Trait(trait_) => trait_.methods,
+        _ => vec![],
+    }
+}
+
+/// Finds the method with the given name inside an `impl` or `trait`.
+pub fn find_method(db: &dyn TypeckDatabase, item: ItemId, name: StringId) -> Option<ItemId> {
+    methods_of(db, item)
+        .into_iter()
+        .find(|&method| def(db, method).name.id == name)
+}
+
+/// Finds the method with the given name that is callable for
+/// the given `impl`.
+///
+/// If the impl is a trait impl and doesn't define the method itself,
+/// the default method of the trait is returned.
+pub fn impl_method(db: &dyn TypeckDatabase, imp: ItemId, name: StringId) -> Option<ItemId> {
+    find_method(db, imp, name).or_else(|| {
+        let method = find_method(db, db.impl_trait(imp)?, name)?;
+        def(db, method).body.as_ref().map(|_| method)
+    })
+}
+
+/// Instantiates the generics of `imp` with new inference variables and
+/// tries to unify the self type of the impl with `ty`.
+///
+/// Returns the generic arguments of the impl, if they could be unified.
+pub fn match_impl(
+    table: &mut InferTable<'_>,
+    db: &dyn TypeckDatabase,
+    imp: ItemId,
+    ty: Ty,
+) -> Option<Vec<Ty>> {
+    let snapshot = table.snapshot();
+    let args = table.new_vars(db.generics_of(imp).len());
+    let self_ty = db.impl_self_ty(imp).subst(db, &args);
+    if table.unify(self_ty, ty) {
+        Some(args)
+    } else {
+        table.rollback(snapshot);
+        None
+    }
+}
+
+/// Checks if `ty` implements `trait_`.
+///
+/// `generics` are the generics that are in scope where the check is
+/// performed, and are used to look up the bounds of generic parameters.
+pub fn implements(db: &dyn TypeckDatabase, generics: &Generics, ty: Ty, trait_: ItemId) -> bool {
+    implements_inner(db, generics, ty, trait_, 0)
+}
+
+fn implements_inner(
+    db: &dyn TypeckDatabase,
+    generics: &Generics,
+    ty: Ty,
+    trait_: ItemId,
+    depth: usize,
+) -> bool {
+    match ty.kind(db) {
+        TyKind::Param(idx, _) => {
+            return generics
+                .params
+                .get(idx as usize)
+                .is_some_and(|param| param.bounds.contains(&trait_))
+        }
+        TyKind::Error | TyKind::Infer(_) | TyKind::Never => return true,
+        _ if depth > MAX_IMPL_DEPTH => return false,
+        _ => {}
+    }
+
+    db.crate_impls().iter().any(|&imp| {
+        if db.impl_trait(imp) != Some(trait_) {
+            return false;
+        }
+
+        let mut table = InferTable::new(db);
+        let args = match match_impl(&mut table, db, imp, ty) {
+            Some(args) => args,
+            None => return false,
+        };
+
+        // every bound on the generics of the impl must be satisfied too
+        db.generics_of(imp)
+            .params
+            .iter()
+            .zip(args)
+            .all(|(param, arg)| {
+                let arg = table.resolve(arg);
+                param
+                    .bounds
+                    .iter()
+                    .all(|&bound| implements_inner(db, generics, arg, bound, depth + 1))
+            })
+    })
+}
+
+/// Resolves a call to `def` with the generic arguments `args` to the
+/// function that will actually be executed.
+///
+/// If `def` is a method of a trait and `Self` is known, this will find the impl
+/// for `Self` and return the method inside that impl, together with the
+/// generic arguments for it. In every other case, `def` and `args`
+/// are returned unchanged.
+pub fn resolve_instance(db: &dyn TypeckDatabase, def: ItemId, args: &[Ty]) -> (ItemId, Vec<Ty>) {
+    let trait_ = match scope::parent_item(db, def) {
+        Some(parent) if matches!(db.lookup_intern_item(parent).kind, ItemKind::Trait(_)) => parent,
+        _ => return (def, args.to_vec()),
+    };
+    let self_ty = args[0];
+    if matches!(self_ty.kind(db), TyKind::Param(..)) {
+        return (def, args.to_vec());
+    }
+
+    let name = self::def(db, def).name.id;
+    for &imp in db.crate_impls().iter() {
+        if db.impl_trait(imp) != Some(trait_) {
+            continue;
+        }
+
+        let mut table = InferTable::new(db);
+        let impl_args = match match_impl(&mut table, db, imp, self_ty) {
+            Some(args) => args,
+            None => continue,
+        };
+
+        return match find_method(db, imp, name) {
+            Some(method) => {
+                let mut method_args = impl_args
+                    .into_iter()
+                    .map(|ty| table.resolve(ty))
+                    .collect::<Vec<_>>();
+                method_args.extend_from_slice(&args[1..]);
+                (method, method_args)
+            }
+            // the impl uses the default implementation of the trait
+            None => (def, args.to_vec()),
+        };
+    }
+
+    (def, args.to_vec())
+}
+
+/// Returns the span that is used to report errors for an impl.
+///
+/// This is the name of the trait for trait impls, and the whole impl
+/// for inherent impls.
+fn impl_span(db: &dyn TypeckDatabase, imp: ItemId) -> Span {
+    let item = db.lookup_intern_item(imp);
+    match item.kind {
+        ItemKind::Impl(imp) => imp.trait_.map_or(item.span, |name| name.span),
+        _ => item.span,
+    }
+}
+
+/// Checks every impl inside the file.
+///
+/// This makes sure that:
+/// - trait impls refer to a trait
+/// - trait impls implement every required method, with the right signature
+/// - trait impls only define methods that are part of the trait
+/// - there are no two impls of the same trait for overlapping types
+/// - inherent impls don't define the same method twice for overlapping types
+pub(crate) fn check_impls(db: &dyn TypeckDatabase, file: FileId) -> Arc<Vec<Locatable<Error>>> {
+    let mut errors = vec![];
+    let mut error =
+        |err: TypeError, span: Span| errors.push(Locatable::new(err.into(), file, span));
+    let rodeo = db.rodeo();
+
+    let impls = db.impls(file);
+    for &imp in impls.iter() {
+        let item = match db.lookup_intern_item(imp).kind {
+            ItemKind::Impl(item) => item,
+            _ => unreachable!(),
+        };
+
+        let mut seen = HashMap::new();
+        for &method in &item.methods {
+            let name = def(db, method).name;
+            if let Some(previous) = seen.insert(name.id, name.span) {
+                error(
+                    TypeError::DuplicateMethod {
+                        ty: db.impl_self_ty(imp).display(db),
+                        method: rodeo.resolve(&name.id).to_string(),
+                        previous_file: file,
+                        previous,
+                    },
+                    name.span,
+                );
+            }
+        }
+
+        let trait_name = match item.trait_ {
+            Some(name) => name,
+            None => continue,
+        };
+        let trait_ = match db.impl_trait(imp) {
+            Some(trait_) => trait_,
+            None => {
+                let name = rodeo.resolve(&trait_name.id).to_string();
+                error(TypeError::UnresolvedTrait { name }, trait_name.span);
+                continue;
+            }
+        };
+
+        let trait_display = rodeo.resolve(&trait_name.id).to_string();
+        let self_ty = db.impl_self_ty(imp);
+        let impl_generics = db.generics_of(imp).len();
+
+        for &method in &item.methods {
+            let method_def = def(db, method);
+            let name = method_def.name;
+            let trait_method = match find_method(db, trait_, name.id) {
+                Some(trait_method) => trait_method,
+                None => {
+                    error(
+                        TypeError::NotATraitMember {
+                            trait_: trait_display.clone(),
+                            method: rodeo.resolve(&name.id).to_string(),
+                        },
+                        name.span,
+                    );
+                    continue;
+                }
+            };
+
+            // map the generics of the trait method to the ones of the impl method
+            let impl_method_generics = db.generics_of(method);
+            let trait_method_generics = db.generics_of(trait_method);
+            let own = impl_method_generics.len() - impl_generics;
+            if own != trait_method_generics.len() - 1 {
+                error(
+                    TypeError::WrongGenericCount {
+                        expected: trait_method_generics.len() - 1,
+                        found: own,
+                    },
+                    name.span,
+                );
+                continue;
+            }
+
+            let mut args = vec![self_ty];
+            args.extend_from_slice(&impl_method_generics.identity(db)[impl_generics..]);
+            let expected = db.fn_sig(trait_method).subst(db, &args);
+            let found = db.fn_sig(method);
+
+            let same = expected.params.len() == found.params.len()
+                && expected
+                    .params
+                    .iter()
+                    .zip(&found.params)
+                    .all(|(a, b)| a.same_as(db, *b))
+                && expected.ret.same_as(db, found.ret);
+            if !same {
+                error(
+                    TypeError::TraitSignatureMismatch {
+                        trait_: trait_display.clone(),
+                        method: rodeo.resolve(&name.id).to_string(),
+                    },
+                    name.span,
+                );
+            }
+        }
+
+        for trait_method in methods_of(db, trait_) {
+            let trait_method = def(db, trait_method);
+            if trait_method.body.is_none() && !seen.contains_key(&trait_method.name.id) {
+                error(
+                    TypeError::MissingTraitMethod {
+                        trait_: trait_display.clone(),
+                        method: rodeo.resolve(&trait_method.name.id).to_string(),
+                    },
+                    trait_name.span,
+                );
+            }
+        }
+    }
+
+    // every impl of this file is compared to the impls that come before it,
+    // which can be defined in other modules too
+    let crate_impls = db.crate_impls();
+    for &imp in impls.iter() {
+        let idx = crate_impls
+            .iter()
+            .position(|&other| other == imp)
+            .unwrap_or(crate_impls.len());
+        let previous_impls = &crate_impls[..idx];
+
+        match db.impl_trait(imp) {
+            // check for overlapping trait impls
+            Some(trait_) => {
+                let previous = previous_impls.iter().find(|&&other| {
+                    db.impl_trait(other) == Some(trait_) && overlap(db, imp, other)
+                });
+                if let Some(&previous) = previous {
+                    let trait_ = def_name(db, trait_);
+                    error(
+                        TypeError::OverlappingImpls {
+                            trait_: rodeo.resolve(&trait_).to_string(),
+                            ty: db.impl_self_ty(imp).display(db),
+                            previous_file: db.lookup_intern_item(previous).file,
+                            previous: impl_span(db, previous),
+                        },
+                        impl_span(db, imp),
+                    );
+                }
+            }
+            // check for methods with the same name in overlapping inherent impls
+            None if is_inherent(db, imp) => {
+                for &other in previous_impls {
+                    if !is_inherent(db, other) || !overlap(db, imp, other) {
+                        continue;
+                    }
+
+                    for method in methods_of(db, imp) {
+                        let name = def(db, method).name;
+                        if let Some(previous) = find_method(db, other, name.id) {
+                            error(
+                                TypeError::DuplicateMethod {
+                                    ty: db.impl_self_ty(imp).display(db),
+                                    method: rodeo.resolve(&name.id).to_string(),
+                                    previous_file: db.lookup_intern_item(previous).file,
+                                    previous: def(db, previous).name.span,
+                                },
+                                name.span,
+                            );
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    Arc::new(errors)
+}
+
+/// Checks if the impl doesn't implement a trait.
+fn is_inherent(db: &dyn TypeckDatabase, imp: ItemId) -> bool {
+    match db.lookup_intern_item(imp).kind {
+        ItemKind::Impl(imp) => imp.trait_.is_none(),
+        _ => false,
+    }
+}
+
+/// Checks if there is a type that both impls apply to.
+fn overlap(db: &dyn TypeckDatabase, a: ItemId, b: ItemId) -> bool {
+    let mut table = InferTable::new(db);
+    let args = table.new_vars(db.generics_of(b).len());
+    let b = db.impl_self_ty(b).subst(db, &args);
+    match_impl(&mut table, db, a, b).is_some()
+}
+
+/// Returns the name of a named item.
+fn def_name(db: &dyn TypeckDatabase, item: ItemId) -> StringId {
+    db.lookup_intern_item(item)
+        .kind
+        .name()
+        .expect("item has no name")
+        .id
+}
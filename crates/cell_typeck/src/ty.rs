@@ -0,0 +1,237 @@
+//! The semantic representation of types.
+
+use crate::db::TypeckDatabase;
+use cell_common::{intern_id_struct, strings::StringId};
+use cell_syntax::ast::ItemId;
+
+intern_id_struct! {
+    /// An interned [`TyKind`].
+    ///
+    /// [`TyKind`]: ./enum.TyKind.html
+    pub struct Ty;
+}
+
+/// A fully resolved type.
+///
+/// In contrast to [`ast::Type`], every name inside a `TyKind` is
+/// resolved to the item it refers to.
+///
+/// [`ast::Type`]: ../../cell_syntax/ast/enum.Type.html
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum TyKind {
+    Int {
+        width: u16,
+        signed: bool,
+    },
+    Float {
+        width: u16,
+    },
+    Bool,
+    String,
+    Unit,
+    Char,
+    Pointer(Ty),
+    Tuple(Vec<Ty>),
+    NamedTuple(Vec<(StringId, Ty)>),
+    /// A `type` or `union` definition together with its generic arguments.
+    Adt(ItemId, Vec<Ty>),
+    /// A generic parameter.
+    ///
+    /// The index points into the [`Generics`] of the item
+    /// this type was created for.
+    ///
+    /// [`Generics`]: ../lower/struct.Generics.html
+    Param(u32, StringId),
+    /// The type of expressions that never produce a value, like `return`.
+    Never,
+    /// A type that is not known yet. These only exist while a body
+    /// is being checked.
+    Infer(InferVar),
+    /// The type of something that failed to type check.
+    ///
+    /// This type is compatible with every other type,
+    /// to prevent follow-up errors.
+    Error,
+}
+
+/// A type variable that is created during type inference.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum InferVar {
+    /// A variable that can be resolved to any type.
+    General(u32),
+    /// A variable that can only be resolved to an integer type.
+    Int(u32),
+    /// A variable that can only be resolved to a float type.
+    Float(u32),
+}
+
+impl InferVar {
+    /// Returns the index of this variable.
+    pub fn index(self) -> u32 {
+        match self {
+            InferVar::General(idx) | InferVar::Int(idx) | InferVar::Float(idx) => idx,
+        }
+    }
+}
+
+impl TyKind {
+    /// Interns this type.
+    pub fn intern(self, db: &dyn TypeckDatabase) -> Ty {
+        db.intern_ty(self)
+    }
+
+    /// The default integer type, that is used if the type of an
+    /// integer literal can not be inferred.
+    pub fn default_int() -> Self {
+        TyKind::Int {
+            width: 32,
+            signed: true,
+        }
+    }
+
+    /// The default float type, that is used if the type of a
+    /// float literal can not be inferred.
+    pub fn default_float() -> Self {
+        TyKind::Float { width: 64 }
+    }
+}
+
+impl Ty {
+    /// Looks up the kind of this type.
+    pub fn kind(self, db: &dyn TypeckDatabase) -> TyKind {
+        db.lookup_intern_ty(self)
+    }
+
+    /// Checks if this type is an error type.
+    pub fn is_error(self, db: &dyn TypeckDatabase) -> bool {
+        self.kind(db) == TyKind::Error
+    }
+
+    /// Replaces every generic parameter inside this type with the
+    /// type at the same index in `args`.
+    ///
+    /// Parameters that have no matching argument are left untouched.
+    pub fn subst(self, db: &dyn TypeckDatabase, args: &[Ty]) -> Ty {
+        if args.is_empty() {
+            return self;
+        }
+        self.map(db, &mut |db, ty| match ty.kind(db) {
+            TyKind::Param(idx, _) => args.get(idx as usize).copied(),
+            _ => None,
+        })
+    }
+
+    /// Walks this type bottom up and replaces every type for which `f`
+    /// returns `Some`.
+    pub fn map(
+        self,
+        db: &dyn TypeckDatabase,
+        f: &mut dyn FnMut(&dyn TypeckDatabase, Ty) -> Option<Ty>,
+    ) -> Ty {
+        if let Some(ty) = f(db, self) {
+            return ty;
+        }
+
+        let kind = match self.kind(db) {
+            TyKind::Pointer(inner) => TyKind::Pointer(inner.map(db, f)),
+            TyKind::Tuple(tys) => TyKind::Tuple(tys.into_iter().map(|ty| ty.map(db, f)).collect()),
+            TyKind::NamedTuple(fields) => TyKind::NamedTuple(
+                fields
+                    .into_iter()
+                    .map(|(name, ty)| (name, ty.map(db, f)))
+                    .collect(),
+            ),
+            TyKind::Adt(item, args) => {
+                TyKind::Adt(item, args.into_iter().map(|ty| ty.map(db, f)).collect())
+            }
+            _ => return self,
+        };
+        kind.intern(db)
+    }
+
+    /// Checks if `f` returns `true` for this type, or any type inside it.
+    pub fn any(self, db: &dyn TypeckDatabase, f: &mut dyn FnMut(&TyKind) -> bool) -> bool {
+        let kind = self.kind(db);
+        if f(&kind) {
+            return true;
+        }
+
+        match kind {
+            TyKind::Pointer(inner) => inner.any(db, f),
+            TyKind::Tuple(tys) | TyKind::Adt(_, tys) => tys.into_iter().any(|ty| ty.any(db, f)),
+            TyKind::NamedTuple(fields) => fields.into_iter().any(|(_, ty)| ty.any(db, f)),
+            _ => false,
+        }
+    }
+
+    /// Checks if two types are the same, ignoring the names of
+    /// generic parameters.
+    pub fn same_as(self, db: &dyn TypeckDatabase, other: Ty) -> bool {
+        if self == other {
+            return true;
+        }
+
+        let all = |a: Vec<Ty>, b: Vec<Ty>| {
+            a.len() == b.len() && a.into_iter().zip(b).all(|(a, b)| a.same_as(db, b))
+        };
+        match (self.kind(db), other.kind(db)) {
+            (TyKind::Param(a, _), TyKind::Param(b, _)) => a == b,
+            (TyKind::Pointer(a), TyKind::Pointer(b)) => a.same_as(db, b),
+            (TyKind::Tuple(a), TyKind::Tuple(b)) => all(a, b),
+            (TyKind::Adt(a, a_args), TyKind::Adt(b, b_args)) => a == b && all(a_args, b_args),
+            (TyKind::NamedTuple(a), TyKind::NamedTuple(b)) => {
+                a.len() == b.len()
+                    && a.into_iter()
+                        .zip(b)
+                        .all(|((a_name, a), (b_name, b))| a_name == b_name && a.same_as(db, b))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns a human readable representation of this type.
+    pub fn display(self, db: &dyn TypeckDatabase) -> String {
+        let list = |tys: Vec<Ty>| {
+            tys.into_iter()
+                .map(|ty| ty.display(db))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self.kind(db) {
+            TyKind::Int { width, signed } => format!("{}{}", if signed { 'i' } else { 'u' }, width),
+            TyKind::Float { width } => format!("f{}", width),
+            TyKind::Bool => "bool".to_string(),
+            TyKind::String => "String".to_string(),
+            TyKind::Unit => "()".to_string(),
+            TyKind::Char => "char".to_string(),
+            TyKind::Pointer(inner) => format!("*{}", inner.display(db)),
+            TyKind::Tuple(tys) => format!("{{{}}}", list(tys)),
+            TyKind::NamedTuple(fields) => {
+                let rodeo = db.rodeo();
+                let fields = fields
+                    .into_iter()
+                    .map(|(name, ty)| format!("{}: {}", rodeo.resolve(&name), ty.display(db)))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", fields.join(", "))
+            }
+            TyKind::Adt(item, args) => {
+                let item = db.lookup_intern_item(item);
+                let name = match item.kind.name() {
+                    Some(name) => db.rodeo().resolve(&name.id).to_string(),
+                    None => "{unknown}".to_string(),
+                };
+                if args.is_empty() {
+                    name
+                } else {
+                    format!("{}[{}]", name, list(args))
+                }
+            }
+            TyKind::Param(_, name) => db.rodeo().resolve(&name).to_string(),
+            TyKind::Never => "never".to_string(),
+            TyKind::Infer(InferVar::Int(_)) => "{integer}".to_string(),
+            TyKind::Infer(InferVar::Float(_)) => "{float}".to_string(),
+            TyKind::Infer(InferVar::General(_)) | TyKind::Error => "{unknown}".to_string(),
+        }
+    }
+}
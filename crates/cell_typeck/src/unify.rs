@@ -0,0 +1,175 @@
+//! The unification table that is used for type inference.
+
+use crate::{
+    db::TypeckDatabase,
+    ty::{InferVar, Ty, TyKind},
+};
+
+/// A snapshot of an [`InferTable`] that can be used to undo
+/// every change that was made after the snapshot was taken.
+///
+/// [`InferTable`]: ./struct.InferTable.html
+pub struct Snapshot {
+    vars: Vec<Option<Ty>>,
+}
+
+/// Stores the values of every inference variable.
+pub struct InferTable<'db> {
+    db: &'db dyn TypeckDatabase,
+    vars: Vec<Option<Ty>>,
+}
+
+impl<'db> InferTable<'db> {
+    /// Creates an empty table.
+    pub fn new(db: &'db dyn TypeckDatabase) -> Self {
+        Self { db, vars: vec![] }
+    }
+
+    fn new_var_with(&mut self, var: fn(u32) -> InferVar) -> Ty {
+        let idx = self.vars.len() as u32;
+        self.vars.push(None);
+        TyKind::Infer(var(idx)).intern(self.db)
+    }
+
+    /// Creates a new variable that can be resolved to any type.
+    pub fn new_var(&mut self) -> Ty {
+        self.new_var_with(InferVar::General)
+    }
+
+    /// Creates a new variable that can only be resolved to integers.
+    pub fn new_int_var(&mut self) -> Ty {
+        self.new_var_with(InferVar::Int)
+    }
+
+    /// Creates a new variable that can only be resolved to floats.
+    pub fn new_float_var(&mut self) -> Ty {
+        self.new_var_with(InferVar::Float)
+    }
+
+    /// Creates `count` new general variables.
+    pub fn new_vars(&mut self, count: usize) -> Vec<Ty> {
+        (0..count).map(|_| self.new_var()).collect()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            vars: self.vars.clone(),
+        }
+    }
+
+    pub fn rollback(&mut self, snapshot: Snapshot) {
+        self.vars = snapshot.vars;
+    }
+
+    /// Follows the bindings of inference variables until a type
+    /// is found that is not a bound variable.
+    pub fn shallow_resolve(&self, mut ty: Ty) -> Ty {
+        while let TyKind::Infer(var) = ty.kind(self.db) {
+            match self.vars[var.index() as usize] {
+                Some(bound) => ty = bound,
+                None => break,
+            }
+        }
+        ty
+    }
+
+    /// Replaces every bound inference variable inside `ty`.
+    pub fn resolve(&self, ty: Ty) -> Ty {
+        ty.map(self.db, &mut |db, ty| match ty.kind(db) {
+            TyKind::Infer(_) => {
+                let resolved = self.shallow_resolve(ty);
+                if resolved == ty {
+                    Some(ty)
+                } else {
+                    Some(self.resolve(resolved))
+                }
+            }
+            _ => None,
+        })
+    }
+
+    /// Resolves `ty` completely and replaces every variable that is still
+    /// unbound by a default type.
+    ///
+    /// Integer and float variables default to `i32` and `f64`. General
+    /// variables are replaced by the error type and are returned in `unresolved`.
+    pub fn fallback(&self, ty: Ty, unresolved: &mut Vec<u32>) -> Ty {
+        let ty = self.resolve(ty);
+        ty.map(self.db, &mut |db, ty| match ty.kind(db) {
+            TyKind::Infer(InferVar::Int(_)) => Some(TyKind::default_int().intern(db)),
+            TyKind::Infer(InferVar::Float(_)) => Some(TyKind::default_float().intern(db)),
+            TyKind::Infer(InferVar::General(idx)) => {
+                unresolved.push(idx);
+                Some(TyKind::Error.intern(db))
+            }
+            _ => None,
+        })
+    }
+
+    /// Tries to make `a` and `b` the same type, by binding
+    /// inference variables.
+    ///
+    /// Returns `false` if the types are incompatible. Variables may already
+    /// be bound if this fails, so use a snapshot if that matters.
+    pub fn unify(&mut self, a: Ty, b: Ty) -> bool {
+        let a = self.shallow_resolve(a);
+        let b = self.shallow_resolve(b);
+        if a == b {
+            return true;
+        }
+
+        let db = self.db;
+        match (a.kind(db), b.kind(db)) {
+            (TyKind::Error, _) | (_, TyKind::Error) => true,
+            (TyKind::Infer(var), other) => self.bind(var, b, &other),
+            (other, TyKind::Infer(var)) => self.bind(var, a, &other),
+            (TyKind::Pointer(a), TyKind::Pointer(b)) => self.unify(a, b),
+            (TyKind::Tuple(a), TyKind::Tuple(b)) => self.unify_all(a, b),
+            (TyKind::NamedTuple(a), TyKind::NamedTuple(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(&b).all(|((a, _), (b, _))| a == b)
+                    && self.unify_all(
+                        a.into_iter().map(|(_, ty)| ty).collect(),
+                        b.into_iter().map(|(_, ty)| ty).collect(),
+                    )
+            }
+            (TyKind::Adt(a, a_args), TyKind::Adt(b, b_args)) => {
+                a == b && self.unify_all(a_args, b_args)
+            }
+            _ => false,
+        }
+    }
+
+    fn unify_all(&mut self, a: Vec<Ty>, b: Vec<Ty>) -> bool {
+        a.len() == b.len() && a.into_iter().zip(b).all(|(a, b)| self.unify(a, b))
+    }
+
+    /// Binds the variable `var` to `ty`, whose kind is `kind`.
+    fn bind(&mut self, var: InferVar, ty: Ty, kind: &TyKind) -> bool {
+        let compatible = match (var, kind) {
+            (_, TyKind::Infer(InferVar::General(other))) => {
+                // bind the general variable instead, to keep the more specific one
+                let var = TyKind::Infer(var).intern(self.db);
+                self.vars[*other as usize] = Some(var);
+                return true;
+            }
+            (InferVar::General(_), _) => !self.occurs(var, ty),
+            (InferVar::Int(_), TyKind::Int { .. })
+            | (InferVar::Int(_), TyKind::Infer(InferVar::Int(_))) => true,
+            (InferVar::Float(_), TyKind::Float { .. })
+            | (InferVar::Float(_), TyKind::Infer(InferVar::Float(_))) => true,
+            _ => false,
+        };
+
+        if compatible {
+            self.vars[var.index() as usize] = Some(ty);
+        }
+        compatible
+    }
+
+    /// Checks if the variable appears inside `ty`.
+    fn occurs(&self, var: InferVar, ty: Ty) -> bool {
+        let ty = self.resolve(ty);
+        ty.any(self.db, &mut |kind| *kind == TyKind::Infer(var))
+    }
+}
@@ -0,0 +1,46 @@
+//! Helpers to check programs that are given as source code.
+
+use cell_common::{
+    source::{FileId, SourceDatabase},
+    Diagnostic,
+};
+use cell_db::RootDatabase;
+use cell_typeck::db::TypeckDatabase;
+use std::{collections::HashMap, path::PathBuf};
+
+/// Loads `source` as the root module `main.cell`.
+pub fn load(source: &str) -> (RootDatabase, FileId) {
+    let mut db = RootDatabase::new();
+    let path = PathBuf::from("main.cell");
+    let mut overlay = HashMap::new();
+    overlay.insert(path.clone(), source.to_string());
+    let file = db
+        .load_program_with(&path, &overlay)
+        .expect("failed to load the program");
+    (db, file)
+}
+
+/// Checks `source` and returns every error and warning like
+/// "E0201 literal out of range for `u8`: `300`", where the
+/// last part is the source code that the primary label points to.
+pub fn check(source: &str) -> Vec<String> {
+    let (db, file) = load(source);
+    let errors = db.check_file(file);
+    let warnings = db.file_warnings(file);
+    let errors = errors.iter().cloned().map(Diagnostic::from);
+    let warnings = warnings.iter().cloned().map(Diagnostic::from);
+
+    let text = db.file_text(file);
+    errors
+        .chain(warnings)
+        .map(|diagnostic| {
+            let range = diagnostic.labels[0].range.clone();
+            format!(
+                "{} {}: `{}`",
+                diagnostic.code.unwrap_or_default(),
+                diagnostic.message,
+                &text[range]
+            )
+        })
+        .collect()
+}
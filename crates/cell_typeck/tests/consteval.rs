@@ -0,0 +1,286 @@
+//! Tests of the constant evaluator.
+
+mod common;
+
+use cell_common::error::ConstError;
+use cell_syntax::ast::{BinOp, CmpOp, UnOp};
+use cell_typeck::consteval::{binary_op, compare, unary_op, ConstInt, ConstValue};
+use ordered_float::OrderedFloat;
+
+fn int(val: i128, ty: &str) -> ConstValue {
+    let signed = ty.starts_with('i');
+    let width = ty[1..].parse().unwrap();
+    let int = ConstInt::from_i128(val, width, signed)
+        .unwrap_or_else(|| panic!("`{}` doesn't fit into `{}`", val, ty));
+    ConstValue::Int(int)
+}
+
+fn uint(val: u128, ty: &str) -> ConstValue {
+    let int = ConstInt::from_u128(val, ty[1..].parse().unwrap(), ty.starts_with('i'))
+        .unwrap_or_else(|| panic!("`{}` doesn't fit into `{}`", val, ty));
+    ConstValue::Int(int)
+}
+
+fn float(val: f64, width: u16) -> ConstValue {
+    ConstValue::Float {
+        val: OrderedFloat(val),
+        width,
+    }
+}
+
+fn overflow(ty: &str) -> ConstError {
+    ConstError::Overflow { ty: ty.to_owned() }
+}
+
+#[test]
+fn arithmetic() {
+    let cases = [
+        (BinOp::Add, 100, 27, "i8", Ok(127)),
+        (BinOp::Add, 100, 28, "i8", Err(overflow("i8"))),
+        (BinOp::Sub, -100, 28, "i8", Ok(-128)),
+        (BinOp::Sub, -100, 29, "i8", Err(overflow("i8"))),
+        (BinOp::Sub, 0, 1, "u8", Err(overflow("u8"))),
+        (BinOp::Mul, 16, 15, "u8", Ok(240)),
+        (BinOp::Mul, 16, 16, "u8", Err(overflow("u8"))),
+        (BinOp::Mul, -64, 2, "i8", Ok(-128)),
+        (BinOp::Div, -7, 2, "i32", Ok(-3)),
+        (BinOp::Div, -128, -1, "i8", Err(overflow("i8"))),
+        (BinOp::Div, 1, 0, "u8", Err(ConstError::DivisionByZero)),
+        (BinOp::Mod, -7, 2, "i32", Ok(-1)),
+        (BinOp::Mod, -128, -1, "i8", Ok(0)),
+        (BinOp::Mod, 1, 0, "i64", Err(ConstError::RemainderByZero)),
+        (BinOp::Pow, 2, 7, "u8", Ok(128)),
+        (BinOp::Pow, -2, 7, "i8", Ok(-128)),
+        (BinOp::Pow, 2, 7, "i8", Err(overflow("i8"))),
+        (BinOp::Pow, -1, 1001, "i32", Ok(-1)),
+        (BinOp::Pow, 0, 0, "i32", Ok(1)),
+        (BinOp::Pow, 2, -1, "i32", Err(ConstError::NegativeExponent)),
+    ];
+    for (op, a, b, ty, expected) in cases.iter().cloned() {
+        let found = binary_op(op, int(a, ty), int(b, ty));
+        assert_eq!(
+            found,
+            expected.map(|val| int(val, ty)),
+            "`{} {} {}` as `{}`",
+            a,
+            op,
+            b,
+            ty
+        );
+    }
+}
+
+/// Powers are calculated exactly instead of with floats,
+/// even if the result doesn't fit into a `f64`.
+#[test]
+fn precise_pow() {
+    assert_eq!(
+        binary_op(BinOp::Pow, uint(3, "u64"), uint(40, "u64")),
+        Ok(uint(12_157_665_459_056_928_801, "u64"))
+    );
+    assert_eq!(
+        binary_op(BinOp::Pow, uint(3, "u128"), uint(80, "u128")),
+        Ok(uint(
+            147_808_829_414_345_923_316_083_210_206_383_297_601,
+            "u128"
+        ))
+    );
+    assert_eq!(
+        binary_op(BinOp::Pow, uint(3, "u128"), uint(81, "u128")),
+        Err(overflow("u128"))
+    );
+    assert_eq!(
+        binary_op(BinOp::Pow, int(-2, "i128"), int(127, "i128")),
+        Ok(int(i128::MIN, "i128"))
+    );
+}
+
+#[test]
+fn bitwise() {
+    let cases = [
+        (BinOp::BitiwseAnd, 0b1100, 0b1010, "u8", Ok(0b1000)),
+        (BinOp::BitwiseOr, 0b1100, 0b1010, "u8", Ok(0b1110)),
+        (BinOp::BitwiseXor, 0b1100, 0b1010, "u8", Ok(0b0110)),
+        (BinOp::BitiwseAnd, -1, 0x7f, "i8", Ok(0x7f)),
+        (BinOp::LeftShift, 1, 7, "u8", Ok(128)),
+        (BinOp::LeftShift, 3, 7, "u8", Ok(128)),
+        (BinOp::LeftShift, 1, 7, "i8", Ok(-128)),
+        (BinOp::RightShift, -128, 7, "i8", Ok(-1)),
+        (BinOp::RightShift, 128, 7, "i16", Ok(1)),
+    ];
+    for (op, a, b, ty, expected) in cases.iter().cloned() {
+        let found = binary_op(op, int(a, ty), int(b, ty));
+        assert_eq!(
+            found,
+            expected.map(|val| int(val, ty)),
+            "`{} {} {}` as `{}`",
+            a,
+            op,
+            b,
+            ty
+        );
+    }
+
+    let too_large = |amount: &str| ConstError::ShiftTooLarge {
+        ty: "u8".to_owned(),
+        amount: amount.to_owned(),
+    };
+    assert_eq!(
+        binary_op(BinOp::LeftShift, int(1, "u8"), int(8, "u8")),
+        Err(too_large("8"))
+    );
+    assert_eq!(
+        binary_op(BinOp::RightShift, int(1, "u8"), int(200, "u8")),
+        Err(too_large("200"))
+    );
+}
+
+#[test]
+fn unary() {
+    assert_eq!(unary_op(UnOp::Minus, int(127, "i8")), Ok(int(-127, "i8")));
+    assert_eq!(unary_op(UnOp::Minus, int(-128, "i8")), Err(overflow("i8")));
+    assert_eq!(unary_op(UnOp::Minus, int(0, "u8")), Ok(int(0, "u8")));
+    assert_eq!(unary_op(UnOp::Minus, int(1, "u8")), Err(overflow("u8")));
+    assert_eq!(unary_op(UnOp::Plus, int(-5, "i8")), Ok(int(-5, "i8")));
+    assert_eq!(unary_op(UnOp::Not, int(0, "u8")), Ok(int(255, "u8")));
+    assert_eq!(unary_op(UnOp::Not, int(0, "i8")), Ok(int(-1, "i8")));
+    assert_eq!(
+        unary_op(UnOp::Not, ConstValue::Bool(true)),
+        Ok(ConstValue::Bool(false))
+    );
+    assert_eq!(unary_op(UnOp::Minus, float(1.5, 64)), Ok(float(-1.5, 64)));
+    assert_eq!(
+        unary_op(UnOp::Deref, int(1, "i32")),
+        Err(ConstError::NotConst)
+    );
+}
+
+#[test]
+fn floats_and_bools() {
+    assert_eq!(
+        binary_op(BinOp::Div, float(1.0, 64), float(4.0, 64)),
+        Ok(float(0.25, 64))
+    );
+    assert_eq!(
+        binary_op(BinOp::Pow, float(2.0, 64), float(0.5, 64)),
+        Ok(float(2f64.sqrt(), 64))
+    );
+    // the result is rounded to the precision of a `f32`
+    assert_eq!(
+        binary_op(BinOp::Div, float(1.0, 32), float(3.0, 32)),
+        Ok(float((1f32 / 3.0) as f64, 32))
+    );
+    assert_eq!(
+        binary_op(
+            BinOp::BitwiseXor,
+            ConstValue::Bool(true),
+            ConstValue::Bool(true)
+        ),
+        Ok(ConstValue::Bool(false))
+    );
+    assert_eq!(
+        binary_op(
+            BinOp::LogicalOr,
+            ConstValue::Bool(false),
+            ConstValue::Bool(true)
+        ),
+        Ok(ConstValue::Bool(true))
+    );
+    assert_eq!(
+        binary_op(BinOp::Add, ConstValue::Bool(true), ConstValue::Bool(true)),
+        Err(ConstError::NotConst)
+    );
+}
+
+#[test]
+fn comparisons() {
+    let ops = [
+        CmpOp::Equal,
+        CmpOp::NotEqual,
+        CmpOp::Less,
+        CmpOp::LessEqual,
+        CmpOp::Greater,
+        CmpOp::GreaterEqual,
+    ];
+    let cases = [
+        (
+            int(-1, "i8"),
+            int(1, "i8"),
+            [false, true, true, true, false, false],
+        ),
+        (
+            int(255, "u8"),
+            int(1, "u8"),
+            [false, true, false, false, true, true],
+        ),
+        (
+            int(3, "i64"),
+            int(3, "i64"),
+            [true, false, false, true, false, true],
+        ),
+        (
+            float(0.5, 64),
+            float(-0.5, 64),
+            [false, true, false, false, true, true],
+        ),
+        (
+            float(f64::NAN, 64),
+            float(f64::NAN, 64),
+            [false, true, false, false, false, false],
+        ),
+        (
+            ConstValue::Char('a'),
+            ConstValue::Char('b'),
+            [false, true, true, true, false, false],
+        ),
+        (
+            ConstValue::Bool(true),
+            ConstValue::Bool(false),
+            [false, true, false, false, true, true],
+        ),
+    ];
+    for (a, b, expected) in cases.iter() {
+        for (op, expected) in ops.iter().zip(expected) {
+            assert_eq!(
+                compare(*op, a, b),
+                Ok(*expected),
+                "`{:?} {} {:?}`",
+                a,
+                op,
+                b
+            );
+        }
+    }
+
+    let a = ConstValue::String("a".to_owned());
+    let b = ConstValue::String("b".to_owned());
+    assert_eq!(compare(CmpOp::NotEqual, &a, &b), Ok(true));
+    assert_eq!(compare(CmpOp::Less, &a, &b), Err(ConstError::NotConst));
+}
+
+#[test]
+fn const_items() {
+    let errors = common::check(
+        "const A: u8 = 200 + 56;
+        const B: i32 = 1 / (Z - Z);
+        const Z: i32 = 5;
+        const C: i64 = 7 % 0;
+        const D: u16 = 1 << 16;
+        const E: i32 = 2 ** -1;
+        const F: u8 = 256;
+        const G: i8 = -128;
+        const H: u64 = 3 ** 40;
+        def main() {}",
+    );
+    assert_eq!(
+        errors,
+        [
+            "E0200 this arithmetic operation will overflow `u8`: `200 + 56`",
+            "E0202 attempt to divide by zero: `1 / (Z - Z)`",
+            "E0203 attempt to calculate the remainder with a divisor of zero: `7 % 0`",
+            "E0204 attempt to shift `u16` by `16`, which is too large: `1 << 16`",
+            "E0205 attempt to raise an integer to a negative power: `2 ** -1`",
+            "E0201 literal out of range for `u8`: `256`",
+        ]
+    );
+}
@@ -0,0 +1,172 @@
+//! Checks that non-exhaustive matches and unreachable arms are reported.
+
+mod common;
+
+use common::check;
+
+const COMPLEX: &str = "
+union Complex {
+    First = {a: i32, b: i32},
+    Second = {String, bool},
+    Third,
+}
+";
+
+#[test]
+fn exhaustive() {
+    let source = format!(
+        "{}
+def f(c: Complex, n: u8, t: {{bool, i8}}): i32 {{
+    var a = match c {{
+        Complex:First = {{a: x, b: y}} -> x + y,
+        Complex:Second = {{s, true}} -> 1,
+        Complex:Second = {{s, false}} -> 2,
+        Complex:Third -> 3,
+    }};
+    var b = match n {{
+        0 -> 1,
+        1..100 -> 2,
+        101..255 -> 3,
+    }};
+    var c = match t {{
+        {{true, -128..-1}} -> 1,
+        {{true, 0..127}} -> 2,
+        {{false, else}} -> 3,
+    }};
+    return a + b + c;
+}}",
+        COMPLEX
+    );
+    assert_eq!(check(&source), Vec::<String>::new());
+}
+
+#[test]
+fn missing_variant() {
+    let source = format!(
+        "{}
+def f(c: Complex): i32 {{
+    return match c {{
+        Complex:First = {{a: x, b: y}} -> x,
+        Complex:Third -> 3,
+    }};
+}}",
+        COMPLEX
+    );
+    assert_eq!(
+        check(&source),
+        ["E0132 non-exhaustive patterns: `Complex:Second` not covered: `c`"]
+    );
+}
+
+#[test]
+fn missing_range() {
+    let source = "
+def f(n: u8): i32 {
+    return match n {
+        0..100 -> 1,
+        50 -> 2,
+        101..254 -> 3,
+    };
+}";
+    assert_eq!(
+        check(source),
+        [
+            "E0132 non-exhaustive patterns: `255` not covered: `n`",
+            "W0001 unreachable pattern: `50`",
+        ]
+    );
+}
+
+#[test]
+fn missing_tuple() {
+    let source = "
+def f(t: {i8, bool}): i32 {
+    return match t {
+        {-128..127, true} -> 1,
+        {0, false} -> 2,
+    };
+}";
+    assert_eq!(
+        check(source),
+        ["E0132 non-exhaustive patterns: `{-128..-1, false}` and `{1..127, false}` not covered: `t`"]
+    );
+}
+
+#[test]
+fn unreachable_arms() {
+    let source = format!(
+        "{}
+def f(c: Complex, b: bool): i32 {{
+    var x = match c {{
+        else -> 1,
+        Complex:Third -> 2,
+    }};
+    var y = match b {{
+        true -> 1,
+        false -> 2,
+        true -> 3,
+    }};
+    return x + y;
+}}",
+        COMPLEX
+    );
+    assert_eq!(
+        check(&source),
+        [
+            "W0001 unreachable pattern: `Complex:Third`",
+            "W0001 unreachable pattern: `true`",
+        ]
+    );
+}
+
+#[test]
+fn literal_out_of_range() {
+    let source = "
+def h(x: u8): i32 {
+    return match x {
+        300 -> 1,
+        44 -> 2,
+        else -> 3,
+    };
+}
+
+def g(x: i8): i32 {
+    return match x {
+        -129..0 -> 1,
+        1..200 -> 2,
+        -1 -> 3,
+        else -> 4,
+    };
+}
+
+def k(x: u8): i32 {
+    return match x {
+        -1 -> 1,
+        else -> 2,
+    };
+}";
+    assert_eq!(
+        check(source),
+        [
+            "E0201 literal out of range for `u8`: `300`",
+            "E0201 literal out of range for `i8`: `-129..0`",
+            "E0201 literal out of range for `i8`: `1..200`",
+            "E0201 literal out of range for `u8`: `-1`",
+        ]
+    );
+}
+
+#[test]
+fn empty_range() {
+    let source = "
+def f(x: u8): i32 {
+    return match x {
+        5..1 -> 1,
+        else -> 2,
+    };
+}";
+    assert_eq!(
+        check(source),
+        ["E0134 lower bound of range pattern is greater than the upper bound: `5..1`"]
+    );
+}
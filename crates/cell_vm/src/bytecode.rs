@@ -0,0 +1,235 @@
+//! The instructions and data that make up a compiled program.
+
+use cell_common::source::{FileId, Span};
+use cell_mir::body::{BinOp, UnOp};
+
+/// The content of a single slot.
+///
+/// Integers are stored truncated to their width, floats as the bits
+/// of an `f64`, chars as their code point and strings as an index into
+/// [`Program::strings`]. Pointers store the memory they point into in
+/// the upper 64 bits and the index of the slot in the lower 64 bits.
+///
+/// [`Program::strings`]: ./struct.Program.html#structfield.strings
+pub type Slot = u128;
+
+/// Marks a pointer into the stack.
+pub const STACK: Slot = 1 << 64;
+/// Marks a pointer into the heap.
+pub const HEAP: Slot = 2 << 64;
+
+/// A register, which is the index of a slot inside the current frame.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Reg(pub u32);
+
+/// The type of the operands of an arithmetic instruction.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Scalar {
+    Int { width: u16, signed: bool },
+    Float { width: u16 },
+    Bool,
+    Char,
+    Pointer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instr {
+    /// Copies `count` slots of the constant pool, starting at `idx`.
+    Const {
+        dst: Reg,
+        idx: u32,
+        count: u32,
+    },
+    Move {
+        dst: Reg,
+        src: Reg,
+        count: u32,
+    },
+    /// Applies an arithmetic operator or compares two scalars.
+    Binary {
+        op: BinOp,
+        ty: Scalar,
+        dst: Reg,
+        left: Reg,
+        right: Reg,
+    },
+    Unary {
+        op: UnOp,
+        ty: Scalar,
+        dst: Reg,
+        src: Reg,
+    },
+    /// Compares `count` slots of two values, which is used for
+    /// values that are not scalars.
+    Equal {
+        dst: Reg,
+        left: Reg,
+        right: Reg,
+        count: u32,
+        negate: bool,
+    },
+    /// Moves the pointer by `count * stride` slots,
+    /// where `count` is an integer of type `ty`.
+    Offset {
+        dst: Reg,
+        ptr: Reg,
+        count: Reg,
+        ty: Scalar,
+        stride: i32,
+    },
+    /// Creates a pointer to a register.
+    AddrLocal {
+        dst: Reg,
+        src: Reg,
+    },
+    /// Creates a pointer to the slot at `offset` behind the pointer.
+    AddrField {
+        dst: Reg,
+        ptr: Reg,
+        offset: u32,
+    },
+    /// Reads `count` slots behind the pointer, starting at `offset`.
+    Load {
+        dst: Reg,
+        ptr: Reg,
+        offset: u32,
+        count: u32,
+    },
+    /// Writes `count` slots behind the pointer, starting at `offset`.
+    Store {
+        ptr: Reg,
+        offset: u32,
+        src: Reg,
+        count: u32,
+    },
+    Jump {
+        target: u32,
+    },
+    /// Jumps if the `bool` inside the register is `true`.
+    JumpIf {
+        cond: Reg,
+        target: u32,
+    },
+    /// Jumps to the target inside the [`SwitchTable`] at index `table`
+    /// that matches the value inside `discr`.
+    ///
+    /// [`SwitchTable`]: ./struct.SwitchTable.html
+    Switch {
+        discr: Reg,
+        table: u32,
+    },
+    /// Calls the function at index `func`.
+    ///
+    /// The arguments are stored in consecutive registers starting at
+    /// `args`, and the return value is stored at `dst`.
+    Call {
+        func: u32,
+        args: Reg,
+        dst: Reg,
+    },
+    Return,
+    /// Prints a value, whose type is at index `ty` of [`Program::types`].
+    ///
+    /// [`Program::types`]: ./struct.Program.html#structfield.types
+    Print {
+        src: Reg,
+        ty: u32,
+    },
+    /// Allocates `count * stride` slots on the heap, where
+    /// `count` is an integer of type `ty`.
+    Alloc {
+        dst: Reg,
+        count: Reg,
+        ty: Scalar,
+        stride: u32,
+    },
+    /// Stops the program, because unreachable code was reached.
+    Trap,
+}
+
+/// The targets of a [`Switch`] instruction.
+///
+/// [`Switch`]: ./enum.Instr.html#variant.Switch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchTable {
+    pub targets: Vec<(Slot, u32)>,
+    pub otherwise: u32,
+}
+
+/// A single compiled function.
+///
+/// Every generic function is compiled once for every combination
+/// of generic arguments it is called with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    /// The file the function is defined in.
+    pub file: FileId,
+    /// The number of slots of the return value, which is stored
+    /// at the start of the frame.
+    pub ret_size: u32,
+    /// The number of slots of all arguments, which are
+    /// stored directly behind the return value.
+    pub arg_size: u32,
+    /// The number of slots of the whole frame.
+    pub frame_size: u32,
+    pub code: Vec<Instr>,
+    /// The span of the MIR statement every instruction was compiled from.
+    pub spans: Vec<Span>,
+    pub switches: Vec<SwitchTable>,
+}
+
+/// A human readable description of a type, used to print values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeDesc {
+    Scalar(Scalar),
+    String,
+    Unit,
+    Tuple(Vec<TypeDesc>),
+    NamedTuple(Vec<(String, TypeDesc)>),
+    Struct {
+        name: String,
+        fields: Vec<(String, TypeDesc)>,
+    },
+    /// A union, whose first slot stores the index of the variant.
+    Union {
+        name: String,
+        variants: Vec<(String, Option<TypeDesc>)>,
+    },
+}
+
+impl TypeDesc {
+    /// Returns the number of slots a value of this type needs.
+    pub fn size(&self) -> usize {
+        match self {
+            TypeDesc::Scalar(_) | TypeDesc::String => 1,
+            TypeDesc::Unit => 0,
+            TypeDesc::Tuple(tys) => tys.iter().map(TypeDesc::size).sum(),
+            TypeDesc::NamedTuple(fields) | TypeDesc::Struct { fields, .. } => {
+                fields.iter().map(|(_, ty)| ty.size()).sum()
+            }
+            TypeDesc::Union { variants, .. } => {
+                let data = variants
+                    .iter()
+                    .filter_map(|(_, data)| data.as_ref().map(TypeDesc::size))
+                    .max();
+                1 + data.unwrap_or(0)
+            }
+        }
+    }
+}
+
+/// A complete program, that can be executed by the [`Vm`].
+///
+/// [`Vm`]: ../vm/struct.Vm.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    /// The function that is executed first.
+    pub main: u32,
+    pub consts: Vec<Slot>,
+    pub strings: Vec<String>,
+    pub types: Vec<TypeDesc>,
+    /// The initial content of the heap, which contains every `static`.
+    pub heap: Vec<Slot>,
+}
@@ -0,0 +1,732 @@
+//! Compiles the MIR of every function that is reachable from `main` into bytecode.
+
+use crate::{
+    bytecode::{Function, Instr, Program, Reg, Slot, SwitchTable, HEAP},
+    layout::{field_offset, scalar, size_of, type_desc},
+};
+use cell_common::{
+    error::Error,
+    profiler,
+    source::{Locatable, Span},
+};
+use cell_mir::{
+    body::{
+        AggregateKind, BinOp, Body, Operand, Place, PlaceElem, Rvalue, StatementKind,
+        TerminatorKind,
+    },
+    db::MirDatabase,
+    mono::{self, Instance},
+    opt::OptLevel,
+};
+use cell_syntax::ast::ItemId;
+use cell_typeck::{
+    consteval::ConstValue,
+    lower::AdtDef,
+    prelude::{self, HostFn},
+    ty::{Ty, TyKind},
+};
+use std::collections::HashMap;
+
+/// Compiles the `main` function of the root module, and every
+/// function that is called by it, using the MIR that is optimized at `level`.
+pub fn compile(db: &dyn MirDatabase, level: OptLevel) -> Result<Program, Locatable<Error>> {
+    let _profiler = profiler::trace("Vm", "Compile");
+
+    let main = mono::find_main(db)?;
+    let instances = mono::collect(db, &[Instance::new(main, vec![])], level)?;
+
+    let mut compiler = Compiler {
+        db,
+        program: Program::default(),
+        instances: HashMap::new(),
+        consts: HashMap::new(),
+        strings: HashMap::new(),
+        types: HashMap::new(),
+        statics: HashMap::new(),
+        error: None,
+    };
+    for (idx, instance) in instances.iter().enumerate() {
+        compiler.program.functions.push(Function {
+            name: instance.display(db),
+            file: db.lookup_intern_item(instance.def).file,
+            ret_size: 0,
+            arg_size: 0,
+            frame_size: 0,
+            code: vec![],
+            spans: vec![],
+            switches: vec![],
+        });
+        compiler.instances.insert(instance.clone(), idx as u32);
+    }
+    // `main` is always the first instance
+    compiler.program.main = 0;
+    for (idx, instance) in instances.iter().enumerate() {
+        let body = db.optimized_mir(instance.def, level).unwrap();
+        let mut function = FnCompiler::new(&mut compiler, &body, &instance.args).compile();
+        let slot = &mut compiler.program.functions[idx];
+        function.name = std::mem::take(&mut slot.name);
+        *slot = function;
+    }
+
+    match compiler.error {
+        Some(err) => Err(err),
+        None => Ok(compiler.program),
+    }
+}
+
+struct Compiler<'db> {
+    db: &'db dyn MirDatabase,
+    program: Program,
+    /// The index of every function instance.
+    instances: HashMap<Instance, u32>,
+    consts: HashMap<Vec<Slot>, u32>,
+    strings: HashMap<String, u32>,
+    types: HashMap<Ty, u32>,
+    /// The address of every `static` on the heap.
+    statics: HashMap<ItemId, Slot>,
+    /// The first error that happened while compiling.
+    error: Option<Locatable<Error>>,
+}
+
+impl Compiler<'_> {
+    /// Returns the index of a function instance.
+    fn instance(&self, instance: &Instance) -> u32 {
+        self.instances[instance]
+    }
+
+    /// Adds the slots to the constant pool and returns their index.
+    fn constant(&mut self, slots: Vec<Slot>) -> u32 {
+        if let Some(&idx) = self.consts.get(&slots) {
+            return idx;
+        }
+        let idx = self.program.consts.len() as u32;
+        self.program.consts.extend(&slots);
+        self.consts.insert(slots, idx);
+        idx
+    }
+
+    fn string(&mut self, val: &str) -> Slot {
+        if let Some(&idx) = self.strings.get(val) {
+            return idx as Slot;
+        }
+        let idx = self.program.strings.len() as u32;
+        self.program.strings.push(val.to_string());
+        self.strings.insert(val.to_string(), idx);
+        idx as Slot
+    }
+
+    fn type_desc(&mut self, ty: Ty) -> u32 {
+        if let Some(&idx) = self.types.get(&ty) {
+            return idx;
+        }
+        let idx = self.program.types.len() as u32;
+        self.program.types.push(type_desc(self.db, ty));
+        self.types.insert(ty, idx);
+        idx
+    }
+
+    /// Returns a pointer to the `static`, which is stored on the heap.
+    fn static_ptr(&mut self, item: ItemId) -> Slot {
+        if let Some(&ptr) = self.statics.get(&item) {
+            return ptr;
+        }
+
+        let db = self.db;
+        let ty = db.global_ty(item);
+        let mut slots = vec![];
+        match db.const_value(item) {
+            Ok(val) => self.flatten(&val, ty, &mut slots),
+            Err(err) => {
+                self.error.get_or_insert(err);
+            }
+        }
+        slots.resize(size_of(db, ty) as usize, 0);
+
+        let ptr = HEAP | self.program.heap.len() as Slot;
+        self.program.heap.extend(slots);
+        self.statics.insert(item, ptr);
+        ptr
+    }
+
+    /// Splits a constant value into slots.
+    fn flatten(&mut self, val: &ConstValue, ty: Ty, slots: &mut Vec<Slot>) {
+        let db = self.db;
+        match val {
+            ConstValue::Int(int) => slots.push(int.as_u128()),
+            ConstValue::Float { val, .. } => slots.push(val.into_inner().to_bits() as Slot),
+            ConstValue::Bool(val) => slots.push(*val as Slot),
+            ConstValue::Char(val) => slots.push(*val as Slot),
+            ConstValue::String(val) => {
+                let idx = self.string(val);
+                slots.push(idx);
+            }
+            ConstValue::Unit => {}
+            ConstValue::Tuple(vals) => {
+                let tys = match ty.kind(db) {
+                    TyKind::Tuple(tys) => tys,
+                    _ => panic!("tuple constant with a type that is not a tuple"),
+                };
+                for (val, ty) in vals.iter().zip(tys) {
+                    self.flatten(val, ty, slots);
+                }
+            }
+            ConstValue::NamedTuple(vals) => {
+                // the fields are stored in the order of the type
+                let fields = match ty.kind(db) {
+                    TyKind::NamedTuple(fields) => fields,
+                    TyKind::Adt(item, args) => match &*db.adt_def(item) {
+                        AdtDef::Struct { fields } => fields
+                            .iter()
+                            .map(|(name, ty)| (*name, ty.subst(db, &args)))
+                            .collect(),
+                        AdtDef::Union { .. } => vec![],
+                    },
+                    _ => vec![],
+                };
+                for (name, ty) in fields {
+                    if let Some((_, val)) = vals.iter().find(|(field, _)| *field == name) {
+                        self.flatten(val, ty, slots);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Where a place is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Loc {
+    Reg(Reg),
+    /// Behind the pointer that is stored in the register.
+    Mem {
+        ptr: Reg,
+        offset: u32,
+    },
+}
+
+impl Loc {
+    fn offset(self, offset: u32) -> Self {
+        match self {
+            Loc::Reg(reg) => Loc::Reg(Reg(reg.0 + offset)),
+            Loc::Mem { ptr, offset: base } => Loc::Mem {
+                ptr,
+                offset: base + offset,
+            },
+        }
+    }
+}
+
+struct FnCompiler<'a, 'db> {
+    cx: &'a mut Compiler<'db>,
+    body: &'a Body,
+    /// The generic arguments of this instance.
+    args: &'a [Ty],
+    /// The type of every local, with the generic arguments substituted.
+    tys: Vec<Ty>,
+    /// The first register of every local.
+    regs: Vec<Reg>,
+    /// The first register that is not used by any local.
+    temp_start: u32,
+    next_temp: u32,
+    frame_size: u32,
+    code: Vec<Instr>,
+    spans: Vec<Span>,
+    switches: Vec<SwitchTable>,
+    /// The span of the statement that is currently compiled.
+    span: Span,
+}
+
+impl<'a, 'db> FnCompiler<'a, 'db> {
+    fn new(cx: &'a mut Compiler<'db>, body: &'a Body, args: &'a [Ty]) -> Self {
+        let db = cx.db;
+        let tys = body
+            .locals
+            .iter()
+            .map(|local| local.ty.subst(db, args))
+            .collect::<Vec<_>>();
+
+        // the return place and the arguments come first,
+        // so the caller knows where to put the arguments
+        let mut regs = vec![];
+        let mut next = 0;
+        for &ty in &tys {
+            regs.push(Reg(next));
+            next += size_of(db, ty);
+        }
+
+        Self {
+            cx,
+            body,
+            args,
+            tys,
+            regs,
+            temp_start: next,
+            next_temp: next,
+            frame_size: next,
+            code: vec![],
+            spans: vec![],
+            switches: vec![],
+            span: Span::default(),
+        }
+    }
+
+    fn compile(mut self) -> Function {
+        let db = self.cx.db;
+        let mut starts = vec![];
+        for (block, data) in self.body.basic_blocks() {
+            starts.push(self.code.len() as u32);
+            for statement in &data.statements {
+                self.span = statement.span;
+                self.next_temp = self.temp_start;
+                match &statement.kind {
+                    StatementKind::Assign(place, rvalue) => self.assign(place, rvalue),
+                    StatementKind::Phi(..) => unreachable!("phi nodes are removed before codegen"),
+                    StatementKind::Nop => {}
+                }
+            }
+
+            let terminator = data.terminator();
+            self.span = terminator.span;
+            self.next_temp = self.temp_start;
+            self.terminator(&terminator.kind, block.0 + 1);
+        }
+
+        // jumps point to blocks until every block was compiled
+        for instr in &mut self.code {
+            match instr {
+                Instr::Jump { target } | Instr::JumpIf { target, .. } => {
+                    *target = starts[*target as usize]
+                }
+                _ => {}
+            }
+        }
+        for table in &mut self.switches {
+            for (_, target) in &mut table.targets {
+                *target = starts[*target as usize];
+            }
+            table.otherwise = starts[table.otherwise as usize];
+        }
+
+        let ret_size = size_of(db, self.tys[0]);
+        let arg_size = self
+            .body
+            .args()
+            .map(|arg| size_of(db, self.tys[arg.index()]))
+            .sum();
+        Function {
+            // the name is already known by the caller
+            name: String::new(),
+            file: db.lookup_intern_item(self.body.item).file,
+            ret_size,
+            arg_size,
+            frame_size: self.frame_size,
+            code: self.code,
+            spans: self.spans,
+            switches: self.switches,
+        }
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.code.push(instr);
+        self.spans.push(self.span);
+    }
+
+    /// Allocates registers, that are only used by the current statement.
+    fn temp(&mut self, size: u32) -> Reg {
+        let reg = Reg(self.next_temp);
+        self.next_temp += size;
+        self.frame_size = self.frame_size.max(self.next_temp);
+        reg
+    }
+
+    fn size_of(&self, ty: Ty) -> u32 {
+        size_of(self.cx.db, ty)
+    }
+
+    fn subst(&self, ty: Ty) -> Ty {
+        ty.subst(self.cx.db, self.args)
+    }
+
+    /// Returns where the place is stored, and its type.
+    fn place(&mut self, place: &Place) -> (Loc, Ty) {
+        let db = self.cx.db;
+        let mut loc = Loc::Reg(self.regs[place.local.index()]);
+        let mut ty = self.tys[place.local.index()];
+        for elem in &place.projection {
+            match *elem {
+                PlaceElem::Deref => {
+                    let ptr = self.read(loc, 1);
+                    loc = Loc::Mem { ptr, offset: 0 };
+                    ty = pointee(db, ty);
+                }
+                PlaceElem::Field(idx) => {
+                    loc = loc.offset(field_offset(db, ty, idx));
+                    ty = cell_mir::body::field_ty(db, ty, idx);
+                }
+                PlaceElem::Downcast(idx) => {
+                    // skip the discriminant
+                    loc = loc.offset(1);
+                    ty = cell_mir::body::variant_ty(db, ty, idx);
+                }
+                PlaceElem::Index(local) => {
+                    let ptr = self.read(loc, 1);
+                    let elem = pointee(db, ty);
+                    let count_ty = self.tys[local.index()];
+                    let dst = self.temp(1);
+                    self.emit(Instr::Offset {
+                        dst,
+                        ptr,
+                        count: self.regs[local.index()],
+                        ty: scalar(db, count_ty).expect("index is not an integer"),
+                        stride: self.size_of(elem) as i32,
+                    });
+                    loc = Loc::Mem {
+                        ptr: dst,
+                        offset: 0,
+                    };
+                    ty = elem;
+                }
+            }
+        }
+        (loc, ty)
+    }
+
+    /// Returns a register that contains the value at the location.
+    fn read(&mut self, loc: Loc, count: u32) -> Reg {
+        match loc {
+            Loc::Reg(reg) => reg,
+            Loc::Mem { ptr, offset } => {
+                let dst = self.temp(count);
+                self.emit(Instr::Load {
+                    dst,
+                    ptr,
+                    offset,
+                    count,
+                });
+                dst
+            }
+        }
+    }
+
+    /// Writes the registers to the location.
+    fn write(&mut self, loc: Loc, src: Reg, count: u32) {
+        match loc {
+            Loc::Reg(dst) if dst == src || count == 0 => {}
+            Loc::Reg(dst) => self.emit(Instr::Move { dst, src, count }),
+            Loc::Mem { ptr, offset } => self.emit(Instr::Store {
+                ptr,
+                offset,
+                src,
+                count,
+            }),
+        }
+    }
+
+    /// Returns the registers an instruction should write its
+    /// result into, before it's written to the location.
+    fn target(&mut self, loc: Loc, count: u32) -> Reg {
+        match loc {
+            Loc::Reg(reg) => reg,
+            Loc::Mem { .. } => self.temp(count),
+        }
+    }
+
+    fn operand_ty(&self, op: &Operand) -> Ty {
+        self.subst(op.ty(self.cx.db, self.body))
+    }
+
+    /// Returns a register that contains the value of the operand.
+    fn operand(&mut self, op: &Operand) -> Reg {
+        match op {
+            Operand::Copy(place) => {
+                let (loc, ty) = self.place(place);
+                let size = self.size_of(ty);
+                self.read(loc, size)
+            }
+            Operand::Constant(constant) => {
+                let ty = self.subst(constant.ty);
+                let size = self.size_of(ty);
+                let dst = self.temp(size);
+                self.constant_into(&constant.val, ty, dst);
+                dst
+            }
+        }
+    }
+
+    fn constant_into(&mut self, val: &ConstValue, ty: Ty, dst: Reg) {
+        let mut slots = vec![];
+        self.cx.flatten(val, ty, &mut slots);
+        if !slots.is_empty() {
+            let count = slots.len() as u32;
+            let idx = self.cx.constant(slots);
+            self.emit(Instr::Const { dst, idx, count });
+        }
+    }
+
+    /// Stores the value of the operand at the location.
+    fn operand_into(&mut self, op: &Operand, loc: Loc) {
+        match (op, loc) {
+            (Operand::Constant(constant), Loc::Reg(dst)) => {
+                let ty = self.subst(constant.ty);
+                self.constant_into(&constant.val, ty, dst);
+            }
+            (Operand::Copy(place), Loc::Reg(dst)) => {
+                let (src, ty) = self.place(place);
+                let count = self.size_of(ty);
+                match src {
+                    Loc::Reg(src) => self.write(loc, src, count),
+                    Loc::Mem { ptr, offset } if count > 0 => self.emit(Instr::Load {
+                        dst,
+                        ptr,
+                        offset,
+                        count,
+                    }),
+                    Loc::Mem { .. } => {}
+                }
+            }
+            (op, loc) => {
+                let count = self.size_of(self.operand_ty(op));
+                let src = self.operand(op);
+                self.write(loc, src, count);
+            }
+        }
+    }
+
+    fn assign(&mut self, place: &Place, rvalue: &Rvalue) {
+        let db = self.cx.db;
+        let (dst, ty) = self.place(place);
+        let size = self.size_of(ty);
+        match rvalue {
+            Rvalue::Use(op) => self.operand_into(op, dst),
+            Rvalue::BinaryOp(op, left, right) => {
+                let left_ty = self.operand_ty(left);
+                let right_ty = self.operand_ty(right);
+                let left = self.operand(left);
+                let right = self.operand(right);
+                let out = self.target(dst, size);
+                let instr = match left_ty.kind(db) {
+                    TyKind::Pointer(elem) if matches!(op, BinOp::Add | BinOp::Sub) => {
+                        let stride = self.size_of(elem) as i32;
+                        Instr::Offset {
+                            dst: out,
+                            ptr: left,
+                            count: right,
+                            ty: scalar(db, right_ty).expect("offset is not an integer"),
+                            stride: if *op == BinOp::Sub { -stride } else { stride },
+                        }
+                    }
+                    _ => match scalar(db, left_ty) {
+                        Some(ty) => Instr::Binary {
+                            op: *op,
+                            ty,
+                            dst: out,
+                            left,
+                            right,
+                        },
+                        None => Instr::Equal {
+                            dst: out,
+                            left,
+                            right,
+                            count: self.size_of(left_ty),
+                            negate: *op == BinOp::Ne,
+                        },
+                    },
+                };
+                self.emit(instr);
+                self.write(dst, out, size);
+            }
+            Rvalue::UnaryOp(op, val) => {
+                let ty = scalar(db, self.operand_ty(val)).expect("operand is not a scalar");
+                let src = self.operand(val);
+                let out = self.target(dst, size);
+                self.emit(Instr::Unary {
+                    op: *op,
+                    ty,
+                    dst: out,
+                    src,
+                });
+                self.write(dst, out, size);
+            }
+            Rvalue::AddressOf(place) => {
+                let (loc, _) = self.place(place);
+                let out = self.target(dst, 1);
+                match loc {
+                    Loc::Reg(src) => self.emit(Instr::AddrLocal { dst: out, src }),
+                    Loc::Mem { ptr, offset } => self.emit(Instr::AddrField {
+                        dst: out,
+                        ptr,
+                        offset,
+                    }),
+                }
+                self.write(dst, out, 1);
+            }
+            Rvalue::AddressOfStatic(item) => {
+                let ptr = self.cx.static_ptr(*item);
+                let idx = self.cx.constant(vec![ptr]);
+                let out = self.target(dst, 1);
+                self.emit(Instr::Const {
+                    dst: out,
+                    idx,
+                    count: 1,
+                });
+                self.write(dst, out, 1);
+            }
+            Rvalue::Discriminant(place) => {
+                let (loc, _) = self.place(place);
+                let src = self.read(loc, 1);
+                self.write(dst, src, 1);
+            }
+            Rvalue::Aggregate(kind, ops) => {
+                // build the value in a temporary if it reads from the place it's written to
+                let overlaps = ops.iter().any(|op| match op {
+                    Operand::Copy(op) => op.local == place.local,
+                    Operand::Constant(_) => false,
+                });
+                let out = match dst {
+                    Loc::Reg(reg) if !overlaps => reg,
+                    _ => self.temp(size),
+                };
+
+                match kind {
+                    AggregateKind::Variant(_, idx, _) => {
+                        let discr = self.cx.constant(vec![*idx as Slot]);
+                        self.emit(Instr::Const {
+                            dst: out,
+                            idx: discr,
+                            count: 1,
+                        });
+                        if let Some(op) = ops.first() {
+                            self.operand_into(op, Loc::Reg(Reg(out.0 + 1)));
+                        }
+                    }
+                    _ => {
+                        for (idx, op) in ops.iter().enumerate() {
+                            let offset = field_offset(db, ty, idx as u32);
+                            self.operand_into(op, Loc::Reg(Reg(out.0 + offset)));
+                        }
+                    }
+                }
+                self.write(dst, out, size);
+            }
+        }
+    }
+
+    fn terminator(&mut self, kind: &TerminatorKind, next: u32) {
+        let db = self.cx.db;
+        match kind {
+            TerminatorKind::Goto { target } => self.jump(target.0, next),
+            TerminatorKind::SwitchInt {
+                discr,
+                targets,
+                otherwise,
+            } => {
+                let is_bool = self.operand_ty(discr).kind(db) == TyKind::Bool;
+                let cond = self.operand(discr);
+                match targets.as_slice() {
+                    [(0, target)] if is_bool => {
+                        self.emit(Instr::JumpIf {
+                            cond,
+                            target: otherwise.0,
+                        });
+                        self.jump(target.0, next);
+                    }
+                    _ => {
+                        let table = self.switches.len() as u32;
+                        self.switches.push(SwitchTable {
+                            targets: targets
+                                .iter()
+                                .map(|(val, target)| (*val as Slot, target.0))
+                                .collect(),
+                            otherwise: otherwise.0,
+                        });
+                        self.emit(Instr::Switch { discr: cond, table });
+                    }
+                }
+            }
+            TerminatorKind::Return => self.emit(Instr::Return),
+            TerminatorKind::Call {
+                func,
+                generic_args,
+                args,
+                destination,
+                target,
+            } => {
+                let generic_args = generic_args
+                    .iter()
+                    .map(|&ty| self.subst(ty))
+                    .collect::<Vec<_>>();
+                self.call(*func, generic_args, args, destination);
+                self.jump(target.0, next);
+            }
+            TerminatorKind::Unreachable => self.emit(Instr::Trap),
+        }
+    }
+
+    fn jump(&mut self, target: u32, next: u32) {
+        if target != next {
+            self.emit(Instr::Jump { target });
+        }
+    }
+
+    fn call(&mut self, func: ItemId, generic_args: Vec<Ty>, args: &[Operand], dest: &Place) {
+        let db = self.cx.db;
+        match prelude::host_fn(db, func) {
+            Some(HostFn::Println) => {
+                // the first argument is the pointer to `io`
+                let ty = self.operand_ty(&args[1]);
+                let ty = self.cx.type_desc(ty);
+                let src = self.operand(&args[1]);
+                self.emit(Instr::Print { src, ty });
+                return;
+            }
+            Some(HostFn::Alloc) => {
+                let count_ty = self.operand_ty(&args[0]);
+                let count = self.operand(&args[0]);
+                let (dst, _) = self.place(dest);
+                let out = self.target(dst, 1);
+                self.emit(Instr::Alloc {
+                    dst: out,
+                    count,
+                    ty: scalar(db, count_ty).expect("count is not an integer"),
+                    stride: self.size_of(generic_args[0]),
+                });
+                self.write(dst, out, 1);
+                return;
+            }
+            None => {}
+        }
+
+        let func = self
+            .cx
+            .instance(&Instance::resolve(db, func, &generic_args));
+
+        // the arguments are placed in consecutive registers
+        let sizes = args
+            .iter()
+            .map(|arg| self.size_of(self.operand_ty(arg)))
+            .collect::<Vec<_>>();
+        let start = self.temp(sizes.iter().sum());
+        let mut offset = 0;
+        for (arg, size) in args.iter().zip(sizes) {
+            self.operand_into(arg, Loc::Reg(Reg(start.0 + offset)));
+            offset += size;
+        }
+
+        let (dst, ty) = self.place(dest);
+        let size = self.size_of(ty);
+        let out = self.target(dst, size);
+        self.emit(Instr::Call {
+            func,
+            args: start,
+            dst: out,
+        });
+        self.write(dst, out, size);
+    }
+}
+
+fn pointee(db: &dyn MirDatabase, ty: Ty) -> Ty {
+    match ty.kind(db) {
+        TyKind::Pointer(inner) => inner,
+        _ => panic!("dereferenced a type that is not a pointer"),
+    }
+}
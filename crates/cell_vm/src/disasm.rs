@@ -0,0 +1,227 @@
+//! A human readable representation of a compiled program.
+//!
+//! ```text
+//! fn fib (ret: 1, args: 1, frame: 5)
+//!     0000  const     r2, c0           ; 2
+//!     0001  lt.u32    r3, r1, r2
+//!     0002  jump_if   r3, @0012
+//!     ...
+//! ```
+
+use crate::bytecode::{Function, Instr, Program, Reg, Scalar, Slot, HEAP, STACK};
+use cell_mir::body::{BinOp, UnOp};
+use std::fmt::Write;
+
+/// Returns the disassembly of every function of the program.
+pub fn disassemble(program: &Program) -> String {
+    let mut out = String::new();
+    for (idx, func) in program.functions.iter().enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+        out.push_str(&disassemble_function(program, func));
+    }
+    out
+}
+
+/// Returns the disassembly of a single function.
+pub fn disassemble_function(program: &Program, func: &Function) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "fn {} (ret: {}, args: {}, frame: {})",
+        func.name, func.ret_size, func.arg_size, func.frame_size
+    )
+    .unwrap();
+
+    for (pc, instr) in func.code.iter().enumerate() {
+        let (name, operands, comment) = instruction(program, func, instr);
+        let mut line = format!("    {:04}  {:<10}{}", pc, name, operands.join(", "));
+        if let Some(comment) = comment {
+            line = format!("{:<44}; {}", line, comment);
+        }
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+    out
+}
+
+fn instruction(
+    program: &Program,
+    func: &Function,
+    instr: &Instr,
+) -> (String, Vec<String>, Option<String>) {
+    let reg = |reg: Reg| format!("r{}", reg.0);
+    let target = |target: u32| format!("@{:04}", target);
+    match *instr {
+        Instr::Const { dst, idx, count } => {
+            let vals = program.consts[idx as usize..(idx + count) as usize]
+                .iter()
+                .map(|val| match val & !(u64::MAX as Slot) {
+                    // pointers to statics
+                    STACK | HEAP => format!("{:#x}", val),
+                    _ => val.to_string(),
+                })
+                .collect::<Vec<_>>();
+            (
+                "const".to_string(),
+                vec![reg(dst), format!("c{}", idx), count.to_string()],
+                Some(vals.join(", ")),
+            )
+        }
+        Instr::Move { dst, src, count } => (
+            "move".to_string(),
+            vec![reg(dst), reg(src), count.to_string()],
+            None,
+        ),
+        Instr::Binary {
+            op,
+            ty,
+            dst,
+            left,
+            right,
+        } => (
+            format!("{}.{}", bin_op(op), scalar(ty)),
+            vec![reg(dst), reg(left), reg(right)],
+            None,
+        ),
+        Instr::Unary { op, ty, dst, src } => {
+            let op = match op {
+                UnOp::Not => "not",
+                UnOp::Neg => "neg",
+            };
+            (
+                format!("{}.{}", op, scalar(ty)),
+                vec![reg(dst), reg(src)],
+                None,
+            )
+        }
+        Instr::Equal {
+            dst,
+            left,
+            right,
+            count,
+            negate,
+        } => (
+            if negate { "ne" } else { "eq" }.to_string(),
+            vec![reg(dst), reg(left), reg(right), count.to_string()],
+            None,
+        ),
+        Instr::Offset {
+            dst,
+            ptr,
+            count,
+            ty,
+            stride,
+        } => (
+            format!("offset.{}", scalar(ty)),
+            vec![reg(dst), reg(ptr), reg(count), stride.to_string()],
+            None,
+        ),
+        Instr::AddrLocal { dst, src } => ("addr".to_string(), vec![reg(dst), reg(src)], None),
+        Instr::AddrField { dst, ptr, offset } => (
+            "addr".to_string(),
+            vec![reg(dst), format!("[{}+{}]", reg(ptr), offset)],
+            None,
+        ),
+        Instr::Load {
+            dst,
+            ptr,
+            offset,
+            count,
+        } => (
+            "load".to_string(),
+            vec![
+                reg(dst),
+                format!("[{}+{}]", reg(ptr), offset),
+                count.to_string(),
+            ],
+            None,
+        ),
+        Instr::Store {
+            ptr,
+            offset,
+            src,
+            count,
+        } => (
+            "store".to_string(),
+            vec![
+                format!("[{}+{}]", reg(ptr), offset),
+                reg(src),
+                count.to_string(),
+            ],
+            None,
+        ),
+        Instr::Jump { target: to } => ("jump".to_string(), vec![target(to)], None),
+        Instr::JumpIf { cond, target: to } => {
+            ("jump_if".to_string(), vec![reg(cond), target(to)], None)
+        }
+        Instr::Switch { discr, table } => {
+            let table = &func.switches[table as usize];
+            let mut targets = table
+                .targets
+                .iter()
+                .map(|(val, to)| format!("{}: {}", val, target(*to)))
+                .collect::<Vec<_>>();
+            targets.push(format!("otherwise: {}", target(table.otherwise)));
+            (
+                "switch".to_string(),
+                vec![reg(discr), format!("[{}]", targets.join(", "))],
+                None,
+            )
+        }
+        Instr::Call { func, args, dst } => (
+            "call".to_string(),
+            vec![reg(dst), format!("f{}", func), reg(args)],
+            Some(program.functions[func as usize].name.clone()),
+        ),
+        Instr::Return => ("return".to_string(), vec![], None),
+        Instr::Print { src, ty } => (
+            "print".to_string(),
+            vec![reg(src), format!("t{}", ty)],
+            None,
+        ),
+        Instr::Alloc {
+            dst,
+            count,
+            ty,
+            stride,
+        } => (
+            format!("alloc.{}", scalar(ty)),
+            vec![reg(dst), reg(count), stride.to_string()],
+            None,
+        ),
+        Instr::Trap => ("trap".to_string(), vec![], None),
+    }
+}
+
+fn bin_op(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::Div => "div",
+        BinOp::Rem => "rem",
+        BinOp::Pow => "pow",
+        BinOp::BitAnd => "and",
+        BinOp::BitOr => "or",
+        BinOp::BitXor => "xor",
+        BinOp::Shl => "shl",
+        BinOp::Shr => "shr",
+        BinOp::Eq => "eq",
+        BinOp::Ne => "ne",
+        BinOp::Lt => "lt",
+        BinOp::Le => "le",
+        BinOp::Gt => "gt",
+        BinOp::Ge => "ge",
+    }
+}
+
+fn scalar(ty: Scalar) -> String {
+    match ty {
+        Scalar::Int { width, signed } => format!("{}{}", if signed { 'i' } else { 'u' }, width),
+        Scalar::Float { width } => format!("f{}", width),
+        Scalar::Bool => "bool".to_string(),
+        Scalar::Char => "char".to_string(),
+        Scalar::Pointer => "ptr".to_string(),
+    }
+}
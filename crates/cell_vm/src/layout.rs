@@ -0,0 +1,99 @@
+//! How values of each type are split into slots.
+
+use crate::bytecode::{Scalar, TypeDesc};
+use cell_mir::body::{field_ty, variant_ty};
+use cell_syntax::ast::ItemId;
+use cell_typeck::{
+    db::TypeckDatabase,
+    lower::AdtDef,
+    ty::{Ty, TyKind},
+};
+
+/// Returns the scalar type, if values of the type fit into a single slot
+/// and can be used by arithmetic instructions.
+pub fn scalar(db: &dyn TypeckDatabase, ty: Ty) -> Option<Scalar> {
+    let scalar = match ty.kind(db) {
+        TyKind::Int { width, signed } => Scalar::Int { width, signed },
+        TyKind::Float { width } => Scalar::Float { width },
+        TyKind::Bool => Scalar::Bool,
+        TyKind::Char => Scalar::Char,
+        TyKind::Pointer(_) => Scalar::Pointer,
+        _ => return None,
+    };
+    Some(scalar)
+}
+
+/// Returns the number of slots a value of the type needs.
+pub fn size_of(db: &dyn TypeckDatabase, ty: Ty) -> u32 {
+    match ty.kind(db) {
+        TyKind::Unit | TyKind::Never => 0,
+        TyKind::Tuple(tys) => tys.into_iter().map(|ty| size_of(db, ty)).sum(),
+        TyKind::NamedTuple(fields) => fields.into_iter().map(|(_, ty)| size_of(db, ty)).sum(),
+        TyKind::Adt(item, args) => match &*db.adt_def(item) {
+            AdtDef::Struct { fields } => fields
+                .iter()
+                .map(|(_, ty)| size_of(db, ty.subst(db, &args)))
+                .sum(),
+            // the discriminant, followed by the data of the largest variant
+            AdtDef::Union { variants } => {
+                let data = (0..variants.len())
+                    .map(|idx| size_of(db, variant_ty(db, ty, idx as u32)))
+                    .max();
+                1 + data.unwrap_or(0)
+            }
+        },
+        _ => 1,
+    }
+}
+
+/// Returns the offset of the field at the index of a tuple, named tuple or `type`.
+pub fn field_offset(db: &dyn TypeckDatabase, ty: Ty, idx: u32) -> u32 {
+    (0..idx).map(|idx| size_of(db, field_ty(db, ty, idx))).sum()
+}
+
+/// Describes the type, so values of it can be printed.
+pub fn type_desc(db: &dyn TypeckDatabase, ty: Ty) -> TypeDesc {
+    let name = |name| db.rodeo().resolve(&name).to_string();
+    match ty.kind(db) {
+        TyKind::String => TypeDesc::String,
+        TyKind::Tuple(tys) => {
+            TypeDesc::Tuple(tys.into_iter().map(|ty| type_desc(db, ty)).collect())
+        }
+        TyKind::NamedTuple(fields) => TypeDesc::NamedTuple(
+            fields
+                .into_iter()
+                .map(|(field, ty)| (name(field), type_desc(db, ty)))
+                .collect(),
+        ),
+        TyKind::Adt(item, args) => match &*db.adt_def(item) {
+            AdtDef::Struct { fields } => TypeDesc::Struct {
+                name: item_name(db, item),
+                fields: fields
+                    .iter()
+                    .map(|(field, ty)| (name(*field), type_desc(db, ty.subst(db, &args))))
+                    .collect(),
+            },
+            AdtDef::Union { variants } => TypeDesc::Union {
+                name: item_name(db, item),
+                variants: variants
+                    .iter()
+                    .map(|(variant, data)| {
+                        let data = data.map(|ty| type_desc(db, ty.subst(db, &args)));
+                        (name(*variant), data)
+                    })
+                    .collect(),
+            },
+        },
+        _ => match scalar(db, ty) {
+            Some(scalar) => TypeDesc::Scalar(scalar),
+            None => TypeDesc::Unit,
+        },
+    }
+}
+
+fn item_name(db: &dyn TypeckDatabase, item: ItemId) -> String {
+    match db.lookup_intern_item(item).kind.name() {
+        Some(name) => db.rodeo().resolve(&name.id).to_string(),
+        None => "{unknown}".to_string(),
+    }
+}
@@ -0,0 +1,16 @@
+//! A register based virtual machine, that executes bytecode
+//! which is compiled from the MIR.
+//!
+//! Every value is split into slots, that each store a single scalar.
+//! The registers of a function are the slots of its frame on the stack,
+//! so pointers can point into the stack or into the heap, which stores
+//! every `static` and the memory that is allocated using `alloc`.
+
+pub mod bytecode;
+mod compile;
+pub mod disasm;
+mod layout;
+pub mod vm;
+
+pub use compile::compile;
+pub use vm::Vm;
@@ -0,0 +1,536 @@
+//! The virtual machine that executes a [`Program`].
+//!
+//! [`Program`]: ../bytecode/struct.Program.html
+
+use crate::bytecode::{Function, Instr, Program, Reg, Scalar, Slot, TypeDesc, HEAP, STACK};
+use cell_common::{
+    error::{ConstError, Error, RuntimeError},
+    profiler,
+    source::Locatable,
+};
+use cell_mir::body::{BinOp, UnOp};
+use cell_syntax::ast;
+use cell_typeck::consteval::{self, ConstInt, ConstValue};
+use std::{convert::TryFrom, io::Write};
+
+/// The maximum number of slots on the stack.
+const MAX_STACK: usize = 1 << 20;
+
+/// The maximum number of slots on the heap.
+const MAX_HEAP: usize = 1 << 24;
+
+/// Selects the index of a slot from a pointer.
+const INDEX: Slot = u64::MAX as Slot;
+
+/// A function that called another function.
+struct Frame {
+    func: u32,
+    /// The instruction that is executed after the call returns.
+    pc: usize,
+    base: usize,
+    /// Where the return value is stored.
+    dst: usize,
+}
+
+/// Executes a compiled program.
+pub struct Vm<'p> {
+    program: &'p Program,
+    stack: Vec<Slot>,
+    heap: Vec<Slot>,
+    /// Where `io.println` writes to.
+    out: Box<dyn Write + 'p>,
+}
+
+impl<'p> Vm<'p> {
+    /// Creates a virtual machine that prints to stdout.
+    pub fn new(program: &'p Program) -> Self {
+        Self::with_output(program, std::io::stdout())
+    }
+
+    /// Creates a virtual machine that prints to the given writer.
+    pub fn with_output(program: &'p Program, out: impl Write + 'p) -> Self {
+        Self {
+            program,
+            stack: vec![],
+            heap: program.heap.clone(),
+            out: Box::new(out),
+        }
+    }
+
+    /// Runs the `main` function of the program.
+    pub fn run(&mut self) -> Result<(), Locatable<Error>> {
+        let _profiler = profiler::trace("Vm", "Run");
+
+        let program = self.program;
+        let mut frames = Vec::<Frame>::new();
+        let mut func_idx = program.main;
+        let mut func = &program.functions[func_idx as usize];
+        let mut base = 0;
+        let mut pc = 0;
+        self.stack.resize(func.frame_size as usize, 0);
+
+        // the error of the instruction that was executed last
+        let trap = |func: &Function, pc: usize, err: RuntimeError| {
+            Locatable::new(err.into(), func.file, func.spans[pc - 1])
+        };
+        let reg = |base: usize, reg: Reg| base + reg.0 as usize;
+
+        loop {
+            let instr = func.code[pc];
+            pc += 1;
+            match instr {
+                Instr::Const { dst, idx, count } => {
+                    let idx = idx as usize;
+                    let dst = reg(base, dst);
+                    self.stack[dst..dst + count as usize]
+                        .copy_from_slice(&program.consts[idx..idx + count as usize]);
+                }
+                Instr::Move { dst, src, count } => {
+                    let src = reg(base, src);
+                    self.stack
+                        .copy_within(src..src + count as usize, reg(base, dst));
+                }
+                Instr::Binary {
+                    op,
+                    ty,
+                    dst,
+                    left,
+                    right,
+                } => {
+                    let left = self.stack[reg(base, left)];
+                    let right = self.stack[reg(base, right)];
+                    let val = binary(op, ty, left, right)
+                        .map_err(|err| trap(func, pc, RuntimeError::Arithmetic(err)))?;
+                    self.stack[reg(base, dst)] = val;
+                }
+                Instr::Unary { op, ty, dst, src } => {
+                    let val = unary(op, ty, self.stack[reg(base, src)])
+                        .map_err(|err| trap(func, pc, RuntimeError::Arithmetic(err)))?;
+                    self.stack[reg(base, dst)] = val;
+                }
+                Instr::Equal {
+                    dst,
+                    left,
+                    right,
+                    count,
+                    negate,
+                } => {
+                    let (left, right) = (reg(base, left), reg(base, right));
+                    let count = count as usize;
+                    let equal = self.stack[left..left + count] == self.stack[right..right + count];
+                    self.stack[reg(base, dst)] = (equal != negate) as Slot;
+                }
+                Instr::Offset {
+                    dst,
+                    ptr,
+                    count,
+                    ty,
+                    stride,
+                } => {
+                    let ptr = self.stack[reg(base, ptr)];
+                    let count = as_i128(self.stack[reg(base, count)], ty);
+                    self.stack[reg(base, dst)] = offset(ptr, count.saturating_mul(stride as i128));
+                }
+                Instr::AddrLocal { dst, src } => {
+                    self.stack[reg(base, dst)] = STACK | reg(base, src) as Slot;
+                }
+                Instr::AddrField {
+                    dst,
+                    ptr,
+                    offset: off,
+                } => {
+                    let ptr = self.stack[reg(base, ptr)];
+                    self.stack[reg(base, dst)] = offset(ptr, off as i128);
+                }
+                Instr::Load {
+                    dst,
+                    ptr,
+                    offset,
+                    count,
+                } => {
+                    let ptr = self.stack[reg(base, ptr)];
+                    let dst = reg(base, dst);
+                    let count = count as usize;
+                    match self.resolve(ptr, offset, count, base + func.frame_size as usize) {
+                        Some((true, idx)) => self.stack.copy_within(idx..idx + count, dst),
+                        Some((false, idx)) => self.stack[dst..dst + count]
+                            .copy_from_slice(&self.heap[idx..idx + count]),
+                        None => return Err(trap(func, pc, RuntimeError::InvalidPointer)),
+                    }
+                }
+                Instr::Store {
+                    ptr,
+                    offset,
+                    src,
+                    count,
+                } => {
+                    let ptr = self.stack[reg(base, ptr)];
+                    let src = reg(base, src);
+                    let count = count as usize;
+                    match self.resolve(ptr, offset, count, base + func.frame_size as usize) {
+                        Some((true, idx)) => self.stack.copy_within(src..src + count, idx),
+                        Some((false, idx)) => self.heap[idx..idx + count]
+                            .copy_from_slice(&self.stack[src..src + count]),
+                        None => return Err(trap(func, pc, RuntimeError::InvalidPointer)),
+                    }
+                }
+                Instr::Jump { target } => pc = target as usize,
+                Instr::JumpIf { cond, target } => {
+                    if self.stack[reg(base, cond)] != 0 {
+                        pc = target as usize;
+                    }
+                }
+                Instr::Switch { discr, table } => {
+                    let discr = self.stack[reg(base, discr)];
+                    let table = &func.switches[table as usize];
+                    let target = table
+                        .targets
+                        .iter()
+                        .find(|(val, _)| *val == discr)
+                        .map_or(table.otherwise, |(_, target)| *target);
+                    pc = target as usize;
+                }
+                Instr::Call {
+                    func: callee_idx,
+                    args,
+                    dst,
+                } => {
+                    let callee = &program.functions[callee_idx as usize];
+                    let callee_base = base + func.frame_size as usize;
+                    let top = callee_base + callee.frame_size as usize;
+                    if top > MAX_STACK {
+                        let name = callee.name.clone();
+                        return Err(trap(func, pc, RuntimeError::StackOverflow { name }));
+                    }
+                    if self.stack.len() < top {
+                        self.stack.resize(top, 0);
+                    }
+
+                    let args = reg(base, args);
+                    self.stack.copy_within(
+                        args..args + callee.arg_size as usize,
+                        callee_base + callee.ret_size as usize,
+                    );
+                    frames.push(Frame {
+                        func: func_idx,
+                        pc,
+                        base,
+                        dst: reg(base, dst),
+                    });
+                    func_idx = callee_idx;
+                    func = callee;
+                    base = callee_base;
+                    pc = 0;
+                }
+                Instr::Return => {
+                    let frame = match frames.pop() {
+                        Some(frame) => frame,
+                        None => return Ok(()),
+                    };
+                    self.stack
+                        .copy_within(base..base + func.ret_size as usize, frame.dst);
+                    func_idx = frame.func;
+                    func = &program.functions[func_idx as usize];
+                    base = frame.base;
+                    pc = frame.pc;
+                }
+                Instr::Print { src, ty } => {
+                    let src = reg(base, src);
+                    let text = match &program.types[ty as usize] {
+                        TypeDesc::String => program.strings[self.stack[src] as usize].clone(),
+                        TypeDesc::Scalar(Scalar::Char) => display_char(self.stack[src]).to_string(),
+                        ty => self.display(ty, &self.stack[src..src + ty.size()]),
+                    };
+                    writeln!(self.out, "{}", text).map_err(|err| {
+                        let message = err.to_string();
+                        trap(func, pc, RuntimeError::Output { message })
+                    })?;
+                }
+                Instr::Alloc {
+                    dst,
+                    count,
+                    ty,
+                    stride,
+                } => {
+                    let count = as_i128(self.stack[reg(base, count)], ty);
+                    let size = usize::try_from(count.saturating_mul(stride as i128))
+                        .ok()
+                        .filter(|size| self.heap.len() + size <= MAX_HEAP);
+                    let size = match size {
+                        Some(size) => size,
+                        None => return Err(trap(func, pc, RuntimeError::OutOfMemory)),
+                    };
+                    let ptr = HEAP | self.heap.len() as Slot;
+                    self.heap.resize(self.heap.len() + size, 0);
+                    self.stack[reg(base, dst)] = ptr;
+                }
+                Instr::Trap => return Err(trap(func, pc, RuntimeError::Unreachable)),
+            }
+        }
+    }
+
+    /// Returns if the pointer points into the stack, and the index of the
+    /// first slot, if `count` slots starting at `offset` are inside the memory.
+    ///
+    /// Pointers into the stack are only valid below `top`, the end of the current frame.
+    fn resolve(&self, ptr: Slot, offset: u32, count: usize, top: usize) -> Option<(bool, usize)> {
+        let idx = usize::try_from(ptr & INDEX)
+            .ok()?
+            .checked_add(offset as usize)?;
+        let (is_stack, len) = match ptr & !INDEX {
+            STACK => (true, top),
+            HEAP => (false, self.heap.len()),
+            _ => return None,
+        };
+        if idx.checked_add(count)? <= len {
+            Some((is_stack, idx))
+        } else {
+            None
+        }
+    }
+
+    /// Formats a value the same way the interpreter does.
+    fn display(&self, ty: &TypeDesc, slots: &[Slot]) -> String {
+        let fields = |fields: &[(String, TypeDesc)]| {
+            let mut offset = 0;
+            let fields = fields
+                .iter()
+                .map(|(name, ty)| {
+                    let val = self.display(ty, &slots[offset..offset + ty.size()]);
+                    offset += ty.size();
+                    format!("{}: {}", name, val)
+                })
+                .collect::<Vec<_>>();
+            fields.join(", ")
+        };
+
+        match ty {
+            TypeDesc::Scalar(scalar) => {
+                let slot = slots[0];
+                match *scalar {
+                    Scalar::Int { width, signed } => to_const(slot, width, signed).to_string(),
+                    Scalar::Float { .. } => f64::from_bits(slot as u64).to_string(),
+                    Scalar::Bool => (slot != 0).to_string(),
+                    Scalar::Char => format!("{:?}", display_char(slot)),
+                    Scalar::Pointer => {
+                        let memory = if slot & !INDEX == HEAP {
+                            "heap"
+                        } else {
+                            "stack"
+                        };
+                        format!("<pointer {}+{}>", memory, slot & INDEX)
+                    }
+                }
+            }
+            TypeDesc::String => format!("{:?}", self.program.strings[slots[0] as usize]),
+            TypeDesc::Unit => "()".to_string(),
+            TypeDesc::Tuple(tys) => {
+                let mut offset = 0;
+                let vals = tys
+                    .iter()
+                    .map(|ty| {
+                        let val = self.display(ty, &slots[offset..offset + ty.size()]);
+                        offset += ty.size();
+                        val
+                    })
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", vals.join(", "))
+            }
+            TypeDesc::NamedTuple(tys) => format!("{{{}}}", fields(tys)),
+            TypeDesc::Struct { name, fields: tys } => format!("{} {{{}}}", name, fields(tys)),
+            TypeDesc::Union { name, variants } => {
+                let (variant, data) = &variants[slots[0] as usize];
+                match data {
+                    Some(ty) => format!(
+                        "{}:{}({})",
+                        name,
+                        variant,
+                        self.display(ty, &slots[1..1 + ty.size()])
+                    ),
+                    None => format!("{}:{}", name, variant),
+                }
+            }
+        }
+    }
+}
+
+fn display_char(slot: Slot) -> char {
+    std::char::from_u32(slot as u32).unwrap_or(std::char::REPLACEMENT_CHARACTER)
+}
+
+/// Moves the pointer by `count` slots.
+///
+/// Pointers that would be moved outside of the memory become invalid.
+fn offset(ptr: Slot, count: i128) -> Slot {
+    let idx = ((ptr & INDEX) as i128)
+        .checked_add(count)
+        .filter(|idx| *idx >= 0 && *idx < INDEX as i128)
+        .unwrap_or(INDEX as i128);
+    (ptr & !INDEX) | idx as Slot
+}
+
+fn mask(width: u16) -> Slot {
+    Slot::MAX >> (128 - width as u32)
+}
+
+fn sign_extend(slot: Slot, width: u16) -> i128 {
+    let shift = 128 - width as u32;
+    ((slot << shift) as i128) >> shift
+}
+
+/// Returns the value of an integer, saturated to the range of an `i128`.
+fn as_i128(slot: Slot, ty: Scalar) -> i128 {
+    match ty {
+        Scalar::Int {
+            width,
+            signed: true,
+        } => sign_extend(slot, width),
+        _ => i128::try_from(slot).unwrap_or(i128::MAX),
+    }
+}
+
+fn to_const(slot: Slot, width: u16, signed: bool) -> ConstInt {
+    let int = if signed {
+        ConstInt::from_i128(sign_extend(slot, width), width, signed)
+    } else {
+        ConstInt::from_u128(slot, width, signed)
+    };
+    int.expect("integer is wider than its type")
+}
+
+fn binary(op: BinOp, ty: Scalar, left: Slot, right: Slot) -> Result<Slot, ConstError> {
+    match ty {
+        Scalar::Int { width, signed } => int_binary(op, width, signed, left, right),
+        Scalar::Float { width } => {
+            let float = |slot: Slot| ConstValue::Float {
+                val: f64::from_bits(slot as u64).into(),
+                width,
+            };
+            match ast_op(op) {
+                Ok(op) => match consteval::binary_op(op, float(left), float(right))? {
+                    ConstValue::Float { val, .. } => Ok(val.into_inner().to_bits() as Slot),
+                    _ => Err(ConstError::NotConst),
+                },
+                Err(op) => Ok(consteval::compare(op, &float(left), &float(right))? as Slot),
+            }
+        }
+        Scalar::Bool | Scalar::Char | Scalar::Pointer => {
+            let val = match op {
+                BinOp::BitAnd => left & right,
+                BinOp::BitOr => left | right,
+                BinOp::BitXor => left ^ right,
+                BinOp::Eq => (left == right) as Slot,
+                BinOp::Ne => (left != right) as Slot,
+                BinOp::Lt => (left < right) as Slot,
+                BinOp::Le => (left <= right) as Slot,
+                BinOp::Gt => (left > right) as Slot,
+                BinOp::Ge => (left >= right) as Slot,
+                _ => return Err(ConstError::NotConst),
+            };
+            Ok(val)
+        }
+    }
+}
+
+fn int_binary(
+    op: BinOp,
+    width: u16,
+    signed: bool,
+    left: Slot,
+    right: Slot,
+) -> Result<Slot, ConstError> {
+    let (a, b) = if signed {
+        (sign_extend(left, width), sign_extend(right, width))
+    } else {
+        (left as i128, right as i128)
+    };
+
+    // the common operations are computed directly, everything else
+    // and every error is handled by the constant evaluator
+    let fast = match op {
+        BinOp::Eq => Some((left == right) as Slot),
+        BinOp::Ne => Some((left != right) as Slot),
+        BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge if signed || width < 128 => {
+            let val = match op {
+                BinOp::Lt => a < b,
+                BinOp::Le => a <= b,
+                BinOp::Gt => a > b,
+                _ => a >= b,
+            };
+            Some(val as Slot)
+        }
+        BinOp::BitAnd => Some(left & right),
+        BinOp::BitOr => Some(left | right),
+        BinOp::BitXor => Some(left ^ right),
+        BinOp::Add | BinOp::Sub | BinOp::Mul if width <= 64 => {
+            let val = match op {
+                BinOp::Add => a.checked_add(b),
+                BinOp::Sub => a.checked_sub(b),
+                _ => a.checked_mul(b),
+            };
+            let (min, max) = if signed {
+                (-(1i128 << (width - 1)), (1i128 << (width - 1)) - 1)
+            } else {
+                (0, mask(width) as i128)
+            };
+            val.filter(|val| *val >= min && *val <= max)
+                .map(|val| val as Slot & mask(width))
+        }
+        _ => None,
+    };
+    if let Some(val) = fast {
+        return Ok(val);
+    }
+
+    let (left, right) = (
+        ConstValue::Int(to_const(left, width, signed)),
+        ConstValue::Int(to_const(right, width, signed)),
+    );
+    match ast_op(op) {
+        Ok(op) => match consteval::binary_op(op, left, right)? {
+            ConstValue::Int(int) => Ok(int.as_u128()),
+            _ => Err(ConstError::NotConst),
+        },
+        Err(op) => Ok(consteval::compare(op, &left, &right)? as Slot),
+    }
+}
+
+fn unary(op: UnOp, ty: Scalar, val: Slot) -> Result<Slot, ConstError> {
+    match (op, ty) {
+        (UnOp::Not, Scalar::Bool) => Ok(val ^ 1),
+        (UnOp::Not, Scalar::Int { width, .. }) => Ok(!val & mask(width)),
+        (UnOp::Neg, Scalar::Float { .. }) => Ok((-f64::from_bits(val as u64)).to_bits() as Slot),
+        (UnOp::Neg, Scalar::Int { width, signed }) => {
+            let val = ConstValue::Int(to_const(val, width, signed));
+            match consteval::unary_op(ast::UnOp::Minus, val)? {
+                ConstValue::Int(int) => Ok(int.as_u128()),
+                _ => Err(ConstError::NotConst),
+            }
+        }
+        _ => Err(ConstError::NotConst),
+    }
+}
+
+/// Converts an operator into the operator of the constant evaluator.
+fn ast_op(op: BinOp) -> Result<ast::BinOp, ast::CmpOp> {
+    let op = match op {
+        BinOp::Add => ast::BinOp::Add,
+        BinOp::Sub => ast::BinOp::Sub,
+        BinOp::Mul => ast::BinOp::Mul,
+        BinOp::Div => ast::BinOp::Div,
+        BinOp::Rem => ast::BinOp::Mod,
+        BinOp::Pow => ast::BinOp::Pow,
+        BinOp::BitAnd => ast::BinOp::BitiwseAnd,
+        BinOp::BitOr => ast::BinOp::BitwiseOr,
+        BinOp::BitXor => ast::BinOp::BitwiseXor,
+        BinOp::Shl => ast::BinOp::LeftShift,
+        BinOp::Shr => ast::BinOp::RightShift,
+        BinOp::Eq => return Err(ast::CmpOp::Equal),
+        BinOp::Ne => return Err(ast::CmpOp::NotEqual),
+        BinOp::Lt => return Err(ast::CmpOp::Less),
+        BinOp::Le => return Err(ast::CmpOp::LessEqual),
+        BinOp::Gt => return Err(ast::CmpOp::Greater),
+        BinOp::Ge => return Err(ast::CmpOp::GreaterEqual),
+    };
+    Ok(op)
+}
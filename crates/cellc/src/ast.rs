@@ -0,0 +1,224 @@
+//! Prints syntax trees with resolved names, which is used by `cellc parse`
+//! and the `:ast` command of the REPL.
+
+use cell_parser::{db::ParseDatabase, pretty::Print};
+use cell_syntax::{
+    ast::{
+        Block, Expr, ExprId, ExprKind, FieldName, Generic, Identifier, Item, ItemId, ItemKind,
+        MatchArm, Pattern, Repr, Stmt, StmtKind, Type, Visibility,
+    },
+    db::AstDatabase,
+    visit::{self, Visitor},
+};
+use std::fmt::{self, Write};
+
+/// Returns the syntax tree of an item.
+pub fn item(db: &dyn ParseDatabase, item: ItemId) -> String {
+    let mut printer = AstPrinter::new(db);
+    printer.visit_item_id(db, item);
+    printer.out
+}
+
+/// Returns the syntax tree of an expression.
+pub fn expr(db: &dyn ParseDatabase, expr: ExprId) -> String {
+    let mut printer = AstPrinter::new(db);
+    printer.visit_expr_id(db, expr);
+    printer.out
+}
+
+/// Prints a syntax tree, one node per line,
+/// where children are indented below their parent.
+struct AstPrinter<'db> {
+    db: &'db dyn ParseDatabase,
+    depth: usize,
+    out: String,
+}
+
+impl<'db> AstPrinter<'db> {
+    fn new(db: &'db dyn ParseDatabase) -> Self {
+        Self {
+            db,
+            depth: 0,
+            out: String::new(),
+        }
+    }
+
+    fn node(&mut self, label: impl fmt::Display, children: impl FnOnce(&mut Self)) {
+        writeln!(self.out, "{:indent$}{}", "", label, indent = self.depth * 2).unwrap();
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+    }
+
+    fn leaf(&mut self, label: impl fmt::Display) {
+        self.node(label, |_| {});
+    }
+
+    fn name(&self, ident: &Identifier) -> String {
+        self.db.rodeo().resolve(&ident.id).to_string()
+    }
+
+    /// Returns the label of a named node, like `Def pub main`.
+    fn named(&self, kind: &str, vis: Visibility, name: &Identifier) -> String {
+        match vis {
+            Visibility::Public => format!("{} pub {}", kind, self.name(name)),
+            Visibility::Private => format!("{} {}", kind, self.name(name)),
+        }
+    }
+
+    fn generics(&mut self, generics: &[Generic]) {
+        for generic in generics {
+            let mut label = format!("Generic {}", self.name(&generic.name));
+            for (idx, bound) in generic.bounds.iter().enumerate() {
+                let sep = if idx == 0 { ":" } else { " +" };
+                write!(label, "{} {}", sep, self.name(bound)).unwrap();
+            }
+            self.leaf(label);
+        }
+    }
+
+    fn repr(&mut self, repr: Repr) {
+        match repr {
+            Repr::Default => {}
+            Repr::C => self.leaf("Repr C"),
+            Repr::Packed => self.leaf("Repr packed"),
+        }
+    }
+}
+
+impl Visitor for AstPrinter<'_> {
+    fn visit_item(&mut self, db: &dyn AstDatabase, item: &Item) {
+        let vis = item.vis;
+        match &item.kind {
+            ItemKind::TypeDef(def) => self.node(self.named("TypeDef", vis, &def.name), |p| {
+                p.repr(def.repr);
+                p.generics(&def.generics);
+                for field in &def.fields {
+                    let label = p.named("Field", field.vis, &field.name);
+                    p.leaf(format!("{}: {}", label, field.ty.display(p.db)));
+                }
+            }),
+            ItemKind::Def(def) => self.node(self.named("Def", vis, &def.name), |p| {
+                p.generics(&def.generics);
+                for (name, ty) in &def.args {
+                    p.leaf(format!("Param {}: {}", p.name(name), ty.display(p.db)));
+                }
+                p.leaf(format!("Returns {}", def.return_ty.display(p.db)));
+                if let Some(body) = &def.body {
+                    p.visit_block(db, body);
+                }
+            }),
+            ItemKind::Alias(alias) => self.node(self.named("Alias", vis, &alias.name), |p| {
+                p.generics(&alias.generics);
+                p.leaf(format!("Type {}", alias.ty.display(p.db)));
+            }),
+            ItemKind::Union(union) => self.node(self.named("Union", vis, &union.name), |p| {
+                p.repr(union.repr);
+                p.generics(&union.generics);
+                for variant in &union.variants {
+                    let label = p.named("Variant", variant.vis, &variant.name);
+                    match variant.data {
+                        Some(ty) => p.leaf(format!("{} = {}", label, ty.display(p.db))),
+                        None => p.leaf(label),
+                    }
+                }
+            }),
+            ItemKind::Trait(trait_) => self.node(self.named("Trait", vis, &trait_.name), |p| {
+                trait_.methods.iter().for_each(|&m| p.visit_item_id(db, m));
+            }),
+            ItemKind::Impl(impl_) => {
+                let mut label = format!("Impl {}", impl_.ty.display(self.db));
+                if let Some(trait_) = &impl_.trait_ {
+                    write!(label, ": {}", self.name(trait_)).unwrap();
+                }
+                self.node(label, |p| {
+                    p.generics(&impl_.generics);
+                    impl_.methods.iter().for_each(|&m| p.visit_item_id(db, m));
+                });
+            }
+            ItemKind::Const(global) | ItemKind::Static(global) => {
+                let kind = match &item.kind {
+                    ItemKind::Const(_) => "Const",
+                    _ => "Static",
+                };
+                let label = self.named(kind, vis, &global.name);
+                let label = format!("{}: {}", label, global.ty.display(self.db));
+                self.node(label, |p| p.visit_expr_id(db, global.val));
+            }
+            ItemKind::Import(import) => self.leaf(self.named("Import", vis, &import.name)),
+            ItemKind::Use(use_) => {
+                let path = use_
+                    .path
+                    .segments
+                    .iter()
+                    .map(|segment| self.name(segment))
+                    .collect::<Vec<_>>()
+                    .join(":");
+                let label = match vis {
+                    Visibility::Public => format!("Use pub {}", path),
+                    Visibility::Private => format!("Use {}", path),
+                };
+                self.leaf(label);
+            }
+        }
+    }
+
+    fn visit_block(&mut self, db: &dyn AstDatabase, block: &Block) {
+        self.node("Block", |p| visit::walk_block(p, db, block));
+    }
+
+    fn visit_stmt(&mut self, db: &dyn AstDatabase, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Var(var) => {
+                let mut label = format!("Var {}", self.name(&var.name));
+                if let Some(ty) = &var.ty {
+                    write!(label, ": {}", ty.display(self.db)).unwrap();
+                }
+                self.node(label, |p| p.visit_expr_id(db, var.val));
+            }
+            StmtKind::Expr(expr) => self.visit_expr_id(db, *expr),
+        }
+    }
+
+    fn visit_expr(&mut self, db: &dyn AstDatabase, expr: &Expr) {
+        let label = match &expr.kind {
+            ExprKind::Literal(_) => format!("Literal {}", expr.display(self.db)),
+            ExprKind::Binary(binary) => format!("Binary {}", binary.op.data()),
+            ExprKind::Comparison(cmp) => format!("Comparison {}", cmp.op.data()),
+            ExprKind::Unary(unary) => format!("Unary {}", unary.op.data()),
+            ExprKind::Tuple(_) => "Tuple".to_string(),
+            ExprKind::NamedTuple(_) => "NamedTuple".to_string(),
+            ExprKind::If(_) => "If".to_string(),
+            ExprKind::Match(_) => "Match".to_string(),
+            ExprKind::While(_) => "While".to_string(),
+            // the block prints itself
+            ExprKind::Block(block) => return self.visit_block(db, block),
+            ExprKind::Path(_) => format!("Path {}", expr.display(self.db)),
+            ExprKind::Call(_) => "Call".to_string(),
+            ExprKind::MethodCall(call) => format!("MethodCall {}", self.name(&call.name)),
+            ExprKind::Field(field) => match &field.field {
+                FieldName::Named(name) => format!("Field {}", self.name(name)),
+                FieldName::Index(idx) => format!("Field {}", idx.data()),
+            },
+            ExprKind::Assign(assign) => match &assign.op {
+                Some(op) => format!("Assign {}=", op.data()),
+                None => "Assign =".to_string(),
+            },
+            ExprKind::Return(_) => "Return".to_string(),
+            ExprKind::Break => "Break".to_string(),
+            ExprKind::Continue => "Continue".to_string(),
+            ExprKind::This => "This".to_string(),
+        };
+        self.node(label, |p| visit::walk_expr(p, db, expr));
+    }
+
+    fn visit_match_arm(&mut self, db: &dyn AstDatabase, arm: &MatchArm) {
+        self.node("Arm", |p| visit::walk_match_arm(p, db, arm));
+    }
+
+    fn visit_pattern(&mut self, _db: &dyn AstDatabase, pattern: &Pattern) {
+        self.leaf(format!("Pattern {}", pattern.display(self.db)));
+    }
+
+    fn visit_type(&mut self, _db: &dyn AstDatabase, _ty: &Type) {}
+}
@@ -0,0 +1,75 @@
+//! The JSON output of `--error-format=json`.
+//!
+//! Every diagnostic is written as one JSON object on its own line:
+//!
+//! ```text
+//! {
+//!   "severity": "error",
+//!   "code": "E0111",
+//!   "message": "mismatched types: expected `i32`, found `bool`",
+//!   "labels": [{
+//!     "file": "main.cell",
+//!     "primary": true,
+//!     "message": "",
+//!     "byte_start": 20,
+//!     "byte_end": 24,
+//!     "line_start": 2,
+//!     "column_start": 13,
+//!     "line_end": 2,
+//!     "column_end": 17
+//!   }],
+//!   "notes": []
+//! }
+//! ```
+//!
+//! Every code is listed in `Errors.md`. Lines and columns start at one
+//! and columns count characters, like the positions in the human readable output.
+
+use cell_common::{
+    source::{Encoding, FileId, Index, SourceDatabase},
+    Diagnostic,
+};
+use codespan_reporting::diagnostic::{Label, LabelStyle, Severity};
+use serde_json::{json, Value};
+
+/// Converts a diagnostic into its JSON object.
+pub fn diagnostic(db: &dyn SourceDatabase, diagnostic: &Diagnostic) -> Value {
+    let severity = match diagnostic.severity {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    };
+    let labels = diagnostic
+        .labels
+        .iter()
+        .map(|l| label(db, l))
+        .collect::<Vec<_>>();
+
+    json!({
+        "severity": severity,
+        "code": diagnostic.code,
+        "message": diagnostic.message,
+        "labels": labels,
+        "notes": diagnostic.notes,
+    })
+}
+
+fn label(db: &dyn SourceDatabase, label: &Label<FileId>) -> Value {
+    let file = label.file_id;
+    let start = db.line_col(file, Index::from(label.range.start as u32), Encoding::Char);
+    let end = db.line_col(file, Index::from(label.range.end as u32), Encoding::Char);
+
+    json!({
+        "file": db.name(file).as_str(),
+        "primary": label.style == LabelStyle::Primary,
+        "message": label.message,
+        "byte_start": label.range.start,
+        "byte_end": label.range.end,
+        "line_start": start.line + 1,
+        "column_start": start.col + 1,
+        "line_end": end.line + 1,
+        "column_end": end.col + 1,
+    })
+}
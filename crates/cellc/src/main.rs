@@ -0,0 +1,488 @@
+//! The command line driver of the cell compiler.
+
+mod ast;
+mod json;
+mod repl;
+
+use cell_common::{
+    error::{Error, SyntaxError},
+    source::{FileCache, FileId, Locatable, SourceDatabase},
+    Diagnostic,
+};
+use cell_db::RootDatabase;
+use cell_mir::{
+    layout::{self, Target},
+    opt::OptLevel,
+    pretty,
+};
+use cell_parser::{db::ParseDatabase, pretty::Print, token::Kind};
+use cell_typeck::db::TypeckDatabase;
+use codespan_reporting::{
+    diagnostic::Severity,
+    term::{
+        self,
+        termcolor::{ColorChoice, StandardStream},
+    },
+};
+use salsa::ParallelDatabase;
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    io::{self, IsTerminal, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    thread,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "cellc",
+    about = "The compiler for the cell programming language"
+)]
+struct Args {
+    /// How to print errors and warnings: `human` or `json`
+    #[structopt(long, global = true, default_value = "human")]
+    error_format: ErrorFormat,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Prints the tokens of a file
+    Lex {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Prints the items of a file
+    Parse {
+        /// Prints the items as source code instead of their syntax tree
+        #[structopt(long)]
+        pretty: bool,
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Checks a program for errors, without compiling it
+    Check {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Compiles a program
+    Build {
+        #[structopt(flatten)]
+        options: Options,
+        /// What to generate: `exe`, `c`, `wasm`, `wat`, `mir`, `layout` or `bytecode`
+        #[structopt(long, default_value = "exe")]
+        emit: Emit,
+        /// The file to write the output to,
+        /// which defaults to the name of the input file
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Compiles and runs a program
+    Run {
+        #[structopt(flatten)]
+        options: Options,
+        /// How to run the program: `vm` or `interp`
+        #[structopt(long, default_value = "vm")]
+        backend: Backend,
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Evaluates expressions, statements and items interactively
+    Repl,
+}
+
+/// The options that control code generation.
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// The optimization level: `0`, `1` or `2`
+    #[structopt(short = "O", default_value = "0")]
+    opt_level: OptLevel,
+    /// Checks the MIR after every optimization pass and panics if it's
+    /// invalid, which is only useful for debugging the compiler
+    #[structopt(long)]
+    verify_mir: bool,
+}
+
+/// The output of `cellc build`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Emit {
+    /// An executable, compiled through C.
+    Exe,
+    C,
+    Wasm,
+    /// WebAssembly in the text format.
+    Wat,
+    Mir,
+    /// The memory layout of every type without generic parameters.
+    Layout,
+    /// The disassembled bytecode of the virtual machine.
+    Bytecode,
+}
+
+impl Emit {
+    /// The extension of the output file, if none is given.
+    fn extension(self) -> &'static str {
+        match self {
+            Emit::Exe => std::env::consts::EXE_EXTENSION,
+            Emit::C => "c",
+            Emit::Wasm => "wasm",
+            Emit::Wat => "wat",
+            Emit::Mir => "mir",
+            Emit::Layout => "layout",
+            Emit::Bytecode => "bytecode",
+        }
+    }
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exe" => Ok(Emit::Exe),
+            "c" => Ok(Emit::C),
+            "wasm" => Ok(Emit::Wasm),
+            "wat" => Ok(Emit::Wat),
+            "mir" => Ok(Emit::Mir),
+            "layout" => Ok(Emit::Layout),
+            "bytecode" => Ok(Emit::Bytecode),
+            _ => Err(format!(
+                "invalid output `{}`, expected `exe`, `c`, `wasm`, `wat`, `mir`, `layout` or `bytecode`",
+                s
+            )),
+        }
+    }
+}
+
+/// How `cellc run` executes the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    /// The bytecode virtual machine.
+    Vm,
+    /// The tree-walking interpreter.
+    Interp,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vm" => Ok(Backend::Vm),
+            "interp" => Ok(Backend::Interp),
+            _ => Err(format!(
+                "invalid backend `{}`, expected `vm` or `interp`",
+                s
+            )),
+        }
+    }
+}
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    /// Rendered with the source code they point at.
+    Human,
+    /// One JSON object per line, for tools that parse the output.
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!(
+                "invalid error format `{}`, expected `human` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+/// The code of errors that are reported by the driver itself,
+/// like a file that can't be read.
+const DRIVER_ERROR: &str = "E0500";
+
+/// Returned if a command failed.
+/// The reason was already reported when this is returned.
+struct Failed;
+
+/// Runs commands and reports their errors.
+struct Driver {
+    db: RootDatabase,
+    stderr: StandardStream,
+    config: term::Config,
+    format: ErrorFormat,
+}
+
+impl Driver {
+    fn new(format: ErrorFormat) -> Self {
+        let color = if io::stderr().is_terminal() {
+            ColorChoice::Auto
+        } else {
+            ColorChoice::Never
+        };
+        Self {
+            db: RootDatabase::new(),
+            stderr: StandardStream::stderr(color),
+            config: term::Config::default(),
+            format,
+        }
+    }
+
+    fn emit(&self, diagnostic: &Diagnostic) {
+        let mut stderr = self.stderr.lock();
+        match self.format {
+            ErrorFormat::Human => {
+                let files = FileCache::new(&self.db);
+                term::emit(&mut stderr, &self.config, &files, diagnostic)
+            }
+            ErrorFormat::Json => {
+                let json = json::diagnostic(&self.db, diagnostic);
+                writeln!(stderr, "{}", json)
+            }
+        }
+        .expect("failed to write to stderr");
+    }
+
+    /// Reports an error that has no location in the source code.
+    fn error(&self, msg: impl fmt::Display) -> Failed {
+        let diagnostic = Diagnostic::error()
+            .with_code(DRIVER_ERROR)
+            .with_message(msg.to_string());
+        self.emit(&diagnostic);
+        Failed
+    }
+
+    fn located_error(&self, err: Locatable<Error>) -> Failed {
+        self.emit(&err.into());
+        Failed
+    }
+
+    /// Emits the diagnostics and fails if any of them is an error.
+    fn emit_all(&self, diagnostics: impl IntoIterator<Item = Diagnostic>) -> Result<(), Failed> {
+        let mut failed = false;
+        for diagnostic in diagnostics {
+            failed |= diagnostic.severity >= Severity::Error;
+            self.emit(&diagnostic);
+        }
+        if failed {
+            Err(Failed)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<FileId, Failed> {
+        self.db.load_file(path).map_err(|err| self.error(err))
+    }
+
+    /// Loads the program and reports every error and warning in it.
+    fn load_program(&mut self, path: &Path) -> Result<(), Failed> {
+        self.db.load_program(path).map_err(|err| self.error(err))?;
+
+        let files = self.db.module_tree().files().collect::<Vec<_>>();
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk_size = files.len().div_ceil(threads).max(1);
+
+        // every thread checks some of the modules on its own snapshot
+        let diagnostics = thread::scope(|scope| {
+            let handles = files
+                .chunks(chunk_size)
+                .map(|files| {
+                    let db = self.db.snapshot();
+                    scope.spawn(move || {
+                        files
+                            .iter()
+                            .flat_map(|&file| file_diagnostics(&*db, file))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("a checker thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        self.emit_all(diagnostics)
+    }
+
+    fn run(&mut self, command: Command) -> Result<(), Failed> {
+        match command {
+            Command::Lex { file } => self.lex(&file),
+            Command::Parse { pretty, file } => self.parse(&file, pretty),
+            Command::Check { file } => self.load_program(&file),
+            Command::Build {
+                options,
+                emit,
+                output,
+                file,
+            } => {
+                cell_mir::opt::set_verify(options.verify_mir);
+                let output = output.unwrap_or_else(|| file.with_extension(emit.extension()));
+                self.load_program(&file)?;
+                self.build(emit, options.opt_level, &output)
+            }
+            Command::Run {
+                options,
+                backend,
+                file,
+            } => {
+                cell_mir::opt::set_verify(options.verify_mir);
+                self.load_program(&file)?;
+                self.execute(backend, options.opt_level)
+            }
+            Command::Repl => self.repl(),
+        }
+    }
+
+    fn lex(&mut self, path: &Path) -> Result<(), Failed> {
+        let file = self.load_file(path)?;
+        let source = self.db.file_text(file);
+
+        let mut out = String::new();
+        let mut errors = vec![];
+        for token in self.db.lex(file) {
+            let text = &source[Range::<usize>::from(token.span)];
+            writeln!(out, "{} {:?}", token, text).unwrap();
+            if token.kind == Kind::Error {
+                let err = SyntaxError::InvalidToken;
+                errors.push(Locatable::new(Error::from(err), file, token.span).into());
+            }
+        }
+        self.print(&out)?;
+        self.emit_all(errors)
+    }
+
+    fn parse(&mut self, path: &Path, pretty: bool) -> Result<(), Failed> {
+        let file = self.load_file(path)?;
+        let parsed = self.db.parse(file);
+        let mut out = String::new();
+        for &item in parsed.items.iter() {
+            if pretty {
+                writeln!(out, "{}\n", item.display(&self.db)).unwrap();
+            } else {
+                out.push_str(&ast::item(&self.db, item));
+            }
+        }
+        self.print(&out)?;
+        self.emit_all(parsed.errors.iter().cloned().map(Diagnostic::from))
+    }
+
+    /// Writes `text` to stdout. A closed pipe isn't an error, because it only
+    /// means that the reader has seen enough, like in `cellc parse f.cell | head`.
+    fn print(&self, text: &str) -> Result<(), Failed> {
+        let mut stdout = io::stdout().lock();
+        match stdout
+            .write_all(text.as_bytes())
+            .and_then(|()| stdout.flush())
+        {
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+                Err(self.error(format!("failed to write to stdout: {}", err)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn build(&self, emit: Emit, level: OptLevel, output: &Path) -> Result<(), Failed> {
+        let db = &self.db;
+        let write = |contents: &[u8]| {
+            fs::write(output, contents).map_err(|err| {
+                self.error(format!("failed to write `{}`: {}", output.display(), err))
+            })
+        };
+
+        match emit {
+            Emit::Exe => {
+                let source =
+                    cell_codegen_c::generate(db, level).map_err(|err| self.located_error(err))?;
+                cell_codegen_c::build(&source, output).map_err(|err| self.error(err))
+            }
+            Emit::C => {
+                let source =
+                    cell_codegen_c::generate(db, level).map_err(|err| self.located_error(err))?;
+                write(source.as_bytes())
+            }
+            Emit::Wasm | Emit::Wat => {
+                let source = cell_codegen_wasm::generate(db, level)
+                    .map_err(|err| self.located_error(err))?;
+                if emit == Emit::Wat {
+                    return write(source.as_bytes());
+                }
+                let binary = cell_codegen_wasm::assemble(&source)
+                    .map_err(|err| self.error(format!("failed to assemble the module: {}", err)))?;
+                write(&binary)
+            }
+            Emit::Mir => {
+                let mir = db
+                    .module_tree()
+                    .files()
+                    .map(|file| pretty::pretty_file(db, file, level))
+                    .collect::<String>();
+                write(mir.as_bytes())
+            }
+            Emit::Layout => {
+                // the layouts of the C backend, which are the ones of the host
+                let mut layouts = db
+                    .module_tree()
+                    .files()
+                    .map(|file| layout::dump_file(db, file, Target::HOST))
+                    .collect::<Vec<_>>();
+                let instances = layout::dump_instances(db, level, Target::HOST)
+                    .map_err(|err| self.located_error(err))?;
+                layouts.push(instances);
+                layouts.retain(|layouts| !layouts.is_empty());
+                write(layouts.join("\n").as_bytes())
+            }
+            Emit::Bytecode => {
+                let program = cell_vm::compile(db, level).map_err(|err| self.located_error(err))?;
+                write(cell_vm::disasm::disassemble(&program).as_bytes())
+            }
+        }
+    }
+
+    fn execute(&self, backend: Backend, level: OptLevel) -> Result<(), Failed> {
+        let db = &self.db;
+        match backend {
+            Backend::Vm => {
+                let program = cell_vm::compile(db, level).map_err(|err| self.located_error(err))?;
+                let result = cell_vm::Vm::new(&program).run();
+                result.map_err(|err| self.located_error(err))
+            }
+            Backend::Interp => {
+                let db = db.snapshot();
+                let result = cell_interp::with_stack(move || {
+                    cell_interp::Interpreter::new(&*db).run_main().map(|_| ())
+                });
+                result.map_err(|err| self.located_error(err))
+            }
+        }
+    }
+}
+
+/// Returns the errors and warnings of the file.
+fn file_diagnostics(db: &dyn TypeckDatabase, file: FileId) -> Vec<Diagnostic> {
+    let errors = db.check_file(file);
+    let warnings = db.file_warnings(file);
+    let errors = errors.iter().cloned().map(Diagnostic::from);
+    let warnings = warnings.iter().cloned().map(Diagnostic::from);
+    errors.chain(warnings).collect()
+}
+
+fn main() {
+    let args = Args::from_args();
+    let mut driver = Driver::new(args.error_format);
+    if driver.run(args.command).is_err() {
+        process::exit(1);
+    }
+}
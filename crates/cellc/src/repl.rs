@@ -0,0 +1,338 @@
+//! The interactive REPL of `cellc repl`.
+//!
+//! Every input is stored as a new file in the database, so diagnostics
+//! point at the input that caused them. An input is either a list of items
+//! or a list of statements:
+//!
+//! - Items are kept as outer files of the source root, which makes them
+//!   available to every later input. Redefining a name is an error, because
+//!   earlier inputs would silently refer to the new definition otherwise.
+//! - Statements are type checked and interpreted as the body of a new
+//!   function, which starts with the variables of the earlier inputs as its
+//!   locals. The interpreter keeps its memory between inputs, so variables
+//!   and `static`s keep their values and only the new statements are evaluated.
+
+use crate::{ast, Driver, Failed};
+use cell_common::{
+    error::TypeError,
+    source::{self, FileId, Locatable, SourceDatabase, SourceRoot, Span},
+    strings::StringId,
+    Diagnostic,
+};
+use cell_interp::{
+    value::{Pointer, Value},
+    with_stack, Interpreter, State,
+};
+use cell_parser::{
+    db::ParseDatabase,
+    parse::{Input, Parser},
+    token::{self, Kind},
+};
+use cell_syntax::ast::{
+    Block, Def, ExprId, Identifier, Item, ItemId, ItemKind, StmtId, StmtKind, Type, Visibility,
+};
+use cell_typeck::{
+    db::TypeckDatabase,
+    infer::{self, TypeckResults},
+    prelude,
+    scope::FileScope,
+    ty::Ty,
+};
+use salsa::ParallelDatabase;
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, IsTerminal, Write},
+    sync::Arc,
+};
+
+const HELP: &str = "\
+Enter an expression, a `var` statement or an item to evaluate it.
+Definitions are kept until the REPL exits.
+
+Commands:
+  :type <expr>  Prints the type of an expression
+  :ast <expr>   Prints the syntax tree of an expression
+  :help         Prints this message
+  :quit         Exits the REPL";
+
+/// The state that is kept across inputs.
+struct Repl<'a> {
+    driver: &'a mut Driver,
+    /// The number of inputs so far, which is used to name their files.
+    inputs: usize,
+    /// Every input that defined items, in the order they were entered.
+    items: Vec<FileId>,
+    /// The variables of earlier inputs that are not shadowed by a later one.
+    vars: Vec<Variable>,
+    /// The memory of the interpreter, which stores the variables.
+    state: State,
+}
+
+/// A variable that was declared by an earlier input.
+struct Variable {
+    name: StringId,
+    ty: Ty,
+    ptr: Pointer,
+}
+
+impl Driver {
+    /// Reads inputs from stdin and evaluates them until stdin is closed.
+    pub(crate) fn repl(&mut self) -> Result<(), Failed> {
+        let mut repl = Repl {
+            driver: self,
+            inputs: 0,
+            items: vec![],
+            vars: vec![],
+            state: State::default(),
+        };
+
+        let stdin = io::stdin();
+        // don't print prompts if the input is piped in
+        let interactive = stdin.is_terminal();
+        let mut lines = stdin.lock().lines();
+        let mut input = String::new();
+        loop {
+            if interactive {
+                prompt(if input.is_empty() { "> " } else { "... " });
+            }
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => return Err(repl.driver.error(err)),
+                None => break,
+            };
+            input.push_str(&line);
+            input.push('\n');
+            if !is_complete(&input) {
+                continue;
+            }
+
+            let text = std::mem::take(&mut input);
+            match text.trim() {
+                "" => {}
+                ":quit" | ":q" => break,
+                ":help" | ":h" => println!("{}", HELP),
+                text => {
+                    // the error was already reported
+                    let _ = repl.input(text);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Repl<'_> {
+    fn input(&mut self, text: &str) -> Result<(), Failed> {
+        if text.starts_with(':') {
+            let end = text.find(char::is_whitespace).unwrap_or(text.len());
+            let (command, arg) = text.split_at(end);
+            return match command {
+                ":type" | ":t" => self.type_of(arg),
+                ":ast" => self.ast(arg),
+                _ => Err(self.driver.error(format!("unknown command `{}`", command))),
+            };
+        }
+
+        let (file, input) = self.parse(text)?;
+        match input {
+            Input::Items(items) => self.define(file, &items),
+            Input::Stmts(stmts) => self.eval(file, stmts),
+        }
+    }
+
+    /// Adds the input as a new file and makes it the root module,
+    /// enclosed by every input that defined items.
+    fn parse(&mut self, text: &str) -> Result<(FileId, Input), Failed> {
+        self.inputs += 1;
+        let path = format!("<repl:{}>", self.inputs);
+        let db = &mut self.driver.db;
+        let file = source::set_file(db, path.as_str(), text);
+
+        let mut root = SourceRoot::new(path, file);
+        for &outer in &self.items {
+            root.push_outer(db.lookup_intern_path(outer).as_str(), outer);
+        }
+        prelude::load(db, &mut root);
+        db.set_source_root(Arc::new(root));
+
+        let parsed = Parser::new(&self.driver.db, file).parse_input();
+        self.driver
+            .emit_all(parsed.errors.into_iter().map(Diagnostic::from))?;
+        Ok((file, parsed.input))
+    }
+
+    /// Checks the items of an input and keeps them for every later input.
+    fn define(&mut self, file: FileId, items: &[ItemId]) -> Result<(), Failed> {
+        let db = &self.driver.db;
+        let mut errors = db.check_file(file).to_vec();
+
+        for &item in items {
+            let item = db.lookup_intern_item(item);
+            let name = match item.kind.name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let previous = self.items.iter().find_map(|&outer| {
+                let scope = db.file_scope(outer);
+                namespace(&scope, &item.kind).get(&name.id).copied()
+            });
+            if let Some(previous) = previous {
+                let previous = db.lookup_intern_item(previous);
+                let err = TypeError::DuplicateDefinition {
+                    name: db.rodeo().resolve(&name.id).to_string(),
+                    previous_file: previous.file,
+                    previous: previous.kind.name().unwrap().span,
+                };
+                errors.push(Locatable::new(err.into(), file, name.span));
+            }
+        }
+
+        self.driver
+            .emit_all(errors.into_iter().map(Diagnostic::from))?;
+        self.items.push(file);
+        Ok(())
+    }
+
+    /// Evaluates the statements of an input and prints the value of the last one.
+    fn eval(&mut self, file: FileId, stmts: Vec<StmtId>) -> Result<(), Failed> {
+        let (def, results) = self.body(file, &stmts)?;
+        let env = self.vars.iter().map(|var| var.ptr.clone()).collect();
+
+        let snapshot = self.driver.db.snapshot();
+        let state = std::mem::take(&mut self.state);
+        let body = Arc::clone(&results);
+        let (result, state) = with_stack(move || {
+            let mut interp = Interpreter::with_state(&*snapshot, state, io::stdout());
+            let result = interp.eval_body(def, body, env);
+            (result, interp.into_state())
+        });
+        self.state = state;
+        let (val, locals) = result.map_err(|err| self.driver.located_error(err))?;
+
+        // the variables of this input shadow the earlier ones with the same name
+        for stmt in &stmts {
+            if let Some(&id) = results.vars.get(stmt) {
+                let local = results.local(id);
+                self.vars.retain(|var| var.name != local.name);
+                self.vars.push(Variable {
+                    name: local.name,
+                    ty: local.ty,
+                    ptr: locals[id.0 as usize].clone(),
+                });
+            }
+        }
+
+        if val != Value::unit() {
+            println!("{}", val.display(&self.driver.db));
+        }
+        Ok(())
+    }
+
+    /// Prints the type of an expression, without evaluating it.
+    fn type_of(&mut self, text: &str) -> Result<(), Failed> {
+        let (file, stmt, expr) = self.parse_expr(":type", text)?;
+        let (_, results) = self.body(file, &[stmt])?;
+        let db = &self.driver.db;
+        println!("{}", results.type_of(expr).display(db));
+        Ok(())
+    }
+
+    /// Prints the syntax tree of an expression.
+    fn ast(&mut self, text: &str) -> Result<(), Failed> {
+        let (_, _, expr) = self.parse_expr(":ast", text)?;
+        print!("{}", ast::expr(&self.driver.db, expr));
+        Ok(())
+    }
+
+    /// Parses the argument of a command, which has to be a single expression.
+    fn parse_expr(
+        &mut self,
+        command: &str,
+        text: &str,
+    ) -> Result<(FileId, StmtId, ExprId), Failed> {
+        let (file, input) = self.parse(text.trim())?;
+        if let Input::Stmts(stmts) = input {
+            if let [stmt] = stmts[..] {
+                if let StmtKind::Expr(expr) = self.driver.db.lookup_intern_stmt(stmt).kind {
+                    return Ok((file, stmt, expr));
+                }
+            }
+        }
+        Err(self
+            .driver
+            .error(format!("`{}` expects a single expression", command)))
+    }
+
+    /// Creates a function whose body are the statements of the input,
+    /// type checks it and reports its errors.
+    ///
+    /// The function is part of `file`, so it can use everything the
+    /// current input can use, and the variables of earlier inputs
+    /// are its first locals.
+    fn body(
+        &mut self,
+        file: FileId,
+        stmts: &[StmtId],
+    ) -> Result<(ItemId, Arc<TypeckResults>), Failed> {
+        let db = &self.driver.db;
+        let span = Span::new(0, db.file_text(file).len() as u32);
+        let name = Identifier {
+            span,
+            id: db.rodeo().get_or_intern("<repl>"),
+        };
+        let body = Block {
+            span,
+            stmts: stmts.to_vec(),
+        };
+        let def = db.intern_item(Item {
+            span,
+            file,
+            vis: Visibility::Private,
+            kind: ItemKind::Def(Def {
+                name,
+                generics: vec![],
+                args: vec![],
+                return_ty: db.intern_type(Type::Unit),
+                body: Some(body),
+            }),
+        });
+
+        let vars = self
+            .vars
+            .iter()
+            .map(|var| (var.name, var.ty))
+            .collect::<Vec<_>>();
+        let results = Arc::new(infer::typeck_with_locals(db, def, &vars));
+        self.driver
+            .emit_all(results.errors.iter().cloned().map(Diagnostic::from))?;
+        Ok((def, results))
+    }
+}
+
+/// Returns the names of `scope` that an item of the given kind is defined in.
+fn namespace<'a>(scope: &'a FileScope, kind: &ItemKind) -> &'a HashMap<StringId, ItemId> {
+    match kind {
+        ItemKind::Def(_) | ItemKind::Const(_) | ItemKind::Static(_) => &scope.values,
+        ItemKind::Import(_) => &scope.imports,
+        ItemKind::Use(_) => &scope.uses,
+        _ => &scope.types,
+    }
+}
+
+fn prompt(prompt: &str) {
+    print!("{}", prompt);
+    io::stdout().flush().expect("failed to write to stdout");
+}
+
+/// Checks if every bracket in the input is closed, otherwise
+/// the input continues on the next line.
+fn is_complete(input: &str) -> bool {
+    let depth = token::lex(input)
+        .iter()
+        .fold(0i32, |depth, token| match token.kind {
+            Kind::LeftParen | Kind::LeftCurly | Kind::LeftBracket => depth + 1,
+            Kind::RightParen | Kind::RightCurly | Kind::RightBracket => depth - 1,
+            _ => depth,
+        });
+    depth <= 0
+}
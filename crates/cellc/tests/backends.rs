@@ -0,0 +1,159 @@
+//! Runs the programs in `tests/programs` with every backend and checks
+//! that they print the same as `cellc run`, which uses the VM.
+//!
+//! A program that fails at runtime has to fail with the same error on every
+//! backend, after printing the same output. The C backend is skipped if there's
+//! no C compiler, and the wasm backend is skipped if `node` is missing.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+const CELLC: &str = env!("CARGO_BIN_EXE_cellc");
+const HOST: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../cell_codegen_wasm/src/host.js"
+);
+
+/// What a program printed and how it ended.
+#[derive(Debug, PartialEq, Eq)]
+struct Run {
+    stdout: String,
+    /// The message of the runtime error, if the program failed.
+    error: Option<String>,
+}
+
+impl Run {
+    /// Reads the result of a run, where `prefix` starts the line
+    /// of the error message on stderr.
+    fn new(output: Output, prefix: &str) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let error = if output.status.success() {
+            None
+        } else {
+            let message = stderr
+                .lines()
+                .find_map(|line| line.strip_prefix(prefix))
+                .unwrap_or_else(|| panic!("no error message in:\n{}", stderr));
+            Some(message.to_owned())
+        };
+        Self {
+            stdout: String::from_utf8(output.stdout).expect("output is not UTF-8"),
+            error,
+        }
+    }
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut programs = fs::read_dir(dir)
+        .expect("failed to read `tests/programs`")
+        .map(|entry| entry.expect("failed to read `tests/programs`").path())
+        .filter(|path| path.extension() == Some("cell".as_ref()))
+        .collect::<Vec<_>>();
+    programs.sort();
+    assert!(!programs.is_empty());
+    programs
+}
+
+/// Runs `program` with `cellc run --backend <backend>`.
+fn run(backend: &str, level: &str, program: &Path) -> Run {
+    let output = Command::new(CELLC)
+        .args(["run", "--backend", backend, level])
+        .arg(program)
+        .output()
+        .expect("failed to run `cellc`");
+    Run::new(output, "error[E0300]: ")
+}
+
+/// Builds `program` with `cellc build --emit <emit>` and returns the output file.
+fn build(emit: &str, level: &str, program: &Path) -> PathBuf {
+    let name = program.file_stem().unwrap().to_string_lossy();
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "{}{}.{}",
+        name,
+        level,
+        if emit == "exe" { "out" } else { emit }
+    ));
+    let result = Command::new(CELLC)
+        .args(["build", "--emit", emit, level, "-o"])
+        .arg(&output)
+        .arg(program)
+        .output()
+        .expect("failed to run `cellc`");
+    assert!(
+        result.status.success(),
+        "failed to build `{}`:\n{}",
+        program.display(),
+        String::from_utf8_lossy(&result.stderr)
+    );
+    output
+}
+
+/// Runs every program with `f` and compares the result with the VM.
+fn compare(f: impl Fn(&str, &Path) -> Run) {
+    for program in programs() {
+        let expected = run("vm", "-O0", &program);
+        for level in ["-O0", "-O2"] {
+            let found = f(level, &program);
+            assert_eq!(
+                expected,
+                found,
+                "`{}` differs with {}",
+                program.display(),
+                level
+            );
+        }
+    }
+}
+
+fn has_command(name: &str, arg: &str) -> bool {
+    let found = Command::new(name).arg(arg).output().is_ok();
+    if !found {
+        eprintln!("skipped, because `{}` was not found", name);
+    }
+    found
+}
+
+#[test]
+fn vm() {
+    compare(|level, program| run("vm", level, program));
+}
+
+#[test]
+fn interp() {
+    compare(|level, program| run("interp", level, program));
+}
+
+#[test]
+fn c() {
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    if !has_command(&cc, "--version") {
+        return;
+    }
+    compare(|level, program| {
+        let exe = build("exe", level, program);
+        let output = Command::new(&exe)
+            .output()
+            .expect("failed to run the executable");
+        Run::new(output, "error: runtime: ")
+    });
+}
+
+#[test]
+fn wasm() {
+    if !has_command("node", "--version") {
+        return;
+    }
+    compare(|level, program| {
+        let wasm = build("wasm", level, program);
+        let output = Command::new("node")
+            .arg(HOST)
+            .arg(&wasm)
+            .output()
+            .expect("failed to run `node`");
+        Run::new(output, "error: runtime: ")
+    });
+}
@@ -0,0 +1,78 @@
+//! Tests of the commands that print the tokens, the syntax tree
+//! and the layouts of a program.
+
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+const CELLC: &str = env!("CARGO_BIN_EXE_cellc");
+
+fn program(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/programs")
+        .join(name);
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn parse_resolves_names() {
+    let output = Command::new(CELLC)
+        .args(["parse", &program("fib.cell")])
+        .output()
+        .expect("failed to run `cellc`");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Def fib\n  Param n: "), "{}", stdout);
+    assert!(stdout.contains("\nDef main\n"), "{}", stdout);
+    assert!(!stdout.contains("Id("), "{}", stdout);
+}
+
+/// Checks that `cellc lex f.cell | head` and the like don't fail
+/// when the reader closes the pipe before everything was written.
+#[test]
+fn closed_pipe() {
+    for command in ["lex", "parse"] {
+        let mut child = Command::new(CELLC)
+            .args([command, &program("unions.cell")])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to run `cellc`");
+        drop(child.stdout.take());
+
+        let output = child.wait_with_output().unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            output.status.success(),
+            "`cellc {}` failed:\n{}",
+            command,
+            stderr
+        );
+        assert!(stderr.is_empty(), "{}", stderr);
+    }
+}
+
+/// Generic types are only laid out for the arguments they're used
+/// with, like `Opt[*i32]`, which uses a null pointer as its niche.
+#[test]
+fn emit_layout_instances() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("niches.layout");
+    let result = Command::new(CELLC)
+        .args(["build", "--emit", "layout", "-o"])
+        .arg(&output)
+        .arg(program("niches.cell"))
+        .output()
+        .expect("failed to run `cellc`");
+    assert!(result.status.success());
+
+    let layouts = std::fs::read_to_string(&output).unwrap();
+    let expected = "\
+union Opt[*i32]: size 8, align 8
+    0..8    Some = *i32
+    None is stored as null at offset 0
+";
+    assert!(layouts.contains(expected), "{}", layouts);
+    assert!(layouts.starts_with("type Pair: "), "{}", layouts);
+}
@@ -0,0 +1,189 @@
+//! Documents that are laid out to fit into a maximum line width.
+//!
+//! The layout algorithm is the one from Wadler's "A prettier printer":
+//! a group is printed on a single line if it fits into the remaining width,
+//! otherwise every line break that belongs to the group becomes a newline.
+
+/// The number of spaces that are used for one level of indentation.
+const INDENT: usize = 4;
+
+#[derive(Debug, Clone)]
+pub enum Doc {
+    Text(String),
+    /// A space if the group is printed on one line, a newline otherwise.
+    Line,
+    /// Nothing if the group is printed on one line, a newline otherwise.
+    SoftLine,
+    /// Always a newline. A group that contains a hard line never fits on one line.
+    HardLine,
+    /// A newline, unless nothing was printed on the current line yet.
+    FreshLine,
+    /// Indents every line of the document by one more level.
+    Indent(Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+    /// Text that is only printed if the group is broken into multiple lines,
+    /// which is used for trailing commas.
+    IfBreak(&'static str),
+    /// Forces every group that contains it to be broken into multiple lines.
+    BreakParent,
+    /// A comment at the end of a line. It's not counted when checking if the
+    /// line fits, but a group that contains it is always broken.
+    Trailing(String),
+}
+
+impl Doc {
+    pub fn nil() -> Doc {
+        Doc::Concat(vec![])
+    }
+
+    pub fn text(text: impl Into<String>) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    pub fn indent(doc: Doc) -> Doc {
+        Doc::Indent(Box::new(doc))
+    }
+
+    pub fn group(doc: Doc) -> Doc {
+        Doc::Group(Box::new(doc))
+    }
+}
+
+impl From<&str> for Doc {
+    fn from(text: &str) -> Self {
+        Doc::text(text)
+    }
+}
+
+impl From<Vec<Doc>> for Doc {
+    fn from(docs: Vec<Doc>) -> Self {
+        Doc::Concat(docs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Lays out the document so that no line is longer than `width`, if possible.
+pub fn print(doc: &Doc, width: usize) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        col: 0,
+        indent: Some(0),
+        width,
+    };
+    printer.print(doc);
+    printer.out
+}
+
+struct Printer {
+    out: String,
+    /// The column that the next text is printed at.
+    col: usize,
+    /// The indentation that still has to be printed on the current line.
+    /// Indentation is printed lazily, so empty lines have no trailing whitespace.
+    indent: Option<usize>,
+    width: usize,
+}
+
+impl Printer {
+    fn print(&mut self, doc: &Doc) {
+        let mut stack = vec![(0, Mode::Break, doc)];
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => self.text(text),
+                Doc::Trailing(text) => {
+                    let len = self.out.trim_end_matches(' ').len();
+                    self.out.truncate(len);
+                    self.text(text);
+                }
+                Doc::Line if mode == Mode::Flat => self.text(" "),
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => self.newline(indent),
+                Doc::FreshLine => {
+                    if self.indent.is_none() {
+                        self.newline(indent);
+                    }
+                }
+                Doc::Indent(doc) => stack.push((indent + INDENT, mode, doc)),
+                Doc::Group(doc) => {
+                    let flat = mode == Mode::Flat
+                        || fits(doc, &stack, self.width as isize - self.col as isize);
+                    let mode = if flat { Mode::Flat } else { Mode::Break };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+                Doc::IfBreak(text) => {
+                    if mode == Mode::Break {
+                        self.text(text);
+                    }
+                }
+                Doc::BreakParent => {}
+            }
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(indent) = self.indent.take() {
+            self.out.extend(std::iter::repeat_n(' ', indent));
+        }
+        self.out.push_str(text);
+        self.col = match text.rfind('\n') {
+            Some(idx) => text[idx + 1..].chars().count(),
+            None => self.col + text.chars().count(),
+        };
+    }
+
+    fn newline(&mut self, indent: usize) {
+        let len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(len);
+        self.out.push('\n');
+        self.indent = Some(indent);
+        self.col = indent;
+    }
+}
+
+/// Checks if the document fits into the remaining width if it's printed
+/// on one line, together with everything up to the next line break after it.
+fn fits(doc: &Doc, rest: &[(usize, Mode, &Doc)], mut remaining: isize) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        if remaining < 0 {
+            return false;
+        }
+
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => remaining -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine => return true,
+            Doc::HardLine | Doc::FreshLine | Doc::Trailing(_) => return mode == Mode::Break,
+            Doc::BreakParent => {
+                if mode == Mode::Flat {
+                    return false;
+                }
+            }
+            Doc::Indent(doc) | Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+            Doc::IfBreak(text) => {
+                if mode == Mode::Break {
+                    remaining -= text.len() as isize;
+                }
+            }
+        }
+    }
+}
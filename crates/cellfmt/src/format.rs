@@ -0,0 +1,338 @@
+//! Formatting of a single file.
+//!
+//! The layout of the output comes from the syntax tree, while the text comes
+//! from the tokens of the file. The formatter walks both at the same time, so
+//! literals, comments and parentheses are printed exactly like they are written.
+
+mod expr;
+mod item;
+mod pattern;
+mod ty;
+
+use crate::doc::{self, Doc};
+use cell_common::{
+    error::Error,
+    source::{FileId, Locatable},
+};
+use cell_parser::{
+    db::ParseDatabase,
+    token::{Kind, Token},
+};
+use cell_syntax::ast::{ItemId, ItemKind};
+use std::{ops::Range, sync::Arc};
+
+/// Why a file could not be formatted.
+#[derive(Debug)]
+pub enum FormatError {
+    /// The file contains syntax errors.
+    Syntax(Vec<Locatable<Error>>),
+    /// The tokens of the file don't match its syntax tree,
+    /// which is a bug in the formatter.
+    Mismatch,
+}
+
+/// Formats the file, so that no line is longer than `width` if possible.
+pub fn format(db: &dyn ParseDatabase, file: FileId, width: usize) -> Result<String, FormatError> {
+    let parsed = db.parse(file);
+    if !parsed.errors.is_empty() {
+        return Err(FormatError::Syntax(parsed.errors.clone()));
+    }
+
+    let mut formatter = Formatter {
+        db,
+        source: db.file_text(file),
+        tokens: db.lex(file),
+        pos: 0,
+        prev_end: 0,
+        split_index: false,
+        failed: false,
+    };
+    let doc = formatter.file(&parsed.items);
+    if formatter.failed || formatter.pos != formatter.tokens.len() {
+        return Err(FormatError::Mismatch);
+    }
+    Ok(doc::print(&doc, width))
+}
+
+/// How the elements of a comma separated list are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    /// `{a, b}` on one line if it fits.
+    Compact,
+    /// `{ a, b }` on one line if it fits.
+    Padded,
+    /// Every element on its own line.
+    Broken,
+}
+
+struct Formatter<'db> {
+    db: &'db dyn ParseDatabase,
+    source: Arc<String>,
+    /// Every token of the file, including comments.
+    tokens: Vec<Token>,
+    pos: usize,
+    /// The end of the last token that was printed.
+    prev_end: usize,
+    /// `tuple.0.1` is lexed as a single float token after the first dot,
+    /// so both indices are printed by the inner field access.
+    split_index: bool,
+    /// Set if a token didn't match the syntax tree.
+    failed: bool,
+}
+
+impl Formatter<'_> {
+    fn file(&mut self, items: &[ItemId]) -> Doc {
+        let db = self.db;
+        let blank = |a: &ItemId, b: &ItemId| !is_short(db, *a) || !is_short(db, *b);
+        match self.lines(items, blank, |this, &item| this.item(item)) {
+            Some(doc) => Doc::Concat(vec![doc, Doc::HardLine]),
+            None => Doc::nil(),
+        }
+    }
+    fn text(&self, token: Token) -> &str {
+        &self.source[Range::<usize>::from(token.span)]
+    }
+
+    /// Returns the next token that is not a comment.
+    fn peek(&self) -> Option<Token> {
+        self.tokens[self.pos..]
+            .iter()
+            .find(|token| token.kind != Kind::Comment)
+            .copied()
+    }
+
+    fn at(&self, kind: Kind) -> bool {
+        self.peek().is_some_and(|token| token.kind == kind)
+    }
+
+    fn at_comment(&self) -> bool {
+        self.tokens
+            .get(self.pos)
+            .is_some_and(|token| token.kind == Kind::Comment)
+    }
+
+    /// Returns the number of newlines between the last printed token and the next token.
+    fn newlines(&self) -> usize {
+        let end = self
+            .tokens
+            .get(self.pos)
+            .map_or(self.source.len(), |token| usize::from(token.span.start()));
+        self.source[self.prev_end..end].matches('\n').count()
+    }
+
+    fn advance(&mut self) -> Doc {
+        let token = self.tokens[self.pos];
+        self.pos += 1;
+        self.prev_end = usize::from(token.span.end());
+        Doc::text(self.text(token))
+    }
+
+    /// Prints the comment that is the next token.
+    fn comment(&mut self) -> Doc {
+        self.advance()
+    }
+
+    /// Prints the comments in front of the next token.
+    ///
+    /// This is only used for comments in unusual places, like inside an expression.
+    /// Comments between items, statements and list elements are handled by
+    /// `lines` and `list`, which keep them on the line they were written on.
+    fn comments(&mut self) -> Doc {
+        let mut docs = vec![];
+        while self.at_comment() {
+            if let Some(comment) = self.trailing_comment() {
+                docs.push(comment);
+            } else {
+                docs.push(Doc::FreshLine);
+                docs.push(self.comment());
+            }
+            docs.push(Doc::HardLine);
+        }
+        Doc::Concat(docs)
+    }
+
+    /// Prints the next token, which must be of the given kind.
+    fn token(&mut self, kind: Kind) -> Doc {
+        let comments = self.comments();
+        if self.at(kind) {
+            Doc::Concat(vec![comments, self.advance()])
+        } else {
+            self.failed = true;
+            comments
+        }
+    }
+
+    /// Prints the next token, whatever it is.
+    fn any_token(&mut self) -> Doc {
+        let comments = self.comments();
+        if self.pos < self.tokens.len() {
+            Doc::Concat(vec![comments, self.advance()])
+        } else {
+            self.failed = true;
+            comments
+        }
+    }
+
+    /// Skips the next token if it is of the given kind,
+    /// which is used for optional tokens that are printed by the formatter itself.
+    fn skip(&mut self, kind: Kind) -> Doc {
+        if !self.at(kind) {
+            return Doc::nil();
+        }
+        let comments = self.comments();
+        self.advance();
+        comments
+    }
+
+    fn ident(&mut self) -> Doc {
+        self.token(Kind::Identifier)
+    }
+
+    /// Prints the comment that follows the last token on the same line.
+    fn trailing_comment(&mut self) -> Option<Doc> {
+        if !self.at_comment() || self.newlines() > 0 {
+            return None;
+        }
+        let comment = self.tokens[self.pos];
+        self.advance();
+        Some(Doc::Trailing(format!(" {}", self.text(comment))))
+    }
+
+    /// Puts every element on its own line, together with the comments between them.
+    ///
+    /// A blank line between two elements is kept, and `blank` decides
+    /// if there must be a blank line between two elements.
+    /// Returns `None` if there were neither elements nor comments.
+    fn lines<T>(
+        &mut self,
+        elems: &[T],
+        blank: impl Fn(&T, &T) -> bool,
+        mut f: impl FnMut(&mut Self, &T) -> Doc,
+    ) -> Option<Doc> {
+        let mut docs = vec![];
+        for (idx, elem) in elems.iter().enumerate() {
+            let mut blank = idx > 0 && blank(&elems[idx - 1], elem);
+            while self.at_comment() {
+                self.separate(&mut docs, blank);
+                blank = false;
+                docs.push(self.comment());
+            }
+            self.separate(&mut docs, blank);
+            docs.push(f(self, elem));
+            docs.extend(self.trailing_comment());
+        }
+        while self.at_comment() {
+            self.separate(&mut docs, false);
+            docs.push(self.comment());
+        }
+
+        if docs.is_empty() {
+            None
+        } else {
+            Some(Doc::Concat(docs))
+        }
+    }
+
+    /// Starts a new line, unless nothing was printed yet.
+    fn separate(&self, docs: &mut Vec<Doc>, blank: bool) {
+        if !docs.is_empty() {
+            docs.push(Doc::HardLine);
+            if blank || self.newlines() > 1 {
+                docs.push(Doc::HardLine);
+            }
+        }
+    }
+
+    /// Prints a comma separated list that is surrounded by `open` and `close`.
+    ///
+    /// If the list doesn't fit on one line, every element is put on its
+    /// own line and the last element gets a trailing comma.
+    fn list<T>(
+        &mut self,
+        open: Kind,
+        close: Kind,
+        elems: &[T],
+        style: Style,
+        mut f: impl FnMut(&mut Self, &T) -> Doc,
+    ) -> Doc {
+        let open = self.token(open);
+        let edge = match style {
+            Style::Padded => Doc::Line,
+            Style::Compact | Style::Broken => Doc::SoftLine,
+        };
+
+        let mut inner = vec![];
+        inner.extend(self.trailing_comment());
+        for (idx, elem) in elems.iter().enumerate() {
+            inner.push(if idx == 0 { edge.clone() } else { Doc::Line });
+            if idx > 0 && style == Style::Broken && self.newlines() > 1 {
+                inner.push(Doc::HardLine);
+            }
+            while self.at_comment() {
+                inner.push(self.comment());
+                inner.push(Doc::HardLine);
+            }
+            inner.push(f(self, elem));
+            inner.push(self.skip(Kind::Comma));
+            inner.push(if idx + 1 == elems.len() {
+                Doc::IfBreak(",")
+            } else {
+                Doc::text(",")
+            });
+            inner.extend(self.trailing_comment());
+        }
+        while self.at_comment() {
+            inner.push(if elems.is_empty() {
+                edge.clone()
+            } else {
+                Doc::Line
+            });
+            inner.push(self.comment());
+            inner.push(Doc::BreakParent);
+        }
+
+        let close = self.token(close);
+        if inner.is_empty() {
+            return Doc::Concat(vec![open, close]);
+        }
+        if style == Style::Broken {
+            inner.push(Doc::BreakParent);
+        }
+        Doc::group(Doc::Concat(vec![
+            open,
+            Doc::indent(Doc::Concat(inner)),
+            edge,
+            close,
+        ]))
+    }
+}
+
+/// Puts the body between the braces on their own lines, or prints `{}` if it's empty.
+fn braced(open: Doc, trailing: Option<Doc>, body: Option<Doc>, close: Doc) -> Doc {
+    let mut docs = vec![open];
+    let empty = trailing.is_none() && body.is_none();
+    docs.extend(trailing);
+    if let Some(body) = body {
+        docs.push(Doc::indent(Doc::Concat(vec![Doc::HardLine, body])));
+    }
+    if !empty {
+        docs.push(Doc::HardLine);
+    }
+    docs.push(close);
+    Doc::Concat(docs)
+}
+
+/// Checks if the item is usually written on a single line.
+///
+/// There is always a blank line between two items, unless both of them are short.
+fn is_short(db: &dyn ParseDatabase, item: ItemId) -> bool {
+    match db.lookup_intern_item(item).kind {
+        ItemKind::Import(_)
+        | ItemKind::Use(_)
+        | ItemKind::Const(_)
+        | ItemKind::Static(_)
+        | ItemKind::Alias(_) => true,
+        ItemKind::Def(def) => def.body.is_none(),
+        _ => false,
+    }
+}
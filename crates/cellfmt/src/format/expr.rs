@@ -0,0 +1,251 @@
+//! Formatting of statements and expressions.
+
+use super::{braced, Formatter, Style};
+use crate::doc::Doc;
+use cell_parser::token::Kind;
+use cell_syntax::ast::{Block, ExprId, ExprKind, FieldName, Literal, Path, StmtId, StmtKind};
+
+impl Formatter<'_> {
+    pub(super) fn block(&mut self, block: &Block) -> Doc {
+        let open = self.token(Kind::LeftCurly);
+        let trailing = self.trailing_comment();
+        let body = self.lines(&block.stmts, |_, _| false, |this, &stmt| this.stmt(stmt));
+        let close = self.token(Kind::RightCurly);
+        braced(open, trailing, body, close)
+    }
+
+    fn stmt(&mut self, id: StmtId) -> Doc {
+        match self.db.lookup_intern_stmt(id).kind {
+            StmtKind::Var(var) => {
+                let mut docs = vec![self.token(Kind::Var), Doc::text(" "), self.ident()];
+                if let Some(ty) = &var.ty {
+                    docs.push(self.token(Kind::Colon));
+                    docs.push(Doc::text(" "));
+                    docs.push(self.type_(ty));
+                }
+                docs.push(Doc::text(" "));
+                docs.push(self.token(Kind::Equal));
+                docs.push(self.operand(var.val));
+                docs.push(self.token(Kind::Semicolon));
+                Doc::Concat(docs)
+            }
+            StmtKind::Expr(expr) => {
+                let block_like = matches!(
+                    self.db.lookup_intern_expr(expr).kind,
+                    ExprKind::If(_) | ExprKind::Match(_) | ExprKind::While(_) | ExprKind::Block(_)
+                );
+                let doc = self.expr(expr);
+                // the `;` after a block is optional, and left out
+                let end = if block_like {
+                    self.skip(Kind::Semicolon)
+                } else {
+                    self.token(Kind::Semicolon)
+                };
+                Doc::Concat(vec![doc, end])
+            }
+        }
+    }
+
+    pub(super) fn expr(&mut self, id: ExprId) -> Doc {
+        let expr = self.db.lookup_intern_expr(id);
+
+        // parentheses are not part of the syntax tree, but every `(` in front
+        // of the expression belongs to it
+        let mut parens = 0;
+        let mut docs = vec![];
+        while self.peek().is_some_and(|token| {
+            token.kind == Kind::LeftParen && token.span.start() < expr.span.start()
+        }) {
+            docs.push(self.token(Kind::LeftParen));
+            parens += 1;
+        }
+
+        let doc = match &expr.kind {
+            ExprKind::Literal(Literal::Unit) => Doc::Concat(vec![
+                self.token(Kind::LeftParen),
+                self.token(Kind::RightParen),
+            ]),
+            ExprKind::Literal(_) => self.any_token(),
+            ExprKind::Binary(binary) => self.infix(binary.left, binary.right),
+            ExprKind::Comparison(cmp) => self.infix(cmp.left, cmp.right),
+            ExprKind::Unary(unary) => Doc::Concat(vec![self.any_token(), self.expr(unary.val)]),
+            ExprKind::Tuple(tuple) => self.list(
+                Kind::LeftCurly,
+                Kind::RightCurly,
+                &tuple.values,
+                Style::Compact,
+                |this, &val| this.expr(val),
+            ),
+            ExprKind::NamedTuple(tuple) => self.list(
+                Kind::LeftCurly,
+                Kind::RightCurly,
+                &tuple.values,
+                Style::Compact,
+                |this, &(_, val)| {
+                    Doc::Concat(vec![
+                        this.ident(),
+                        this.token(Kind::Colon),
+                        Doc::text(" "),
+                        this.expr(val),
+                    ])
+                },
+            ),
+            ExprKind::If(if_) => {
+                let mut docs = vec![
+                    self.token(Kind::If),
+                    Doc::text(" "),
+                    self.expr(if_.cond),
+                    Doc::text(" "),
+                    self.block(&if_.then),
+                ];
+                for arm in &if_.arms {
+                    docs.push(Doc::text(" "));
+                    docs.push(self.token(Kind::Else));
+                    docs.push(Doc::text(" "));
+                    docs.push(self.token(Kind::If));
+                    docs.push(Doc::text(" "));
+                    docs.push(self.expr(arm.cond));
+                    docs.push(Doc::text(" "));
+                    docs.push(self.block(&arm.action));
+                }
+                if let Some(else_) = &if_.else_ {
+                    docs.push(Doc::text(" "));
+                    docs.push(self.token(Kind::Else));
+                    docs.push(Doc::text(" "));
+                    docs.push(self.block(else_));
+                }
+                Doc::Concat(docs)
+            }
+            ExprKind::Match(match_) => Doc::Concat(vec![
+                self.token(Kind::Match),
+                Doc::text(" "),
+                self.expr(match_.val),
+                Doc::text(" "),
+                self.list(
+                    Kind::LeftCurly,
+                    Kind::RightCurly,
+                    &match_.arms,
+                    Style::Broken,
+                    |this, arm| {
+                        Doc::Concat(vec![
+                            this.pattern(arm.pattern),
+                            Doc::text(" "),
+                            this.token(Kind::ThinArrow),
+                            this.operand(arm.body),
+                        ])
+                    },
+                ),
+            ]),
+            ExprKind::While(while_) => {
+                let mut docs = vec![self.token(Kind::While)];
+                if let Some(cond) = while_.cond {
+                    docs.push(Doc::text(" "));
+                    docs.push(self.expr(cond));
+                }
+                docs.push(Doc::text(" "));
+                docs.push(self.block(&while_.block));
+                Doc::Concat(docs)
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Path(path) => self.path(path),
+            ExprKind::Call(call) => {
+                Doc::Concat(vec![self.expr(call.callee), self.args(&call.args)])
+            }
+            ExprKind::MethodCall(call) => Doc::Concat(vec![
+                self.expr(call.receiver),
+                self.token(Kind::Dot),
+                self.ident(),
+                self.args(&call.args),
+            ]),
+            ExprKind::Field(field) => {
+                let val = self.expr(field.val);
+                match &field.field {
+                    FieldName::Named(_) => {
+                        Doc::Concat(vec![val, self.token(Kind::Dot), self.ident()])
+                    }
+                    FieldName::Index(_) if self.split_index => {
+                        self.split_index = false;
+                        val
+                    }
+                    FieldName::Index(_) => {
+                        let dot = self.token(Kind::Dot);
+                        let index = if self.at(Kind::Float) {
+                            self.split_index = true;
+                            self.token(Kind::Float)
+                        } else {
+                            self.token(Kind::Integer)
+                        };
+                        Doc::Concat(vec![val, dot, index])
+                    }
+                }
+            }
+            ExprKind::Assign(assign) => Doc::Concat(vec![
+                self.expr(assign.target),
+                Doc::text(" "),
+                self.any_token(),
+                self.operand(assign.val),
+            ]),
+            ExprKind::Return(val) => {
+                let mut docs = vec![self.token(Kind::Return)];
+                if let Some(val) = val {
+                    docs.push(Doc::text(" "));
+                    docs.push(self.expr(*val));
+                }
+                Doc::Concat(docs)
+            }
+            ExprKind::Break => self.token(Kind::Break),
+            ExprKind::Continue => self.token(Kind::Continue),
+            ExprKind::This => self.token(Kind::This),
+        };
+
+        docs.push(doc);
+        for _ in 0..parens {
+            docs.push(self.token(Kind::RightParen));
+        }
+        Doc::Concat(docs)
+    }
+
+    /// Prints a binary operator, which breaks the line in front of the operator.
+    fn infix(&mut self, left: ExprId, right: ExprId) -> Doc {
+        let left = self.expr(left);
+        let op = self.any_token();
+        let right = self.expr(right);
+        Doc::group(Doc::Concat(vec![
+            left,
+            Doc::indent(Doc::Concat(vec![Doc::Line, op, Doc::text(" "), right])),
+        ]))
+    }
+
+    fn args(&mut self, args: &[ExprId]) -> Doc {
+        self.list(
+            Kind::LeftParen,
+            Kind::RightParen,
+            args,
+            Style::Compact,
+            |this, &arg| this.expr(arg),
+        )
+    }
+
+    /// Prints the expression after a `=` or `->`, separated by a space. A comment
+    /// after the operator moves the expression to the next line, one level deeper.
+    fn operand(&mut self, expr: ExprId) -> Doc {
+        match self.trailing_comment() {
+            Some(comment) => Doc::Concat(vec![
+                comment,
+                Doc::indent(Doc::Concat(vec![Doc::HardLine, self.expr(expr)])),
+            ]),
+            None => Doc::Concat(vec![Doc::text(" "), self.expr(expr)]),
+        }
+    }
+
+    pub(super) fn path(&mut self, path: &Path) -> Doc {
+        let mut docs = vec![];
+        for idx in 0..path.segments.len() {
+            if idx > 0 {
+                docs.push(self.token(Kind::Colon));
+            }
+            docs.push(self.ident());
+        }
+        Doc::Concat(docs)
+    }
+}
@@ -0,0 +1,210 @@
+//! Formatting of items.
+
+use super::{braced, is_short, Formatter, Style};
+use crate::doc::Doc;
+use cell_parser::token::Kind;
+use cell_syntax::ast::{Generic, ItemId, ItemKind};
+
+impl Formatter<'_> {
+    pub(super) fn item(&mut self, id: ItemId) -> Doc {
+        let item = self.db.lookup_intern_item(id);
+        let mut docs = vec![];
+        if self.at(Kind::At) {
+            docs.push(self.token(Kind::At));
+            docs.push(self.ident());
+            docs.push(self.token(Kind::LeftParen));
+            docs.push(self.ident());
+            docs.push(self.token(Kind::RightParen));
+            docs.push(Doc::HardLine);
+        }
+        docs.push(self.visibility());
+
+        match &item.kind {
+            ItemKind::TypeDef(def) => {
+                docs.push(self.token(Kind::Type));
+                docs.push(Doc::text(" "));
+                docs.push(self.ident());
+                docs.push(self.generics(&def.generics));
+                docs.push(Doc::text(" "));
+                docs.push(self.list(
+                    Kind::LeftCurly,
+                    Kind::RightCurly,
+                    &def.fields,
+                    Style::Broken,
+                    |this, field| {
+                        Doc::Concat(vec![
+                            this.visibility(),
+                            this.ident(),
+                            this.token(Kind::Colon),
+                            Doc::text(" "),
+                            this.ty(field.ty),
+                        ])
+                    },
+                ));
+            }
+            ItemKind::Def(def) => {
+                docs.push(self.token(Kind::Def));
+                docs.push(Doc::text(" "));
+                docs.push(self.ident());
+                docs.push(self.generics(&def.generics));
+                docs.push(self.list(
+                    Kind::LeftParen,
+                    Kind::RightParen,
+                    &def.args,
+                    Style::Compact,
+                    |this, &(_, ty)| {
+                        Doc::Concat(vec![
+                            this.ident(),
+                            this.token(Kind::Colon),
+                            Doc::text(" "),
+                            this.ty(ty),
+                        ])
+                    },
+                ));
+                // a missing return type is `()`, which has no tokens
+                if self.at(Kind::Colon) {
+                    docs.push(self.token(Kind::Colon));
+                    docs.push(Doc::text(" "));
+                    docs.push(self.ty(def.return_ty));
+                }
+                match &def.body {
+                    Some(body) => {
+                        docs.push(Doc::text(" "));
+                        docs.push(self.block(body));
+                    }
+                    None => docs.push(self.token(Kind::Semicolon)),
+                }
+            }
+            ItemKind::Alias(alias) => {
+                docs.push(self.token(Kind::Type));
+                docs.push(Doc::text(" "));
+                docs.push(self.token(Kind::Alias));
+                docs.push(Doc::text(" "));
+                docs.push(self.ident());
+                docs.push(self.generics(&alias.generics));
+                docs.push(Doc::text(" "));
+                docs.push(self.token(Kind::Equal));
+                docs.push(Doc::text(" "));
+                docs.push(self.ty(alias.ty));
+                docs.push(self.token(Kind::Semicolon));
+            }
+            ItemKind::Union(union) => {
+                docs.push(self.token(Kind::Union));
+                docs.push(Doc::text(" "));
+                docs.push(self.ident());
+                docs.push(self.generics(&union.generics));
+                docs.push(Doc::text(" "));
+                docs.push(self.list(
+                    Kind::LeftCurly,
+                    Kind::RightCurly,
+                    &union.variants,
+                    Style::Broken,
+                    |this, variant| {
+                        let mut docs = vec![this.visibility(), this.ident()];
+                        if let Some(ty) = variant.data {
+                            docs.push(Doc::text(" "));
+                            docs.push(this.token(Kind::Equal));
+                            docs.push(Doc::text(" "));
+                            docs.push(this.ty(ty));
+                        }
+                        Doc::Concat(docs)
+                    },
+                ));
+            }
+            ItemKind::Trait(trait_) => {
+                docs.push(self.token(Kind::Trait));
+                docs.push(Doc::text(" "));
+                docs.push(self.ident());
+                docs.push(Doc::text(" "));
+                docs.push(self.skip(Kind::Equal));
+                docs.push(Doc::text("= "));
+                docs.push(self.methods(&trait_.methods));
+            }
+            ItemKind::Impl(impl_) => {
+                docs.push(self.token(Kind::Impl));
+                docs.push(self.generics(&impl_.generics));
+                docs.push(Doc::text(" "));
+                docs.push(self.ty(impl_.ty));
+                if impl_.trait_.is_some() {
+                    docs.push(self.token(Kind::Colon));
+                    docs.push(Doc::text(" "));
+                    docs.push(self.ident());
+                }
+                docs.push(Doc::text(" "));
+                docs.push(self.methods(&impl_.methods));
+            }
+            ItemKind::Const(global) | ItemKind::Static(global) => {
+                docs.push(self.any_token());
+                docs.push(Doc::text(" "));
+                docs.push(self.ident());
+                docs.push(self.token(Kind::Colon));
+                docs.push(Doc::text(" "));
+                docs.push(self.ty(global.ty));
+                docs.push(Doc::text(" "));
+                docs.push(self.token(Kind::Equal));
+                docs.push(Doc::text(" "));
+                docs.push(self.expr(global.val));
+                docs.push(self.token(Kind::Semicolon));
+            }
+            ItemKind::Import(_) => {
+                docs.push(self.token(Kind::Import));
+                docs.push(Doc::text(" "));
+                docs.push(self.ident());
+                docs.push(self.token(Kind::Semicolon));
+            }
+            ItemKind::Use(use_) => {
+                docs.push(self.token(Kind::Use));
+                docs.push(Doc::text(" "));
+                docs.push(self.path(&use_.path));
+                docs.push(self.token(Kind::Semicolon));
+            }
+        }
+        Doc::Concat(docs)
+    }
+
+    fn visibility(&mut self) -> Doc {
+        if self.at(Kind::Pub) {
+            Doc::Concat(vec![self.token(Kind::Pub), Doc::text(" ")])
+        } else {
+            Doc::nil()
+        }
+    }
+
+    fn generics(&mut self, generics: &[Generic]) -> Doc {
+        if generics.is_empty() {
+            return Doc::nil();
+        }
+
+        self.list(
+            Kind::LeftBracket,
+            Kind::RightBracket,
+            generics,
+            Style::Compact,
+            |this, generic| {
+                let mut docs = vec![this.ident()];
+                for (idx, _) in generic.bounds.iter().enumerate() {
+                    if idx == 0 {
+                        docs.push(this.token(Kind::Colon));
+                    } else {
+                        docs.push(Doc::text(" "));
+                        docs.push(this.token(Kind::Plus));
+                    }
+                    docs.push(Doc::text(" "));
+                    docs.push(this.ident());
+                }
+                Doc::Concat(docs)
+            },
+        )
+    }
+
+    /// Prints the methods of a trait or impl.
+    fn methods(&mut self, methods: &[ItemId]) -> Doc {
+        let open = self.token(Kind::LeftCurly);
+        let trailing = self.trailing_comment();
+        let db = self.db;
+        let blank = |a: &ItemId, b: &ItemId| !is_short(db, *a) || !is_short(db, *b);
+        let body = self.lines(methods, blank, |this, &method| this.item(method));
+        let close = self.token(Kind::RightCurly);
+        braced(open, trailing, body, close)
+    }
+}
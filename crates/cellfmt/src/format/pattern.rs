@@ -0,0 +1,81 @@
+//! Formatting of the patterns inside `match` arms.
+
+use super::{Formatter, Style};
+use crate::doc::Doc;
+use cell_parser::token::Kind;
+use cell_syntax::ast::{Identifier, PatternId, PatternKind};
+
+impl Formatter<'_> {
+    pub(super) fn pattern(&mut self, id: PatternId) -> Doc {
+        match self.db.lookup_intern_pattern(id).kind {
+            PatternKind::Wildcard => self.token(Kind::Else),
+            PatternKind::Binding(_) => self.ident(),
+            PatternKind::Literal(_) => self.signed_literal(),
+            PatternKind::Range { .. } => Doc::Concat(vec![
+                self.signed_literal(),
+                self.token(Kind::DotDot),
+                self.signed_literal(),
+            ]),
+            PatternKind::Tuple(patterns) => self.list(
+                Kind::LeftCurly,
+                Kind::RightCurly,
+                &patterns,
+                Style::Compact,
+                |this, &pattern| this.pattern(pattern),
+            ),
+            PatternKind::NamedTuple(fields) => self.list(
+                Kind::LeftCurly,
+                Kind::RightCurly,
+                &fields,
+                Style::Compact,
+                Self::field_pattern,
+            ),
+            PatternKind::Struct { fields, .. } => Doc::Concat(vec![
+                self.ident(),
+                Doc::text(" "),
+                self.list(
+                    Kind::LeftCurly,
+                    Kind::RightCurly,
+                    &fields,
+                    Style::Padded,
+                    Self::field_pattern,
+                ),
+            ]),
+            PatternKind::Variant { path, data } => {
+                let mut docs = vec![self.path(&path)];
+                if let Some(data) = data {
+                    docs.push(Doc::text(" "));
+                    docs.push(self.token(Kind::Equal));
+                    docs.push(Doc::text(" "));
+                    docs.push(self.pattern(data));
+                }
+                Doc::Concat(docs)
+            }
+        }
+    }
+
+    /// Prints a field of a struct or named tuple pattern.
+    fn field_pattern(&mut self, &(_, pattern): &(Identifier, PatternId)) -> Doc {
+        let name = self.ident();
+        // a field without a pattern binds the field to a variable of the same name
+        if !self.at(Kind::Colon) {
+            return name;
+        }
+        Doc::Concat(vec![
+            name,
+            self.token(Kind::Colon),
+            Doc::text(" "),
+            self.pattern(pattern),
+        ])
+    }
+
+    /// Prints a literal in a pattern, which may be prefixed by a `-`.
+    fn signed_literal(&mut self) -> Doc {
+        let minus = if self.at(Kind::Minus) {
+            self.token(Kind::Minus)
+        } else {
+            Doc::nil()
+        };
+        Doc::Concat(vec![minus, self.any_token()])
+    }
+}
@@ -0,0 +1,61 @@
+//! Formatting of types.
+
+use super::{Formatter, Style};
+use crate::doc::Doc;
+use cell_parser::token::Kind;
+use cell_syntax::ast::{Type, TypeId};
+
+impl Formatter<'_> {
+    pub(super) fn ty(&mut self, id: TypeId) -> Doc {
+        let ty = self.db.lookup_intern_type(id);
+        self.type_(&ty)
+    }
+
+    pub(super) fn type_(&mut self, ty: &Type) -> Doc {
+        match ty {
+            Type::Pointer(ty) => Doc::Concat(vec![self.token(Kind::Star), self.ty(*ty)]),
+            Type::Unit => Doc::Concat(vec![
+                self.token(Kind::LeftParen),
+                self.token(Kind::RightParen),
+            ]),
+            Type::Tuple(types) => self.list(
+                Kind::LeftCurly,
+                Kind::RightCurly,
+                types,
+                Style::Compact,
+                |this, &ty| this.ty(ty),
+            ),
+            Type::NamedTuple(fields) => self.list(
+                Kind::LeftCurly,
+                Kind::RightCurly,
+                fields,
+                Style::Compact,
+                |this, &(_, ty)| {
+                    Doc::Concat(vec![
+                        this.ident(),
+                        this.token(Kind::Colon),
+                        Doc::text(" "),
+                        this.ty(ty),
+                    ])
+                },
+            ),
+            Type::Named { generics, .. } => {
+                let name = self.ident();
+                if generics.is_empty() {
+                    return name;
+                }
+                let generics = self.list(
+                    Kind::LeftBracket,
+                    Kind::RightBracket,
+                    generics,
+                    Style::Compact,
+                    |this, &ty| this.ty(ty),
+                );
+                Doc::Concat(vec![name, generics])
+            }
+            Type::Int { .. } | Type::Float { .. } | Type::Bool | Type::String | Type::Char => {
+                self.ident()
+            }
+        }
+    }
+}
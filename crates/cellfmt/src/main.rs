@@ -0,0 +1,150 @@
+//! The formatter of cell, which prints source files in a canonical layout.
+//!
+//! Formatting a file that is already formatted doesn't change it, so
+//! `cellfmt --check` can be used to make sure that every file is formatted.
+
+mod doc;
+mod format;
+
+use cell_common::{
+    source::{self, FileCache, SourceDatabase},
+    Diagnostic,
+};
+use cell_db::RootDatabase;
+use cell_parser::db::ParseDatabase;
+use codespan_reporting::term::{
+    self,
+    termcolor::{ColorChoice, StandardStream},
+};
+use format::FormatError;
+use std::{
+    fmt, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "cellfmt",
+    about = "The formatter for the cell programming language"
+)]
+struct Options {
+    /// Checks if the files are formatted, without changing them,
+    /// and fails if any of them is not
+    #[structopt(long)]
+    check: bool,
+    /// The maximum width of a line
+    #[structopt(long, default_value = "100")]
+    width: usize,
+    /// The files to format
+    #[structopt(parse(from_os_str), required = true)]
+    files: Vec<PathBuf>,
+}
+
+/// Returned if formatting a file failed.
+/// The reason was already reported when this is returned.
+struct Failed;
+
+/// Formats files and reports their errors.
+struct Driver {
+    db: RootDatabase,
+    stderr: StandardStream,
+    config: term::Config,
+    options: Options,
+}
+
+impl Driver {
+    fn new(options: Options) -> Self {
+        let color = if io::stderr().is_terminal() {
+            ColorChoice::Auto
+        } else {
+            ColorChoice::Never
+        };
+        Self {
+            db: RootDatabase::new(),
+            stderr: StandardStream::stderr(color),
+            config: term::Config::default(),
+            options,
+        }
+    }
+
+    fn emit(&self, diagnostic: &Diagnostic) {
+        let files = FileCache::new(&self.db);
+        term::emit(&mut self.stderr.lock(), &self.config, &files, diagnostic)
+            .expect("failed to write to stderr");
+    }
+
+    /// Reports an error that has no location in the source code.
+    fn error(&self, msg: impl fmt::Display) -> Failed {
+        self.emit(&Diagnostic::error().with_message(msg.to_string()));
+        Failed
+    }
+
+    fn format_file(&mut self, path: &Path) -> Result<(), Failed> {
+        let file = self.db.load_file(path).map_err(|err| self.error(err))?;
+        let formatted = match format::format(&self.db, file, self.options.width) {
+            Ok(formatted) => formatted,
+            Err(FormatError::Syntax(errors)) => {
+                for err in errors {
+                    self.emit(&err.into());
+                }
+                return Err(Failed);
+            }
+            Err(FormatError::Mismatch) => {
+                return Err(self.error(format!(
+                    "failed to format `{}`, because its tokens don't match the syntax tree",
+                    path.display()
+                )))
+            }
+        };
+
+        // never write code that doesn't parse anymore
+        let output = source::set_file(
+            &mut self.db,
+            format!("{} (formatted)", path.display()),
+            formatted.clone(),
+        );
+        if !self.db.parse(output).errors.is_empty() {
+            return Err(self.error(format!(
+                "failed to format `{}`, because the formatted code is invalid",
+                path.display()
+            )));
+        }
+
+        if *self.db.file_text(file) == formatted {
+            return Ok(());
+        }
+        if self.options.check {
+            let line = first_difference(&self.db.file_text(file), &formatted);
+            println!("{}:{}: not formatted", path.display(), line + 1);
+            return Err(Failed);
+        }
+        fs::write(path, formatted)
+            .map_err(|err| self.error(format!("failed to write `{}`: {}", path.display(), err)))
+    }
+
+    fn run(&mut self) -> Result<(), Failed> {
+        let files = self.options.files.clone();
+        let mut result = Ok(());
+        for path in files {
+            if self.format_file(&path).is_err() {
+                result = Err(Failed);
+            }
+        }
+        result
+    }
+}
+
+/// Returns the index of the first line that differs between the two texts.
+fn first_difference(a: &str, b: &str) -> usize {
+    a.lines().zip(b.lines()).take_while(|(a, b)| a == b).count()
+}
+
+fn main() {
+    let mut driver = Driver::new(Options::from_args());
+    if driver.run().is_err() {
+        process::exit(1);
+    }
+}
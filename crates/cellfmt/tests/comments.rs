@@ -0,0 +1,56 @@
+//! Checks where comments end up after formatting.
+
+use std::{fs, path::Path, process::Command};
+
+const CELLFMT: &str = env!("CARGO_BIN_EXE_cellfmt");
+
+/// Formats `text` and returns the result.
+fn format(name: &str, text: &str) -> String {
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&file, text).expect("failed to write the input");
+    let output = Command::new(CELLFMT)
+        .arg(&file)
+        .output()
+        .expect("failed to run `cellfmt`");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    fs::read_to_string(&file).unwrap()
+}
+
+/// A comment after `->` or `=` moves the value to the next line,
+/// which is indented below the arm or the assignment.
+#[test]
+fn comment_after_operator() {
+    let text = "\
+def f(x: i32): i32 {
+var y = // c
+2;
+y += // d
+3;
+return match x {
+1 -> // a
+y,
+else -> // b
+3,
+};
+}
+";
+    let expected = "\
+def f(x: i32): i32 {
+    var y = // c
+        2;
+    y += // d
+        3;
+    return match x {
+        1 -> // a
+            y,
+        else -> // b
+            3,
+    };
+}
+";
+    assert_eq!(format("operator.cell", text), expected);
+}
@@ -0,0 +1,78 @@
+//! Checks that formatting a file twice gives the same result as formatting
+//! it once, and that `cellfmt --check` accepts the formatted file.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const CELLFMT: &str = env!("CARGO_BIN_EXE_cellfmt");
+
+/// The files that are formatted, which are the files in `tests/inputs`,
+/// the prelude and the programs of the backend tests.
+fn inputs() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut inputs = vec![root.join("../cell_typeck/src/prelude.cell")];
+    for dir in ["tests/inputs", "../cellc/tests/programs"] {
+        let entries = fs::read_dir(root.join(dir))
+            .unwrap_or_else(|err| panic!("failed to read `{}`: {}", dir, err));
+        inputs.extend(
+            entries
+                .map(|entry| entry.expect("failed to read an input").path())
+                .filter(|path| path.extension() == Some("cell".as_ref())),
+        );
+    }
+    inputs.sort();
+    inputs
+}
+
+/// Runs `cellfmt` on `file` and returns whether it succeeded.
+fn cellfmt(args: &[&str], file: &Path) -> bool {
+    let output = Command::new(CELLFMT)
+        .args(args)
+        .arg(file)
+        .output()
+        .expect("failed to run `cellfmt`");
+    if !output.status.success() {
+        eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+    }
+    output.status.success()
+}
+
+fn check_width(width: &str) {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("cellfmt-{}", width));
+    fs::create_dir_all(&dir).expect("failed to create the output directory");
+
+    for input in inputs() {
+        let file = dir.join(input.file_name().unwrap());
+        fs::copy(&input, &file).expect("failed to copy the input");
+
+        assert!(cellfmt(&["--width", width], &file));
+        let once = fs::read_to_string(&file).unwrap();
+        assert!(
+            cellfmt(&["--check", "--width", width], &file),
+            "`--check` rejects the formatted `{}`:\n{}",
+            input.display(),
+            once
+        );
+        assert!(cellfmt(&["--width", width], &file));
+        let twice = fs::read_to_string(&file).unwrap();
+        assert_eq!(
+            once,
+            twice,
+            "formatting `{}` twice changes it",
+            input.display()
+        );
+    }
+}
+
+#[test]
+fn default_width() {
+    check_width("100");
+}
+
+#[test]
+fn narrow() {
+    check_width("40");
+}